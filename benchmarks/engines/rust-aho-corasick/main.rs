@@ -148,6 +148,25 @@ fn main() -> anyhow::Result<()> {
                 })?;
             model_count_packed(&b, &searcher)?
         }
+        // The chunked replacer, which is given the entire haystack as a
+        // single chunk. This is mostly useful for measuring the overhead of
+        // managing the replacer's output buffer on large chunks.
+        ("count", "replacer/standard") => {
+            let ac = builder_ac(&b)?.build(&b.needles)?;
+            model_count_replacer(&b, &ac)?
+        }
+        // A two-level searcher routing on the first two bytes of each
+        // pattern, with a DFA for each bucket. Compare this with the
+        // 'dfa/leftmost-first' engine, which is one DFA for all patterns.
+        ("count", "two-level/leftmost-first") => {
+            let searcher = builder_ac(&b)?
+                .prefilter(false)
+                .kind(Some(AhoCorasickKind::DFA))
+                .match_kind(MatchKind::LeftmostFirst)
+                .build_two_level(&b.needles, 2)?;
+            let haystack = &*b.haystack;
+            shared::run(&b, || Ok(searcher.find_iter(haystack).count()))?
+        }
         _ => anyhow::bail!(
             "unsupported model/engine pair, model={} engine={}",
             b.model,
@@ -209,6 +228,35 @@ fn model_count_ac_overlapping(
     shared::run(b, || Ok(ac.find_overlapping_iter(haystack).count()))
 }
 
+/// Implements the "count all matches" model for the chunked replacer.
+///
+/// Every match is replaced with a NUL byte, and the NUL bytes in the output
+/// are counted. A new replacer is created for every sample, so that the
+/// cost of growing its output buffer is included.
+fn model_count_replacer(
+    b: &Benchmark,
+    ac: &AhoCorasick,
+) -> anyhow::Result<Vec<Sample>> {
+    anyhow::ensure!(
+        !b.haystack.contains(&0),
+        "rust/aho-corasick/replacer engines are incompatible \
+         with haystacks that contain NUL bytes"
+    );
+
+    let haystack = &*b.haystack;
+    shared::run(b, || {
+        let mut replacer = ac.replacer(vec![vec![0]; b.needles.len()])?;
+        let mut count = bytecount(replacer.replace(haystack)?);
+        count += bytecount(replacer.finish()?);
+        Ok(count)
+    })
+}
+
+/// Returns the number of NUL bytes in the given slice.
+fn bytecount(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b == 0).count()
+}
+
 /// Implements the "count all matches" model for packed substring search.
 fn model_count_packed(
     b: &Benchmark,
@@ -0,0 +1,64 @@
+//! Structured inputs shared by the replacement fuzz targets.
+//!
+//! These are built from the `Arbitrary` inputs provided by the library's
+//! `arbitrary` feature. See `PatternSetSpec` for why patterns and haystacks
+//! are not simply arbitrary bytes.
+
+#![allow(dead_code)]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary, Unstructured};
+
+use aho_corasick::{
+    AhoCorasick, BuilderOptions, ChunkPlan, MatchKind, PatternSetSpec,
+    StartKind,
+};
+
+/// A pattern set, a haystack that is likely to contain its patterns, the
+/// chunks to split the haystack into and the options to build a searcher
+/// with.
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub spec: PatternSetSpec,
+    pub haystack: Vec<u8>,
+    pub chunks: ChunkPlan,
+    pub options: BuilderOptions,
+}
+
+impl Case {
+    /// Build a searcher for the patterns of this case that supports chunked
+    /// replacement, i.e., one with standard match semantics and support for
+    /// unanchored searches.
+    pub fn searcher(&self) -> AhoCorasick {
+        self.options
+            .builder()
+            .match_kind(MatchKind::Standard)
+            .start_kind(StartKind::Unanchored)
+            .build(self.spec.patterns())
+            .unwrap()
+    }
+}
+
+impl<'a> Arbitrary<'a> for Case {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Case> {
+        let spec = PatternSetSpec::arbitrary(u)?;
+        let haystack = spec.haystack(u)?;
+        let chunks = ChunkPlan::arbitrary(u)?;
+        let options = BuilderOptions::arbitrary(u)?;
+        Ok(Case { spec, haystack, chunks, options })
+    }
+}
+
+/// Replace all matches in the given chunks with a chunked replacer.
+pub fn replace_chunked(
+    ac: &AhoCorasick,
+    replacements: &[Vec<u8>],
+    chunks: &[&[u8]],
+) -> Vec<u8> {
+    let mut replacer = ac.replacer(replacements.to_vec()).unwrap();
+    let mut out = vec![];
+    for chunk in chunks.iter() {
+        out.extend_from_slice(replacer.replace(chunk).unwrap());
+    }
+    out.extend_from_slice(replacer.finish().unwrap());
+    out
+}
@@ -0,0 +1,140 @@
+#![no_main]
+
+// Checks that the async writer gives the same result as the chunked replacer
+// when the underlying sink is not ready for an arbitrary number of polls
+// before each write, whether chunks are written one at a time or together
+// with vectored writes.
+
+use std::{
+    io::IoSlice,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{task::noop_waker, AsyncWrite};
+use libfuzzer_sys::{arbitrary, fuzz_target};
+
+mod common;
+
+use common::{replace_chunked, Case};
+
+#[derive(arbitrary::Arbitrary, Debug)]
+struct Inputs {
+    case: Case,
+    /// The number of times the sink reports that it is not ready, cycled
+    /// through for each call to 'poll_write'. An empty list means the sink
+    /// is always ready.
+    pending: Vec<u8>,
+    /// Whether chunks are written with vectored writes.
+    vectored: bool,
+}
+
+/// A sink that collects everything written to it, after answering with
+/// 'Poll::Pending' a scripted number of times for every write.
+struct ScriptedSink {
+    out: Vec<u8>,
+    pending: Vec<u8>,
+    writes: usize,
+    polls: u8,
+}
+
+impl AsyncWrite for ScriptedSink {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let wait = match self.pending.len() {
+            0 => 0,
+            len => self.pending[self.writes % len] % 4,
+        };
+        if self.polls < wait {
+            self.polls += 1;
+            return Poll::Pending;
+        }
+        self.polls = 0;
+        self.writes += 1;
+        self.out.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fuzz_target!(|input: Inputs| {
+    let spec = &input.case.spec;
+    let ac = input.case.searcher();
+    let chunks = input.case.chunks.split(&input.case.haystack);
+    let expected = replace_chunked(&ac, spec.replacements(), &chunks);
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut sink = ScriptedSink {
+        out: vec![],
+        pending: input.pending,
+        writes: 0,
+        polls: 0,
+    };
+    let mut writer = ac.async_writer(&mut sink, spec.replacements()).unwrap();
+    if input.vectored {
+        // Write all remaining chunks at once, skipping what a vectored
+        // write consumed. The first remaining chunk may be partially
+        // consumed.
+        let mut rest: Vec<&[u8]> = chunks.clone();
+        while rest.iter().any(|chunk| !chunk.is_empty()) {
+            let bufs: Vec<IoSlice<'_>> =
+                rest.iter().map(|chunk| IoSlice::new(chunk)).collect();
+            if let Poll::Ready(result) =
+                Pin::new(&mut writer).poll_write_vectored(&mut cx, &bufs)
+            {
+                let mut n = result.unwrap();
+                assert!(0 < n);
+                while n > 0 {
+                    let len = rest[0].len().min(n);
+                    rest[0] = &rest[0][len..];
+                    n -= len;
+                    if rest[0].is_empty() {
+                        rest.remove(0);
+                    }
+                }
+            }
+        }
+    } else {
+        for chunk in chunks.iter() {
+            // A write may consume only part of a chunk, so keep writing the
+            // rest of it. Since the sink is never pending more than 3 times
+            // in a row, this always finishes.
+            let mut chunk = *chunk;
+            while !chunk.is_empty() {
+                if let Poll::Ready(result) =
+                    Pin::new(&mut writer).poll_write(&mut cx, chunk)
+                {
+                    let n = result.unwrap();
+                    assert!(0 < n && n <= chunk.len());
+                    chunk = &chunk[n..];
+                }
+            }
+        }
+    }
+    loop {
+        if let Poll::Ready(result) = Pin::new(&mut writer).poll_close(&mut cx)
+        {
+            result.unwrap();
+            break;
+        }
+    }
+    drop(writer);
+    assert_eq!(expected, sink.out);
+});
@@ -0,0 +1,37 @@
+#![no_main]
+
+// Checks that replacing a haystack chunk by chunk gives the same result no
+// matter how the haystack is split, that it agrees with 'replace_all' and
+// that writing it to a sink with 'replace_to' gives the same result.
+
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+use common::{replace_chunked, Case};
+
+fuzz_target!(|case: Case| {
+    let spec = &case.spec;
+    let ac = case.searcher();
+    let chunks = case.chunks.split(&case.haystack);
+    let got = replace_chunked(&ac, spec.replacements(), &chunks);
+    let whole = replace_chunked(&ac, spec.replacements(), &[&case.haystack]);
+    assert_eq!(whole, got);
+
+    let mut replacer = ac.replacer(spec.replacements().to_vec()).unwrap();
+    let mut sunk = vec![];
+    for chunk in chunks.iter() {
+        replacer.replace_to(chunk, &mut sunk).unwrap();
+    }
+    sunk.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(got, sunk);
+
+    // The chunked replacer does not report empty matches, while
+    // 'replace_all' reports one at every position, so the results are only
+    // comparable without empty patterns.
+    if !spec.has_empty() {
+        let expected =
+            ac.replace_all_bytes(&case.haystack, spec.replacements());
+        assert_eq!(expected, got);
+    }
+});
@@ -0,0 +1,151 @@
+#![no_main]
+
+// Checks the 'perf-unchecked' DFA transitions against the bounds checked
+// noncontiguous NFA. Both automata are built from the same patterns and
+// configuration, so every search must report the same results.
+//
+// The unchecked transitions are also run on deserialized DFAs, since
+// 'DFA::from_bytes' is the other way to create a DFA. A DFA read back from
+// its own bytes must find the same matches, and a DFA read from corrupted
+// bytes (with a valid checksum) must either be rejected or be searchable
+// without reading out of bounds, which the debug assertion in
+// 'next_state_unchecked' checks. Contiguous NFAs read from corrupted bytes
+// are checked the same way, since 'NFA::from_bytes' validates them too.
+
+use libfuzzer_sys::{arbitrary, fuzz_target};
+
+use aho_corasick::{
+    automaton::Automaton, dfa::DFA, nfa::contiguous, AhoCorasick,
+    AhoCorasickKind, Anchored, Input, Match, MatchKind, StartKind,
+};
+
+#[derive(arbitrary::Arbitrary, Debug, Clone)]
+struct Inputs {
+    patterns: Vec<Vec<u8>>,
+    haystack: Vec<u8>,
+    match_kind: u8,
+    ascii_case_insensitive: bool,
+    byte_classes: bool,
+    chunk_len: u8,
+    corruptions: Vec<(u16, u8)>,
+}
+
+/// The 64-bit FNV-1a hash that serialized automata end with.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Run every kind of search the given automaton supports, only checking that
+/// none of them panics.
+fn search<A: Automaton>(aut: &A, haystack: &[u8]) {
+    for anchored in [Anchored::No, Anchored::Yes] {
+        let input = Input::new(haystack).anchored(anchored);
+        if let Ok(it) = aut.try_find_iter(input.clone()) {
+            it.for_each(drop);
+        }
+        if let Ok(it) = aut.try_find_overlapping_iter(input) {
+            it.for_each(drop);
+        }
+    }
+}
+
+fuzz_target!(|input: Inputs| {
+    let match_kind = match input.match_kind % 3 {
+        0 => MatchKind::Standard,
+        1 => MatchKind::LeftmostFirst,
+        2 => MatchKind::LeftmostLongest,
+        _ => unreachable!(),
+    };
+    let build = |kind| {
+        AhoCorasick::builder()
+            .kind(Some(kind))
+            .match_kind(match_kind)
+            .ascii_case_insensitive(input.ascii_case_insensitive)
+            .byte_classes(input.byte_classes)
+            .prefilter(false)
+            .build(&input.patterns)
+    };
+    let (dfa, nfa) = match (
+        build(AhoCorasickKind::DFA),
+        build(AhoCorasickKind::NoncontiguousNFA),
+    ) {
+        (Ok(dfa), Ok(nfa)) => (dfa, nfa),
+        _ => return,
+    };
+
+    let got: Vec<Match> = dfa.find_iter(&input.haystack).collect();
+    let expected: Vec<Match> = nfa.find_iter(&input.haystack).collect();
+    assert_eq!(expected, got);
+
+    let built = DFA::builder()
+        .match_kind(match_kind)
+        .ascii_case_insensitive(input.ascii_case_insensitive)
+        .byte_classes(input.byte_classes)
+        .prefilter(false)
+        .start_kind(StartKind::Both)
+        .build(&input.patterns)
+        .unwrap();
+    let bytes = built.to_bytes();
+    let read = DFA::from_bytes(&bytes).unwrap();
+    let got: Vec<Match> =
+        read.try_find_iter(Input::new(&input.haystack)).unwrap().collect();
+    assert_eq!(expected, got);
+
+    // The magic number and the version of the encoding (the first 12
+    // bytes) and the checksum (the last 8 bytes) are left alone, since
+    // corrupting them is always rejected.
+    let corrupt = |mut bytes: Vec<u8>| -> Vec<u8> {
+        let body_len = bytes.len() - 8;
+        for &(at, byte) in input.corruptions.iter() {
+            bytes[12 + usize::from(at) % (body_len - 12)] ^= byte;
+        }
+        let checksum = fnv1a(&bytes[..body_len]);
+        bytes[body_len..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    };
+    if let Ok(read) = DFA::from_bytes(&corrupt(bytes)) {
+        search(&read, &input.haystack);
+    }
+    let nfa_bytes = contiguous::NFA::builder()
+        .match_kind(match_kind)
+        .ascii_case_insensitive(input.ascii_case_insensitive)
+        .byte_classes(input.byte_classes)
+        .prefilter(false)
+        .build(&input.patterns)
+        .unwrap()
+        .to_bytes();
+    if let Ok(read) = contiguous::NFA::from_bytes(&corrupt(nfa_bytes)) {
+        search(&read, &input.haystack);
+    }
+
+    if match_kind != MatchKind::Standard
+        || input.patterns.iter().any(|p| p.is_empty())
+    {
+        return;
+    }
+    let got: Vec<Match> = dfa
+        .stream_find_iter(&input.haystack[..])
+        .map(|result| result.unwrap())
+        .collect();
+    assert_eq!(expected, got);
+
+    let replacements: Vec<Vec<u8>> = (0..input.patterns.len())
+        .map(|i| i.to_string().into_bytes())
+        .collect();
+    let chunk_len = usize::from(input.chunk_len).max(1);
+    let replace = |ac: &AhoCorasick| -> Vec<u8> {
+        let mut replacer = ac.replacer(replacements.clone()).unwrap();
+        let mut out = vec![];
+        for chunk in input.haystack.chunks(chunk_len) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        out
+    };
+    assert_eq!(replace(&nfa), replace(&dfa));
+});
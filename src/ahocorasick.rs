@@ -3,26 +3,56 @@ use core::{
     panic::{RefUnwindSafe, UnwindSafe}, any::Any,
 };
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{borrow::Cow, string::String, sync::Arc, vec::Vec};
 
 use crate::{
     automaton::{self, Automaton, OverlappingState, AutomatonImpl},
     dfa,
+    dictionary::HyperscanExport,
     nfa::{contiguous, noncontiguous},
+    sharded::ShardedSearcher,
+    signature::{Signature, SignatureError, SignatureSearcher},
+    two_level::TwoLevelSearcher,
+    utf16::{self, Utf16Endian, Utf16Searcher},
     util::{
         error::{BuildError, MatchError},
+        graph::AutomatonGraph,
+        lookahead::{self, Lookahead},
+        overlap::{OverlapPolicy, OverlapResolver},
         prefilter::Prefilter,
         primitives::{PatternID, StateID},
-        search::{Anchored, Input, Match, MatchKind, StartKind},
+        profile::SearchProfiler,
+        provenance::Provenance,
+        search::{
+            Anchored, DuplicatePolicy, Input, Match, MatchKind, MatchU32,
+            StartKind,
+        },
+        validate::{self, ContainedPatterns, ValidationReport},
     },
 };
 
+#[cfg(feature = "std")]
+use crate::util::{
+    position::{ColumnUnit, LineColumn},
+    rate::MatchRates,
+    top_k::TopPatterns,
+    wire::{Reader, Writer},
+};
+
 #[cfg(all(feature = "async", feature = "std"))]
 use crate::r#async::{
     reader::AhoCorasickAsyncReader, writer::AhoCorasickAsyncWriter,
 };
 
-pub use crate::replacer::AhoCorasickReplacer;
+#[cfg(feature = "std")]
+pub use crate::replacer::{AhoCorasickReader, AhoCorasickWriter};
+pub use crate::replacer::{
+    AhoCorasickReplacer, AhoCorasickReplacerBuilder, AhoCorasickReplacerWith,
+    AhoCorasickStreamSplitter, ChunkedReplacer, EventStreamReplacer,
+    LineReplacer, MemoryLimits, OverlongLine, Padding, PendingLimit,
+    ReplaceIter, ReplaceStats, ReplacerError, Selection, SelectionState,
+    ShadowDiff, ShadowReplacer, SplitRecord, SplitRecords,
+};
 
 /// An automaton for searching multiple strings in linear time.
 ///
@@ -140,6 +170,20 @@ pub use crate::replacer::AhoCorasickReplacer;
 /// should be no need to handle errors anywhere and it is generally encouraged
 /// to `unwrap()` (or `expect()`) both build and search time calls.
 ///
+/// # Sharing between threads
+///
+/// An `AhoCorasick` searcher is `Send` and `Sync`, and searching never
+/// mutates it. Cloning a searcher is cheap, since the automaton is shared
+/// behind an `Arc`. Replacers created by [`AhoCorasick::replacer`] or an
+/// [`AhoCorasickReplacerBuilder`] hold their own reference to the automaton
+/// and keep all of their mutable state to themselves.
+///
+/// When the `std` feature is enabled, a
+/// [`SearcherHandle`](crate::handle::SearcherHandle) swaps a searcher while
+/// it is in use, such as when reloading a dictionary. Searches and replacers
+/// that started before a swap keep using the searcher they were started with
+/// until they are dropped.
+///
 /// # Examples
 ///
 /// This example shows how to search for occurrences of multiple patterns
@@ -218,6 +262,42 @@ pub struct AhoCorasick {
     /// was asked for (and vice versa), even if the underlying automaton
     /// supports it.
     start_kind: StartKind,
+    /// The patterns this automaton was built from, if
+    /// [`AhoCorasickBuilder::store_patterns`] was enabled.
+    patterns: Option<Arc<[Vec<u8>]>>,
+    /// Whether the automaton was built with ASCII case insensitivity. Exact
+    /// case searches only need to verify matches when it was.
+    ascii_case_insensitive: bool,
+    /// The table that translates haystack bytes before they're matched, if
+    /// [`AhoCorasickBuilder::translation_table`] was used. Verifying a match
+    /// compares its translated bytes to its pattern.
+    translation_table: Option<Arc<[u8; 256]>>,
+    /// The function that verifies matches, if
+    /// [`AhoCorasickBuilder::verify`] was used. The patterns are always
+    /// stored when it's set.
+    verifier: Option<Verifier>,
+    /// The lookahead of each pattern, indexed by pattern ID, if
+    /// [`AhoCorasickBuilder::lookahead`] was used for any pattern. The
+    /// patterns are always stored when it's set.
+    lookaheads: Option<Arc<[Option<Lookahead>]>>,
+    /// Whether matches must be bounded by non-word bytes, if
+    /// [`AhoCorasickBuilder::word_boundary`] was enabled. The patterns are
+    /// always stored when it's set.
+    word_boundary: bool,
+    /// The replacement tables set by
+    /// [`AhoCorasickBuilder::replacement_tables`]. Every replacer built from
+    /// one of them shares it.
+    replacement_tables: Arc<[Arc<[Vec<u8>]>]>,
+    /// The policy that decides which overlapping matches are replaced, if
+    /// [`AhoCorasickBuilder::overlap_policy`] was used.
+    overlap_policy: Option<OverlapPolicy>,
+    /// Whether each pattern is disabled, indexed by pattern ID, if any
+    /// pattern was disabled with [`AhoCorasick::disable_pattern`]. The
+    /// patterns are always stored when it's set.
+    disabled: Option<Arc<Vec<bool>>>,
+    /// The patterns that each pattern is identical to once folded, if
+    /// [`AhoCorasickBuilder::track_provenance`] was enabled.
+    provenance: Option<Arc<Provenance>>,
 }
 
 /// Convenience constructors for an Aho-Corasick searcher. To configure the
@@ -316,8 +396,12 @@ impl AhoCorasick {
     /// assert!(!ac.is_match("xxx qux xxx"));
     /// ```
     pub fn is_match<'h, I: Into<Input<'h>>>(&self, input: I) -> bool {
+        let input = input.into().earliest(true);
+        if self.needs_verification(&input) {
+            return self.find(input).is_some();
+        }
         self.aut
-            .try_find(&input.into().earliest(true))
+            .try_find(&input)
             .expect("AhoCorasick::try_find is not expected to fail")
             .is_some()
     }
@@ -622,6 +706,71 @@ impl AhoCorasick {
         )
     }
 
+    /// Returns the number of overlapping matches in the given input, which
+    /// is the number of matches reported by
+    /// [`AhoCorasick::find_overlapping_iter`].
+    ///
+    /// This is the infallible version of
+    /// [`AhoCorasick::try_count_overlapping_fast`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when `AhoCorasick::try_count_overlapping_fast` would
+    /// return an error, which is when `AhoCorasick::find_overlapping_iter`
+    /// would panic.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["a", "aa", "aaa"]).unwrap();
+    /// let haystack = "a".repeat(1000);
+    /// assert_eq!(
+    ///     ac.find_overlapping_iter(&haystack).count(),
+    ///     ac.count_overlapping_fast(&haystack),
+    /// );
+    /// assert_eq!(1000 + 999 + 998, ac.count_overlapping_fast(&haystack));
+    /// ```
+    pub fn count_overlapping_fast<'h, I: Into<Input<'h>>>(
+        &self,
+        input: I,
+    ) -> usize {
+        self.try_count_overlapping_fast(input).expect(
+            "AhoCorasick::try_count_overlapping_fast is not expected to fail",
+        )
+    }
+
+    /// Estimate the number of matches per byte in the given haystack by
+    /// searching a deterministic sample of it.
+    ///
+    /// This is the infallible version of
+    /// [`AhoCorasick::try_estimate_hit_rate`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when `AhoCorasick::try_estimate_hit_rate` would return an
+    /// error, which is when `AhoCorasick::find_iter` would panic.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["needle"]).unwrap();
+    /// let haystack = "hay ".repeat(10_000);
+    /// assert_eq!(0.0, ac.estimate_hit_rate(&haystack, 4096));
+    /// ```
+    pub fn estimate_hit_rate<H: AsRef<[u8]>>(
+        &self,
+        haystack: H,
+        sample_stride: usize,
+    ) -> f64 {
+        self.try_estimate_hit_rate(haystack, sample_stride).expect(
+            "AhoCorasick::try_estimate_hit_rate is not expected to fail",
+        )
+    }
+
     /// Replace all matches with a corresponding value in the `replace_with`
     /// slice given. Matches correspond to the same matches as reported by
     /// [`AhoCorasick::find_iter`].
@@ -709,6 +858,100 @@ impl AhoCorasick {
             .expect("AhoCorasick::try_replace_all_bytes should not fail")
     }
 
+    /// Replace all matches using raw bytes with a corresponding value in the
+    /// `replace_with` slice given, without copying the haystack when nothing
+    /// matches. Matches correspond to the same matches as reported by
+    /// [`AhoCorasick::find_iter`].
+    ///
+    /// This gives the same output as [`AhoCorasick::replace_all_bytes`], but
+    /// borrows the haystack when no pattern matches it. This avoids an
+    /// allocation and a copy for every haystack when matches are rare.
+    ///
+    /// This is the infallible version of
+    /// [`AhoCorasick::try_replace_all_cow`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when [`AhoCorasick::try_replace_all_cow`] would return an
+    /// error.
+    ///
+    /// This also panics when `replace_with.len()` does not equal
+    /// [`AhoCorasick::patterns_len`].
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["apple", "banana"]).unwrap();
+    /// let result = ac.replace_all_cow(b"an apple a day", &["pear", "kiwi"]);
+    /// assert_eq!(b"an pear a day", &*result);
+    /// assert!(matches!(result, Cow::Owned(_)));
+    ///
+    /// let haystack = b"a cherry a day";
+    /// let result = ac.replace_all_cow(haystack, &["pear", "kiwi"]);
+    /// assert!(matches!(result, Cow::Borrowed(b) if b == haystack));
+    /// ```
+    pub fn replace_all_cow<'h, B>(
+        &self,
+        haystack: &'h [u8],
+        replace_with: &[B],
+    ) -> Cow<'h, [u8]>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.try_replace_all_cow(haystack, replace_with)
+            .expect("AhoCorasick::try_replace_all_cow should not fail")
+    }
+
+    /// Replace all matches with a corresponding value in the `replace_with`
+    /// slice given, without copying the haystack when nothing matches.
+    /// Matches correspond to the same matches as reported by
+    /// [`AhoCorasick::find_iter`].
+    ///
+    /// This gives the same output as [`AhoCorasick::replace_all`], but
+    /// borrows the haystack when no pattern matches it.
+    ///
+    /// This is the infallible version of
+    /// [`AhoCorasick::try_replace_all_cow_str`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when [`AhoCorasick::try_replace_all_cow_str`] would return
+    /// an error.
+    ///
+    /// This also panics when `replace_with.len()` does not equal
+    /// [`AhoCorasick::patterns_len`].
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["apple", "banana"]).unwrap();
+    /// let with = &["pear", "kiwi"];
+    /// let result = ac.replace_all_cow_str("an apple a day", with);
+    /// assert_eq!("an pear a day", result);
+    ///
+    /// let result = ac.replace_all_cow_str("a cherry a day", with);
+    /// assert!(matches!(result, Cow::Borrowed("a cherry a day")));
+    /// ```
+    pub fn replace_all_cow_str<'h, B>(
+        &self,
+        haystack: &'h str,
+        replace_with: &[B],
+    ) -> Cow<'h, str>
+    where
+        B: AsRef<str>,
+    {
+        self.try_replace_all_cow_str(haystack, replace_with)
+            .expect("AhoCorasick::try_replace_all_cow_str should not fail")
+    }
+
     /// Replace all matches using a closure called on each match.
     /// Matches correspond to the same matches as reported by
     /// [`AhoCorasick::find_iter`].
@@ -917,6 +1160,100 @@ impl AhoCorasick {
         self.try_stream_find_iter(rdr)
             .expect("AhoCorasick::try_stream_find_iter should not fail")
     }
+
+    /// Returns the non-overlapping matches in each of the given haystacks.
+    ///
+    /// The matches reported for each haystack are precisely the matches
+    /// that [`AhoCorasick::find_iter`] would report for that haystack, and
+    /// the `i`th element of the result corresponds to the `i`th haystack
+    /// given. Compared to calling `find_iter` in a loop, this validates the
+    /// search configuration and resolves the underlying automaton once for
+    /// the whole batch, which matters when searching very many small
+    /// haystacks.
+    ///
+    /// # Panics
+    ///
+    /// This panics when [`AhoCorasick::try_find_many`] would return an error.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Match};
+    ///
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// let haystacks = &["foo bar", "quux", "barfoo"];
+    /// assert_eq!(
+    ///     vec![
+    ///         vec![Match::must(0, 0..3), Match::must(1, 4..7)],
+    ///         vec![],
+    ///         vec![Match::must(1, 0..3), Match::must(0, 3..6)],
+    ///     ],
+    ///     ac.find_many(haystacks),
+    /// );
+    /// ```
+    pub fn find_many<H: AsRef<[u8]>>(
+        &self,
+        haystacks: &[H],
+    ) -> Vec<Vec<Match>> {
+        self.try_find_many(haystacks)
+            .expect("AhoCorasick::try_find_many should not fail")
+    }
+
+    /// Like [`AhoCorasick::find_many`], but searches the haystacks in
+    /// parallel using the global `rayon` thread pool.
+    ///
+    /// # Panics
+    ///
+    /// This panics when [`AhoCorasick::try_par_find_many`] would return an
+    /// error.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Match};
+    ///
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// let haystacks = &["foo bar", "quux", "barfoo"];
+    /// assert_eq!(ac.find_many(haystacks), ac.par_find_many(haystacks));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_find_many<H: AsRef<[u8]> + Sync>(
+        &self,
+        haystacks: &[H],
+    ) -> Vec<Vec<Match>> {
+        self.try_par_find_many(haystacks)
+            .expect("AhoCorasick::try_par_find_many should not fail")
+    }
+
+    /// Returns an iterator of the non-overlapping matches in the given
+    /// haystack, which is searched in parallel using the global `rayon`
+    /// thread pool.
+    ///
+    /// This is the infallible version of
+    /// [`AhoCorasick::try_par_find_iter`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when [`AhoCorasick::try_par_find_iter`] would return an
+    /// error.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// let haystack = "foo bar foobar ".repeat(100_000);
+    /// assert!(ac.find_iter(&haystack).eq(ac.par_find_iter(&haystack)));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_find_iter<H: AsRef<[u8]>>(
+        &self,
+        haystack: H,
+    ) -> alloc::vec::IntoIter<Match> {
+        self.try_par_find_iter(haystack)
+            .expect("AhoCorasick::try_par_find_iter should not fail")
+    }
 }
 
 /// Fallible search routines. These APIs return an error in cases where the
@@ -1031,6 +1368,9 @@ impl AhoCorasick {
     ) -> Result<Option<Match>, MatchError> {
         let input = input.into();
         enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
+        if let Some(verification) = self.verification(&input)? {
+            return self.try_find_verified(input, verification);
+        }
         self.aut.try_find(&input)
     }
 
@@ -1195,7 +1535,17 @@ impl AhoCorasick {
     ) -> Result<(), MatchError> {
         let input = input.into();
         enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
-        self.aut.try_find_overlapping(&input, state)
+        let verification = match self.verification(&input)? {
+            None => return self.aut.try_find_overlapping(&input, state),
+            Some(verification) => verification,
+        };
+        loop {
+            self.aut.try_find_overlapping(&input, state)?;
+            match state.get_match() {
+                Some(m) if !verification.accepts(&input, m) => {}
+                _ => return Ok(()),
+            }
+        }
     }
 
     /// Returns an iterator of non-overlapping matches, using the match
@@ -1285,7 +1635,13 @@ impl AhoCorasick {
     ) -> Result<FindIter<'a, 'h>, MatchError> {
         let input = input.into();
         enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
-        Ok(FindIter(self.aut.try_find_iter(input)?))
+        match self.verification(&input)? {
+            None => {
+                let it = self.aut.try_find_iter(input)?;
+                Ok(FindIter(FindIterImp::Automaton(it)))
+            }
+            Some(verification) => self.verified_find_iter(input, verification),
+        }
     }
 
     /// Returns an iterator of overlapping matches.
@@ -1360,107 +1716,692 @@ impl AhoCorasick {
     ) -> Result<FindOverlappingIter<'a, 'h>, MatchError> {
         let input = input.into();
         enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
-        Ok(FindOverlappingIter(self.aut.try_find_overlapping_iter(input)?))
+        let verification =
+            self.verification(&input)?.map(|v| (v, input.clone()));
+        let it = self.aut.try_find_overlapping_iter(input)?;
+        Ok(FindOverlappingIter { it, verification })
     }
 
-    /// Replace all matches with a corresponding value in the `replace_with`
-    /// slice given. Matches correspond to the same matches as reported by
-    /// [`AhoCorasick::try_find_iter`].
+    /// Returns the number of overlapping matches in the given input, without
+    /// reporting each of them.
+    ///
+    /// This returns the same count as
+    /// [`AhoCorasick::try_find_overlapping_iter`], but the number of matches
+    /// ending at each position is added at once, so that the search takes
+    /// time proportional to the length of the haystack rather than to the
+    /// number of matches. This matters when there are far more matches than
+    /// bytes, for example with many short patterns that are suffixes of
+    /// each other.
+    ///
+    /// This only applies to the DFA and the contiguous NFA. A search with a
+    /// noncontiguous NFA still walks the matches of each match state. When
+    /// every match has to be verified, such as with a
+    /// [verifier](AhoCorasickBuilder::verify) or a
+    /// [lookahead](AhoCorasickBuilder::lookahead), the matches are counted
+    /// one at a time instead.
     ///
-    /// Replacements are determined by the index of the matching pattern.
-    /// For example, if the pattern with index `2` is found, then it is
-    /// replaced by `replace_with[2]`.
+    /// # Errors
     ///
-    /// # Panics
+    /// This returns an error in the same cases as
+    /// [`AhoCorasick::try_find_overlapping_iter`]. That is, when this
+    /// automaton doesn't use [`MatchKind::Standard`] semantics, or when the
+    /// search is anchored.
     ///
-    /// This panics when `replace_with.len()` does not equal
-    /// [`AhoCorasick::patterns_len`].
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["append", "appendage", "app"]).unwrap();
+    /// let haystack = "append the app to the appendage";
+    /// assert_eq!(6, ac.try_count_overlapping_fast(haystack)?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_count_overlapping_fast<'h, I: Into<Input<'h>>>(
+        &self,
+        input: I,
+    ) -> Result<usize, MatchError> {
+        let input = input.into();
+        if self.verification(&input)?.is_some() {
+            return Ok(self.try_find_overlapping_iter(input)?.count());
+        }
+        enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
+        if !self.match_kind().is_standard() {
+            return Err(MatchError::unsupported_overlapping(
+                self.match_kind(),
+            ));
+        }
+        if input.get_anchored().is_anchored() {
+            return Err(MatchError::invalid_input_anchored());
+        }
+        match self.aut.as_ref().coerce_concrete(self.kind) {
+            AutomatonImpl::NoncontiguousNFA(nfa) => {
+                automaton::count_overlapping_fwd(nfa, &input)
+            }
+            AutomatonImpl::ContiguousNFA(nfa) => {
+                automaton::count_overlapping_fwd(nfa, &input)
+            }
+            AutomatonImpl::DFA(dfa) => {
+                automaton::count_overlapping_fwd(dfa, &input)
+            }
+        }
+    }
+
+    /// Estimate the number of matches per byte in the given haystack by
+    /// searching a deterministic sample of it.
+    ///
+    /// The haystack is split into consecutive strides of `sample_stride`
+    /// bytes, and only a window at the start of each stride is searched.
+    /// Each window is 64 bytes long, or twice the length of the longest
+    /// pattern if that is longer, and the matches starting in it are
+    /// counted as reported by [`AhoCorasick::try_find_iter`]. The estimate
+    /// is the number of matches counted divided by the number of bytes in
+    /// the windows, or `0.0` for an empty haystack.
+    ///
+    /// This is cheap enough to decide whether a full search of a large
+    /// haystack is worth it, for example to skip the files of a data
+    /// pipeline that are unlikely to contain any match. Since the same
+    /// windows are always sampled, the estimate of a given haystack never
+    /// changes. A stride no longer than a window samples the whole
+    /// haystack, and the estimate is then exact. Matches clustered between
+    /// the windows are missed, so a rate of `0.0` doesn't mean that there
+    /// is no match.
     ///
     /// # Errors
     ///
-    /// This returns an error when this Aho-Corasick searcher does not support
-    /// the default `Input` configuration. More specifically, this occurs only
-    /// when the Aho-Corasick searcher does not support unanchored searches
-    /// since this replacement routine always does an unanchored search.
+    /// This returns an error in the same cases as
+    /// [`AhoCorasick::try_find_iter`]. That is, when this Aho-Corasick
+    /// searcher doesn't support unanchored searches.
     ///
     /// # Example: basic usage
     ///
     /// ```
-    /// use aho_corasick::{AhoCorasick, MatchKind};
-    ///
-    /// let patterns = &["append", "appendage", "app"];
-    /// let haystack = "append the app to the appendage";
+    /// use aho_corasick::AhoCorasick;
     ///
-    /// let ac = AhoCorasick::builder()
-    ///     .match_kind(MatchKind::LeftmostFirst)
-    ///     .build(patterns)
-    ///     .unwrap();
-    /// let result = ac.try_replace_all(haystack, &["x", "y", "z"])?;
-    /// assert_eq!("x the z to the xage", result);
+    /// let ac = AhoCorasick::new(&["needle"]).unwrap();
+    /// let haystack = "a needle in a haystack ".repeat(1000);
+    /// // Only about one byte in 16 is searched.
+    /// let rate = ac.try_estimate_hit_rate(&haystack, 1024)?;
+    /// assert!((rate - 1.0 / 23.0).abs() < 0.01);
+    /// // The whole haystack is searched.
+    /// assert_eq!(1.0 / 23.0, ac.try_estimate_hit_rate(&haystack, 0)?);
     ///
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn try_replace_all<B>(
+    pub fn try_estimate_hit_rate<H: AsRef<[u8]>>(
         &self,
-        haystack: &str,
-        replace_with: &[B],
-    ) -> Result<String, MatchError>
-    where
-        B: AsRef<str>,
-    {
-        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
-        self.aut.try_replace_all(haystack, replace_with)
+        haystack: H,
+        sample_stride: usize,
+    ) -> Result<f64, MatchError> {
+        const MIN_WINDOW_LEN: usize = 64;
+
+        let haystack = haystack.as_ref();
+        let max_len = self.max_pattern_len();
+        let window = core::cmp::max(MIN_WINDOW_LEN, 2 * max_len);
+        let stride = core::cmp::max(window, sample_stride);
+        let (mut matches, mut sampled, mut last_end) = (0, 0, 0);
+        let mut start = 0;
+        while start < haystack.len() {
+            let end = core::cmp::min(start + window, haystack.len());
+            // A match starting in the window may end after it, in which case
+            // the next window is searched from the end of that match.
+            let search_end = core::cmp::min(
+                end + max_len.saturating_sub(1),
+                haystack.len(),
+            );
+            let from = core::cmp::max(start, last_end);
+            let input = Input::new(haystack).range(from..search_end);
+            for m in self.try_find_iter(input)? {
+                if m.start() >= end {
+                    break;
+                }
+                matches += 1;
+                last_end = m.end();
+            }
+            sampled += end - start;
+            start = start.saturating_add(stride);
+        }
+        if sampled == 0 {
+            return Ok(0.0);
+        }
+        Ok(matches as f64 / sampled as f64)
     }
 
-    /// Replace all matches using raw bytes with a corresponding value in the
-    /// `replace_with` slice given. Matches correspond to the same matches as
-    /// reported by [`AhoCorasick::try_find_iter`].
+    /// Returns an iterator of non-overlapping matches in compact form.
     ///
-    /// Replacements are determined by the index of the matching pattern.
-    /// For example, if the pattern with index `2` is found, then it is
-    /// replaced by `replace_with[2]`.
+    /// This reports precisely the same matches as
+    /// [`AhoCorasick::try_find_iter`], but each match is represented by a
+    /// [`MatchU32`], which uses half as much memory as a [`Match`] on 64-bit
+    /// targets. This is useful when collecting very many matches, for example
+    /// into an arena or a pre-allocated buffer.
     ///
-    /// This is the fallible version of [`AhoCorasick::replace_all_bytes`].
+    /// # Errors
     ///
-    /// # Panics
+    /// In addition to the errors returned by [`AhoCorasick::try_find_iter`],
+    /// this returns an error when a match might not fit into a `MatchU32`.
+    /// That is, when the end of the search is greater than `u32::MAX` or when
+    /// the longest pattern in this automaton is longer than `u16::MAX`. Since
+    /// this is checked before the search begins, the iterator returned never
+    /// needs to report an error.
     ///
-    /// This panics when `replace_with.len()` does not equal
-    /// [`AhoCorasick::patterns_len`].
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Input, Match, MatchU32};
+    ///
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// let mut arena: Vec<MatchU32> = Vec::with_capacity(16);
+    /// arena.extend(ac.try_find_iter_u32(Input::new("foo bar"))?);
+    /// assert_eq!(
+    ///     vec![Match::must(0, 0..3), Match::must(1, 4..7)],
+    ///     arena.iter().map(|m| m.to_match()).collect::<Vec<Match>>(),
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_find_iter_u32<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> Result<FindIterU32<'a, 'h>, MatchError> {
+        let input = input.into();
+        let fits = u32::try_from(input.end()).is_ok()
+            && u16::try_from(self.max_pattern_len()).is_ok();
+        if !fits {
+            return Err(MatchError::unsupported_compact(
+                input.end(),
+                self.max_pattern_len(),
+            ));
+        }
+        Ok(FindIterU32(self.try_find_iter(input)?))
+    }
+
+    /// Returns the non-overlapping matches in each of the given haystacks.
+    ///
+    /// This is the fallible version of [`AhoCorasick::find_many`].
     ///
     /// # Errors
     ///
     /// This returns an error when this Aho-Corasick searcher does not support
     /// the default `Input` configuration. More specifically, this occurs only
     /// when the Aho-Corasick searcher does not support unanchored searches
-    /// since this replacement routine always does an unanchored search.
+    /// since this routine always does an unanchored search.
     ///
     /// # Example: basic usage
     ///
     /// ```
-    /// use aho_corasick::{AhoCorasick, MatchKind};
-    ///
-    /// let patterns = &["append", "appendage", "app"];
-    /// let haystack = b"append the app to the appendage";
+    /// use aho_corasick::{AhoCorasick, Match};
     ///
-    /// let ac = AhoCorasick::builder()
-    ///     .match_kind(MatchKind::LeftmostFirst)
-    ///     .build(patterns)
-    ///     .unwrap();
-    /// let result = ac.try_replace_all_bytes(haystack, &["x", "y", "z"])?;
-    /// assert_eq!(b"x the z to the xage".to_vec(), result);
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// let matches = ac.try_find_many(&["foo", "xbar"])?;
+    /// assert_eq!(
+    ///     vec![vec![Match::must(0, 0..3)], vec![Match::must(1, 1..4)]],
+    ///     matches,
+    /// );
     ///
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn try_replace_all_bytes<B>(
+    pub fn try_find_many<H: AsRef<[u8]>>(
         &self,
-        haystack: &[u8],
-        replace_with: &[B],
-    ) -> Result<Vec<u8>, MatchError>
-    where
-        B: AsRef<[u8]>,
-    {
+        haystacks: &[H],
+    ) -> Result<Vec<Vec<Match>>, MatchError> {
         enforce_anchored_consistency(self.start_kind, Anchored::No)?;
-        self.aut.try_replace_all_bytes(haystack, replace_with)
+        if self.filters_matches() {
+            // The matches rejected by every search are skipped like in any
+            // other search, which the automaton can't do by itself.
+            let mut all = Vec::with_capacity(haystacks.len());
+            for haystack in haystacks.iter() {
+                let input = Input::new(haystack).verify(false);
+                all.push(self.try_find_iter(input)?.collect());
+            }
+            return Ok(all);
+        }
+        // Resolve the concrete automaton once so that searching each
+        // haystack doesn't go through dynamic dispatch.
+        let aut = self.aut.as_ref().as_any();
+        match self.kind {
+            AhoCorasickKind::NoncontiguousNFA => try_find_many_imp(
+                aut.downcast_ref::<noncontiguous::NFA>().unwrap(),
+                haystacks,
+            ),
+            AhoCorasickKind::ContiguousNFA => try_find_many_imp(
+                aut.downcast_ref::<contiguous::NFA>().unwrap(),
+                haystacks,
+            ),
+            AhoCorasickKind::DFA => try_find_many_imp(
+                aut.downcast_ref::<dfa::DFA>().unwrap(),
+                haystacks,
+            ),
+        }
+    }
+
+    /// Like [`AhoCorasick::try_find_many`], but searches the haystacks in
+    /// parallel using the global `rayon` thread pool.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::try_find_many`].
+    #[cfg(feature = "rayon")]
+    pub fn try_par_find_many<H: AsRef<[u8]> + Sync>(
+        &self,
+        haystacks: &[H],
+    ) -> Result<Vec<Vec<Match>>, MatchError> {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        if self.filters_matches() {
+            use rayon::prelude::*;
+
+            return haystacks
+                .par_iter()
+                .map(|haystack| {
+                    let input = Input::new(haystack).verify(false);
+                    Ok(self.try_find_iter(input)?.collect())
+                })
+                .collect();
+        }
+        let aut = self.aut.as_ref().as_any();
+        match self.kind {
+            AhoCorasickKind::NoncontiguousNFA => try_par_find_many_imp(
+                aut.downcast_ref::<noncontiguous::NFA>().unwrap(),
+                haystacks,
+            ),
+            AhoCorasickKind::ContiguousNFA => try_par_find_many_imp(
+                aut.downcast_ref::<contiguous::NFA>().unwrap(),
+                haystacks,
+            ),
+            AhoCorasickKind::DFA => try_par_find_many_imp(
+                aut.downcast_ref::<dfa::DFA>().unwrap(),
+                haystacks,
+            ),
+        }
+    }
+
+    /// Returns an iterator of the non-overlapping matches in the given
+    /// haystack, which is searched in parallel using the global `rayon`
+    /// thread pool.
+    ///
+    /// This yields precisely the same matches as
+    /// [`AhoCorasick::try_find_iter`], which is useful to search very large
+    /// haystacks, such as memory mapped files, with every core.
+    ///
+    /// The haystack is split into shards that are searched independently.
+    /// Each shard reports the matches that start in it, so the search of a
+    /// shard extends past its end by the length of the longest pattern
+    /// minus one. Since a match reported by a non-overlapping search
+    /// depends on where the previous one ended, the shards are then merged
+    /// in order: when the last match of a shard ends in the next shard,
+    /// the next shard is searched again from the end of that match, until
+    /// the matches found agree with the ones it reported.
+    ///
+    /// The matches are collected before they're yielded. Haystacks too small
+    /// to be worth splitting are searched on the calling thread, as are the
+    /// haystacks of a searcher with leftmost match semantics and an empty
+    /// pattern, whose empty matches depend on the rest of the haystack.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::try_find_many`]. That is, when this Aho-Corasick
+    /// searcher doesn't support unanchored searches.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Match, MatchKind};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostLongest)
+    ///     .build(&["ab", "abcd", "cd"])
+    ///     .unwrap();
+    /// let haystack = "abcd".repeat(1_000_000);
+    /// let matches: Vec<Match> = ac.try_par_find_iter(&haystack)?.collect();
+    /// assert_eq!(1_000_000, matches.len());
+    /// assert_eq!(Match::must(1, 3_999_996..4_000_000), matches[999_999]);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn try_par_find_iter<H: AsRef<[u8]>>(
+        &self,
+        haystack: H,
+    ) -> Result<alloc::vec::IntoIter<Match>, MatchError> {
+        // Shards are large enough that searching them outweighs the cost of
+        // scheduling them, and that resynchronizing after a match crossing
+        // their end is rare.
+        const MIN_SHARD_LEN: usize = 1 << 16;
+
+        let haystack = haystack.as_ref();
+        let shards = 4 * rayon::current_num_threads();
+        let shard_len = core::cmp::max(MIN_SHARD_LEN, haystack.len() / shards);
+        let matches = self.try_par_find_sharded(haystack, shard_len)?;
+        Ok(matches.into_iter())
+    }
+
+    /// Returns the non-overlapping matches in the given haystack, as
+    /// reported by `try_find_iter`, by searching shards of the given length
+    /// in parallel. See `try_par_find_iter`.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn try_par_find_sharded(
+        &self,
+        haystack: &[u8],
+        shard_len: usize,
+    ) -> Result<Vec<Match>, MatchError> {
+        use rayon::prelude::*;
+
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        // A shard is never shorter than a match, so that the search of a
+        // shard only ever extends into the next one.
+        let shard_len = core::cmp::max(shard_len, self.max_pattern_len());
+        // With leftmost semantics, where a search reports an empty match
+        // depends on how far past it the search goes, so a shard can't be
+        // searched without the rest of the haystack.
+        let empty_leftmost =
+            !self.match_kind().is_standard() && self.min_pattern_len() == 0;
+        if haystack.len() <= shard_len || empty_leftmost {
+            return Ok(self.try_find_iter(haystack)?.collect());
+        }
+        let overlap = self.max_pattern_len().saturating_sub(1);
+        // Returns the matches starting at or after 'from' and before
+        // 'end', as reported by a search from 'from'. The last shard also
+        // has the empty matches at the end of the haystack.
+        let search = |from: usize, end: usize| {
+            let search_end = core::cmp::min(end + overlap, haystack.len());
+            let last = end == haystack.len();
+            let input = Input::new(haystack).range(from..search_end);
+            self.try_find_iter(input)
+                .map(|it| it.take_while(move |m| last || m.start() < end))
+        };
+        let starts: Vec<usize> =
+            (0..haystack.len()).step_by(shard_len).collect();
+        let shards: Vec<Vec<Match>> = starts
+            .par_iter()
+            .map(|&start| {
+                let end = core::cmp::min(start + shard_len, haystack.len());
+                Ok(search(start, end)?.collect())
+            })
+            .collect::<Result<_, MatchError>>()?;
+        let mut matches: Vec<Match> = Vec::new();
+        for (&start, shard) in starts.iter().zip(shards) {
+            let end = core::cmp::min(start + shard_len, haystack.len());
+            let last_end = matches.last().map(|m| m.end());
+            // As with the iterator, an empty match may not overlap with the
+            // end of the previous match.
+            let overlaps =
+                |m: &Match| m.is_empty() && Some(m.end()) == last_end;
+            let from = last_end.unwrap_or(0);
+            if from <= start {
+                // The previous shard reported every match starting before
+                // this one, so the search from its last match finds the
+                // same matches as the search of this shard.
+                matches.extend(shard.into_iter().skip_while(overlaps));
+                continue;
+            }
+            if from > end || (from == end && end < haystack.len()) {
+                continue;
+            }
+            // Once both searches report the same match, they go on from the
+            // same position and agree from then on.
+            for m in search(from, end)?.skip_while(overlaps) {
+                match shard.binary_search_by_key(&m.start(), |m| m.start()) {
+                    Ok(i) if shard[i] == m => {
+                        matches.extend_from_slice(&shard[i..]);
+                        break;
+                    }
+                    _ => matches.push(m),
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Replace all matches with a corresponding value in the `replace_with`
+    /// slice given. Matches correspond to the same matches as reported by
+    /// [`AhoCorasick::try_find_iter`].
+    ///
+    /// Replacements are determined by the index of the matching pattern.
+    /// For example, if the pattern with index `2` is found, then it is
+    /// replaced by `replace_with[2]`.
+    ///
+    /// # Panics
+    ///
+    /// This panics when `replace_with.len()` does not equal
+    /// [`AhoCorasick::patterns_len`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when this Aho-Corasick searcher does not support
+    /// the default `Input` configuration. More specifically, this occurs only
+    /// when the Aho-Corasick searcher does not support unanchored searches
+    /// since this replacement routine always does an unanchored search.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, MatchKind};
+    ///
+    /// let patterns = &["append", "appendage", "app"];
+    /// let haystack = "append the app to the appendage";
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostFirst)
+    ///     .build(patterns)
+    ///     .unwrap();
+    /// let result = ac.try_replace_all(haystack, &["x", "y", "z"])?;
+    /// assert_eq!("x the z to the xage", result);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_replace_all<B>(
+        &self,
+        haystack: &str,
+        replace_with: &[B],
+    ) -> Result<String, MatchError>
+    where
+        B: AsRef<str>,
+    {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        if self.overlap_policy.is_none() && !self.verifies_replacements() {
+            return self.aut.try_replace_all(haystack, replace_with);
+        }
+        assert_eq!(
+            replace_with.len(),
+            self.patterns_len(),
+            "replace_all requires a replacement for every pattern \
+             in the automaton"
+        );
+        let mut dst = String::with_capacity(haystack.len());
+        self.try_replace_all_with(haystack, &mut dst, |mat, _, dst| {
+            dst.push_str(replace_with[mat.pattern()].as_ref());
+            true
+        })?;
+        Ok(dst)
+    }
+
+    /// Replace all matches using raw bytes with a corresponding value in the
+    /// `replace_with` slice given. Matches correspond to the same matches as
+    /// reported by [`AhoCorasick::try_find_iter`].
+    ///
+    /// Replacements are determined by the index of the matching pattern.
+    /// For example, if the pattern with index `2` is found, then it is
+    /// replaced by `replace_with[2]`.
+    ///
+    /// This is the fallible version of [`AhoCorasick::replace_all_bytes`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when `replace_with.len()` does not equal
+    /// [`AhoCorasick::patterns_len`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when this Aho-Corasick searcher does not support
+    /// the default `Input` configuration. More specifically, this occurs only
+    /// when the Aho-Corasick searcher does not support unanchored searches
+    /// since this replacement routine always does an unanchored search.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, MatchKind};
+    ///
+    /// let patterns = &["append", "appendage", "app"];
+    /// let haystack = b"append the app to the appendage";
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostFirst)
+    ///     .build(patterns)
+    ///     .unwrap();
+    /// let result = ac.try_replace_all_bytes(haystack, &["x", "y", "z"])?;
+    /// assert_eq!(b"x the z to the xage".to_vec(), result);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_replace_all_bytes<B>(
+        &self,
+        haystack: &[u8],
+        replace_with: &[B],
+    ) -> Result<Vec<u8>, MatchError>
+    where
+        B: AsRef<[u8]>,
+    {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        if self.overlap_policy.is_none() && !self.verifies_replacements() {
+            return self.aut.try_replace_all_bytes(haystack, replace_with);
+        }
+        assert_eq!(
+            replace_with.len(),
+            self.patterns_len(),
+            "replace_all requires a replacement for every pattern \
+             in the automaton"
+        );
+        let mut dst = Vec::with_capacity(haystack.len());
+        self.try_replace_all_with_bytes(haystack, &mut dst, |mat, _, dst| {
+            dst.extend(replace_with[mat.pattern()].as_ref());
+            true
+        })?;
+        Ok(dst)
+    }
+
+    /// Replace all matches using raw bytes with a corresponding value in the
+    /// `replace_with` slice given, without copying the haystack when nothing
+    /// matches. Matches correspond to the same matches as reported by
+    /// [`AhoCorasick::try_find_iter`].
+    ///
+    /// This gives the same output as [`AhoCorasick::try_replace_all_bytes`],
+    /// but returns [`Cow::Borrowed`] when no pattern matches the haystack.
+    /// This avoids an allocation and a copy for every haystack when matches
+    /// are rare. Otherwise, the haystack is searched up to its first match
+    /// twice.
+    ///
+    /// This is the fallible version of [`AhoCorasick::replace_all_cow`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when `replace_with.len()` does not equal
+    /// [`AhoCorasick::patterns_len`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when this Aho-Corasick searcher does not support
+    /// the default `Input` configuration. More specifically, this occurs only
+    /// when the Aho-Corasick searcher does not support unanchored searches
+    /// since this replacement routine always does an unanchored search.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["apple", "banana"]).unwrap();
+    /// let haystack = b"a cherry a day";
+    /// let result = ac.try_replace_all_cow(haystack, &["pear", "kiwi"])?;
+    /// assert!(matches!(result, Cow::Borrowed(b) if b == haystack));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_replace_all_cow<'h, B>(
+        &self,
+        haystack: &'h [u8],
+        replace_with: &[B],
+    ) -> Result<Cow<'h, [u8]>, MatchError>
+    where
+        B: AsRef<[u8]>,
+    {
+        if !self.try_replaces_any(haystack)? {
+            assert_eq!(
+                replace_with.len(),
+                self.patterns_len(),
+                "replace_all requires a replacement for every pattern \
+                 in the automaton"
+            );
+            return Ok(Cow::Borrowed(haystack));
+        }
+        self.try_replace_all_bytes(haystack, replace_with).map(Cow::Owned)
+    }
+
+    /// Replace all matches with a corresponding value in the `replace_with`
+    /// slice given, without copying the haystack when nothing matches.
+    /// Matches correspond to the same matches as reported by
+    /// [`AhoCorasick::try_find_iter`].
+    ///
+    /// This gives the same output as [`AhoCorasick::try_replace_all`], but
+    /// returns [`Cow::Borrowed`] when no pattern matches the haystack.
+    ///
+    /// This is the fallible version of [`AhoCorasick::replace_all_cow_str`].
+    ///
+    /// # Panics
+    ///
+    /// This panics when `replace_with.len()` does not equal
+    /// [`AhoCorasick::patterns_len`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when this Aho-Corasick searcher does not support
+    /// the default `Input` configuration. More specifically, this occurs only
+    /// when the Aho-Corasick searcher does not support unanchored searches
+    /// since this replacement routine always does an unanchored search.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["apple", "banana"]).unwrap();
+    /// let with = &["pear", "kiwi"];
+    /// let result = ac.try_replace_all_cow_str("a banana", with)?;
+    /// assert_eq!("a kiwi", result);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_replace_all_cow_str<'h, B>(
+        &self,
+        haystack: &'h str,
+        replace_with: &[B],
+    ) -> Result<Cow<'h, str>, MatchError>
+    where
+        B: AsRef<str>,
+    {
+        if !self.try_replaces_any(haystack.as_bytes())? {
+            assert_eq!(
+                replace_with.len(),
+                self.patterns_len(),
+                "replace_all requires a replacement for every pattern \
+                 in the automaton"
+            );
+            return Ok(Cow::Borrowed(haystack));
+        }
+        self.try_replace_all(haystack, replace_with).map(Cow::Owned)
+    }
+
+    /// Returns true if replacing all matches in the given haystack replaces
+    /// at least one of them.
+    ///
+    /// A match is replaced under any overlap policy if and only if there is
+    /// one at all, so the first match found is enough.
+    fn try_replaces_any(&self, haystack: &[u8]) -> Result<bool, MatchError> {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        Ok(self.try_find_replaced_iter(haystack)?.next().is_some())
     }
 
     /// Replace all matches using a closure called on each match.
@@ -1538,7 +2479,50 @@ impl AhoCorasick {
         F: FnMut(&Match, &str, &mut String) -> bool,
     {
         enforce_anchored_consistency(self.start_kind, Anchored::No)?;
-        self.aut.try_replace_all_with(haystack, dst, replace_with)
+        let policy = match self.overlap_policy {
+            None if !self.verifies_replacements() => {
+                return self.aut.try_replace_all_with(
+                    haystack,
+                    dst,
+                    replace_with,
+                )
+            }
+            None => {
+                let mut replace_with = replace_with;
+                let mut last_match = 0;
+                for m in self.try_find_replaced_iter(haystack.as_bytes())? {
+                    if !haystack.is_char_boundary(m.start())
+                        || !haystack.is_char_boundary(m.end())
+                    {
+                        continue;
+                    }
+                    dst.push_str(&haystack[last_match..m.start()]);
+                    last_match = m.end();
+                    if !replace_with(&m, &haystack[m.start()..m.end()], dst) {
+                        break;
+                    }
+                }
+                dst.push_str(&haystack[last_match..]);
+                return Ok(());
+            }
+            Some(policy) => policy,
+        };
+        let mut replace_with = replace_with;
+        let mut last_match = 0;
+        self.try_for_each_resolved(policy, haystack.as_bytes(), |m| {
+            // As with non-overlapping matches, a match that splits a
+            // codepoint is skipped.
+            if !haystack.is_char_boundary(m.start())
+                || !haystack.is_char_boundary(m.end())
+            {
+                return true;
+            }
+            dst.push_str(&haystack[last_match..m.start()]);
+            last_match = m.end();
+            replace_with(&m, &haystack[m.start()..m.end()], dst)
+        })?;
+        dst.push_str(&haystack[last_match..]);
+        Ok(())
     }
 
     /// Replace all matches using raw bytes with a closure called on each
@@ -1614,7 +2598,74 @@ impl AhoCorasick {
         F: FnMut(&Match, &[u8], &mut Vec<u8>) -> bool,
     {
         enforce_anchored_consistency(self.start_kind, Anchored::No)?;
-        self.aut.try_replace_all_with_bytes(haystack, dst, replace_with)
+        let policy = match self.overlap_policy {
+            None if !self.verifies_replacements() => {
+                return self.aut.try_replace_all_with_bytes(
+                    haystack,
+                    dst,
+                    replace_with,
+                )
+            }
+            None => {
+                let mut replace_with = replace_with;
+                let mut last_match = 0;
+                for m in self.try_find_replaced_iter(haystack)? {
+                    dst.extend(&haystack[last_match..m.start()]);
+                    last_match = m.end();
+                    if !replace_with(&m, &haystack[m.start()..m.end()], dst) {
+                        break;
+                    }
+                }
+                dst.extend(&haystack[last_match..]);
+                return Ok(());
+            }
+            Some(policy) => policy,
+        };
+        let mut replace_with = replace_with;
+        let mut last_match = 0;
+        self.try_for_each_resolved(policy, haystack, |m| {
+            dst.extend(&haystack[last_match..m.start()]);
+            last_match = m.end();
+            replace_with(&m, &haystack[m.start()..m.end()], dst)
+        })?;
+        dst.extend(&haystack[last_match..]);
+        Ok(())
+    }
+
+    /// Call the given closure on each match that is replaced in the given
+    /// haystack under the given overlap policy, in order, until it returns
+    /// `false`.
+    fn try_for_each_resolved<F>(
+        &self,
+        policy: OverlapPolicy,
+        haystack: &[u8],
+        mut f: F,
+    ) -> Result<(), MatchError>
+    where
+        F: FnMut(Match) -> bool,
+    {
+        let mut resolver = OverlapResolver::new(policy);
+        let mut replaced = Vec::new();
+        let max_pattern_len = self.max_pattern_len();
+        for m in self.try_find_overlapping_iter(haystack)? {
+            // Overlapping matches are found in the order of their end, so
+            // no match found from here on starts before this bound.
+            let bound = m.end().saturating_sub(max_pattern_len);
+            resolver.resolve(bound, &mut replaced);
+            for r in replaced.drain(..) {
+                if !f(r) {
+                    return Ok(());
+                }
+            }
+            resolver.add(m);
+        }
+        resolver.resolve(usize::MAX, &mut replaced);
+        for r in replaced {
+            if !f(r) {
+                break;
+            }
+        }
+        Ok(())
     }
 
     /// Returns an iterator of non-overlapping matches in the given
@@ -1658,6 +2709,11 @@ impl AhoCorasick {
     /// searches. Only searchers built with [`MatchKind::Standard`] semantics
     /// support stream searches.
     ///
+    /// It also returns an error if [`AhoCorasickBuilder::word_boundary`] is
+    /// enabled, or if a pattern has a
+    /// [lookahead](AhoCorasickBuilder::lookahead), since stream searches
+    /// don't honor them.
+    ///
     /// # Example: basic usage
     ///
     /// ```
@@ -1686,16 +2742,179 @@ impl AhoCorasick {
         rdr: R,
     ) -> Result<StreamFindIter<'a, R>, MatchError> {
         enforce_anchored_consistency(self.start_kind, Anchored::No)?;
-        self.aut.try_stream_find_iter(rdr).map(StreamFindIter)
+        self.enforce_no_context()?;
+        automaton::StreamFindIter::new(&self.aut, rdr, false, self.disabled())
+            .map(StreamFindIter)
     }
 
-    /// Search for and replace all matches of this automaton in
-    /// the given reader, and write the replacements to the given
-    /// writer. Matches correspond to the same matches as reported by
-    /// [`AhoCorasick::try_find_iter`].
+    /// Returns a finder that reports the matches in a stream given to it
+    /// in chunks.
     ///
-    /// Replacements are determined by the index of the matching pattern. For
-    /// example, if the pattern with index `2` is found, then it is replaced by
+    /// This is the push-based equivalent of
+    /// [`AhoCorasick::try_stream_find_iter`]: instead of reading from a
+    /// `std::io::Read`, the finder is given each chunk of the stream as it
+    /// arrives, and returns the matches that end in it. It reports the same
+    /// matches, with offsets into the whole stream. It's also available
+    /// without the `std` feature.
+    ///
+    /// See [`AhoCorasickStreamFinder`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::try_stream_find_iter`], or when this searcher contains
+    /// an empty pattern.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Match};
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut finder = ac.stream_finder()?;
+    /// let mut matches = finder.find(b"The brown f").to_vec();
+    /// matches.extend_from_slice(finder.find(b"ox."));
+    /// matches.extend_from_slice(finder.finish());
+    /// assert_eq!(
+    ///     vec![Match::must(1, 4..9), Match::must(0, 10..13)],
+    ///     matches,
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stream_finder(
+        &self,
+    ) -> Result<AhoCorasickStreamFinder, MatchError> {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        self.enforce_no_context()?;
+        if !self.match_kind().is_standard() {
+            return Err(MatchError::unsupported_stream(self.match_kind()));
+        }
+        if self.min_pattern_len() == 0 {
+            return Err(MatchError::unsupported_empty());
+        }
+        let start = self.aut.start_state(Anchored::No)?;
+        Ok(AhoCorasickStreamFinder {
+            aut: Arc::clone(&self.aut),
+            kind: self.kind,
+            disabled: self.disabled.clone(),
+            start,
+            sid: start,
+            position: 0,
+            matches: Vec::new(),
+        })
+    }
+
+    /// Returns an iterator of non-overlapping matches in the given
+    /// stream, where each match is reported as soon as a match state is
+    /// entered. This is the streaming equivalent of searching with
+    /// [`Input::earliest`] enabled.
+    ///
+    /// For searchers built with [`MatchKind::Standard`] semantics, this
+    /// reports the same matches as [`AhoCorasick::try_stream_find_iter`],
+    /// since standard semantics already report matches as soon as they are
+    /// found. For searchers built with leftmost semantics, this permits
+    /// stream searching by giving up on leftmost-first or leftmost-longest
+    /// match resolution. That is, the match reported is the first one to
+    /// _end_, and its pattern is whichever pattern the automaton associates
+    /// with the match state that was entered. This is useful in latency
+    /// critical paths where only the fact that a match occurred (and
+    /// roughly where) matters.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when this Aho-Corasick searcher does not support
+    /// unanchored searches, when it contains an empty pattern, when
+    /// [`AhoCorasickBuilder::word_boundary`] is enabled, or when a pattern
+    /// has a [lookahead](AhoCorasickBuilder::lookahead).
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Match, MatchKind};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostLongest)
+    ///     .build(&["abcd", "abc"])
+    ///     .unwrap();
+    /// // A normal stream search is not supported with leftmost semantics.
+    /// assert!(ac.try_stream_find_iter("abcd".as_bytes()).is_err());
+    ///
+    /// let mut matches = vec![];
+    /// for result in ac.try_stream_find_iter_earliest("abcd".as_bytes())? {
+    ///     matches.push(result?);
+    /// }
+    /// // Without earliest semantics, 'abcd' would be the match reported.
+    /// assert_eq!(vec![Match::must(1, 0..3)], matches);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn try_stream_find_iter_earliest<'a, R: std::io::Read>(
+        &'a self,
+        rdr: R,
+    ) -> Result<StreamFindIter<'a, R>, MatchError> {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        self.enforce_no_context()?;
+        automaton::StreamFindIter::new(&self.aut, rdr, true, self.disabled())
+            .map(StreamFindIter)
+    }
+
+    /// Returns an iterator of non-overlapping matches in the given
+    /// stream, where each match is reported along with the line and column
+    /// at which it starts. Matches correspond to the same matches as reported
+    /// by [`AhoCorasick::try_stream_find_iter`].
+    ///
+    /// Lines and columns are tracked while the stream is searched, so this
+    /// doesn't require a second pass over the stream. Lines and columns both
+    /// start at `1`, and columns are counted in the given unit. See
+    /// [`ColumnUnit`] for details.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same cases as
+    /// [`AhoCorasick::try_stream_find_iter`].
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, ColumnUnit};
+    ///
+    /// let ac = AhoCorasick::new(&["TODO", "FIXME"]).unwrap();
+    /// let rdr = "fn main() {\n    // TODO: ünïcode\nFIXME\n}".as_bytes();
+    ///
+    /// let mut positions = vec![];
+    /// for result in ac.try_stream_find_iter_line_column(rdr, ColumnUnit::Char)? {
+    ///     let (mat, pos) = result?;
+    ///     positions.push((mat.pattern().as_usize(), pos.line(), pos.column()));
+    /// }
+    /// assert_eq!(vec![(0, 2, 8), (1, 3, 1)], positions);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn try_stream_find_iter_line_column<'a, R: std::io::Read>(
+        &'a self,
+        rdr: R,
+        unit: ColumnUnit,
+    ) -> Result<StreamLineColumnIter<'a, R>, MatchError> {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        self.enforce_no_context()?;
+        automaton::StreamLineColumnIter::new(
+            &self.aut,
+            rdr,
+            unit,
+            self.disabled(),
+        )
+        .map(StreamLineColumnIter)
+    }
+
+    /// Search for and replace all matches of this automaton in
+    /// the given reader, and write the replacements to the given
+    /// writer. Matches correspond to the same matches as reported by
+    /// [`AhoCorasick::try_find_iter`].
+    ///
+    /// Replacements are determined by the index of the matching pattern. For
+    /// example, if the pattern with index `2` is found, then it is replaced by
     /// `replace_with[2]`.
     ///
     /// After all matches are replaced, the writer is _not_ flushed.
@@ -1734,6 +2953,11 @@ impl AhoCorasick {
     /// searches. Only searchers built with [`MatchKind::Standard`] semantics
     /// support stream searches.
     ///
+    /// It also returns an error if [`AhoCorasickBuilder::word_boundary`] is
+    /// enabled, or if a pattern has a
+    /// [lookahead](AhoCorasickBuilder::lookahead), since stream
+    /// replacements don't honor them.
+    ///
     /// # Example: basic usage
     ///
     /// ```
@@ -1767,8 +2991,22 @@ impl AhoCorasick {
         B: AsRef<[u8]>,
     {
         enforce_anchored_consistency(self.start_kind, Anchored::No)
+            .and_then(|()| self.enforce_no_overlap_policy())
+            .and_then(|()| self.enforce_no_context())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        self.aut.try_stream_replace_all(rdr, wtr, replace_with)
+        assert_eq!(
+            replace_with.len(),
+            self.patterns_len(),
+            "streaming replace_all requires a replacement for every pattern \
+             in the automaton",
+        );
+        automaton::stream_replace_all_with(
+            &self.aut,
+            rdr,
+            wtr,
+            self.disabled(),
+            |mat, _, wtr| wtr.write_all(replace_with[mat.pattern()].as_ref()),
+        )
     }
 
     /// Search the given reader and replace all matches of this automaton
@@ -1810,6 +3048,11 @@ impl AhoCorasick {
     /// searches. Only searchers built with [`MatchKind::Standard`] semantics
     /// support stream searches.
     ///
+    /// It also returns an error if [`AhoCorasickBuilder::word_boundary`] is
+    /// enabled, or if a pattern has a
+    /// [lookahead](AhoCorasickBuilder::lookahead), since stream
+    /// replacements don't honor them.
+    ///
     /// # Example: basic usage
     ///
     /// ```
@@ -1845,8 +3088,16 @@ impl AhoCorasick {
         F: FnMut(&Match, &[u8], &mut W) -> Result<(), std::io::Error>,
     {
         enforce_anchored_consistency(self.start_kind, Anchored::No)
+            .and_then(|()| self.enforce_no_overlap_policy())
+            .and_then(|()| self.enforce_no_context())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        self.aut.try_stream_replace_all_with(rdr, wtr, replace_with)
+        automaton::stream_replace_all_with(
+            &self.aut,
+            rdr,
+            wtr,
+            self.disabled(),
+            replace_with,
+        )
     }
 
     /// Instantiate an AhoCorasickReplacer
@@ -1856,18 +3107,395 @@ impl AhoCorasick {
     /// 
     /// finish() must be called after chunks are processed, as it might return remaining pending bytes,
     /// in case the last part of the last chunk is a matching suffix
-    #[cfg(all(feature = "std"))]
-    pub fn replacer(
+    ///
+    /// Use [`AhoCorasick::replacer_with`] for more control over how the
+    /// replacer behaves, such as length preserving replacement.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the number of replacements is different
+    /// from the number of patterns in the automaton, if this automaton uses
+    /// [`MatchKind::LeftmostFirst`] semantics, or if it doesn't support
+    /// unanchored searches. With [`MatchKind::LeftmostLongest`] semantics,
+    /// it also returns an error if an option that such a replacer doesn't
+    /// support is set. See the
+    /// [match semantics](AhoCorasickReplacer#match-semantics) of a
+    /// replacer.
+    ///
+    /// When the automaton itself can't be used for replacement, its error is
+    /// returned as is. Otherwise, the kind of the error is
+    /// [`MatchErrorKind::Replacer`](crate::MatchErrorKind::Replacer), which
+    /// holds the [`ReplacerError`] that says why. Use
+    /// [`AhoCorasick::try_replacer`] to get a [`BuildError`] instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, MatchKind};
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// let mut out = replacer.replace(b"The brown f")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"ox.")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"The white bear.".to_vec(), out);
+    ///
+    /// // Replacements must be given for every pattern.
+    /// assert!(ac.replacer(&["bear"]).is_err());
+    /// // Leftmost-first semantics can't be used for replacing chunks.
+    /// let ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostFirst)
+    ///     .build(&["fox", "brown"])
+    ///     .unwrap();
+    /// assert!(ac.replacer(&["bear", "white"]).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replacer<I, B>(
         &self,
-        replace_with: Vec<Vec<u8>>,
+        replace_with: I,
     ) -> Result<AhoCorasickReplacer, MatchError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        AhoCorasickReplacerBuilder::new()
+            .build_replacer(self, replace_with)
+            .map_err(ReplacerError::into_match_error)
+    }
+
+    /// Instantiate an AhoCorasickReplacer as [`AhoCorasick::replacer`] does,
+    /// but report a failure to build it as a [`BuildError`], like the other
+    /// constructors of this crate.
+    ///
+    /// The replacements are checked when the replacer is built, so a
+    /// replacer returned by this never fails to find the replacement of a
+    /// match while replacing.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::replacer`], in particular if the number of
+    /// replacements is different from the number of patterns in the
+    /// automaton.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.try_replacer(vec!["bear", "white"])?;
+    /// let mut out = replacer.replace(b"The brown f")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"ox")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"The white bear".to_vec(), out);
+    ///
+    /// let err = ac.try_replacer(&["bear"]).err().unwrap();
+    /// assert_eq!(
+    ///     "expected 2 replacements (one per pattern), but got 1",
+    ///     err.to_string(),
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn try_replacer<I, B>(
+        &self,
+        replace_with: I,
+    ) -> Result<AhoCorasickReplacer, BuildError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        AhoCorasickReplacerBuilder::new()
+            .build_replacer(self, replace_with)
+            .map_err(BuildError::replacer)
+    }
+
+    /// Instantiate an AhoCorasickReplacer configured by the given builder,
+    /// where the pattern with ID `i` is replaced by `replace_with[i]`.
+    ///
+    /// See [`AhoCorasickReplacerBuilder`] for the available options.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::replacer`], and if padding is enabled and a
+    /// replacement is longer than its pattern.
+    pub fn replacer_with<I, B>(
+        &self,
+        builder: &AhoCorasickReplacerBuilder,
+        replace_with: I,
+    ) -> Result<AhoCorasickReplacer, ReplacerError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        builder.build_replacer(self, replace_with)
+    }
+
+    /// Instantiate an AhoCorasickReplacer configured by the given builder,
+    /// where each match of the pattern with ID `i` is replaced by one of the
+    /// alternatives in `alternatives[i]`, chosen by the given selection.
+    ///
+    /// This generates variants of a text at stream speed, for example to
+    /// augment data. Every selection is deterministic, so the same stream
+    /// always gets the same replacements, and
+    /// [`AhoCorasickReplacer::reset`] starts the selection over. See
+    /// [`Selection`] for the available strategies.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::replacer_with`], where each alternative is checked
+    /// like a replacement, if a pattern has no alternative, or if the
+    /// weights of a [`Selection::Weighted`] don't give every alternative a
+    /// weight, with at least one of them positive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder, Selection};
+    ///
+    /// let ac = AhoCorasick::new(&["cat", "dog"]).unwrap();
+    /// let mut replacer = ac.replacer_with_alternatives(
+    ///     &AhoCorasickReplacerBuilder::new(),
+    ///     [&["kitten", "tabby"][..], &["puppy"]],
+    ///     Selection::RoundRobin,
+    /// )?;
+    /// let mut out = replacer.replace(b"cat, dog, cat, cat.")?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"kitten, puppy, tabby, kitten.".to_vec(), out);
+    ///
+    /// // The same seed always selects the same alternatives.
+    /// let variant = |seed| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    ///     let mut replacer = ac.replacer_with_alternatives(
+    ///         &AhoCorasickReplacerBuilder::new(),
+    ///         [&["kitten", "tabby"][..], &["puppy", "hound"]],
+    ///         Selection::Random { seed },
+    ///     )?;
+    ///     let mut out = replacer.replace(b"cat dog cat dog")?.to_vec();
+    ///     out.extend_from_slice(replacer.finish()?);
+    ///     Ok(out)
+    /// };
+    /// assert_eq!(variant(7)?, variant(7)?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replacer_with_alternatives<I, A, B>(
+        &self,
+        builder: &AhoCorasickReplacerBuilder,
+        alternatives: I,
+        selection: Selection,
+    ) -> Result<AhoCorasickReplacer, ReplacerError>
+    where
+        I: IntoIterator<Item = A>,
+        A: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        builder.build_replacer_alternatives(self, alternatives, selection)
+    }
+
+    /// Instantiate an [`AhoCorasickReplacerWith`], which replaces each match
+    /// by the bytes returned by the given closure when called with its
+    /// pattern and the bytes it matched.
+    ///
+    /// See [`AhoCorasickReplacerWith`] for when the closure is called and an
+    /// example.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::replacer`], other than the number of replacements.
+    pub fn replacer_fn<F>(
+        &self,
+        replace_with: F,
+    ) -> Result<AhoCorasickReplacerWith<F>, ReplacerError>
+    where
+        F: FnMut(PatternID, &[u8]) -> Vec<u8>,
+    {
+        AhoCorasickReplacerBuilder::new().build_replacer_fn(self, replace_with)
+    }
+
+    /// Instantiate an [`AhoCorasickReplacerWith`] configured by the given
+    /// builder, which replaces each match by the bytes returned by the
+    /// given closure. See [`AhoCorasick::replacer_fn`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::replacer_fn`].
+    pub fn replacer_with_fn<F>(
+        &self,
+        builder: &AhoCorasickReplacerBuilder,
+        replace_with: F,
+    ) -> Result<AhoCorasickReplacerWith<F>, ReplacerError>
+    where
+        F: FnMut(PatternID, &[u8]) -> Vec<u8>,
+    {
+        builder.build_replacer_fn(self, replace_with)
+    }
+
+    /// Instantiate an AhoCorasickReplacer using one of the replacement tables
+    /// set by [`AhoCorasickBuilder::replacement_tables`], where the pattern
+    /// with ID `i` is replaced by the `i`th replacement of the table.
+    ///
+    /// Every replacer built from the same table shares its replacements, and
+    /// all of them share this automaton, so serving another table, such as
+    /// the redaction text of another locale, costs nothing beyond the
+    /// replacer itself.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if there is no table with the given ID, and in
+    /// the same circumstances as [`AhoCorasick::replacer`], in particular if
+    /// the table doesn't have one replacement per pattern.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .replacement_tables([["[name]", "[email]"], ["[nom]", "[courriel]"]])
+    ///     .build(&["Alice", "alice@example.com"])
+    ///     .unwrap();
+    ///
+    /// let haystack = b"Alice <alice@example.com>";
+    /// let mut english = ac.replacer_with_table(0)?;
+    /// let mut out = english.replace(haystack)?.to_vec();
+    /// out.extend_from_slice(english.finish()?);
+    /// assert_eq!(b"[name] <[email]>".to_vec(), out);
+    ///
+    /// let mut french = ac.replacer_with_table(1)?;
+    /// let mut out = french.replace(haystack)?.to_vec();
+    /// out.extend_from_slice(french.finish()?);
+    /// assert_eq!(b"[nom] <[courriel]>".to_vec(), out);
+    ///
+    /// assert!(ac.replacer_with_table(2).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replacer_with_table(
+        &self,
+        table_id: usize,
+    ) -> Result<AhoCorasickReplacer, ReplacerError> {
+        AhoCorasickReplacerBuilder::new()
+            .build_replacer_with_table(self, table_id)
+    }
+
+    /// Instantiate a [`LineReplacer`], which rewrites a stream one line at a
+    /// time, where the pattern with ID `i` is replaced by `replace_with[i]`.
+    ///
+    /// Lines are searched with the given anchored mode, and at most
+    /// `max_line_len` bytes of a line are buffered. Unlike
+    /// [`AhoCorasick::replacer`], this works with every match kind.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the number of replacements is different
+    /// from the number of patterns in the automaton, or if this automaton
+    /// doesn't support the given anchored mode.
+    pub fn line_replacer<I, B>(
+        &self,
+        anchored: Anchored,
+        max_line_len: usize,
+        replace_with: I,
+    ) -> Result<LineReplacer, ReplacerError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
     {
-        AhoCorasickReplacer::new(Arc::clone(&self.aut), self.kind, replace_with)
+        LineReplacer::new(self, anchored, max_line_len, replace_with)
+    }
+
+    /// Instantiate an [`AhoCorasickStreamSplitter`], which splits a stream
+    /// given in chunks into the records between the matches of the patterns
+    /// of this automaton.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasick::replacer`], other than the number of replacements,
+    /// such as when this automaton was built with
+    /// [`MatchKind::LeftmostFirst`].
+    pub fn stream_splitter(
+        &self,
+    ) -> Result<AhoCorasickStreamSplitter, ReplacerError> {
+        AhoCorasickStreamSplitter::new(self)
+    }
+
+    /// Returns the number of replacement tables set by
+    /// [`AhoCorasickBuilder::replacement_tables`].
+    pub fn replacement_tables_len(&self) -> usize {
+        self.replacement_tables.len()
+    }
+
+    /// Returns the replacement table with the given ID, or `None` if there
+    /// is no such table.
+    ///
+    /// A table may be combined with other replacer options by giving it to
+    /// [`AhoCorasick::replacer_with`].
+    pub fn replacement_table(&self, table_id: usize) -> Option<&[Vec<u8>]> {
+        self.replacement_tables.get(table_id).map(|table| &**table)
+    }
+
+    /// Returns a shared handle to the replacement table with the given ID.
+    pub(crate) fn replacement_table_shared(
+        &self,
+        table_id: usize,
+    ) -> Option<Arc<[Vec<u8>]>> {
+        self.replacement_tables.get(table_id).map(Arc::clone)
+    }
+
+    /// Create a profiler that records how often each state of this automaton
+    /// is visited while scanning haystacks.
+    ///
+    /// See [`SearchProfiler`] for how the profile is collected and an
+    /// example.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if this automaton doesn't support unanchored
+    /// searches.
+    pub fn profiler(&self) -> Result<SearchProfiler, MatchError> {
+        enforce_anchored_consistency(self.start_kind, Anchored::No)?;
+        SearchProfiler::new(Arc::clone(&self.aut), self.kind)
+    }
+
+    /// Returns a read-only view of the states and transitions of this
+    /// automaton.
+    ///
+    /// See [`AutomatonGraph`] for more details and an example.
+    pub fn graph(&self) -> AutomatonGraph<'_> {
+        AutomatonGraph::new(self.aut.as_ref().coerce_concrete(self.kind))
+    }
+
+    /// Returns the underlying automaton, for routines that drive it a byte
+    /// at a time.
+    #[cfg(feature = "std")]
+    pub(crate) fn automaton(&self) -> AutomatonImpl<'_> {
+        self.aut.as_ref().coerce_concrete(self.kind)
+    }
+
+    /// Returns the lookahead of each pattern, if any pattern has one.
+    pub(crate) fn lookaheads(&self) -> Option<Arc<[Option<Lookahead>]>> {
+        self.lookaheads.clone()
+    }
+
+    /// Returns true if matches must be bounded by non-word bytes.
+    pub(crate) fn word_boundary(&self) -> bool {
+        self.word_boundary
+    }
+
+    /// Returns the automaton and kind used to construct a replacer.
+    pub(crate) fn replacer_parts(
+        &self,
+    ) -> (Arc<dyn AcAutomaton>, AhoCorasickKind) {
+        (Arc::clone(&self.aut), self.kind)
     }
 
     /// Obtain AhoCorasickAsyncReader wrapping an original AsyncRead source
     /// Reading from this new reader will yield chunks with patterns already replaced
     /// Poll will only return Ok(0) if the poll to the original source also returned 0 bytes.
+    /// All of its buffers are allocated when it is created, so reading never
+    /// allocates.
     ///
     /// # Example: basic usage
     ///
@@ -1904,6 +3532,8 @@ impl AhoCorasick {
              in the automaton",
         );
         enforce_anchored_consistency(self.start_kind, Anchored::No)
+            .and_then(|()| self.enforce_no_overlap_policy())
+            .and_then(|()| self.enforce_no_context())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
         AhoCorasickAsyncReader::new(
@@ -1918,6 +3548,19 @@ impl AhoCorasick {
     /// Obtain AhoCorasickAsyncWriter wrapping an original AsyncWrite sink.
     /// Writing to this new writer will perform the replacements before sending the bytes to your sink
     ///
+    /// Like a buffered writer, a write may report fewer bytes than it was
+    /// given when the sink is not ready, and replaced bytes may be held back
+    /// until the next write, flush or close. All of its buffers are
+    /// allocated when it is created, so writing never allocates.
+    ///
+    /// Vectored writes are processed as if the slices were a single slice,
+    /// so a match may span slices, and their replaced output is sent to the
+    /// sink in as few writes as possible.
+    ///
+    /// Writes may have any size. An empty write is a no-op that returns `0`,
+    /// and writing a stream one byte at a time gives the same output as
+    /// writing it all at once.
+    ///
     /// # Example: basic usage
     ///
     /// ```
@@ -1936,7 +3579,7 @@ impl AhoCorasick {
     ///     loop {
     ///         let bytes_read = haystack.read(&mut buf).await.unwrap();
     ///         if bytes_read > 0 {
-    ///             ac_async_writer.write(&buf[..bytes_read]).await.unwrap();
+    ///             ac_async_writer.write_all(&buf[..bytes_read]).await.unwrap();
     ///         } else {
     ///             ac_async_writer.close().await.unwrap();
     ///             break;
@@ -1962,6 +3605,8 @@ impl AhoCorasick {
              in the automaton",
         );
         enforce_anchored_consistency(self.start_kind, Anchored::No)
+            .and_then(|()| self.enforce_no_overlap_policy())
+            .and_then(|()| self.enforce_no_context())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
         AhoCorasickAsyncWriter::new(
@@ -2003,8 +3648,9 @@ impl AhoCorasick {
         let mut buffer = alloc::vec![b'\0'; buffer_size];
         let ac_writer = self.async_writer(writer, replace_with)?;
 
-        let mut pinned_reader = alloc::boxed::Box::pin(reader);
-        let mut pinned_writer = alloc::boxed::Box::pin(ac_writer);
+        // Pinning on the stack rather than in a box avoids an allocation
+        let (pinned_reader, pinned_writer) = (reader, ac_writer);
+        futures::pin_mut!(pinned_reader, pinned_writer);
         loop {
             let bytes_read =
                 futures::AsyncReadExt::read(&mut pinned_reader, &mut buffer)
@@ -2013,7 +3659,7 @@ impl AhoCorasick {
                 futures::AsyncWriteExt::close(&mut pinned_writer).await?;
                 break;
             } else {
-                futures::AsyncWriteExt::write(
+                futures::AsyncWriteExt::write_all(
                     &mut pinned_writer,
                     &buffer[..bytes_read],
                 )
@@ -2083,6 +3729,36 @@ impl AhoCorasick {
         self.aut.match_kind()
     }
 
+    /// Returns the policy that decides which overlapping matches are
+    /// replaced, if one was set with
+    /// [`AhoCorasickBuilder::overlap_policy`].
+    pub fn overlap_policy(&self) -> Option<OverlapPolicy> {
+        self.overlap_policy
+    }
+
+    /// Returns an error if an overlap policy is set, for the stream
+    /// replacements that don't support one.
+    #[cfg(feature = "std")]
+    fn enforce_no_overlap_policy(&self) -> Result<(), MatchError> {
+        match self.overlap_policy {
+            None => Ok(()),
+            Some(_) => Err(MatchError::unsupported_overlap_policy()),
+        }
+    }
+
+    /// Returns an error if word boundaries are enabled or a pattern has a
+    /// lookahead, for the stream searches and replacements that don't honor
+    /// them.
+    fn enforce_no_context(&self) -> Result<(), MatchError> {
+        if self.word_boundary {
+            return Err(MatchError::unsupported_word_boundary());
+        }
+        if self.lookaheads.is_some() {
+            return Err(MatchError::unsupported_lookahead());
+        }
+        Ok(())
+    }
+
     /// Returns the length of the shortest pattern matched by this automaton.
     ///
     /// # Examples
@@ -2147,8 +3823,219 @@ impl AhoCorasick {
         self.aut.patterns_len()
     }
 
-    /// Returns the approximate total amount of heap used by this automaton, in
-    /// units of bytes.
+    /// Returns the bytes of the pattern with the given ID.
+    ///
+    /// This returns `None` if the given ID does not correspond to a pattern
+    /// in this automaton, or if the automaton does not store its patterns.
+    /// Patterns are only stored when [`AhoCorasickBuilder::store_patterns`]
+    /// is enabled or a verifier is set with [`AhoCorasickBuilder::verify`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .store_patterns(true)
+    ///     .build(&["foo", "bar"])
+    ///     .unwrap();
+    /// assert_eq!(Some(&b"bar"[..]), ac.pattern(PatternID::must(1)));
+    /// assert_eq!(None, ac.pattern(PatternID::must(2)));
+    ///
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// assert_eq!(None, ac.pattern(PatternID::must(1)));
+    /// ```
+    pub fn pattern(&self, pid: PatternID) -> Option<&[u8]> {
+        let patterns = self.patterns.as_ref()?;
+        patterns.get(pid.as_usize()).map(|p| p.as_slice())
+    }
+
+    /// Returns the IDs of the patterns that a match of the pattern with the
+    /// given ID originates from.
+    ///
+    /// These are the patterns that are identical to the given pattern once
+    /// folded by ASCII case insensitivity, including itself, in ascending
+    /// order. Without case insensitivity, these are the patterns that are
+    /// byte-for-byte identical to it.
+    ///
+    /// This returns `None` if the given ID does not correspond to a pattern
+    /// in this automaton, or if
+    /// [`AhoCorasickBuilder::track_provenance`] wasn't enabled.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, DuplicatePolicy, PatternID};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .duplicate_policy(DuplicatePolicy::Lowest)
+    ///     .track_provenance(true)
+    ///     .build(&["Apple", "pear", "APPLE"])
+    ///     .unwrap();
+    /// let origins: Vec<Vec<PatternID>> = ac
+    ///     .find_iter("apple, PEAR")
+    ///     .map(|m| ac.provenance(m.pattern()).unwrap().to_vec())
+    ///     .collect();
+    /// assert_eq!(vec![
+    ///     vec![PatternID::must(0), PatternID::must(2)],
+    ///     vec![PatternID::must(1)],
+    /// ], origins);
+    ///
+    /// let ac = AhoCorasick::new(&["Apple", "pear"]).unwrap();
+    /// assert_eq!(None, ac.provenance(PatternID::must(0)));
+    /// ```
+    pub fn provenance(&self, pid: PatternID) -> Option<&[PatternID]> {
+        self.provenance.as_ref()?.origins(pid)
+    }
+
+    /// Disable the pattern with the given ID, so that none of its matches
+    /// are reported.
+    ///
+    /// A disabled pattern is treated as if it wasn't in the automaton, so
+    /// other patterns may match where it would have matched instead. This
+    /// applies to every search and replacement of this searcher, including
+    /// stream searches, stream replacements and
+    /// [stream finders](AhoCorasick::stream_finder) created afterwards.
+    /// Replacers built from this searcher afterwards start out with the same
+    /// patterns disabled (see [`AhoCorasickReplacer::set_enabled`]), except
+    /// that a replacer with [`MatchKind::LeftmostLongest`] semantics can't
+    /// be built while a pattern is disabled. Clones of this searcher are
+    /// unaffected.
+    ///
+    /// The one exception is an
+    /// [earliest stream search](AhoCorasick::try_stream_find_iter_earliest)
+    /// with leftmost semantics, which has no leftmost match to fall back
+    /// on. It restarts after a match of a disabled pattern as it does after
+    /// any other match, so it may miss matches that overlap with it.
+    ///
+    /// This makes it possible to drop a pattern without rebuilding the
+    /// automaton. The cost is that every match has to be checked, and that
+    /// finding the match of another pattern where a disabled one matched
+    /// compares the stored patterns with the haystack, so searches are
+    /// slower while any pattern is disabled.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if this automaton doesn't store its patterns.
+    /// Patterns are stored when [`AhoCorasickBuilder::store_patterns`] is
+    /// enabled. No pattern is disabled when an error is returned.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the given ID doesn't correspond to a pattern in this
+    /// automaton.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Match, MatchKind, PatternID};
+    ///
+    /// let mut ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostFirst)
+    ///     .store_patterns(true)
+    ///     .build(&["Samwise", "Sam", "wise"])
+    ///     .unwrap();
+    /// ac.disable_pattern(PatternID::must(0))?;
+    /// assert!(!ac.is_pattern_enabled(PatternID::must(0)));
+    ///
+    /// let matches: Vec<Match> = ac.find_iter("Samwise").collect();
+    /// assert_eq!(vec![Match::must(1, 0..3), Match::must(2, 3..7)], matches);
+    /// assert_eq!("Sir wise", ac.replace_all("Samwise", &["", "Sir ", "wise"]));
+    ///
+    /// ac.enable_pattern(PatternID::must(0));
+    /// assert_eq!(Some(Match::must(0, 0..7)), ac.find("Samwise"));
+    ///
+    /// // Without the stored patterns, disabling a pattern fails.
+    /// let mut ac = AhoCorasick::new(&["Samwise", "Sam"]).unwrap();
+    /// assert!(ac.disable_pattern(PatternID::must(0)).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn disable_pattern(
+        &mut self,
+        pid: PatternID,
+    ) -> Result<(), MatchError> {
+        self.check_pattern_id(pid);
+        if self.patterns.is_none() {
+            return Err(MatchError::unsupported_disabled_pattern());
+        }
+        self.set_pattern_enabled(pid, false);
+        Ok(())
+    }
+
+    /// Enable the pattern with the given ID again, after it was disabled
+    /// with [`AhoCorasick::disable_pattern`].
+    ///
+    /// Every pattern is enabled by default, and enabling a pattern that is
+    /// already enabled has no effect. Once every pattern is enabled again,
+    /// searches are as fast as they were before any pattern was disabled.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the given ID doesn't correspond to a pattern in this
+    /// automaton.
+    pub fn enable_pattern(&mut self, pid: PatternID) {
+        self.check_pattern_id(pid);
+        self.set_pattern_enabled(pid, true);
+    }
+
+    /// Returns true if and only if the pattern with the given ID is enabled.
+    ///
+    /// This returns false for a pattern disabled with
+    /// [`AhoCorasick::disable_pattern`], and for an ID that doesn't
+    /// correspond to a pattern in this automaton.
+    pub fn is_pattern_enabled(&self, pid: PatternID) -> bool {
+        pid.as_usize() < self.patterns_len()
+            && !self.disabled.as_ref().map_or(false, |d| d[pid])
+    }
+
+    /// Panics if the given ID doesn't correspond to a pattern in this
+    /// automaton.
+    fn check_pattern_id(&self, pid: PatternID) {
+        assert!(
+            pid.as_usize() < self.patterns_len(),
+            "pattern ID {} is invalid for an automaton with {} patterns",
+            pid.as_usize(),
+            self.patterns_len(),
+        );
+    }
+
+    /// Enable or disable the given pattern. The caller must have checked
+    /// that the pattern ID is valid, and that the patterns are stored when
+    /// disabling a pattern.
+    fn set_pattern_enabled(&mut self, pid: PatternID, yes: bool) {
+        if yes && self.disabled.is_none() {
+            return;
+        }
+        let len = self.patterns_len();
+        let disabled = Arc::make_mut(
+            self.disabled
+                .get_or_insert_with(|| Arc::new(alloc::vec![false; len])),
+        );
+        disabled[pid] = !yes;
+        if disabled.iter().all(|&d| !d) {
+            self.disabled = None;
+        }
+    }
+
+    /// Returns whether each pattern is disabled, indexed by pattern ID, or
+    /// an empty vector if none of them are.
+    pub(crate) fn disabled_patterns(&self) -> Vec<bool> {
+        self.disabled.as_ref().map_or(Vec::new(), |d| d.to_vec())
+    }
+
+    /// Returns whether each pattern is disabled, indexed by pattern ID, or
+    /// `None` if none of them are.
+    fn disabled(&self) -> Option<&[bool]> {
+        self.disabled.as_deref().map(|d| d.as_slice())
+    }
+
+    /// Returns the approximate total amount of heap used by this automaton, in
+    /// units of bytes.
     ///
     /// # Examples
     ///
@@ -2200,7 +4087,285 @@ impl AhoCorasick {
     /// assert_eq!(11_136, ac.memory_usage());
     /// ```
     pub fn memory_usage(&self) -> usize {
-        self.aut.memory_usage()
+        let stored = self.patterns.as_ref().map_or(0, |patterns| {
+            patterns.iter().map(|p| p.len()).sum::<usize>()
+        });
+        let provenance =
+            self.provenance.as_ref().map_or(0, |p| p.memory_usage());
+        self.aut.memory_usage() + stored + provenance
+    }
+}
+
+/// Routines for searches whose matches are verified against the stored
+/// patterns. See [`Input::exact_case`] and [`Input::verify`].
+impl AhoCorasick {
+    /// Returns true if the matches reported by the automaton for the given
+    /// search have to be verified against their pattern.
+    fn needs_verification(&self, input: &Input<'_>) -> bool {
+        self.filters_matches()
+            || input.get_pattern_set().is_some()
+            || (input.get_exact_case() && self.ascii_case_insensitive)
+            || (input.get_verify()
+                && (self.verifier.is_some() || self.lookaheads.is_some()))
+    }
+
+    /// Returns true if some matches reported by the automaton are rejected
+    /// by every search, whatever its configuration, because their pattern
+    /// is disabled or they aren't bounded by non-word bytes.
+    fn filters_matches(&self) -> bool {
+        self.disabled.is_some() || self.word_boundary
+    }
+
+    /// Returns true if some matches reported by the automaton aren't
+    /// replaced, because they're rejected by every search or by their
+    /// lookahead.
+    fn verifies_replacements(&self) -> bool {
+        self.filters_matches() || self.lookaheads.is_some()
+    }
+
+    /// Returns an iterator of the non-overlapping matches that are replaced
+    /// in the given haystack.
+    ///
+    /// Replacements honor lookaheads, but not the verifier, so that they
+    /// replace the same matches as replacers do.
+    fn try_find_replaced_iter<'a, 'h>(
+        &'a self,
+        haystack: &'h [u8],
+    ) -> Result<FindIter<'a, 'h>, MatchError> {
+        let input = Input::new(haystack);
+        if !self.verifies_replacements() {
+            return self.try_find_iter(input.verify(false));
+        }
+        let mut verification = self.verification_with(&input, None)?;
+        verification.verifier = None;
+        self.verified_find_iter(input, verification)
+    }
+
+    /// Returns how the matches of the given search have to be verified, if
+    /// at all. This returns an error if they have to be verified, but this
+    /// automaton doesn't store its patterns.
+    fn verification(
+        &self,
+        input: &Input<'_>,
+    ) -> Result<Option<Verification<'_>>, MatchError> {
+        if !self.needs_verification(input) {
+            return Ok(None);
+        }
+        self.verification_with(input, None).map(Some)
+    }
+
+    /// Returns how the matches of the given search have to be verified,
+    /// checking that they're aligned to the code units of the given UTF-16
+    /// encoding, if any.
+    fn verification_with(
+        &self,
+        input: &Input<'_>,
+        utf16: Option<Utf16Endian>,
+    ) -> Result<Verification<'_>, MatchError> {
+        // Disabled patterns, a verifier, lookaheads, word boundaries or a
+        // UTF-16 encoding are only ever set along with the stored patterns, so this can only
+        // fail for an exact case search or a search restricted to a set of
+        // patterns.
+        let patterns = self.patterns.as_deref().ok_or_else(|| {
+            if input.get_pattern_set().is_some() {
+                MatchError::unsupported_pattern_set()
+            } else {
+                MatchError::unsupported_exact_case()
+            }
+        })?;
+        Ok(Verification {
+            patterns,
+            translation_table: self.translation_table.as_deref(),
+            exact_case: input.get_exact_case() && self.ascii_case_insensitive,
+            verifier: self.verifier.as_ref().filter(|_| input.get_verify()),
+            lookaheads: self
+                .lookaheads
+                .as_deref()
+                .filter(|_| input.get_verify()),
+            disabled: self.disabled(),
+            // The code units of a UTF-16 haystack aren't word bytes.
+            word_boundary: self.word_boundary && utf16.is_none(),
+            utf16,
+        })
+    }
+
+    /// Executes a non-overlapping search that only reports matches accepted
+    /// by the given verification. The caller must have checked that the
+    /// anchored mode of the input is supported.
+    ///
+    /// This reports the match that the automaton would report if the
+    /// patterns didn't match wherever their match is rejected.
+    fn try_find_verified(
+        &self,
+        input: Input<'_>,
+        verification: Verification<'_>,
+    ) -> Result<Option<Match>, MatchError> {
+        // An "earliest" match may not be leftmost, which would break the
+        // reasoning below.
+        let mut input = input.earliest(false);
+        let anchored = input.get_anchored().is_anchored();
+        if self.match_kind() == MatchKind::Standard && !anchored {
+            // With standard semantics, the overlapping matches are reported
+            // in order of their end, and then from longest to shortest. So
+            // the first one accepted is the one a non-overlapping search
+            // would report.
+            let mut state = OverlappingState::start();
+            loop {
+                self.aut.try_find_overlapping(&input, &mut state)?;
+                let m = state.get_match();
+                if m.map_or(true, |m| verification.accepts(&input, m)) {
+                    return Ok(m);
+                }
+            }
+        }
+        loop {
+            let rejected = match self.aut.try_find(&input)? {
+                None => return Ok(None),
+                Some(m) if verification.accepts(&input, m) => {
+                    return Ok(Some(m));
+                }
+                Some(m) => m,
+            };
+            // No pattern matches before the start of the rejected match,
+            // but other patterns may match at the same position and be
+            // accepted. Otherwise, the next match starts after it, unless
+            // the search is anchored.
+            let at = rejected.start();
+            let m = self.verified_match_at(&input, verification, at);
+            if m.is_some() || anchored {
+                return Ok(m);
+            }
+            input.set_start(at + 1);
+        }
+    }
+
+    /// Returns an iterator of the non-overlapping matches accepted by the
+    /// given verification. The caller must have checked that the anchored
+    /// mode of the input is supported.
+    fn verified_find_iter<'a, 'h>(
+        &'a self,
+        input: Input<'h>,
+        verification: Verification<'a>,
+    ) -> Result<FindIter<'a, 'h>, MatchError> {
+        // This ensures that no search with this configuration can fail, just
+        // like the iterator of the automaton does.
+        self.aut.start_state(input.get_anchored())?;
+        Ok(FindIter(FindIterImp::Verified {
+            ac: self,
+            verification,
+            input,
+            last_match_end: None,
+        }))
+    }
+
+    /// Executes a non-overlapping search of a UTF-16 haystack, whose
+    /// patterns are stored encoded with the given byte order.
+    pub(crate) fn try_find_utf16(
+        &self,
+        input: Input<'_>,
+        endian: Utf16Endian,
+    ) -> Result<Option<Match>, MatchError> {
+        enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
+        let verification = self.verification_with(&input, Some(endian))?;
+        self.try_find_verified(input, verification)
+    }
+
+    /// Returns an iterator of the non-overlapping matches in a UTF-16
+    /// haystack, whose patterns are stored encoded with the given byte
+    /// order.
+    pub(crate) fn try_find_iter_utf16<'a, 'h>(
+        &'a self,
+        input: Input<'h>,
+        endian: Utf16Endian,
+    ) -> Result<FindIter<'a, 'h>, MatchError> {
+        enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
+        let verification = self.verification_with(&input, Some(endian))?;
+        self.verified_find_iter(input, verification)
+    }
+
+    /// Returns true if a match of the given pattern on the given bytes of a
+    /// UTF-16 stream is accepted. The caller must have checked that the
+    /// match starts on a code unit boundary.
+    #[cfg(feature = "std")]
+    pub(crate) fn accepts_utf16(
+        &self,
+        endian: Utf16Endian,
+        pid: PatternID,
+        bytes: &[u8],
+    ) -> bool {
+        let input = Input::new(bytes);
+        self.verification_with(&input, Some(endian))
+            .expect("a UTF-16 searcher always stores its patterns")
+            .accepts(&input, Match::new(pid, 0..bytes.len()))
+    }
+
+    /// Returns the match that this automaton would report at the given
+    /// position of the input, among the matches accepted by the given
+    /// verification.
+    ///
+    /// This checks every pattern, so it's only used once the automaton has
+    /// reported a match at this position that was rejected.
+    fn verified_match_at(
+        &self,
+        input: &Input<'_>,
+        verification: Verification<'_>,
+        at: usize,
+    ) -> Option<Match> {
+        let rest = &input.haystack()[at..input.end()];
+        let mut best: Option<Match> = None;
+        let patterns = verification.patterns;
+        for (pid, p) in PatternID::iter(patterns.len()).zip(patterns.iter()) {
+            let matches = rest.get(..p.len()).map_or(false, |bytes| {
+                let bytes = verification.translate(bytes);
+                if self.ascii_case_insensitive {
+                    bytes.eq_ignore_ascii_case(p)
+                } else {
+                    bytes == &p[..]
+                }
+            });
+            let m = Match::new(pid, at..at + p.len());
+            if !matches || !verification.accepts(input, m) {
+                continue;
+            }
+            // Standard semantics report the match that ends first, while
+            // leftmost-first semantics report the pattern given first.
+            let better = match (best, self.match_kind()) {
+                (None, _) => true,
+                (Some(b), MatchKind::Standard) => m.len() < b.len(),
+                (Some(b), MatchKind::LeftmostLongest) => m.len() > b.len(),
+                (Some(_), _) => false,
+            };
+            if better {
+                best = Some(m);
+            }
+        }
+        best
+    }
+}
+
+/// Persistence of automata, for the `cache` module.
+#[cfg(feature = "std")]
+impl AhoCorasick {
+    /// Write the kind and the automaton of this searcher to the given
+    /// writer, or return false without writing anything if its kind can't
+    /// be persisted.
+    ///
+    /// Only the contiguous NFA and the DFA can be persisted. A
+    /// noncontiguous NFA is about as fast to build as it would be to read.
+    pub(crate) fn write_automaton(&self, w: &mut Writer) -> bool {
+        let aut = (*self.aut).as_any();
+        match self.kind {
+            AhoCorasickKind::NoncontiguousNFA => return false,
+            AhoCorasickKind::ContiguousNFA => {
+                w.u8(1);
+                aut.downcast_ref::<contiguous::NFA>().unwrap().write_to(w);
+            }
+            AhoCorasickKind::DFA => {
+                w.u8(2);
+                aut.downcast_ref::<dfa::DFA>().unwrap().write_to(w);
+            }
+        }
+        true
     }
 }
 
@@ -2225,14 +4390,76 @@ impl core::fmt::Debug for AhoCorasick {
 ///
 /// The lifetime `'h` refers to the lifetime of the haystack being searched.
 #[derive(Debug)]
-pub struct FindIter<'a, 'h>(automaton::FindIter<'a, 'h, Arc<dyn AcAutomaton>>);
+pub struct FindIter<'a, 'h>(FindIterImp<'a, 'h>);
+
+#[derive(Debug)]
+enum FindIterImp<'a, 'h> {
+    Automaton(automaton::FindIter<'a, 'h, Arc<dyn AcAutomaton>>),
+    /// The matches of a search that are verified against the stored
+    /// patterns of the searcher.
+    Verified {
+        ac: &'a AhoCorasick,
+        verification: Verification<'a>,
+        input: Input<'h>,
+        last_match_end: Option<usize>,
+    },
+}
 
 impl<'a, 'h> Iterator for FindIter<'a, 'h> {
     type Item = Match;
 
     #[inline]
     fn next(&mut self) -> Option<Match> {
-        self.0.next()
+        let (ac, verification, input, last_match_end) = match self.0 {
+            FindIterImp::Automaton(ref mut it) => return it.next(),
+            FindIterImp::Verified {
+                ac,
+                verification,
+                ref mut input,
+                ref mut last_match_end,
+            } => (ac, verification, input, last_match_end),
+        };
+        // The unwraps are OK because we check at iterator construction time
+        // that no search with the same configuration can fail.
+        let search = |input: &Input<'_>| {
+            ac.try_find_verified(input.clone(), verification)
+                .expect("already checked that no match error can occur")
+        };
+        let mut m = search(input)?;
+        // As with the iterator of the automaton, an empty match may not
+        // overlap with the end of the previous match.
+        if m.is_empty() && Some(m.end()) == *last_match_end {
+            input.set_start(input.start().checked_add(1).unwrap());
+            m = search(input)?;
+        }
+        input.set_start(m.end());
+        *last_match_end = Some(m.end());
+        Some(m)
+    }
+}
+
+/// An iterator of non-overlapping matches in compact form.
+///
+/// This iterator yields [`MatchU32`] values. It is constructed via the
+/// [`AhoCorasick::try_find_iter_u32`] method, which guarantees that every
+/// match reported fits into a `MatchU32`.
+///
+/// The lifetime `'a` refers to the lifetime of the `AhoCorasick` automaton.
+///
+/// The lifetime `'h` refers to the lifetime of the haystack being searched.
+#[derive(Debug)]
+pub struct FindIterU32<'a, 'h>(FindIter<'a, 'h>);
+
+impl<'a, 'h> Iterator for FindIterU32<'a, 'h> {
+    type Item = MatchU32;
+
+    #[inline]
+    fn next(&mut self) -> Option<MatchU32> {
+        let mat = self.0.next()?;
+        Some(
+            MatchU32::from_match(mat)
+                .expect("already checked that every match fits"),
+        )
     }
 }
 
@@ -2248,16 +4475,23 @@ impl<'a, 'h> Iterator for FindIter<'a, 'h> {
 ///
 /// The lifetime `'h` refers to the lifetime of the haystack being searched.
 #[derive(Debug)]
-pub struct FindOverlappingIter<'a, 'h>(
-    automaton::FindOverlappingIter<'a, 'h, Arc<dyn AcAutomaton>>,
-);
+pub struct FindOverlappingIter<'a, 'h> {
+    it: automaton::FindOverlappingIter<'a, 'h, Arc<dyn AcAutomaton>>,
+    /// How matches are verified, along with the input whose haystack they
+    /// are found in, for a search whose matches have to be verified.
+    verification: Option<(Verification<'a>, Input<'h>)>,
+}
 
 impl<'a, 'h> Iterator for FindOverlappingIter<'a, 'h> {
     type Item = Match;
 
     #[inline]
     fn next(&mut self) -> Option<Match> {
-        self.0.next()
+        let (verification, input) = match self.verification {
+            None => return self.it.next(),
+            Some((verification, ref input)) => (verification, input),
+        };
+        self.it.by_ref().find(|&m| verification.accepts(input, m))
     }
 }
 
@@ -2291,6 +4525,246 @@ impl<'a, R: std::io::Read> Iterator for StreamFindIter<'a, R> {
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a, R: std::io::Read> StreamFindIter<'a, R> {
+    /// Consume the rest of the stream and return an estimate of the `k`
+    /// patterns that matched most often, without storing every match.
+    ///
+    /// See [`TopPatterns`] for how counts are estimated.
+    ///
+    /// # Errors
+    ///
+    /// This returns the first error reported by the iterator, in which case
+    /// the matches counted so far are lost.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let ac = AhoCorasick::new(&["GET", "POST", "DELETE"]).unwrap();
+    /// let log = "GET /a\nPOST /b\nGET /c\nDELETE /d\nGET /e\n";
+    ///
+    /// let top = ac.stream_find_iter(log.as_bytes()).top_patterns(2)?;
+    /// assert_eq!(5, top.total());
+    /// assert_eq!(Some((PatternID::must(0), 3)), top.top().first().copied());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn top_patterns(
+        self,
+        k: usize,
+    ) -> Result<TopPatterns, std::io::Error> {
+        let mut top = TopPatterns::new(k);
+        for result in self {
+            top.add(&result?);
+        }
+        Ok(top)
+    }
+
+    /// Track the decayed rate of matches of each pattern as the stream is
+    /// searched, in which the weight of a match halves every `half_life`
+    /// bytes.
+    ///
+    /// The returned iterator yields the same matches as this one, and
+    /// updates the rates with each match before yielding it. The rates can
+    /// be inspected between matches with [`StreamRateIter::rates`]. See
+    /// [`MatchRates`] for how rates are computed.
+    ///
+    /// # Panics
+    ///
+    /// This panics when `half_life` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let ac = AhoCorasick::new(&["ok", "fail"]).unwrap();
+    /// let log = format!("{}{}", "ok ".repeat(100), "fail ".repeat(20));
+    ///
+    /// let mut it = ac.stream_find_iter(log.as_bytes()).track_rates(50);
+    /// let mut spikes = 0;
+    /// while let Some(m) = it.next() {
+    ///     let m = m?;
+    ///     let rates = it.rates();
+    ///     if rates.rate(m.pattern()) > rates.rate(PatternID::must(0)) {
+    ///         spikes += 1;
+    ///     }
+    /// }
+    /// assert!(spikes > 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn track_rates(self, half_life: usize) -> StreamRateIter<'a, R> {
+        StreamRateIter { it: self, rates: MatchRates::new(half_life) }
+    }
+}
+
+/// An iterator that reports Aho-Corasick matches in a stream, and tracks the
+/// decayed rate of matches of each pattern as it goes.
+///
+/// This iterator yields elements of type `Result<Match, std::io::Error>`,
+/// exactly like [`StreamFindIter`].
+///
+/// This iterator is constructed via the [`StreamFindIter::track_rates`]
+/// method.
+///
+/// The type variable `R` refers to the `io::Read` stream that is being read
+/// from.
+///
+/// The lifetime `'a` refers to the lifetime of the corresponding
+/// [`AhoCorasick`] searcher.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamRateIter<'a, R> {
+    it: StreamFindIter<'a, R>,
+    rates: MatchRates,
+}
+
+#[cfg(feature = "std")]
+impl<'a, R> StreamRateIter<'a, R> {
+    /// Returns the rates as of the last match yielded.
+    pub fn rates(&self) -> &MatchRates {
+        &self.rates
+    }
+
+    /// Returns the rates as of the last match yielded, which may be used to
+    /// move the current offset forward.
+    pub fn rates_mut(&mut self) -> &mut MatchRates {
+        &mut self.rates
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, R: std::io::Read> Iterator for StreamRateIter<'a, R> {
+    type Item = Result<Match, std::io::Error>;
+
+    fn next(&mut self) -> Option<Result<Match, std::io::Error>> {
+        let result = self.it.next()?;
+        if let Ok(ref m) = result {
+            self.rates.add(m);
+        }
+        Some(result)
+    }
+}
+
+/// A finder that reports the matches in a stream given to it in chunks.
+///
+/// Each chunk is given to [`AhoCorasickStreamFinder::find`], which returns
+/// the matches that end in it. Matches are reported with offsets into the
+/// whole stream, so a match may start in an earlier chunk. The matches are
+/// the same as the ones reported by [`AhoCorasick::try_stream_find_iter`]
+/// for the concatenation of the chunks, no matter how the stream is split.
+///
+/// Since only [`MatchKind::Standard`] semantics are supported, a match is
+/// reported as soon as its last byte is seen, and no bytes of the stream
+/// are ever held back. The finder only keeps the state of the automaton and
+/// its position in the stream between chunks.
+///
+/// This finder is constructed via the [`AhoCorasick::stream_finder`]
+/// method.
+#[derive(Clone, Debug)]
+pub struct AhoCorasickStreamFinder {
+    aut: Arc<dyn AcAutomaton>,
+    kind: AhoCorasickKind,
+    /// Whether each pattern is disabled, as of when the finder was created.
+    disabled: Option<Arc<Vec<bool>>>,
+    /// The unanchored starting state of the automaton.
+    start: StateID,
+    /// The state of the automaton after the bytes given so far.
+    sid: StateID,
+    /// The number of bytes given so far.
+    position: usize,
+    /// The matches of the last chunk.
+    matches: Vec<Match>,
+}
+
+impl AhoCorasickStreamFinder {
+    /// Give the next chunk of the stream to the finder, returning the
+    /// matches that end in it, in order.
+    ///
+    /// Chunks may have any size, and an empty chunk has no matches.
+    pub fn find(&mut self, chunk: &[u8]) -> &[Match] {
+        self.matches.clear();
+        let aut = self.aut.as_ref().coerce_concrete(self.kind);
+        for &byte in chunk.iter() {
+            self.sid = aut.next_state(Anchored::No, self.sid, byte);
+            self.position += 1;
+            if !aut.is_match(self.sid) {
+                continue;
+            }
+            // As with the stream iterators, the first enabled pattern is
+            // reported, and if there is none, the search carries on from
+            // this state.
+            let disabled = self.disabled.as_deref();
+            let enabled = (0..aut.match_len(self.sid))
+                .map(|i| aut.match_pattern(self.sid, i))
+                .find(|&pid| disabled.map_or(true, |d| !d[pid]));
+            if let Some(pid) = enabled {
+                let start = self.position - aut.pattern_len(pid);
+                self.matches.push(Match::new(pid, start..self.position));
+                self.sid = self.start;
+            }
+        }
+        &self.matches
+    }
+
+    /// End the stream, returning the matches that end with it.
+    ///
+    /// Every match is reported as soon as its last byte is seen, so there
+    /// are never any matches left and this always returns an empty slice.
+    /// It's provided so that the finder is used like the
+    /// [`AhoCorasickReplacer`], and it resets the finder, so that the chunks
+    /// given next start a new stream.
+    pub fn finish(&mut self) -> &[Match] {
+        self.reset();
+        &self.matches
+    }
+
+    /// Returns the number of bytes of the stream given to the finder so far,
+    /// which is the offset of the first byte of the next chunk.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Reset this finder, so that the chunks given next start a new stream.
+    pub fn reset(&mut self) {
+        self.sid = self.start;
+        self.position = 0;
+        self.matches.clear();
+    }
+}
+
+/// An iterator that reports Aho-Corasick matches in a stream along with the
+/// line and column at which each match starts.
+///
+/// This iterator yields elements of type
+/// `Result<(Match, LineColumn), std::io::Error>`, where an error is reported
+/// if there was a problem reading from the underlying stream. The iterator
+/// terminates only when the underlying stream reaches `EOF`.
+///
+/// This iterator is constructed via the
+/// [`AhoCorasick::try_stream_find_iter_line_column`] method.
+///
+/// The type variable `R` refers to the `io::Read` stream that is being read
+/// from.
+///
+/// The lifetime `'a` refers to the lifetime of the corresponding
+/// [`AhoCorasick`] searcher.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamLineColumnIter<'a, R>(
+    automaton::StreamLineColumnIter<'a, Arc<dyn AcAutomaton>, R>,
+);
+
+#[cfg(feature = "std")]
+impl<'a, R: std::io::Read> Iterator for StreamLineColumnIter<'a, R> {
+    type Item = Result<(Match, LineColumn), std::io::Error>;
+
+    fn next(&mut self) -> Option<Result<(Match, LineColumn), std::io::Error>> {
+        self.0.next()
+    }
+}
+
 /// A builder for configuring an Aho-Corasick automaton.
 ///
 /// # Quick advice
@@ -2319,9 +4793,22 @@ pub struct AhoCorasickBuilder {
     dfa: dfa::Builder,
     kind: Option<AhoCorasickKind>,
     start_kind: StartKind,
+    store_patterns: bool,
+    track_provenance: bool,
+    verifier: Option<Verifier>,
+    lookaheads: Vec<Option<Lookahead>>,
+    word_boundary: bool,
+    replacement_tables: Vec<Arc<[Vec<u8>]>>,
+    overlap_policy: Option<OverlapPolicy>,
 }
 
 impl AhoCorasickBuilder {
+    /// The version of the encoding of `options_key`, which is incremented
+    /// whenever an option that affects automata is added or its encoding
+    /// changes.
+    #[cfg(feature = "std")]
+    pub(crate) const OPTIONS_KEY_VERSION: u32 = 1;
+
     /// Create a new builder for configuring an Aho-Corasick automaton.
     ///
     /// The builder provides a way to configure a number of things, including
@@ -2335,6 +4822,12 @@ impl AhoCorasickBuilder {
     ///
     /// A builder may be reused to create more automatons.
     ///
+    /// The pattern with ID `i` is always the `i`th pattern given, so pattern
+    /// IDs are dense. An automaton is never modified once built, so patterns
+    /// are added by building another automaton. Appending them to the
+    /// patterns given before keeps every previously assigned pattern ID
+    /// stable, and there is no need to reserve IDs for them in advance.
+    ///
     /// # Examples
     ///
     /// Basic usage:
@@ -2354,6 +4847,168 @@ impl AhoCorasickBuilder {
         I: IntoIterator<Item = P>,
         P: AsRef<[u8]>,
     {
+        if !self.needs_patterns() {
+            return self.build_automaton(patterns);
+        }
+        let patterns: Vec<Vec<u8>> =
+            patterns.into_iter().map(|p| p.as_ref().to_vec()).collect();
+        let ac = self.build_automaton(&patterns)?;
+        Ok(self.attach_patterns(ac, patterns))
+    }
+
+    /// Returns true if a searcher built with this builder needs its
+    /// patterns once its automaton is built, either to store them or to
+    /// track their provenance.
+    fn needs_patterns(&self) -> bool {
+        self.store_patterns
+            || self.track_provenance
+            || self.verifier.is_some()
+            || self.lookaheads.iter().any(|l| l.is_some())
+            || self.word_boundary
+    }
+
+    /// Store the given patterns in the given searcher, along with the
+    /// verifier and the lookaheads of this builder, and track their
+    /// provenance if enabled. Matches that must be bounded by non-word bytes
+    /// are verified, so the patterns are stored for them too.
+    fn attach_patterns(
+        &self,
+        mut ac: AhoCorasick,
+        patterns: Vec<Vec<u8>>,
+    ) -> AhoCorasick {
+        if self.track_provenance {
+            ac.provenance = Some(Arc::new(Provenance::new(
+                &patterns,
+                self.nfa_noncontiguous.get_ascii_case_insensitive(),
+            )));
+        }
+        let has_lookaheads = self.lookaheads.iter().any(|l| l.is_some());
+        if !self.store_patterns
+            && self.verifier.is_none()
+            && !has_lookaheads
+            && !self.word_boundary
+        {
+            return ac;
+        }
+        if has_lookaheads {
+            let mut lookaheads = self.lookaheads.clone();
+            lookaheads.resize(patterns.len(), None);
+            // A lookahead set for a pattern the automaton doesn't have is
+            // ignored.
+            if lookaheads.iter().any(|l| l.is_some()) {
+                ac.lookaheads = Some(Arc::from(lookaheads));
+            }
+        }
+        ac.patterns = Some(Arc::from(patterns));
+        ac.verifier = self.verifier.clone();
+        ac
+    }
+
+    /// Read a searcher written by `AhoCorasick::write_automaton`, as if it
+    /// were built from the given patterns with this builder.
+    ///
+    /// This returns `None` if the input is invalid, or if it doesn't hold
+    /// an automaton of the given patterns. The caller is responsible for
+    /// the automaton having been built with the same options.
+    #[cfg(feature = "std")]
+    pub(crate) fn read_automaton(
+        &self,
+        patterns: &[Vec<u8>],
+        r: &mut Reader<'_>,
+    ) -> Option<AhoCorasick> {
+        let prefilter = self.nfa_noncontiguous.build_prefilter(patterns);
+        let (aut, kind): (Arc<dyn AcAutomaton>, AhoCorasickKind) =
+            match r.u8()? {
+                1 => (
+                    Arc::new(contiguous::NFA::read_from(r, prefilter)?),
+                    AhoCorasickKind::ContiguousNFA,
+                ),
+                2 => (
+                    Arc::new(dfa::DFA::read_from(r, prefilter)?),
+                    AhoCorasickKind::DFA,
+                ),
+                _ => return None,
+            };
+        if self.kind.map_or(false, |k| k != kind)
+            || aut.patterns_len() != patterns.len()
+            || aut.min_pattern_len()
+                != patterns.iter().map(|p| p.len()).min().unwrap_or(0)
+            || aut.max_pattern_len()
+                != patterns.iter().map(|p| p.len()).max().unwrap_or(0)
+            || patterns.iter().enumerate().any(|(i, p)| {
+                aut.pattern_len(PatternID::new_unchecked(i)) != p.len()
+            })
+        {
+            return None;
+        }
+        let ac = self.finish_automaton(aut, kind);
+        if self.needs_patterns() {
+            return Some(self.attach_patterns(ac, patterns.to_vec()));
+        }
+        Some(ac)
+    }
+
+    /// Returns a key identifying the options of this builder that affect
+    /// the automaton it builds, such that builders with the same key build
+    /// the same automaton from the same patterns.
+    ///
+    /// The options that only configure the searcher wrapping the automaton,
+    /// such as the verifier, the lookaheads or the replacement tables, are
+    /// left out. A searcher built with the same key is given those of
+    /// another builder with `AhoCorasickBuilder::reuse_automaton`.
+    ///
+    /// The key is an explicit encoding of the options, starting with
+    /// `OPTIONS_KEY_VERSION`, so that it only changes when that version
+    /// does. Keys are persisted in the names and headers of the files of an
+    /// `AutomatonCache`.
+    #[cfg(feature = "std")]
+    pub(crate) fn options_key(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u32(AhoCorasickBuilder::OPTIONS_KEY_VERSION);
+        w.u8(match self.kind {
+            None => 0,
+            Some(AhoCorasickKind::NoncontiguousNFA) => 1,
+            Some(AhoCorasickKind::ContiguousNFA) => 2,
+            Some(AhoCorasickKind::DFA) => 3,
+        });
+        w.start_kind(self.start_kind);
+        self.nfa_noncontiguous.write_options(&mut w);
+        self.nfa_contiguous.write_options(&mut w);
+        self.dfa.write_options(&mut w);
+        w.as_bytes().to_vec()
+    }
+
+    /// Returns a searcher sharing the automaton of the given searcher, as
+    /// if it were built from the given patterns with this builder.
+    ///
+    /// The caller is responsible for the given searcher having been built
+    /// from the same patterns with a builder of the same `options_key`.
+    #[cfg(feature = "std")]
+    pub(crate) fn reuse_automaton(
+        &self,
+        ac: &AhoCorasick,
+        patterns: &[Vec<u8>],
+    ) -> AhoCorasick {
+        let ac = self.finish_automaton(Arc::clone(&ac.aut), ac.kind);
+        if self.needs_patterns() {
+            return self.attach_patterns(ac, patterns.to_vec());
+        }
+        ac
+    }
+
+    /// Build an Aho-Corasick automaton without storing its patterns.
+    fn build_automaton<I, P>(
+        &self,
+        patterns: I,
+    ) -> Result<AhoCorasick, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let match_kind = self.nfa_noncontiguous.get_match_kind();
+        if self.overlap_policy.is_some() && !match_kind.is_standard() {
+            return Err(BuildError::overlap_policy_match_kind(match_kind));
+        }
         let nfa = self.nfa_noncontiguous.build(patterns)?;
         let (aut, kind): (Arc<dyn AcAutomaton>, AhoCorasickKind) =
             match self.kind {
@@ -2384,7 +5039,415 @@ impl AhoCorasickBuilder {
                     (Arc::new(dfa), AhoCorasickKind::DFA)
                 }
             };
-        Ok(AhoCorasick { aut, kind, start_kind: self.start_kind })
+        Ok(self.finish_automaton(aut, kind))
+    }
+
+    /// Wrap the given automaton in a searcher, configured with the search
+    /// options of this builder.
+    fn finish_automaton(
+        &self,
+        aut: Arc<dyn AcAutomaton>,
+        kind: AhoCorasickKind,
+    ) -> AhoCorasick {
+        AhoCorasick {
+            aut,
+            kind,
+            start_kind: self.start_kind,
+            patterns: None,
+            ascii_case_insensitive: self
+                .nfa_noncontiguous
+                .get_ascii_case_insensitive(),
+            translation_table: self
+                .nfa_noncontiguous
+                .get_translation_table()
+                .map(|table| Arc::new(*table)),
+            verifier: None,
+            lookaheads: None,
+            word_boundary: self.word_boundary,
+            replacement_tables: Arc::from(self.replacement_tables.clone()),
+            overlap_policy: self.overlap_policy,
+            disabled: None,
+            provenance: None,
+        }
+    }
+
+    /// Check the given patterns for problems, without building an
+    /// automaton.
+    ///
+    /// This reports empty patterns, duplicate patterns, patterns that are
+    /// too long to build an automaton from and, when
+    /// [`MatchKind::LeftmostFirst`] semantics are configured, patterns that
+    /// can never match because an earlier pattern is a prefix of them.
+    /// Duplicates are detected with respect to
+    /// [`AhoCorasickBuilder::ascii_case_insensitive`].
+    ///
+    /// None of these problems prevent building an automaton (except for
+    /// patterns that are too long), but they usually indicate a mistake in
+    /// the patterns. This is useful for checking dictionaries before they
+    /// are deployed.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     dictionary, AhoCorasickBuilder, MatchKind, ValidationIssue,
+    /// };
+    ///
+    /// let text = b"# keywords\nfoo\nbar\n\nfoobar\nfoo\n";
+    /// let patterns = dictionary::parse_patterns_with_lines(text)?;
+    ///
+    /// let report = AhoCorasickBuilder::new()
+    ///     .match_kind(MatchKind::LeftmostFirst)
+    ///     .validate(patterns.iter().map(|(_, p)| p));
+    /// assert!(!report.is_ok());
+    ///
+    /// let mut errors = vec![];
+    /// for issue in report.issues() {
+    ///     let line = patterns[issue.pattern().unwrap().as_usize()].0;
+    ///     errors.push(format!("line {}: {}", line, issue));
+    /// }
+    /// assert_eq!(
+    ///     vec![
+    ///         "line 5: pattern 2 can never match because pattern 0 is a \
+    ///          prefix of it",
+    ///         "line 6: pattern 3 is a duplicate of pattern 0",
+    ///     ],
+    ///     errors,
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn validate<I, P>(&self, patterns: I) -> ValidationReport
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        validate::validate(
+            patterns,
+            self.nfa_noncontiguous.get_match_kind(),
+            self.nfa_noncontiguous.get_ascii_case_insensitive(),
+        )
+    }
+
+    /// Report which of the given patterns occur anywhere in another one of
+    /// them.
+    ///
+    /// This builds an automaton with [`MatchKind::Standard`] semantics from
+    /// the patterns, whose failure transitions already record, for every
+    /// prefix of every pattern, which patterns are a suffix of it. It's
+    /// useful to predict which matches nest, or to prune a dictionary.
+    ///
+    /// ASCII case insensitivity is taken into account, but the match
+    /// semantics, the translation table and the lookaheads set on this
+    /// builder are not.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when an automaton can't be built from the
+    /// patterns, in the same way as [`AhoCorasickBuilder::build`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasickBuilder, PatternID};
+    ///
+    /// let patterns = &["Sherlock", "lock", "Holmes", "LOCK"];
+    /// let contained = AhoCorasickBuilder::new()
+    ///     .ascii_case_insensitive(true)
+    ///     .contained_patterns(patterns)?;
+    ///
+    /// let pid = |i| PatternID::must(i);
+    /// assert!(contained.contains(pid(0), pid(1)));
+    /// assert!(!contained.contains(pid(1), pid(0)));
+    /// assert_eq!(&[pid(1), pid(3)], contained.contained_in(pid(0)));
+    /// // Duplicates occur in each other.
+    /// assert_eq!(
+    ///     vec![pid(0), pid(3)],
+    ///     contained.containers(pid(1)).collect::<Vec<_>>(),
+    /// );
+    /// assert!(contained.contained_in(pid(2)).is_empty());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn contained_patterns<I, P>(
+        &self,
+        patterns: I,
+    ) -> Result<ContainedPatterns, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let patterns: Vec<Vec<u8>> =
+            patterns.into_iter().map(|p| p.as_ref().to_vec()).collect();
+        let mut builder = self.nfa_noncontiguous.clone();
+        builder
+            .match_kind(MatchKind::Standard)
+            .translation_table(None)
+            .duplicate_policy(DuplicatePolicy::All)
+            .prefilter(false);
+        let nfa = builder.build(&patterns)?;
+        let contained = patterns
+            .iter()
+            .enumerate()
+            .map(|(i, pattern)| {
+                let mut pids = nfa.occurring_patterns(pattern);
+                pids.retain(|pid| pid.as_usize() != i);
+                pids
+            })
+            .collect();
+        Ok(ContainedPatterns::new(contained))
+    }
+
+    /// Export the given patterns, with the configuration set on this builder,
+    /// for use with Hyperscan or Vectorscan.
+    ///
+    /// This makes it possible to maintain a single dictionary for
+    /// deployments that use either engine. ASCII case insensitivity is
+    /// exported as a flag, and pattern IDs are assigned in the same way as
+    /// [`AhoCorasickBuilder::build`] assigns them.
+    ///
+    /// Patterns are only exported as anchored when the start kind is
+    /// [`StartKind::Anchored`]. With [`StartKind::Both`], whether a search is
+    /// anchored is decided per search, which Hyperscan can't express, so the
+    /// patterns are exported as unanchored. The match semantics are not
+    /// exported. See [`HyperscanExport`](crate::dictionary::HyperscanExport)
+    /// for how they relate to Hyperscan's own semantics.
+    ///
+    /// No automaton is built, so this never fails.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasickBuilder, StartKind};
+    ///
+    /// let export = AhoCorasickBuilder::new()
+    ///     .ascii_case_insensitive(true)
+    ///     .export_hyperscan(["foo", "a.b", ""]);
+    /// assert_eq!(
+    ///     "0:/foo/iL\n1:/a\\x2Eb/iL\n2://iVL\n",
+    ///     export.to_string(),
+    /// );
+    ///
+    /// let export = AhoCorasickBuilder::new()
+    ///     .start_kind(StartKind::Anchored)
+    ///     .export_hyperscan(["GET /"]);
+    /// let pattern = &export.patterns()[0];
+    /// assert_eq!(0, pattern.id().as_usize());
+    /// assert_eq!("^GET\\x20\\x2F", pattern.expression());
+    /// assert_eq!("L", pattern.flags());
+    /// ```
+    pub fn export_hyperscan<I, P>(&self, patterns: I) -> HyperscanExport
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        crate::dictionary::export_hyperscan(
+            patterns,
+            self.nfa_noncontiguous.get_ascii_case_insensitive(),
+            self.start_kind == StartKind::Anchored,
+        )
+    }
+
+    /// Build a searcher whose patterns are split into at most `shards`
+    /// automata according to their first byte, using the configuration set
+    /// on this builder for each automaton.
+    ///
+    /// The searcher reports the same matches as an automaton built from the
+    /// same patterns with [`AhoCorasickBuilder::build`]. See the
+    /// [`sharded`](crate::sharded) module for how patterns are assigned to
+    /// shards and how searches consult them. A `shards` value of `0` is
+    /// treated as `1`.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the automaton of any shard could not be
+    /// built.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasickBuilder, PatternID};
+    ///
+    /// let sharded = AhoCorasickBuilder::new()
+    ///     .build_sharded(&["foo", "bar", "baz", "quux"], 2)?;
+    /// assert_eq!(2, sharded.shards_len());
+    /// // 'bar' and 'baz' start with the same byte, so they share a shard.
+    /// assert_eq!(
+    ///     &[PatternID::must(1), PatternID::must(2)],
+    ///     sharded.shard_patterns(0),
+    /// );
+    /// assert_eq!(
+    ///     Some(PatternID::must(3)),
+    ///     sharded.find("xxx quux xxx").map(|m| m.pattern()),
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build_sharded<I, P>(
+        &self,
+        patterns: I,
+        shards: usize,
+    ) -> Result<ShardedSearcher, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        ShardedSearcher::build(self, patterns, shards)
+    }
+
+    /// Build a searcher that routes each position of a haystack to a small
+    /// automaton according to the first `prefix_len` bytes at that position,
+    /// using the configuration set on this builder for each automaton.
+    ///
+    /// The searcher reports the same matches as an automaton built from the
+    /// same patterns with [`AhoCorasickBuilder::build`]. See the
+    /// [`two_level`](crate::two_level) module for how patterns are grouped
+    /// into buckets and when this is faster than a single automaton. A
+    /// `prefix_len` value of `0` is treated as `1`.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the automaton of any bucket could not be
+    /// built.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasickBuilder, PatternID};
+    ///
+    /// let searcher = AhoCorasickBuilder::new()
+    ///     .build_two_level(&["foobar", "foobaz", "quux", "ab"], 3)?;
+    /// // 'foobar' and 'foobaz' start with 'foo', so they share a bucket.
+    /// assert_eq!(2, searcher.buckets_len());
+    /// assert_eq!(
+    ///     &[PatternID::must(0), PatternID::must(1)],
+    ///     searcher.bucket_patterns(0),
+    /// );
+    /// assert_eq!(
+    ///     Some(PatternID::must(1)),
+    ///     searcher.find("xx foobaz xx").map(|m| m.pattern()),
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build_two_level<I, P>(
+        &self,
+        patterns: I,
+        prefix_len: usize,
+    ) -> Result<TwoLevelSearcher, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        TwoLevelSearcher::build(self, patterns, prefix_len)
+    }
+
+    /// Build a searcher for haystacks encoded as UTF-16 with the given byte
+    /// order, using the configuration set on this builder.
+    ///
+    /// Each pattern is encoded as UTF-16 before the automaton is built, and
+    /// the searcher only reports matches that start on a code unit
+    /// boundary. Match offsets are byte offsets into the UTF-16 haystack.
+    /// See the [`utf16`](crate::utf16) module for more details.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the automaton could not be built.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{utf16::Utf16Endian, AhoCorasickBuilder, PatternID};
+    ///
+    /// let searcher = AhoCorasickBuilder::new()
+    ///     .build_utf16(&["foo", "bar"], Utf16Endian::Little)?;
+    /// let haystack: Vec<u8> =
+    ///     "xbar".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    /// let mat = searcher.find(&haystack).unwrap();
+    /// assert_eq!(PatternID::must(1), mat.pattern());
+    /// assert_eq!(2..8, mat.range());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build_utf16<I, P>(
+        &self,
+        patterns: I,
+        endian: Utf16Endian,
+    ) -> Result<Utf16Searcher, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        Utf16Searcher::build(self, patterns, endian)
+    }
+
+    /// Compile the given byte signatures and build a searcher for them,
+    /// using the configuration set on this builder for the underlying
+    /// Aho-Corasick automaton.
+    ///
+    /// Each signature is written in the syntax described in the
+    /// [`signature`](crate::signature) module: hex encoded bytes, `?`
+    /// wildcards, alternations of single bytes like `(0A | 0D)`, bounded
+    /// repetitions like `00{2,8}` and string literals like `"MZ"` (or
+    /// `"this program"i` for an ASCII case insensitive literal).
+    ///
+    /// Options that control the implementation of the automaton, such as
+    /// [`AhoCorasickBuilder::kind`] and [`AhoCorasickBuilder::prefilter`],
+    /// are respected. The match semantics, start kind, ASCII case
+    /// insensitivity, translation table and lookahead options are ignored,
+    /// since the signature searcher manages them itself.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if any signature fails to parse, in which case
+    /// [`SignatureError::signature`](crate::signature::SignatureError::signature)
+    /// reports the index of the offending signature. An error is also
+    /// returned if a signature has no literal byte to anchor its search, or
+    /// if the automaton could not be built.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasickBuilder, AhoCorasickKind, Match};
+    ///
+    /// let searcher = AhoCorasickBuilder::new()
+    ///     .kind(Some(AhoCorasickKind::DFA))
+    ///     .build_signatures([
+    ///         r#""MZ" ?? ?? 00"#,
+    ///         r#""this program"i (20 | 00)"#,
+    ///     ])?;
+    /// let haystack = b"MZ\x90\x00\x00 This Program ";
+    /// let matches: Vec<Match> = searcher.find_iter(haystack).collect();
+    /// assert_eq!(vec![Match::must(0, 0..5), Match::must(1, 6..19)], matches);
+    ///
+    /// let err = AhoCorasickBuilder::new()
+    ///     .build_signatures(["4D 5A", "4D 5"])
+    ///     .unwrap_err();
+    /// assert_eq!(Some(1), err.signature());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build_signatures<I, S>(
+        &self,
+        signatures: I,
+    ) -> Result<SignatureSearcher, SignatureError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let signatures = signatures
+            .into_iter()
+            .enumerate()
+            .map(|(i, sig)| {
+                Signature::new(sig.as_ref())
+                    .map_err(|err| err.with_signature(i))
+            })
+            .collect::<Result<Vec<Signature>, SignatureError>>()?;
+        Ok(SignatureSearcher::build(self, signatures)?)
     }
 
     /// Implements the automatic selection logic for the Aho-Corasick
@@ -2395,12 +5458,8 @@ impl AhoCorasickBuilder {
         &self,
         nfa: noncontiguous::NFA,
     ) -> (Arc<dyn AcAutomaton>, AhoCorasickKind) {
-        // We try to build a DFA if we have a very small number of patterns,
-        // otherwise the memory usage just gets too crazy. We also only do it
-        // when the start kind is unanchored or anchored, but not both, because
-        // both implies two full copies of the transition table.
-        let try_dfa = !matches!(self.start_kind, StartKind::Both)
-            && nfa.patterns_len() <= 100;
+        let try_dfa = self.recommend_kind_for_len(nfa.patterns_len())
+            == AhoCorasickKind::DFA;
         if try_dfa {
             match self.dfa.build_from_noncontiguous(&nfa) {
                 Ok(dfa) => {
@@ -2437,8 +5496,98 @@ impl AhoCorasickBuilder {
                 );
             }
         }
-        debug!("chose non-contiguous NFA");
-        (Arc::new(nfa), AhoCorasickKind::NoncontiguousNFA)
+        debug!("chose non-contiguous NFA");
+        (Arc::new(nfa), AhoCorasickKind::NoncontiguousNFA)
+    }
+
+    /// Returns the kind of automaton that [`AhoCorasickBuilder::build`]
+    /// chooses for the given patterns when no kind is set with
+    /// [`AhoCorasickBuilder::kind`].
+    ///
+    /// This doesn't build anything, and the kind set with
+    /// [`AhoCorasickBuilder::kind`] is ignored, so an application can log
+    /// the recommendation or compare it with the kind it overrides it with.
+    /// The recommendation is:
+    ///
+    /// * [`AhoCorasickKind::DFA`] for at most 100 patterns, when the start
+    ///   kind is not [`StartKind::Both`]. A DFA uses a lot of memory for each
+    ///   pattern, and supporting both anchored and unanchored searches
+    ///   requires two copies of its transition table.
+    /// * [`AhoCorasickKind::ContiguousNFA`] otherwise. It is nearly as fast
+    ///   as a DFA and has excellent memory usage.
+    ///
+    /// With a small number of patterns, searches usually spend most of their
+    /// time in a prefilter, which may use a packed SIMD searcher no matter
+    /// the kind of automaton. See [`AhoCorasickBuilder::prefilter`].
+    ///
+    /// The builder may still fall back to another kind if the recommended
+    /// one can't be built, such as when there are too many states for a DFA
+    /// or a contiguous NFA, in which case it tries a contiguous NFA and then
+    /// a noncontiguous NFA. [`AhoCorasick::kind`] reports the kind that was
+    /// actually built.
+    ///
+    /// Note that this heuristic may be changed in a semver compatible
+    /// release.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickKind, StartKind};
+    ///
+    /// let patterns = &["foo", "bar", "quux"];
+    /// let mut builder = AhoCorasick::builder();
+    /// assert_eq!(AhoCorasickKind::DFA, builder.recommend_kind(patterns));
+    /// assert_eq!(
+    ///     AhoCorasickKind::DFA,
+    ///     builder.build(patterns).unwrap().kind(),
+    /// );
+    ///
+    /// builder.start_kind(StartKind::Both);
+    /// assert_eq!(
+    ///     AhoCorasickKind::ContiguousNFA,
+    ///     builder.recommend_kind(patterns),
+    /// );
+    /// ```
+    pub fn recommend_kind<I, P>(&self, patterns: I) -> AhoCorasickKind
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        self.recommend_kind_for_len(patterns.into_iter().count())
+    }
+
+    /// Returns the kind of automaton recommended for the given number of
+    /// patterns. See [`AhoCorasickBuilder::recommend_kind`].
+    fn recommend_kind_for_len(&self, patterns_len: usize) -> AhoCorasickKind {
+        // We try to build a DFA if we have a very small number of patterns,
+        // otherwise the memory usage just gets too crazy. We also only do it
+        // when the start kind is unanchored or anchored, but not both, because
+        // both implies two full copies of the transition table.
+        if !matches!(self.start_kind, StartKind::Both) && patterns_len <= 100 {
+            AhoCorasickKind::DFA
+        } else {
+            AhoCorasickKind::ContiguousNFA
+        }
+    }
+
+    /// Returns the match semantics set on this builder.
+    pub(crate) fn get_match_kind(&self) -> MatchKind {
+        self.nfa_noncontiguous.get_match_kind()
+    }
+
+    /// Returns whether ASCII case insensitivity is enabled on this builder.
+    pub(crate) fn get_ascii_case_insensitive(&self) -> bool {
+        self.nfa_noncontiguous.get_ascii_case_insensitive()
+    }
+
+    /// Returns the translation table set on this builder, if any.
+    pub(crate) fn get_translation_table(&self) -> Option<&[u8; 256]> {
+        self.nfa_noncontiguous.get_translation_table()
+    }
+
+    /// Returns the start kind set on this builder.
+    pub(crate) fn get_start_kind(&self) -> StartKind {
+        self.start_kind
     }
 
     /// Set the desired match semantics.
@@ -2621,6 +5770,361 @@ impl AhoCorasickBuilder {
         self
     }
 
+    /// Set the replacement tables that replacers may be built from with
+    /// [`AhoCorasick::replacer_with_table`].
+    ///
+    /// Each table is identified by its position, starting at `0`, and must
+    /// have one replacement per pattern, which is checked when a replacer is
+    /// built from it. This makes it possible to serve several sets of
+    /// replacements, such as per-locale redaction text, with a single
+    /// automaton instead of building one per set.
+    ///
+    /// By default, there are no replacement tables.
+    ///
+    /// # Examples
+    ///
+    /// See [`AhoCorasick::replacer_with_table`].
+    pub fn replacement_tables<T, I, B>(
+        &mut self,
+        tables: T,
+    ) -> &mut AhoCorasickBuilder
+    where
+        T: IntoIterator<Item = I>,
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        self.replacement_tables = tables
+            .into_iter()
+            .map(|table| {
+                table.into_iter().map(|r| r.as_ref().to_vec()).collect()
+            })
+            .collect();
+        self
+    }
+
+    /// Set the policy that decides which of two overlapping matches is
+    /// replaced, or use the matches of a non-overlapping search with
+    /// `None`.
+    ///
+    /// See [`OverlapPolicy`] for how matches are decided. The policy is
+    /// used by [`AhoCorasick::replace_all`] and the other batch
+    /// replacements, as well as by replacers, such as the one built by
+    /// [`AhoCorasick::replacer`], which hold back the bytes of a match
+    /// until no match found later may win against it. Since every
+    /// overlapping match is considered, this requires [`MatchKind::Standard`]
+    /// semantics: otherwise, building the searcher returns an error. Stream
+    /// replacements, such as [`AhoCorasick::try_stream_replace_all`], and
+    /// the async adapters don't support overlap policies and return an
+    /// error when one is set. Searches are unaffected. A replacer may use
+    /// another policy than the automaton it's built from, with
+    /// [`AhoCorasickReplacerBuilder::overlap_policy`].
+    ///
+    /// By default, no overlap policy is set.
+    ///
+    /// # Example
+    ///
+    /// By default, a replacer replaces the match that ends first, while this
+    /// policy replaces the longest match, however the stream is split.
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, OverlapPolicy};
+    ///
+    /// let patterns = &["net", "network"];
+    /// let replace_with = &["<net>", "<network>"];
+    /// let replace = |ac: &AhoCorasick| {
+    ///     let mut replacer = ac.replacer(replace_with).unwrap();
+    ///     let mut out = replacer.replace(b"a net").unwrap().to_vec();
+    ///     out.extend_from_slice(replacer.replace(b"work").unwrap());
+    ///     out.extend_from_slice(replacer.finish().unwrap());
+    ///     String::from_utf8(out).unwrap()
+    /// };
+    ///
+    /// let ac = AhoCorasick::new(patterns).unwrap();
+    /// assert_eq!("a <net>work", replace(&ac));
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .overlap_policy(Some(OverlapPolicy::LongestWins))
+    ///     .build(patterns)
+    ///     .unwrap();
+    /// assert_eq!("a <network>", replace(&ac));
+    /// assert_eq!("a <network>", ac.replace_all("a network", replace_with));
+    /// ```
+    pub fn overlap_policy(
+        &mut self,
+        policy: Option<OverlapPolicy>,
+    ) -> &mut AhoCorasickBuilder {
+        self.overlap_policy = policy;
+        self
+    }
+
+    /// Store a copy of the patterns in the automaton.
+    ///
+    /// When enabled, the bytes of each pattern can be retrieved with
+    /// [`AhoCorasick::pattern`], which also makes it possible to compare two
+    /// automata with [`dictionary::diff`](crate::dictionary::diff). This is
+    /// disabled by default, since the automaton itself does not need the
+    /// patterns, and storing them increases memory usage by roughly the
+    /// total length of the patterns.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .store_patterns(true)
+    ///     .build(&["foo", "bar"])
+    ///     .unwrap();
+    /// let m = ac.find("xbarx").unwrap();
+    /// assert_eq!(Some(&b"bar"[..]), ac.pattern(m.pattern()));
+    /// ```
+    pub fn store_patterns(&mut self, yes: bool) -> &mut AhoCorasickBuilder {
+        self.store_patterns = yes;
+        self
+    }
+
+    /// Track which patterns each match originates from.
+    ///
+    /// When ASCII case insensitivity is enabled, several patterns may be
+    /// identical once folded, such as `FOO` and `foo`, and so always match
+    /// at the same positions. A non-overlapping search only reports one of
+    /// their IDs for each match, and so does every search when only the
+    /// lowest ID is kept (see [`AhoCorasickBuilder::duplicate_policy`]).
+    /// When this is enabled, the IDs of all of them can be retrieved from
+    /// the ID of any one of them with [`AhoCorasick::provenance`].
+    ///
+    /// This is disabled by default. Enabling it increases memory usage by a
+    /// few words per pattern, but doesn't store the patterns themselves.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .track_provenance(true)
+    ///     .build(&["foo", "bar", "FOO", "Foo"])
+    ///     .unwrap();
+    /// let m = ac.find("xfOox").unwrap();
+    /// assert_eq!(PatternID::must(0), m.pattern());
+    /// assert_eq!(
+    ///     vec![PatternID::must(0), PatternID::must(2), PatternID::must(3)],
+    ///     ac.provenance(m.pattern()).unwrap(),
+    /// );
+    /// ```
+    pub fn track_provenance(&mut self, yes: bool) -> &mut AhoCorasickBuilder {
+        self.track_provenance = yes;
+        self
+    }
+
+    /// Set a function that verifies every match reported by searches,
+    /// given the stored bytes of its pattern and the bytes it matched in the
+    /// haystack. A match is only reported if the function returns true.
+    ///
+    /// This is useful when the haystack or the patterns go through a
+    /// folding or normalization layer that can make the automaton report
+    /// false positives, which the original pattern bytes can rule out.
+    /// Setting a verifier stores the patterns, as if
+    /// [`AhoCorasickBuilder::store_patterns`] was enabled.
+    ///
+    /// Searches report the matches that the automaton would report if the
+    /// patterns didn't match wherever their match is rejected. Verification
+    /// can be turned off per search with [`Input::verify`]. When a match is
+    /// rejected, the search checks every pattern at its position before
+    /// resuming just after its start, so searches can be much slower when
+    /// many matches are rejected. Only searches that accept an [`Input`]
+    /// verify matches. Stream searches and replacements don't, although
+    /// replacements honor [lookaheads](AhoCorasickBuilder::lookahead).
+    ///
+    /// By default, no verifier is set.
+    ///
+    /// # Examples
+    ///
+    /// This searches without respect to case, except that the first letter
+    /// of a match must have the same case as in its pattern.
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Input, Match, MatchKind};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .match_kind(MatchKind::LeftmostLongest)
+    ///     .verify(|pattern, bytes| pattern.first() == bytes.first())
+    ///     .build(&["Ab", "AbC"])
+    ///     .unwrap();
+    /// let haystack = "AbC abc AbCd";
+    ///
+    /// assert_eq!(
+    ///     vec![Match::must(1, 0..3), Match::must(1, 8..11)],
+    ///     ac.find_iter(haystack).collect::<Vec<_>>(),
+    /// );
+    /// // Verification can be turned off for a single search.
+    /// let input = Input::new(haystack).verify(false);
+    /// assert_eq!(3, ac.find_iter(input).count());
+    /// ```
+    pub fn verify<F>(&mut self, verify: F) -> &mut AhoCorasickBuilder
+    where
+        F: Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.verifier = Some(Verifier(Arc::new(verify)));
+        self
+    }
+
+    /// Set a lookahead that every match of the given pattern must satisfy,
+    /// or remove it with `None`.
+    ///
+    /// A match of the pattern is only reported if it's followed by one of
+    /// the bytes of the lookahead within the number of bytes it considers.
+    /// See [`Lookahead`] for details. This makes it possible to require
+    /// some context after a match, such as a top level domain after a host
+    /// name, without a regex engine. Setting a lookahead stores the
+    /// patterns, as if [`AhoCorasickBuilder::store_patterns`] was enabled. A
+    /// lookahead set for a pattern ID that the automaton doesn't have is
+    /// ignored.
+    ///
+    /// Lookaheads are honored by searches that accept an [`Input`], as part
+    /// of the verification of their matches, which is described in
+    /// [`AhoCorasickBuilder::verify`]. They are also honored by replacers,
+    /// which hold back the bytes after a match until it's decided, that is,
+    /// at most as many bytes as its lookahead considers. When a match is
+    /// rejected, a replacer tries the shorter patterns that end at the same
+    /// position, from the longest one. Replacers with
+    /// [`MatchKind::LeftmostLongest`] semantics don't support lookaheads,
+    /// and building one returns an error. Replacements of a whole haystack,
+    /// such as [`AhoCorasick::replace_all`], replace the same matches as
+    /// [`AhoCorasick::find_iter`]. Other stream searches and replacements,
+    /// such as [`AhoCorasick::stream_finder`], return an error when a
+    /// pattern has a lookahead.
+    ///
+    /// By default, no pattern has a lookahead.
+    ///
+    /// # Examples
+    ///
+    /// This only matches host names that are followed by a top level
+    /// domain.
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Lookahead, PatternID};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .lookahead(PatternID::must(0), Some(Lookahead::new(1).bytes(b".")))
+    ///     .build(&["example"])
+    ///     .unwrap();
+    /// let haystack = "example example.com";
+    ///
+    /// let starts: Vec<usize> =
+    ///     ac.find_iter(haystack).map(|m| m.start()).collect();
+    /// assert_eq!(vec![8], starts);
+    ///
+    /// let mut replacer = ac.replacer(&["host"])?;
+    /// let mut out = replacer.replace(b"example example")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b".com")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"example host.com".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn lookahead(
+        &mut self,
+        pattern: PatternID,
+        lookahead: Option<Lookahead>,
+    ) -> &mut AhoCorasickBuilder {
+        let i = pattern.as_usize();
+        if self.lookaheads.len() <= i {
+            self.lookaheads.resize(i + 1, None);
+        }
+        self.lookaheads[i] = lookahead;
+        self
+    }
+
+    /// Remove the lookahead of every pattern.
+    pub(crate) fn clear_lookaheads(&mut self) -> &mut AhoCorasickBuilder {
+        self.lookaheads.clear();
+        self
+    }
+
+    /// Returns a builder whose lookaheads apply to the given subset of the
+    /// patterns, where the pattern at index `i` of the subset has the ID
+    /// `pids[i]` among all patterns, or `None` if no pattern has a
+    /// lookahead.
+    pub(crate) fn for_pattern_subset(
+        &self,
+        pids: &[PatternID],
+    ) -> Option<AhoCorasickBuilder> {
+        if self.lookaheads.iter().all(|l| l.is_none()) {
+            return None;
+        }
+        let mut builder = self.clone();
+        builder.lookaheads = pids
+            .iter()
+            .map(|pid| self.lookaheads.get(pid.as_usize()).copied().flatten())
+            .collect();
+        Some(builder)
+    }
+
+    /// Only report matches that are bounded by non-word bytes.
+    ///
+    /// When enabled, a match is only reported if the byte before it and the
+    /// byte after it are both non-word bytes, where the start and the end of
+    /// the haystack or stream count as non-word bytes. A word byte is an
+    /// ASCII letter, an ASCII digit or an underscore. This is what keyword
+    /// filters usually want, so that `cat` doesn't match in `concatenate`.
+    /// Unlike the `\b` assertion of a regex, both bytes are required to be
+    /// non-word bytes, whatever the first and last bytes of the pattern are.
+    /// Both bytes may be outside of the span searched. Setting this stores
+    /// the patterns, as if [`AhoCorasickBuilder::store_patterns`] was
+    /// enabled.
+    ///
+    /// This applies to every search and replacement that accepts an
+    /// [`Input`] or a haystack, including [`AhoCorasick::replace_all`], as if
+    /// the patterns didn't match wherever their match isn't bounded by
+    /// non-word bytes, which is described in [`AhoCorasickBuilder::verify`].
+    /// It can't be turned off with [`Input::verify`]. It also applies to
+    /// replacers, which hold back the byte after a match until it's decided
+    /// and try the shorter patterns that end at the same position when a
+    /// match is rejected, unless they have [`MatchKind::LeftmostLongest`]
+    /// semantics, in which case building one returns an error. Other stream
+    /// searches and replacements, such as
+    /// [`AhoCorasick::stream_finder`], return an error when it's enabled,
+    /// and searches of UTF-16 haystacks don't honor it.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .word_boundary(true)
+    ///     .build(&["cat", "dog"])
+    ///     .unwrap();
+    /// let haystack = "cat concatenate hotdog dog_ (dog)";
+    ///
+    /// let starts: Vec<usize> =
+    ///     ac.find_iter(haystack).map(|m| m.start()).collect();
+    /// assert_eq!(vec![0, 29], starts);
+    /// assert_eq!(
+    ///     "pet concatenate hotdog dog_ (pet)",
+    ///     ac.replace_all(haystack, &["pet", "pet"]),
+    /// );
+    ///
+    /// let mut replacer = ac.replacer(&["pet", "pet"])?;
+    /// let mut out = replacer.replace(b"a cat")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"fish and a cat")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"a catfish and a pet".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn word_boundary(&mut self, yes: bool) -> &mut AhoCorasickBuilder {
+        self.word_boundary = yes;
+        self
+    }
+
     /// Enable ASCII-aware case insensitive matching.
     ///
     /// When this option is enabled, searching will be performed without
@@ -2629,6 +6133,13 @@ impl AhoCorasickBuilder {
     /// Enabling this option does not change the search algorithm, but it may
     /// increase the size of the automaton.
     ///
+    /// Since case insensitivity is built into the automaton, it applies to
+    /// every search and replacement with it, including stream searches and
+    /// the replacers returned by [`AhoCorasick::replacer`]. There is no need
+    /// to add every combination of cases of a pattern, and doing so only
+    /// creates patterns that are duplicates of each other (see
+    /// [`AhoCorasickBuilder::duplicate_policy`]).
+    ///
     /// **NOTE:** It is unlikely that support for Unicode case folding will
     /// be added in the future. The ASCII case works via a simple hack to the
     /// underlying automaton, but full Unicode handling requires a fair bit of
@@ -2662,6 +6173,131 @@ impl AhoCorasickBuilder {
         self
     }
 
+    /// Set a table that translates every byte of a haystack before it is
+    /// matched against the patterns.
+    ///
+    /// With a table, a haystack byte `b` matches a pattern byte `p` when
+    /// `table[b] == p`. This permits searching haystacks in a single byte
+    /// encoding, such as Latin-1 or CP-1252, for patterns written in another
+    /// one, or folding several haystack bytes into one, without transcoding
+    /// the haystack first. Match offsets are offsets into the haystack
+    /// as given, since every byte is translated to exactly one byte.
+    ///
+    /// The table is compiled into the transitions of the automaton, so
+    /// searching is as fast as without one. However, prefilters are
+    /// disabled, since they look for the patterns themselves in a
+    /// haystack. When ASCII case insensitivity is also enabled, a haystack
+    /// byte matches a pattern byte when its translation is equal to the
+    /// pattern byte in either case. A pattern with a byte that no haystack
+    /// byte translates to can never match.
+    ///
+    /// By default, no translation table is set.
+    ///
+    /// # Examples
+    ///
+    /// This searches Latin-1 text for ASCII patterns, ignoring the accents
+    /// on vowels.
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let mut table = [0u8; 256];
+    /// for b in 0..=255u8 {
+    ///     table[usize::from(b)] = match b {
+    ///         0xE0..=0xE5 => b'a',
+    ///         0xE8..=0xEB => b'e',
+    ///         _ => b,
+    ///     };
+    /// }
+    /// let ac = AhoCorasick::builder()
+    ///     .translation_table(Some(table))
+    ///     .build(&["cafe", "creme"])
+    ///     .unwrap();
+    /// // 'café crème' in Latin-1
+    /// let haystack = b"caf\xE9 cr\xE8me";
+    ///
+    /// let matches: Vec<(PatternID, usize, usize)> = ac
+    ///     .find_iter(haystack)
+    ///     .map(|m| (m.pattern(), m.start(), m.end()))
+    ///     .collect();
+    /// assert_eq!(vec![
+    ///     (PatternID::must(0), 0, 4),
+    ///     (PatternID::must(1), 5, 10),
+    /// ], matches);
+    /// ```
+    pub fn translation_table(
+        &mut self,
+        table: Option<[u8; 256]>,
+    ) -> &mut AhoCorasickBuilder {
+        self.nfa_noncontiguous.translation_table(table);
+        self.nfa_contiguous.translation_table(table);
+        self.dfa.translation_table(table);
+        self
+    }
+
+    /// Set what to do with patterns that are identical to an earlier
+    /// pattern once they are folded.
+    ///
+    /// Patterns are folded when ASCII case insensitivity is enabled, such
+    /// that, for example, `FOO` and `foo` are duplicates. Patterns that are
+    /// byte-for-byte identical are always duplicates. Since duplicates
+    /// match at the same positions, only their IDs can tell them apart, and
+    /// this determines which of them are reported. See [`DuplicatePolicy`]
+    /// for the choices.
+    ///
+    /// By default, every duplicate is kept. A non-overlapping search then
+    /// reports the lowest ID among them, and an overlapping search reports
+    /// all of them in ascending order of their IDs.
+    ///
+    /// # Examples
+    ///
+    /// This reports only the first of two case insensitive duplicates, even
+    /// in an overlapping search, or rejects them.
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, DuplicatePolicy, PatternID};
+    ///
+    /// let patterns = &["FOO", "bar", "foo"];
+    /// let haystack = "foo";
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .build(patterns)
+    ///     .unwrap();
+    /// let matches: Vec<PatternID> = ac
+    ///     .find_overlapping_iter(haystack)
+    ///     .map(|mat| mat.pattern())
+    ///     .collect();
+    /// assert_eq!(vec![PatternID::must(0), PatternID::must(2)], matches);
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .duplicate_policy(DuplicatePolicy::Lowest)
+    ///     .build(patterns)
+    ///     .unwrap();
+    /// let matches: Vec<PatternID> = ac
+    ///     .find_overlapping_iter(haystack)
+    ///     .map(|mat| mat.pattern())
+    ///     .collect();
+    /// assert_eq!(vec![PatternID::must(0)], matches);
+    ///
+    /// let err = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .duplicate_policy(DuplicatePolicy::Error)
+    ///     .build(patterns)
+    ///     .unwrap_err();
+    /// assert_eq!("pattern 2 is a duplicate of pattern 0", err.to_string());
+    /// ```
+    pub fn duplicate_policy(
+        &mut self,
+        policy: DuplicatePolicy,
+    ) -> &mut AhoCorasickBuilder {
+        self.nfa_noncontiguous.duplicate_policy(policy);
+        self.nfa_contiguous.duplicate_policy(policy);
+        self.dfa.duplicate_policy(policy);
+        self
+    }
+
     /// Choose the type of underlying automaton to use.
     ///
     /// Currently, there are four choices:
@@ -2701,7 +6337,9 @@ impl AhoCorasickBuilder {
     /// noncontiguous NFA.
     ///
     /// If `None` is given, then one may use [`AhoCorasick::kind`] to determine
-    /// which Aho-Corasick implementation was chosen.
+    /// which Aho-Corasick implementation was chosen, or
+    /// [`AhoCorasickBuilder::recommend_kind`] to determine which one will be
+    /// chosen without building anything.
     ///
     /// Note that the heuristics used for choosing which `AhoCorasickKind`
     /// may be changed in a semver compatible release.
@@ -2795,6 +6433,44 @@ impl AhoCorasickBuilder {
         self.dfa.byte_classes(yes);
         self
     }
+
+    /// Build the transition table of a DFA in parallel, using the global
+    /// `rayon` thread pool.
+    ///
+    /// Determinizing the noncontiguous NFA into a DFA resolves the failure
+    /// transitions of every state for every equivalence class, which
+    /// dominates the build time of a DFA for many patterns. Since the
+    /// transitions of each DFA state only depend on the NFA, they are
+    /// computed for many states at once when this is enabled. The DFA is
+    /// identical either way.
+    ///
+    /// This only applies when a DFA is built, and only when it's built for
+    /// either unanchored or anchored searches, but not both. See
+    /// [`AhoCorasickBuilder::start_kind`]. The NFA itself, including its
+    /// failure transitions, is always built on the calling thread.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickKind};
+    ///
+    /// let patterns: Vec<String> =
+    ///     (0..1000).map(|i| format!("pattern{}", i)).collect();
+    /// let ac = AhoCorasick::builder()
+    ///     .kind(Some(AhoCorasickKind::DFA))
+    ///     .parallel(true)
+    ///     .build(&patterns)
+    ///     .unwrap();
+    /// assert_eq!(AhoCorasickKind::DFA, ac.kind());
+    /// assert!(ac.is_match("a pattern999"));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn parallel(&mut self, yes: bool) -> &mut AhoCorasickBuilder {
+        self.dfa.parallel(yes);
+        self
+    }
 }
 
 /// The type of Aho-Corasick implementation to use in an [`AhoCorasick`]
@@ -2876,6 +6552,16 @@ unsafe impl Automaton for Arc<dyn AcAutomaton> {
         (**self).next_state(anchored, sid, byte)
     }
 
+    #[inline(always)]
+    unsafe fn next_state_unchecked(
+        &self,
+        anchored: Anchored,
+        sid: StateID,
+        byte: u8,
+    ) -> StateID {
+        (**self).next_state_unchecked(anchored, sid, byte)
+    }
+
     #[inline(always)]
     fn is_special(&self, sid: StateID) -> bool {
         (**self).is_special(sid)
@@ -2974,10 +6660,126 @@ unsafe impl Automaton for Arc<dyn AcAutomaton> {
     }
 }
 
+/// Collects the non-overlapping matches of every haystack using a concrete
+/// automaton.
+fn try_find_many_imp<A: Automaton, H: AsRef<[u8]>>(
+    aut: &A,
+    haystacks: &[H],
+) -> Result<Vec<Vec<Match>>, MatchError> {
+    let mut all = Vec::with_capacity(haystacks.len());
+    for haystack in haystacks.iter() {
+        all.push(aut.try_find_iter(Input::new(haystack))?.collect());
+    }
+    Ok(all)
+}
+
+/// Like `try_find_many_imp`, but searches the haystacks in parallel.
+#[cfg(feature = "rayon")]
+fn try_par_find_many_imp<A: Automaton + Sync, H: AsRef<[u8]> + Sync>(
+    aut: &A,
+    haystacks: &[H],
+) -> Result<Vec<Vec<Match>>, MatchError> {
+    use rayon::prelude::*;
+
+    haystacks
+        .par_iter()
+        .map(|haystack| Ok(aut.try_find_iter(Input::new(haystack))?.collect()))
+        .collect()
+}
+
+/// How the matches of a search are verified against the stored patterns.
+#[derive(Clone, Copy, Debug)]
+struct Verification<'a> {
+    patterns: &'a [Vec<u8>],
+    /// The table that the bytes of a match are translated with before
+    /// they're compared to its pattern.
+    translation_table: Option<&'a [u8; 256]>,
+    /// Whether a match must have the exact case of its pattern.
+    exact_case: bool,
+    verifier: Option<&'a Verifier>,
+    /// The lookahead of each pattern, indexed by pattern ID.
+    lookaheads: Option<&'a [Option<Lookahead>]>,
+    /// Whether each pattern is disabled, indexed by pattern ID.
+    disabled: Option<&'a [bool]>,
+    /// Whether a match must be bounded by non-word bytes.
+    word_boundary: bool,
+    /// The byte order of the code units of a UTF-16 haystack, whose matches
+    /// must be aligned to its code units.
+    utf16: Option<Utf16Endian>,
+}
+
+impl<'a> Verification<'a> {
+    /// Returns true if the given match in the haystack of the given search
+    /// is accepted.
+    fn accepts(&self, input: &Input<'_>, m: Match) -> bool {
+        let pid = m.pattern();
+        if self.disabled.map_or(false, |disabled| disabled[pid])
+            || input.get_pattern_set().map_or(false, |set| !set.contains(pid))
+        {
+            return false;
+        }
+        let haystack = input.haystack();
+        if self.word_boundary
+            && !lookahead::is_word_bounded(haystack, m.range())
+        {
+            return false;
+        }
+        let pattern = &self.patterns[m.pattern()][..];
+        let bytes = &haystack[m.range()];
+        let translated = self.translate(bytes);
+        (!self.exact_case || &*translated == pattern)
+            && self.utf16.map_or(true, |endian| {
+                utf16::is_aligned_match(
+                    endian,
+                    pattern,
+                    &translated,
+                    m.start(),
+                )
+            })
+            && self.verifier.map_or(true, |v| (v.0)(pattern, bytes))
+            && self.lookaheads.map_or(true, |lookaheads| {
+                lookaheads[m.pattern()].map_or(true, |lookahead| {
+                    lookahead.accepts(&haystack[m.end()..])
+                })
+            })
+    }
+
+    /// Returns the given haystack bytes as they're matched against the
+    /// patterns.
+    fn translate<'b>(&self, bytes: &'b [u8]) -> Cow<'b, [u8]> {
+        match self.translation_table {
+            None => Cow::Borrowed(bytes),
+            Some(table) => Cow::Owned(
+                bytes.iter().map(|&b| table[usize::from(b)]).collect(),
+            ),
+        }
+    }
+}
+
+/// A function that verifies the matches of a searcher. See
+/// [`AhoCorasickBuilder::verify`].
+#[derive(Clone)]
+pub(crate) struct Verifier(Arc<VerifyFn>);
+
+/// The type of function used to verify a match, given its pattern and the
+/// bytes it matched.
+type VerifyFn = dyn Fn(&[u8], &[u8]) -> bool + Send + Sync;
+
+// A searcher never changes any state of its own from within a verifier, so a
+// verifier that panics can't leave a searcher in an inconsistent state.
+impl UnwindSafe for Verifier {}
+impl RefUnwindSafe for Verifier {}
+
+impl core::fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Verifier(..)")
+    }
+}
+
 /// Returns an error if the start state configuration does not support the
 /// desired search configuration. See the internal 'AhoCorasick::start_kind'
 /// field docs for more details.
-fn enforce_anchored_consistency(
+pub(crate) fn enforce_anchored_consistency(
     have: StartKind,
     want: Anchored,
 ) -> Result<(), MatchError> {
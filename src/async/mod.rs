@@ -1,5 +1,143 @@
+use alloc::collections::VecDeque;
+
+use crate::{
+    automaton::{Automaton, StateID},
+    Anchored, MatchError, PatternID,
+};
+
 pub(crate) mod reader;
 pub(crate) mod writer;
 
 #[cfg(test)]
 mod tests;
+
+/// The default size of the fixed buffers used by the async adapters.
+const DEFAULT_BUFFER_SIZE: usize = 8 * (1 << 10);
+
+/// The replacement state shared by the async reader and writer.
+///
+/// Every buffer used here is allocated up front, so that stepping through a
+/// stream never allocates. In particular, the buffer of bytes that might
+/// start a match never holds more than `max_pattern_len` bytes, since any
+/// older bytes can no longer be part of a match and are written out as soon
+/// as they are seen.
+#[derive(Debug)]
+pub(crate) struct ReplaceState {
+    start: StateID,
+    sid: StateID,
+    /// The bytes seen since the last start state, which may be the start of
+    /// a match.
+    potential: VecDeque<u8>,
+    max_pattern_len: usize,
+    max_replacement_len: usize,
+    /// The empty pattern with the lowest ID, if any. It matches at every
+    /// position, so that no other pattern ever matches.
+    empty: Option<PatternID>,
+}
+
+impl ReplaceState {
+    pub(crate) fn new<A: Automaton, B: AsRef<[u8]>>(
+        aut: A,
+        replace_with: &[B],
+    ) -> Result<ReplaceState, MatchError> {
+        let start = aut.start_state(Anchored::No)?;
+        let max_pattern_len = aut.max_pattern_len();
+        let max_replacement_len =
+            replace_with.iter().map(|r| r.as_ref().len()).max().unwrap_or(0);
+        // Only the start state of an automaton with an empty pattern is a
+        // match state.
+        let empty = if aut.is_match(start) {
+            (0..aut.match_len(start))
+                .map(|index| aut.match_pattern(start, index))
+                .find(|&pid| aut.pattern_len(pid) == 0)
+        } else {
+            None
+        };
+        Ok(ReplaceState {
+            start,
+            sid: start,
+            potential: VecDeque::with_capacity(max_pattern_len),
+            max_pattern_len,
+            max_replacement_len,
+            empty,
+        })
+    }
+
+    /// Returns the most bytes that a single call to `step` may emit.
+    pub(crate) fn max_step_len(&self) -> usize {
+        self.max_pattern_len + self.max_replacement_len + 1
+    }
+
+    /// Returns the length of a buffer that fits the output of at least one
+    /// call to `step`, and that is not unreasonably small otherwise.
+    pub(crate) fn buffer_len(&self) -> usize {
+        core::cmp::max(DEFAULT_BUFFER_SIZE, self.max_step_len())
+    }
+
+    /// Feed a single byte of the stream through the automaton, emitting the
+    /// bytes that are known not to be part of a match along with the
+    /// replacement of every match found.
+    #[inline(always)]
+    pub(crate) fn step<A: Automaton, B: AsRef<[u8]>>(
+        &mut self,
+        aut: A,
+        replace_with: &[B],
+        byte: u8,
+        mut emit: impl FnMut(u8),
+    ) {
+        if let Some(pid) = self.empty {
+            replace_with[pid].as_ref().iter().copied().for_each(&mut emit);
+            emit(byte);
+            return;
+        }
+        // SAFETY: 'sid' is always either the start state of the automaton
+        // this state was created from, or a state returned by a previous
+        // transition of it. Callers always give the same automaton.
+        self.sid =
+            unsafe { aut.next_state_unchecked(Anchored::No, self.sid, byte) };
+        if aut.is_start(self.sid) {
+            // No potential replacements
+            self.potential.drain(..).for_each(&mut emit);
+            emit(byte);
+            return;
+        }
+        self.potential.push_back(byte);
+        if !aut.is_match(self.sid) {
+            // A state that isn't a match is less deep than the longest
+            // pattern, so anything before that can't be part of a match.
+            // Writing it out now, rather than when the state resets, keeps
+            // the buffer within its initial capacity.
+            let keep = self.max_pattern_len.saturating_sub(1);
+            while self.potential.len() > keep {
+                emit(self.potential.pop_front().unwrap());
+            }
+            return;
+        }
+        let pattern_id = aut.match_pattern(self.sid, 0);
+        let pattern_len = aut.pattern_len(pattern_id);
+        // Either we followed a potential word all the way down, or we jumped
+        // to a different branch following the suffix link. In the second
+        // case, the start of the potential buffer isn't part of the match.
+        while self.potential.len() > pattern_len {
+            emit(self.potential.pop_front().unwrap());
+        }
+        self.potential.clear();
+        replace_with[pattern_id].as_ref().iter().copied().for_each(emit);
+        // Reset the state after a replacement
+        self.sid = self.start;
+    }
+
+    /// Emit the bytes that were held back as the possible start of a match,
+    /// for when the stream has ended, followed by the replacement of the
+    /// empty pattern, if any, which also matches at the end of the stream.
+    pub(crate) fn finish<B: AsRef<[u8]>>(
+        &mut self,
+        replace_with: &[B],
+        mut emit: impl FnMut(u8),
+    ) {
+        self.potential.drain(..).for_each(&mut emit);
+        if let Some(pid) = self.empty {
+            replace_with[pid].as_ref().iter().copied().for_each(emit);
+        }
+    }
+}
@@ -1,22 +1,67 @@
 use crate::{
-    ahocorasick::AcAutomaton, automaton::{StateID, Automaton}, Anchored, MatchError, AhoCorasickKind,
+    ahocorasick::AcAutomaton, r#async::ReplaceState, AhoCorasickKind,
+    MatchError,
 };
-use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use alloc::{collections::VecDeque, sync::Arc, vec, vec::Vec};
 use core::task::Poll;
 use futures::AsyncRead;
 use pin_project_lite::pin_project;
 
-// Wrapper over an AsyncRead. Reading from AhoCorasickAsyncReader polls replaced results
 pin_project! {
+    /// An `AsyncRead` adapter that replaces matches in the bytes read from
+    /// another `AsyncRead`.
+    ///
+    /// Replacing while reading permits transforming a stream whose consumer
+    /// controls the read side, such as an HTTP response body handed to a
+    /// client library, where an [`AhoCorasickAsyncWriter`] would require
+    /// restructuring the code around a writer. The same matches are
+    /// replaced either way.
+    ///
+    /// All buffers are allocated when the reader is created, so that polling
+    /// it never allocates.
+    ///
+    /// This reader is constructed via the [`AhoCorasick::async_reader`]
+    /// method.
+    ///
+    /// # Example
+    ///
+    /// This wraps a body in a reader whose type can be named, for example
+    /// to return it from a function or store it in a struct.
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickAsyncReader};
+    /// use futures::{AsyncRead, AsyncReadExt};
+    ///
+    /// const REDACTED: &[&str] = &["[redacted]"];
+    ///
+    /// fn redact<R: AsyncRead>(
+    ///     body: R,
+    /// ) -> AhoCorasickAsyncReader<'static, R, &'static str> {
+    ///     let ac = AhoCorasick::new(&["hunter2"]).unwrap();
+    ///     ac.async_reader(body, REDACTED).unwrap()
+    /// }
+    ///
+    /// let body = futures::io::Cursor::new("password: hunter2");
+    /// let mut result = String::new();
+    /// futures::executor::block_on(async {
+    ///     redact(body).read_to_string(&mut result).await.unwrap();
+    /// });
+    /// assert_eq!("password: [redacted]", result);
+    /// ```
+    ///
+    /// [`AhoCorasickAsyncWriter`]: crate::AhoCorasickAsyncWriter
+    /// [`AhoCorasick::async_reader`]: crate::AhoCorasick::async_reader
     pub struct AhoCorasickAsyncReader<'a, R, B> {
         #[pin]
         source: R,
         aut: Arc<dyn AcAutomaton>,
         kind: AhoCorasickKind,
-        sid: StateID,
+        state: ReplaceState,
         replace_with: &'a [B],
         buffer: Vec<u8>, // Used to buffer initially read bytes (before replacements)
-        potential_buffer: VecDeque<u8>, // Buffer holding the start of a potential match
+        start: usize, // The start of the bytes in buffer that have not been processed yet
+        end: usize, // The end of the bytes read into buffer
+        eof: bool, // Whether the source has reached its end
         pending_write_buffer: VecDeque<u8>, // Buffer holding the data ready to be written. Might need to wait until next chunk
     }
 }
@@ -32,38 +77,43 @@ where
         source: R,
         replace_with: &'a [B],
     ) -> Result<Self, MatchError> {
-        let sid = aut.start_state(Anchored::No)?;
+        let state = ReplaceState::new(
+            aut.as_ref().coerce_concrete(kind),
+            replace_with,
+        )?;
+        let buffer = vec![0; state.buffer_len()];
+        // At most one step overflows the caller's buffer, since steps stop
+        // as soon as anything is pending
+        let pending_write_buffer =
+            VecDeque::with_capacity(state.max_step_len());
         Ok(AhoCorasickAsyncReader {
             source,
             aut,
             kind,
-            sid,
+            state,
             replace_with,
-            buffer: Vec::new(),
-            potential_buffer: VecDeque::new(),
-            pending_write_buffer: VecDeque::new(),
+            buffer,
+            start: 0,
+            end: 0,
+            eof: false,
+            pending_write_buffer,
         })
     }
+}
 
-    // Helper uniformizing method : writing to buffer with index. Does not check index boundary and may panic
-    #[inline(always)]
-    fn write_to_buffer(buf: &mut [u8], idx: &mut usize, char: u8) {
+// Helper uniformizing method : writes to the buffer at index, or pushes the char to the deque in case of buffer overflow
+#[inline(always)]
+fn write_to_buffer_overflow_deque(
+    buf: &mut [u8],
+    deque: &mut VecDeque<u8>,
+    idx: &mut usize,
+    char: u8,
+) {
+    if *idx < buf.len() {
         buf[*idx] = char;
         *idx += 1;
-    }
-    // Helper uniformizing method : writes to the buffer at index, or pushes the char to the deque in case of buffer overflow
-    #[inline(always)]
-    fn write_to_buffer_overflow_deque(
-        buf: &mut [u8],
-        deque: &mut VecDeque<u8>,
-        idx: &mut usize,
-        char: u8,
-    ) {
-        if *idx < buf.len() {
-            Self::write_to_buffer(buf, idx, char);
-        } else {
-            deque.push_back(char);
-        }
+    } else {
+        deque.push_back(char);
     }
 }
 
@@ -73,149 +123,78 @@ where
     B: AsRef<[u8]> + 'a,
 {
     fn poll_read(
-        mut self: std::pin::Pin<&mut Self>,
+        self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buf: &mut [u8],
     ) -> std::task::Poll<std::io::Result<usize>> {
-        let this = self.as_mut().project();
+        let mut this = self.project();
         let aut = this.aut.as_ref().coerce_concrete(*this.kind);
-        if this.buffer.len() < buf.len() {
-            this.buffer.resize(buf.len(), b'\0');
-        }
         let mut write_idx: usize = 0;
-        while this.pending_write_buffer.len() > 0 {
-            // First, write pending buffer if any
-            if write_idx < buf.len() {
-                Self::write_to_buffer(
-                    buf,
-                    &mut write_idx,
-                    this.pending_write_buffer.pop_front().unwrap(),
-                );
-            } else {
-                break;
+        // First, write pending buffer if any
+        while write_idx < buf.len() {
+            match this.pending_write_buffer.pop_front() {
+                Some(byte) => {
+                    buf[write_idx] = byte;
+                    write_idx += 1;
+                }
+                None => break,
             }
         }
-        if write_idx >= buf.len() {
-            // Pending buffer had enough data to fully fill buf => no need to poll from source, wait for next read
-            return Poll::Ready(Ok(write_idx));
-        }
-        match this.source.poll_read(cx, this.buffer) {
-            Poll::Ready(result) => {
-                match result {
-                    Ok(size) => {
-                        if size == 0 {
-                            // End reached - discard potential buffer
-                            while this.potential_buffer.len() > 0 {
-                                Self::write_to_buffer_overflow_deque(
-                                    buf,
-                                    this.pending_write_buffer,
-                                    &mut write_idx,
-                                    this.potential_buffer.pop_front().unwrap(),
-                                );
-                            }
-                        }
-                        for byte in &this.buffer[..size] {
-                            *this.sid = aut.next_state(
-                                Anchored::No,
-                                *this.sid,
-                                *byte,
-                            );
-                            if aut.is_start(*this.sid) {
-                                // No potential replacements
-                                while this.potential_buffer.len() > 0 {
-                                    // At this point potential buffer is discareded (written)
-                                    Self::write_to_buffer_overflow_deque(
-                                        buf,
-                                        this.pending_write_buffer,
-                                        &mut write_idx,
-                                        this.potential_buffer
-                                            .pop_front()
-                                            .unwrap(),
-                                    );
-                                }
-                                Self::write_to_buffer_overflow_deque(
-                                    buf,
-                                    this.pending_write_buffer,
-                                    &mut write_idx,
-                                    *byte,
-                                );
-                            } else {
-                                this.potential_buffer.push_back(*byte);
-                                if aut.is_match(*this.sid) {
-                                    let pattern_id =
-                                        aut.match_pattern(*this.sid, 0);
-                                    let pattern_len =
-                                        aut.pattern_len(pattern_id);
-                                    // Either we followed a potential word all the way down, or we jumped to a different branch following the suffix link
-                                    // In the second case, we need to discard (write away) first part of the potential buffer, as it will be bigger than the max match,
-                                    // keeping as new potential the last part containing the amount of bytes equal to the new state node depth (equal to the pattern_len)
-                                    while this.potential_buffer.len()
-                                        > pattern_len
-                                    {
-                                        Self::write_to_buffer_overflow_deque(
-                                            buf,
-                                            this.pending_write_buffer,
-                                            &mut write_idx,
-                                            this.potential_buffer
-                                                .pop_front()
-                                                .unwrap(),
-                                        );
-                                    }
-
-                                    let replacement =
-                                        this.replace_with[pattern_id].as_ref();
-                                    // Replacement is given by the automaton node, so we only need to clear the potential buffer
-                                    this.potential_buffer.clear();
-                                    for replaced_byte in replacement.iter() {
-                                        Self::write_to_buffer_overflow_deque(
-                                            buf,
-                                            this.pending_write_buffer,
-                                            &mut write_idx,
-                                            *replaced_byte,
-                                        );
-                                    }
-                                    // Reset the state after a replacement
-                                    *this.sid = this
-                                        .aut
-                                        .start_state(Anchored::No)
-                                        .map_err(|e| {
-                                            std::io::Error::new(
-                                                std::io::ErrorKind::Other,
-                                                e,
-                                            )
-                                        })?;
-                                }
-                            }
-                        }
-                        if write_idx > 0 {
-                            // Something has been written
-                            Poll::Ready(Ok(write_idx))
-                        } else if size > 0 {
-                            // Special cases handling : a non-empty chunk has been read from the source, however nothing has been written
-                            // Identified cases where this might happen :
-                            // 1. When the pattern exceeds the chunk size, and is fully buffered in potential_buffer waiting to be replaced or discarded
-                            // 2. When the chunk fully matches a pattern, and the replacement is an empty string (very specific)
-                            //
-                            // We cannot respond with Ok(0), which would mean end of read, so we simply request a new poll immediately,
-                            // and proceed reading more chunks from the source
-                            cx.waker().wake_by_ref();
-                            Poll::Pending
-                        } else {
-                            // Nothing left to write
-                            Poll::Ready(Ok(0))
-                        }
+        while write_idx < buf.len() && this.pending_write_buffer.is_empty() {
+            if *this.start == *this.end {
+                if *this.eof {
+                    break;
+                }
+                // Read the next chunk from the source. When a chunk produces
+                // no output, for example because it's entirely the start of
+                // a potential match, we simply keep reading.
+                match this.source.as_mut().poll_read(cx, this.buffer) {
+                    Poll::Ready(Ok(0)) => {
+                        // End reached - discard potential buffer
+                        *this.eof = true;
+                        let pending = &mut *this.pending_write_buffer;
+                        this.state.finish(this.replace_with, |b| {
+                            write_to_buffer_overflow_deque(
+                                buf,
+                                pending,
+                                &mut write_idx,
+                                b,
+                            )
+                        });
+                        break;
                     }
-                    Err(err) => Poll::Ready(Err(err)),
+                    Poll::Ready(Ok(size)) => {
+                        *this.start = 0;
+                        *this.end = size;
+                    }
+                    Poll::Ready(Err(err)) if write_idx == 0 => {
+                        return Poll::Ready(Err(err));
+                    }
+                    // Return what has been written so far. The source will
+                    // most likely report the error again on the next read
+                    Poll::Ready(Err(_)) => break,
+                    // While waiting for the source, if some bytes have
+                    // already been written, we can return them immediately
+                    Poll::Pending if write_idx == 0 => return Poll::Pending,
+                    Poll::Pending => break,
                 }
             }
-            Poll::Pending => {
-                if write_idx > 0 {
-                    // While waiting for the source, if some bytes have already been written from pending buffer, we can return them immediately to speed things up
-                    Poll::Ready(Ok(write_idx))
-                } else {
-                    Poll::Pending
-                }
+            let pending = &mut *this.pending_write_buffer;
+            while *this.start < *this.end && pending.is_empty() {
+                let byte = this.buffer[*this.start];
+                *this.start += 1;
+                this.state.step(aut, this.replace_with, byte, |b| {
+                    write_to_buffer_overflow_deque(
+                        buf,
+                        pending,
+                        &mut write_idx,
+                        b,
+                    )
+                });
             }
         }
+        // Zero is only ever returned once the source has ended and everything
+        // has been written, or if buf is empty
+        Poll::Ready(Ok(write_idx))
     }
 }
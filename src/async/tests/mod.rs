@@ -1,7 +1,11 @@
-use core::str::from_utf8;
+use core::{
+    pin::Pin,
+    str::from_utf8,
+    task::{Context, Poll},
+};
 
 use alloc::{string::ToString, vec::Vec};
-use futures::{AsyncReadExt, AsyncWriteExt};
+use futures::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::{
     r#async::tests::test_utils::{BytesAsyncReader, BytesAsyncWriter},
@@ -69,6 +73,17 @@ fn test_async() {
                 ),
                 "Now she is shere, so is he. her hair is blond, sher bag is huge".to_string(),
             ),
+            (
+                // An empty pattern matches at every position, so it is the only one replaced
+                "abc".repeat(2),
+                Vec::from(
+                    [
+                        ("ab","AB"),
+                        ("","-"),
+                    ]
+                ),
+                "-a-b-c".repeat(2) + "-",
+            ),
         ].iter().enumerate() {
             // Testing different buffer sizes to confirm the proper behavior of matches in consecutive chunks
             for test_buffer_size in [1,2,3,5,7,10,100,10000] {
@@ -144,3 +159,60 @@ fn test_async() {
         }
     });
 }
+
+#[test]
+fn test_async_write_vectored() {
+    let ac = AhoCorasick::new(["fox", "brown", "quick"]).unwrap();
+    let replacements = ["bear", "white", "slow"];
+    let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+    for forced_pending in [0usize, 2] {
+        let writer = BytesAsyncWriter::new(forced_pending);
+        let mut ac_writer =
+            ac.async_writer(writer.clone(), &replacements).unwrap();
+        // Patterns are split across slices, and some slices are empty
+        let slices = ["The qu", "", "ick b", "r", "own f", "ox", "."];
+        let bufs: Vec<std::io::IoSlice<'_>> = slices
+            .iter()
+            .map(|s| std::io::IoSlice::new(s.as_bytes()))
+            .collect();
+        let poll =
+            Pin::new(&mut ac_writer).poll_write_vectored(&mut cx, &bufs);
+        match poll {
+            Poll::Ready(result) => {
+                assert_eq!(slices.concat().len(), result.unwrap())
+            }
+            Poll::Pending => panic!("vectored write consumed nothing"),
+        }
+        loop {
+            if let Poll::Ready(result) =
+                Pin::new(&mut ac_writer).poll_close(&mut cx)
+            {
+                result.unwrap();
+                break;
+            }
+        }
+        assert_eq!(&b"The slow white bear."[..], &*writer.sink.borrow());
+    }
+}
+
+#[test]
+fn test_async_empty_writes() {
+    futures::executor::block_on(async {
+        let ac = AhoCorasick::new(["abc", "bcd"]).unwrap();
+        let replacements = ["1", "2"];
+        for forced_pending in [0usize, 2] {
+            let writer = BytesAsyncWriter::new(forced_pending);
+            let mut ac_writer =
+                ac.async_writer(writer.clone(), &replacements).unwrap();
+            assert_eq!(0, ac_writer.write(b"").await.unwrap());
+            for byte in b"xabcd abbcd".iter() {
+                ac_writer.write_all(&[*byte]).await.unwrap();
+                // An empty write in the middle of a potential match is a
+                // no-op
+                assert_eq!(0, ac_writer.write(b"").await.unwrap());
+            }
+            ac_writer.close().await.unwrap();
+            assert_eq!(&b"x1d ab2"[..], &*writer.sink.borrow());
+        }
+    });
+}
@@ -1,31 +1,39 @@
 use crate::{
-    ahocorasick::AcAutomaton, automaton::{StateID, Automaton}, Anchored, MatchError, AhoCorasickKind,
+    ahocorasick::AcAutomaton, r#async::ReplaceState, AhoCorasickKind,
+    MatchError,
 };
-use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use alloc::{sync::Arc, vec, vec::Vec};
 use core::task::Poll;
 use futures::AsyncWrite;
 use pin_project_lite::pin_project;
 
-// Wrapper over an AsyncWrite. Writing to AhoCorasickAsyncWriter will write replaced results to the underlying writer
 pin_project! {
+    /// An `AsyncWrite` adapter that replaces matches in the bytes written to
+    /// it before writing them to another `AsyncWrite`.
+    ///
+    /// All buffers are allocated when the writer is created, so that polling
+    /// it never allocates. See [`AhoCorasickAsyncReader`] for the adapter
+    /// that replaces matches while reading instead.
+    ///
+    /// This writer is constructed via the [`AhoCorasick::async_writer`]
+    /// method.
+    ///
+    /// [`AhoCorasickAsyncReader`]: crate::AhoCorasickAsyncReader
+    /// [`AhoCorasick::async_writer`]: crate::AhoCorasick::async_writer
     pub struct AhoCorasickAsyncWriter<'a, W, B> {
         #[pin]
         sink: W,
         aut: Arc<dyn AcAutomaton>,
         kind: AhoCorasickKind,
-        sid: StateID,
+        state: ReplaceState,
         replace_with: &'a [B],
         buffer: Vec<u8>, // Buffer holding the data that will be sent to the sink
-        potential_buffer: VecDeque<u8>, // Buffer holding the start of a potential match
-        pending_state: Option<PendingState> // If the underlying sink responded with Pending, we save the state
+        start: usize, // The start of the data in buffer that has not been sent to the sink yet
+        end: usize, // The end of the data in buffer
+        finished: bool, // Whether the potential buffer has been written after close was requested
     }
 }
 
-struct PendingState {
-    bytes_to_write: usize, // How much bytes to send from the buffer to the sink
-    bytes_read: usize, // How much input bytes have been processed (previous buf.len basically)
-}
-
 impl<'a, W, B> AhoCorasickAsyncWriter<'a, W, B>
 where
     W: AsyncWrite,
@@ -36,34 +44,127 @@ where
         kind: AhoCorasickKind,
         sink: W,
         replace_with: &'a [B],
-    ) -> Result<Self, MatchError>
-    where
-        W: AsyncWrite,
-        B: AsRef<[u8]> + 'a,
-    {
-        let sid = aut.start_state(Anchored::No)?;
+    ) -> Result<Self, MatchError> {
+        let state = ReplaceState::new(
+            aut.as_ref().coerce_concrete(kind),
+            replace_with,
+        )?;
+        let buffer = vec![0; state.buffer_len()];
         Ok(AhoCorasickAsyncWriter {
             sink,
             aut,
             kind,
-            sid,
+            state,
             replace_with,
-            buffer: Vec::new(),
-            potential_buffer: VecDeque::new(),
-            pending_state: None,
+            buffer,
+            start: 0,
+            end: 0,
+            finished: false,
         })
     }
 
-    /// Writing to the buffer while making rare incremental resizes
-    #[inline(always)]
-    fn write_to_buffer(buf: &mut Vec<u8>, idx: &mut usize, char: u8) {
-        if *idx >= buf.len() {
-            // Since this function is called with incremental idx, we simply double current buffer length every time
-            buf.resize(buf.len() * 2, b'\0');
+    /// Process the given slices in order, as if they were a single slice.
+    ///
+    /// The replaced output of every slice goes to the same buffer, so a
+    /// potential match may span slices, and the output of many small slices
+    /// is sent to the sink in a single write.
+    fn poll_write_slices(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut this = self.project();
+        let aut = this.aut.as_ref().coerce_concrete(*this.kind);
+        let max_step_len = this.state.max_step_len();
+        let mut consumed = 0;
+        'slices: for buf in bufs.iter() {
+            let mut at = 0;
+            while at < buf.len() {
+                if *this.end + max_step_len > this.buffer.len() {
+                    // The buffer is full: it has to be sent to the sink
+                    // before more input can be processed
+                    match poll_drain(
+                        this.sink.as_mut(),
+                        cx,
+                        this.buffer,
+                        this.start,
+                        this.end,
+                    ) {
+                        Poll::Ready(Ok(())) => {}
+                        Poll::Ready(Err(err)) if consumed == 0 => {
+                            return Poll::Ready(Err(err));
+                        }
+                        // Report the bytes that were consumed so far. An
+                        // error will be reported again by the next call
+                        Poll::Ready(Err(_)) => break 'slices,
+                        Poll::Pending if consumed == 0 => {
+                            return Poll::Pending
+                        }
+                        Poll::Pending => break 'slices,
+                    }
+                }
+                // The replaced output of every byte goes to the buffer,
+                // which always has room for it at this point
+                let (buffer, end) = (&mut *this.buffer, &mut *this.end);
+                while at < buf.len() && *end + max_step_len <= buffer.len() {
+                    this.state.step(aut, this.replace_with, buf[at], |b| {
+                        buffer[*end] = b;
+                        *end += 1;
+                    });
+                    at += 1;
+                    consumed += 1;
+                }
+            }
+        }
+        // Now, either the replaced bytes are in the buffer ready to be
+        // written, or they are in the potential buffer waiting for the next
+        // chunk. In both cases, they are considered "written" from the
+        // standpoint of AhoCorasickAsyncWriter. We still try to send the
+        // buffer right away, but if the sink isn't ready, it will be sent
+        // with the next write, flush or close.
+        if *this.start < *this.end {
+            if let Poll::Ready(Err(err)) = poll_drain(
+                this.sink.as_mut(),
+                cx,
+                this.buffer,
+                this.start,
+                this.end,
+            ) {
+                if consumed == 0 {
+                    return Poll::Ready(Err(err));
+                }
+            }
+        }
+        Poll::Ready(Ok(consumed))
+    }
+}
+
+/// Send everything in `buffer[*start..*end]` to the sink. This returns
+/// `Poll::Pending` if the sink is not ready, in which case the bytes that
+/// have not been sent yet stay in the buffer.
+fn poll_drain<W: AsyncWrite>(
+    mut sink: core::pin::Pin<&mut W>,
+    cx: &mut std::task::Context<'_>,
+    buffer: &[u8],
+    start: &mut usize,
+    end: &mut usize,
+) -> Poll<std::io::Result<()>> {
+    while *start < *end {
+        match sink.as_mut().poll_write(cx, &buffer[*start..*end]) {
+            Poll::Ready(Ok(0)) => {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    "failed to write replaced bytes to the sink",
+                )));
+            }
+            Poll::Ready(Ok(n)) => *start += n,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
         }
-        buf[*idx] = char;
-        *idx += 1;
     }
+    *start = 0;
+    *end = 0;
+    Poll::Ready(Ok(()))
 }
 
 impl<'a, W, B> AsyncWrite for AhoCorasickAsyncWriter<'a, W, B>
@@ -76,133 +177,62 @@ where
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        let this = self.project();
-        let aut = this.aut.as_ref().coerce_concrete(*this.kind);
-        if let Some(pending_state) = this.pending_state.take() {
-            return match this
-                .sink
-                .poll_write(cx, &this.buffer[..pending_state.bytes_to_write])
-            {
-                Poll::Ready(_) => Poll::Ready(Ok(pending_state.bytes_read)),
-                Poll::Pending => {
-                    // Still not ready : put back PendingState, as it has been taken
-                    *this.pending_state = Some(pending_state);
-                    Poll::Pending
-                }
-            };
-        }
-        if this.buffer.len() < buf.len() + this.potential_buffer.len() {
-            // Default buffer length to buf once to avoid incremental size increases & capacity reallocations during the buffer writing process
-            this.buffer.resize(buf.len() + this.potential_buffer.len(), b'\0');
-        }
-        let mut write_idx = 0usize;
-        for byte in buf {
-            *this.sid = aut.next_state(Anchored::No, *this.sid, *byte);
-            if aut.is_start(*this.sid) {
-                // No potential replacements
-                while this.potential_buffer.len() > 0 {
-                    // At this point potential buffer is discareded (written)
-                    Self::write_to_buffer(
-                        this.buffer,
-                        &mut write_idx,
-                        this.potential_buffer.pop_front().unwrap(),
-                    );
-                }
-                Self::write_to_buffer(this.buffer, &mut write_idx, *byte);
-            } else {
-                this.potential_buffer.push_back(*byte);
-                if aut.is_match(*this.sid) {
-                    let pattern_id = aut.match_pattern(*this.sid, 0);
-                    let pattern_len = aut.pattern_len(pattern_id);
-                    // Either we followed a potential word all the way down, or we jumped to a different branch following the suffix link
-                    // In the second case, we need to discard (write away) first part of the potential buffer, as it will be bigger than the max match,
-                    // keeping as new potential the last part containing the amount of bytes equal to the new state node depth (equal to the pattern_len)
-                    while this.potential_buffer.len() > pattern_len {
-                        Self::write_to_buffer(
-                            this.buffer,
-                            &mut write_idx,
-                            this.potential_buffer.pop_front().unwrap(),
-                        );
-                    }
+        self.poll_write_slices(cx, &[std::io::IoSlice::new(buf)])
+    }
 
-                    let replacement = this.replace_with[pattern_id].as_ref();
-                    // Replacement is given by the automaton node, so we only need to clear the potential buffer
-                    this.potential_buffer.clear();
-                    for replaced_byte in replacement.iter() {
-                        Self::write_to_buffer(
-                            this.buffer,
-                            &mut write_idx,
-                            *replaced_byte,
-                        );
-                    }
-                    // Reset the state after a replacement
-                    *this.sid =
-                        aut.start_state(Anchored::No).map_err(|e| {
-                            std::io::Error::new(std::io::ErrorKind::Other, e)
-                        })?;
-                }
-            }
-        }
-        // Now (unless buf was empty), either the bytes are in the buffer ready to be written, or they are in the potential buffer awaiting for the next chunk before being written
-        // In both cases, all of them are considered "written" from the standpoint of AhoCorasickAsyncWriter, and we need to return not how many we have actually written to the sink with replacements,
-        // but how many we have "consumed" - which should always match the length of input buf. So the return count is independent from write_idx
-        if write_idx > 0 {
-            match this.sink.poll_write(cx, &this.buffer[..write_idx]) {
-                Poll::Ready(_) => Poll::Ready(Ok(buf.len())),
-                Poll::Pending => {
-                    // Tricky state : the sink is not yet ready to accept the buffer, but we have processed the chunk, including moving automaton state around
-                    // So because don't want to redo the processing, we simply save the Pending state with current buffer & write idx,
-                    // and on the next call at the beginning of this poll_write, this Pending state is handled
-                    *this.pending_state = Some(PendingState {
-                        bytes_to_write: write_idx,
-                        bytes_read: buf.len(),
-                    });
-                    Poll::Pending
-                }
-            }
-        } else if this.potential_buffer.len() > 0 {
-            // Nothing written, but potential buffer is not empty - request immediate poll again with new buffer by saying we have accepted the buffer fully
-            // This case happens when the potential buffer (replacement word length) exceeds the current chunk size while matching the entire chunk :
-            // nothing can be written yet, but next chunk(s) are needed to determine the outcome (discard as-is, or replace)
-            // Different to the Reader, here we cannot reply with Pending, as same bytes will be sent again - we have to acknowledge that we have consumed them
-            Poll::Ready(Ok(buf.len()))
-        } else {
-            // This case can happen in 2 scenarios :
-            // 1. Input buf is empty (most likely a bug on the consumer side)
-            // 2. The contents of buf match entirely a word which has the empty string replacement. We still inform the consumer that we have "written" the bytes we received,
-            //    even though we has nothing to write to the sink
-            Poll::Ready(Ok(buf.len()))
-        }
+    fn poll_write_vectored(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        self.poll_write_slices(cx, bufs)
     }
 
     fn poll_flush(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<std::io::Result<()>> {
-        // Nothing special to do here
-        self.project().sink.poll_flush(cx)
+        let mut this = self.project();
+        futures::ready!(poll_drain(
+            this.sink.as_mut(),
+            cx,
+            this.buffer,
+            this.start,
+            this.end,
+        ))?;
+        this.sink.poll_flush(cx)
     }
 
     fn poll_close(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<std::io::Result<()>> {
-        let this = self.project();
-        if this.potential_buffer.len() > 0 {
-            // We have to ensure that potential buffer bytes are written, in case there was a beginning of a match at the end of the stream
-            this.potential_buffer.make_contiguous();
-            match this.sink.poll_write(cx, this.potential_buffer.as_slices().0)
-            {
-                Poll::Ready(_) => {
-                    // Bytes have been written : empty potential_buffer, and ask for the next call to poll_close
-                    this.potential_buffer.clear();
-                    cx.waker().wake_by_ref();
-                    Poll::Pending
-                }
-                Poll::Pending => Poll::Pending, // The last bytes can't be written yet, so poll_close will be called again when sink.poll_write is ready to make progress
-            }
-        } else {
-            this.sink.poll_close(cx)
+        let mut this = self.project();
+        futures::ready!(poll_drain(
+            this.sink.as_mut(),
+            cx,
+            this.buffer,
+            this.start,
+            this.end,
+        ))?;
+        if !*this.finished {
+            // We have to ensure that potential buffer bytes are written, in
+            // case there was a beginning of a match at the end of the stream.
+            // The buffer is empty at this point and always fits them.
+            let (buffer, end) = (&mut *this.buffer, &mut *this.end);
+            this.state.finish(this.replace_with, |b| {
+                buffer[*end] = b;
+                *end += 1;
+            });
+            *this.finished = true;
+            futures::ready!(poll_drain(
+                this.sink.as_mut(),
+                cx,
+                this.buffer,
+                this.start,
+                this.end,
+            ))?;
         }
+        this.sink.poll_close(cx)
     }
 }
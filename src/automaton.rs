@@ -241,6 +241,29 @@ pub unsafe trait Automaton: private::Sealed {
         byte: u8,
     ) -> StateID;
 
+    /// Like `Automaton::next_state`, but permits implementations to skip
+    /// bounds checks when the `perf-unchecked` feature is enabled.
+    ///
+    /// The default implementation calls `Automaton::next_state`. This is
+    /// used by the search loops in this crate that only ever pass state IDs
+    /// that came from the same automaton.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `sid` is valid, as defined by
+    /// `Automaton::next_state`. Passing an invalid state ID results in
+    /// undefined behavior.
+    #[doc(hidden)]
+    #[inline(always)]
+    unsafe fn next_state_unchecked(
+        &self,
+        anchored: Anchored,
+        sid: StateID,
+        byte: u8,
+    ) -> StateID {
+        self.next_state(anchored, sid, byte)
+    }
+
     /// Returns true if the given ID represents a "special" state. A special
     /// state is a dead, match or start state.
     ///
@@ -563,7 +586,25 @@ pub unsafe trait Automaton: private::Sealed {
     where
         Self: Sized,
     {
-        Ok(StreamFindIter { it: StreamChunkIter::new(self, rdr)? })
+        StreamFindIter::new(self, rdr, false, None)
+    }
+
+    /// Returns an iterator of non-overlapping matches with this automaton
+    /// from the stream given, where each match is reported as soon as it is
+    /// known to have occurred.
+    ///
+    /// See
+    /// [`AhoCorasick::try_stream_find_iter_earliest`](crate::AhoCorasick::try_stream_find_iter_earliest)
+    /// for more documentation and examples.
+    #[cfg(feature = "std")]
+    fn try_stream_find_iter_earliest<'a, R: std::io::Read>(
+        &'a self,
+        rdr: R,
+    ) -> Result<StreamFindIter<'a, Self, R>, MatchError>
+    where
+        Self: Sized,
+    {
+        StreamFindIter::new(self, rdr, true, None)
     }
 
     /// Replaces all non-overlapping matches in `rdr` with strings from
@@ -608,8 +649,8 @@ pub unsafe trait Automaton: private::Sealed {
     fn try_stream_replace_all_with<R, W, F>(
         &self,
         rdr: R,
-        mut wtr: W,
-        mut replace_with: F,
+        wtr: W,
+        replace_with: F,
     ) -> std::io::Result<()>
     where
         Self: Sized,
@@ -617,22 +658,7 @@ pub unsafe trait Automaton: private::Sealed {
         W: std::io::Write,
         F: FnMut(&Match, &[u8], &mut W) -> std::io::Result<()>,
     {
-        let mut it = StreamChunkIter::new(self, rdr).map_err(|e| {
-            let kind = std::io::ErrorKind::Other;
-            std::io::Error::new(kind, e)
-        })?;
-        while let Some(result) = it.next() {
-            let chunk = result?;
-            match chunk {
-                StreamChunk::NonMatch { bytes, .. } => {
-                    wtr.write_all(bytes)?;
-                }
-                StreamChunk::Match { bytes, mat } => {
-                    replace_with(&mat, bytes, &mut wtr)?;
-                }
-            }
-        }
-        Ok(())
+        stream_replace_all_with(self, rdr, wtr, None, replace_with)
     }
 }
 
@@ -654,6 +680,16 @@ unsafe impl<'a, A: Automaton + ?Sized> Automaton for &'a A {
         (**self).next_state(anchored, sid, byte)
     }
 
+    #[inline(always)]
+    unsafe fn next_state_unchecked(
+        &self,
+        anchored: Anchored,
+        sid: StateID,
+        byte: u8,
+    ) -> StateID {
+        (**self).next_state_unchecked(anchored, sid, byte)
+    }
+
     #[inline(always)]
     fn is_special(&self, sid: StateID) -> bool {
         (**self).is_special(sid)
@@ -992,6 +1028,21 @@ pub struct StreamFindIter<'a, A, R> {
     it: StreamChunkIter<'a, A, R>,
 }
 
+#[cfg(feature = "std")]
+impl<'a, A: Automaton, R: std::io::Read> StreamFindIter<'a, A, R> {
+    /// Create a new stream iterator that never reports the patterns that
+    /// are disabled in the given flags, indexed by pattern ID.
+    pub(crate) fn new(
+        aut: &'a A,
+        rdr: R,
+        earliest: bool,
+        disabled: Option<&'a [bool]>,
+    ) -> Result<StreamFindIter<'a, A, R>, MatchError> {
+        let it = StreamChunkIter::new(aut, rdr, earliest, disabled)?;
+        Ok(StreamFindIter { it })
+    }
+}
+
 #[cfg(feature = "std")]
 impl<'a, A: Automaton, R: std::io::Read> Iterator
     for StreamFindIter<'a, A, R>
@@ -1012,6 +1063,96 @@ impl<'a, A: Automaton, R: std::io::Read> Iterator
     }
 }
 
+/// Replaces all non-overlapping matches in `rdr` by calling the
+/// `replace_with` closure given and writing the result to `wtr`. The
+/// patterns that are disabled in the given flags, indexed by pattern ID,
+/// never match.
+///
+/// This is the implementation of
+/// [`Automaton::try_stream_replace_all_with`].
+#[cfg(feature = "std")]
+pub(crate) fn stream_replace_all_with<A, R, W, F>(
+    aut: &A,
+    rdr: R,
+    mut wtr: W,
+    disabled: Option<&[bool]>,
+    mut replace_with: F,
+) -> std::io::Result<()>
+where
+    A: Automaton,
+    R: std::io::Read,
+    W: std::io::Write,
+    F: FnMut(&Match, &[u8], &mut W) -> std::io::Result<()>,
+{
+    let mut it =
+        StreamChunkIter::new(aut, rdr, false, disabled).map_err(|e| {
+            let kind = std::io::ErrorKind::Other;
+            std::io::Error::new(kind, e)
+        })?;
+    while let Some(result) = it.next() {
+        let chunk = result?;
+        match chunk {
+            StreamChunk::NonMatch { bytes, .. } => {
+                wtr.write_all(bytes)?;
+            }
+            StreamChunk::Match { bytes, mat } => {
+                replace_with(&mat, bytes, &mut wtr)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An iterator that reports matches in a stream along with the line and
+/// column at which each match starts.
+///
+/// This is like [`StreamFindIter`], except it also observes every byte of
+/// the stream in order to track lines and columns.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct StreamLineColumnIter<'a, A, R> {
+    it: StreamChunkIter<'a, A, R>,
+    tracker: crate::util::position::LineColumnTracker,
+}
+
+#[cfg(feature = "std")]
+impl<'a, A: Automaton, R: std::io::Read> StreamLineColumnIter<'a, A, R> {
+    pub(crate) fn new(
+        aut: &'a A,
+        rdr: R,
+        unit: crate::util::position::ColumnUnit,
+        disabled: Option<&'a [bool]>,
+    ) -> Result<StreamLineColumnIter<'a, A, R>, MatchError> {
+        let it = StreamChunkIter::new(aut, rdr, false, disabled)?;
+        let tracker = crate::util::position::LineColumnTracker::new(unit);
+        Ok(StreamLineColumnIter { it, tracker })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, A: Automaton, R: std::io::Read> Iterator
+    for StreamLineColumnIter<'a, A, R>
+{
+    type Item = std::io::Result<(Match, crate::util::position::LineColumn)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.it.next() {
+                None => return None,
+                Some(Err(err)) => return Some(Err(err)),
+                Some(Ok(StreamChunk::NonMatch { bytes })) => {
+                    self.tracker.advance(bytes);
+                }
+                Some(Ok(StreamChunk::Match { bytes, mat })) => {
+                    let pos = self.tracker.position();
+                    self.tracker.advance(bytes);
+                    return Some(Ok((mat, pos)));
+                }
+            }
+        }
+    }
+}
+
 /// An iterator that reports matches in a stream.
 ///
 /// (This doesn't actually implement the `Iterator` trait because it returns
@@ -1046,6 +1187,9 @@ struct StreamChunkIter<'a, A, R> {
     /// stream as non-overlapping blocks of bytes. We need to permit some
     /// overlap while we retain bytes from a previous `read` call in memory.
     buf: crate::util::buffer::Buffer,
+    /// Whether each pattern is disabled, indexed by pattern ID. The matches
+    /// of disabled patterns are never reported.
+    disabled: Option<&'a [bool]>,
     /// The unanchored starting state of this automaton.
     start: StateID,
     /// The state of the automaton.
@@ -1064,9 +1208,14 @@ struct StreamChunkIter<'a, A, R> {
 
 #[cfg(feature = "std")]
 impl<'a, A: Automaton, R: std::io::Read> StreamChunkIter<'a, A, R> {
+    /// Create a new chunk iterator. When `earliest` is enabled, the
+    /// automaton's match semantics are not enforced, since every search
+    /// stops at the first match state it sees regardless of match kind.
     fn new(
         aut: &'a A,
         rdr: R,
+        earliest: bool,
+        disabled: Option<&'a [bool]>,
     ) -> Result<StreamChunkIter<'a, A, R>, MatchError> {
         // This restriction is a carry-over from older versions of this crate.
         // I didn't have the bandwidth to think through how to handle, say,
@@ -1084,7 +1233,13 @@ impl<'a, A: Automaton, R: std::io::Read> StreamChunkIter<'a, A, R> {
         // refills. Which this code doesn't really handle. It does preserve
         // *some* state across buffer refills, basically ensuring that a match
         // span is always in memory.
-        if !aut.match_kind().is_standard() {
+        //
+        // The exception is an "earliest" search, which stops at the first
+        // match state regardless of match semantics. Since a leftmost
+        // automaton only transitions to a dead state after a match state has
+        // been seen, and we always restart after a match, an earliest search
+        // never needs to know where a leftmost match would have ended.
+        if !earliest && !aut.match_kind().is_standard() {
             return Err(MatchError::unsupported_stream(aut.match_kind()));
         }
         // This is kind of a cop-out, but empty matches are SUPER annoying.
@@ -1106,6 +1261,7 @@ impl<'a, A: Automaton, R: std::io::Read> StreamChunkIter<'a, A, R> {
             aut,
             rdr,
             buf: crate::util::buffer::Buffer::new(aut.max_pattern_len()),
+            disabled,
             start,
             sid: start,
             absolute_pos: 0,
@@ -1133,17 +1289,30 @@ impl<'a, A: Automaton, R: std::io::Read> StreamChunkIter<'a, A, R> {
         // this is what match spans are reported in terms of.
         loop {
             if self.aut.is_match(self.sid) {
-                let mat = self.get_match();
-                if let Some(r) = self.get_non_match_chunk(mat) {
-                    self.buffer_reported_pos += r.len();
-                    let bytes = &self.buf.buffer()[r];
-                    return Some(Ok(StreamChunk::NonMatch { bytes }));
+                match self.get_match() {
+                    Some(mat) => {
+                        if let Some(r) = self.get_non_match_chunk(mat) {
+                            self.buffer_reported_pos += r.len();
+                            let bytes = &self.buf.buffer()[r];
+                            return Some(Ok(StreamChunk::NonMatch { bytes }));
+                        }
+                        self.sid = self.start;
+                        let r = self.get_match_chunk(mat);
+                        self.buffer_reported_pos += r.len();
+                        let bytes = &self.buf.buffer()[r];
+                        return Some(Ok(StreamChunk::Match { bytes, mat }));
+                    }
+                    // Every pattern matched here is disabled. A standard
+                    // search carries on from this state, just as if those
+                    // patterns weren't in the automaton. An earliest search
+                    // of a leftmost automaton restarts instead, as it does
+                    // after any match, since a leftmost automaton may enter
+                    // a dead state after a match state.
+                    None if !self.aut.match_kind().is_standard() => {
+                        self.sid = self.start;
+                    }
+                    None => {}
                 }
-                self.sid = self.start;
-                let r = self.get_match_chunk(mat);
-                self.buffer_reported_pos += r.len();
-                let bytes = &self.buf.buffer()[r];
-                return Some(Ok(StreamChunk::Match { bytes, mat }));
             }
             if self.buffer_pos >= self.buf.buffer().len() {
                 if let Some(r) = self.get_pre_roll_non_match_chunk() {
@@ -1175,7 +1344,11 @@ impl<'a, A: Automaton, R: std::io::Read> StreamChunkIter<'a, A, R> {
             }
             let start = self.absolute_pos;
             for &byte in self.buf.buffer()[self.buffer_pos..].iter() {
-                self.sid = self.aut.next_state(Anchored::No, self.sid, byte);
+                // SAFETY: 'self.sid' is only ever set to IDs returned by
+                // 'self.aut', and so is always valid.
+                self.sid = unsafe {
+                    self.aut.next_state_unchecked(Anchored::No, self.sid, byte)
+                };
                 self.absolute_pos += 1;
                 if self.aut.is_match(self.sid) {
                     break;
@@ -1235,11 +1408,21 @@ impl<'a, A: Automaton, R: std::io::Read> StreamChunkIter<'a, A, R> {
         None
     }
 
-    /// Return the match at the current position for the current state.
+    /// Return the match at the current position for the current state, or
+    /// `None` if every pattern it matches is disabled. When some are, this
+    /// returns the first enabled one, which is the one an overlapping search
+    /// reports first.
     ///
     /// This panics if `self.aut.is_match(self.sid)` isn't true.
-    fn get_match(&self) -> Match {
-        get_match(self.aut, self.sid, 0, self.absolute_pos)
+    fn get_match(&self) -> Option<Match> {
+        let (aut, sid, at) = (self.aut, self.sid, self.absolute_pos);
+        let disabled = match self.disabled {
+            None => return Some(get_match(aut, sid, 0, at)),
+            Some(disabled) => disabled,
+        };
+        (0..aut.match_len(sid))
+            .map(|i| get_match(aut, sid, i, at))
+            .find(|m| !disabled[m.pattern()])
     }
 }
 
@@ -1310,12 +1493,16 @@ fn try_find_fwd_imp<A: Automaton + ?Sized>(
     while at < input.end() {
         // I've tried unrolling this loop and eliding bounds checks, but no
         // matter what I did, I could not observe a consistent improvement on
-        // any benchmark I could devise. (If someone wants to re-litigate this,
-        // the way to do it is to add an 'next_state_unchecked' method to the
-        // 'Automaton' trait with a default impl that uses 'next_state'. Then
-        // use 'aut.next_state_unchecked' here and implement it on DFA using
-        // unchecked slice index acces.)
-        sid = aut.next_state(anchored, sid, input.haystack()[at]);
+        // any benchmark I could devise. Eliding the transition table bounds
+        // check is nevertheless available via the 'perf-unchecked' feature,
+        // which only changes the implementation of 'next_state_unchecked' for
+        // the DFA.
+        //
+        // SAFETY: 'sid' always comes from 'start_state' or a previous
+        // transition of 'aut', and is therefore valid.
+        sid = unsafe {
+            aut.next_state_unchecked(anchored, sid, input.haystack()[at])
+        };
         if aut.is_special(sid) {
             if aut.is_dead(sid) {
                 return Ok(mat);
@@ -1536,6 +1723,54 @@ fn try_find_overlapping_fwd_imp<A: Automaton + ?Sized>(
     Ok(())
 }
 
+/// Counts the overlapping matches in the given input, without reporting any
+/// of them. The caller must have checked that the automaton supports
+/// overlapping searches with the given input.
+///
+/// Since the matches of a state include every match reachable through its
+/// failure transitions, which are copied into it when the automaton is
+/// built, the number of overlapping matches ending at a position is the
+/// number of matches of the state the search is in there. So the count is
+/// summed a state at a time, instead of a match at a time.
+#[inline(never)]
+pub(crate) fn count_overlapping_fwd<A: Automaton + ?Sized>(
+    aut: &A,
+    input: &Input<'_>,
+) -> Result<usize, MatchError> {
+    if input.is_done() {
+        return Ok(0);
+    }
+    let pre = aut.prefilter();
+    let mut sid = aut.start_state(input.get_anchored())?;
+    let mut count = 0;
+    if aut.is_match(sid) {
+        count += aut.match_len(sid);
+    }
+    let mut at = input.start();
+    while at < input.end() {
+        sid = aut.next_state(input.get_anchored(), sid, input.haystack()[at]);
+        at += 1;
+        if aut.is_special(sid) {
+            if aut.is_dead(sid) {
+                break;
+            } else if aut.is_match(sid) {
+                count += aut.match_len(sid);
+            } else if let Some(pre) = pre {
+                // As in an overlapping search, a special state that is not a
+                // dead or a match state must be a start state, from which
+                // the prefilter skips to the next candidate.
+                debug_assert!(aut.is_start(sid));
+                let span = Span::from(at..input.end());
+                match pre.find_in(input.haystack(), span).into_option() {
+                    None => break,
+                    Some(i) => at = core::cmp::max(at, i),
+                }
+            }
+        }
+    }
+    Ok(count)
+}
+
 #[inline(always)]
 fn get_match<A: Automaton + ?Sized>(
     aut: &A,
@@ -1609,6 +1844,7 @@ pub(crate) fn sparse_transitions<'a>(
 
 /// Holds a variant of an implemented automaton, as a concrete type rather than trait object
 /// This is useful for coercion of dynamic trait objects, for devirtualized calls
+#[derive(Clone, Copy, Debug)]
 pub(crate) enum AutomatonImpl<'a> {
     NoncontiguousNFA(&'a noncontiguous::NFA),
     ContiguousNFA(&'a contiguous::NFA),
@@ -1655,6 +1891,26 @@ unsafe impl<'a>  Automaton for AutomatonImpl<'a>  {
         }
     }
 
+    #[inline(always)]
+    unsafe fn next_state_unchecked(
+        &self,
+        anchored: Anchored,
+        sid: StateID,
+        byte: u8,
+    ) -> StateID {
+        match self {
+            AutomatonImpl::NoncontiguousNFA(aut) => {
+                aut.next_state_unchecked(anchored, sid, byte)
+            }
+            AutomatonImpl::ContiguousNFA(aut) => {
+                aut.next_state_unchecked(anchored, sid, byte)
+            }
+            AutomatonImpl::DFA(aut) => {
+                aut.next_state_unchecked(anchored, sid, byte)
+            }
+        }
+    }
+
     fn is_special(&self, sid: StateID) -> bool {
         match self {
             AutomatonImpl::NoncontiguousNFA(aut) => aut.is_special(sid),
@@ -0,0 +1,335 @@
+/*!
+A command line tool that replaces the patterns of a dictionary in a stream.
+
+This reads a stream from stdin and writes it to stdout, with every match of a
+pattern replaced by its replacement. Patterns and replacements are read from
+dictionaries in the format of the [`aho_corasick::dictionary`] module, either
+from a pattern dictionary and a replacement dictionary with one replacement
+per pattern, or from a single replacement dictionary. Since empty lines are
+skipped in a dictionary, an empty replacement can only be given in a
+replacement dictionary.
+
+The stream is replaced in chunks with an
+[`AhoCorasickReplacer`](aho_corasick::AhoCorasickReplacer), so that it's
+never held in memory at once. Replacers don't support leftmost-first
+semantics, so with those the whole stream is read first.
+
+This binary is only built when the `cli` feature is enabled.
+*/
+
+use std::{
+    error::Error,
+    ffi::OsString,
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    process,
+};
+
+use aho_corasick::{dictionary, AhoCorasick, AhoCorasickKind, MatchKind};
+
+const USAGE: &str = "\
+Replace the patterns of a dictionary while streaming from stdin to stdout.
+
+USAGE:
+    acr [OPTIONS] <PATTERNS> <REPLACEMENTS>
+    acr [OPTIONS] <DICTIONARY>
+
+ARGS:
+    <PATTERNS>        A pattern dictionary, with one pattern per line.
+    <REPLACEMENTS>    A pattern dictionary, with the replacement of each
+                      pattern on the same line as the pattern.
+    <DICTIONARY>      A replacement dictionary, with a pattern and its
+                      replacement separated by a tab on each line.
+
+OPTIONS:
+    --kind <KIND>               auto, noncontiguous, contiguous or dfa
+                                [default: auto]
+    --match-kind <MATCH_KIND>   standard, leftmost-first or leftmost-longest
+                                [default: standard]
+    --chunk-size <BYTES>        The number of bytes read at a time
+                                [default: 65536]
+    -h, --help                  Print this message.
+";
+
+fn main() {
+    let args = match Args::parse(std::env::args_os().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("acr: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(&args) {
+        eprintln!("acr: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Build the searcher and replace stdin to stdout.
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let patterns = fs::read(&args.patterns)?;
+    let replacements = match args.replacements {
+        None => None,
+        Some(ref path) => Some(fs::read(path)?),
+    };
+    let (ac, replacements) = args.build(&patterns, replacements.as_deref())?;
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut wtr = io::BufWriter::new(stdout.lock());
+    replace(&ac, &replacements, args.chunk_size, stdin.lock(), &mut wtr)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+/// The replacement of each pattern, indexed by pattern ID.
+type Replacements = Vec<Vec<u8>>;
+
+/// The parsed command line arguments.
+#[derive(Debug)]
+struct Args {
+    /// The pattern dictionary, or the replacement dictionary when no
+    /// replacements are given separately.
+    patterns: PathBuf,
+    replacements: Option<PathBuf>,
+    kind: Option<AhoCorasickKind>,
+    match_kind: MatchKind,
+    chunk_size: usize,
+}
+
+impl Args {
+    /// Parse the given arguments, excluding the name of the binary. This
+    /// returns `None` if help was requested.
+    fn parse<I: Iterator<Item = OsString>>(
+        mut it: I,
+    ) -> Result<Option<Args>, String> {
+        let mut positional = vec![];
+        let mut kind = None;
+        let mut match_kind = MatchKind::Standard;
+        let mut chunk_size = 1 << 16;
+        while let Some(arg) = it.next() {
+            let arg = match arg.into_string() {
+                Ok(arg) => arg,
+                Err(arg) => {
+                    positional.push(PathBuf::from(arg));
+                    continue;
+                }
+            };
+            let mut value = |flag: &str| {
+                it.next()
+                    .and_then(|v| v.into_string().ok())
+                    .ok_or_else(|| format!("{} requires a value", flag))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--kind" => {
+                    kind = match value("--kind")?.as_str() {
+                        "auto" => None,
+                        "noncontiguous" => {
+                            Some(AhoCorasickKind::NoncontiguousNFA)
+                        }
+                        "contiguous" => Some(AhoCorasickKind::ContiguousNFA),
+                        "dfa" => Some(AhoCorasickKind::DFA),
+                        v => return Err(format!("unknown kind '{}'", v)),
+                    };
+                }
+                "--match-kind" => {
+                    match_kind = match value("--match-kind")?.as_str() {
+                        "standard" => MatchKind::Standard,
+                        "leftmost-first" => MatchKind::LeftmostFirst,
+                        "leftmost-longest" => MatchKind::LeftmostLongest,
+                        v => {
+                            return Err(format!("unknown match kind '{}'", v))
+                        }
+                    };
+                }
+                "--chunk-size" => {
+                    let v = value("--chunk-size")?;
+                    chunk_size = match v.parse() {
+                        Ok(size) if size > 0 => size,
+                        _ => {
+                            return Err(format!("invalid chunk size '{}'", v))
+                        }
+                    };
+                }
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("unknown flag '{}'", flag));
+                }
+                _ => positional.push(PathBuf::from(arg)),
+            }
+        }
+        if positional.len() > 2 {
+            return Err("too many arguments".to_string());
+        }
+        let mut positional = positional.into_iter();
+        let patterns = match positional.next() {
+            Some(patterns) => patterns,
+            None => return Err("missing dictionary".to_string()),
+        };
+        let replacements = positional.next();
+        Ok(Some(Args { patterns, replacements, kind, match_kind, chunk_size }))
+    }
+
+    /// Build a searcher from the contents of the dictionaries, and return it
+    /// along with the replacement of each pattern.
+    fn build(
+        &self,
+        patterns: &[u8],
+        replacements: Option<&[u8]>,
+    ) -> Result<(AhoCorasick, Replacements), Box<dyn Error>> {
+        let (patterns, replacements) = match replacements {
+            None => dictionary::parse_replacements(patterns)?.into_parts(),
+            Some(replacements) => {
+                let patterns = dictionary::parse_patterns(patterns)?;
+                let replacements = dictionary::parse_patterns(replacements)?;
+                if patterns.len() != replacements.len() {
+                    return Err(format!(
+                        "{} patterns but {} replacements",
+                        patterns.len(),
+                        replacements.len(),
+                    )
+                    .into());
+                }
+                (patterns, replacements)
+            }
+        };
+        let ac = AhoCorasick::builder()
+            .kind(self.kind)
+            .match_kind(self.match_kind)
+            .build(&patterns)?;
+        Ok((ac, replacements))
+    }
+}
+
+/// Replace every match in the given reader with its replacement, and write
+/// the result to the given writer, reading the given number of bytes at a
+/// time.
+fn replace<R: Read, W: Write>(
+    ac: &AhoCorasick,
+    replacements: &[Vec<u8>],
+    chunk_size: usize,
+    mut rdr: R,
+    mut wtr: W,
+) -> Result<(), Box<dyn Error>> {
+    if ac.match_kind() == MatchKind::LeftmostFirst {
+        let mut haystack = vec![];
+        rdr.read_to_end(&mut haystack)?;
+        wtr.write_all(&ac.try_replace_all_bytes(&haystack, replacements)?)?;
+        return Ok(());
+    }
+    let mut replacer = ac.replacer(replacements)?;
+    let mut buf = vec![0; chunk_size];
+    loop {
+        let n = match rdr.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        wtr.write_all(replacer.replace_borrowed(&buf[..n])?)?;
+    }
+    wtr.write_all(replacer.finish()?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        Args::parse(args.iter().map(OsString::from))
+    }
+
+    /// A reader that returns at most one byte at a time.
+    struct OneByte<'a>(&'a [u8]);
+
+    impl<'a> Read for OneByte<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn parse_args() {
+        let args = parse(&["--kind", "dfa", "pats", "--chunk-size", "3"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(PathBuf::from("pats"), args.patterns);
+        assert_eq!(None, args.replacements);
+        assert_eq!(Some(AhoCorasickKind::DFA), args.kind);
+        assert_eq!(MatchKind::Standard, args.match_kind);
+        assert_eq!(3, args.chunk_size);
+
+        let args = parse(&["--match-kind", "leftmost-first", "p", "r"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(PathBuf::from("r")), args.replacements);
+        assert_eq!(MatchKind::LeftmostFirst, args.match_kind);
+        assert!(parse(&["p", "--help"]).unwrap().is_none());
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["p", "r", "x"]).is_err());
+        assert!(parse(&["--kind", "nfa", "p"]).is_err());
+        assert!(parse(&["--chunk-size", "0", "p"]).is_err());
+        assert!(parse(&["--chunk-size"]).is_err());
+        assert!(parse(&["--verbose", "p"]).is_err());
+    }
+
+    #[test]
+    fn replace_streams() {
+        let patterns = b"fox\nbrown\nquick\n";
+        let replacements = b"bear\nwhite\nslow\n";
+        let dict = b"fox\tbear\nbrown\twhite\nquick\t\n";
+        let haystack = b"The quick brown fox.";
+        for kind in ["auto", "noncontiguous", "contiguous", "dfa"] {
+            for chunk_size in 1..=haystack.len() + 1 {
+                let chunk_size = chunk_size.to_string();
+                let args =
+                    parse(&["--kind", kind, "--chunk-size", &chunk_size, "p"])
+                        .unwrap()
+                        .unwrap();
+
+                let (ac, with) =
+                    args.build(patterns, Some(replacements)).unwrap();
+                let mut out = vec![];
+                replace(&ac, &with, args.chunk_size, &haystack[..], &mut out)
+                    .unwrap();
+                assert_eq!(b"The slow white bear.".to_vec(), out);
+
+                let (ac, with) = args.build(dict, None).unwrap();
+                let mut out = vec![];
+                let rdr = OneByte(haystack);
+                replace(&ac, &with, args.chunk_size, rdr, &mut out).unwrap();
+                assert_eq!(b"The  white bear.".to_vec(), out);
+            }
+        }
+    }
+
+    #[test]
+    fn replace_leftmost() {
+        let args = parse(&["--match-kind", "leftmost-longest", "p"])
+            .unwrap()
+            .unwrap();
+        let (ac, with) = args.build(b"ab\tX\nabcd\tY\n", None).unwrap();
+        let mut out = vec![];
+        replace(&ac, &with, args.chunk_size, OneByte(b"abcd ab"), &mut out)
+            .unwrap();
+        assert_eq!(b"Y X".to_vec(), out);
+    }
+
+    #[test]
+    fn build_errors() {
+        let args = parse(&["p", "r"]).unwrap().unwrap();
+        assert!(args.build(b"a\nb\n", Some(b"x\n")).is_err());
+        assert!(args.build(b"\\xZZ\n", Some(b"x\n")).is_err());
+        let args = parse(&["p"]).unwrap().unwrap();
+        assert!(args.build(b"no tab\n", None).is_err());
+    }
+}
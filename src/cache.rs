@@ -0,0 +1,789 @@
+/*!
+Provides a cache of built automata, for services that build searchers from
+the same dictionaries over and over.
+
+Building an automaton is much more expensive than searching with it. When the
+patterns come from tenant or request specific dictionaries, many of them are
+often the same, and an [`AutomatonCache`] avoids rebuilding them. It maps the
+hash of a pattern set, along with the options of the builder, to the automaton
+built from them.
+
+Entries are evicted once the cache holds more than its capacity, starting
+from the least recently used one, and optionally once they are older than a
+time to live. [`AutomatonCache::stats`] reports the number of hits, misses and
+evictions, from which the hit rate is computed.
+
+# Persistence
+
+Automata may also be persisted in a directory, so that a new process doesn't
+have to build them again, with [`AutomatonCache::directory`] or
+[`build_cached`]. Each automaton is written to its own file, named after the
+hash of its patterns and options, which also records the patterns and options
+themselves along with a checksum. Files are written to a temporary file
+first and then renamed, so that concurrent processes sharing a directory
+never read a partially written file.
+
+Reading an automaton is much faster than building it, but the format is
+specific to the version of this crate that wrote it. Files that can't be read
+for any reason, including files written by another version, are ignored and
+the automaton is built again. Only automata using a
+[`ContiguousNFA`](crate::AhoCorasickKind::ContiguousNFA) or a
+[`DFA`](crate::AhoCorasickKind::DFA) are persisted, since a noncontiguous NFA
+is about as fast to build as it would be to read. Prefilters aren't persisted
+either, and are rebuilt from the patterns.
+
+This module is only available when the `std` feature is enabled.
+
+# Example
+
+```
+use aho_corasick::{cache::AutomatonCache, AhoCorasick, MatchKind};
+
+let cache = AutomatonCache::new(100);
+let mut builder = AhoCorasick::builder();
+builder.match_kind(MatchKind::LeftmostFirst);
+
+let ac = cache.get_or_build(&builder, &["Sherlock", "Watson"])?;
+assert_eq!(1, ac.find("Dr. Watson").unwrap().pattern().as_usize());
+// The same patterns with the same options are only built once.
+cache.get_or_build(&builder, &["Sherlock", "Watson"])?;
+// But different options build another automaton.
+cache.get_or_build(&AhoCorasick::builder(), &["Sherlock", "Watson"])?;
+
+let stats = cache.stats();
+assert_eq!((1, 2), (stats.hits(), stats.misses()));
+assert_eq!(2, cache.len());
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+use crate::{
+    ahocorasick::{AhoCorasick, AhoCorasickBuilder},
+    util::{
+        error::BuildError,
+        sync::{Mutex, MutexGuard},
+        wire::{self, Reader, Writer},
+    },
+};
+
+/// Returns the automaton built with the given builder from the given
+/// patterns, reading it from the given directory if it was persisted there
+/// before, and persisting it there otherwise.
+///
+/// This is the same as a lookup in an [`AutomatonCache`] with a directory
+/// and no capacity. See the [module documentation](self#persistence) for how
+/// automata are persisted.
+///
+/// Failures to read or write the directory are ignored, so that this only
+/// fails when the automaton fails to build.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{cache, AhoCorasick, AhoCorasickKind};
+///
+/// let dir = std::env::temp_dir().join("aho-corasick-build-cached-doctest");
+/// let mut builder = AhoCorasick::builder();
+/// builder.kind(Some(AhoCorasickKind::DFA));
+///
+/// // The first call builds the automaton and persists it, while the second
+/// // one reads it.
+/// let built = cache::build_cached(&["Sherlock", "Watson"], &builder, &dir)?;
+/// let read = cache::build_cached(&["Sherlock", "Watson"], &builder, &dir)?;
+/// assert_eq!(built.find("Dr. Watson"), read.find("Dr. Watson"));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+///
+/// This returns an error when the automaton fails to build.
+pub fn build_cached<I, P, D>(
+    patterns: I,
+    options: &AhoCorasickBuilder,
+    dir: D,
+) -> Result<AhoCorasick, BuildError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+    D: AsRef<Path>,
+{
+    let patterns: Vec<Vec<u8>> =
+        patterns.into_iter().map(|p| p.as_ref().to_vec()).collect();
+    let key = options.options_key();
+    let artifact =
+        Artifact { dir: dir.as_ref(), options: &key, patterns: &patterns };
+    if let Some(ac) = artifact.read(options) {
+        return Ok(ac);
+    }
+    let ac = options.build(&patterns)?;
+    // This is only an optimization for the next process, so errors are
+    // ignored.
+    let _ = artifact.write(&ac);
+    Ok(ac)
+}
+
+/// A thread safe cache of automata, keyed by the hash of their patterns and
+/// the options they were built with.
+///
+/// See the [module documentation](self) for an overview.
+///
+/// Automata are cheap to clone, since they share their internal state, so
+/// the cache hands out clones of the automata it holds.
+#[derive(Debug)]
+pub struct AutomatonCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    directory: Option<PathBuf>,
+    inner: Mutex<Inner>,
+}
+
+/// The entries and statistics of a cache, behind its lock.
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<Key, Entry>,
+    /// Incremented on every use of an entry, to find the least recently
+    /// used one.
+    clock: u64,
+    stats: CacheStats,
+}
+
+/// Identifies the automata that may be shared by a lookup.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Key {
+    /// The hash of the patterns.
+    patterns: u64,
+    /// The options of the builder. See `AhoCorasickBuilder::options_key`.
+    options: Vec<u8>,
+}
+
+/// An automaton in the cache.
+#[derive(Debug)]
+struct Entry {
+    ac: AhoCorasick,
+    /// The patterns the automaton was built from, to tell them apart from
+    /// other patterns with the same hash.
+    patterns: Vec<Vec<u8>>,
+    created: Instant,
+    last_used: u64,
+}
+
+impl AutomatonCache {
+    /// Create a new cache that holds at most `capacity` automata.
+    ///
+    /// A capacity of `0` never holds any automaton, so that every lookup
+    /// builds a new one.
+    pub fn new(capacity: usize) -> AutomatonCache {
+        AutomatonCache {
+            capacity,
+            ttl: None,
+            directory: None,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Evict the automata that were built more than the given duration ago,
+    /// or never evict them because of their age when `None`.
+    ///
+    /// This is `None` by default.
+    pub fn ttl(mut self, ttl: Option<Duration>) -> AutomatonCache {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Persist automata in the given directory, or only hold them in memory
+    /// when `None`.
+    ///
+    /// When set, an automaton that isn't in memory is read from the
+    /// directory if it was persisted there, which counts as a
+    /// [disk hit](CacheStats::disk_hits), and every automaton built is
+    /// persisted there. The directory is created when needed, and may be
+    /// shared by several processes. See the
+    /// [module documentation](self#persistence) for how automata are
+    /// persisted.
+    ///
+    /// This is `None` by default.
+    pub fn directory(mut self, dir: Option<PathBuf>) -> AutomatonCache {
+        self.directory = dir;
+        self
+    }
+
+    /// Returns the automaton built with the given builder from the given
+    /// patterns, building it only if the cache doesn't already hold it.
+    ///
+    /// Only the options that affect the automaton itself tell cached
+    /// automata apart. The searcher returned shares a cached automaton, but
+    /// is given the other options of the given builder, such as its
+    /// [verifier](AhoCorasickBuilder::verify) or its
+    /// [lookaheads](AhoCorasickBuilder::lookahead).
+    ///
+    /// The lock of the cache isn't held while an automaton is built, so
+    /// concurrent lookups of automata that aren't in the cache may build
+    /// the same one more than once.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when the automaton fails to build. Errors
+    /// aren't cached. Failures to read or write the
+    /// [directory](AutomatonCache::directory) are ignored.
+    pub fn get_or_build<I, P>(
+        &self,
+        builder: &AhoCorasickBuilder,
+        patterns: I,
+    ) -> Result<AhoCorasick, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let patterns: Vec<Vec<u8>> =
+            patterns.into_iter().map(|p| p.as_ref().to_vec()).collect();
+        let mut hasher = DefaultHasher::new();
+        patterns.hash(&mut hasher);
+        let key =
+            Key { patterns: hasher.finish(), options: builder.options_key() };
+        if self.capacity > 0 {
+            if let Some(ac) = self.get(&key, &patterns) {
+                return Ok(builder.reuse_automaton(&ac, &patterns));
+            }
+        }
+        let artifact = self.directory.as_ref().map(|dir| Artifact {
+            dir,
+            options: &key.options,
+            patterns: &patterns,
+        });
+        if let Some(ac) = artifact.as_ref().and_then(|a| a.read(builder)) {
+            self.lock().stats.disk_hits += 1;
+            self.insert(key, patterns, ac.clone());
+            return Ok(ac);
+        }
+        self.lock().stats.misses += 1;
+        let ac = builder.build(&patterns)?;
+        if let Some(ref artifact) = artifact {
+            let _ = artifact.write(&ac);
+        }
+        self.insert(key, patterns, ac.clone());
+        Ok(ac)
+    }
+
+    /// Returns the number of automata in the cache, including the ones that
+    /// expired but weren't evicted yet.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns true if the cache holds no automaton.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every automaton from the cache. The statistics are kept.
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// Returns the statistics of the lookups made so far.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Returns the automaton of the given key if it holds the given
+    /// patterns and hasn't expired, and counts it as a hit.
+    fn get(&self, key: &Key, patterns: &[Vec<u8>]) -> Option<AhoCorasick> {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        let expired = match inner.entries.get(key) {
+            Some(entry) => self.is_expired(entry),
+            None => false,
+        };
+        if expired {
+            inner.entries.remove(key);
+            inner.stats.evictions += 1;
+        }
+        inner.clock += 1;
+        match inner.entries.get_mut(key) {
+            Some(entry) if entry.patterns == patterns => {
+                entry.last_used = inner.clock;
+                inner.stats.hits += 1;
+                Some(entry.ac.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Add an automaton to the cache, replacing any automaton with the same
+    /// key, and evict the expired and least recently used automata that
+    /// don't fit anymore.
+    fn insert(&self, key: Key, patterns: Vec<Vec<u8>>, ac: AhoCorasick) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        inner.clock += 1;
+        let entry = Entry {
+            ac,
+            patterns,
+            created: Instant::now(),
+            last_used: inner.clock,
+        };
+        inner.entries.insert(key, entry);
+        if self.ttl.is_some() {
+            let before = inner.entries.len();
+            inner.entries.retain(|_, entry| !self.is_expired(entry));
+            inner.stats.evictions += (before - inner.entries.len()) as u64;
+        }
+        while inner.entries.len() > self.capacity {
+            let lru = inner
+                .entries
+                .iter()
+                .min_by_key(|&(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            inner.entries.remove(&lru);
+            inner.stats.evictions += 1;
+        }
+    }
+
+    fn is_expired(&self, entry: &Entry) -> bool {
+        self.ttl.map_or(false, |ttl| entry.created.elapsed() >= ttl)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // The entries are always consistent between statements, so a panic
+        // while the lock was held doesn't make them unusable.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The statistics of the lookups made in an [`AutomatonCache`].
+///
+/// This is returned by [`AutomatonCache::stats`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    hits: u64,
+    disk_hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl CacheStats {
+    /// Returns the number of lookups that returned an automaton from the
+    /// cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of lookups that read an automaton from the
+    /// [directory](AutomatonCache::directory) of the cache.
+    pub fn disk_hits(&self) -> u64 {
+        self.disk_hits
+    }
+
+    /// Returns the number of lookups that built an automaton, including the
+    /// ones that failed to build.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the number of automata evicted because the cache was full or
+    /// because they expired.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Returns the fraction of lookups that returned an automaton from the
+    /// cache, in memory or on disk, or `0.0` if there were no lookups.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits + self.disk_hits;
+        let lookups = hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        hits as f64 / lookups as f64
+    }
+}
+
+/// Identifies the file that an automaton is persisted to.
+#[derive(Debug)]
+struct Artifact<'a> {
+    dir: &'a Path,
+    /// The options of the builder. See `AhoCorasickBuilder::options_key`.
+    options: &'a [u8],
+    patterns: &'a [Vec<u8>],
+}
+
+impl<'a> Artifact<'a> {
+    /// Identifies the format of a file, and whether it's one at all.
+    const MAGIC: &'static [u8; 8] = b"ACAUTO\0\0";
+    /// The version of the encoding of automata, shared with the bytes
+    /// written by the `to_bytes` methods of the automata.
+    const VERSION: u32 = wire::VERSION;
+
+    /// Read the automaton persisted in this artifact, or return `None` if it
+    /// doesn't exist or is invalid.
+    fn read(&self, builder: &AhoCorasickBuilder) -> Option<AhoCorasick> {
+        let header = self.header();
+        let data = fs::read(self.path(&header)).ok()?;
+        let (body, checksum) = data.split_at(data.len().checked_sub(8)?);
+        let mut checksum = Reader::new(checksum);
+        // The header encodes both the patterns and the options, so this also
+        // rules out a file of other patterns with the same name.
+        if checksum.u64()? != wire::fnv1a(body)
+            || !body.starts_with(header.as_bytes())
+        {
+            return None;
+        }
+        let mut r = Reader::new(&body[header.len()..]);
+        let ac = builder.read_automaton(self.patterns, &mut r)?;
+        if !r.is_empty() {
+            return None;
+        }
+        Some(ac)
+    }
+
+    /// Persist the given automaton in this artifact, unless its kind can't
+    /// be persisted.
+    ///
+    /// The automaton is written to a temporary file in the same directory,
+    /// which is then renamed, so that the artifact is replaced atomically.
+    fn write(&self, ac: &AhoCorasick) -> io::Result<()> {
+        static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+        let mut w = self.header();
+        let path = self.path(&w);
+        if !ac.write_automaton(&mut w) {
+            return Ok(());
+        }
+        let checksum = wire::fnv1a(w.as_bytes());
+        w.u64(checksum);
+
+        fs::create_dir_all(self.dir)?;
+        let temp = path.with_extension(std::format!(
+            "tmp.{}.{}",
+            std::process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed),
+        ));
+        let result = fs::File::create(&temp).and_then(|mut file| {
+            file.write_all(w.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temp, &path)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Returns the header of the file, which identifies the automaton it
+    /// holds.
+    fn header(&self) -> Writer {
+        let mut w = Writer::new();
+        w.raw(Artifact::MAGIC);
+        w.u32(Artifact::VERSION);
+        w.bytes(self.options);
+        w.usize(self.patterns.len());
+        for pattern in self.patterns.iter() {
+            w.bytes(pattern);
+        }
+        w
+    }
+
+    /// Returns the path of the file with the given header.
+    fn path(&self, header: &Writer) -> PathBuf {
+        let name = std::format!("{:016x}.ac", wire::fnv1a(header.as_bytes()));
+        self.dir.join(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{time::Duration, vec};
+
+    use crate::{
+        util::rng::Rng, AhoCorasickKind, Anchored, Input, MatchKind, StartKind,
+    };
+
+    use super::*;
+
+    /// Returns an empty directory for the given test.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(std::format!(
+            "aho-corasick-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn matches(
+        ac: &AhoCorasick,
+        haystack: &[u8],
+    ) -> Vec<(usize, usize, usize)> {
+        let mut matches = vec![];
+        for anchored in [Anchored::No, Anchored::Yes] {
+            let input = Input::new(haystack).anchored(anchored);
+            matches.extend(
+                ac.find_iter(input.clone())
+                    .map(|m| (m.pattern().as_usize(), m.start(), m.end())),
+            );
+            if ac.match_kind() == MatchKind::Standard
+                && !anchored.is_anchored()
+            {
+                matches.extend(
+                    ac.try_find_overlapping_iter(input)
+                        .unwrap()
+                        .map(|m| (m.pattern().as_usize(), m.start(), m.end())),
+                );
+            }
+        }
+        matches
+    }
+
+    #[test]
+    fn persisted_automata_search_like_built_ones() {
+        let dir = temp_dir("persisted");
+        let mut rng = Rng::new(0x2545F4914F6CDD1D);
+        for i in 0..200 {
+            let patterns: Vec<Vec<u8>> = (0..1 + rng.below(6))
+                .map(|_| rng.word(0, 4, b"abcAB"))
+                .collect();
+            let mut builder = AhoCorasick::builder();
+            builder
+                .kind(Some(if i % 2 == 0 {
+                    AhoCorasickKind::DFA
+                } else {
+                    AhoCorasickKind::ContiguousNFA
+                }))
+                .match_kind(
+                    [
+                        MatchKind::Standard,
+                        MatchKind::LeftmostFirst,
+                        MatchKind::LeftmostLongest,
+                    ][rng.below(3)],
+                )
+                .start_kind(StartKind::Both)
+                .ascii_case_insensitive(rng.below(2) == 0)
+                .byte_classes(rng.below(2) == 0)
+                .prefilter(rng.below(2) == 0);
+            let cache = AutomatonCache::new(0).directory(Some(dir.clone()));
+            let built = cache.get_or_build(&builder, &patterns).unwrap();
+            let read = cache.get_or_build(&builder, &patterns).unwrap();
+            assert_eq!(
+                (1, 1),
+                (cache.stats().misses(), cache.stats().disk_hits())
+            );
+            assert_eq!(built.kind(), read.kind());
+            for _ in 0..5 {
+                let haystack = rng.word(0, 20, b"abcAB");
+                assert_eq!(
+                    matches(&built, &haystack),
+                    matches(&read, &haystack),
+                    "{:?} {:?}",
+                    patterns,
+                    haystack
+                );
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_artifacts_are_rebuilt() {
+        let dir = temp_dir("invalid");
+        let patterns = ["foo", "bar", "foobar"];
+        let mut builder = AhoCorasick::builder();
+        builder.kind(Some(AhoCorasickKind::DFA));
+        let expected: Vec<usize> = builder
+            .build(patterns)
+            .unwrap()
+            .find_iter("xfoobarbarfoo")
+            .map(|m| m.pattern().as_usize())
+            .collect();
+        build_cached(patterns, &builder, &dir).unwrap();
+        let files: Vec<PathBuf> =
+            fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(1, files.len());
+        let valid = fs::read(&files[0]).unwrap();
+
+        let mut invalid = vec![valid[..valid.len() - 1].to_vec(), vec![]];
+        for &at in [0, 8, 20, valid.len() / 2, valid.len() - 1].iter() {
+            let mut data = valid.clone();
+            data[at] ^= 0x10;
+            invalid.push(data);
+        }
+        for data in invalid {
+            fs::write(&files[0], &data).unwrap();
+            let cache = AutomatonCache::new(1).directory(Some(dir.clone()));
+            let ac = cache.get_or_build(&builder, patterns).unwrap();
+            let found: Vec<usize> = ac
+                .find_iter("xfoobarbarfoo")
+                .map(|m| m.pattern().as_usize())
+                .collect();
+            assert_eq!(expected, found);
+            assert_eq!(0, cache.stats().disk_hits());
+            // The rebuilt automaton replaces the invalid file.
+            assert_eq!(valid, fs::read(&files[0]).unwrap());
+        }
+
+        // Other patterns or options never read that file, and automata
+        // using a noncontiguous NFA aren't persisted.
+        let cache = AutomatonCache::new(0).directory(Some(dir.clone()));
+        cache.get_or_build(&builder, ["foo", "bar"]).unwrap();
+        builder.kind(Some(AhoCorasickKind::NoncontiguousNFA));
+        cache.get_or_build(&builder, patterns).unwrap();
+        cache.get_or_build(&builder, patterns).unwrap();
+        assert_eq!(
+            (0, 3),
+            (cache.stats().disk_hits(), cache.stats().misses())
+        );
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tampered_contiguous_nfas_are_rebuilt() {
+        let dir = temp_dir("tampered");
+        let patterns = ["a", "bcd"];
+        let mut builder = AhoCorasick::builder();
+        builder.kind(Some(AhoCorasickKind::ContiguousNFA));
+        build_cached(patterns, &builder, &dir).unwrap();
+        let files: Vec<PathBuf> =
+            fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(1, files.len());
+        let valid = fs::read(&files[0]).unwrap();
+
+        // Make the state of 'a' report 'bcd' instead, with a valid checksum.
+        // Its matches would then start before the start of the haystack.
+        let packed = (1u32 << 31).to_le_bytes();
+        let at: Vec<usize> = (0..valid.len() - 8)
+            .filter(|&at| valid[at..].starts_with(&packed))
+            .collect();
+        assert_eq!(1, at.len());
+        let mut data = valid.clone();
+        data[at[0]] = 1;
+        let body_len = data.len() - 8;
+        let checksum = wire::fnv1a(&data[..body_len]);
+        data[body_len..].copy_from_slice(&checksum.to_le_bytes());
+        fs::write(&files[0], &data).unwrap();
+
+        let cache = AutomatonCache::new(1).directory(Some(dir.clone()));
+        let ac = cache.get_or_build(&builder, patterns).unwrap();
+        let found: Vec<(usize, usize)> =
+            ac.find_iter("abcd").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(vec![(0, 1), (1, 4)], found);
+        assert_eq!(0, cache.stats().disk_hits());
+        assert_eq!(valid, fs::read(&files[0]).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn options_key_is_stable() {
+        let configure = |builder: &mut AhoCorasickBuilder| {
+            builder
+                .kind(Some(AhoCorasickKind::DFA))
+                .match_kind(MatchKind::LeftmostLongest)
+                .start_kind(StartKind::Both)
+                .ascii_case_insensitive(true)
+                .dense_depth(3);
+        };
+        // Equal options set separately, in another order, and along with
+        // options that don't affect the automaton give the same key.
+        let mut first = AhoCorasick::builder();
+        configure(&mut first);
+        let mut second = AhoCorasick::builder();
+        second
+            .lookahead(crate::PatternID::ZERO, None)
+            .replacement_tables([["x"]])
+            .dense_depth(3)
+            .verify(|_, _| true);
+        configure(&mut second);
+        assert_eq!(first.options_key(), second.options_key());
+
+        // Any option that affects the automaton changes the key.
+        let key = first.options_key();
+        assert_ne!(key, first.clone().byte_classes(false).options_key());
+        assert_ne!(key, first.clone().prefilter(false).options_key());
+        assert_ne!(
+            key,
+            first.clone().start_kind(StartKind::Anchored).options_key()
+        );
+        assert_ne!(key, first.clone().kind(None).options_key());
+
+        // The key is versioned, so that automata persisted with another
+        // encoding of the options are never read.
+        assert_eq!(
+            &AhoCorasickBuilder::OPTIONS_KEY_VERSION.to_le_bytes(),
+            &key[..4],
+        );
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = AutomatonCache::new(2);
+        let builder = AhoCorasick::builder();
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        cache.get_or_build(&builder, ["b"]).unwrap();
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        // This evicts "b", which was used less recently than "a".
+        cache.get_or_build(&builder, ["c"]).unwrap();
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        cache.get_or_build(&builder, ["b"]).unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(
+            (2, 4, 2),
+            (stats.hits(), stats.misses(), stats.evictions())
+        );
+        assert_eq!(2.0 / 6.0, stats.hit_rate());
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn expired_automata_are_rebuilt() {
+        let builder = AhoCorasick::builder();
+        let cache = AutomatonCache::new(10).ttl(Some(Duration::ZERO));
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        let stats = cache.stats();
+        // Each automaton expires as soon as it is added.
+        assert_eq!(
+            (0, 2, 2),
+            (stats.hits(), stats.misses(), stats.evictions())
+        );
+
+        // The verifier doesn't affect the automaton, so a searcher with a
+        // verifier shares the automaton of one without, but is given its
+        // own verifier.
+        let cache = AutomatonCache::new(10).ttl(Some(Duration::from_secs(60)));
+        let mut verified = AhoCorasick::builder();
+        verified.verify(|_, _| false);
+        let ac = cache.get_or_build(&builder, ["a"]).unwrap();
+        assert!(ac.is_match("a"));
+        let ac = cache.get_or_build(&verified, ["a"]).unwrap();
+        assert!(!ac.is_match("a"));
+        let ac = cache.get_or_build(&builder, ["a"]).unwrap();
+        assert!(ac.is_match("a"));
+        assert_eq!((2, 1), (cache.stats().hits(), cache.stats().misses()));
+        assert_eq!(1, cache.len());
+
+        // A cache without capacity never holds anything.
+        let cache = AutomatonCache::new(0);
+        assert_eq!(0.0, cache.stats().hit_rate());
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        cache.get_or_build(&builder, ["a"]).unwrap();
+        assert!(cache.is_empty());
+        assert_eq!(2, cache.stats().misses());
+    }
+}
@@ -0,0 +1,406 @@
+/*!
+Checks that replacements agree with the upstream `aho-corasick` crate.
+
+This module is only available when the `conformance` feature is enabled. It
+depends on the version of `aho-corasick` published on crates.io, and uses its
+[`replace_all_bytes`](upstream::AhoCorasick::replace_all_bytes) as the
+reference for the replacements made by this crate. A [`Checker`] builds an
+automaton with each crate from the same patterns and options, and compares
+the output of:
+
+* [`AhoCorasick::try_replace_all_bytes`], for every match kind.
+* An [`AhoCorasickReplacer`](crate::AhoCorasickReplacer) fed with the
+  haystack split into chunks, for [`MatchKind::Standard`] and
+  [`MatchKind::LeftmostLongest`] semantics, which are the ones supported for
+  replacing chunks.
+* [`AhoCorasick::try_stream_replace_all`], reading the haystack in the same
+  chunks, for [`MatchKind::Standard`] semantics, which is the only one
+  supported for stream replacements, and when no pattern is empty.
+
+So with [`MatchKind::LeftmostFirst`] semantics, only
+[`AhoCorasick::try_replace_all_bytes`] is checked.
+
+The checks are meant to be run on arbitrary inputs, for example from a fuzzer
+or a property test, by downstream packagers as well as by this crate's own
+tests.
+
+# Example
+
+```
+use aho_corasick::{conformance::Checker, MatchKind};
+
+let mut checker = Checker::new();
+checker.match_kind(MatchKind::Standard).ascii_case_insensitive(true);
+let result = checker.check(
+    &["fox", "brown", ""],
+    &["bear", "white", "-"],
+    b"The Brown Fox.",
+    &[3, 1, 0, 5],
+);
+assert!(result.is_ok());
+```
+*/
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    ahocorasick::{AhoCorasick, AhoCorasickKind},
+    util::search::MatchKind,
+};
+
+/// The options of the automata that a conformance check compares.
+///
+/// The same options are given to the builders of both crates. By default,
+/// this uses [`MatchKind::Standard`] semantics, is case sensitive, and lets
+/// each crate choose the kind of automaton.
+#[derive(Clone, Debug)]
+pub struct Checker {
+    match_kind: MatchKind,
+    ascii_case_insensitive: bool,
+    kind: Option<AhoCorasickKind>,
+}
+
+impl Default for Checker {
+    fn default() -> Checker {
+        Checker::new()
+    }
+}
+
+impl Checker {
+    /// Create a new checker with the default options.
+    pub fn new() -> Checker {
+        Checker {
+            match_kind: MatchKind::Standard,
+            ascii_case_insensitive: false,
+            kind: None,
+        }
+    }
+
+    /// Set the match semantics of the automata.
+    ///
+    /// See [`AhoCorasickBuilder::match_kind`](crate::AhoCorasickBuilder::match_kind).
+    pub fn match_kind(&mut self, kind: MatchKind) -> &mut Checker {
+        self.match_kind = kind;
+        self
+    }
+
+    /// Enable ASCII-aware case insensitive matching.
+    ///
+    /// See [`AhoCorasickBuilder::ascii_case_insensitive`](crate::AhoCorasickBuilder::ascii_case_insensitive).
+    pub fn ascii_case_insensitive(&mut self, yes: bool) -> &mut Checker {
+        self.ascii_case_insensitive = yes;
+        self
+    }
+
+    /// Set the kind of automaton to build, or let each crate choose it when
+    /// `None`.
+    ///
+    /// See [`AhoCorasickBuilder::kind`](crate::AhoCorasickBuilder::kind).
+    pub fn kind(&mut self, kind: Option<AhoCorasickKind>) -> &mut Checker {
+        self.kind = kind;
+        self
+    }
+
+    /// Check that this crate replaces the matches of the given patterns in
+    /// the haystack in the same way as the upstream crate does, where the
+    /// pattern with ID `i` is replaced by `replacements[i]`.
+    ///
+    /// The haystack is given to the streaming replacers in chunks of the
+    /// given sizes, which are repeated until the haystack is exhausted. A
+    /// size of `0` gives an empty chunk to an
+    /// [`AhoCorasickReplacer`](crate::AhoCorasickReplacer), and is skipped
+    /// when reading. When there are no sizes, or only sizes of `0`, the
+    /// haystack is given as a single chunk.
+    ///
+    /// Automata that fail to build with both crates, such as when there are
+    /// too many patterns for the automaton kind, are not a divergence.
+    ///
+    /// Chunks are only replaced with the match kinds that support it, as
+    /// listed in the [module documentation](self). With
+    /// [`MatchKind::LeftmostFirst`] semantics, the chunk sizes are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This returns the first divergence found, if any.
+    ///
+    /// # Panics
+    ///
+    /// This panics when the number of replacements is different from the
+    /// number of patterns.
+    pub fn check<P, R>(
+        &self,
+        patterns: &[P],
+        replacements: &[R],
+        haystack: &[u8],
+        chunk_sizes: &[usize],
+    ) -> Result<(), Divergence>
+    where
+        P: AsRef<[u8]>,
+        R: AsRef<[u8]>,
+    {
+        assert_eq!(
+            patterns.len(),
+            replacements.len(),
+            "there must be one replacement for every pattern",
+        );
+        let (ours, theirs) =
+            match (self.build(patterns), self.upstream(patterns)) {
+                (Ok(ours), Ok(theirs)) => (ours, theirs),
+                (Err(_), Err(_)) => return Ok(()),
+                (ours, theirs) => {
+                    return Err(Divergence {
+                        mode: Mode::Build,
+                        expected: vec![],
+                        actual: vec![],
+                        built: (ours.is_ok(), theirs.is_ok()),
+                    });
+                }
+            };
+        let expected = theirs.replace_all_bytes(haystack, replacements);
+        let divergence = |mode, actual: Vec<u8>| {
+            if actual == expected {
+                return Ok(());
+            }
+            Err(Divergence {
+                mode,
+                expected: expected.clone(),
+                actual,
+                built: (true, true),
+            })
+        };
+        // This can only fail when the automaton doesn't support unanchored
+        // searches, which is never the case here.
+        let actual =
+            ours.try_replace_all_bytes(haystack, replacements).unwrap();
+        divergence(Mode::ReplaceAll, actual)?;
+        // Replacers don't support leftmost-first semantics.
+        if self.match_kind == MatchKind::LeftmostFirst {
+            return Ok(());
+        }
+
+        let chunks = split(haystack, chunk_sizes);
+        let mut replacer = ours.replacer(replacements).unwrap();
+        let mut actual = vec![];
+        for chunk in chunks.iter() {
+            actual.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        actual.extend_from_slice(replacer.finish().unwrap());
+        divergence(Mode::Replacer, actual)?;
+
+        // Stream replacements only support standard semantics, and stream
+        // searches don't support empty patterns.
+        if self.match_kind != MatchKind::Standard
+            || ours.min_pattern_len() == 0
+        {
+            return Ok(());
+        }
+        let mut actual = vec![];
+        let rdr = ChunkReader { chunks: chunks.into_iter().rev().collect() };
+        ours.try_stream_replace_all(rdr, &mut actual, replacements).unwrap();
+        divergence(Mode::StreamReplaceAll, actual)
+    }
+
+    /// Build an automaton with this crate.
+    fn build<P: AsRef<[u8]>>(
+        &self,
+        patterns: &[P],
+    ) -> Result<AhoCorasick, crate::BuildError> {
+        AhoCorasick::builder()
+            .match_kind(self.match_kind)
+            .ascii_case_insensitive(self.ascii_case_insensitive)
+            .kind(self.kind)
+            .build(patterns)
+    }
+
+    /// Build an automaton with the upstream crate.
+    fn upstream<P: AsRef<[u8]>>(
+        &self,
+        patterns: &[P],
+    ) -> Result<upstream::AhoCorasick, upstream::BuildError> {
+        let match_kind = match self.match_kind {
+            MatchKind::Standard => upstream::MatchKind::Standard,
+            MatchKind::LeftmostFirst => upstream::MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest => upstream::MatchKind::LeftmostLongest,
+        };
+        let kind = self.kind.map(|kind| match kind {
+            AhoCorasickKind::NoncontiguousNFA => {
+                upstream::AhoCorasickKind::NoncontiguousNFA
+            }
+            AhoCorasickKind::ContiguousNFA => {
+                upstream::AhoCorasickKind::ContiguousNFA
+            }
+            AhoCorasickKind::DFA => upstream::AhoCorasickKind::DFA,
+        });
+        upstream::AhoCorasick::builder()
+            .match_kind(match_kind)
+            .ascii_case_insensitive(self.ascii_case_insensitive)
+            .kind(kind)
+            .build(patterns)
+    }
+}
+
+/// The way of replacing matches in which a [`Divergence`] was found.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Only one of the crates built the automaton.
+    Build,
+    /// The replacements made by [`AhoCorasick::try_replace_all_bytes`].
+    ReplaceAll,
+    /// The replacements made by an
+    /// [`AhoCorasickReplacer`](crate::AhoCorasickReplacer).
+    Replacer,
+    /// The replacements made by [`AhoCorasick::try_stream_replace_all`].
+    StreamReplaceAll,
+}
+
+/// A difference between the replacements made by this crate and by the
+/// upstream crate.
+///
+/// This is returned by [`Checker::check`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Divergence {
+    mode: Mode,
+    expected: Vec<u8>,
+    actual: Vec<u8>,
+    /// Whether the automaton was built by this crate and by the upstream
+    /// crate.
+    built: (bool, bool),
+}
+
+impl Divergence {
+    /// Returns the way of replacing matches that diverged.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the output of the upstream crate.
+    ///
+    /// This is empty when the divergence is in building the automaton.
+    pub fn expected(&self) -> &[u8] {
+        &self.expected
+    }
+
+    /// Returns the output of this crate.
+    ///
+    /// This is empty when the divergence is in building the automaton.
+    pub fn actual(&self) -> &[u8] {
+        &self.actual
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+impl core::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.mode == Mode::Build {
+            let (ours, theirs) = self.built;
+            let status = |built| if built { "built" } else { "failed" };
+            return write!(
+                f,
+                "automaton {} with this crate but {} upstream",
+                status(ours),
+                status(theirs),
+            );
+        }
+        write!(
+            f,
+            "{:?} gave \"{}\" but upstream replace_all gave \"{}\"",
+            self.mode,
+            self.actual.escape_ascii(),
+            self.expected.escape_ascii(),
+        )
+    }
+}
+
+/// Split the haystack into chunks of the given sizes, repeated until the
+/// haystack is exhausted.
+fn split<'h>(mut haystack: &'h [u8], sizes: &[usize]) -> Vec<&'h [u8]> {
+    if sizes.iter().all(|&size| size == 0) {
+        return vec![haystack];
+    }
+    let mut chunks = vec![];
+    for &size in sizes.iter().cycle() {
+        if haystack.is_empty() {
+            break;
+        }
+        let (chunk, rest) =
+            haystack.split_at(core::cmp::min(size, haystack.len()));
+        chunks.push(chunk);
+        haystack = rest;
+    }
+    chunks
+}
+
+/// A reader that returns at most one chunk for every read, skipping empty
+/// chunks since they would signal the end of the stream.
+struct ChunkReader<'h> {
+    /// The chunks that remain to be read, in reverse order.
+    chunks: Vec<&'h [u8]>,
+}
+
+impl<'h> std::io::Read for ChunkReader<'h> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while let Some(chunk) = self.chunks.pop() {
+            if chunk.is_empty() {
+                continue;
+            }
+            let n = core::cmp::min(buf.len(), chunk.len());
+            buf[..n].copy_from_slice(&chunk[..n]);
+            if n < chunk.len() {
+                self.chunks.push(&chunk[n..]);
+            }
+            return Ok(n);
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::util::rng::Rng;
+
+    use super::*;
+
+    #[test]
+    fn replacements_conform_to_upstream() {
+        let mut rng = Rng::new(0x9E3779B97F4A7C15);
+        let kinds = [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ];
+        let automata = [
+            None,
+            Some(AhoCorasickKind::NoncontiguousNFA),
+            Some(AhoCorasickKind::ContiguousNFA),
+            Some(AhoCorasickKind::DFA),
+        ];
+        for _ in 0..500 {
+            let n = rng.below(5);
+            let patterns: Vec<Vec<u8>> =
+                (0..n).map(|_| rng.word(0, 3, b"abA")).collect();
+            let replacements: Vec<Vec<u8>> =
+                (0..n).map(|_| rng.word(0, 4, b"abA")).collect();
+            let haystack = rng.word(0, 24, b"abA");
+            let sizes: Vec<usize> =
+                (0..1 + rng.below(3)).map(|_| rng.below(5)).collect();
+            let mut checker = Checker::new();
+            checker
+                .match_kind(kinds[rng.below(kinds.len())])
+                .ascii_case_insensitive(rng.below(2) == 0)
+                .kind(automata[rng.below(automata.len())]);
+            if let Err(err) =
+                checker.check(&patterns, &replacements, &haystack, &sizes)
+            {
+                panic!(
+                    "{} with {:?}, patterns {:?}, replacements {:?}, \
+                     haystack {:?} and chunks {:?}",
+                    err, checker, patterns, replacements, haystack, sizes,
+                );
+            }
+        }
+    }
+}
@@ -7,19 +7,20 @@ instead of a DFA directly. Using an `DFA` directly is typically only necessary
 when one needs access to the [`Automaton`] trait implementation.
 */
 
-use alloc::{vec, vec::Vec};
+use alloc::{collections::VecDeque, vec, vec::Vec};
 
 use crate::{
     automaton::Automaton,
     nfa::noncontiguous,
     util::{
         alphabet::ByteClasses,
-        error::{BuildError, MatchError},
+        error::{BuildError, DeserializeError, MatchError},
         int::{Usize, U32},
         prefilter::Prefilter,
         primitives::{IteratorIndexExt, PatternID, SmallIndex, StateID},
-        search::{Anchored, MatchKind, StartKind},
+        search::{Anchored, DuplicatePolicy, MatchKind, StartKind},
         special::Special,
+        wire::{self, Reader, Writer},
     },
 };
 
@@ -166,6 +167,27 @@ impl DFA {
     /// than the NFAs in this crate.
     const DEAD: StateID = StateID::new_unchecked(0);
 
+    /// Returns the ID of every state in this DFA, in the order in which they
+    /// appear in the transition table. The FAIL state, which is in the table
+    /// but never used, isn't included.
+    pub(crate) fn state_ids(&self) -> Vec<StateID> {
+        (0..self.state_len)
+            .filter(|&index| index != 1)
+            .map(|index| StateID::new_unchecked(index << self.stride2))
+            .collect()
+    }
+
+    /// Returns the transition of the given state for every byte, in
+    /// ascending order of byte.
+    pub(crate) fn byte_transitions(&self, sid: StateID) -> Vec<(u8, StateID)> {
+        (0..=255u8)
+            .map(|byte| {
+                let class = usize::from(self.byte_classes.get(byte));
+                (byte, self.trans[sid.as_usize() + class])
+            })
+            .collect()
+    }
+
     /// Adds the given pattern IDs as matches to the given state and also
     /// records the added memory usage.
     fn set_matches(
@@ -184,6 +206,218 @@ impl DFA {
     }
 }
 
+impl DFA {
+    /// Identifies the bytes of a serialized DFA.
+    const MAGIC: &'static [u8; 8] = b"ACDFA\0\0\0";
+
+    /// Serialize this DFA into bytes, from which [`DFA::from_bytes`]
+    /// reads it back without building it again.
+    ///
+    /// Building an automaton from many patterns is much more expensive than
+    /// reading it, so this permits to build it once, persist it, and read it
+    /// at startup. Every integer is encoded in little endian byte order, so
+    /// the bytes don't depend on the platform they were written on. They
+    /// begin with a version of the encoding, which is specific to the
+    /// version of this crate, and end with a checksum.
+    ///
+    /// The prefilter of this DFA isn't serialized, since it doesn't have
+    /// the patterns it would be built from. A deserialized DFA finds the
+    /// same matches, but without a prefilter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{automaton::Automaton, dfa::DFA, Input};
+    ///
+    /// let aut = DFA::new(&["Sherlock", "Watson"]).unwrap();
+    /// let bytes = aut.to_bytes();
+    /// let read = DFA::from_bytes(&bytes)?;
+    /// let m = read.try_find(&Input::new("Dr. Watson"))?.unwrap();
+    /// assert_eq!(1, m.pattern().as_usize());
+    ///
+    /// // Corrupted bytes are rejected.
+    /// assert!(DFA::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::seal(DFA::MAGIC, |w| self.write_to(w))
+    }
+
+    /// Deserialize a DFA from bytes written by [`DFA::to_bytes`].
+    ///
+    /// The bytes are checked to describe a DFA that this crate could have
+    /// built, so that searching the DFA never misbehaves, even if the bytes
+    /// were corrupted. The transitions are copied out of the bytes, so they
+    /// may be dropped, or unmapped if they are a memory mapped file, once
+    /// this returns.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the bytes weren't written by
+    /// [`DFA::to_bytes`], if they were written with another version of the
+    /// encoding, or if they are truncated or corrupted.
+    pub fn from_bytes(bytes: &[u8]) -> Result<DFA, DeserializeError> {
+        let mut r = wire::unseal(DFA::MAGIC, bytes)?;
+        match DFA::read_from(&mut r, None) {
+            Some(aut) if r.is_empty() => Ok(aut),
+            _ => Err(DeserializeError::invalid()),
+        }
+    }
+
+    /// Write this DFA, except for its prefilter, to the given writer.
+    ///
+    /// Whether the DFA has a prefilter is written, since its special states
+    /// depend on it, but the prefilter itself is rebuilt from the patterns
+    /// when the DFA is read, if they are known.
+    pub(crate) fn write_to(&self, w: &mut Writer) {
+        w.match_kind(self.match_kind);
+        w.usize(self.state_len);
+        w.raw(self.byte_classes.as_bytes());
+        w.usize(self.min_pattern_len);
+        w.usize(self.max_pattern_len);
+        w.special(&self.special);
+        w.state_ids(&self.trans);
+        w.usize(self.matches.len());
+        for pids in self.matches.iter() {
+            w.pattern_ids(pids);
+        }
+        w.small_indices(&self.pattern_lens);
+        w.bool(self.prefilter.is_some());
+    }
+
+    /// Read a DFA written by `DFA::write_to`, with the given prefilter.
+    ///
+    /// Without a prefilter, an automaton that was written with one is read
+    /// as if it was built without one.
+    ///
+    /// This returns `None` if the input is truncated, or if it doesn't
+    /// describe a DFA this crate could have built. In particular, every
+    /// state ID is checked to point to a state in the transition table,
+    /// since searches may elide bounds checks on them.
+    pub(crate) fn read_from(
+        r: &mut Reader<'_>,
+        prefilter: Option<Prefilter>,
+    ) -> Option<DFA> {
+        let match_kind = r.match_kind()?;
+        let state_len = r.usize()?;
+        let mut classes = [0; 256];
+        classes.copy_from_slice(r.raw(256)?);
+        let byte_classes = ByteClasses::from_bytes(classes)?;
+        let min_pattern_len = r.usize()?;
+        let max_pattern_len = r.usize()?;
+        let mut special = r.special()?;
+        let trans = r.state_ids()?;
+        let matches_len = r.usize()?;
+        let mut matches = vec![];
+        for _ in 0..matches_len {
+            matches.push(r.pattern_ids()?);
+        }
+        let pattern_lens = r.small_indices()?;
+        match (r.bool()?, prefilter.is_some()) {
+            (false, true) => return None,
+            // Start states are only special when there is a prefilter, so
+            // they become ordinary states once it's dropped, as if the
+            // automaton was built without one.
+            (true, false) => special.max_special_id = special.max_match_id,
+            // Without a prefilter, only the dead and match states are
+            // special, which searches rely on.
+            (false, false)
+                if special.max_special_id != special.max_match_id =>
+            {
+                return None
+            }
+            _ => {}
+        }
+
+        let stride2 = byte_classes.stride2();
+        if state_len.checked_mul(byte_classes.stride())? != trans.len() {
+            return None;
+        }
+        let is_valid = |sid: StateID| {
+            sid.as_usize() < trans.len()
+                && sid.as_usize() & (byte_classes.stride() - 1) == 0
+        };
+        if !trans.iter().all(|&sid| is_valid(sid))
+            || !is_valid(special.max_special_id)
+            || !is_valid(special.max_match_id)
+            || !is_valid(special.start_unanchored_id)
+            || !is_valid(special.start_anchored_id)
+        {
+            return None;
+        }
+        let match_states =
+            (special.max_match_id.as_usize() >> stride2).saturating_sub(1);
+        if matches.len() < match_states
+            || matches
+                .iter()
+                .flatten()
+                .any(|pid| pid.as_usize() >= pattern_lens.len())
+        {
+            return None;
+        }
+        // Searches compute the start of a match by subtracting the length of
+        // its pattern from its end, so every pattern of a match state must be
+        // no longer than the fewest transitions leading to the state from a
+        // start state. Every match state reached must also have a pattern,
+        // and the FAIL state must never be reached.
+        let mut depths = vec![usize::MAX; state_len];
+        let mut queue = VecDeque::new();
+        for &sid in
+            [special.start_unanchored_id, special.start_anchored_id].iter()
+        {
+            let index = sid.as_usize() >> stride2;
+            if depths[index] == usize::MAX {
+                depths[index] = 0;
+                queue.push_back(index);
+            }
+        }
+        while let Some(index) = queue.pop_front() {
+            let row = index << stride2;
+            for &next in trans[row..row + byte_classes.alphabet_len()].iter() {
+                let next_index = next.as_usize() >> stride2;
+                if depths[next_index] == usize::MAX {
+                    depths[next_index] = depths[index] + 1;
+                    queue.push_back(next_index);
+                }
+            }
+        }
+        for (index, &depth) in depths.iter().enumerate().skip(1) {
+            if depth == usize::MAX {
+                continue;
+            }
+            if index == 1 {
+                return None;
+            }
+            if (index << stride2) > special.max_match_id.as_usize() {
+                continue;
+            }
+            let pids = &matches[index - 2];
+            if pids.is_empty()
+                || pids.iter().any(|&pid| pattern_lens[pid].as_usize() > depth)
+            {
+                return None;
+            }
+        }
+        let matches_memory_usage =
+            matches.iter().map(|pids| pids.len() * PatternID::SIZE).sum();
+        Some(DFA {
+            trans,
+            matches,
+            matches_memory_usage,
+            pattern_lens,
+            prefilter,
+            match_kind,
+            state_len,
+            alphabet_len: byte_classes.alphabet_len(),
+            stride2,
+            byte_classes,
+            min_pattern_len,
+            max_pattern_len,
+            special,
+        })
+    }
+}
+
 // SAFETY: 'start_state' always returns a valid state ID, 'next_state' always
 // returns a valid state ID given a valid state ID. We otherwise claim that
 // all other methods are correct as well.
@@ -225,6 +459,42 @@ unsafe impl Automaton for DFA {
         self.trans[(sid.as_u32() + u32::from(class)).as_usize()]
     }
 
+    // This is the only place in the DFA that elides a bounds check. The
+    // argument for why it is sound goes like this:
+    //
+    // 1. A valid state ID is either a start state ID or an ID found in
+    // 'trans'. All of them are pre-multiplied, i.e., 'sid = i << stride2' for
+    // some state index 'i < state_len', and 'trans.len()' is exactly
+    // 'state_len << stride2'. A DFA is either built by the DFA builder, which
+    // upholds this for every entry of 'trans' it writes, or deserialized by
+    // 'DFA::read_from' (used by 'DFA::from_bytes' and persisted automata),
+    // which rejects its input unless 'state_len << stride2 == trans.len()'
+    // and every entry of 'trans', as well as every start and special state
+    // ID, is less than 'trans.len()' and a multiple of the stride.
+    // 2. 'class' is an equivalence class, which is always less than
+    // 'alphabet_len', which in turn is at most the stride '1 << stride2'.
+    // The stride is computed from the classes, including for a deserialized
+    // DFA, whose classes are checked by 'ByteClasses::from_bytes'.
+    //
+    // Therefore, 'sid + class < (i + 1) << stride2 <= trans.len()'. The
+    // caller is responsible for the sid being valid, which the search loops
+    // in this crate guarantee by only using IDs returned by this DFA. The
+    // 'fuzz-unchecked' fuzz target checks this for built DFAs as well as for
+    // deserialized ones, including corrupted ones that are accepted.
+    #[cfg(feature = "perf-unchecked")]
+    #[inline(always)]
+    unsafe fn next_state_unchecked(
+        &self,
+        _anchored: Anchored,
+        sid: StateID,
+        byte: u8,
+    ) -> StateID {
+        let class = self.byte_classes.get(byte);
+        let index = (sid.as_u32() + u32::from(class)).as_usize();
+        debug_assert!(index < self.trans.len());
+        *self.trans.get_unchecked(index)
+    }
+
     #[inline(always)]
     fn is_special(&self, sid: StateID) -> bool {
         sid <= self.special.max_special_id
@@ -390,6 +660,8 @@ pub struct Builder {
     noncontiguous: noncontiguous::Builder,
     start_kind: StartKind,
     byte_classes: bool,
+    #[cfg(feature = "rayon")]
+    parallel: bool,
 }
 
 impl Default for Builder {
@@ -398,6 +670,8 @@ impl Default for Builder {
             noncontiguous: noncontiguous::Builder::new(),
             start_kind: StartKind::Unanchored,
             byte_classes: true,
+            #[cfg(feature = "rayon")]
+            parallel: false,
         }
     }
 }
@@ -554,43 +828,28 @@ impl Builder {
             StateID::new_unchecked(oldsid.as_usize() << stride2)
         };
         for (oldsid, state) in nnfa.states().iter().with_state_ids() {
-            let newsid = old2new(oldsid);
             if state.is_match() {
-                dfa.set_matches(newsid, nnfa.iter_matches(oldsid));
+                dfa.set_matches(old2new(oldsid), nnfa.iter_matches(oldsid));
             }
-            sparse_iter(
-                nnfa,
-                oldsid,
-                &dfa.byte_classes,
-                |byte, class, mut oldnextsid| {
-                    if oldnextsid == noncontiguous::NFA::FAIL {
-                        if anchored.is_anchored() {
-                            oldnextsid = noncontiguous::NFA::DEAD;
-                        } else if state.fail() == noncontiguous::NFA::DEAD {
-                            // This is a special case that avoids following
-                            // DEAD transitions in a non-contiguous NFA.
-                            // Following these transitions is pretty slow
-                            // because the non-contiguous NFA will always use
-                            // a sparse representation for it (because the
-                            // DEAD state is usually treated as a sentinel).
-                            // The *vast* majority of failure states are DEAD
-                            // states, so this winds up being pretty slow if
-                            // we go through the non-contiguous NFA state
-                            // transition logic. Instead, just do it ourselves.
-                            oldnextsid = noncontiguous::NFA::DEAD;
-                        } else {
-                            oldnextsid = nnfa.next_state(
-                                Anchored::No,
-                                state.fail(),
-                                byte,
-                            );
-                        }
-                    }
-                    dfa.trans[newsid.as_usize() + usize::from(class)] =
-                        old2new(oldnextsid);
-                },
-            );
         }
+        // Each DFA state corresponds to the NFA state with the same index,
+        // and its transitions only depend on the NFA. So the rows of the
+        // transition table can be filled in any order.
+        let (classes, trans) = (&dfa.byte_classes, &mut dfa.trans);
+        let fill = |(i, row): (usize, &mut [StateID])| {
+            let oldsid = StateID::new_unchecked(i);
+            fill_one_start_row(anchored, nnfa, classes, oldsid, row);
+        };
+        #[cfg(feature = "rayon")]
+        if self.parallel {
+            use rayon::prelude::*;
+
+            trans.par_chunks_mut(1 << stride2).enumerate().for_each(fill);
+        } else {
+            trans.chunks_mut(1 << stride2).enumerate().for_each(fill);
+        }
+        #[cfg(not(feature = "rayon"))]
+        trans.chunks_mut(1 << stride2).enumerate().for_each(fill);
         // Now that we've remapped all the IDs in our states, all that's left
         // is remapping the special state IDs.
         let old = nnfa.special();
@@ -749,6 +1008,40 @@ impl Builder {
         self
     }
 
+    /// Set a table that translates every byte of a haystack before it is
+    /// matched against the patterns.
+    ///
+    /// This only applies when using [`Builder::build`] and not
+    /// [`Builder::build_from_noncontiguous`].
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::translation_table`](crate::AhoCorasickBuilder::translation_table)
+    /// for more documentation and examples.
+    pub fn translation_table(
+        &mut self,
+        table: Option<[u8; 256]>,
+    ) -> &mut Builder {
+        self.noncontiguous.translation_table(table);
+        self
+    }
+
+    /// Set what to do with patterns that are identical to an earlier
+    /// pattern once they are folded.
+    ///
+    /// This only applies when using [`Builder::build`] and not
+    /// [`Builder::build_from_noncontiguous`].
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::duplicate_policy`](crate::AhoCorasickBuilder::duplicate_policy)
+    /// for more documentation and examples.
+    pub fn duplicate_policy(
+        &mut self,
+        policy: DuplicatePolicy,
+    ) -> &mut Builder {
+        self.noncontiguous.duplicate_policy(policy);
+        self
+    }
+
     /// Enable heuristic prefilter optimizations.
     ///
     /// This only applies when using [`Builder::build`] and not
@@ -787,6 +1080,67 @@ impl Builder {
         self.byte_classes = yes;
         self
     }
+
+    /// Fill in the transitions of the DFA in parallel, using the global
+    /// `rayon` thread pool.
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::parallel`](crate::AhoCorasickBuilder::parallel)
+    /// for more documentation.
+    #[cfg(feature = "rayon")]
+    pub fn parallel(&mut self, yes: bool) -> &mut Builder {
+        self.parallel = yes;
+        self
+    }
+
+    /// Write the options of this builder that affect a DFA it builds from a
+    /// noncontiguous NFA. Filling its transitions in parallel builds the
+    /// same DFA, so that option isn't written. See
+    /// `AhoCorasickBuilder::options_key`.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_options(&self, w: &mut Writer) {
+        w.start_kind(self.start_kind);
+        w.bool(self.byte_classes);
+    }
+}
+
+/// Fill in the transitions of the DFA state for the given NFA state, for a
+/// DFA that supports either unanchored or anchored searches, but not both.
+///
+/// The row has one transition for each equivalence class, and its
+/// transitions are set to the DFA state IDs of the NFA states they lead to.
+fn fill_one_start_row(
+    anchored: Anchored,
+    nnfa: &noncontiguous::NFA,
+    classes: &ByteClasses,
+    oldsid: StateID,
+    row: &mut [StateID],
+) {
+    let stride2 = classes.stride2();
+    let old2new =
+        |oldsid: StateID| StateID::new_unchecked(oldsid.as_usize() << stride2);
+    let state = &nnfa.states()[oldsid];
+    sparse_iter(nnfa, oldsid, classes, |byte, class, mut oldnextsid| {
+        if oldnextsid == noncontiguous::NFA::FAIL {
+            if anchored.is_anchored() {
+                oldnextsid = noncontiguous::NFA::DEAD;
+            } else if state.fail() == noncontiguous::NFA::DEAD {
+                // This is a special case that avoids following DEAD
+                // transitions in a non-contiguous NFA. Following these
+                // transitions is pretty slow because the non-contiguous NFA
+                // will always use a sparse representation for it (because
+                // the DEAD state is usually treated as a sentinel). The
+                // *vast* majority of failure states are DEAD states, so this
+                // winds up being pretty slow if we go through the
+                // non-contiguous NFA state transition logic. Instead, just do
+                // it ourselves.
+                oldnextsid = noncontiguous::NFA::DEAD;
+            } else {
+                oldnextsid = nnfa.next_state(Anchored::No, state.fail(), byte);
+            }
+        }
+        row[usize::from(class)] = old2new(oldnextsid);
+    });
 }
 
 /// Iterate over all possible equivalence class transitions in this state.
@@ -0,0 +1,1184 @@
+/*!
+Provides loaders for pattern and replacement dictionaries stored as text.
+
+Patterns handed to an [`AhoCorasick`](crate::AhoCorasick) searcher are
+arbitrary bytes. They may contain `NUL` bytes, invalid UTF-8 or bytes that
+would otherwise be meaningful to a line oriented text format, such as `\n` or
+`\t`. This module provides a small, binary-safe text format for storing such
+patterns (and their replacements) in files, along with routines for reading
+and writing it.
+
+# Format
+
+A dictionary is a sequence of lines separated by `\n`. A trailing `\r` on a
+line is ignored. Empty lines and lines whose first byte is `#` are skipped.
+
+In a pattern dictionary, every remaining line is a single pattern. In a
+replacement dictionary, every remaining line is a pattern followed by a single
+tab (`\t`) followed by its replacement.
+
+Within a pattern or replacement, the following escape sequences are
+recognized:
+
+* `\\` is a literal backslash.
+* `\t`, `\n`, `\r` and `\0` are a tab, line feed, carriage return and
+  `NUL` byte, respectively.
+* `\xHH` is the byte with the hexadecimal value `HH`. Exactly two hex digits
+  are required.
+
+All other bytes, including bytes that are not valid UTF-8, are taken
+verbatim. A literal `#` at the start of a line can be written as `\x23`.
+
+[`escape`] produces the escaped form of any sequence of bytes, such that
+[`unescape`] always returns the original bytes.
+
+Patterns can also be exported for Hyperscan with
+[`AhoCorasickBuilder::export_hyperscan`](crate::AhoCorasickBuilder::export_hyperscan),
+so that the same dictionary can be used with either engine. Conversely,
+[`parse_ndb`] imports the basic signatures of a ClamAV NDB database for use
+with a [`SignatureSearcher`](crate::signature::SignatureSearcher), and
+[`parse_masked`] reads patterns whose bytes only need to match in the bits of
+a mask.
+
+# Example
+
+This example shows how to load a replacement dictionary containing binary
+patterns and use it to build a searcher.
+
+```
+use aho_corasick::{dictionary, AhoCorasick};
+
+let text = b"## Redact NUL delimited credentials.
+user\\x00secret\t<redacted>
+\\xFF\\xFE\tBOM
+";
+let dict = dictionary::parse_replacements(text)?;
+assert_eq!(b"user\x00secret", dict.patterns()[0].as_slice());
+
+let ac = AhoCorasick::new(dict.patterns())?;
+let result =
+    ac.replace_all_bytes(b"user\x00secret\xFF\xFE!", dict.replacements());
+assert_eq!(b"<redacted>BOM!", result.as_slice());
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{signature::Signature, util::primitives::PatternID, AhoCorasick};
+
+/// An error that occurred while parsing a dictionary.
+///
+/// The error reports the line (starting at `1`) on which it occurred.
+///
+/// When the `std` feature is enabled, this implements the `std::error::Error`
+/// trait.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DictionaryError {
+    line: usize,
+    kind: ErrorKind,
+}
+
+/// The kind of error that occurred.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ErrorKind {
+    /// A backslash was followed by an unrecognized byte, or was the last byte
+    /// of a field.
+    InvalidEscape {
+        /// The offset of the backslash in the line.
+        offset: usize,
+    },
+    /// A `\x` escape was not followed by two hexadecimal digits.
+    InvalidHexEscape {
+        /// The offset of the backslash in the line.
+        offset: usize,
+    },
+    /// A line in a replacement dictionary did not contain exactly one tab.
+    MissingReplacement,
+    /// A line in an NDB database did not have the fields of an NDB
+    /// signature.
+    InvalidNdbLine,
+    /// The hex signature of an NDB signature could not be parsed.
+    InvalidNdbSignature {
+        /// The error reported by the signature parser.
+        message: String,
+    },
+    /// A line in a masked dictionary did not contain exactly one tab.
+    MissingMask,
+    /// The pattern and mask of a line in a masked dictionary do not form a
+    /// valid masked signature.
+    InvalidMask {
+        /// The error reported when building the signature.
+        message: String,
+    },
+}
+
+impl DictionaryError {
+    fn new(line: usize, kind: ErrorKind) -> DictionaryError {
+        DictionaryError { line, kind }
+    }
+
+    /// Returns the line, starting at `1`, on which this error occurred.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DictionaryError {}
+
+impl core::fmt::Display for DictionaryError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            ErrorKind::InvalidEscape { offset } => write!(
+                f,
+                "invalid escape sequence at line {}, offset {}",
+                self.line, offset,
+            ),
+            ErrorKind::InvalidHexEscape { offset } => write!(
+                f,
+                "invalid hex escape sequence at line {}, offset {} \
+                 (expected exactly two hex digits)",
+                self.line, offset,
+            ),
+            ErrorKind::MissingReplacement => write!(
+                f,
+                "expected a pattern and replacement separated by a single \
+                 tab at line {}",
+                self.line,
+            ),
+            ErrorKind::InvalidNdbLine => write!(
+                f,
+                "expected an NDB signature of the form \
+                 Name:TargetType:Offset:HexSignature at line {}",
+                self.line,
+            ),
+            ErrorKind::InvalidNdbSignature { ref message } => write!(
+                f,
+                "invalid NDB hex signature at line {}: {}",
+                self.line, message,
+            ),
+            ErrorKind::MissingMask => write!(
+                f,
+                "expected a pattern and mask separated by a single tab at \
+                 line {}",
+                self.line,
+            ),
+            ErrorKind::InvalidMask { ref message } => write!(
+                f,
+                "invalid masked pattern at line {}: {}",
+                self.line, message,
+            ),
+        }
+    }
+}
+
+/// Parse a pattern dictionary, returning one pattern per non-empty,
+/// non-comment line.
+///
+/// See the [module documentation](self) for a description of the format.
+///
+/// # Errors
+///
+/// This returns an error if any line contains an invalid escape sequence.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// let patterns = dictionary::parse_patterns(b"# comment\nfoo\n\\x00bar\n")?;
+/// assert_eq!(vec![b"foo".to_vec(), b"\x00bar".to_vec()], patterns);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_patterns(text: &[u8]) -> Result<Vec<Vec<u8>>, DictionaryError> {
+    lines(text)
+        .map(|(line, bytes)| {
+            unescape_field(bytes)
+                .map_err(|kind| DictionaryError::new(line, kind))
+        })
+        .collect()
+}
+
+/// Parse a pattern dictionary like [`parse_patterns`], but also return the
+/// line number (starting at `1`) that each pattern was found on.
+///
+/// This is useful for reporting problems with individual patterns, for
+/// example, the issues found by
+/// [`AhoCorasickBuilder::validate`](crate::AhoCorasickBuilder::validate).
+///
+/// # Errors
+///
+/// This returns an error if any line contains an invalid escape sequence.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// let text = b"# comment\nfoo\n\nbar\n";
+/// let patterns = dictionary::parse_patterns_with_lines(text)?;
+/// assert_eq!(vec![(2, b"foo".to_vec()), (4, b"bar".to_vec())], patterns);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_patterns_with_lines(
+    text: &[u8],
+) -> Result<Vec<(usize, Vec<u8>)>, DictionaryError> {
+    lines(text)
+        .map(|(line, bytes)| {
+            unescape_field(bytes)
+                .map(|pattern| (line, pattern))
+                .map_err(|kind| DictionaryError::new(line, kind))
+        })
+        .collect()
+}
+
+/// Parse a replacement dictionary, returning a pattern and its replacement
+/// for each non-empty, non-comment line.
+///
+/// See the [module documentation](self) for a description of the format.
+///
+/// # Errors
+///
+/// This returns an error if any line contains an invalid escape sequence or
+/// does not contain exactly one tab.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// let dict = dictionary::parse_replacements(b"foo\tbar\n\\x00\t\\\\0\n")?;
+/// assert_eq!(vec![b"foo".to_vec(), b"\x00".to_vec()], dict.patterns());
+/// assert_eq!(vec![b"bar".to_vec(), b"\\0".to_vec()], dict.replacements());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_replacements(
+    text: &[u8],
+) -> Result<ReplacementDictionary, DictionaryError> {
+    let mut dict = ReplacementDictionary::default();
+    for (line, bytes) in lines(text) {
+        let err = |kind| DictionaryError::new(line, kind);
+        let mut fields = bytes.split(|&b| b == b'\t');
+        let (pattern, replacement) =
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(pattern), Some(replacement), None) => {
+                    (pattern, replacement)
+                }
+                _ => return Err(err(ErrorKind::MissingReplacement)),
+            };
+        dict.patterns.push(unescape_field(pattern).map_err(err)?);
+        dict.replacements.push(unescape_field(replacement).map_err(err)?);
+    }
+    Ok(dict)
+}
+
+/// A set of patterns along with the replacement for each pattern.
+///
+/// This is returned by [`parse_replacements`]. The pattern and replacement
+/// at the same index form a pair, which means the patterns can be given
+/// directly to [`AhoCorasick::new`](crate::AhoCorasick::new) and the
+/// replacements to
+/// [`AhoCorasick::replace_all_bytes`](crate::AhoCorasick::replace_all_bytes)
+/// (or any of the other replacement routines).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReplacementDictionary {
+    patterns: Vec<Vec<u8>>,
+    replacements: Vec<Vec<u8>>,
+}
+
+impl ReplacementDictionary {
+    /// Returns the patterns in this dictionary, in the order in which they
+    /// were defined.
+    pub fn patterns(&self) -> &[Vec<u8>] {
+        &self.patterns
+    }
+
+    /// Returns the replacements in this dictionary. The replacement at index
+    /// `i` corresponds to the pattern at index `i`.
+    pub fn replacements(&self) -> &[Vec<u8>] {
+        &self.replacements
+    }
+
+    /// Returns the number of pattern and replacement pairs in this
+    /// dictionary.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Returns true if and only if this dictionary has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns an iterator over each pattern and its replacement.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.patterns
+            .iter()
+            .zip(self.replacements.iter())
+            .map(|(p, r)| (p.as_slice(), r.as_slice()))
+    }
+
+    /// Consume this dictionary and return its patterns and replacements.
+    pub fn into_parts(self) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        (self.patterns, self.replacements)
+    }
+}
+
+/// Write the given patterns as a pattern dictionary.
+///
+/// The result can be parsed with [`parse_patterns`] to get back the original
+/// patterns, as long as none of them are empty. (Empty lines are skipped by
+/// the parser.)
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// let patterns = [&b"foo\nbar"[..], b"#\x00\xFF"];
+/// let text = dictionary::format_patterns(patterns);
+/// assert_eq!("foo\\nbar\n\\x23\\x00\\xFF\n", text);
+/// assert_eq!(patterns.to_vec(), dictionary::parse_patterns(text.as_bytes())?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn format_patterns<I, P>(patterns: I) -> String
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+{
+    let mut text = String::new();
+    for pattern in patterns {
+        escape_line_into(pattern.as_ref(), &mut text);
+        text.push('\n');
+    }
+    text
+}
+
+/// Write the given pairs of patterns and replacements as a replacement
+/// dictionary.
+///
+/// The result can be parsed with [`parse_replacements`] to get back the
+/// original pairs.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// let dict = [(&b"a\tb"[..], &b"\x00"[..])];
+/// let text = dictionary::format_replacements(dict);
+/// assert_eq!("a\\tb\t\\x00\n", text);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn format_replacements<I, P, R>(pairs: I) -> String
+where
+    I: IntoIterator<Item = (P, R)>,
+    P: AsRef<[u8]>,
+    R: AsRef<[u8]>,
+{
+    let mut text = String::new();
+    for (pattern, replacement) in pairs {
+        escape_line_into(pattern.as_ref(), &mut text);
+        text.push('\t');
+        escape_into(replacement.as_ref(), &mut text);
+        text.push('\n');
+    }
+    text
+}
+
+/// The differences between the patterns of two automata.
+///
+/// This is returned by [`diff`]. Patterns are compared by their bytes, so a
+/// pattern that is in both automata but has a different ID in each is
+/// reported as changed rather than as removed and added. The ID of a pattern
+/// matters since it is what matches report, and it determines which
+/// replacement a pattern uses and, for leftmost-first match semantics, which
+/// pattern takes priority.
+///
+/// The `Display` impl writes one line per difference: `+ <id> <pattern>`
+/// for added patterns, `- <id> <pattern>` for removed patterns and
+/// `~ <old id> -> <new id> <pattern>` for changed patterns, with each
+/// pattern written with [`escape`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DictionaryDiff {
+    added: Vec<(PatternID, Vec<u8>)>,
+    removed: Vec<(PatternID, Vec<u8>)>,
+    changed: Vec<(PatternID, PatternID, Vec<u8>)>,
+}
+
+impl DictionaryDiff {
+    /// Returns true if and only if both automata have the same patterns with
+    /// the same IDs.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// Returns the patterns that are only in the new automaton, along with
+    /// their IDs in the new automaton, in order of ID.
+    pub fn added(&self) -> impl Iterator<Item = (PatternID, &[u8])> {
+        self.added.iter().map(|&(pid, ref p)| (pid, p.as_slice()))
+    }
+
+    /// Returns the patterns that are only in the old automaton, along with
+    /// their IDs in the old automaton, in order of ID.
+    pub fn removed(&self) -> impl Iterator<Item = (PatternID, &[u8])> {
+        self.removed.iter().map(|&(pid, ref p)| (pid, p.as_slice()))
+    }
+
+    /// Returns the patterns that are in both automata but with a different
+    /// ID, as their old ID, new ID and bytes, in order of new ID.
+    pub fn changed(
+        &self,
+    ) -> impl Iterator<Item = (PatternID, PatternID, &[u8])> {
+        self.changed.iter().map(|&(old, new, ref p)| (old, new, p.as_slice()))
+    }
+}
+
+impl core::fmt::Display for DictionaryDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (pid, p) in self.removed() {
+            writeln!(f, "- {} {}", pid.as_usize(), escape(p))?;
+        }
+        for (pid, p) in self.added() {
+            writeln!(f, "+ {} {}", pid.as_usize(), escape(p))?;
+        }
+        for (old, new, p) in self.changed() {
+            writeln!(
+                f,
+                "~ {} -> {} {}",
+                old.as_usize(),
+                new.as_usize(),
+                escape(p),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the patterns of two automata, such as the automata built from
+/// two versions of a dictionary.
+///
+/// This reports the patterns added in `new`, the patterns removed from `old`
+/// and the patterns whose ID changed. If a pattern occurs more than once in
+/// an automaton, its occurrences are paired up in order of ID. Only patterns
+/// are compared. Other configuration, such as the match semantics, is not.
+///
+/// # Panics
+///
+/// This panics if either automaton was built without storing its patterns.
+/// See [`AhoCorasickBuilder::store_patterns`](crate::AhoCorasickBuilder::store_patterns).
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::{dictionary, AhoCorasick};
+///
+/// let old = dictionary::parse_patterns(b"foo\nbar\nbaz\n")?;
+/// let new = dictionary::parse_patterns(b"foo\nbaz\nquux\n")?;
+/// let build = |patterns: &[Vec<u8>]| {
+///     AhoCorasick::builder().store_patterns(true).build(patterns)
+/// };
+///
+/// let diff = dictionary::diff(&build(&old)?, &build(&new)?);
+/// assert_eq!("- 1 bar\n+ 2 quux\n~ 2 -> 1 baz\n", diff.to_string());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn diff(old: &AhoCorasick, new: &AhoCorasick) -> DictionaryDiff {
+    let patterns = |ac: &AhoCorasick| -> Vec<(PatternID, Vec<u8>)> {
+        PatternID::iter(ac.patterns_len())
+            .map(|pid| {
+                let p = ac
+                    .pattern(pid)
+                    .expect("diff requires automata that store patterns");
+                (pid, p.to_vec())
+            })
+            .collect()
+    };
+    // Map each pattern in the old automaton to its IDs, in reverse order so
+    // that the lowest remaining ID can be popped off the end.
+    let mut unmatched: BTreeMap<Vec<u8>, Vec<PatternID>> = BTreeMap::new();
+    for (pid, p) in patterns(old).into_iter().rev() {
+        unmatched.entry(p).or_default().push(pid);
+    }
+    let mut diff = DictionaryDiff::default();
+    for (pid, p) in patterns(new) {
+        match unmatched.get_mut(&p).and_then(|pids| pids.pop()) {
+            None => diff.added.push((pid, p)),
+            Some(old) if old != pid => diff.changed.push((old, pid, p)),
+            Some(_) => {}
+        }
+    }
+    for (p, pids) in unmatched {
+        for pid in pids {
+            diff.removed.push((pid, p.clone()));
+        }
+    }
+    diff.removed.sort();
+    diff
+}
+
+/// A set of patterns exported for use with
+/// [Hyperscan](https://github.com/intel/hyperscan) or
+/// [Vectorscan](https://github.com/VectorCamp/vectorscan).
+///
+/// This is returned by
+/// [`AhoCorasickBuilder::export_hyperscan`](crate::AhoCorasickBuilder::export_hyperscan).
+/// Each pattern is exported as a Hyperscan expression that matches exactly
+/// the bytes of the pattern, along with its ID and the Hyperscan flags that
+/// correspond to the configuration of the builder. The expressions, IDs and
+/// flags can be handed directly to the multi-pattern compile functions of a
+/// Hyperscan binding.
+///
+/// The `Display` impl writes one line per pattern in the
+/// `<id>:/<expression>/<flags>` format read by Hyperscan's own tools, such as
+/// `hsbench` and `hscheck`.
+///
+/// # Semantics
+///
+/// Hyperscan always reports every match of every pattern, including
+/// overlapping matches, which corresponds to an overlapping search with
+/// [`MatchKind::Standard`](crate::MatchKind::Standard) semantics. Leftmost
+/// match semantics have no equivalent in Hyperscan, so a consumer that needs
+/// them has to pick matches among the reported ones itself, for example by
+/// using the pattern IDs as priorities. Every pattern is exported with the
+/// `L` flag, so that Hyperscan reports the start of each match.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HyperscanExport {
+    patterns: Vec<HyperscanPattern>,
+}
+
+impl HyperscanExport {
+    /// Returns the exported patterns, in order of ID.
+    pub fn patterns(&self) -> &[HyperscanPattern] {
+        &self.patterns
+    }
+}
+
+impl core::fmt::Display for HyperscanExport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for pattern in self.patterns.iter() {
+            writeln!(f, "{}", pattern)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single pattern exported for use with Hyperscan.
+///
+/// The `Display` impl writes the pattern in the
+/// `<id>:/<expression>/<flags>` format.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HyperscanPattern {
+    id: PatternID,
+    expression: String,
+    caseless: bool,
+    anchored: bool,
+    allow_empty: bool,
+}
+
+impl HyperscanPattern {
+    /// Returns the ID of the pattern, which Hyperscan reports with each
+    /// match of it.
+    pub fn id(&self) -> PatternID {
+        self.id
+    }
+
+    /// Returns the Hyperscan expression for the pattern.
+    ///
+    /// ASCII letters, digits and `_` are written as is, while every other
+    /// byte is written as a `\xHH` escape. An anchored pattern starts with
+    /// `^`. The expression never relies on the UTF-8 mode of Hyperscan, so
+    /// patterns that are not valid UTF-8 are exported as well.
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Returns true if and only if the pattern matches ASCII letters case
+    /// insensitively, which is the `HS_FLAG_CASELESS` flag (`i`).
+    pub fn is_caseless(&self) -> bool {
+        self.caseless
+    }
+
+    /// Returns true if and only if the pattern only matches at the start of
+    /// a haystack.
+    pub fn is_anchored(&self) -> bool {
+        self.anchored
+    }
+
+    /// Returns true if and only if the pattern is empty, which Hyperscan
+    /// only accepts with the `HS_FLAG_ALLOWEMPTY` flag (`V`).
+    pub fn allows_empty(&self) -> bool {
+        self.allow_empty
+    }
+
+    /// Returns the Hyperscan flags of the pattern, as the letters used by
+    /// the `<id>:/<expression>/<flags>` format.
+    ///
+    /// This always includes `L`, which is the `HS_FLAG_SOM_LEFTMOST` flag.
+    pub fn flags(&self) -> String {
+        let mut flags = String::new();
+        if self.caseless {
+            flags.push('i');
+        }
+        if self.allow_empty {
+            flags.push('V');
+        }
+        flags.push('L');
+        flags
+    }
+}
+
+impl core::fmt::Display for HyperscanPattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}:/{}/{}",
+            self.id.as_usize(),
+            self.expression,
+            self.flags()
+        )
+    }
+}
+
+/// Export the given patterns for Hyperscan, with the given configuration.
+pub(crate) fn export_hyperscan<I, P>(
+    patterns: I,
+    caseless: bool,
+    anchored: bool,
+) -> HyperscanExport
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+{
+    use core::fmt::Write;
+
+    let mut export = HyperscanExport::default();
+    for (i, pattern) in patterns.into_iter().enumerate() {
+        let pattern = pattern.as_ref();
+        let mut expression = String::new();
+        if anchored {
+            expression.push('^');
+        }
+        for &b in pattern {
+            if b.is_ascii_alphanumeric() || b == b'_' {
+                expression.push(char::from(b));
+            } else {
+                write!(expression, "\\x{:02X}", b).unwrap();
+            }
+        }
+        export.patterns.push(HyperscanPattern {
+            id: PatternID::must(i),
+            expression,
+            caseless,
+            anchored,
+            allow_empty: pattern.is_empty(),
+        });
+    }
+    export
+}
+
+/// Parse the signatures of a ClamAV NDB database, returning one signature
+/// per non-empty, non-comment line.
+///
+/// Each line is of the form `Name:TargetType:Offset:HexSignature`, possibly
+/// followed by `:MinFL` and `:MaxFL` fields, which are ignored. The hex
+/// signature may use bytes, `??` and nibble wildcards such as `4?`,
+/// alternations of single bytes such as `(0a|0d)` and the bounded gaps
+/// `{n}`, `{n-m}` and `{-m}`. These are the basic NDB signatures that can be
+/// searched for with a
+/// [`SignatureSearcher`](crate::signature::SignatureSearcher), without any
+/// of the regular expression parts of the NDB format.
+///
+/// The target type and offset are reported as is by each [`NdbSignature`],
+/// and are not enforced by the searcher. A caller that needs them can check
+/// the matches it gets against them.
+///
+/// # Errors
+///
+/// This returns an error if a line doesn't have the fields of an NDB
+/// signature, its target type isn't a decimal number, or its hex signature
+/// uses an unsupported construct, such as the unbounded gap `*`.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::{dictionary, signature::SignatureSearcher, Match};
+///
+/// let text = b"\
+/// Win.Trojan.Example-1:1:*:4d5a??{2-4}(50|45)00
+/// Html.Phish.Example-2:3:*:6c6f67696e{-8}70617373:51:255
+/// ";
+/// let sigs = dictionary::parse_ndb(text)?;
+/// assert_eq!("Win.Trojan.Example-1", sigs[0].name());
+/// assert_eq!(3, sigs[1].target_type());
+///
+/// let searcher = SignatureSearcher::new(
+///     sigs.into_iter().map(|sig| sig.into_signature()),
+/// )?;
+/// let haystack = b"..MZ\x90\x00\x00\x50\x00..login: pass";
+/// let matches: Vec<Match> = searcher.find_iter(haystack).collect();
+/// assert_eq!(vec![Match::must(0, 2..9), Match::must(1, 11..22)], matches);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_ndb(text: &[u8]) -> Result<Vec<NdbSignature>, DictionaryError> {
+    let mut sigs = vec![];
+    for (line, bytes) in lines(text) {
+        let err = |kind| DictionaryError::new(line, kind);
+        let fields: Vec<&str> = match core::str::from_utf8(bytes) {
+            Ok(text) => text.split(':').collect(),
+            Err(_) => return Err(err(ErrorKind::InvalidNdbLine)),
+        };
+        if !(4..=6).contains(&fields.len()) || fields[0].is_empty() {
+            return Err(err(ErrorKind::InvalidNdbLine));
+        }
+        let target_type = fields[1]
+            .parse::<u32>()
+            .map_err(|_| err(ErrorKind::InvalidNdbLine))?;
+        let signature = Signature::ndb(fields[3]).map_err(|e| {
+            err(ErrorKind::InvalidNdbSignature { message: e.to_string() })
+        })?;
+        sigs.push(NdbSignature {
+            name: String::from(fields[0]),
+            target_type,
+            offset: String::from(fields[2]),
+            signature,
+        });
+    }
+    Ok(sigs)
+}
+
+/// A signature parsed from a ClamAV NDB database.
+///
+/// This is returned by [`parse_ndb`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NdbSignature {
+    name: String,
+    target_type: u32,
+    offset: String,
+    signature: Signature,
+}
+
+impl NdbSignature {
+    /// Returns the name of the signature, such as `Win.Trojan.Example-1`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of file the signature applies to, where `0` means
+    /// any file.
+    pub fn target_type(&self) -> u32 {
+        self.target_type
+    }
+
+    /// Returns the offset field of the signature, such as `*` for any
+    /// offset, as written in the database.
+    pub fn offset(&self) -> &str {
+        &self.offset
+    }
+
+    /// Returns the byte signature that this NDB signature matches.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Consume this NDB signature and return its byte signature.
+    pub fn into_signature(self) -> Signature {
+        self.signature
+    }
+}
+
+/// Parse a masked dictionary, returning one masked signature per non-empty,
+/// non-comment line.
+///
+/// Every line is a pattern followed by a single tab (`\t`) followed by its
+/// mask, where both use the escape sequences described in the
+/// [module documentation](self). Only the bits set in the mask must match,
+/// as described by [`Signature::masked`].
+///
+/// # Errors
+///
+/// This returns an error if any line contains an invalid escape sequence,
+/// does not contain exactly one tab, or has a pattern that is empty or whose
+/// mask has a different length.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::{dictionary, signature::SignatureSearcher, Match};
+///
+/// // Match the version byte 0x45 followed by a byte with the high bit set,
+/// // and a byte with a high nibble of 0x6 followed by 'f'.
+/// let text = b"E\x80\t\xFF\x80\n\x60f\t\xF0\xFF\n";
+/// let searcher = SignatureSearcher::new(dictionary::parse_masked(text)?)?;
+/// let matches: Vec<Match> = searcher.find_iter(b"E\x9BE\x1Bgf").collect();
+/// assert_eq!(vec![Match::must(0, 0..2), Match::must(1, 4..6)], matches);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_masked(text: &[u8]) -> Result<Vec<Signature>, DictionaryError> {
+    let mut sigs = vec![];
+    for (line, bytes) in lines(text) {
+        let err = |kind| DictionaryError::new(line, kind);
+        let mut fields = bytes.split(|&b| b == b'\t');
+        let (pattern, mask) =
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(pattern), Some(mask), None) => (pattern, mask),
+                _ => return Err(err(ErrorKind::MissingMask)),
+            };
+        let pattern = unescape_field(pattern).map_err(err)?;
+        let mask = unescape_field(mask).map_err(err)?;
+        let sig = Signature::masked(&pattern, &mask).map_err(|e| {
+            err(ErrorKind::InvalidMask { message: e.to_string() })
+        })?;
+        sigs.push(sig);
+    }
+    Ok(sigs)
+}
+
+/// Write the given pairs of patterns and masks as a masked dictionary.
+///
+/// The result can be parsed with [`parse_masked`] to get back the masked
+/// signatures of the original pairs.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::{dictionary, signature::Signature};
+///
+/// let pairs = [(&b"E\x80"[..], &b"\xFF\x80"[..])];
+/// let text = dictionary::format_masked(pairs);
+/// assert_eq!("E\\x80\t\\xFF\\x80\n", text);
+/// assert_eq!(
+///     vec![Signature::masked(b"E\x80", b"\xFF\x80")?],
+///     dictionary::parse_masked(text.as_bytes())?,
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn format_masked<I, P, M>(pairs: I) -> String
+where
+    I: IntoIterator<Item = (P, M)>,
+    P: AsRef<[u8]>,
+    M: AsRef<[u8]>,
+{
+    format_replacements(pairs)
+}
+
+/// Escape the given bytes such that the result is printable ASCII.
+///
+/// Printable ASCII bytes other than `\` are written as is. Tabs, line feeds
+/// and carriage returns use their short escapes and all other bytes use a
+/// `\xHH` escape.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// assert_eq!("a\\\\b\\x00\\t\\xFF", dictionary::escape(b"a\\b\x00\t\xFF"));
+/// ```
+pub fn escape(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    escape_into(bytes, &mut text);
+    text
+}
+
+/// Unescape the given bytes, which may use any of the escape sequences
+/// described in the [module documentation](self).
+///
+/// # Errors
+///
+/// This returns an error if an invalid escape sequence is found. The error
+/// always reports line `1`.
+///
+/// # Example: basic usage
+///
+/// ```
+/// use aho_corasick::dictionary;
+///
+/// assert_eq!(b"a\\b\x00\t\xFF".to_vec(), dictionary::unescape(b"a\\\\b\\0\\t\\xff")?);
+/// assert!(dictionary::unescape(b"\\x0").is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn unescape(bytes: &[u8]) -> Result<Vec<u8>, DictionaryError> {
+    unescape_field(bytes).map_err(|kind| DictionaryError::new(1, kind))
+}
+
+/// Returns an iterator over the lines in the given dictionary text that
+/// should be parsed, along with their line numbers (starting at `1`).
+fn lines(text: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    text.split(|&b| b == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.strip_suffix(b"\r").unwrap_or(line)))
+        .filter(|&(_, line)| !line.is_empty() && line[0] != b'#')
+}
+
+fn unescape_field(bytes: &[u8]) -> Result<Vec<u8>, ErrorKind> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let offset = i;
+        let byte = match bytes.get(i + 1) {
+            Some(b'\\') => b'\\',
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b'0') => b'\0',
+            Some(b'x') => {
+                let hex = bytes.get(i + 2..i + 4).and_then(|hex| {
+                    Some((hex_value(hex[0])?, hex_value(hex[1])?))
+                });
+                match hex {
+                    Some((hi, lo)) => {
+                        out.push((hi << 4) | lo);
+                        i += 4;
+                        continue;
+                    }
+                    None => {
+                        return Err(ErrorKind::InvalidHexEscape { offset })
+                    }
+                }
+            }
+            _ => return Err(ErrorKind::InvalidEscape { offset }),
+        };
+        out.push(byte);
+        i += 2;
+    }
+    Ok(out)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Like `escape_into`, but also escapes a leading `#` so that the line isn't
+/// treated as a comment.
+fn escape_line_into(bytes: &[u8], text: &mut String) {
+    match bytes.split_first() {
+        Some((b'#', rest)) => {
+            text.push_str("\\x23");
+            escape_into(rest, text);
+        }
+        _ => escape_into(bytes, text),
+    }
+}
+
+fn escape_into(bytes: &[u8], text: &mut String) {
+    use core::fmt::Write;
+
+    for &b in bytes {
+        match b {
+            b'\\' => text.push_str("\\\\"),
+            b'\t' => text.push_str("\\t"),
+            b'\n' => text.push_str("\\n"),
+            b'\r' => text.push_str("\\r"),
+            b' '..=b'~' => text.push(char::from(b)),
+            _ => write!(text, "\\x{:02X}", b).unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use super::*;
+
+    #[test]
+    fn escape_roundtrip_all_bytes() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let escaped = escape(&bytes);
+        assert!(escaped.bytes().all(|b| (b' '..=b'~').contains(&b)));
+        assert_eq!(bytes, unescape(escaped.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn raw_bytes_are_verbatim() {
+        let text = b"\x00\xFF\xC3(\r\nnul\x00\tnul\x00\n";
+        assert_eq!(
+            vec![b"\x00\xFF\xC3(".to_vec(), b"nul\x00\tnul\x00".to_vec()],
+            parse_patterns(text).unwrap(),
+        );
+        let dict = parse_replacements(&text[6..]).unwrap();
+        assert_eq!(1, dict.len());
+        assert_eq!(vec![b"nul\x00".to_vec()], dict.patterns());
+        assert_eq!(vec![b"nul\x00".to_vec()], dict.replacements());
+    }
+
+    #[test]
+    fn format_roundtrip() {
+        let pairs = vec![
+            (b"#hash".to_vec(), b"#".to_vec()),
+            (b"\x00\x01\x02".to_vec(), b"".to_vec()),
+            (b"tab\there\r\n".to_vec(), b"\\".to_vec()),
+        ];
+        let text = format_replacements(pairs.clone());
+        let dict = parse_replacements(text.as_bytes()).unwrap();
+        let got: Vec<(Vec<u8>, Vec<u8>)> =
+            dict.iter().map(|(p, r)| (p.to_vec(), r.to_vec())).collect();
+        assert_eq!(pairs, got);
+
+        let patterns: Vec<Vec<u8>> =
+            pairs.into_iter().map(|(p, _)| p).collect();
+        let text = format_patterns(&patterns);
+        assert_eq!(patterns, parse_patterns(text.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn diff_patterns() {
+        let build = |patterns: &[&str]| {
+            AhoCorasick::builder()
+                .store_patterns(true)
+                .build(patterns)
+                .unwrap()
+        };
+        let old = build(&["a", "b", "a", "c", "d"]);
+        let new = build(&["a", "c", "a", "e", "d", "a"]);
+        let diff = diff(&old, &new);
+        assert_eq!(
+            vec![(PatternID::must(3), &b"e"[..]), (PatternID::must(5), b"a")],
+            diff.added().collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            vec![(PatternID::must(1), &b"b"[..])],
+            diff.removed().collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            vec![(PatternID::must(3), PatternID::must(1), &b"c"[..])],
+            diff.changed().collect::<Vec<_>>(),
+        );
+
+        let same = super::diff(&old, &build(&["a", "b", "a", "c", "d"]));
+        assert!(same.is_empty());
+        assert_eq!("", same.to_string());
+    }
+
+    #[test]
+    #[should_panic]
+    fn diff_requires_stored_patterns() {
+        let ac = AhoCorasick::new(["a"]).unwrap();
+        diff(&ac, &ac);
+    }
+
+    #[test]
+    fn hyperscan_export() {
+        let patterns = [&b"a_Z9"[..], b"\x00\xFF/\\", b""];
+        let export = export_hyperscan(patterns, false, false);
+        assert_eq!(
+            "0:/a_Z9/L\n1:/\\x00\\xFF\\x2F\\x5C/L\n2://VL\n",
+            export.to_string(),
+        );
+        let pids: Vec<usize> =
+            export.patterns().iter().map(|p| p.id().as_usize()).collect();
+        assert_eq!(vec![0, 1, 2], pids);
+
+        let export = export_hyperscan(patterns, true, true);
+        let empty = &export.patterns()[2];
+        assert!(empty.is_caseless() && empty.is_anchored());
+        assert!(empty.allows_empty());
+        assert_eq!("2:/^/iVL", empty.to_string());
+        assert_eq!(
+            AhoCorasick::builder()
+                .start_kind(crate::StartKind::Both)
+                .export_hyperscan(patterns),
+            export_hyperscan(patterns, false, false),
+        );
+    }
+
+    #[test]
+    fn ndb() {
+        let text = b"# comment\nA:0:*:00{1-2}01\r\nB:7:EOF-10:aa??:51\n";
+        let sigs = parse_ndb(text).unwrap();
+        assert_eq!(2, sigs.len());
+        assert_eq!(
+            ("A", 0, "*"),
+            (sigs[0].name(), sigs[0].target_type(), sigs[0].offset())
+        );
+        assert_eq!(
+            ("B", 7, "EOF-10"),
+            (sigs[1].name(), sigs[1].target_type(), sigs[1].offset())
+        );
+        assert_eq!(4, sigs[0].signature().max_len());
+
+        for bad in [
+            &b"A:0:*"[..],
+            b"A:0:*:00:1:2:3",
+            b":0:*:00",
+            b"A:x:*:00",
+            b"A:0:*:\xFF",
+        ] {
+            let err = parse_ndb(bad).unwrap_err();
+            assert_eq!(ErrorKind::InvalidNdbLine, err.kind, "{:?}", bad);
+        }
+        let err = parse_ndb(b"A:0:*:00\nB:0:*:00*01").unwrap_err();
+        assert_eq!(2, err.line());
+        assert_eq!(
+            "invalid NDB hex signature at line 2: invalid character '*' in \
+             signature at offset 2",
+            err.to_string(),
+        );
+    }
+
+    #[test]
+    fn masked() {
+        let pairs =
+            [(&b"E\x80"[..], &b"\xFF\x80"[..]), (b"\t#\n", b"\x0F\xFF\x00")];
+        let text = format_masked(pairs);
+        let sigs = parse_masked(text.as_bytes()).unwrap();
+        let expected: Vec<Signature> = pairs
+            .iter()
+            .map(|(p, m)| Signature::masked(p, m).unwrap())
+            .collect();
+        assert_eq!(expected, sigs);
+
+        for bad in [&b"ab"[..], b"a\tb\tc"] {
+            let err = parse_masked(bad).unwrap_err();
+            assert_eq!(ErrorKind::MissingMask, err.kind);
+        }
+        let err = parse_masked(b"ab\t\\xFF\n").unwrap_err();
+        assert_eq!(1, err.line());
+        assert_eq!(
+            "invalid masked pattern at line 1: mask of length 1 doesn't \
+             match signature of length 2",
+            err.to_string(),
+        );
+        let err = parse_masked(b"a\t\\q").unwrap_err();
+        assert_eq!(ErrorKind::InvalidEscape { offset: 0 }, err.kind);
+    }
+
+    #[test]
+    fn errors() {
+        let err = parse_patterns(b"ok\n\n\\q").unwrap_err();
+        assert_eq!(3, err.line());
+        assert_eq!(ErrorKind::InvalidEscape { offset: 0 }, err.kind,);
+
+        let err = parse_patterns(b"ab\\").unwrap_err();
+        assert_eq!(ErrorKind::InvalidEscape { offset: 2 }, err.kind);
+
+        for bad in [&b"\\x"[..], b"\\x1", b"\\xG0", b"\\x0g"] {
+            let err = parse_patterns(bad).unwrap_err();
+            assert_eq!(ErrorKind::InvalidHexEscape { offset: 0 }, err.kind);
+        }
+
+        for bad in [&b"foo"[..], b"a\tb\tc"] {
+            let err = parse_replacements(bad).unwrap_err();
+            assert_eq!(ErrorKind::MissingReplacement, err.kind);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+/*!
+Provides a handle to a searcher that can be swapped while it's in use, for
+services that reload their dictionaries without restarting.
+
+A [`SearcherHandle`] holds the current [`AhoCorasick`] searcher. Any number
+of threads may [load](SearcherHandle::load) it while another thread
+[swaps](SearcherHandle::swap) in a searcher built from a new dictionary.
+Loading a searcher returns a clone of it, which is cheap since searchers
+share their automaton, so that a search or a replacer started before a swap
+keeps using the searcher it was started with until it's dropped. A swap never
+waits for those searches to finish, and never tears a searcher: every load
+returns either the searcher before a swap or the searcher after it.
+
+The handle only holds its lock for as long as it takes to clone or replace
+the searcher, never while searching. Since searchers are immutable, that's
+all the synchronization that concurrent searches during a swap need. This is
+checked by `loom` model tests, which explore every interleaving of loads and
+swaps. They run with:
+
+```text
+RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+```
+
+This module is only available when the `std` feature is enabled.
+
+# Example
+
+```
+use aho_corasick::{handle::SearcherHandle, AhoCorasick};
+
+let handle = SearcherHandle::new(AhoCorasick::new(&["Sherlock"])?);
+let before = handle.load();
+// A reload swaps in a searcher built from a new dictionary.
+handle.swap(AhoCorasick::new(&["Watson"])?);
+
+// The searcher loaded before the swap is still usable, while new loads see
+// the new one.
+assert!(before.is_match("Sherlock Holmes"));
+assert!(!handle.load().is_match("Sherlock Holmes"));
+assert!(handle.load().is_match("Dr. Watson"));
+assert_eq!(1, handle.generation());
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use crate::{ahocorasick::AhoCorasick, util::sync::RwLock};
+
+/// A thread safe handle to a searcher that can be swapped while it's in use.
+///
+/// See the [module documentation](self) for an overview.
+#[derive(Debug)]
+pub struct SearcherHandle {
+    current: RwLock<Current>,
+}
+
+/// The searcher of a handle, behind its lock.
+#[derive(Debug)]
+struct Current {
+    ac: AhoCorasick,
+    /// The number of swaps so far.
+    generation: u64,
+}
+
+impl SearcherHandle {
+    /// Create a new handle holding the given searcher.
+    pub fn new(ac: AhoCorasick) -> SearcherHandle {
+        SearcherHandle { current: RwLock::new(Current { ac, generation: 0 }) }
+    }
+
+    /// Returns the current searcher.
+    ///
+    /// The searcher returned is unaffected by later swaps.
+    pub fn load(&self) -> AhoCorasick {
+        self.read(|current| current.ac.clone())
+    }
+
+    /// Returns the current searcher along with its generation, which is the
+    /// number of swaps that happened before it was swapped in.
+    ///
+    /// Both are read at once, so the generation always matches the searcher.
+    pub fn load_with_generation(&self) -> (AhoCorasick, u64) {
+        self.read(|current| (current.ac.clone(), current.generation))
+    }
+
+    /// Replace the current searcher with the given one, and return the
+    /// searcher it replaced.
+    ///
+    /// This doesn't wait for the searches using the replaced searcher to
+    /// finish.
+    pub fn swap(&self, ac: AhoCorasick) -> AhoCorasick {
+        // A panic while the lock is held can't leave the searcher in an
+        // inconsistent state, since it's replaced in a single statement.
+        let mut current =
+            self.current.write().unwrap_or_else(|err| err.into_inner());
+        current.generation += 1;
+        core::mem::replace(&mut current.ac, ac)
+    }
+
+    /// Returns the number of swaps so far.
+    pub fn generation(&self) -> u64 {
+        self.read(|current| current.generation)
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&Current) -> T) -> T {
+        let current =
+            self.current.read().unwrap_or_else(|err| err.into_inner());
+        f(&current)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::{sync::Arc, thread, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn swap_returns_previous_searcher() {
+        let handle = SearcherHandle::new(AhoCorasick::new(["a"]).unwrap());
+        let old = handle.swap(AhoCorasick::new(["b"]).unwrap());
+        assert!(old.is_match("a"));
+        let (ac, generation) = handle.load_with_generation();
+        assert!(ac.is_match("b") && !ac.is_match("a"));
+        assert_eq!(1, generation);
+    }
+
+    #[test]
+    fn loads_during_swaps_see_whole_searchers() {
+        // Each generation searches for its own number, so that a searcher
+        // loaded with the wrong generation is detected.
+        let searcher = |generation: u64| {
+            AhoCorasick::new([std::format!("<{}>", generation)]).unwrap()
+        };
+        let handle = Arc::new(SearcherHandle::new(searcher(0)));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let handle = Arc::clone(&handle);
+                thread::spawn(move || {
+                    let mut last = 0;
+                    for _ in 0..1000 {
+                        let (ac, generation) = handle.load_with_generation();
+                        assert!(generation >= last);
+                        let needle = std::format!("<{}>", generation);
+                        assert!(ac.is_match(&needle));
+                        last = generation;
+                    }
+                })
+            })
+            .collect();
+        for generation in 1..=100 {
+            handle.swap(searcher(generation));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(100, handle.generation());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::{sync::Arc, thread};
+
+    use crate::cache::AutomatonCache;
+
+    use super::*;
+
+    // This checks that a load racing with a swap sees either the searcher
+    // before the swap or the searcher after it, with the matching
+    // generation, and that a replacer started before the swap keeps
+    // replacing with the searcher it was started with.
+    #[test]
+    fn loom_load_during_swap() {
+        let old = AhoCorasick::new(["old"]).unwrap();
+        let new = AhoCorasick::new(["new"]).unwrap();
+        loom::model(move || {
+            let handle = Arc::new(SearcherHandle::new(old.clone()));
+            let mut replacer = handle.load().replacer(["X"]).unwrap();
+
+            let reader = {
+                let handle = Arc::clone(&handle);
+                thread::spawn(move || {
+                    let (ac, generation) = handle.load_with_generation();
+                    match generation {
+                        0 => {
+                            assert!(ac.is_match("old") && !ac.is_match("new"))
+                        }
+                        1 => {
+                            assert!(ac.is_match("new") && !ac.is_match("old"))
+                        }
+                        _ => panic!("unexpected generation {}", generation),
+                    }
+                })
+            };
+            let replaced = handle.swap(new.clone());
+            assert!(replaced.is_match("old"));
+            reader.join().unwrap();
+
+            let mut out = replacer.replace(b"old new").unwrap().to_vec();
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(b"X new".to_vec(), out);
+            let (ac, generation) = handle.load_with_generation();
+            assert!(ac.is_match("new"));
+            assert_eq!(1, generation);
+        });
+    }
+
+    // This checks that concurrent swaps are serialized: each swap replaces
+    // a different searcher, and the last one stays.
+    #[test]
+    fn loom_concurrent_swaps() {
+        let searchers: std::vec::Vec<AhoCorasick> = ["0", "1", "2"]
+            .iter()
+            .map(|p| AhoCorasick::new([p]).unwrap())
+            .collect();
+        loom::model(move || {
+            let handle = Arc::new(SearcherHandle::new(searchers[0].clone()));
+            let swapper = {
+                let handle = Arc::clone(&handle);
+                let ac = searchers[1].clone();
+                thread::spawn(move || handle.swap(ac))
+            };
+            let mine = handle.swap(searchers[2].clone());
+            let theirs = swapper.join().unwrap();
+            let last = handle.load();
+            assert_eq!(2, handle.generation());
+            // Either this thread swapped first, in which case the other
+            // thread replaced this thread's searcher, or the other way
+            // around.
+            if mine.is_match("0") {
+                assert!(theirs.is_match("2") && last.is_match("1"));
+            } else {
+                assert!(mine.is_match("1"));
+                assert!(theirs.is_match("0") && last.is_match("2"));
+            }
+        });
+    }
+
+    // This checks that concurrent lookups in a shared cache both get a
+    // searcher, and that the cache ends up holding a single one.
+    #[test]
+    fn loom_cache_concurrent_lookups() {
+        let builder = AhoCorasick::builder();
+        loom::model(move || {
+            let cache = Arc::new(AutomatonCache::new(1));
+            let other = {
+                let cache = Arc::clone(&cache);
+                let builder = builder.clone();
+                thread::spawn(move || {
+                    cache.get_or_build(&builder, ["foo"]).unwrap()
+                })
+            };
+            let mine = cache.get_or_build(&builder, ["foo"]).unwrap();
+            let theirs = other.join().unwrap();
+            assert!(mine.is_match("foo") && theirs.is_match("foo"));
+            assert_eq!(1, cache.len());
+            let stats = cache.stats();
+            assert_eq!(2, stats.hits() + stats.misses());
+            assert!(stats.misses() >= 1);
+        });
+    }
+}
@@ -219,9 +219,41 @@ this crate can be used without the standard library.
   Aho-Corasick for large numbers of patterns or otherwise can abide lower
   throughput when searching with a small number of patterns, then it is
   reasonable to disable this feature.
+* **perf-unchecked** -
+  Elides bounds checks on state transitions in the DFA search, stream search
+  and replacement loops. This relies on the DFA's invariants rather than on
+  runtime checks, and is checked by a dedicated fuzz target. This feature is
+  disabled by default.
 * **logging** -
   Enables a dependency on the `log` crate and emits messages to aide in
   diagnostics. This feature is disabled by default.
+* **rayon** -
+  Enables a dependency on the `rayon` crate and provides parallel search
+  routines such as `AhoCorasick::par_find_many`. This feature is disabled by
+  default and implies the `std` feature.
+* **serde** -
+  Enables a dependency on the `serde` crate and provides `Serialize` and
+  `Deserialize` impls for [`Match`], [`Span`] and [`PatternID`]. This feature
+  is disabled by default.
+* **arbitrary** -
+  Enables a dependency on the `arbitrary` crate and provides `Arbitrary`
+  impls for the configuration types, such as [`MatchKind`], and for
+  structured fuzzing inputs: `PatternSetSpec`, `ChunkPlan` and
+  `BuilderOptions`. This feature is disabled by default.
+* **conformance** -
+  Enables a dependency on the upstream `aho-corasick` crate and provides the
+  `conformance` module, which checks that the replacements made by this crate
+  agree with upstream's `replace_all`. This feature is disabled by default
+  and implies the `std` feature.
+* **secrets** -
+  Provides the `secrets` module, which contains a curated preset of rules
+  for finding secrets such as API keys and private keys. This feature is
+  disabled by default and implies the `std` feature.
+* **cli** -
+  Builds the `acr` binary, which replaces the patterns of a dictionary (see
+  the [`dictionary`] module) while streaming from stdin to stdout, with
+  flags for the kind of automaton, the match semantics and the chunk size.
+  This feature is disabled by default and implies the `std` feature.
 */
 
 #![no_std]
@@ -237,17 +269,44 @@ extern crate std;
 doc_comment::doctest!("../README.md");
 
 #[cfg(feature = "std")]
-pub use crate::ahocorasick::StreamFindIter;
+pub use crate::ahocorasick::{
+    AhoCorasickReader, AhoCorasickWriter, StreamFindIter,
+    StreamLineColumnIter, StreamRateIter,
+};
+#[cfg(all(feature = "async", feature = "std"))]
+pub use crate::r#async::{
+    reader::AhoCorasickAsyncReader, writer::AhoCorasickAsyncWriter,
+};
+#[cfg(feature = "arbitrary")]
+pub use crate::util::arbitrary::{BuilderOptions, ChunkPlan, PatternSetSpec};
+#[cfg(feature = "std")]
+pub use crate::util::rate::MatchRates;
 pub use crate::{
     ahocorasick::{
-        AhoCorasick, AhoCorasickBuilder, AhoCorasickKind, FindIter,
-        FindOverlappingIter,
-        AhoCorasickReplacer
+        AhoCorasick, AhoCorasickBuilder, AhoCorasickKind, AhoCorasickReplacer,
+        AhoCorasickReplacerBuilder, AhoCorasickReplacerWith,
+        AhoCorasickStreamFinder, AhoCorasickStreamSplitter, ChunkedReplacer,
+        EventStreamReplacer, FindIter, FindIterU32, FindOverlappingIter,
+        LineReplacer, MemoryLimits, OverlongLine, Padding, PendingLimit,
+        ReplaceIter, ReplaceStats, ReplacerError, Selection, SelectionState,
+        ShadowDiff, ShadowReplacer, SplitRecord, SplitRecords,
     },
     util::{
-        error::{BuildError, MatchError, MatchErrorKind},
+        dedup::MatchDeduper,
+        error::{BuildError, DeserializeError, MatchError, MatchErrorKind},
+        graph::AutomatonGraph,
+        lookahead::Lookahead,
+        overlap::OverlapPolicy,
+        pattern_set::PatternSet,
+        position::{ColumnUnit, LineColumn},
         primitives::{PatternID, PatternIDError},
-        search::{Anchored, Input, Match, MatchKind, Span, StartKind},
+        profile::{SearchProfile, SearchProfiler, StateVisits},
+        search::{
+            Anchored, DuplicatePolicy, Input, Match, MatchKind, MatchU32,
+            Span, StartKind,
+        },
+        top_k::TopPatterns,
+        validate::{ContainedPatterns, ValidationIssue, ValidationReport},
     },
 };
 
@@ -256,12 +315,27 @@ mod macros;
 
 mod ahocorasick;
 pub mod automaton;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "conformance")]
+pub mod conformance;
 pub mod dfa;
+pub mod dictionary;
+#[cfg(feature = "std")]
+pub mod handle;
+pub mod merge;
 pub mod nfa;
 pub mod packed;
 pub mod replacer;
+pub mod scanner;
+#[cfg(feature = "secrets")]
+pub mod secrets;
+pub mod sharded;
+pub mod signature;
 #[cfg(test)]
 mod tests;
+pub mod two_level;
+pub mod utf16;
 // I wrote out the module for implementing fst::Automaton only to later realize
 // that this would make fst a public dependency and fst is not at 1.0 yet. I
 // decided to just keep the code in tree, but build it only during tests.
@@ -289,16 +363,48 @@ mod testoibits {
         assert_all::<AhoCorasick>();
         assert_all::<AhoCorasickBuilder>();
         assert_all::<AhoCorasickKind>();
+        assert_all::<AhoCorasickReplacer>();
+        assert_all::<AhoCorasickReplacerBuilder>();
+        assert_all::<ShadowReplacer>();
+        assert_all::<ShadowDiff>();
+        assert_all::<LineReplacer>();
+        assert_all::<OverlongLine>();
+        assert_all::<ChunkedReplacer>();
+        assert_all::<EventStreamReplacer>();
+        assert_all::<AhoCorasickStreamSplitter>();
+        assert_all::<AhoCorasickStreamFinder>();
+        assert_all::<ReplaceStats>();
+        assert_all::<MatchDeduper>();
+        assert_all::<TopPatterns>();
+        assert_all::<Lookahead>();
+        assert_all::<OverlapPolicy>();
         assert_all::<FindIter>();
         assert_all::<FindOverlappingIter>();
+        assert_all::<FindIterU32>();
+        assert_all::<merge::MergedFindIter>();
+        assert_all::<merge::MergedMatch>();
+        #[cfg(feature = "std")]
+        assert_all::<MatchRates>();
+        #[cfg(feature = "std")]
+        assert_all::<AhoCorasickReader<std::io::Stdin>>();
+        #[cfg(feature = "std")]
+        assert_all::<AhoCorasickWriter<std::io::Stdout>>();
+        #[cfg(feature = "std")]
+        assert_all::<cache::AutomatonCache>();
+        #[cfg(feature = "std")]
+        assert_all::<cache::CacheStats>();
+        #[cfg(feature = "std")]
+        assert_all::<handle::SearcherHandle>();
 
         assert_all::<BuildError>();
+        assert_all::<DeserializeError>();
         assert_all::<MatchError>();
         assert_all::<MatchErrorKind>();
 
         assert_all::<Anchored>();
         assert_all::<Input>();
         assert_all::<Match>();
+        assert_all::<MatchU32>();
         assert_all::<MatchKind>();
         assert_all::<Span>();
         assert_all::<StartKind>();
@@ -0,0 +1,301 @@
+/*!
+Provides an iterator that merges the matches of several automata over the
+same haystack.
+
+It's common to keep patterns of different categories in separate automata,
+for example, because they are maintained or configured independently. A
+[`MergedFindIter`] searches a haystack with all of them at once, and reports
+a single stream of non-overlapping matches in the order of their position,
+as if a single automaton had been searched.
+
+# Match semantics
+
+Each automaton, called a source, reports its matches according to its own
+configuration. Among the matches of all sources from the current position,
+the one that starts first is reported, and among matches starting at the same
+offset, the one of the source given first is reported. This is the same as
+[`MatchKind::LeftmostFirst`](crate::MatchKind::LeftmostFirst), where sources
+play the role of patterns: sources given first have priority. The search then
+resumes at the end of the reported match, so a match of a source that
+overlaps it is never reported, even if it was the leftmost match of that
+source.
+
+In particular, merging automata that all use leftmost-first semantics reports
+the same matches as a single automaton with leftmost-first semantics built
+from the patterns of every source, in the same order, as long as there are no
+empty patterns.
+
+# Example
+
+```
+use aho_corasick::{merge, AhoCorasick, MatchKind};
+
+let kind = MatchKind::LeftmostFirst;
+let names = AhoCorasick::builder()
+    .match_kind(kind)
+    .build(&["Sam", "Samwise", "Frodo"])
+    .unwrap();
+let places = AhoCorasick::builder()
+    .match_kind(kind)
+    .build(&["Shire", "Samwise's garden"])
+    .unwrap();
+let haystack = "Frodo left the Shire. Samwise's garden was empty.";
+
+let matches: Vec<(usize, &str)> = merge::find_iter(&[&names, &places], haystack)
+    .map(|m| (m.source(), &haystack[m.range()]))
+    .collect();
+assert_eq!(vec![(0, "Frodo"), (1, "Shire"), (0, "Sam")], matches);
+```
+*/
+
+use alloc::vec::Vec;
+
+use crate::{
+    ahocorasick::{enforce_anchored_consistency, AhoCorasick},
+    util::{
+        error::MatchError,
+        primitives::PatternID,
+        search::{Input, Match, Span},
+    },
+};
+
+/// Returns an iterator of the non-overlapping matches of every source in the
+/// given input, in the order of their position.
+///
+/// See the [module documentation](self) for the semantics of the matches
+/// reported.
+///
+/// # Panics
+///
+/// This panics when the search is not supported by the configuration of one
+/// of the sources, such as an anchored search when a source was built
+/// without support for them. Use [`try_find_iter`] to handle these errors.
+pub fn find_iter<'a, 'h, I: Into<Input<'h>>>(
+    sources: &[&'a AhoCorasick],
+    input: I,
+) -> MergedFindIter<'a, 'h> {
+    try_find_iter(sources, input).unwrap()
+}
+
+/// Returns an iterator of the non-overlapping matches of every source in the
+/// given input, in the order of their position.
+///
+/// # Errors
+///
+/// This returns an error when the search is not supported by the
+/// configuration of one of the sources, in the same way as
+/// [`AhoCorasick::try_find`].
+pub fn try_find_iter<'a, 'h, I: Into<Input<'h>>>(
+    sources: &[&'a AhoCorasick],
+    input: I,
+) -> Result<MergedFindIter<'a, 'h>, MatchError> {
+    let input = input.into();
+    // This ensures that searches with this configuration never fail, so that
+    // the iterator never has to report an error.
+    for ac in sources.iter() {
+        enforce_anchored_consistency(ac.start_kind(), input.get_anchored())?;
+    }
+    let sources = sources.iter().map(|&ac| (ac, None)).collect();
+    Ok(MergedFindIter { sources, input, last_match_end: None })
+}
+
+/// A match reported by a [`MergedFindIter`], along with the source that
+/// reported it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MergedMatch {
+    source: usize,
+    mat: Match,
+}
+
+impl MergedMatch {
+    /// Returns the index of the source that reported this match, in the
+    /// order the sources were given.
+    pub fn source(&self) -> usize {
+        self.source
+    }
+
+    /// Returns the match, whose pattern ID is the ID of the pattern in its
+    /// source.
+    pub fn as_match(&self) -> Match {
+        self.mat
+    }
+
+    /// Returns the ID of the pattern that matched, in its source.
+    pub fn pattern(&self) -> PatternID {
+        self.mat.pattern()
+    }
+
+    /// Returns the starting offset of the match.
+    pub fn start(&self) -> usize {
+        self.mat.start()
+    }
+
+    /// Returns the ending offset of the match.
+    pub fn end(&self) -> usize {
+        self.mat.end()
+    }
+
+    /// Returns the span of the match.
+    pub fn span(&self) -> Span {
+        self.mat.span()
+    }
+
+    /// Returns the match span as a range.
+    pub fn range(&self) -> core::ops::Range<usize> {
+        self.mat.range()
+    }
+}
+
+/// An iterator of the non-overlapping matches of several automata, in the
+/// order of their position.
+///
+/// This iterator is constructed via [`find_iter`] or [`try_find_iter`].
+///
+/// The lifetime `'a` refers to the lifetime of the sources, and the lifetime
+/// `'h` refers to the lifetime of the haystack being searched.
+#[derive(Debug)]
+pub struct MergedFindIter<'a, 'h> {
+    /// Each source, along with its last result for an unanchored search.
+    ///
+    /// A search only ever moves forward, and the match a source reports is
+    /// the same for any start offset that is not past the start of that
+    /// match. So a result is only recomputed once the search moves past the
+    /// start of its match, and each source scans the haystack roughly once.
+    sources: Vec<(&'a AhoCorasick, Option<Option<Match>>)>,
+    /// The input parameters to give to each search call. The start position
+    /// of the search is mutated during iteration.
+    input: Input<'h>,
+    /// The end offset of the most recent match, which is used to prevent
+    /// empty matches from overlapping with the end of a prior match.
+    last_match_end: Option<usize>,
+}
+
+impl<'a, 'h> MergedFindIter<'a, 'h> {
+    /// Executes a search from the current start position, and returns a
+    /// match if one is found. This does not advance the input.
+    fn search(&mut self) -> Option<MergedMatch> {
+        let start = self.input.start();
+        // An anchored search depends on where it starts, so its results are
+        // never reused.
+        let anchored = self.input.get_anchored().is_anchored();
+        let mut best: Option<MergedMatch> = None;
+        for (source, &mut (ac, ref mut cached)) in
+            self.sources.iter_mut().enumerate()
+        {
+            let m = match *cached {
+                Some(Some(m)) if !anchored && m.start() >= start => Some(m),
+                Some(None) if !anchored => None,
+                _ => {
+                    // The unwrap is OK here because we check at iterator
+                    // construction time that no search with the same
+                    // configuration returns an error.
+                    let m = ac.try_find(self.input.clone()).unwrap();
+                    *cached = Some(m);
+                    m
+                }
+            };
+            if let Some(mat) = m {
+                if best.map_or(true, |b| mat.start() < b.start()) {
+                    best = Some(MergedMatch { source, mat });
+                }
+            }
+        }
+        best
+    }
+}
+
+impl<'a, 'h> Iterator for MergedFindIter<'a, 'h> {
+    type Item = MergedMatch;
+
+    fn next(&mut self) -> Option<MergedMatch> {
+        if self.input.is_done() {
+            return None;
+        }
+        let mut m = self.search()?;
+        if m.mat.is_empty() && Some(m.end()) == self.last_match_end {
+            // As with AhoCorasick::find_iter, an empty match may not overlap
+            // with the end of the previous match.
+            self.input.set_start(self.input.start().checked_add(1).unwrap());
+            m = self.search()?;
+        }
+        self.input.set_start(m.end());
+        self.last_match_end = Some(m.end());
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        util::rng::Rng, AhoCorasick, Anchored, Input, MatchKind, StartKind,
+    };
+
+    #[test]
+    fn same_matches_as_single_leftmost_first_automaton() {
+        let mut rng = Rng::new(0x2545F4914F6CDD1D);
+        let mut builder = AhoCorasick::builder();
+        builder
+            .match_kind(MatchKind::LeftmostFirst)
+            .start_kind(StartKind::Both);
+        for _ in 0..300 {
+            let sources: Vec<Vec<Vec<u8>>> = (0..1 + rng.below(3))
+                .map(|_| {
+                    (0..1 + rng.below(4))
+                        .map(|_| rng.word(1, 3, b"abc"))
+                        .collect()
+                })
+                .collect();
+            let haystack = rng.word(0, 20, b"abc");
+            let automata: Vec<AhoCorasick> =
+                sources.iter().map(|p| builder.build(p).unwrap()).collect();
+            let refs: Vec<&AhoCorasick> = automata.iter().collect();
+            let single = builder.build(sources.concat()).unwrap();
+            for anchored in [Anchored::No, Anchored::Yes] {
+                let input = Input::new(&haystack).anchored(anchored);
+                let merged: Vec<(usize, usize, usize)> =
+                    super::find_iter(&refs, input.clone())
+                        .map(|m| (m.start(), m.end(), m.source()))
+                        .collect();
+                // The pattern of a match of the single automaton tells which
+                // source it's from, since their patterns are concatenated.
+                let expected: Vec<(usize, usize, usize)> = single
+                    .find_iter(input)
+                    .map(|m| {
+                        let mut pid = m.pattern().as_usize();
+                        let mut source = 0;
+                        while pid >= sources[source].len() {
+                            pid -= sources[source].len();
+                            source += 1;
+                        }
+                        (m.start(), m.end(), source)
+                    })
+                    .collect();
+                assert_eq!(expected, merged, "{:?} {:?}", sources, haystack);
+            }
+        }
+    }
+
+    #[test]
+    fn sources_with_different_semantics() {
+        let standard = AhoCorasick::new(["abcd", "b"]).unwrap();
+        let longest = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(["ab", "abc", ""])
+            .unwrap();
+        let merged: Vec<(usize, usize, usize)> =
+            super::find_iter(&[&standard, &longest], "xabcab")
+                .map(|m| (m.source(), m.start(), m.end()))
+                .collect();
+        // The empty match at 0 wins, and once "abc" is reported, the match of
+        // "b" it overlaps is not.
+        assert_eq!(vec![(1, 0, 0), (1, 1, 4), (1, 4, 6)], merged);
+
+        let anchored = AhoCorasick::builder()
+            .start_kind(StartKind::Anchored)
+            .build(["a"])
+            .unwrap();
+        assert!(super::try_find_iter(&[&standard, &anchored], "a").is_err());
+    }
+}
@@ -7,19 +7,20 @@ instead of a contiguous NFA directly. Using an `NFA` directly is typically only
 necessary when one needs access to the [`Automaton`] trait implementation.
 */
 
-use alloc::{vec, vec::Vec};
+use alloc::{collections::VecDeque, vec, vec::Vec};
 
 use crate::{
     automaton::Automaton,
     nfa::noncontiguous,
     util::{
         alphabet::ByteClasses,
-        error::{BuildError, MatchError},
+        error::{BuildError, DeserializeError, MatchError},
         int::{Usize, U16, U32},
         prefilter::Prefilter,
         primitives::{IteratorIndexExt, PatternID, SmallIndex, StateID},
-        search::{Anchored, MatchKind},
+        search::{Anchored, DuplicatePolicy, MatchKind},
         special::Special,
+        wire::{self, Reader, Writer},
     },
 };
 
@@ -168,6 +169,290 @@ impl NFA {
     /// Very hand wavy... But the code complexity that results from this is
     /// very mild.
     const FAIL: StateID = StateID::new_unchecked(1);
+
+    /// Returns the ID of every state in this NFA, in the order in which they
+    /// are laid out. The FAIL state doesn't actually have space allocated for
+    /// it, so it isn't included.
+    pub(crate) fn state_ids(&self) -> Vec<StateID> {
+        let mut sids = vec![];
+        let mut at = 0;
+        while at < self.repr.len() {
+            let sid = StateID::new_unchecked(at);
+            sids.push(sid);
+            let raw = &self.repr[at..];
+            at += State::len(self.alphabet_len, self.is_match(sid), raw);
+        }
+        sids
+    }
+
+    /// Returns the failure transition of the given state.
+    pub(crate) fn fail(&self, sid: StateID) -> StateID {
+        StateID::from_u32_unchecked(self.repr[sid.as_usize() + 1])
+    }
+
+    /// Returns every transition of the given state that isn't to the FAIL
+    /// state, in ascending order of byte.
+    ///
+    /// Unlike the transitions of a state, which are defined on equivalence
+    /// classes, this returns one transition for each byte.
+    pub(crate) fn byte_transitions(&self, sid: StateID) -> Vec<(u8, StateID)> {
+        let raw = &self.repr[sid.as_usize()..];
+        let state = State::read(self.alphabet_len, self.is_match(sid), raw);
+        let mut class_to_next = vec![NFA::FAIL; self.alphabet_len];
+        for (class, next) in state.transitions() {
+            class_to_next[usize::from(class)] = next;
+        }
+        (0..=255u8)
+            .map(|byte| {
+                let class = self.byte_classes.get(byte);
+                (byte, class_to_next[usize::from(class)])
+            })
+            .filter(|&(_, next)| next != NFA::FAIL)
+            .collect()
+    }
+}
+
+impl NFA {
+    /// Identifies the bytes of a serialized NFA.
+    const MAGIC: &'static [u8; 8] = b"ACCNFA\0\0";
+
+    /// Serialize this NFA into bytes, from which [`NFA::from_bytes`]
+    /// reads it back without building it again.
+    ///
+    /// Building an automaton from many patterns is much more expensive than
+    /// reading it, so this permits to build it once, persist it, and read it
+    /// at startup. Every integer is encoded in little endian byte order, so
+    /// the bytes don't depend on the platform they were written on. They
+    /// begin with a version of the encoding, which is specific to the
+    /// version of this crate, and end with a checksum.
+    ///
+    /// The prefilter of this NFA isn't serialized, since it doesn't have
+    /// the patterns it would be built from. A deserialized NFA finds the
+    /// same matches, but without a prefilter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{automaton::Automaton, nfa::contiguous::NFA, Input};
+    ///
+    /// let aut = NFA::new(&["Sherlock", "Watson"]).unwrap();
+    /// let bytes = aut.to_bytes();
+    /// let read = NFA::from_bytes(&bytes)?;
+    /// let m = read.try_find(&Input::new("Dr. Watson"))?.unwrap();
+    /// assert_eq!(1, m.pattern().as_usize());
+    ///
+    /// // Corrupted bytes are rejected.
+    /// assert!(NFA::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::seal(NFA::MAGIC, |w| self.write_to(w))
+    }
+
+    /// Deserialize a NFA from bytes written by [`NFA::to_bytes`].
+    ///
+    /// The bytes are checked to describe a NFA that this crate could have
+    /// built, so that searching the NFA never misbehaves, even if the bytes
+    /// were corrupted. The transitions are copied out of the bytes, so they
+    /// may be dropped, or unmapped if they are a memory mapped file, once
+    /// this returns.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the bytes weren't written by
+    /// [`NFA::to_bytes`], if they were written with another version of the
+    /// encoding, or if they are truncated or corrupted.
+    pub fn from_bytes(bytes: &[u8]) -> Result<NFA, DeserializeError> {
+        let mut r = wire::unseal(NFA::MAGIC, bytes)?;
+        match NFA::read_from(&mut r, None) {
+            Some(aut) if r.is_empty() => Ok(aut),
+            _ => Err(DeserializeError::invalid()),
+        }
+    }
+
+    /// Write this NFA, except for its prefilter, to the given writer.
+    ///
+    /// Whether the NFA has a prefilter is written, since its special states
+    /// depend on it, but the prefilter itself is rebuilt from the patterns
+    /// when the NFA is read, if they are known.
+    pub(crate) fn write_to(&self, w: &mut Writer) {
+        w.match_kind(self.match_kind);
+        w.usize(self.state_len);
+        w.raw(self.byte_classes.as_bytes());
+        w.usize(self.min_pattern_len);
+        w.usize(self.max_pattern_len);
+        w.special(&self.special);
+        w.u32s(self.repr.iter().copied());
+        w.small_indices(&self.pattern_lens);
+        w.bool(self.prefilter.is_some());
+    }
+
+    /// Read an NFA written by `NFA::write_to`, with the given prefilter.
+    ///
+    /// Without a prefilter, an automaton that was written with one is read
+    /// as if it was built without one.
+    ///
+    /// This returns `None` if the input is truncated, or if it doesn't
+    /// describe an NFA this crate could have built.
+    pub(crate) fn read_from(
+        r: &mut Reader<'_>,
+        prefilter: Option<Prefilter>,
+    ) -> Option<NFA> {
+        let match_kind = r.match_kind()?;
+        let state_len = r.usize()?;
+        let mut classes = [0; 256];
+        classes.copy_from_slice(r.raw(256)?);
+        let byte_classes = ByteClasses::from_bytes(classes)?;
+        let min_pattern_len = r.usize()?;
+        let max_pattern_len = r.usize()?;
+        let mut special = r.special()?;
+        let repr = r.u32s()?;
+        let pattern_lens = r.small_indices()?;
+        match (r.bool()?, prefilter.is_some()) {
+            (false, true) => return None,
+            // Start states are only special when there is a prefilter, so
+            // they become ordinary states once it's dropped, as if the
+            // automaton was built without one.
+            (true, false) => special.max_special_id = special.max_match_id,
+            _ => {}
+        }
+        let nfa = NFA {
+            repr,
+            pattern_lens,
+            state_len,
+            prefilter,
+            match_kind,
+            alphabet_len: byte_classes.alphabet_len(),
+            byte_classes,
+            min_pattern_len,
+            max_pattern_len,
+            special,
+        };
+        if !nfa.is_valid() {
+            return None;
+        }
+        Some(nfa)
+    }
+
+    /// Returns true if every state of this NFA is entirely encoded in its
+    /// representation, and every state ID in it points to the start of a
+    /// state, or is the FAIL state for a transition.
+    fn is_valid(&self) -> bool {
+        let mut starts = vec![false; self.repr.len()];
+        let mut sids = vec![];
+        let mut at = 0;
+        while at < self.repr.len() {
+            let sid = match StateID::new(at) {
+                Ok(sid) => sid,
+                Err(_) => return false,
+            };
+            let raw = &self.repr[at..];
+            if raw.len() < 2 {
+                return false;
+            }
+            let kind = State::kind(raw);
+            let trans_len = if kind == State::KIND_DENSE {
+                self.alphabet_len
+            } else if kind == State::KIND_ONE {
+                1
+            } else if kind.as_usize() <= State::MAX_SPARSE_TRANSITIONS {
+                u32_len(kind.as_usize()) + kind.as_usize()
+            } else {
+                return false;
+            };
+            if raw.len() < 2 + trans_len {
+                return false;
+            }
+            let is_match = self.is_match(sid);
+            if is_match {
+                // A "one transition" state is never a match state.
+                if kind == State::KIND_ONE {
+                    return false;
+                }
+                let patterns_len = self.pattern_lens.len();
+                let is_pattern = |&pid: &u32| pid.as_usize() < patterns_len;
+                let is_valid = match raw.get(2 + trans_len) {
+                    None => false,
+                    Some(&packed) if packed & (1 << 31) != 0 => {
+                        is_pattern(&(packed & !(1 << 31)))
+                    }
+                    Some(&packed) => {
+                        match raw[3 + trans_len..].get(..packed.as_usize()) {
+                            Some(pids) => {
+                                !pids.is_empty() && pids.iter().all(is_pattern)
+                            }
+                            None => false,
+                        }
+                    }
+                };
+                if !is_valid {
+                    return false;
+                }
+            }
+            starts[at] = true;
+            sids.push(sid);
+            at += State::len(self.alphabet_len, is_match, raw);
+        }
+        let is_state =
+            |sid: StateID| starts.get(sid.as_usize()) == Some(&true);
+        // The FAIL state isn't encoded, but it is counted.
+        let is_encoded = sids.len().checked_add(1) == Some(self.state_len)
+            && is_state(self.special.max_special_id)
+            && is_state(self.special.max_match_id)
+            && is_state(self.special.start_unanchored_id)
+            && is_state(self.special.start_anchored_id)
+            && sids.iter().all(|&sid| {
+                let raw = &self.repr[sid.as_usize()..];
+                let state =
+                    State::read(self.alphabet_len, self.is_match(sid), raw);
+                is_state(state.fail)
+                    && state
+                        .transitions()
+                        .all(|(_, next)| next == NFA::FAIL || is_state(next))
+            });
+        if !is_encoded {
+            return false;
+        }
+        // Searches compute the start of a match by subtracting the length of
+        // its pattern from its end, so every pattern of a match state must be
+        // no longer than the fewest bytes that lead to the state from a start
+        // state. Following a failure transition consumes no byte, so it is
+        // explored before the transitions that do.
+        let mut depths = vec![usize::MAX; self.repr.len()];
+        let mut queue = VecDeque::new();
+        for &sid in
+            [self.special.start_unanchored_id, self.special.start_anchored_id]
+                .iter()
+        {
+            depths[sid.as_usize()] = 0;
+            queue.push_back(sid);
+        }
+        while let Some(sid) = queue.pop_front() {
+            let depth = depths[sid.as_usize()];
+            let raw = &self.repr[sid.as_usize()..];
+            let state =
+                State::read(self.alphabet_len, self.is_match(sid), raw);
+            if depth < depths[state.fail.as_usize()] {
+                depths[state.fail.as_usize()] = depth;
+                queue.push_front(state.fail);
+            }
+            for (_, next) in state.transitions() {
+                if next != NFA::FAIL && depth + 1 < depths[next.as_usize()] {
+                    depths[next.as_usize()] = depth + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        sids.iter().all(|&sid| {
+            let depth = depths[sid.as_usize()];
+            depth == usize::MAX
+                || !self.is_match(sid)
+                || (0..self.match_len(sid)).all(|index| {
+                    let pid = self.match_pattern(sid, index);
+                    self.pattern_lens[pid].as_usize() <= depth
+                })
+        })
+    }
 }
 
 // SAFETY: 'start_state' always returns a valid state ID, 'next_state' always
@@ -1034,6 +1319,40 @@ impl Builder {
         self
     }
 
+    /// Set a table that translates every byte of a haystack before it is
+    /// matched against the patterns.
+    ///
+    /// This only applies when using [`Builder::build`] and not
+    /// [`Builder::build_from_noncontiguous`].
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::translation_table`](crate::AhoCorasickBuilder::translation_table)
+    /// for more documentation and examples.
+    pub fn translation_table(
+        &mut self,
+        table: Option<[u8; 256]>,
+    ) -> &mut Builder {
+        self.noncontiguous.translation_table(table);
+        self
+    }
+
+    /// Set what to do with patterns that are identical to an earlier
+    /// pattern once they are folded.
+    ///
+    /// This only applies when using [`Builder::build`] and not
+    /// [`Builder::build_from_noncontiguous`].
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::duplicate_policy`](crate::AhoCorasickBuilder::duplicate_policy)
+    /// for more documentation and examples.
+    pub fn duplicate_policy(
+        &mut self,
+        policy: DuplicatePolicy,
+    ) -> &mut Builder {
+        self.noncontiguous.duplicate_policy(policy);
+        self
+    }
+
     /// Enable heuristic prefilter optimizations.
     ///
     /// This only applies when using [`Builder::build`] and not
@@ -1073,6 +1392,14 @@ impl Builder {
         self.byte_classes = yes;
         self
     }
+
+    /// Write the options of this builder that affect an NFA it builds from
+    /// a noncontiguous NFA. See `AhoCorasickBuilder::options_key`.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_options(&self, w: &mut Writer) {
+        w.usize(self.dense_depth);
+        w.bool(self.byte_classes);
+    }
 }
 
 /// Computes the number of u32 values needed to represent one byte per the
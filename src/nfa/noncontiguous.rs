@@ -21,11 +21,14 @@ use crate::{
         prefilter::{self, opposite_ascii_case, Prefilter},
         primitives::{IteratorIndexExt, PatternID, SmallIndex, StateID},
         remapper::Remapper,
-        search::{Anchored, MatchKind},
+        search::{Anchored, DuplicatePolicy, MatchKind},
         special::Special,
     },
 };
 
+#[cfg(feature = "std")]
+use crate::util::wire::Writer;
+
 /// A noncontiguous NFA implementation of Aho-Corasick.
 ///
 /// When possible, prefer using [`AhoCorasick`](crate::AhoCorasick) instead of
@@ -293,6 +296,38 @@ impl NFA {
         })
     }
 
+    /// Returns every transition of the given state that isn't to the FAIL
+    /// state, in ascending order of byte.
+    pub(crate) fn byte_transitions(&self, sid: StateID) -> Vec<(u8, StateID)> {
+        self.iter_trans(sid)
+            .map(|t| (t.byte, t.next))
+            .filter(|&(_, next)| next != NFA::FAIL)
+            .collect()
+    }
+
+    /// Returns the ID of every pattern that occurs in the given pattern, in
+    /// ascending order, including itself.
+    ///
+    /// This only applies to an NFA with standard match semantics built
+    /// without a translation table, given one of the patterns it was built
+    /// from. Every pattern then has a path in the trie, and the failure
+    /// transitions make each state on the path match every pattern that is
+    /// a suffix of the path so far. The patterns that occur in the given
+    /// pattern are those that end at one of the states on its path.
+    pub(crate) fn occurring_patterns(&self, pattern: &[u8]) -> Vec<PatternID> {
+        debug_assert_eq!(MatchKind::Standard, self.match_kind);
+        let mut sid = self.special.start_unanchored_id;
+        let mut pids: Vec<PatternID> = self.iter_matches(sid).collect();
+        for &b in pattern.iter() {
+            sid = self.follow_transition(sid, b);
+            assert_ne!(NFA::FAIL, sid, "pattern must be in the trie");
+            pids.extend(self.iter_matches(sid));
+        }
+        pids.sort();
+        pids.dedup();
+        pids
+    }
+
     /// Iterate over all of the matches for the given state ID.
     pub(crate) fn iter_matches(
         &self,
@@ -843,6 +878,8 @@ pub struct Builder {
     match_kind: MatchKind,
     prefilter: bool,
     ascii_case_insensitive: bool,
+    translation_table: Option<[u8; 256]>,
+    duplicate_policy: DuplicatePolicy,
     dense_depth: usize,
 }
 
@@ -852,6 +889,8 @@ impl Default for Builder {
             match_kind: MatchKind::default(),
             prefilter: true,
             ascii_case_insensitive: false,
+            translation_table: None,
+            duplicate_policy: DuplicatePolicy::default(),
             dense_depth: 3,
         }
     }
@@ -902,6 +941,95 @@ impl Builder {
         self
     }
 
+    /// Set a table that translates every byte of a haystack before it is
+    /// matched against the patterns.
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::translation_table`](crate::AhoCorasickBuilder::translation_table)
+    /// for more documentation and examples.
+    pub fn translation_table(
+        &mut self,
+        table: Option<[u8; 256]>,
+    ) -> &mut Builder {
+        self.translation_table = table;
+        self
+    }
+
+    /// Set what to do with patterns that are identical to an earlier
+    /// pattern once they are folded.
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::duplicate_policy`](crate::AhoCorasickBuilder::duplicate_policy)
+    /// for more documentation and examples.
+    pub fn duplicate_policy(
+        &mut self,
+        policy: DuplicatePolicy,
+    ) -> &mut Builder {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Returns the match semantics set on this builder.
+    pub(crate) fn get_match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Returns whether ASCII case insensitivity is enabled on this builder.
+    pub(crate) fn get_ascii_case_insensitive(&self) -> bool {
+        self.ascii_case_insensitive
+    }
+
+    /// Returns the translation table set on this builder, if any.
+    pub(crate) fn get_translation_table(&self) -> Option<&[u8; 256]> {
+        self.translation_table.as_ref()
+    }
+
+    /// Write every option of this builder, since they all affect the NFA it
+    /// builds. See `AhoCorasickBuilder::options_key`.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_options(&self, w: &mut Writer) {
+        w.match_kind(self.match_kind);
+        w.bool(self.prefilter);
+        w.bool(self.ascii_case_insensitive);
+        w.bool(self.translation_table.is_some());
+        if let Some(ref table) = self.translation_table {
+            w.raw(table);
+        }
+        w.u8(match self.duplicate_policy {
+            DuplicatePolicy::All => 0,
+            DuplicatePolicy::Lowest => 1,
+            DuplicatePolicy::Error => 2,
+        });
+        w.usize(self.dense_depth);
+    }
+
+    /// Build the prefilter that an NFA built from the given patterns would
+    /// have, without building the NFA.
+    ///
+    /// This is used to restore the prefilter of a persisted automaton, since
+    /// it isn't persisted itself.
+    #[cfg(feature = "std")]
+    pub(crate) fn build_prefilter<I, P>(
+        &self,
+        patterns: I,
+    ) -> Option<Prefilter>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        // This mirrors the Compiler, which doesn't add patterns to the
+        // prefilter when a translation table is set.
+        if !self.prefilter || self.translation_table.is_some() {
+            return None;
+        }
+        let mut builder = prefilter::Builder::new(self.match_kind)
+            .ascii_case_insensitive(self.ascii_case_insensitive);
+        for pat in patterns {
+            builder.add(pat.as_ref());
+        }
+        builder.build()
+    }
+
     /// Set the limit on how many states use a dense representation for their
     /// transitions. Other states will generally use a sparse representation.
     ///
@@ -934,6 +1062,10 @@ struct Compiler<'a> {
     prefilter: prefilter::Builder,
     nfa: NFA,
     byteset: ByteClassSet,
+    /// When a translation table is set, the haystack bytes that translate to
+    /// each pattern byte (or to its opposite case, when ASCII case
+    /// insensitivity is enabled), indexed by pattern byte.
+    translated: Option<Vec<Vec<u8>>>,
 }
 
 impl<'a> Compiler<'a> {
@@ -957,6 +1089,20 @@ impl<'a> Compiler<'a> {
                 special: Special::zero(),
             },
             byteset: ByteClassSet::empty(),
+            translated: builder.translation_table.map(|table| {
+                let mut translated = vec![vec![]; 256];
+                for input in 0..=255u8 {
+                    let b = table[usize::from(input)];
+                    translated[usize::from(b)].push(input);
+                    if builder.ascii_case_insensitive {
+                        let opposite = opposite_ascii_case(b);
+                        if opposite != b {
+                            translated[usize::from(opposite)].push(input);
+                        }
+                    }
+                }
+                translated
+            }),
         })
     }
 
@@ -1091,7 +1237,10 @@ impl<'a> Compiler<'a> {
             // prefilter to permit setting your own pattern IDs. Or to just use
             // our own map and go between them. But this case is sufficiently
             // rare that we don't bother and just make sure they're in sync.
-            if self.builder.prefilter {
+            //
+            // A prefilter looks for the patterns themselves in a haystack, so
+            // it can't be used when the haystack is translated first.
+            if self.builder.prefilter && self.translated.is_none() {
                 self.prefilter.add(pat);
             }
 
@@ -1108,11 +1257,48 @@ impl<'a> Compiler<'a> {
                 // leftmost-first and leftmost-longest.
                 saw_match = saw_match || self.nfa.states[prev].is_match();
                 if self.builder.match_kind.is_leftmost_first() && saw_match {
+                    // A pattern that can never match may still be a
+                    // duplicate, which is rejected regardless.
+                    if self.builder.duplicate_policy == DuplicatePolicy::Error
+                    {
+                        if let Some(first) =
+                            self.duplicate_of(prev, &pat[depth..])
+                        {
+                            return Err(BuildError::duplicate_pattern(
+                                pid, first,
+                            ));
+                        }
+                    }
                     // Skip to the next pattern immediately. This avoids
                     // incorrectly adding a match after this loop terminates.
                     continue 'PATTERNS;
                 }
 
+                if let Some(ref translated) = self.translated {
+                    // Every haystack byte that translates to this byte leads
+                    // to the same state. A pattern with a byte that no
+                    // haystack byte translates to can never match.
+                    let inputs = translated[usize::from(b)].clone();
+                    let key = match inputs.first() {
+                        None => continue 'PATTERNS,
+                        Some(&key) => key,
+                    };
+                    for &input in inputs.iter() {
+                        self.byteset.set_range(input, input);
+                    }
+                    let next = self.nfa.follow_transition(prev, key);
+                    if next != NFA::FAIL {
+                        prev = next;
+                    } else {
+                        let next = self.nfa.alloc_state(depth)?;
+                        for &input in inputs.iter() {
+                            self.nfa.add_transition(prev, input, next)?;
+                        }
+                        prev = next;
+                    }
+                    continue;
+                }
+
                 // Add this byte to our equivalence classes. These don't
                 // get used while building the trie, but other Aho-Corasick
                 // implementations may use them.
@@ -1142,6 +1328,15 @@ impl<'a> Compiler<'a> {
                     prev = next;
                 }
             }
+            if let Some(first) = self.duplicate_of(prev, &[]) {
+                match self.builder.duplicate_policy {
+                    DuplicatePolicy::All => {}
+                    DuplicatePolicy::Lowest => continue 'PATTERNS,
+                    DuplicatePolicy::Error => {
+                        return Err(BuildError::duplicate_pattern(pid, first));
+                    }
+                }
+            }
             // Once the pattern has been added, log the match in the final
             // state that it reached.
             self.nfa.add_match(prev, pid)?;
@@ -1149,6 +1344,31 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    /// Returns the lowest ID of the patterns that end at the state reached by
+    /// following the given bytes of a pattern from the given state, if every
+    /// transition exists.
+    ///
+    /// While building the trie, only the ends of patterns are match states,
+    /// so this returns the first pattern that the rest of a pattern being
+    /// added duplicates once folded.
+    fn duplicate_of(
+        &self,
+        mut sid: StateID,
+        rest: &[u8],
+    ) -> Option<PatternID> {
+        for &b in rest.iter() {
+            let key = match self.translated {
+                None => b,
+                Some(ref translated) => *translated[usize::from(b)].first()?,
+            };
+            sid = self.nfa.follow_transition(sid, key);
+            if sid == NFA::FAIL {
+                return None;
+            }
+        }
+        self.nfa.iter_matches(sid).next()
+    }
+
     /// This routine creates failure transitions according to the standard
     /// textbook formulation of the Aho-Corasick algorithm, with a couple small
     /// tweaks to support "leftmost" semantics.
@@ -1527,12 +1747,12 @@ impl<'a> Compiler<'a> {
 
     /// Returns a set that tracked queued states.
     ///
-    /// This is only necessary when ASCII case insensitivity is enabled, since
-    /// it is the only way to visit the same state twice. Otherwise, this
-    /// returns an inert set that nevers adds anything and always reports
-    /// `false` for every member test.
+    /// This is only necessary when ASCII case insensitivity is enabled or a
+    /// translation table is set, since they are the only ways to visit the
+    /// same state twice. Otherwise, this returns an inert set that nevers
+    /// adds anything and always reports `false` for every member test.
     fn queued_set(&self) -> QueuedSet {
-        if self.builder.ascii_case_insensitive {
+        if self.builder.ascii_case_insensitive || self.translated.is_some() {
             QueuedSet::active()
         } else {
             QueuedSet::inert()
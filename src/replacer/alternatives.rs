@@ -0,0 +1,191 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::PatternID;
+
+/// How a replacer selects the replacement of a match among the alternatives
+/// of its pattern.
+///
+/// Every selection is deterministic: the same stream, split into chunks in
+/// any way, always gets the same replacements. The random selections use a
+/// small pseudorandom number generator, which is fine for generating
+/// variants of a text but not for anything that needs to be unpredictable.
+/// The generator is SplitMix64, whose output for a given seed is the same
+/// on every platform and won't change in future versions, so a seed
+/// reproduces the same replacements across runs and machines.
+///
+/// See
+/// [`AhoCorasick::replacer_with_alternatives`](crate::AhoCorasick::replacer_with_alternatives),
+/// and
+/// [`AhoCorasickReplacer::selection_state`](crate::AhoCorasickReplacer::selection_state)
+/// to resume a selection.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Selection {
+    /// Select the alternatives of each pattern in turn, starting from the
+    /// first one.
+    RoundRobin,
+    /// Select an alternative uniformly at random, from a generator seeded
+    /// with the given value.
+    Random {
+        /// The seed of the generator.
+        seed: u64,
+    },
+    /// Select an alternative at random, with a probability proportional to
+    /// its weight, from a generator seeded with the given value.
+    ///
+    /// `weights[i]` gives the weight of each alternative of the pattern with
+    /// ID `i`, in the same order.
+    Weighted {
+        /// The seed of the generator.
+        seed: u64,
+        /// The weights of the alternatives of each pattern.
+        weights: Vec<Vec<u32>>,
+    },
+}
+
+/// The state of the selection of alternative replacements of a replacer.
+///
+/// It only holds plain numbers, which may be persisted in any format to resume
+/// a selection in another run. See
+/// [`AhoCorasickReplacer::selection_state`](crate::AhoCorasickReplacer::selection_state).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SelectionState {
+    /// The state of a [`Selection::RoundRobin`].
+    RoundRobin {
+        /// The index, among the alternatives of the pattern with ID `i`, of
+        /// the one selected next.
+        next: Vec<usize>,
+    },
+    /// The state of the generator of a [`Selection::Random`] or
+    /// [`Selection::Weighted`].
+    Generator {
+        /// The state of the generator, which starts at the seed.
+        state: u64,
+    },
+}
+
+/// The alternative replacements of each pattern, as ranges of the
+/// replacements of a replacer, along with the state of their selection.
+#[derive(Clone, Debug)]
+pub(crate) struct Alternatives {
+    /// The index of the first alternative of each pattern, followed by the
+    /// number of alternatives.
+    pub(crate) starts: Arc<[usize]>,
+    pub(crate) selector: Selector,
+}
+
+/// The state of a [`Selection`].
+#[derive(Clone, Debug)]
+pub(crate) enum Selector {
+    RoundRobin {
+        /// The alternative of each pattern selected next.
+        next: Vec<usize>,
+    },
+    Random {
+        seed: u64,
+        /// The state of the generator, which starts at the seed.
+        rng: u64,
+    },
+    Weighted {
+        seed: u64,
+        rng: u64,
+        /// The running sum of the weights of the alternatives of each
+        /// pattern.
+        cumulative: Arc<[u64]>,
+    },
+}
+
+impl Alternatives {
+    /// Returns the index of the alternative selected for a match of the
+    /// given pattern.
+    pub(crate) fn select(&mut self, pid: PatternID) -> usize {
+        let (start, end) = (self.starts[pid], self.starts[pid.as_usize() + 1]);
+        match self.selector {
+            Selector::RoundRobin { ref mut next } => {
+                let i = next[pid];
+                next[pid] = (i + 1) % (end - start);
+                start + i
+            }
+            Selector::Random { ref mut rng, .. } => {
+                start + below(rng, (end - start) as u64) as usize
+            }
+            Selector::Weighted { ref mut rng, ref cumulative, .. } => {
+                let cumulative = &cumulative[start..end];
+                let r = below(rng, cumulative[cumulative.len() - 1]);
+                start + cumulative.partition_point(|&sum| sum <= r)
+            }
+        }
+    }
+
+    /// Returns the pattern of the alternative with the given index.
+    pub(crate) fn pattern_of(&self, index: usize) -> PatternID {
+        let after = self.starts.partition_point(|&start| start <= index);
+        PatternID::new_unchecked(after - 1)
+    }
+
+    /// Returns the state of the selection.
+    pub(crate) fn state(&self) -> SelectionState {
+        match self.selector {
+            Selector::RoundRobin { ref next } => {
+                SelectionState::RoundRobin { next: next.clone() }
+            }
+            Selector::Random { rng, .. } | Selector::Weighted { rng, .. } => {
+                SelectionState::Generator { state: rng }
+            }
+        }
+    }
+
+    /// Set the state of the selection, and return true, unless it's not a
+    /// state of this selection.
+    pub(crate) fn set_state(&mut self, state: &SelectionState) -> bool {
+        let starts = &self.starts;
+        let rng = match self.selector {
+            Selector::RoundRobin { ref mut next } => {
+                let wanted = match *state {
+                    SelectionState::RoundRobin { next: ref wanted } => wanted,
+                    SelectionState::Generator { .. } => return false,
+                };
+                let valid = wanted.len() == next.len()
+                    && wanted
+                        .iter()
+                        .enumerate()
+                        .all(|(i, &n)| n < starts[i + 1] - starts[i]);
+                if valid {
+                    next.copy_from_slice(wanted);
+                }
+                return valid;
+            }
+            Selector::Random { ref mut rng, .. }
+            | Selector::Weighted { ref mut rng, .. } => rng,
+        };
+        match *state {
+            SelectionState::RoundRobin { .. } => false,
+            SelectionState::Generator { state } => {
+                *rng = state;
+                true
+            }
+        }
+    }
+
+    /// Start the selection over, as if no alternative was selected yet.
+    pub(crate) fn reset(&mut self) {
+        match self.selector {
+            Selector::RoundRobin { ref mut next } => {
+                next.iter_mut().for_each(|i| *i = 0);
+            }
+            Selector::Random { seed, ref mut rng }
+            | Selector::Weighted { seed, ref mut rng, .. } => *rng = seed,
+        }
+    }
+}
+
+/// Returns a pseudorandom number below `n`, which must be positive, and
+/// advances the SplitMix64 generator with the given state.
+pub(crate) fn below(state: &mut u64, n: u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^= z >> 31;
+    // This maps the number to the range without the bias of a remainder.
+    ((u128::from(z) * u128::from(n)) >> 64) as u64
+}
@@ -0,0 +1,963 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use crate::{
+    ahocorasick::enforce_anchored_consistency,
+    automaton::Automaton,
+    replacer::{
+        alternatives::{Alternatives, Selection, Selector},
+        error::{ReplacerError, ReplacerErrorKind},
+        with::AhoCorasickReplacerWith,
+        AhoCorasickReplacer, Leftmost, Recent, Resolving,
+    },
+    util::alphabet::ByteSet,
+    AhoCorasick, Anchored, MatchDeduper, MatchError, MatchKind, OverlapPolicy,
+    PatternID,
+};
+
+/// A builder for configuring an [`AhoCorasickReplacer`].
+///
+/// A builder is useful when the default behavior of
+/// [`AhoCorasick::replacer`] isn't what is wanted, for example, when the
+/// length of the output must match the length of the input. See
+/// [`AhoCorasickReplacerBuilder::padding`]. A replacer with this
+/// configuration is built by [`AhoCorasick::replacer_with`].
+#[derive(Clone, Debug, Default)]
+pub struct AhoCorasickReplacerBuilder {
+    padding: Option<Padding>,
+    anchored: Anchored,
+    max_pending_bytes: Option<PendingLimit>,
+    memory_limits: Option<MemoryLimits>,
+    coalesce: bool,
+    dedup: Option<MatchDeduper>,
+    ignore_empty: bool,
+    replacement_context: bool,
+    quit: ByteSet,
+    overlap_policy: Option<OverlapPolicy>,
+}
+
+impl AhoCorasickReplacerBuilder {
+    /// Create a new builder for configuring a replacer.
+    pub fn new() -> AhoCorasickReplacerBuilder {
+        AhoCorasickReplacerBuilder::default()
+    }
+
+    /// Build a replacer for the given automaton, checking that it can be
+    /// used with the given replacements. See [`AhoCorasick::replacer_with`].
+    pub(crate) fn build_replacer<I, B>(
+        &self,
+        ac: &AhoCorasick,
+        replace_with: I,
+    ) -> Result<AhoCorasickReplacer, ReplacerError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        let replacements: Vec<Vec<u8>> =
+            replace_with.into_iter().map(|r| r.as_ref().to_vec()).collect();
+        self.build_replacer_from(ac, Arc::from(replacements), None)
+    }
+
+    /// Build a replacer for the given automaton that selects the replacement
+    /// of each match among the alternatives of its pattern. See
+    /// [`AhoCorasick::replacer_with_alternatives`].
+    pub(crate) fn build_replacer_alternatives<I, A, B>(
+        &self,
+        ac: &AhoCorasick,
+        alternatives: I,
+        selection: Selection,
+    ) -> Result<AhoCorasickReplacer, ReplacerError>
+    where
+        I: IntoIterator<Item = A>,
+        A: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        let mut replacements: Vec<Vec<u8>> = Vec::new();
+        let mut starts = vec![0];
+        for a in alternatives.into_iter() {
+            replacements.extend(a.into_iter().map(|r| r.as_ref().to_vec()));
+            starts.push(replacements.len());
+        }
+        let patterns_len = starts.len() - 1;
+        if patterns_len != ac.patterns_len() {
+            return Err(ReplacerError::new(
+                ReplacerErrorKind::ReplacementCount {
+                    expected: ac.patterns_len(),
+                    got: patterns_len,
+                },
+            ));
+        }
+        for i in 0..patterns_len {
+            let pattern = PatternID::new_unchecked(i);
+            let len = starts[i + 1] - starts[i];
+            if len == 0 {
+                return Err(ReplacerError::new(
+                    ReplacerErrorKind::NoAlternatives { pattern },
+                ));
+            }
+            if let Selection::Weighted { ref weights, .. } = selection {
+                let weights = weights.get(i).map_or(&[][..], |w| &w[..]);
+                if weights.len() != len || weights.iter().all(|&w| w == 0) {
+                    return Err(ReplacerError::new(
+                        ReplacerErrorKind::InvalidWeights { pattern },
+                    ));
+                }
+            }
+        }
+        let selector = match selection {
+            Selection::RoundRobin => {
+                Selector::RoundRobin { next: vec![0; patterns_len] }
+            }
+            Selection::Random { seed } => Selector::Random { seed, rng: seed },
+            Selection::Weighted { seed, weights } => {
+                // The running sum of the weights of the alternatives of each
+                // pattern, so that a random number below the sum of all of
+                // them selects one by binary search.
+                let cumulative = weights
+                    .iter()
+                    .take(patterns_len)
+                    .flat_map(|w| {
+                        w.iter().scan(0, |sum, &w| {
+                            *sum += u64::from(w);
+                            Some(*sum)
+                        })
+                    })
+                    .collect();
+                Selector::Weighted { seed, rng: seed, cumulative }
+            }
+        };
+        let alternatives =
+            Alternatives { starts: Arc::from(starts), selector };
+        self.build_replacer_from(
+            ac,
+            Arc::from(replacements),
+            Some(alternatives),
+        )
+    }
+
+    /// Build a replacer for the given automaton whose replacements are
+    /// computed by the given closure. See [`AhoCorasick::replacer_with_fn`].
+    pub(crate) fn build_replacer_fn<F>(
+        &self,
+        ac: &AhoCorasick,
+        replace_with: F,
+    ) -> Result<AhoCorasickReplacerWith<F>, ReplacerError>
+    where
+        F: FnMut(PatternID, &[u8]) -> Vec<u8>,
+    {
+        // The replacements the replacer is built with are never written.
+        let unused = vec![Vec::new(); ac.patterns_len()];
+        let replacer =
+            self.build_replacer_from(ac, Arc::from(unused), None)?;
+        Ok(AhoCorasickReplacerWith {
+            replacer,
+            replace_with,
+            padding: self.padding,
+        })
+    }
+
+    /// Build a replacer for the given automaton using one of its
+    /// replacement tables. See [`AhoCorasick::replacer_with_table`].
+    pub(crate) fn build_replacer_with_table(
+        &self,
+        ac: &AhoCorasick,
+        table_id: usize,
+    ) -> Result<AhoCorasickReplacer, ReplacerError> {
+        let table =
+            ac.replacement_table_shared(table_id).ok_or_else(|| {
+                ReplacerError::new(ReplacerErrorKind::UnknownTable {
+                    table: table_id,
+                    len: ac.replacement_tables_len(),
+                })
+            })?;
+        self.build_replacer_from(ac, table, None)
+    }
+
+    /// Build a replacer for the given automaton and replacements. The
+    /// replacements are only copied when they need to be padded, so that
+    /// every replacer built from the same table shares it.
+    ///
+    /// With alternatives, the replacements are the alternatives of every
+    /// pattern, one pattern after the other, instead of one per pattern.
+    fn build_replacer_from(
+        &self,
+        ac: &AhoCorasick,
+        mut replacements: Arc<[Vec<u8>]>,
+        alternatives: Option<Alternatives>,
+    ) -> Result<AhoCorasickReplacer, ReplacerError> {
+        // A replacer reports a match as soon as it enters a match state,
+        // which is only correct for standard match semantics. With
+        // leftmost-longest semantics, it waits for the automaton to tell
+        // that the match can't be extended instead.
+        match ac.match_kind() {
+            MatchKind::Standard => {}
+            MatchKind::LeftmostLongest => self.enforce_leftmost_support(ac)?,
+            kind => {
+                return Err(ReplacerError::new(ReplacerErrorKind::Match(
+                    MatchError::unsupported_stream(kind),
+                )))
+            }
+        }
+        enforce_anchored_consistency(ac.start_kind(), self.anchored)
+            .map_err(ReplacerErrorKind::Match)
+            .map_err(ReplacerError::new)?;
+        let (aut, kind) = ac.replacer_parts();
+        if alternatives.is_none() && replacements.len() != aut.patterns_len() {
+            return Err(ReplacerError::new(
+                ReplacerErrorKind::ReplacementCount {
+                    expected: aut.patterns_len(),
+                    got: replacements.len(),
+                },
+            ));
+        }
+        // A lookahead needs the bytes after a match, which an empty match
+        // inserted before every byte doesn't wait for, and so does a word
+        // boundary. An overlap policy decides empty matches like any other
+        // ones.
+        let lookaheads = ac.lookaheads();
+        let policy = self.overlap_policy_for(ac);
+        let inserted = !self.ignore_empty && policy.is_none();
+        if let (true, Some(lookaheads)) = (inserted, &lookaheads) {
+            for (i, lookahead) in lookaheads.iter().enumerate() {
+                let pid = PatternID::new_unchecked(i);
+                if lookahead.is_some() && aut.pattern_len(pid) == 0 {
+                    return Err(ReplacerError::new(
+                        ReplacerErrorKind::EmptyLookahead { pattern: pid },
+                    ));
+                }
+            }
+        }
+        if inserted && ac.word_boundary() {
+            let empty = PatternID::iter(aut.patterns_len())
+                .find(|&pid| aut.pattern_len(pid) == 0);
+            if let Some(pid) = empty {
+                return Err(ReplacerError::new(
+                    ReplacerErrorKind::EmptyWordBoundary { pattern: pid },
+                ));
+            }
+        }
+        let recent = if ac.word_boundary() {
+            Some(Recent::new(aut.max_pattern_len()))
+        } else {
+            None
+        };
+        // A run is replaced once, by the replacement of its first match,
+        // which can only be padded to the length of that match.
+        if self.padding.is_some() && self.coalesce {
+            return Err(ReplacerError::new(ReplacerErrorKind::PaddedCoalesce));
+        }
+        if let Some(padding) = self.padding {
+            let mut padded = replacements.to_vec();
+            for (i, replacement) in padded.iter_mut().enumerate() {
+                let pid = match alternatives {
+                    None => PatternID::new_unchecked(i),
+                    Some(ref alternatives) => alternatives.pattern_of(i),
+                };
+                let len = aut.pattern_len(pid);
+                if replacement.len() > len {
+                    return Err(ReplacerError::new(
+                        ReplacerErrorKind::ReplacementTooLong {
+                            pattern: pid,
+                            pattern_len: len,
+                            replacement_len: replacement.len(),
+                        },
+                    ));
+                }
+                padding.pad(replacement, len);
+            }
+            replacements = Arc::from(padded);
+        }
+        let mut replacer =
+            AhoCorasickReplacer::new(aut, kind, self.anchored, replacements)
+                .map_err(ReplacerErrorKind::Match)
+                .map_err(ReplacerError::new)?;
+        replacer.coalesce = self.coalesce;
+        replacer.dedup = self.dedup.clone();
+        replacer.lookaheads = lookaheads;
+        replacer.recent = recent;
+        replacer.disabled = ac.disabled_patterns();
+        replacer.ignore_empty = self.ignore_empty;
+        replacer.replacement_context = self.replacement_context;
+        replacer.resolving = policy.map(Resolving::new);
+        replacer.pending_limit = self.max_pending_bytes;
+        replacer.limits = self.memory_limits.unwrap_or_default();
+        replacer.alternatives = alternatives;
+        replacer.quit = Some(self.quit).filter(|quit| !quit.is_empty());
+        if ac.match_kind() == MatchKind::LeftmostLongest {
+            let aut = replacer.aut.as_ref().coerce_concrete(replacer.kind);
+            let leftmost =
+                Leftmost::new(aut, replacer.start, self.ignore_empty);
+            replacer.leftmost = Some(leftmost);
+        }
+        Ok(replacer)
+    }
+
+    /// Returns the overlap policy of a replacer for the given automaton,
+    /// which is the one set on this builder, if any, or the one of the
+    /// automaton.
+    fn overlap_policy_for(&self, ac: &AhoCorasick) -> Option<OverlapPolicy> {
+        self.overlap_policy.or(ac.overlap_policy())
+    }
+
+    /// Returns an error if an option that replacers with leftmost-longest
+    /// semantics don't support is set. They only ever consider the first
+    /// match of each state, as non-overlapping searches do.
+    fn enforce_leftmost_support(
+        &self,
+        ac: &AhoCorasick,
+    ) -> Result<(), ReplacerError> {
+        if self.overlap_policy_for(ac).is_some() {
+            return Err(ReplacerError::new(ReplacerErrorKind::Match(
+                MatchError::unsupported_overlapping(ac.match_kind()),
+            )));
+        }
+        let option = if ac.lookaheads().is_some() {
+            "lookaheads"
+        } else if ac.word_boundary() {
+            "word boundaries"
+        } else if ac.disabled_patterns().contains(&true) {
+            "disabled patterns"
+        } else if self.anchored.is_anchored() {
+            "anchored replacements"
+        } else if self.replacement_context {
+            "replacement contexts"
+        } else {
+            return Ok(());
+        };
+        Err(ReplacerError::new(ReplacerErrorKind::UnsupportedLeftmost {
+            option,
+        }))
+    }
+
+    /// Only replace a match that begins at the start of the stream, or at
+    /// the start of a record, when set to [`Anchored::Yes`].
+    ///
+    /// An anchored replacer searches for a single match at the start of the
+    /// stream. Once it's replaced, or once no pattern can match anymore, the
+    /// rest of the stream is written unchanged, and the bytes are no longer
+    /// searched at all. The search starts again when
+    /// [`AhoCorasickReplacer::start_record`] is called, so that rewriting
+    /// the header of every message of a protocol doesn't need a replacer
+    /// for each message. An empty pattern matches once at the start of each
+    /// record.
+    ///
+    /// An anchored replacer needs an automaton that supports anchored
+    /// searches, which is checked when the replacer is built. See
+    /// [`AhoCorasickBuilder::start_kind`](crate::AhoCorasickBuilder::start_kind).
+    /// With an [overlap policy](crate::AhoCorasickBuilder::overlap_policy),
+    /// the policy decides among the matches that begin at the start of the
+    /// record. [`AhoCorasickReplacerBuilder::replacement_context`] has no
+    /// effect, since no match follows a replacement. Replacers with
+    /// [`MatchKind::LeftmostLongest`] semantics don't support anchored
+    /// replacement, and building one with [`Anchored::Yes`] returns an
+    /// error.
+    ///
+    /// This is [`Anchored::No`] by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacerBuilder, Anchored, StartKind,
+    /// };
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .start_kind(StartKind::Both)
+    ///     .build(&["HTTP/1.0", "HTTP/1."])
+    ///     .unwrap();
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().anchored(Anchored::Yes),
+    ///     &["HTTP/1.1", "HTTP/2."],
+    /// )?;
+    /// let mut out = replacer.replace(b"HTTP/1.")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"0 200 OK HTTP/1.0")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// // The first pattern matched at the start isn't the longest one, since
+    /// // a match is replaced as soon as it ends.
+    /// assert_eq!(b"HTTP/2.0 200 OK HTTP/1.0".to_vec(), out);
+    ///
+    /// // An automaton that only supports unanchored searches can't be used.
+    /// let ac = AhoCorasick::new(&["HTTP/1.0"]).unwrap();
+    /// assert!(ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().anchored(Anchored::Yes),
+    ///     &["HTTP/1.1"],
+    /// ).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn anchored(
+        &mut self,
+        anchored: Anchored,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.anchored = anchored;
+        self
+    }
+
+    /// Replace each run of adjacent matches only once.
+    ///
+    /// When enabled, a match that begins exactly where the previous match
+    /// ended is considered part of the same run, and the entire run is
+    /// replaced by the replacement of its first match. For example, with the
+    /// pattern `foo` and the replacement `***`, the haystack `foofoofoo!` is
+    /// rewritten to `***!` instead of `*********!`. Since the replacer
+    /// restarts its search at the end of every match, matches never overlap,
+    /// so a run is exactly a sequence of back-to-back matches.
+    ///
+    /// Runs may span any number of chunks. This is the only way to get this
+    /// behavior when streaming, since replacements are emitted as soon as
+    /// each match is found.
+    ///
+    /// This can't be combined with [`AhoCorasickReplacerBuilder::padding`],
+    /// since a run of several matches is longer than the replacement of its
+    /// first match, and building such a replacer returns an error.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder};
+    ///
+    /// let ac = AhoCorasick::new(&["foo", "bar"]).unwrap();
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().coalesce(true),
+    ///     &["***", "###"],
+    /// )?;
+    /// let mut out = replacer.replace(b"foofoo bar")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"foo foo")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// // 'bar' and the 'foo' following it form a single run, even though
+    /// // they were given in different chunks.
+    /// assert_eq!(b"*** ### ***".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn coalesce(&mut self, yes: bool) -> &mut AhoCorasickReplacerBuilder {
+        self.coalesce = yes;
+        self
+    }
+
+    /// Only replace the first match of each pattern within a window of bytes,
+    /// as determined by the given deduper. Suppressed matches are written to
+    /// the output unchanged.
+    ///
+    /// See [`MatchDeduper`] for how windows are defined, and how to configure
+    /// them for all patterns or for individual patterns. Windows are measured
+    /// in bytes of the input, across chunks.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder, MatchDeduper};
+    ///
+    /// let ac = AhoCorasick::new(&["alert"]).unwrap();
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new()
+    ///         .dedup(Some(MatchDeduper::new(Some(16)))),
+    ///     &["ALERT"],
+    /// )?;
+    /// let mut out = replacer.replace(b"alert alert ")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"alert alert")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"ALERT alert alert ALERT".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dedup(
+        &mut self,
+        dedup: Option<MatchDeduper>,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Set the policy that decides which of two overlapping matches is
+    /// replaced, instead of the one set on the automaton with
+    /// [`AhoCorasickBuilder::overlap_policy`](crate::AhoCorasickBuilder::overlap_policy).
+    ///
+    /// This makes it possible to build replacers with different policies
+    /// from the same automaton. A replacer with a policy considers every
+    /// match of every pattern, and replaces the ones that the policy picks
+    /// among the matches that overlap. See [`OverlapPolicy`] for how they
+    /// are picked. With `None`, the policy of the automaton is used, if it
+    /// has one.
+    ///
+    /// Since every overlapping match is considered, a policy requires an
+    /// automaton with [`MatchKind::Standard`] semantics, and building a
+    /// replacer with one from any other automaton returns an error.
+    ///
+    /// By default, the policy of the automaton is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder, OverlapPolicy};
+    ///
+    /// let ac = AhoCorasick::new(&["abcd", "bc"]).unwrap();
+    /// let replace = |policy| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    ///     let mut replacer = ac.replacer_with(
+    ///         AhoCorasickReplacerBuilder::new().overlap_policy(Some(policy)),
+    ///         &["1", "2"],
+    ///     )?;
+    ///     let mut out = replacer.replace(b"xab")?.to_vec();
+    ///     out.extend_from_slice(replacer.replace(b"cdx")?);
+    ///     out.extend_from_slice(replacer.finish()?);
+    ///     Ok(out)
+    /// };
+    /// assert_eq!(b"x1x".to_vec(), replace(OverlapPolicy::LongestWins)?);
+    /// assert_eq!(b"x1x".to_vec(), replace(OverlapPolicy::PriorityWins)?);
+    /// assert_eq!(b"xa2dx".to_vec(), {
+    ///     let mut replacer = ac.replacer(&["1", "2"])?;
+    ///     let mut out = replacer.replace(b"xabcdx")?.to_vec();
+    ///     out.extend_from_slice(replacer.finish()?);
+    ///     out
+    /// });
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn overlap_policy(
+        &mut self,
+        policy: Option<OverlapPolicy>,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.overlap_policy = policy;
+        self
+    }
+
+    /// Ignore the matches of empty patterns.
+    ///
+    /// An empty pattern matches at every position of a stream, so by
+    /// default, its replacement is inserted before every byte and once more
+    /// at the end of the stream, as [`AhoCorasick::replace_all`] does. Since
+    /// the search restarts after every match, no other pattern ever matches
+    /// in that case. When enabled, empty patterns never match instead, and
+    /// the other patterns are replaced as if the empty patterns had been
+    /// removed from the automaton.
+    ///
+    /// With an [overlap policy](crate::AhoCorasickBuilder::overlap_policy),
+    /// an empty match is instead decided like any other match, so the other
+    /// patterns may match as well. Without one, a replacer that doesn't
+    /// ignore empty matches can't be built when an empty pattern has a
+    /// [lookahead](crate::AhoCorasickBuilder::lookahead).
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder};
+    ///
+    /// let ac = AhoCorasick::new(&["", "b"]).unwrap();
+    /// let mut replacer = ac.replacer(&["-", "B"])?;
+    /// let mut out = replacer.replace(b"ab")?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"-a-b-".to_vec(), out);
+    /// assert_eq!("-a-b-", ac.replace_all("ab", &["-", "B"]));
+    ///
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().ignore_empty_matches(true),
+    ///     &["-", "B"],
+    /// )?;
+    /// let mut out = replacer.replace(b"ab")?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"aB".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn ignore_empty_matches(
+        &mut self,
+        yes: bool,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.ignore_empty = yes;
+        self
+    }
+
+    /// Stop at the given byte when `yes` is true, or remove it from the
+    /// bytes to stop at otherwise.
+    ///
+    /// This is useful for streams that a replacer must not go past the
+    /// first byte of something it can't handle, such as a byte that is never
+    /// valid in UTF-8. When a chunk has a quit byte, the bytes before it are
+    /// replaced and the call returns an error of kind
+    /// [`MatchErrorKind::Quit`](crate::MatchErrorKind::Quit), with the
+    /// offset of the quit byte in the stream. The stream is handled as if
+    /// it ended right before the quit byte: every byte held back for a
+    /// potential match is decided and emitted, before anything else, by the
+    /// next call or by [`AhoCorasickReplacer::finish`], or written to the
+    /// sink first by [`AhoCorasickReplacer::replace_to`]. The quit byte and
+    /// the bytes after it aren't consumed, so the caller may skip or rewrite
+    /// them and give the rest of the stream to the replacer, which searches
+    /// it as at the start of a record. See
+    /// [`AhoCorasickReplacer::start_record`]. Offsets only count the bytes
+    /// consumed, so a skipped quit byte doesn't shift the offsets after it.
+    ///
+    /// Quit bytes are checked by the calls that return a [`MatchError`].
+    /// [`AhoCorasickReplacer::replace_iter`] doesn't stop at them, and the
+    /// adapters that return the errors of the replacer, such as a
+    /// [`ChunkedReplacer`](crate::ChunkedReplacer), can't be resumed after
+    /// one.
+    ///
+    /// No byte is a quit byte by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacerBuilder, MatchErrorKind,
+    /// };
+    ///
+    /// let ac = AhoCorasick::new(&["caf\u{e9}"]).unwrap();
+    /// let mut builder = AhoCorasickReplacerBuilder::new();
+    /// // These bytes never occur in UTF-8.
+    /// for byte in [0xC0, 0xC1].into_iter().chain(0xF5..=0xFF) {
+    ///     builder.quit(byte, true);
+    /// }
+    /// let mut replacer = ac.replacer_with(&builder, &["tea"])?;
+    /// let chunk = "un caf\u{e9} caf".as_bytes();
+    /// let mut out = replacer.replace(chunk)?.to_vec();
+    /// let err = replacer.replace(b"\xFF\xC3\xA9").unwrap_err();
+    /// assert_eq!(
+    ///     MatchErrorKind::Quit { byte: 0xFF, offset: 12 },
+    ///     *err.kind(),
+    /// );
+    /// // Skip the invalid byte and go on.
+    /// out.extend_from_slice(replacer.replace(b"\xC3\xA9")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!("un tea caf\u{e9}".as_bytes(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn quit(
+        &mut self,
+        byte: u8,
+        yes: bool,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        if yes {
+            self.quit.add(byte);
+        } else {
+            self.quit.remove(byte);
+        }
+        self
+    }
+
+    /// Resume the search after a replacement from the state that the
+    /// replacement itself leads to, instead of the start state.
+    ///
+    /// By default, the search restarts from scratch after every match, so a
+    /// match never depends on what came before it. When enabled, the bytes
+    /// of each replacement are run through the automaton as context, and
+    /// the last ones may begin a match that ends in the input that follows.
+    /// Such a match replaces the bytes of the replacement it begins with,
+    /// along with the bytes of the input it covers. This makes it possible
+    /// to write rules that depend on the output of other rules, such as
+    /// merging a closing tag produced by a replacement with the opening tag
+    /// following it. A match that ends within a replacement is ignored, so
+    /// replacements are never rewritten on their own.
+    ///
+    /// The bytes of a replacement that may begin a match are held back until
+    /// the match is decided, as with any other potential match. The span of
+    /// such a match, as reported by a
+    /// [`ShadowReplacer`](crate::ShadowReplacer), only covers the bytes of the
+    /// input, and it is never part of the same run as the replacement it
+    /// rewrites when [coalescing](Self::coalesce). A match that is coalesced
+    /// into a run writes nothing, so the search restarts after it.
+    ///
+    /// This has no effect with an
+    /// [overlap policy](crate::AhoCorasickBuilder::overlap_policy), since the
+    /// search never restarts in that case, or while an empty pattern
+    /// matches at every position. Replacers with
+    /// [`MatchKind::LeftmostLongest`] semantics don't support it, and
+    /// building one with this enabled returns an error.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder};
+    ///
+    /// let ac = AhoCorasick::new(&["[b]", "[/b]", "</b><b>"]).unwrap();
+    /// let replace_with = &["<b>", "</b>", ""];
+    /// let haystack = b"[b]one[/b]<b>two</b>";
+    ///
+    /// let mut replacer = ac.replacer(replace_with)?;
+    /// let mut out = replacer.replace(haystack)?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"<b>one</b><b>two</b>".to_vec(), out);
+    ///
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().replacement_context(true),
+    ///     replace_with,
+    /// )?;
+    /// let mut out = replacer.replace(haystack)?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"<b>onetwo</b>".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replacement_context(
+        &mut self,
+        yes: bool,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.replacement_context = yes;
+        self
+    }
+
+    /// Enable length preserving replacement by padding replacements that are
+    /// shorter than the pattern they replace.
+    ///
+    /// When enabled, every replacement must be no longer than its pattern,
+    /// which is checked when the replacer is built. Since the replacements
+    /// of an [`AhoCorasickReplacerWith`] aren't known in advance, it
+    /// truncates those that are longer instead. As a result, the output of
+    /// the replacer always has exactly the same length as its input, and
+    /// every byte that isn't part of a match stays at the same offset. This
+    /// is useful for patching binary formats with fixed offsets. This can't
+    /// be combined with [`AhoCorasickReplacerBuilder::coalesce`], which
+    /// replaces a whole run of matches with a single replacement.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickReplacerBuilder, Padding};
+    ///
+    /// let ac = AhoCorasick::new(&["secret", "pin"]).unwrap();
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new()
+    ///         .padding(Some(Padding::Right(b'*'))),
+    ///     &["XXX", "N"],
+    /// )?;
+    /// let mut out = replacer.replace(b"my secret pin!")?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"my XXX*** N**!".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn padding(
+        &mut self,
+        padding: Option<Padding>,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.padding = padding;
+        self
+    }
+
+    /// Limit the number of bytes a replacer holds back once it consumed a
+    /// chunk.
+    ///
+    /// A replacer holds back the bytes that might start a match until it
+    /// knows whether they do, which is up to the length of the longest
+    /// pattern, and more when a match waits for its lookahead or when
+    /// matches are held for an overlap policy. With a limit, memory use
+    /// stays bounded in a streaming service even when a pattern is very
+    /// long, since the longest potential match is never held back in full.
+    /// A match that lies within a single chunk is still found, since the
+    /// limit only applies to the bytes left once the chunk is consumed.
+    ///
+    /// See [`PendingLimit`] for what happens when the limit is exceeded.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacerBuilder, MatchErrorKind,
+    ///     PendingLimit,
+    /// };
+    ///
+    /// let ac = AhoCorasick::new(&["0123456789", "89"]).unwrap();
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new()
+    ///         .max_pending_bytes(Some(PendingLimit::Flush(4))),
+    ///     &["<digits>", "<89>"],
+    /// )?;
+    /// // The potential match of the first pattern is given up on, and the
+    /// // search resumes from the next byte.
+    /// let mut out = replacer.replace(b"x01234")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"56789")?);
+    /// out.extend_from_slice(replacer.replace(b" 0123456789")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"x01234567<89> <digits>".to_vec(), out);
+    ///
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new()
+    ///         .max_pending_bytes(Some(PendingLimit::Error(4))),
+    ///     &["<digits>", "<89>"],
+    /// )?;
+    /// let err = replacer.replace(b"x01234").unwrap_err();
+    /// assert_eq!(
+    ///     MatchErrorKind::PendingLimitExceeded { limit: 4 },
+    ///     *err.kind(),
+    /// );
+    /// // No output is lost.
+    /// assert_eq!(b"x01234", replacer.finish()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn max_pending_bytes(
+        &mut self,
+        limit: Option<PendingLimit>,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.max_pending_bytes = limit;
+        self
+    }
+
+    /// Set hard caps on the memory used by the buffers of the replacer.
+    ///
+    /// This is a bounded mode for memory constrained environments, such as
+    /// sandboxes: once a buffer would grow past its cap, the replacer
+    /// returns a typed error instead. See [`MemoryLimits`] for the buffers
+    /// that are capped and what happens once a cap is exceeded.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacerBuilder, MatchErrorKind,
+    ///     MemoryLimits,
+    /// };
+    ///
+    /// let ac = AhoCorasick::new(&["a"]).unwrap();
+    /// let limits = MemoryLimits::new().output(8).pending(4);
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().memory_limits(Some(limits)),
+    ///     &["<A>"],
+    /// )?;
+    /// assert_eq!(b"<A>b<A>", replacer.replace(b"aba")?);
+    /// let err = replacer.replace(b"aaa").unwrap_err();
+    /// assert_eq!(
+    ///     MatchErrorKind::OutputLimitExceeded { limit: 8 },
+    ///     *err.kind(),
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn memory_limits(
+        &mut self,
+        limits: Option<MemoryLimits>,
+    ) -> &mut AhoCorasickReplacerBuilder {
+        self.memory_limits = limits;
+        self
+    }
+}
+
+/// What a replacer does once it holds back more bytes than allowed.
+///
+/// See [`AhoCorasickReplacerBuilder::max_pending_bytes`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PendingLimit {
+    /// Once more than the given number of bytes are held back, give up on
+    /// the potential match, and write them as is, as if they did not match.
+    /// The search resumes from the next byte, and a match waiting for its
+    /// lookahead is decided as if the stream ended there.
+    Flush(usize),
+    /// Once more than the given number of bytes are held back, return an
+    /// error of kind
+    /// [`MatchErrorKind::PendingLimitExceeded`](crate::MatchErrorKind::PendingLimitExceeded).
+    /// The output of the chunk is then held back as well, so that it's
+    /// returned by [`AhoCorasickReplacer::finish`], along with the rest.
+    /// Only finishing or resetting the replacer is meaningful from there.
+    Error(usize),
+}
+
+/// Hard caps on the memory used by the buffers of a streaming replacer.
+///
+/// By default, the buffers of a replacer grow as needed: the bytes it holds
+/// back for a potential match, the output of each call, which it returns from
+/// an internal buffer, and the differences a
+/// [`ShadowReplacer`](crate::ShadowReplacer) records until they are taken.
+/// With limits, none of them ever grows past its cap, and an error is returned
+/// instead, so that the memory used by a replacer is bounded whatever its
+/// input.
+///
+/// Every cap is unlimited until it's set. See
+/// [`AhoCorasickReplacerBuilder::memory_limits`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MemoryLimits {
+    pub(crate) output: Option<usize>,
+    pub(crate) pending: Option<usize>,
+    pub(crate) events: Option<usize>,
+}
+
+impl MemoryLimits {
+    /// Create limits where every cap is unlimited.
+    pub fn new() -> MemoryLimits {
+        MemoryLimits::default()
+    }
+
+    /// Cap the number of bytes returned by a single call, such as
+    /// [`AhoCorasickReplacer::replace`] or [`AhoCorasickReplacer::finish`].
+    ///
+    /// A call whose output would exceed the cap returns an error of kind
+    /// [`MatchErrorKind::OutputLimitExceeded`](crate::MatchErrorKind::OutputLimitExceeded)
+    /// instead, and the replacer is
+    /// [poisoned](AhoCorasickReplacer::is_poisoned) until it's reset.
+    /// Since replacements may be longer than the bytes they replace, the
+    /// output of a chunk may be longer than the chunk. Writing to a sink, as
+    /// [`AhoCorasickReplacer::replace_to`] does, or to a buffer of the
+    /// caller, as [`AhoCorasickReplacer::replace_to_vec`] does, doesn't use
+    /// the internal buffer, so the cap doesn't apply.
+    pub fn output(self, bytes: usize) -> MemoryLimits {
+        MemoryLimits { output: Some(bytes), ..self }
+    }
+
+    /// Cap the number of bytes held back once a chunk is consumed.
+    ///
+    /// This is the same as [`PendingLimit::Error`], and applies along with
+    /// the limit set by [`AhoCorasickReplacerBuilder::max_pending_bytes`], if
+    /// any.
+    pub fn pending(self, bytes: usize) -> MemoryLimits {
+        MemoryLimits { pending: Some(bytes), ..self }
+    }
+
+    /// Cap the number of differences a
+    /// [`ShadowReplacer`](crate::ShadowReplacer) records until they are taken
+    /// by [`ShadowReplacer::take_diffs`](crate::ShadowReplacer::take_diffs).
+    /// The cap of its active replacer applies.
+    ///
+    /// A call that finds more records them up to the cap, and returns an
+    /// error of kind
+    /// [`MatchErrorKind::EventLimitExceeded`](crate::MatchErrorKind::EventLimitExceeded).
+    /// The other differences are recorded by the next call, once the ones
+    /// recorded so far are taken, so none of them is lost. The output of the
+    /// chunk is held back, and returned by the next call along with its own.
+    pub fn events(self, count: usize) -> MemoryLimits {
+        MemoryLimits { events: Some(count), ..self }
+    }
+}
+
+/// How a replacement shorter than its pattern is padded when length
+/// preserving replacement is enabled.
+///
+/// See [`AhoCorasickReplacerBuilder::padding`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Padding {
+    /// Insert the given byte before the replacement until it is as long as
+    /// its pattern.
+    Left(u8),
+    /// Append the given byte after the replacement until it is as long as its
+    /// pattern.
+    Right(u8),
+}
+
+impl Padding {
+    /// Pad the given replacement to `len` bytes.
+    fn pad(self, replacement: &mut Vec<u8>, len: usize) {
+        let fill = len - replacement.len();
+        match self {
+            Padding::Left(byte) => {
+                replacement.splice(0..0, core::iter::repeat(byte).take(fill));
+            }
+            Padding::Right(byte) => replacement.resize(len, byte),
+        }
+    }
+
+    /// Pad or truncate the given replacement to exactly `len` bytes. A
+    /// replacement is truncated on the side its padding would be inserted.
+    pub(crate) fn fit(self, replacement: &mut Vec<u8>, len: usize) {
+        if replacement.len() <= len {
+            self.pad(replacement, len);
+            return;
+        }
+        match self {
+            Padding::Left(_) => {
+                replacement.drain(..replacement.len() - len);
+            }
+            Padding::Right(_) => replacement.truncate(len),
+        }
+    }
+}
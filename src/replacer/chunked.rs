@@ -0,0 +1,266 @@
+use alloc::vec::Vec;
+
+use crate::{replacer::AhoCorasickReplacer, MatchError};
+
+/// A replacer for a body in the HTTP/1.1 chunked transfer coding, which
+/// rewrites the payload of its chunks and frames the result again.
+///
+/// The framing of the body is decoded as it's given, and only the payload
+/// of each chunk goes through the replacer, as a single stream, so a match
+/// may span several chunks. Since replacements change the length of the
+/// payload, and the replacer holds back the start of a potential match,
+/// the output is framed again into chunks of its own: the decided output
+/// of each call is written as one chunk, with its size, and the bytes held
+/// back are written once the last chunk of the body is read. An empty chunk
+/// is never written, since it would end the body.
+///
+/// The output chunks don't line up with the input chunks, so chunk
+/// extensions are dropped. The trailer section is written as is.
+///
+/// Only a single body is read. Any byte that follows it is an error, whose
+/// offset is where the body ends, and [`ChunkedReplacer::reset`] permits
+/// reading the next one.
+///
+/// This replacer is constructed via the [`AhoCorasickReplacer::chunked`]
+/// method.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(&["secret"]).unwrap();
+/// let mut body = ac.replacer(&["[redacted]"])?.chunked();
+/// let mut out = body.replace(b"3;name=value\r\nsec\r\n4\r")?.to_vec();
+/// out.extend_from_slice(body.replace(b"\nret!\r\n0\r\nX-Sum: 1\r\n\r\n")?);
+/// body.finish()?;
+/// assert_eq!(b"b\r\n[redacted]!\r\n0\r\nX-Sum: 1\r\n\r\n".to_vec(), out);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ChunkedReplacer {
+    replacer: AhoCorasickReplacer,
+    /// Where the decoding of the framing is at.
+    state: ChunkedState,
+    /// The number of bytes of the body consumed so far.
+    position: usize,
+    /// The output of the replacer for the payload consumed by the current
+    /// call, until it's framed.
+    data: Vec<u8>,
+    buffer: Vec<u8>,
+}
+
+/// A position in the framing of a chunked body.
+#[derive(Clone, Copy, Debug)]
+enum ChunkedState {
+    /// In the hexadecimal size of a chunk, given the value of the digits
+    /// read so far and their number.
+    Size { size: usize, digits: usize },
+    /// In whitespace that follows a chunk size, before an extension.
+    SizeSpace { size: usize },
+    /// In the extensions of a chunk, which are dropped.
+    Extension { size: usize },
+    /// After the carriage return that ends the line of a chunk size.
+    SizeLf { size: usize },
+    /// In the payload of a chunk, given the number of bytes that remain.
+    Data { remaining: usize },
+    /// After the payload of a chunk, before its carriage return.
+    DataCr,
+    /// After the carriage return that follows the payload of a chunk.
+    DataLf,
+    /// In a line of the trailer section, given whether it's empty so far.
+    Trailer { empty: bool },
+    /// After the carriage return that ends a line of the trailer section.
+    TrailerLf { empty: bool },
+    /// After the end of the body.
+    Done,
+}
+
+impl ChunkedReplacer {
+    pub(crate) fn new(replacer: AhoCorasickReplacer) -> ChunkedReplacer {
+        ChunkedReplacer {
+            replacer,
+            state: ChunkedState::Size { size: 0, digits: 0 },
+            position: 0,
+            data: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Give the next bytes of the body to the replacer, returning the
+    /// rewritten body they decide, in the chunked transfer coding.
+    ///
+    /// The output may be empty even if the bytes aren't, since the bytes of
+    /// the framing are only written once they are decoded, and the replacer
+    /// may hold back the end of a payload.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error of kind
+    /// [`MatchErrorKind::InvalidChunkedEncoding`](crate::MatchErrorKind::InvalidChunkedEncoding)
+    /// if the bytes aren't a valid continuation of the body, and the
+    /// errors of [`AhoCorasickReplacer::replace`] otherwise. The output of
+    /// the call is lost, so the replacer should not be used any further
+    /// until it's reset.
+    pub fn replace(&mut self, chunk: &[u8]) -> Result<&[u8], MatchError> {
+        let mut out = core::mem::take(&mut self.buffer);
+        out.clear();
+        let result = self.run(chunk, &mut out);
+        self.buffer = out;
+        result.map(|()| &*self.buffer)
+    }
+
+    /// Check that the body is complete once its stream ends.
+    ///
+    /// Everything is already written by [`ChunkedReplacer::replace`] once
+    /// the body is complete, so there is no output left.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error of kind
+    /// [`MatchErrorKind::InvalidChunkedEncoding`](crate::MatchErrorKind::InvalidChunkedEncoding),
+    /// whose offset is the length of the body, if it ends before its last
+    /// chunk and trailer section.
+    pub fn finish(&mut self) -> Result<(), MatchError> {
+        if !self.is_done() {
+            return Err(MatchError::invalid_chunked_encoding(self.position));
+        }
+        Ok(())
+    }
+
+    /// Returns true once the whole body is read, including its trailer
+    /// section.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, ChunkedState::Done)
+    }
+
+    /// Returns the replacer that rewrites the payload.
+    pub fn replacer(&self) -> &AhoCorasickReplacer {
+        &self.replacer
+    }
+
+    /// Reset this replacer so that it can read a new body.
+    pub fn reset(&mut self) {
+        self.replacer.reset();
+        self.state = ChunkedState::Size { size: 0, digits: 0 };
+        self.position = 0;
+        self.data.clear();
+    }
+
+    /// Decode the framing of the given bytes, giving the rewritten body
+    /// they decide to the given output.
+    fn run(
+        &mut self,
+        chunk: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), MatchError> {
+        let mut at = 0;
+        while at < chunk.len() {
+            // The payload is given to the replacer in bulk, rather than a
+            // byte at a time like the framing.
+            if let ChunkedState::Data { remaining } = self.state {
+                let len = core::cmp::min(remaining, chunk.len() - at);
+                let payload = &chunk[at..at + len];
+                self.replacer.replace_to_vec(payload, &mut self.data)?;
+                self.state = if len == remaining {
+                    ChunkedState::DataCr
+                } else {
+                    ChunkedState::Data { remaining: remaining - len }
+                };
+                at += len;
+                self.position += len;
+                continue;
+            }
+            self.state = self.decode(chunk[at], out)?;
+            at += 1;
+            self.position += 1;
+        }
+        if !self.data.is_empty() {
+            write_chunk(&self.data, out);
+            self.data.clear();
+        }
+        Ok(())
+    }
+
+    /// Returns the state that follows the given byte of the framing, and
+    /// writes the framing it completes to the given output.
+    fn decode(
+        &mut self,
+        byte: u8,
+        out: &mut Vec<u8>,
+    ) -> Result<ChunkedState, MatchError> {
+        use self::ChunkedState::*;
+
+        let position = self.position;
+        let invalid = || MatchError::invalid_chunked_encoding(position);
+        let state = match (self.state, byte) {
+            (Size { size, digits }, _) if byte.is_ascii_hexdigit() => {
+                // The unwrap is OK since the byte is a hexadecimal digit.
+                let digit = char::from(byte).to_digit(16).unwrap();
+                let size = size
+                    .checked_mul(16)
+                    .and_then(|size| size.checked_add(digit as usize))
+                    .ok_or_else(invalid)?;
+                Size { size, digits: digits + 1 }
+            }
+            (Size { digits: 0, .. }, _) => return Err(invalid()),
+            (Size { size, .. }, b'\r') => SizeLf { size },
+            (Size { size, .. }, b';') | (SizeSpace { size }, b';') => {
+                Extension { size }
+            }
+            (Size { size, .. }, b' ' | b'\t')
+            | (SizeSpace { size }, b' ' | b'\t') => SizeSpace { size },
+            (Extension { size }, b'\r') => SizeLf { size },
+            (Extension { .. }, b'\n') => return Err(invalid()),
+            (Extension { size }, _) => Extension { size },
+            (SizeLf { size: 0 }, b'\n') => {
+                // The bytes held back are decided once the payload ends.
+                self.data.extend_from_slice(self.replacer.finish()?);
+                if !self.data.is_empty() {
+                    write_chunk(&self.data, out);
+                    self.data.clear();
+                }
+                out.extend_from_slice(b"0\r\n");
+                Trailer { empty: true }
+            }
+            (SizeLf { size }, b'\n') => Data { remaining: size },
+            (DataCr, b'\r') => DataLf,
+            (DataLf, b'\n') => Size { size: 0, digits: 0 },
+            (Trailer { empty }, b'\r') => TrailerLf { empty },
+            (Trailer { .. }, b'\n') => return Err(invalid()),
+            (Trailer { .. }, _) => {
+                out.push(byte);
+                Trailer { empty: false }
+            }
+            (TrailerLf { empty }, b'\n') => {
+                out.extend_from_slice(b"\r\n");
+                if empty {
+                    Done
+                } else {
+                    Trailer { empty: true }
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(state)
+    }
+}
+
+/// Write the given payload as a single chunk, framed by its size.
+fn write_chunk(payload: &[u8], out: &mut Vec<u8>) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let mut digits = [0; 2 * core::mem::size_of::<usize>()];
+    let (mut at, mut len) = (digits.len(), payload.len());
+    loop {
+        at -= 1;
+        digits[at] = HEX[len % 16];
+        len /= 16;
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(&digits[at..]);
+    out.extend_from_slice(b"\r\n");
+    out.extend_from_slice(payload);
+    out.extend_from_slice(b"\r\n");
+}
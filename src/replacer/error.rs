@@ -0,0 +1,133 @@
+use crate::{MatchError, PatternID};
+
+/// An error that occurred while building an
+/// [`AhoCorasickReplacer`](crate::AhoCorasickReplacer).
+///
+/// When the `std` feature is enabled, this implements the `std::error::Error`
+/// trait.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplacerError {
+    kind: ReplacerErrorKind,
+}
+
+/// The kind of error that occurred.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ReplacerErrorKind {
+    /// The number of replacements doesn't match the number of patterns.
+    ReplacementCount { expected: usize, got: usize },
+    /// The automaton has no replacement table with the requested ID.
+    UnknownTable { table: usize, len: usize },
+    /// Padding is enabled, but a replacement is longer than its pattern.
+    ReplacementTooLong {
+        pattern: PatternID,
+        pattern_len: usize,
+        replacement_len: usize,
+    },
+    /// Padding is enabled, but runs of adjacent matches are coalesced.
+    PaddedCoalesce,
+    /// Empty matches are inserted, but an empty pattern has a lookahead.
+    EmptyLookahead { pattern: PatternID },
+    /// Empty matches are inserted, but matches must be bounded by non-word
+    /// bytes.
+    EmptyWordBoundary { pattern: PatternID },
+    /// The automaton has leftmost-longest semantics, which don't support
+    /// this option.
+    UnsupportedLeftmost { option: &'static str },
+    /// A pattern has no alternative replacement to select.
+    NoAlternatives { pattern: PatternID },
+    /// The weights of the alternatives of a pattern don't match them.
+    InvalidWeights { pattern: PatternID },
+    /// A selection state doesn't match the selection of a replacer.
+    SelectionMismatch,
+    /// The automaton can't be used for replacement.
+    Match(MatchError),
+}
+
+impl ReplacerError {
+    pub(crate) fn new(kind: ReplacerErrorKind) -> ReplacerError {
+        ReplacerError { kind }
+    }
+
+    /// Convert this error into the error reported by
+    /// [`AhoCorasick::replacer`], which is the error of the automaton when it
+    /// can't be used for replacement.
+    pub(crate) fn into_match_error(self) -> MatchError {
+        match self.kind {
+            ReplacerErrorKind::Match(err) => err,
+            _ => MatchError::replacer(self),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReplacerError {}
+
+impl core::fmt::Display for ReplacerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            ReplacerErrorKind::ReplacementCount { expected, got } => write!(
+                f,
+                "expected {} replacements (one per pattern), but got {}",
+                expected, got,
+            ),
+            ReplacerErrorKind::UnknownTable { table, len } => write!(
+                f,
+                "replacement table {} does not exist, since the automaton \
+                 has {} replacement tables",
+                table, len,
+            ),
+            ReplacerErrorKind::ReplacementTooLong {
+                pattern,
+                pattern_len,
+                replacement_len,
+            } => write!(
+                f,
+                "replacement for pattern {} has length {}, which exceeds \
+                 the pattern length of {} and cannot be padded",
+                pattern.as_usize(),
+                replacement_len,
+                pattern_len,
+            ),
+            ReplacerErrorKind::PaddedCoalesce => write!(
+                f,
+                "padding is not supported when runs of matches are \
+                 coalesced, since a run is longer than its replacement",
+            ),
+            ReplacerErrorKind::EmptyLookahead { pattern } => write!(
+                f,
+                "empty pattern {} has a lookahead, which is only \
+                 supported when empty matches are ignored",
+                pattern.as_usize(),
+            ),
+            ReplacerErrorKind::EmptyWordBoundary { pattern } => write!(
+                f,
+                "empty pattern {} must be bounded by non-word bytes, \
+                 which is only supported when empty matches are ignored",
+                pattern.as_usize(),
+            ),
+            ReplacerErrorKind::UnsupportedLeftmost { option } => write!(
+                f,
+                "{} are not supported by replacers with leftmost-longest \
+                 match semantics",
+                option,
+            ),
+            ReplacerErrorKind::NoAlternatives { pattern } => write!(
+                f,
+                "pattern {} has no alternative replacement",
+                pattern.as_usize(),
+            ),
+            ReplacerErrorKind::InvalidWeights { pattern } => write!(
+                f,
+                "the alternatives of pattern {} need one weight each, \
+                 with at least one of them positive",
+                pattern.as_usize(),
+            ),
+            ReplacerErrorKind::SelectionMismatch => write!(
+                f,
+                "the selection state doesn't match the selection of \
+                 alternatives of the replacer",
+            ),
+            ReplacerErrorKind::Match(ref err) => err.fmt(f),
+        }
+    }
+}
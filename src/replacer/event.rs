@@ -0,0 +1,185 @@
+use alloc::vec::Vec;
+
+use crate::{replacer::AhoCorasickReplacer, MatchError};
+
+/// A replacer for a stream in the `text/event-stream` format of
+/// Server-Sent Events, which rewrites the values of its `data` fields only.
+///
+/// The lines of the stream are decoded as they're given, and only the value
+/// of each `data` field goes through the replacer. Field names, the values
+/// of other fields, comments and line terminators are written as is, and a
+/// match never spans the end of a line, so it never spans the end of an
+/// event either. Since a line ends every value, the bytes held back for a
+/// potential match are written once its line ends, and the output of an
+/// event is complete as soon as the empty line that dispatches it is given.
+///
+/// Lines may end with a carriage return, a line feed or both, as the format
+/// permits, and a single space after the colon of a `data` field is part of
+/// the framing, so it's never matched.
+///
+/// This replacer is constructed via the
+/// [`AhoCorasickReplacer::event_stream`] method.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(&["secret", "data"]).unwrap();
+/// let mut events = ac.replacer(&["******", "DATA"])?.event_stream();
+/// let mut out = events.replace(b"event: secret\ndata: my sec")?.to_vec();
+/// out.extend_from_slice(events.replace(b"ret\n\ndata: sec\n\n")?);
+/// out.extend_from_slice(events.finish()?);
+/// assert_eq!(
+///     b"event: secret\ndata: my ******\n\ndata: sec\n\n".to_vec(),
+///     out,
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct EventStreamReplacer {
+    replacer: AhoCorasickReplacer,
+    /// Where the decoding of the current line is at.
+    state: EventState,
+    buffer: Vec<u8>,
+}
+
+/// A position in a line of an event stream.
+#[derive(Clone, Copy, Debug)]
+enum EventState {
+    /// At the start of a line, given whether the previous line ended with a
+    /// carriage return, so that a line feed is part of its terminator.
+    LineStart { cr: bool },
+    /// In the name of a field, given the length of the prefix of `data` it
+    /// is so far, if any.
+    Name { data: Option<usize> },
+    /// After the colon of a `data` field, before its optional space.
+    DataStart,
+    /// In the value of a field or a comment, given whether it's the value
+    /// of a `data` field.
+    Value { data: bool },
+}
+
+impl EventStreamReplacer {
+    pub(crate) fn new(replacer: AhoCorasickReplacer) -> EventStreamReplacer {
+        EventStreamReplacer {
+            replacer,
+            state: EventState::LineStart { cr: false },
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Give the next bytes of the stream to the replacer, returning the
+    /// rewritten bytes it decides.
+    ///
+    /// Only the end of a `data` value is ever held back, until the end of
+    /// its line is given.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasickReplacer::replace_to_vec`].
+    pub fn replace(&mut self, chunk: &[u8]) -> Result<&[u8], MatchError> {
+        let mut out = core::mem::take(&mut self.buffer);
+        out.clear();
+        let result = self.run(chunk, &mut out);
+        self.buffer = out;
+        result.map(|()| &*self.buffer)
+    }
+
+    /// Returns the bytes held back once the stream ends, which is only the
+    /// end of a `data` value when the stream ends in the middle of its line.
+    pub fn finish(&mut self) -> Result<&[u8], MatchError> {
+        self.replacer.finish()
+    }
+
+    /// Returns the replacer that rewrites the `data` values.
+    pub fn replacer(&self) -> &AhoCorasickReplacer {
+        &self.replacer
+    }
+
+    /// Reset this replacer so that it can be reused for a new stream.
+    pub fn reset(&mut self) {
+        self.replacer.reset();
+        self.state = EventState::LineStart { cr: false };
+    }
+
+    /// Decode the lines of the given bytes, giving their rewritten bytes to
+    /// the given output.
+    fn run(
+        &mut self,
+        chunk: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), MatchError> {
+        // The start of the bytes of the framing that weren't written yet.
+        let mut framing = 0;
+        let mut at = 0;
+        while at < chunk.len() {
+            match self.state {
+                EventState::Value { data: true } => {
+                    // A data value is given to the replacer in bulk, up to
+                    // the end of its line.
+                    let len = chunk[at..]
+                        .iter()
+                        .position(|&b| b == b'\r' || b == b'\n')
+                        .unwrap_or(chunk.len() - at);
+                    self.write(false, &chunk[framing..at], out)?;
+                    self.write(true, &chunk[at..at + len], out)?;
+                    at += len;
+                    framing = at;
+                    if at < chunk.len() {
+                        self.state = self.state.next(chunk[at]);
+                        at += 1;
+                    }
+                }
+                EventState::DataStart if chunk[at] != b' ' => {
+                    self.state = EventState::Value { data: true };
+                }
+                state => {
+                    self.state = state.next(chunk[at]);
+                    at += 1;
+                }
+            }
+        }
+        self.write(false, &chunk[framing..], out)
+    }
+
+    /// Give the given bytes to the replacer, with matching enabled only if
+    /// they are part of a data value.
+    fn write(
+        &mut self,
+        data: bool,
+        bytes: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), MatchError> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        // The bytes held back for a potential match are written when
+        // matching is disabled, so they never wait past the end of a line.
+        self.replacer.matching_enabled(data);
+        self.replacer.replace_to_vec(bytes, out)
+    }
+}
+
+impl EventState {
+    /// Returns the state that follows the given byte.
+    fn next(self, byte: u8) -> EventState {
+        use self::EventState::*;
+
+        match (self, byte) {
+            (LineStart { cr: true }, b'\n') => LineStart { cr: false },
+            (_, b'\r') => LineStart { cr: true },
+            (_, b'\n') => LineStart { cr: false },
+            (LineStart { .. }, b':') => Value { data: false },
+            (LineStart { .. }, _) => Name { data: Some(0) }.next(byte),
+            (Name { data: Some(4) }, b':') => DataStart,
+            (Name { .. }, b':') => Value { data: false },
+            (Name { data: Some(n) }, _) if n < 4 && b"data"[n] == byte => {
+                Name { data: Some(n + 1) }
+            }
+            (Name { .. }, _) => Name { data: None },
+            (DataStart, _) => Value { data: true },
+            (Value { data }, _) => Value { data },
+        }
+    }
+}
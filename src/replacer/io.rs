@@ -0,0 +1,220 @@
+use alloc::vec::Vec;
+
+use crate::replacer::{io_error, AhoCorasickReplacer};
+
+/// The number of bytes an [`AhoCorasickReader`] reads from its source at a
+/// time.
+pub(crate) const READER_CHUNK_LEN: usize = 8 * 1024;
+
+/// A `std::io::Read` adapter that replaces matches in the bytes read from
+/// another `std::io::Read`.
+///
+/// The source is read in chunks, and each chunk is given to the replacer,
+/// so that the output of a read is the replaced bytes of the chunks read so
+/// far, except for the bytes the replacer holds back until the next chunk
+/// shows whether they begin a match. Once the source reaches `EOF`, the
+/// bytes held back are read too, as returned by
+/// [`AhoCorasickReplacer::finish`], and every subsequent read returns `0`.
+///
+/// A read of the source that is interrupted is retried. Any other error is
+/// returned as is, without changing the state of the replacer, so reading may
+/// carry on after it. An error of the replacer is returned as an error of kind
+/// `std::io::ErrorKind::Other` wrapping a [`MatchError`](crate::MatchError).
+///
+/// This reader is constructed via the [`AhoCorasickReplacer::reader`]
+/// method, and is only available when the `std` feature is enabled. See
+/// [`AhoCorasickWriter`] for the adapter that replaces matches while
+/// writing instead.
+///
+/// The type variable `R` refers to the `io::Read` stream that is being read
+/// from.
+pub struct AhoCorasickReader<R> {
+    pub(crate) replacer: AhoCorasickReplacer,
+    pub(crate) source: R,
+    /// The buffer the source is read into.
+    pub(crate) chunk: Vec<u8>,
+    /// The replaced bytes that haven't been read yet, starting at `pos`.
+    pub(crate) out: Vec<u8>,
+    pub(crate) pos: usize,
+    /// Whether the source reached `EOF` and the bytes held back by the
+    /// replacer are in `out`.
+    pub(crate) done: bool,
+}
+
+impl<R> AhoCorasickReader<R> {
+    /// Returns the replacer used by this reader.
+    pub fn replacer(&self) -> &AhoCorasickReplacer {
+        &self.replacer
+    }
+
+    /// Returns a reference to the source.
+    pub fn get_ref(&self) -> &R {
+        &self.source
+    }
+
+    /// Returns a mutable reference to the source. Reading from it directly
+    /// skips the replacer.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.source
+    }
+
+    /// Returns the source. The replaced bytes that were not read yet,
+    /// including those held back by the replacer, are lost.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for AhoCorasickReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // A chunk may be replaced by nothing when it's held back, so the
+        // source is read until there are replaced bytes or it ends.
+        while self.pos == self.out.len() && !self.done {
+            self.out.clear();
+            self.pos = 0;
+            let n = match self.source.read(&mut self.chunk) {
+                Ok(n) => n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Err(err) => return Err(err),
+            };
+            if n == 0 {
+                self.done = true;
+                let rest = self.replacer.finish().map_err(io_error)?;
+                self.out.extend_from_slice(rest);
+            } else {
+                let replaced = self
+                    .replacer
+                    .replace(&self.chunk[..n])
+                    .map_err(io_error)?;
+                self.out.extend_from_slice(replaced);
+            }
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A `std::io::Write` adapter that replaces matches in the bytes written to
+/// it before writing them to another `std::io::Write`.
+///
+/// Each write is given to the replacer, and its replaced bytes are written
+/// to the sink right away, except for the bytes the replacer holds back
+/// until the next write shows whether they begin a match. Since those bytes
+/// can't be written before the stream ends without missing a match,
+/// [`flush`](std::io::Write::flush) only writes the bytes that can no
+/// longer be part of a match, as [`AhoCorasickReplacer::flush`] does, before
+/// flushing the sink.
+///
+/// The stream ends with [`AhoCorasickWriter::finish`] or
+/// [`AhoCorasickWriter::into_inner`], which write the bytes held back. The
+/// writer is also finished when it's dropped, but any error is ignored
+/// then, so finishing it explicitly is recommended.
+///
+/// An error of the sink is returned as is, and an error of the replacer is
+/// returned as an error of kind `std::io::ErrorKind::Other` wrapping a
+/// [`MatchError`](crate::MatchError). Some of the output of a write may have
+/// been written when that happens, so the replacer is
+/// [poisoned](AhoCorasickReplacer::is_poisoned), and every subsequent write,
+/// flush or finish fails.
+///
+/// This writer is constructed via the [`AhoCorasickReplacer::writer`]
+/// method, and is only available when the `std` feature is enabled. See
+/// [`AhoCorasickReader`] for the adapter that replaces matches while
+/// reading instead.
+///
+/// The type variable `W` refers to the `io::Write` sink that is being
+/// written to.
+pub struct AhoCorasickWriter<W: std::io::Write> {
+    pub(crate) replacer: AhoCorasickReplacer,
+    /// The sink, which is only taken by `into_inner`.
+    pub(crate) sink: Option<W>,
+    /// Whether the sink panicked during a write, in which case it's not
+    /// written to again on drop, as `std::io::BufWriter` does.
+    pub(crate) panicked: bool,
+}
+
+impl<W: std::io::Write> AhoCorasickWriter<W> {
+    /// End the stream, writing the bytes held back by the replacer to the
+    /// sink and flushing it.
+    ///
+    /// The replacer is reset afterwards, even if this fails, so that the
+    /// bytes written next start a new stream.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the replacer or the sink fails.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        let sink = self.sink.as_mut().expect("sink is only taken on drop");
+        self.panicked = true;
+        let result = self.replacer.finish().map_err(io_error);
+        let result = result.and_then(|rest| sink.write_all(rest));
+        self.panicked = false;
+        self.replacer.reset();
+        result?;
+        sink.flush()
+    }
+
+    /// Returns the replacer used by this writer.
+    pub fn replacer(&self) -> &AhoCorasickReplacer {
+        &self.replacer
+    }
+
+    /// Returns a reference to the sink.
+    pub fn get_ref(&self) -> &W {
+        self.sink.as_ref().expect("sink is only taken on drop")
+    }
+
+    /// Returns a mutable reference to the sink. Writing to it directly
+    /// skips the replacer, and the bytes held back by the replacer are
+    /// still written after them.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.sink.as_mut().expect("sink is only taken on drop")
+    }
+
+    /// Finish the stream and return the sink.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if finishing the stream fails, in which case
+    /// the sink is dropped along with the writer.
+    pub fn into_inner(mut self) -> std::io::Result<W> {
+        self.finish()?;
+        Ok(self.sink.take().expect("sink is only taken on drop"))
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for AhoCorasickWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let sink = self.sink.as_mut().expect("sink is only taken on drop");
+        self.panicked = true;
+        let result = self.replacer.replace_to(buf, sink);
+        self.panicked = false;
+        result.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let sink = self.sink.as_mut().expect("sink is only taken on drop");
+        self.panicked = true;
+        let result = self.replacer.flush().map_err(io_error);
+        let result = result.and_then(|decided| sink.write_all(decided));
+        self.panicked = false;
+        result?;
+        sink.flush()
+    }
+}
+
+impl<W: std::io::Write> Drop for AhoCorasickWriter<W> {
+    fn drop(&mut self) {
+        if self.sink.is_some() && !self.panicked {
+            // Errors can't be reported from a destructor.
+            let _ = self.finish();
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use alloc::vec::Vec;
+
+use crate::{
+    ahocorasick::enforce_anchored_consistency,
+    replacer::error::{ReplacerError, ReplacerErrorKind},
+    AhoCorasick, Anchored, Input, Match, MatchError,
+};
+
+/// A replacer that rewrites a stream one line at a time.
+///
+/// The bytes of each line are buffered until its terminator, `\n`, is seen,
+/// and the line is then rewritten as a whole, without its terminator, as with
+/// [`AhoCorasick::try_replace_all_bytes`]. A match therefore never spans two
+/// lines, and it may use any [`MatchKind`](crate::MatchKind) and any other
+/// option of the automaton, such as an
+/// [overlap policy](crate::AhoCorasickBuilder::overlap_policy). When the
+/// replacer is anchored, only a match that begins at the start of a line is
+/// replaced, which permits rules about line prefixes such as log levels.
+///
+/// At most `max_line_len` bytes are buffered. A line that is longer than
+/// that, not counting its terminator, is written unchanged instead, as soon
+/// as it's known to be too long, along with the rest of it as it's given.
+/// Every such line is recorded as an [`OverlongLine`], so that a caller may
+/// report it. The last line of a stream doesn't need a terminator, and it
+/// is rewritten by [`LineReplacer::finish`].
+///
+/// This replacer is constructed via the [`AhoCorasick::line_replacer`]
+/// method.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, Anchored, StartKind};
+///
+/// let ac = AhoCorasick::builder()
+///     .start_kind(StartKind::Both)
+///     .build(&["WARN ", "secret"])
+///     .unwrap();
+/// let mut lines = ac.line_replacer(Anchored::No, 16, &["W ", "******"])?;
+/// let mut out = lines.replace(b"WARN secret\nWARN a secret too l")?.to_vec();
+/// out.extend_from_slice(lines.replace(b"ong\nWARN WARN ")?);
+/// out.extend_from_slice(lines.finish()?);
+/// assert_eq!(
+///     b"W ******\nWARN a secret too long\nW W ".to_vec(),
+///     out,
+/// );
+/// assert_eq!(2, lines.overlong_lines()[0].line());
+///
+/// // Only the prefix of each line is replaced when anchored.
+/// let mut lines = ac.line_replacer(Anchored::Yes, 16, &["W ", "******"])?;
+/// let mut out = lines.replace(b"WARN WARN\nsecret")?.to_vec();
+/// out.extend_from_slice(lines.finish()?);
+/// assert_eq!(b"W WARN\n******".to_vec(), out);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct LineReplacer {
+    ac: AhoCorasick,
+    anchored: Anchored,
+    replace_with: Vec<Vec<u8>>,
+    max_line_len: usize,
+    /// The bytes of the current line seen so far, unless it's overlong.
+    line: Vec<u8>,
+    /// Whether the current line is overlong, and so written unchanged.
+    overlong: bool,
+    /// The number of the current line, starting at `1`.
+    line_number: usize,
+    /// The offset of the first byte of the current line.
+    line_start: usize,
+    /// The number of bytes consumed so far.
+    position: usize,
+    buffer: Vec<u8>,
+    overlong_lines: Vec<OverlongLine>,
+}
+
+impl LineReplacer {
+    /// Create a line replacer, checking that the automaton supports the
+    /// given anchored mode and that there is a replacement for every
+    /// pattern.
+    pub(crate) fn new<I, B>(
+        ac: &AhoCorasick,
+        anchored: Anchored,
+        max_line_len: usize,
+        replace_with: I,
+    ) -> Result<LineReplacer, ReplacerError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        enforce_anchored_consistency(ac.start_kind(), anchored)
+            .map_err(ReplacerErrorKind::Match)
+            .map_err(ReplacerError::new)?;
+        let replace_with: Vec<Vec<u8>> =
+            replace_with.into_iter().map(|r| r.as_ref().to_vec()).collect();
+        if replace_with.len() != ac.patterns_len() {
+            return Err(ReplacerError::new(
+                ReplacerErrorKind::ReplacementCount {
+                    expected: ac.patterns_len(),
+                    got: replace_with.len(),
+                },
+            ));
+        }
+        Ok(LineReplacer {
+            ac: ac.clone(),
+            anchored,
+            replace_with,
+            max_line_len,
+            line: Vec::new(),
+            overlong: false,
+            line_number: 1,
+            line_start: 0,
+            position: 0,
+            buffer: Vec::new(),
+            overlong_lines: Vec::new(),
+        })
+    }
+
+    /// Give the next chunk of the stream to the replacer, returning the
+    /// lines it completes, rewritten, and the bytes of an overlong line.
+    ///
+    /// The bytes of a line that isn't complete yet are held back, so the
+    /// output may be empty even if the chunk isn't.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if searching a line fails, which doesn't
+    /// happen with the automaton and anchored mode checked when the
+    /// replacer was created.
+    pub fn replace(&mut self, chunk: &[u8]) -> Result<&[u8], MatchError> {
+        let mut out = core::mem::take(&mut self.buffer);
+        out.clear();
+        let result = self.run(chunk, &mut out);
+        self.buffer = out;
+        result.map(|()| &*self.buffer)
+    }
+
+    /// Rewrite the last line of the stream, which has no terminator, and
+    /// return it. This returns an empty slice if the stream ended with a
+    /// terminator, or if its last line is overlong.
+    pub fn finish(&mut self) -> Result<&[u8], MatchError> {
+        let mut out = core::mem::take(&mut self.buffer);
+        out.clear();
+        let mut result = Ok(());
+        if !self.overlong {
+            result = self.replace_line(&mut out);
+        }
+        self.line.clear();
+        self.buffer = out;
+        result.map(|()| &*self.buffer)
+    }
+
+    /// Returns the overlong lines seen so far.
+    pub fn overlong_lines(&self) -> &[OverlongLine] {
+        &self.overlong_lines
+    }
+
+    /// Returns the overlong lines seen so far, and forgets them, so that a
+    /// long running stream doesn't accumulate them.
+    pub fn take_overlong_lines(&mut self) -> Vec<OverlongLine> {
+        core::mem::take(&mut self.overlong_lines)
+    }
+
+    /// Split the chunk into lines, giving the rewritten lines it completes
+    /// to the given output.
+    fn run(
+        &mut self,
+        chunk: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), MatchError> {
+        let mut rest = chunk;
+        while !rest.is_empty() {
+            let end = rest.iter().position(|&b| b == b'\n');
+            let part = &rest[..end.unwrap_or(rest.len())];
+            self.position += part.len();
+            if !self.overlong
+                && self.line.len() + part.len() > self.max_line_len
+            {
+                self.overlong = true;
+                self.overlong_lines.push(OverlongLine {
+                    line: self.line_number,
+                    start: self.line_start,
+                });
+                out.extend_from_slice(&self.line);
+                self.line.clear();
+            }
+            if self.overlong {
+                out.extend_from_slice(part);
+            } else {
+                self.line.extend_from_slice(part);
+            }
+            let end = match end {
+                None => break,
+                Some(end) => end,
+            };
+            if !self.overlong {
+                self.replace_line(out)?;
+            }
+            out.push(b'\n');
+            self.position += 1;
+            self.line.clear();
+            self.overlong = false;
+            self.line_number += 1;
+            self.line_start = self.position;
+            rest = &rest[end + 1..];
+        }
+        Ok(())
+    }
+
+    /// Write the current line, rewritten, to the given output.
+    fn replace_line(&self, out: &mut Vec<u8>) -> Result<(), MatchError> {
+        let (line, replace_with) = (&self.line, &self.replace_with);
+        if let Anchored::No = self.anchored {
+            let replace = |m: &Match, _: &[u8], dst: &mut Vec<u8>| {
+                dst.extend_from_slice(&replace_with[m.pattern()]);
+                true
+            };
+            return self.ac.try_replace_all_with_bytes(line, out, replace);
+        }
+        let input = Input::new(line).anchored(self.anchored);
+        match self.ac.try_find(input)? {
+            None => out.extend_from_slice(line),
+            Some(m) => {
+                out.extend_from_slice(&replace_with[m.pattern()]);
+                out.extend_from_slice(&line[m.end()..]);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A line that a [`LineReplacer`] wrote unchanged, because it was longer
+/// than its maximum line length.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OverlongLine {
+    line: usize,
+    start: usize,
+}
+
+impl OverlongLine {
+    /// Returns the number of the line, starting at `1`.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the offset of the first byte of the line, measured from the
+    /// first byte given to the replacer.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+}
@@ -0,0 +1,2938 @@
+/*!
+ * This module enables AhoCorasickReplacer, which is used to manually work with chunks of data
+*/
+use alloc::{borrow::Cow, collections::VecDeque, sync::Arc, vec, vec::Vec};
+
+use crate::{
+    ahocorasick::AcAutomaton,
+    automaton::{Automaton, AutomatonImpl, StateID},
+    replacer::{
+        alternatives::Alternatives, error::ReplacerErrorKind, shadow::Decision,
+    },
+    util::{
+        alphabet::ByteSet, lookahead::is_word_byte, overlap::OverlapResolver,
+    },
+    AhoCorasickKind, Anchored, Lookahead, Match, MatchDeduper, MatchError,
+    OverlapPolicy, PatternID, Span,
+};
+
+#[cfg(feature = "std")]
+use crate::replacer::io::READER_CHUNK_LEN;
+
+#[cfg(feature = "std")]
+pub use crate::replacer::io::{AhoCorasickReader, AhoCorasickWriter};
+pub use crate::replacer::{
+    alternatives::{Selection, SelectionState},
+    builder::{
+        AhoCorasickReplacerBuilder, MemoryLimits, Padding, PendingLimit,
+    },
+    chunked::ChunkedReplacer,
+    error::ReplacerError,
+    event::EventStreamReplacer,
+    line::{LineReplacer, OverlongLine},
+    shadow::{ShadowDiff, ShadowReplacer},
+    split::{AhoCorasickStreamSplitter, SplitRecord, SplitRecords},
+    with::AhoCorasickReplacerWith,
+};
+
+mod alternatives;
+mod builder;
+mod chunked;
+mod error;
+mod event;
+#[cfg(feature = "std")]
+mod io;
+mod line;
+mod shadow;
+mod split;
+mod with;
+
+/// The replacer iself
+///
+/// # Match semantics
+///
+/// A replacer finds the same matches as
+/// [`AhoCorasick::replace_all`](crate::AhoCorasick::replace_all) does with the
+/// same automaton, wherever the stream is split into chunks:
+///
+/// * A match is replaced as soon as it ends, and the search restarts right
+///   after it, so matches never overlap. A match that begins exactly where
+///   the previous one ended, including one that immediately follows a
+///   replacement, is replaced as well. Such back-to-back matches may be
+///   replaced only once with [`AhoCorasickReplacerBuilder::coalesce`].
+///   The search may instead resume from the state that the replacement
+///   leads to with [`AhoCorasickReplacerBuilder::replacement_context`].
+/// * A match at the very start of the stream, at position `0`, is no
+///   different from any other match.
+/// * An empty pattern matches at every position, including before the
+///   first byte and after the last one, even when the stream is empty. Its
+///   replacement is therefore inserted between every byte, and no other
+///   pattern ever matches while it is enabled. Empty matches may be
+///   ignored instead with
+///   [`AhoCorasickReplacerBuilder::ignore_empty_matches`].
+/// * With an [overlap policy](crate::AhoCorasickBuilder::overlap_policy),
+///   every match of every pattern is considered instead, including empty
+///   ones, and the policy decides which of the overlapping ones are
+///   replaced. A replacer may also be given its own policy with
+///   [`AhoCorasickReplacerBuilder::overlap_policy`].
+/// * With [`AhoCorasickReplacerBuilder::anchored`], only a match that
+///   begins at the start of the stream, or at the start of a record, is
+///   replaced. See [`AhoCorasickReplacer::start_record`].
+///
+/// With [`MatchKind::LeftmostLongest`](crate::MatchKind::LeftmostLongest)
+/// semantics, a match is only replaced once the automaton tells that no longer
+/// match begins at the same position, or at a position before it, so that the
+/// matches replaced are the ones reported by
+/// [`AhoCorasick::find_iter`](crate::AhoCorasick::find_iter). With the
+/// patterns `foo` and `foobar`, the bytes of `foo` are held back until the
+/// next bytes show whether they begin `foobar`. The bytes held back never
+/// exceed the length of the longest pattern past the start of a potential
+/// match. Empty matches are found as in a non-overlapping search, so an empty
+/// pattern only matches where no other pattern does. This doesn't support
+/// [lookaheads](crate::AhoCorasickBuilder::lookahead),
+/// [word boundaries](crate::AhoCorasickBuilder::word_boundary),
+/// [disabled patterns](crate::AhoCorasick::disable_pattern),
+/// [anchored replacement](AhoCorasickReplacerBuilder::anchored) or
+/// [`AhoCorasickReplacerBuilder::replacement_context`], and building such a
+/// replacer returns an error.
+/// [`MatchKind::LeftmostFirst`](crate::MatchKind::LeftmostFirst) semantics
+/// aren't supported at all.
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, MatchKind};
+///
+/// let ac = AhoCorasick::builder()
+///     .match_kind(MatchKind::LeftmostLongest)
+///     .build(&["foo", "foobar"])
+///     .unwrap();
+/// let mut replacer = ac.replacer(&["1", "2"])?;
+/// let mut out = replacer.replace(b"foo fo")?.to_vec();
+/// out.extend_from_slice(replacer.replace(b"oba")?);
+/// out.extend_from_slice(replacer.replace(b"r foob")?);
+/// out.extend_from_slice(replacer.finish()?);
+/// assert_eq!(b"1 2 1b".to_vec(), out);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Replacing on another thread
+///
+/// A replacer owns all of its state, and it's `Send` and `'static`, so
+/// replacing a large chunk can be moved off a thread that must not block,
+/// such as the thread of an async executor, by moving the replacer to
+/// another thread along with the chunk, and moving it back with the
+/// output. Since a replacer is only ever used by one thread at a time, the
+/// state it holds back between chunks stays consistent, and the output is
+/// the same as if every chunk was replaced on the same thread. With tokio,
+/// the closure below may be given to `tokio::task::spawn_blocking` as is,
+/// and with the `async` feature, `replace_chunk_blocking` does this for any
+/// function that spawns a blocking job.
+///
+/// ```
+/// use aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+/// let mut replacer = ac.replacer(&["bear", "white"])?;
+/// let mut out = vec![];
+/// for chunk in [b"The brown f".to_vec(), b"ox.".to_vec()] {
+///     let handle = std::thread::spawn(move || {
+///         let output = replacer.replace(&chunk).map(|out| out.to_vec());
+///         (replacer, output)
+///     });
+///     let (back, output) = handle.join().unwrap();
+///     replacer = back;
+///     out.extend_from_slice(&output?);
+/// }
+/// out.extend_from_slice(replacer.finish()?);
+/// assert_eq!(b"The white bear.".to_vec(), out);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct AhoCorasickReplacer {
+    aut: Arc<dyn AcAutomaton>,
+    kind: AhoCorasickKind,
+    anchored: Anchored, // Whether only matches at the start of a record are replaced
+    start: StateID,
+    sid: StateID,
+    replace_with: Arc<[Vec<u8>]>,
+    buffer: Vec<u8>, // Buffer holding the replaced data
+    potential_buffer: VecDeque<u8>, // Buffer holding the start of a potential match
+    coalesce: bool, // Whether a run of adjacent matches is replaced only once
+    in_run: bool,   // Whether the last bytes written were a replacement
+    dedup: Option<MatchDeduper>, // Suppresses repeated matches of the same pattern
+    position: usize,             // Number of bytes consumed so far
+    record_start: usize, // Position at which the current record started
+    matching_enabled: bool, // Whether matches are replaced in the bytes consumed now
+    disabled: Vec<bool>, // Patterns whose matches are ignored, empty if there are none
+    decisions: Option<Vec<Decision>>, // The replacements made, recorded only when shadowing
+    lookaheads: Option<Arc<[Option<Lookahead>]>>, // The lookahead of each pattern, if any has one
+    pending: Option<PendingLookahead>, // A match waiting for the bytes that decide its lookahead
+    ignore_empty: bool,                // Whether empty patterns never match
+    empty_at_end: bool, // Whether the empty match at the end of the stream was written
+    resolving: Option<Resolving>, // The matches and bytes held back when an overlap policy is set
+    replacement_context: bool, // Whether the search resumes from the state the replacement leads to
+    replaced_end: usize,       // The position where the last replacement ended
+    stats: ReplaceStats, // The number of replacements made since the last reset
+    pending_limit: Option<PendingLimit>, // The most bytes held back once a chunk is consumed
+    alternatives: Option<Alternatives>, // The alternatives of each pattern, if it may have several replacements
+    limits: MemoryLimits, // The caps on the memory of the buffers
+    recent: Option<Recent>, // The last bytes consumed, kept only when matches must be bounded by non-word bytes
+    leftmost: Option<Leftmost>, // The bytes held back and the current search with leftmost-longest semantics
+    poisoned: bool, // Whether an error stopped the replacer in the middle of a chunk
+    quit: Option<ByteSet>, // The bytes the replacer stops at, if any
+    stopped: Option<MatchError>, // The error of the quit byte the last chunk stopped at
+}
+
+/// The state of a replacer that decides overlapping matches with an
+/// [`OverlapPolicy`].
+///
+/// The bytes that aren't written yet are held back from the first byte
+/// that a match found later may still replace.
+#[derive(Clone, Debug)]
+struct Resolving {
+    resolver: OverlapResolver,
+    /// The bytes that weren't written yet.
+    held: VecDeque<u8>,
+    /// The offset of the first byte held back.
+    held_start: usize,
+    /// Matches waiting for the bytes that decide their lookahead, along
+    /// with the number of bytes after them seen so far.
+    undecided: Vec<(Match, usize)>,
+    /// The matches decided to be replaced, reused between calls.
+    replaced: Vec<Match>,
+    /// Whether the matches of the start state at the current position have
+    /// yet to be found, which is the case before the first byte and after
+    /// matching is toggled.
+    at_start: bool,
+}
+
+impl Resolving {
+    fn new(policy: OverlapPolicy) -> Resolving {
+        Resolving {
+            resolver: OverlapResolver::new(policy),
+            held: VecDeque::new(),
+            held_start: 0,
+            undecided: Vec::new(),
+            replaced: Vec::new(),
+            at_start: true,
+        }
+    }
+
+    /// Forget the bytes and matches of the current stream, keeping the
+    /// allocations.
+    fn reset(&mut self) {
+        self.resolver.clear();
+        self.held.clear();
+        self.held_start = 0;
+        self.undecided.clear();
+        self.replaced.clear();
+        self.at_start = true;
+    }
+}
+
+/// The state of a replacer with [`MatchKind::LeftmostLongest`] semantics.
+///
+/// A match can't be replaced as soon as it ends, since a longer one may
+/// begin at the same position, so each search goes on until the automaton
+/// tells that no match found later can win, exactly like a non-overlapping
+/// search of the whole stream does. That happens within the length of the
+/// longest pattern after the start of the last match found, so only that
+/// many bytes are held back.
+#[derive(Clone, Debug)]
+struct Leftmost {
+    /// The bytes that weren't written yet.
+    held: VecDeque<u8>,
+    /// The offset of the first byte held back.
+    held_start: usize,
+    /// The offset at which the current search began.
+    search_start: usize,
+    /// The offset up to which the current search consumed the bytes held
+    /// back.
+    scanned: usize,
+    /// The last match found by the current search, which is the one
+    /// replaced unless the search finds another one.
+    last: Option<Match>,
+    /// The end of the last match replaced, so that an empty match there is
+    /// skipped, like in a non-overlapping search.
+    last_match_end: Option<usize>,
+    /// The empty pattern matched by the start state, if empty matches
+    /// aren't ignored.
+    empty: Option<PatternID>,
+    /// Whether the automaton has an empty pattern whose matches are
+    /// ignored. A search may then end without a match, in which case it
+    /// starts again one byte later.
+    ignored_empty: bool,
+}
+
+impl Leftmost {
+    fn new(
+        aut: AutomatonImpl<'_>,
+        start: StateID,
+        ignore_empty: bool,
+    ) -> Leftmost {
+        // Only the start state of an automaton with an empty pattern is a
+        // match state, and its first pattern is the empty one.
+        let empty = if aut.is_match(start) {
+            Some(aut.match_pattern(start, 0))
+                .filter(|&pid| aut.pattern_len(pid) == 0)
+        } else {
+            None
+        };
+        let mut leftmost = Leftmost {
+            held: VecDeque::new(),
+            held_start: 0,
+            search_start: 0,
+            scanned: 0,
+            last: None,
+            last_match_end: None,
+            empty: empty.filter(|_| !ignore_empty),
+            ignored_empty: empty.is_some() && ignore_empty,
+        };
+        leftmost.start_search(0);
+        leftmost
+    }
+
+    /// Start a new search at the given offset, which the start state
+    /// matches if there's an empty pattern.
+    fn start_search(&mut self, at: usize) {
+        self.search_start = at;
+        self.scanned = at;
+        self.last = self.empty.map(|pid| Match::new(pid, at..at));
+    }
+
+    /// Forget the bytes and matches of the current stream, keeping the
+    /// allocations.
+    fn reset(&mut self) {
+        self.held.clear();
+        self.held_start = 0;
+        self.last_match_end = None;
+        self.start_search(0);
+    }
+}
+
+/// What the match state a replacer is in turned out to be.
+#[derive(Clone, Copy, Debug)]
+enum Decided {
+    /// The match of this pattern is replaced.
+    Accept(PatternID),
+    /// The match of this pattern needs more bytes to decide its lookahead.
+    Pending(PatternID),
+    /// Every pattern matched by the state is either disabled or rejected by
+    /// its lookahead.
+    Nothing,
+}
+
+/// The last bytes consumed by a replacer whose matches must be bounded by
+/// non-word bytes, which tell whether a match is preceded by a word byte.
+///
+/// A match is decided as soon as it ends, so only the bytes of the longest
+/// pattern and the byte before them are ever needed.
+#[derive(Clone, Debug)]
+struct Recent {
+    bytes: VecDeque<u8>,
+    /// The most bytes kept.
+    capacity: usize,
+}
+
+impl Recent {
+    fn new(max_pattern_len: usize) -> Recent {
+        Recent { bytes: VecDeque::new(), capacity: max_pattern_len + 1 }
+    }
+
+    /// Record the given bytes as the last bytes consumed.
+    fn push(&mut self, bytes: &[u8]) {
+        let skip = bytes.len().saturating_sub(self.capacity);
+        for &byte in bytes[skip..].iter() {
+            if self.bytes.len() == self.capacity {
+                self.bytes.pop_front();
+            }
+            self.bytes.push_back(byte);
+        }
+    }
+
+    /// Returns the byte consumed `back` bytes before the last one, if it
+    /// was kept.
+    fn get(&self, back: usize) -> Option<u8> {
+        let i = self.bytes.len().checked_sub(back + 1)?;
+        self.bytes.get(i).copied()
+    }
+}
+
+/// A match that ends at the current position of a replacer, and whose
+/// lookahead needs more bytes than were given so far to be decided.
+///
+/// The bytes after the match are held back at the end of the potential
+/// buffer, and they are only searched once the match is decided.
+#[derive(Clone, Copy, Debug)]
+struct PendingLookahead {
+    pattern_id: PatternID,
+    /// The number of bytes after the match that were held back.
+    seen: usize,
+}
+
+impl AhoCorasickReplacer {
+    /// Instantiate a new Replacer. The caller must have checked that the
+    /// automaton and replacements can be used together.
+    fn new(
+        aut: Arc<dyn AcAutomaton>,
+        kind: AhoCorasickKind,
+        anchored: Anchored,
+        replace_with: Arc<[Vec<u8>]>,
+    ) -> Result<Self, MatchError> {
+        // The start state is the only fallible call to the automaton, so
+        // it's looked up once, and the search of a stream never fails.
+        let start = aut.start_state(anchored)?;
+        Ok(Self {
+            aut,
+            kind,
+            anchored,
+            start,
+            sid: start,
+            replace_with,
+            buffer: Vec::new(),
+            potential_buffer: VecDeque::new(),
+            coalesce: false,
+            in_run: false,
+            dedup: None,
+            position: 0,
+            record_start: 0,
+            matching_enabled: true,
+            disabled: Vec::new(),
+            decisions: None,
+            lookaheads: None,
+            pending: None,
+            recent: None,
+            leftmost: None,
+            poisoned: false,
+            quit: None,
+            stopped: None,
+            ignore_empty: false,
+            empty_at_end: false,
+            resolving: None,
+            replacement_context: false,
+            replaced_end: 0,
+            stats: ReplaceStats::default(),
+            pending_limit: None,
+            alternatives: None,
+            limits: MemoryLimits::new(),
+        })
+    }
+
+    /// Perform potential replacements in the chunk, reading the reference to the internal buffer containing the chunk data with eventually replaced bytes.
+    /// self reference might be of 0 length even if the input was non-zero,
+    /// because it might be holding onto a potential match without being able to decide whether replace or discard it yet
+    ///
+    /// Chunks may have any size. In particular, an empty chunk is a no-op
+    /// that returns an empty slice, and splitting a stream into chunks of
+    /// a single byte gives exactly the same output as giving it all at once,
+    /// since a potential match is carried over from one call to the next.
+    /// The overhead of each call is small and constant, so tiny chunks, as
+    /// delivered by interactive sources, are handled efficiently.
+    pub fn replace(&mut self, chunk: &[u8]) -> Result<&[u8], MatchError> {
+        self.check_poisoned()?;
+        if chunk.is_empty() {
+            return Ok(&[]);
+        }
+        // The buffer is reused between calls. Its length is the number of
+        // bytes written so far, so it is never filled with bytes that are
+        // about to be overwritten, and reserving room for the chunk once
+        // avoids incremental capacity reallocations in the common case.
+        let mut buffer = core::mem::take(&mut self.buffer);
+        buffer.clear();
+        let limit = self.output_limit();
+        buffer.reserve(core::cmp::min(
+            chunk.len() + self.potential_buffer.len(),
+            limit,
+        ));
+        let result =
+            self.run_bounded(chunk, &mut Bounded { buf: &mut buffer, limit });
+        self.buffer = buffer;
+        result?;
+        self.check_stopped()?;
+        Ok(&self.buffer)
+    }
+
+    /// Perform potential replacements in the chunk, returning a slice of the
+    /// chunk itself when its output is a prefix of it.
+    ///
+    /// The output is the same as the one returned by
+    /// [`AhoCorasickReplacer::replace`]. When no match is replaced in the
+    /// chunk and no byte is held back from the previous chunk, which is the
+    /// common case in a stream where matches are rare, the output is the
+    /// chunk, minus the bytes held back at its end, and it's returned
+    /// without being copied. Otherwise, it's copied to the internal buffer,
+    /// as with [`AhoCorasickReplacer::replace`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasickReplacer::replace`], except that an output returned
+    /// without being copied isn't bounded by [`MemoryLimits::output`], since
+    /// it doesn't use the internal buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// let chunk = b"The quick f";
+    /// let out = replacer.replace_borrowed(chunk)?;
+    /// // The start of "fox" is held back, and the rest isn't copied.
+    /// assert_eq!(b"The quick ", out);
+    /// assert_eq!(chunk.as_ptr(), out.as_ptr());
+    /// let mut out = replacer.replace_borrowed(b"ox.")?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"bear.".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replace_borrowed<'a>(
+        &'a mut self,
+        chunk: &'a [u8],
+    ) -> Result<&'a [u8], MatchError> {
+        self.check_poisoned()?;
+        if chunk.is_empty() {
+            return Ok(&[]);
+        }
+        let mut buffer = core::mem::take(&mut self.buffer);
+        buffer.clear();
+        let limit = self.output_limit();
+        let mut out =
+            PassThrough { chunk, borrowed: Some(0), buf: &mut buffer, limit };
+        let result = self.run_bounded(chunk, &mut out);
+        let borrowed = out.borrowed;
+        self.buffer = buffer;
+        result?;
+        if let Err(err) = self.check_stopped() {
+            // The output borrowed from the chunk goes first too, so that
+            // none of it is lost.
+            if let Some(len) = borrowed {
+                self.hold_front(chunk[..len].to_vec());
+            }
+            return Err(err);
+        }
+        match borrowed {
+            Some(len) => Ok(&chunk[..len]),
+            None => Ok(&self.buffer),
+        }
+    }
+
+    /// Perform potential replacements in a chunk made of the given slices,
+    /// one after the other, as returned by a vectored read.
+    ///
+    /// This gives the same output as giving the concatenation of the slices
+    /// to [`AhoCorasickReplacer::replace`], without copying them into a
+    /// single buffer first. In particular, a match may span several slices,
+    /// and the limit set by [`AhoCorasickReplacerBuilder::max_pending_bytes`]
+    /// only applies once every slice is consumed.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`AhoCorasickReplacer::replace`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::IoSlice;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// let slices = [IoSlice::new(b"The bro"), IoSlice::new(b"wn f")];
+    /// let mut out = replacer.replace_vectored(&slices)?.to_vec();
+    /// let slices =
+    ///     [IoSlice::new(b"ox"), IoSlice::new(b""), IoSlice::new(b".")];
+    /// out.extend_from_slice(replacer.replace_vectored(&slices)?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"The white bear.".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn replace_vectored(
+        &mut self,
+        chunks: &[std::io::IoSlice<'_>],
+    ) -> Result<&[u8], MatchError> {
+        self.check_poisoned()?;
+        let len: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+        if len == 0 {
+            return Ok(&[]);
+        }
+        let mut buffer = core::mem::take(&mut self.buffer);
+        buffer.clear();
+        let limit = self.output_limit();
+        let reserve = len + self.potential_buffer.len();
+        buffer.reserve(core::cmp::min(reserve, limit));
+        let mut out = Bounded { buf: &mut buffer, limit };
+        // The slices after a quit byte aren't consumed.
+        let result = chunks
+            .iter()
+            .try_for_each(|chunk| match self.stopped {
+                Some(_) => Ok(()),
+                None => self.run_to_quit(chunk, &mut out),
+            })
+            .and_then(|()| self.flush_over_limit(&mut out));
+        self.poisoned |= result.is_err();
+        self.buffer = buffer;
+        result?;
+        self.check_stopped()?;
+        Ok(&self.buffer)
+    }
+
+    /// Perform potential replacements in the chunk, writing the result
+    /// directly to the given sink instead of the internal buffer.
+    ///
+    /// Bytes of the chunk that are not part of a match are written straight
+    /// from the chunk, and replacements straight from the replacer, so the
+    /// output is never copied into an intermediate buffer. Only the start of
+    /// a potential match at the end of the chunk is held back, as with
+    /// [`AhoCorasickReplacer::replace`], and it is returned by
+    /// [`AhoCorasickReplacer::finish`] once the stream ends.
+    ///
+    /// The bytes of a chunk are generally given to the sink in several
+    /// writes, so a sink that is not already buffered should be wrapped in
+    /// a `std::io::BufWriter`.
+    ///
+    /// As with [`AhoCorasickReplacer::replace`], chunks may have any size,
+    /// and an empty chunk writes nothing to the sink.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if writing to the sink fails. Some of the
+    /// output of the chunk may have been written when that happens, so the
+    /// replacer is [poisoned](AhoCorasickReplacer::is_poisoned) until it's
+    /// reset.
+    ///
+    /// This also returns an error of kind `std::io::ErrorKind::Other`,
+    /// wrapping a [`MatchError`], when the replacer holds back more bytes
+    /// than allowed by [`PendingLimit::Error`]. The output of the chunk is
+    /// written to the sink first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// let mut out = vec![];
+    /// for chunk in [&b"The brown f"[..], b"ox."] {
+    ///     replacer.replace_to(chunk, &mut out)?;
+    /// }
+    /// out.write_all(replacer.finish()?)?;
+    /// assert_eq!(b"The white bear.".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn replace_to<W: std::io::Write>(
+        &mut self,
+        chunk: &[u8],
+        sink: &mut W,
+    ) -> std::io::Result<()> {
+        self.check_poisoned().map_err(io_error)?;
+        // As with `replace_to_vec`, nothing is held back on an error.
+        self.buffer.clear();
+        self.run_bounded(chunk, &mut WriteOutput(sink))?;
+        self.check_stopped().map_err(io_error)
+    }
+
+    /// Perform potential replacements in the chunk, appending the result to
+    /// the given buffer instead of returning it from the internal buffer.
+    ///
+    /// The output is the same as the one returned by
+    /// [`AhoCorasickReplacer::replace`], but it's owned by the caller, so it
+    /// can be kept across calls or sent to another thread without copying
+    /// it first. The bytes already in the buffer are left as is, so giving
+    /// the same buffer to every call collects the whole output.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when the replacer holds back more bytes than
+    /// allowed by [`PendingLimit::Error`]. As with
+    /// [`AhoCorasickReplacer::replace_to`], the output of the chunk is
+    /// appended to the buffer first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// let mut out = vec![];
+    /// for chunk in [&b"The brown f"[..], b"ox."] {
+    ///     replacer.replace_to_vec(chunk, &mut out)?;
+    /// }
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"The white bear.".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replace_to_vec(
+        &mut self,
+        chunk: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), MatchError> {
+        self.check_poisoned()?;
+        // The output doesn't go to the internal buffer, so it holds nothing
+        // that would have to be held back on an error.
+        self.buffer.clear();
+        out.reserve(chunk.len() + self.potential_buffer.len());
+        match self.run_bounded(chunk, out) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        self.check_stopped()
+    }
+
+    /// Enable or disable matching for the bytes given to the replacer from
+    /// now on.
+    ///
+    /// While matching is disabled, chunks are passed through verbatim. This
+    /// permits rewriting only the payload of a framed protocol: a caller
+    /// that parses the framing disables matching for the bytes of each frame
+    /// header, and enables it again for its payload. Toggling may happen
+    /// between any two chunks, so a chunk should end wherever a region ends.
+    ///
+    /// A match never spans a toggle point. Bytes held back at a toggle point
+    /// because they might have started a match are written verbatim before
+    /// the output of the next chunk, or returned by
+    /// [`AhoCorasickReplacer::finish`], so the output is always in the same
+    /// order as the input. Matches are found again from scratch once
+    /// matching is enabled, and a run of matches never continues across
+    /// a toggle point when coalescing.
+    ///
+    /// Matching is enabled by default. Setting it to its current value does
+    /// nothing.
+    ///
+    /// # Example
+    ///
+    /// This rewrites the payload of frames, made of a 2 byte header with the
+    /// payload length in its second byte, without ever touching a header.
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["\x05", "hello"]).unwrap();
+    /// let mut replacer = ac.replacer(&["\x00", "HOWDY"])?;
+    /// let stream = b"\x81\x05hello\x81\x05\x05hell";
+    ///
+    /// let mut out = vec![];
+    /// let mut at = 0;
+    /// while at < stream.len() {
+    ///     let len = 2 + usize::from(stream[at + 1]);
+    ///     replacer.matching_enabled(false);
+    ///     out.extend_from_slice(replacer.replace(&stream[at..at + 2])?);
+    ///     replacer.matching_enabled(true);
+    ///     out.extend_from_slice(replacer.replace(&stream[at + 2..at + len])?);
+    ///     at += len;
+    /// }
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"\x81\x05HOWDY\x81\x05\x00hell".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn matching_enabled(&mut self, yes: bool) {
+        if yes == self.matching_enabled {
+            return;
+        }
+        // A match waiting for its lookahead is decided as if the stream
+        // ended here, since matches never span a toggle point.
+        self.settle();
+        self.toggle_matching();
+    }
+
+    /// Toggle matching once the match waiting for its lookahead, if any, is
+    /// decided. See [`AhoCorasickReplacer::matching_enabled`].
+    fn toggle_matching(&mut self) {
+        self.matching_enabled = !self.matching_enabled;
+        self.restart();
+    }
+
+    /// Start a new record, so that the bytes given to the replacer from now
+    /// on are searched as if they began a stream.
+    ///
+    /// This is mostly useful with [`AhoCorasickReplacerBuilder::anchored`],
+    /// where only a match at the start of the stream is replaced otherwise.
+    /// A caller that parses the records of a protocol, such as the messages
+    /// of a connection, calls this before the first byte of each record, so
+    /// that a header at the start of every record is rewritten.
+    ///
+    /// As with [`AhoCorasickReplacer::matching_enabled`], a match never
+    /// spans the start of a record, and the bytes held back before it are
+    /// written verbatim before the output of the next chunk. Enabling
+    /// matching also starts a new record.
+    ///
+    /// A record is empty until a byte is given to the replacer, so calling
+    /// this again before that has no effect, and the stream begins with a
+    /// record. In particular, an empty pattern matches at the start of the
+    /// stream once, and the last record may be empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacerBuilder, Anchored, StartKind,
+    /// };
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .start_kind(StartKind::Anchored)
+    ///     .build(&["GET /v1/", "Host: a"])
+    ///     .unwrap();
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().anchored(Anchored::Yes),
+    ///     &["GET /v2/", "Host: b"],
+    /// )?;
+    /// let mut out = vec![];
+    /// for record in [&b"GET /v1/x GET /v1/y\n"[..], b"Host: a\n", b"GET /v"] {
+    ///     replacer.start_record();
+    ///     out.extend_from_slice(replacer.replace(record)?);
+    /// }
+    /// replacer.start_record();
+    /// out.extend_from_slice(replacer.replace(b"1/z")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"GET /v2/x GET /v1/y\nHost: b\nGET /v1/z".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn start_record(&mut self) {
+        // A record can't be told apart from the empty one before it.
+        if self.position == self.record_start {
+            return;
+        }
+        // A match waiting for its lookahead is decided as if the stream
+        // ended here, since matches never span the start of a record.
+        self.settle();
+        self.restart();
+    }
+
+    /// Restart the search from the start state, once the match waiting for
+    /// its lookahead, if any, is decided.
+    fn restart(&mut self) {
+        // The bytes held back can't start a match anymore. They stay in the
+        // potential buffer until the next output, which the search handles
+        // like any other bytes preceding the current state.
+        self.sid = self.start;
+        self.in_run = false;
+        self.record_start = self.position;
+        if let Some(ref mut resolving) = self.resolving {
+            resolving.at_start = true;
+        }
+        if let Some(ref mut leftmost) = self.leftmost {
+            leftmost.held_start = self.position;
+            leftmost.start_search(self.position);
+        }
+    }
+
+    /// Returns true if matching is enabled for the bytes given to the
+    /// replacer from now on. See [`AhoCorasickReplacer::matching_enabled`].
+    pub fn is_matching_enabled(&self) -> bool {
+        self.matching_enabled
+    }
+
+    /// Enable or disable replacing the matches of the given pattern.
+    ///
+    /// A disabled pattern is treated as if it wasn't in the automaton: the
+    /// bytes it matches are written unchanged, unless an enabled pattern
+    /// matches them. When the patterns matched by a state are a mix of
+    /// disabled and enabled patterns, the longest enabled one is replaced.
+    /// This makes it possible to share one replacer configuration across
+    /// requests or tenants that each turn on a different subset of the
+    /// rules, without building another automaton.
+    ///
+    /// The change applies to matches found from now on, which includes a
+    /// potential match whose start is held back from previous chunks.
+    ///
+    /// Every pattern is enabled by default, except for the patterns that were
+    /// disabled with
+    /// [`AhoCorasick::disable_pattern`](crate::AhoCorasick::disable_pattern)
+    /// when this replacer was created.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the given pattern ID isn't a pattern of the automaton
+    /// this replacer was created from, or if a pattern is disabled while the
+    /// automaton has
+    /// [`MatchKind::LeftmostLongest`](crate::MatchKind::LeftmostLongest)
+    /// semantics, which don't support disabled patterns.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let ac = AhoCorasick::new(&["secret", "cret"]).unwrap();
+    /// let mut replacer = ac.replacer(&["******", "####"])?;
+    /// replacer.set_enabled(PatternID::must(0), false);
+    /// let mut out = replacer.replace(b"top secret")?.to_vec();
+    /// replacer.set_enabled(PatternID::must(1), false);
+    /// out.extend_from_slice(replacer.replace(b" secret")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"top se#### secret".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_enabled(&mut self, pid: PatternID, yes: bool) {
+        let patterns_len = self.aut.patterns_len();
+        assert!(
+            pid.as_usize() < patterns_len,
+            "pattern ID {} is out of range for an automaton with {} patterns",
+            pid.as_usize(),
+            patterns_len,
+        );
+        assert!(
+            yes || self.leftmost.is_none(),
+            "patterns can't be disabled with leftmost-longest semantics",
+        );
+        if self.disabled.is_empty() {
+            if yes {
+                return;
+            }
+            self.disabled = vec![false; patterns_len];
+        }
+        self.disabled[pid] = !yes;
+    }
+
+    /// Returns true if the matches of the given pattern are replaced. See
+    /// [`AhoCorasickReplacer::set_enabled`].
+    pub fn is_enabled(&self, pid: PatternID) -> bool {
+        !self.disabled.get(pid.as_usize()).copied().unwrap_or(false)
+    }
+
+    /// Reset this replacer so that it can be reused for a new stream.
+    ///
+    /// Every byte given to the replacer that wasn't written yet, such as the
+    /// start of a potential match, is discarded, and the search restarts
+    /// from the start state at position `0`. So call
+    /// [`AhoCorasickReplacer::finish`] first to get the end of the current
+    /// stream. The output buffer keeps its allocation, and the automaton and
+    /// the replacements are still shared, so reusing a replacer is cheaper
+    /// than building a new one for each stream.
+    ///
+    /// The options the replacer was built with, the patterns disabled with
+    /// [`AhoCorasickReplacer::set_enabled`] and whether
+    /// [matching is enabled](AhoCorasickReplacer::matching_enabled) are
+    /// kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// // The start of "fox" is held back, and discarded by the reset.
+    /// assert_eq!(b"The white ".to_vec(), replacer.replace(b"The brown fo")?);
+    /// replacer.reset();
+    /// let mut out = replacer.replace(b"ox and fox")?.to_vec();
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"ox and bear".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn reset(&mut self) {
+        self.sid = self.start;
+        self.buffer.clear();
+        self.potential_buffer.clear();
+        self.in_run = false;
+        self.position = 0;
+        self.record_start = 0;
+        self.pending = None;
+        if let Some(ref mut recent) = self.recent {
+            recent.bytes.clear();
+        }
+        self.empty_at_end = false;
+        self.replaced_end = 0;
+        if let Some(ref mut dedup) = self.dedup {
+            dedup.reset();
+        }
+        if let Some(ref mut decisions) = self.decisions {
+            decisions.clear();
+        }
+        if let Some(ref mut resolving) = self.resolving {
+            resolving.reset();
+        }
+        if let Some(ref mut leftmost) = self.leftmost {
+            leftmost.reset();
+        }
+        if let Some(ref mut alternatives) = self.alternatives {
+            alternatives.reset();
+        }
+        self.poisoned = false;
+        self.stopped = None;
+        self.stats.reset();
+    }
+
+    /// Returns true if an error left this replacer in an undefined state,
+    /// in which case every call that consumes the stream returns an error
+    /// of kind [`MatchErrorKind::Poisoned`](crate::MatchErrorKind::Poisoned)
+    /// until the replacer is [reset](AhoCorasickReplacer::reset).
+    ///
+    /// Searching a stream never fails, so only an output that fails in the
+    /// middle of a chunk poisons a replacer: the output of a call exceeding
+    /// the cap set by [`MemoryLimits::output`], or a sink given to
+    /// [`AhoCorasickReplacer::replace_to`] returning an error. The bytes of
+    /// the chunk after that point were neither written nor held back. The
+    /// other errors occur once a chunk is consumed, and leave the replacer
+    /// in a well-defined state: after exceeding the limit set by
+    /// [`PendingLimit::Error`], the stream may still be finished, and after
+    /// exceeding the cap set by [`MemoryLimits::events`], it may go on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacerBuilder, MatchErrorKind,
+    ///     MemoryLimits,
+    /// };
+    ///
+    /// let ac = AhoCorasick::new(&["a"]).unwrap();
+    /// let limits = MemoryLimits::new().output(4);
+    /// let mut replacer = ac.replacer_with(
+    ///     AhoCorasickReplacerBuilder::new().memory_limits(Some(limits)),
+    ///     &["<A>"],
+    /// )?;
+    /// assert!(replacer.replace(b"aa").is_err());
+    /// assert!(replacer.is_poisoned());
+    /// let err = replacer.finish().unwrap_err();
+    /// assert_eq!(MatchErrorKind::Poisoned, *err.kind());
+    ///
+    /// replacer.reset();
+    /// assert_eq!(b"<A>b", replacer.replace(b"ab")?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Returns the number of replacements made since this replacer was
+    /// created or last reset, in total and for each pattern.
+    ///
+    /// A match is counted once a replacement is written for it, so a match
+    /// that is suppressed by deduplication, disabled, rejected by its
+    /// lookahead or coalesced into a run is not counted. With
+    /// [`AhoCorasick::replacer_with_alternatives`](crate::AhoCorasick::replacer_with_alternatives),
+    /// a replacement is counted for its pattern, whichever alternative was
+    /// selected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, PatternID};
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "dog"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "cat"])?;
+    /// replacer.replace(b"fox, dog and f")?;
+    /// assert_eq!(2, replacer.stats().total());
+    /// replacer.replace(b"ox")?;
+    /// replacer.finish()?;
+    /// assert_eq!(3, replacer.stats().total());
+    /// assert_eq!(2, replacer.stats().count(PatternID::must(0)));
+    /// assert_eq!(1, replacer.stats().count(PatternID::must(1)));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stats(&self) -> &ReplaceStats {
+        &self.stats
+    }
+
+    /// Returns an error if an error left this replacer in an undefined
+    /// state. See [`AhoCorasickReplacer::is_poisoned`].
+    fn check_poisoned(&self) -> Result<(), MatchError> {
+        if self.poisoned {
+            return Err(MatchError::poisoned());
+        }
+        Ok(())
+    }
+
+    /// Returns the state of the selection of alternative replacements, or
+    /// `None` if this replacer wasn't built with
+    /// [`AhoCorasick::replacer_with_alternatives`](crate::AhoCorasick::replacer_with_alternatives).
+    ///
+    /// Along with [`AhoCorasickReplacer::set_selection_state`], this lets a
+    /// stream processed in several runs, possibly on different machines,
+    /// get the same replacements as if it was processed in a single one.
+    /// The state only changes when a match is replaced, so it should be
+    /// taken once the bytes before the point where processing resumes are
+    /// [finished](AhoCorasickReplacer::finish).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, AhoCorasickReplacer, AhoCorasickReplacerBuilder,
+    ///     Selection,
+    /// };
+    ///
+    /// let ac = AhoCorasick::new(&["cat"]).unwrap();
+    /// let replacer = || {
+    ///     ac.replacer_with_alternatives(
+    ///         &AhoCorasickReplacerBuilder::new(),
+    ///         [&["kitten", "tabby", "tomcat"]],
+    ///         Selection::Random { seed: 42 },
+    ///     )
+    /// };
+    /// let run = |r: &mut AhoCorasickReplacer, chunk: &[u8]| {
+    ///     let mut out = r.replace(chunk).unwrap().to_vec();
+    ///     out.extend_from_slice(r.finish().unwrap());
+    ///     out
+    /// };
+    /// let expected = run(&mut replacer()?, b"cat cat cat, cat cat cat");
+    ///
+    /// // The stream is processed in two runs, the second one resuming from
+    /// // the state the first one saved.
+    /// let mut first = replacer()?;
+    /// let mut out = run(&mut first, b"cat cat cat, ");
+    /// let state = first.selection_state().unwrap();
+    /// let mut second = replacer()?;
+    /// second.set_selection_state(&state)?;
+    /// out.extend_from_slice(&run(&mut second, b"cat cat cat"));
+    /// assert_eq!(expected, out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn selection_state(&self) -> Option<SelectionState> {
+        self.alternatives.as_ref().map(|alternatives| alternatives.state())
+    }
+
+    /// Set the state of the selection of alternative replacements, as
+    /// returned by [`AhoCorasickReplacer::selection_state`], so that the
+    /// next replacements are the ones that followed when it was taken.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when this replacer wasn't built with
+    /// alternatives, or when the state is for a different kind of
+    /// selection or a different number of alternatives, in which case the
+    /// selection is left as is.
+    pub fn set_selection_state(
+        &mut self,
+        state: &SelectionState,
+    ) -> Result<(), ReplacerError> {
+        let set = match self.alternatives {
+            None => false,
+            Some(ref mut alternatives) => alternatives.set_state(state),
+        };
+        if !set {
+            return Err(ReplacerError::new(
+                ReplacerErrorKind::SelectionMismatch,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the longest enabled pattern matched by the current state, or
+    /// `None` if all of them are disabled. Patterns with the same length are
+    /// ordered by pattern ID. Empty patterns are never returned, since they
+    /// are either ignored or inserted by `run_empty`.
+    ///
+    /// When `below` is given, only the patterns that come after it in this
+    /// order are considered, so that the patterns of a state may be tried
+    /// one after the other.
+    fn enabled_match(
+        &self,
+        aut: AutomatonImpl<'_>,
+        below: Option<PatternID>,
+    ) -> Option<PatternID> {
+        // Without empty patterns, the first pattern of a state is its
+        // longest one.
+        if self.disabled.is_empty()
+            && self.recent.is_none()
+            && below.is_none()
+            && !aut.is_match(self.start)
+        {
+            let pid = aut.match_pattern(self.sid, 0);
+            return Some(pid).filter(|&pid| self.starts_record(aut, pid));
+        }
+        let key =
+            |pid: PatternID| (core::cmp::Reverse(aut.pattern_len(pid)), pid);
+        let mut longest: Option<PatternID> = None;
+        for index in 0..aut.match_len(self.sid) {
+            let pid = aut.match_pattern(self.sid, index);
+            if aut.pattern_len(pid) == 0
+                || !self.is_enabled(pid)
+                || !self.starts_record(aut, pid)
+                || !self.follows_non_word(aut.pattern_len(pid))
+                || below.map_or(false, |b| key(pid) <= key(b))
+            {
+                continue;
+            }
+            if longest.map_or(true, |l| key(pid) < key(l)) {
+                longest = Some(pid);
+            }
+        }
+        longest
+    }
+
+    /// Decide which match the current state is, given the bytes that follow
+    /// it, by trying the enabled patterns it matches from the longest one
+    /// until one of them satisfies its lookahead, if it has one, and is
+    /// followed by a non-word byte when it must be. Only the
+    /// patterns that come after `below` are tried, as with `enabled_match`.
+    ///
+    /// When `end` is true, no bytes will follow the ones given, so a match
+    /// is never pending.
+    fn decide_match(
+        &self,
+        aut: AutomatonImpl<'_>,
+        after: &[u8],
+        mut below: Option<PatternID>,
+        end: bool,
+    ) -> Decided {
+        loop {
+            let pid = match self.enabled_match(aut, below) {
+                None => return Decided::Nothing,
+                Some(pid) => pid,
+            };
+            match self.decide_after(pid, 0, after, end) {
+                Some(true) => return Decided::Accept(pid),
+                None => return Decided::Pending(pid),
+                // A rejected match is ignored, as if its pattern did not match here
+                Some(false) => below = Some(pid),
+            }
+        }
+    }
+
+    /// Run the chunk through the automaton, giving the replaced bytes to the
+    /// given output.
+    ///
+    /// The bytes of a potential match are always the most recent bytes
+    /// seen, so they are tracked as the bytes carried over from previous
+    /// chunks in the potential buffer, followed by `chunk[pot..i]`. Bytes of
+    /// this chunk that turn out not to be part of a match precede them, and
+    /// are written in runs of `chunk[verbatim..pot]`. Such a run is only
+    /// ever non-empty once the potential buffer has been emptied, which
+    /// keeps the output in order.
+    fn run<O: Output>(
+        &mut self,
+        chunk: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        if let Some(mut pending) = self.pending.take() {
+            let pid = pending.pattern_id;
+            match self.decide_after(pid, pending.seen, chunk, false) {
+                None => {
+                    pending.seen += chunk.len();
+                    self.potential_buffer.extend(chunk);
+                    self.pending = Some(pending);
+                    return Ok(());
+                }
+                Some(accept) => {
+                    self.decide_pending(pending, accept, false, out)?;
+                    return self.run(chunk, out);
+                }
+            }
+        }
+        if !self.matching_enabled {
+            // Bytes held back before matching was disabled go first.
+            self.consume(chunk);
+            write_front(&mut self.potential_buffer, usize::MAX, out)?;
+            return out.write(chunk);
+        }
+        // The automaton is shared, so that the state of the replacer can be
+        // updated while it's borrowed.
+        let shared = Arc::clone(&self.aut);
+        let aut = shared.as_ref().coerce_concrete(self.kind);
+        if let Some(mut resolving) = self.resolving.take() {
+            let result = self.run_resolving(&mut resolving, aut, chunk, out);
+            self.resolving = Some(resolving);
+            return result;
+        }
+        if let Some(mut leftmost) = self.leftmost.take() {
+            let result = self.run_leftmost(&mut leftmost, aut, chunk, out);
+            self.leftmost = Some(leftmost);
+            return result;
+        }
+        if self.anchored.is_anchored() {
+            self.anchored_empty(aut, out)?;
+        } else if let Some(pid) = self.empty_match(aut) {
+            return self.run_empty(pid, chunk, out);
+        }
+        if aut.is_dead(self.sid) {
+            // Only an anchored search ever ends, and no match is replaced
+            // until the next record.
+            self.consume(chunk);
+            write_front(&mut self.potential_buffer, usize::MAX, out)?;
+            return out.write(chunk);
+        }
+        let (mut verbatim, mut pot) = (0, 0);
+        // An anchored search never skips, since its matches must begin at
+        // the start of a record.
+        let prefilter = match self.anchored {
+            Anchored::No => aut.prefilter(),
+            Anchored::Yes => None,
+        };
+        // A match that begins in the last `max_pattern_len - 1` bytes may
+        // end in the next chunk, which the prefilter can't see, so no byte
+        // is skipped from there on.
+        let bound = chunk
+            .len()
+            .saturating_sub(aut.max_pattern_len().saturating_sub(1));
+        let mut at = 0;
+        while at < chunk.len() {
+            if let Some(pre) = prefilter {
+                if at < bound
+                    && aut.is_start(self.sid)
+                    && self.potential_buffer.is_empty()
+                {
+                    // No match begins before the candidate, so the bytes up
+                    // to it are written as is, as they would be after going
+                    // through the automaton. The state they lead to may
+                    // differ from the start state, but only by the prefix
+                    // of a pattern that doesn't match, so the matches found
+                    // from the candidate on are the same.
+                    let span = Span::from(at..chunk.len());
+                    let to = match pre.find_in(chunk, span).into_option() {
+                        None => bound,
+                        Some(start) => core::cmp::min(start, bound),
+                    };
+                    if to > at {
+                        self.consume(&chunk[at..to]);
+                        self.in_run = false;
+                        pot = to;
+                        at = to;
+                        continue;
+                    }
+                }
+            }
+            let (i, byte) = (at, chunk[at]);
+            at += 1;
+            self.consume(&chunk[i..i + 1]);
+            // SAFETY: 'self.sid' is only ever set to IDs returned by the
+            // automaton this replacer was created from, and so is always
+            // valid.
+            self.sid = unsafe {
+                aut.next_state_unchecked(self.anchored, self.sid, byte)
+            };
+            if aut.is_start(self.sid) || aut.is_dead(self.sid) {
+                // No potential replacements: at this point the potential
+                // buffer is discarded (written), along with this byte
+                self.in_run = false;
+                write_front(&mut self.potential_buffer, usize::MAX, out)?;
+                pot = i + 1;
+                continue;
+            }
+            if !aut.is_match(self.sid) {
+                continue;
+            }
+            let after = &chunk[i + 1..];
+            let pattern_id = match self.decide_match(aut, after, None, false) {
+                Decided::Accept(pattern_id) => pattern_id,
+                Decided::Nothing => continue,
+                Decided::Pending(pattern_id) => {
+                    // The next chunks decide the match, so the rest of this chunk is held back along with it,
+                    // and is only searched once the match is decided
+                    out.write(&chunk[verbatim..pot])?;
+                    self.potential_buffer.extend(&chunk[pot..]);
+                    let seen = after.len();
+                    self.pending = Some(PendingLookahead { pattern_id, seen });
+                    return Ok(());
+                }
+            };
+            let pattern_len = aut.pattern_len(pattern_id);
+            // Either we followed a potential word all the way down, or we jumped to a different branch following the suffix link
+            // In the second case, we need to discard (write away) first part of the potential buffer, as it will be bigger than the max match,
+            // keeping as new potential the last part containing the amount of bytes equal to the new state node depth (equal to the pattern_len)
+            let len = self.potential_buffer.len() + (i + 1 - pot);
+            if len > pattern_len {
+                self.end_run_before(pattern_len);
+                let excess = len - pattern_len;
+                let carried = self.potential_buffer.len();
+                write_front(&mut self.potential_buffer, excess, out)?;
+                pot += excess.saturating_sub(carried);
+            }
+
+            if !self.dedup_accepts(pattern_id, pattern_len) {
+                // A suppressed match is written as is, as if it did not match
+                self.in_run = false;
+                write_front(&mut self.potential_buffer, usize::MAX, out)?;
+                pot = i + 1;
+                self.sid = self.restart_state();
+                continue;
+            }
+
+            // Replacement is given by the automaton node, so we only need to clear the potential buffer
+            out.write(&chunk[verbatim..pot])?;
+            let mut matched = Vec::new();
+            if O::MATCHED {
+                matched.extend(self.potential_buffer.iter());
+                matched.extend_from_slice(&chunk[pot..i + 1]);
+            }
+            self.potential_buffer.clear();
+            verbatim = i + 1;
+            pot = i + 1;
+            self.write_replacement(pattern_id, pattern_len, &matched, out)?;
+        }
+        // Now (unless chunk was empty), either the bytes have been written, or they are in the potential buffer awaiting for the next chunk before being written
+        // In both cases, all of them are considered "written" from the standpoint of the caller. The output might be empty even if the chunk wasn't,
+        // either because the chunk is entirely the start of a potential match, or because it matches a word which has the empty string replacement
+        out.write(&chunk[verbatim..pot])?;
+        self.potential_buffer.extend(&chunk[pot..]);
+        Ok(())
+    }
+
+    /// Run the chunk through the automaton when an overlap policy is set.
+    ///
+    /// Every match of an enabled pattern is given to the resolver, once its
+    /// lookahead is decided if it has one, and the search never restarts.
+    /// The bytes are held back until the resolver tells that the ones
+    /// before some offset are decided.
+    fn run_resolving<O: Output>(
+        &mut self,
+        r: &mut Resolving,
+        aut: AutomatonImpl<'_>,
+        chunk: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        // The output held back by an error of the last call goes first.
+        write_front(&mut self.potential_buffer, usize::MAX, out)?;
+        if r.held.is_empty() {
+            r.held_start = self.position;
+        }
+        if r.at_start {
+            r.at_start = false;
+            self.add_matches(r, aut);
+        }
+        let max_pattern_len = aut.max_pattern_len();
+        for &byte in chunk.iter() {
+            let mut i = 0;
+            while i < r.undecided.len() {
+                let (m, seen) = r.undecided[i];
+                match self.decide_after(m.pattern(), seen, &[byte], false) {
+                    None => {
+                        r.undecided[i].1 += 1;
+                        i += 1;
+                        continue;
+                    }
+                    Some(true) => r.resolver.add(m),
+                    Some(false) => {}
+                }
+                r.undecided.remove(i);
+            }
+            self.consume(&[byte]);
+            r.held.push_back(byte);
+            // SAFETY: 'self.sid' is only ever set to IDs returned by the
+            // automaton this replacer was created from, and so is always
+            // valid.
+            self.sid = unsafe {
+                aut.next_state_unchecked(self.anchored, self.sid, byte)
+            };
+            self.add_matches(r, aut);
+            if !r.resolver.is_empty() {
+                // Matches found from here on end after this byte.
+                let bound =
+                    (self.position + 1).saturating_sub(max_pattern_len);
+                self.write_resolved(r, bound, out)?;
+            }
+        }
+        let bound = (self.position + 1).saturating_sub(max_pattern_len);
+        self.write_resolved(r, bound, out)
+    }
+
+    /// Give the matches of the enabled patterns that end at the current
+    /// position in the current state to the resolver, or keep them until
+    /// their lookahead is decided.
+    fn add_matches(&self, r: &mut Resolving, aut: AutomatonImpl<'_>) {
+        if !aut.is_match(self.sid) {
+            return;
+        }
+        for index in 0..aut.match_len(self.sid) {
+            let pid = aut.match_pattern(self.sid, index);
+            let len = aut.pattern_len(pid);
+            if !self.is_enabled(pid)
+                || (len == 0 && self.ignore_empty)
+                || !self.starts_record(aut, pid)
+                || !self.follows_non_word(len)
+            {
+                continue;
+            }
+            let m = Match::new(pid, self.position - len..self.position);
+            if self.lookahead(pid).is_none() && self.recent.is_none() {
+                r.resolver.add(m);
+            } else {
+                r.undecided.push((m, 0));
+            }
+        }
+    }
+
+    /// Write the matches decided to be replaced, given that every match
+    /// found from now on starts at or after `bound`, along with the bytes
+    /// before them and the decided bytes after them.
+    fn write_resolved<O: Output>(
+        &mut self,
+        r: &mut Resolving,
+        bound: usize,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        let undecided = r.undecided.iter().map(|&(m, _)| m.start());
+        let bound = undecided.fold(bound, core::cmp::min);
+        let settled = r.resolver.resolve(bound, &mut r.replaced);
+        for i in 0..r.replaced.len() {
+            let m = r.replaced[i];
+            if m.start() > r.held_start {
+                self.in_run = false;
+                write_front(&mut r.held, m.start() - r.held_start, out)?;
+                r.held_start = m.start();
+            }
+            if !self.dedup.as_mut().map_or(true, |dedup| dedup.accept(&m)) {
+                // A suppressed match is written as is, along with the bytes
+                // after it.
+                continue;
+            }
+            // When coalescing, a match immediately following the previous
+            // replacement is part of the same run
+            let replaced = !(self.coalesce && self.in_run);
+            let choice =
+                if replaced { Some(self.choose(m.pattern())) } else { None };
+            if let Some(choice) = choice {
+                let mut matched = Vec::new();
+                if O::MATCHED {
+                    matched.extend(r.held.range(..m.len()));
+                }
+                let replace_with = Arc::clone(&self.replace_with);
+                let fixed = &replace_with[choice];
+                let replacement =
+                    out.replacement(m.pattern(), &matched, fixed);
+                out.write(&replacement)?;
+            }
+            if let Some(ref mut decisions) = self.decisions {
+                decisions.push(Decision { mat: m, choice });
+            }
+            self.in_run = true;
+            r.held.drain(..m.len());
+            r.held_start = m.end();
+        }
+        r.replaced.clear();
+        let decided = settled.saturating_sub(r.held_start);
+        let n = core::cmp::min(decided, r.held.len());
+        if n > 0 {
+            self.in_run = false;
+            write_front(&mut r.held, n, out)?;
+            r.held_start += n;
+        }
+        Ok(())
+    }
+
+    /// Run the chunk through the automaton with leftmost-longest semantics.
+    ///
+    /// The bytes held back are searched like a haystack that grows with
+    /// every chunk, and each search restarts from the start state after
+    /// the match reported by the previous one, as with
+    /// [`AhoCorasick::find_iter`].
+    fn run_leftmost<O: Output>(
+        &mut self,
+        l: &mut Leftmost,
+        aut: AutomatonImpl<'_>,
+        chunk: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        // Bytes held back before matching was toggled go first.
+        write_front(&mut self.potential_buffer, usize::MAX, out)?;
+        self.consume(chunk);
+        l.held.extend(chunk);
+        self.search_leftmost(l, aut, false, out)
+    }
+
+    /// Search the bytes held back with leftmost-longest semantics, writing
+    /// them once they're decided, along with the replacements of the
+    /// matches reported.
+    ///
+    /// When `end` is true, no bytes follow the ones held back, so every
+    /// search ends and nothing is held back anymore.
+    fn search_leftmost<O: Output>(
+        &mut self,
+        l: &mut Leftmost,
+        aut: AutomatonImpl<'_>,
+        end: bool,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        loop {
+            let mut dead = false;
+            while l.scanned < self.position {
+                let byte = l.held[l.scanned - l.held_start];
+                l.scanned += 1;
+                // SAFETY: 'self.sid' is only ever set to IDs returned by the
+                // automaton this replacer was created from, and so is always
+                // valid.
+                self.sid = unsafe {
+                    aut.next_state_unchecked(Anchored::No, self.sid, byte)
+                };
+                if aut.is_dead(self.sid) {
+                    dead = true;
+                    break;
+                }
+                if aut.is_match(self.sid) {
+                    let pid = aut.match_pattern(self.sid, 0);
+                    let len = aut.pattern_len(pid);
+                    if len > 0 || l.empty.is_some() {
+                        let span = l.scanned - len..l.scanned;
+                        l.last = Some(Match::new(pid, span));
+                    }
+                }
+            }
+            if !dead && !end {
+                // A match found later ends after the last byte, and the
+                // one reported begins at or before the last match found.
+                // Bytes that weren't given yet can't be written, which
+                // only matters when no pattern is longer than zero bytes.
+                let bound = core::cmp::min(
+                    l.scanned,
+                    (l.scanned + 1).saturating_sub(aut.max_pattern_len()),
+                );
+                let bound = match l.last {
+                    Some(m) => core::cmp::min(bound, m.start()),
+                    None if l.ignored_empty => {
+                        core::cmp::min(bound, l.search_start + 1)
+                    }
+                    None => bound,
+                };
+                return self.write_held(l, bound, out);
+            }
+            let next = match l.last.take() {
+                // An empty match at the end of the previous match is
+                // skipped, as in a non-overlapping search.
+                Some(m)
+                    if m.is_empty() && l.last_match_end == Some(m.end()) =>
+                {
+                    m.start() + 1
+                }
+                Some(m) => {
+                    self.replace_leftmost(l, m, out)?;
+                    m.end()
+                }
+                // Only a search for patterns whose empty matches are
+                // ignored ends without a match before the end.
+                None if dead => l.search_start + 1,
+                None => self.position + 1,
+            };
+            self.sid = self.start;
+            if next > self.position {
+                self.write_held(l, self.position, out)?;
+                l.start_search(self.position);
+                return Ok(());
+            }
+            self.write_held(l, next, out)?;
+            l.start_search(next);
+        }
+    }
+
+    /// Write the bytes held back before the given offset as is.
+    fn write_held<O: Output>(
+        &mut self,
+        l: &mut Leftmost,
+        upto: usize,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        if upto > l.held_start {
+            self.in_run = false;
+            write_front(&mut l.held, upto - l.held_start, out)?;
+            l.held_start = upto;
+        }
+        Ok(())
+    }
+
+    /// Replace the given match, reported by a search with leftmost-longest
+    /// semantics, once the bytes before it are written.
+    fn replace_leftmost<O: Output>(
+        &mut self,
+        l: &mut Leftmost,
+        m: Match,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        self.write_held(l, m.start(), out)?;
+        l.last_match_end = Some(m.end());
+        if !self.dedup.as_mut().map_or(true, |dedup| dedup.accept(&m)) {
+            // A suppressed match is written as is, and the next search
+            // still begins after it.
+            return self.write_held(l, m.end(), out);
+        }
+        // When coalescing, a match immediately following the previous
+        // replacement is part of the same run
+        let replaced = !(self.coalesce && self.in_run);
+        let choice =
+            if replaced { Some(self.choose(m.pattern())) } else { None };
+        if let Some(choice) = choice {
+            let mut matched = Vec::new();
+            if O::MATCHED {
+                matched.extend(l.held.range(..m.len()));
+            }
+            let replace_with = Arc::clone(&self.replace_with);
+            let fixed = &replace_with[choice];
+            let replacement = out.replacement(m.pattern(), &matched, fixed);
+            out.write(&replacement)?;
+        }
+        if let Some(ref mut decisions) = self.decisions {
+            decisions.push(Decision { mat: m, choice });
+        }
+        self.in_run = true;
+        l.held.drain(..m.len());
+        l.held_start = m.end();
+        Ok(())
+    }
+
+    /// Replace the match of the empty pattern at the start of the record, if
+    /// the search is anchored and nothing was searched since it started.
+    ///
+    /// Since the search stops at its first match, this is the only match of
+    /// the record, and no more bytes are searched until the next one.
+    fn anchored_empty<O: Output>(
+        &mut self,
+        aut: AutomatonImpl<'_>,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        if self.sid != self.start {
+            return Ok(());
+        }
+        if let Some(pid) = self.empty_match(aut) {
+            write_front(&mut self.potential_buffer, usize::MAX, out)?;
+            self.write_empty(pid, out)?;
+            self.sid = self.restart_state();
+        }
+        Ok(())
+    }
+
+    /// Returns true unless the search is anchored and a match of the given
+    /// pattern ending at the current position begins after the start of the
+    /// record.
+    ///
+    /// An anchored search doesn't follow failure transitions, but the states
+    /// it goes through still carry the matches copied from the states their
+    /// failure transitions lead to, which are the ones this rules out.
+    fn starts_record(&self, aut: AutomatonImpl<'_>, pid: PatternID) -> bool {
+        !self.anchored.is_anchored()
+            || aut.pattern_len(pid) == self.position - self.record_start
+    }
+
+    /// Run the chunk through the automaton, and then give up on the
+    /// potential match if the bytes held back for it exceed the limit set
+    /// by [`PendingLimit::Flush`].
+    fn run_bounded<O: Output>(
+        &mut self,
+        chunk: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        let result = self
+            .run_to_quit(chunk, out)
+            .and_then(|()| self.flush_over_limit(out));
+        // The output failed somewhere in the chunk, and the bytes after that
+        // point were neither written nor held back.
+        self.poisoned |= result.is_err();
+        result
+    }
+
+    /// Run the chunk through the automaton up to its first quit byte, if
+    /// any.
+    ///
+    /// The stream then ends before the quit byte: the bytes held back are
+    /// decided and written, and the search restarts from it as at the start
+    /// of a record. The error returned by `check_stopped` tells where.
+    fn run_to_quit<O: Output>(
+        &mut self,
+        chunk: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        let at = match self.quit_position(chunk) {
+            None => return self.run(chunk, out),
+            Some(at) => at,
+        };
+        self.run(&chunk[..at], out)?;
+        self.settle_to(out)?;
+        write_front(&mut self.potential_buffer, usize::MAX, out)?;
+        self.restart();
+        self.stopped = Some(MatchError::quit(chunk[at], self.position));
+        Ok(())
+    }
+
+    /// Returns the position of the first quit byte in the chunk, if any.
+    fn quit_position(&self, chunk: &[u8]) -> Option<usize> {
+        let quit = self.quit.as_ref()?;
+        chunk.iter().position(|&byte| quit.contains(byte))
+    }
+
+    /// Give up on the potential match if the bytes held back for it exceed
+    /// the limit set by [`PendingLimit::Flush`].
+    fn flush_over_limit<O: Output>(
+        &mut self,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        if let Some(PendingLimit::Flush(limit)) = self.pending_limit {
+            if self.pending_len() > limit {
+                self.give_up(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the last chunk stopped at a quit byte, or if the
+    /// bytes held back exceed the limit set by [`PendingLimit::Error`].
+    fn check_stopped(&mut self) -> Result<(), MatchError> {
+        if let Some(err) = self.stopped.take() {
+            // As with the pending limit, the output of the chunk goes first.
+            let output = core::mem::take(&mut self.buffer);
+            self.hold_front(output);
+            return Err(err);
+        }
+        self.check_pending_limit()
+    }
+
+    /// Returns an error if the bytes held back exceed the limit set by
+    /// [`PendingLimit::Error`].
+    fn check_pending_limit(&mut self) -> Result<(), MatchError> {
+        let limit = match self.pending_limit {
+            Some(PendingLimit::Error(limit)) => Some(limit),
+            _ => None,
+        };
+        // The cap set by the memory limits behaves in the same way.
+        let limit = match (limit, self.limits.pending) {
+            (Some(limit), Some(cap)) => Some(core::cmp::min(limit, cap)),
+            (limit, cap) => limit.or(cap),
+        };
+        match limit {
+            Some(limit) if self.pending_len() > limit => {
+                // The output of the chunk goes first once the stream is
+                // finished, so that none of it is lost.
+                let output = core::mem::take(&mut self.buffer);
+                self.hold_front(output);
+                Err(MatchError::pending_limit_exceeded(limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the most bytes the output of a single call may have.
+    fn output_limit(&self) -> usize {
+        self.limits.output.unwrap_or(usize::MAX)
+    }
+
+    /// Returns the number of bytes held back until they are decided.
+    fn pending_len(&self) -> usize {
+        let held = self.resolving.as_ref().map_or(0, |r| r.held.len());
+        let searched = self.leftmost.as_ref().map_or(0, |l| l.held.len());
+        self.potential_buffer.len() + held + searched
+    }
+
+    /// Write the bytes held back for the potential match as is, as if they
+    /// did not match, and search the next bytes from the start state.
+    fn give_up<O: Output>(&mut self, out: &mut O) -> Result<(), O::Error> {
+        // A match waiting for its lookahead is decided as if the stream
+        // ended here, and so are the matches held for an overlap policy.
+        self.settle_to(out)?;
+        write_front(&mut self.potential_buffer, usize::MAX, out)?;
+        self.in_run = false;
+        // Only the replacements written before the bytes held back may
+        // exceed the limit in the start state, which doesn't give up on
+        // anything.
+        if self.sid != self.start {
+            self.sid = self.restart_state();
+        }
+        Ok(())
+    }
+
+    /// Returns the state the search continues from once a match is replaced
+    /// or suppressed.
+    ///
+    /// An anchored search ends with its first match, so it continues from
+    /// the dead state, which is the state with ID `0` in every automaton of
+    /// this crate, until the next record starts.
+    fn restart_state(&self) -> StateID {
+        if self.anchored.is_anchored() {
+            StateID::ZERO
+        } else {
+            self.start
+        }
+    }
+
+    /// Returns the enabled empty pattern with the lowest ID, if empty
+    /// matches aren't ignored.
+    fn empty_match(&self, aut: AutomatonImpl<'_>) -> Option<PatternID> {
+        // Only the start state of an automaton with an empty pattern is a
+        // match state.
+        if self.ignore_empty || !aut.is_match(self.start) {
+            return None;
+        }
+        (0..aut.match_len(self.start))
+            .map(|index| aut.match_pattern(self.start, index))
+            .filter(|&pid| aut.pattern_len(pid) == 0)
+            .find(|&pid| self.is_enabled(pid))
+    }
+
+    /// Run the chunk while the given empty pattern is enabled. It matches
+    /// before every byte, and since the search restarts after each of its
+    /// matches, no other pattern ever matches.
+    fn run_empty<O: Output>(
+        &mut self,
+        pid: PatternID,
+        chunk: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        // Bytes held back before the pattern was enabled go first.
+        write_front(&mut self.potential_buffer, usize::MAX, out)?;
+        self.in_run = false;
+        self.sid = self.start;
+        for i in 0..chunk.len() {
+            self.write_empty(pid, out)?;
+            self.consume(&chunk[i..i + 1]);
+            out.write(&chunk[i..i + 1])?;
+        }
+        Ok(())
+    }
+
+    /// Write the replacement of the match of the given empty pattern at the
+    /// current position, unless the deduper suppresses it.
+    fn write_empty<O: Output>(
+        &mut self,
+        pid: PatternID,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        if !self.dedup_accepts(pid, 0) {
+            return Ok(());
+        }
+        let choice = self.choose(pid);
+        let replace_with = Arc::clone(&self.replace_with);
+        let replacement = out.replacement(pid, &[], &replace_with[choice]);
+        out.write(&replacement)?;
+        if let Some(ref mut decisions) = self.decisions {
+            let mat = Match::new(pid, self.position..self.position);
+            decisions.push(Decision { mat, choice: Some(choice) });
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the replacement of a match of the given pattern
+    /// in the replacements of this replacer, selecting one of the
+    /// alternatives of the pattern if it has several.
+    ///
+    /// This is only called once a match is decided to be replaced, so it
+    /// also counts the replacement.
+    fn choose(&mut self, pid: PatternID) -> usize {
+        self.stats.add(pid);
+        match self.alternatives {
+            None => pid.as_usize(),
+            Some(ref mut alternatives) => alternatives.select(pid),
+        }
+    }
+
+    /// Returns the lookahead of the given pattern, if it has one.
+    fn lookahead(&self, pid: PatternID) -> Option<Lookahead> {
+        self.lookaheads.as_ref().and_then(|lookaheads| lookaheads[pid])
+    }
+
+    /// Advance the current position past the given bytes, remembering them
+    /// when matches must be bounded by non-word bytes.
+    fn consume(&mut self, bytes: &[u8]) {
+        self.position += bytes.len();
+        if let Some(ref mut recent) = self.recent {
+            recent.push(bytes);
+        }
+    }
+
+    /// Returns true unless matches must be bounded by non-word bytes and
+    /// the match of a pattern with the given length that ends at the
+    /// current position is preceded by a word byte.
+    fn follows_non_word(&self, pattern_len: usize) -> bool {
+        match self.recent {
+            None => true,
+            Some(ref recent) => {
+                !recent.get(pattern_len).map_or(false, is_word_byte)
+            }
+        }
+    }
+
+    /// Decide whether the match of the given pattern that ends at the
+    /// current position is accepted by the bytes after it, given the `seen`
+    /// bytes after it that didn't decide it followed by the bytes in `more`.
+    /// The match must satisfy its lookahead, if it has one, and be followed
+    /// by a non-word byte when it must be.
+    ///
+    /// This returns `None` if more bytes are needed, unless `end` is true,
+    /// in which case no bytes will follow the ones given.
+    fn decide_after(
+        &self,
+        pid: PatternID,
+        seen: usize,
+        more: &[u8],
+        end: bool,
+    ) -> Option<bool> {
+        // The first byte after the match decides whether it's followed by
+        // a non-word byte, so it was accepted if any byte was seen.
+        if self.recent.is_some() && seen == 0 {
+            match more.first() {
+                Some(&byte) if is_word_byte(byte) => return Some(false),
+                None if !end => return None,
+                _ => {}
+            }
+        }
+        match self.lookahead(pid) {
+            None => Some(true),
+            Some(lookahead) => match lookahead.decide(seen, more) {
+                None if end => Some(false),
+                decided => decided,
+            },
+        }
+    }
+
+    /// Returns true if the deduper, if any, accepts the match of the given
+    /// pattern that ends at the current position.
+    fn dedup_accepts(
+        &mut self,
+        pattern_id: PatternID,
+        pattern_len: usize,
+    ) -> bool {
+        let span = self.match_span(pattern_len);
+        match self.dedup {
+            None => true,
+            Some(ref mut dedup) => dedup.accept(&Match::new(pattern_id, span)),
+        }
+    }
+
+    /// End the current run of matches, if any, unless the match of a
+    /// pattern with the given length that ends at the current position
+    /// begins where the last replacement ended. The bytes written before it
+    /// are then the bytes of that replacement held back as context.
+    fn end_run_before(&mut self, pattern_len: usize) {
+        if self.match_span(pattern_len).start > self.replaced_end {
+            self.in_run = false;
+        }
+    }
+
+    /// Returns the span of the match of a pattern with the given length that
+    /// ends at the current position.
+    ///
+    /// With [`AhoCorasickReplacerBuilder::replacement_context`], a match may
+    /// begin with the bytes of the previous replacement, so its span only
+    /// covers the bytes of the input after that replacement.
+    fn match_span(&self, pattern_len: usize) -> core::ops::Range<usize> {
+        let start = self.position.saturating_sub(pattern_len);
+        core::cmp::max(start, self.replaced_end)..self.position
+    }
+
+    /// Write the replacement of the match of the given pattern that ends at
+    /// the current position, once every byte before it has been written, and
+    /// restart the search.
+    fn write_replacement<O: Output>(
+        &mut self,
+        pattern_id: PatternID,
+        pattern_len: usize,
+        matched: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        // When coalescing, a match immediately following the previous replacement is part of the same run,
+        // which has already been replaced
+        // A match that begins with the bytes of the previous replacement
+        // rewrites them, so it's never part of the same run.
+        let span = self.match_span(pattern_len);
+        let rewrites = span.end - span.start < pattern_len;
+        let replaced = !(self.coalesce && self.in_run) || rewrites;
+        let choice =
+            if replaced { Some(self.choose(pattern_id)) } else { None };
+        if let Some(ref mut decisions) = self.decisions {
+            let mat = Match::new(pattern_id, span);
+            decisions.push(Decision { mat, choice });
+        }
+        self.in_run = true;
+        self.replaced_end = self.position;
+        // Reset the state after a replacement
+        self.sid = self.restart_state();
+        let choice = match choice {
+            None => return Ok(()),
+            Some(choice) => choice,
+        };
+        let replace_with = Arc::clone(&self.replace_with);
+        let fixed = &replace_with[choice];
+        let replacement = out.replacement(pattern_id, matched, fixed);
+        // An anchored search ends with its match, so the replacement can't
+        // begin another one.
+        if self.replacement_context && !self.anchored.is_anchored() {
+            self.write_context(&replacement, out)
+        } else {
+            out.write(&replacement)
+        }
+    }
+
+    /// Write the given replacement by running it through the automaton from
+    /// the start state, so that the search resumes from the state it leads
+    /// to.
+    ///
+    /// Matches that end within the replacement are ignored, since the
+    /// replacement is never searched for its own sake. Its last bytes may
+    /// still begin a match that ends in the input given next, so they're
+    /// held back in the potential buffer like the start of any other
+    /// potential match. Such a match needs at least one byte of input, so
+    /// no more than `max_pattern_len - 1` bytes are ever held back.
+    fn write_context<O: Output>(
+        &mut self,
+        replacement: &[u8],
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        let shared = Arc::clone(&self.aut);
+        let aut = shared.as_ref().coerce_concrete(self.kind);
+        // The number of bytes that can't begin a match ending in the input.
+        let mut written = 0;
+        for (i, &byte) in replacement.iter().enumerate() {
+            // SAFETY: 'self.sid' is only ever set to IDs returned by the
+            // automaton this replacer was created from, and so is always
+            // valid.
+            self.sid = unsafe {
+                aut.next_state_unchecked(Anchored::No, self.sid, byte)
+            };
+            if aut.is_start(self.sid) {
+                written = i + 1;
+            }
+        }
+        let keep = aut.max_pattern_len().saturating_sub(1);
+        let held =
+            core::cmp::max(written, replacement.len().saturating_sub(keep));
+        self.potential_buffer.extend(&replacement[held..]);
+        out.write(&replacement[..held])
+    }
+
+    /// Replace the match waiting for its lookahead if it's accepted, or
+    /// decide which of the shorter patterns matched by its state replaces
+    /// it otherwise, and search the bytes held back after it.
+    ///
+    /// The bytes after the match are given to `run` again, which may hold
+    /// back a match of its own. Since that match ends among the bytes after
+    /// this one, this only ever recurses as many times as the number of
+    /// bytes a lookahead considers.
+    fn decide_pending<O: Output>(
+        &mut self,
+        pending: PendingLookahead,
+        accept: bool,
+        end: bool,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        let held = self.potential_buffer.len() - pending.seen;
+        let after: Vec<u8> = self.potential_buffer.drain(held..).collect();
+        let shared = Arc::clone(&self.aut);
+        let aut = shared.as_ref().coerce_concrete(self.kind);
+        let decided = if accept {
+            Decided::Accept(pending.pattern_id)
+        } else {
+            self.decide_match(aut, &after, Some(pending.pattern_id), end)
+        };
+        match decided {
+            Decided::Pending(pattern_id) => {
+                self.potential_buffer.extend(after.iter());
+                let seen = after.len();
+                self.pending = Some(PendingLookahead { pattern_id, seen });
+                return Ok(());
+            }
+            Decided::Accept(pattern_id) => {
+                let pattern_len = aut.pattern_len(pattern_id);
+                if held > pattern_len {
+                    self.end_run_before(pattern_len);
+                    let excess = held - pattern_len;
+                    write_front(&mut self.potential_buffer, excess, out)?;
+                }
+                if self.dedup_accepts(pattern_id, pattern_len) {
+                    let mut matched = Vec::new();
+                    if O::MATCHED {
+                        matched.extend(self.potential_buffer.iter());
+                    }
+                    self.potential_buffer.clear();
+                    self.write_replacement(
+                        pattern_id,
+                        pattern_len,
+                        &matched,
+                        out,
+                    )?;
+                } else {
+                    self.in_run = false;
+                    write_front(&mut self.potential_buffer, usize::MAX, out)?;
+                    self.sid = self.restart_state();
+                }
+            }
+            // Every match is ignored, so the search carries on from the
+            // state it ended in.
+            Decided::Nothing => {}
+        }
+        self.run(&after, out)
+    }
+
+    /// Decide the match waiting for its lookahead, if any, as if the stream
+    /// ended here, so that no bytes are held back for it anymore.
+    ///
+    /// The output of the bytes that were held back is kept at the front of
+    /// the potential buffer, which is always written before anything else.
+    fn settle(&mut self) {
+        let mut output = Vec::new();
+        match self.settle_to(&mut output) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        self.hold_front(output);
+    }
+
+    /// Decide the match waiting for its lookahead, if any, as `settle`
+    /// does, but give the output of the bytes that were held back to the
+    /// given output.
+    fn settle_to<O: Output>(&mut self, out: &mut O) -> Result<(), O::Error> {
+        while let Some(pending) = self.pending.take() {
+            // There are no more bytes to satisfy the lookahead, but the end
+            // of the stream counts as a non-word byte.
+            let accept =
+                self.decide_after(pending.pattern_id, pending.seen, &[], true)
+                    == Some(true);
+            self.decide_pending(pending, accept, true, out)?;
+        }
+        // An empty record still has the match of the empty pattern.
+        if self.anchored.is_anchored()
+            && self.resolving.is_none()
+            && self.matching_enabled
+        {
+            let shared = Arc::clone(&self.aut);
+            let aut = shared.as_ref().coerce_concrete(self.kind);
+            self.anchored_empty(aut, out)?;
+        }
+        if let Some(mut r) = self.resolving.take() {
+            let shared = Arc::clone(&self.aut);
+            let aut = shared.as_ref().coerce_concrete(self.kind);
+            if r.held.is_empty() {
+                r.held_start = self.position;
+            }
+            if r.at_start && self.matching_enabled {
+                r.at_start = false;
+                self.add_matches(&mut r, aut);
+            }
+            for (m, seen) in r.undecided.drain(..) {
+                if self.decide_after(m.pattern(), seen, &[], true)
+                    == Some(true)
+                {
+                    r.resolver.add(m);
+                }
+            }
+            let result = self.write_resolved(&mut r, usize::MAX, out);
+            self.resolving = Some(r);
+            result?;
+        }
+        // The bytes consumed while matching is disabled are never held.
+        let leftmost =
+            if self.matching_enabled { self.leftmost.take() } else { None };
+        if let Some(mut l) = leftmost {
+            let shared = Arc::clone(&self.aut);
+            let aut = shared.as_ref().coerce_concrete(self.kind);
+            let result = self.search_leftmost(&mut l, aut, true, out);
+            self.leftmost = Some(l);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Put the given output at the front of the potential buffer, which is
+    /// always written before anything else.
+    fn hold_front(&mut self, mut output: Vec<u8>) {
+        if !output.is_empty() {
+            output.extend(self.potential_buffer.drain(..));
+            self.potential_buffer = VecDeque::from(output);
+        }
+    }
+
+    /// Returns the end of the match waiting for its lookahead, if any.
+    fn pending_end(&self) -> Option<usize> {
+        match self.resolving {
+            // No match decided later ends before the first byte held back.
+            Some(ref r)
+                if !r.held.is_empty()
+                    || !r.resolver.is_empty()
+                    || !r.undecided.is_empty() =>
+            {
+                Some(r.held_start)
+            }
+            _ => match self.leftmost {
+                Some(ref l) if !l.held.is_empty() || l.last.is_some() => {
+                    Some(l.held_start)
+                }
+                _ => self.pending.map(|_| self.position),
+            },
+        }
+    }
+
+    /// Returns every byte held back whose fate is already decided, without
+    /// ending the stream, so that only the bytes that may still be part of
+    /// a match stay held back.
+    ///
+    /// [`AhoCorasickReplacer::replace`] may hold back more bytes than it
+    /// has to. For example, with the pattern `abcd`, the bytes `abcabc`
+    /// given in two chunks of `abc` are all held back, even though only the
+    /// last `abc` may still begin a match. Calling this between chunks
+    /// minimizes the latency of the output, and the bytes returned are
+    /// exactly the ones that the next calls would have returned first, so
+    /// the output of the stream is the same. Unlike
+    /// [`AhoCorasickReplacer::finish`], this doesn't decide a match waiting
+    /// for its lookahead, and the stream goes on with the next chunk.
+    ///
+    /// This takes time proportional to the length of the longest pattern,
+    /// times its logarithm.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the replacer is poisoned, or if the bytes
+    /// returned exceed the limit set by [`MemoryLimits::output`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["abcd"]).unwrap();
+    /// let mut replacer = ac.replacer(&["X"])?;
+    /// assert_eq!(b"", replacer.replace(b"abc")?);
+    /// assert_eq!(b"", replacer.replace(b"abc")?);
+    /// assert_eq!(b"abc", replacer.flush()?);
+    /// assert_eq!(b"", replacer.flush()?);
+    /// assert_eq!(b"X", replacer.replace(b"d")?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn flush(&mut self) -> Result<&[u8], MatchError> {
+        self.check_poisoned()?;
+        let mut buffer = core::mem::take(&mut self.buffer);
+        buffer.clear();
+        let limit = self.output_limit();
+        let result = self.flush_to(&mut Bounded { buf: &mut buffer, limit });
+        self.buffer = buffer;
+        // Nothing is removed from the potential buffer when the limit is
+        // exceeded, so the bytes are still held back and the replacer isn't
+        // poisoned.
+        result?;
+        Ok(&self.buffer)
+    }
+
+    /// Give the bytes held back whose fate is already decided to the given
+    /// output, as `flush` does.
+    fn flush_to<O: Output>(&mut self, out: &mut O) -> Result<(), O::Error> {
+        // The bytes held back for an overlap policy, or for leftmost-longest
+        // semantics, are held elsewhere, and are always decided otherwise.
+        // The potential buffer then only holds the output kept back by an
+        // error.
+        if !self.matching_enabled
+            || self.resolving.is_some()
+            || self.leftmost.is_some()
+        {
+            return write_front(&mut self.potential_buffer, usize::MAX, out);
+        }
+        // Every byte since the start of a record may begin an anchored
+        // match, until the search ends.
+        if self.anchored.is_anchored() {
+            return Ok(());
+        }
+        let seen = self.pending.map_or(0, |pending| pending.seen);
+        let searched = self.potential_buffer.len() - seen;
+        let undecided = match self.undecided_len(searched) {
+            None => return Ok(()),
+            Some(undecided) => undecided,
+        };
+        if undecided < searched {
+            // As when the bytes before a match are written, the run of
+            // matches ends unless a match may still begin where the last
+            // replacement ended.
+            self.end_run_before(undecided);
+            let decided = searched - undecided;
+            write_front(&mut self.potential_buffer, decided, out)?;
+        }
+        Ok(())
+    }
+
+    /// Returns how many of the first `searched` bytes of the potential
+    /// buffer, counting from the last one, may still begin a match, if it
+    /// can be told.
+    ///
+    /// Feeding the last bytes searched from the start state leads to the
+    /// current state once they include every byte that may still begin a
+    /// match, since the state only depends on the longest suffix of the
+    /// bytes searched that is a prefix of a pattern. So the shortest such
+    /// suffix is found by a binary search, which only ever returns a length
+    /// that leads to the current state.
+    fn undecided_len(&self, searched: usize) -> Option<usize> {
+        let aut = self.aut.as_ref().coerce_concrete(self.kind);
+        let leads_to_current = |n: usize| {
+            let mut sid = self.start;
+            for &byte in self.potential_buffer.range(searched - n..searched) {
+                // SAFETY: 'sid' is only ever set to IDs returned by the
+                // automaton this replacer was created from, and so is
+                // always valid.
+                sid = unsafe {
+                    aut.next_state_unchecked(self.anchored, sid, byte)
+                };
+            }
+            sid == self.sid
+        };
+        if leads_to_current(0) {
+            return Some(0);
+        }
+        let mut hi = core::cmp::min(searched, aut.max_pattern_len());
+        if !leads_to_current(hi) {
+            // The bytes searched don't explain the current state, such as
+            // when the search restarted after some of them.
+            return None;
+        }
+        let mut lo = 1;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if leads_to_current(mid) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        Some(hi)
+    }
+
+    /// Returns the potentially buffered bytes of the last chunk, once the
+    /// matches that end at the end of the stream are decided.
+    ///
+    /// A match is replaced as soon as its last byte is seen, so one that
+    /// ends with the last chunk was already replaced. What's left are the
+    /// bytes that could have started a longer match, and the matches
+    /// waiting for their lookahead, which are decided with no more bytes to
+    /// accept them. A rejected match gives way to the shorter matches that
+    /// end at the same position, and the bytes held back after it are
+    /// searched, as they would have been if the stream went on.
+    pub fn finish(&mut self) -> Result<&[u8], MatchError> {
+        self.check_poisoned()?;
+        let mut output = Vec::new();
+        let limit = self.output_limit();
+        self.finish_to(&mut Bounded { buf: &mut output, limit })?;
+        self.hold_finished(output)
+    }
+
+    /// Give every byte held back to the given output, once the matches
+    /// waiting for their lookahead are decided, followed by the empty match
+    /// at the end of the stream, if any.
+    fn finish_to<O: Output>(&mut self, out: &mut O) -> Result<(), O::Error> {
+        let result = self.finish_unchecked(out);
+        self.poisoned |= result.is_err();
+        result
+    }
+
+    /// Give every byte held back to the given output, as `finish_to` does,
+    /// without tracking whether the output failed.
+    fn finish_unchecked<O: Output>(
+        &mut self,
+        out: &mut O,
+    ) -> Result<(), O::Error> {
+        self.settle_to(out)?;
+        // Every byte left went through the automaton, and every match that
+        // ends in them was either replaced or rejected, so no replacement is
+        // missed by writing them as is.
+        write_front(&mut self.potential_buffer, usize::MAX, out)?;
+        // An empty pattern also matches at the end of the stream, unless
+        // the resolver already decided it.
+        let aut = self.aut.as_ref().coerce_concrete(self.kind);
+        // When anchored, it only matches there if the record is empty, and
+        // the match was already replaced when settling.
+        let empty = match self.resolving {
+            Some(_) => None,
+            // The search with leftmost-longest semantics already reported
+            // it when settling.
+            None if self.leftmost.is_some() => None,
+            None if self.anchored.is_anchored() => None,
+            None => self.empty_match(aut),
+        };
+        if let (Some(pid), true) = (empty, self.matching_enabled) {
+            if !self.empty_at_end {
+                self.write_empty(pid, out)?;
+                self.empty_at_end = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hold back the output of `finish_to` in the potential buffer, so that
+    /// calling `finish` again returns it again, and return it.
+    fn hold_finished(&mut self, output: Vec<u8>) -> Result<&[u8], MatchError> {
+        self.potential_buffer = VecDeque::from(output);
+        if self.potential_buffer.len() > 0 {
+            self.potential_buffer.make_contiguous();
+            Ok(self.potential_buffer.as_slices().0)
+        } else {
+            Ok(&self.buffer[..0])
+        }
+    }
+
+    /// Returns an iterator that replaces matches in each chunk yielded by
+    /// the given iterator, as if each chunk was given to
+    /// [`AhoCorasickReplacer::replace`].
+    ///
+    /// This is useful for sources that deliver their contents as an
+    /// iterator of chunks instead of a `std::io::Read` stream, such as paged
+    /// downloads. Each chunk yields its replaced bytes, which may be empty
+    /// when the chunk is entirely the start of a potential match. Once the
+    /// chunks are exhausted, the bytes held back, as returned by
+    /// [`AhoCorasickReplacer::finish`], are yielded as a final item, unless
+    /// there are none.
+    ///
+    /// An error yielded by the chunks is yielded as is, without changing
+    /// the state of the replacer, so iteration may carry on with the chunks
+    /// after it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let replacer = ac.replacer(&["bear", "white"])?;
+    /// let pages: Vec<std::io::Result<Vec<u8>>> =
+    ///     vec![Ok(b"The brown f".to_vec()), Ok(b"ox jumps".to_vec())];
+    /// let out: Vec<Vec<u8>> =
+    ///     replacer.replace_iter(pages).collect::<Result<_, _>>()?;
+    /// assert_eq!(
+    ///     vec![b"The white ".to_vec(), b"bear jumps".to_vec()],
+    ///     out,
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replace_iter<I, B, E>(self, chunks: I) -> ReplaceIter<I::IntoIter>
+    where
+        I: IntoIterator<Item = Result<B, E>>,
+        B: AsRef<[u8]>,
+    {
+        ReplaceIter { replacer: self, chunks: chunks.into_iter(), done: false }
+    }
+
+    /// Returns a reader that replaces matches in the bytes read from the
+    /// given source.
+    ///
+    /// See [`AhoCorasickReader`] for how the source is read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut rdr = ac
+    ///     .replacer(&["bear", "white"])?
+    ///     .reader(&b"The brown fox."[..]);
+    /// let mut out = String::new();
+    /// rdr.read_to_string(&mut out)?;
+    /// assert_eq!("The white bear.", out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn reader<R: std::io::Read>(self, source: R) -> AhoCorasickReader<R> {
+        AhoCorasickReader {
+            replacer: self,
+            source,
+            chunk: vec![0; READER_CHUNK_LEN],
+            out: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Returns a writer that replaces matches in the bytes written to it
+    /// before writing them to the given sink.
+    ///
+    /// See [`AhoCorasickWriter`] for when the bytes reach the sink.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut wtr = ac.replacer(&["bear", "white"])?.writer(vec![]);
+    /// wtr.write_all(b"The brown f")?;
+    /// wtr.write_all(b"ox.")?;
+    /// assert_eq!(b"The white bear.".to_vec(), wtr.into_inner()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn writer<W: std::io::Write>(self, sink: W) -> AhoCorasickWriter<W> {
+        AhoCorasickWriter { replacer: self, sink: Some(sink), panicked: false }
+    }
+
+    /// Perform potential replacements in the given chunk on a thread that
+    /// may block, and return this replacer along with the output of the
+    /// chunk once it's done.
+    ///
+    /// The replacer and the chunk are moved into a job that is given to
+    /// `spawn`, which must run it on a thread that may block and return a
+    /// future resolving to its result: the replacer, along with the output
+    /// of the chunk. This keeps replacing a large chunk off the thread of an
+    /// async executor. As explained for [`AhoCorasickReplacer`], the output
+    /// is the same as if every chunk was replaced on the same thread.
+    ///
+    /// There is no variant of this method for a specific runtime, since
+    /// the `async` feature only depends on the `futures` traits, and
+    /// `spawn` is a one line closure for any runtime. With tokio, it's
+    /// `|job| async { tokio::task::spawn_blocking(job).await.unwrap() }`.
+    ///
+    /// The output and the errors are those of
+    /// [`AhoCorasickReplacer::replace`], and the replacer is returned even
+    /// when replacing fails, so that it can be inspected or
+    /// [reset](AhoCorasickReplacer::reset).
+    ///
+    /// This is only available when the `async` and `std` features are
+    /// enabled.
+    ///
+    /// # Example
+    ///
+    /// This runs each job on a new thread, and waits for its result with a
+    /// channel.
+    ///
+    /// ```
+    /// use std::future::Future;
+    ///
+    /// use aho_corasick::{AhoCorasick, MatchError};
+    /// use futures::{channel::oneshot, executor::block_on};
+    ///
+    /// fn spawn<T: Send + 'static>(
+    ///     job: impl FnOnce() -> T + Send + 'static,
+    /// ) -> impl Future<Output = T> {
+    ///     let (tx, rx) = oneshot::channel();
+    ///     std::thread::spawn(move || tx.send(job()));
+    ///     async { rx.await.unwrap() }
+    /// }
+    ///
+    /// let ac = AhoCorasick::new(&["fox", "brown"]).unwrap();
+    /// let mut replacer = ac.replacer(&["bear", "white"])?;
+    /// let out = block_on(async move {
+    ///     let mut out = vec![];
+    ///     for chunk in [b"The brown f".to_vec(), b"ox.".to_vec()] {
+    ///         let (back, output) =
+    ///             replacer.replace_chunk_blocking(chunk, spawn).await;
+    ///         replacer = back;
+    ///         out.extend_from_slice(&output?);
+    ///     }
+    ///     out.extend_from_slice(replacer.finish()?);
+    ///     Ok::<_, MatchError>(out)
+    /// })?;
+    /// assert_eq!(b"The white bear.".to_vec(), out);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(all(feature = "async", feature = "std"))]
+    pub fn replace_chunk_blocking<S, F>(
+        mut self,
+        chunk: Vec<u8>,
+        spawn: S,
+    ) -> F
+    where
+        S: FnOnce(
+            alloc::boxed::Box<
+                dyn FnOnce() -> (
+                        AhoCorasickReplacer,
+                        Result<Vec<u8>, MatchError>,
+                    ) + Send,
+            >,
+        ) -> F,
+        F: core::future::Future<
+            Output = (AhoCorasickReplacer, Result<Vec<u8>, MatchError>),
+        >,
+    {
+        spawn(alloc::boxed::Box::new(move || {
+            let output = self.replace(&chunk).map(|out| out.to_vec());
+            (self, output)
+        }))
+    }
+
+    /// Returns a replacer that runs the given candidate replacer over the
+    /// same chunks as this one, as a dry run, and reports where their
+    /// decisions differ.
+    ///
+    /// The output is always the output of this replacer, so a candidate
+    /// dictionary can be validated on production traffic before it is
+    /// rolled out. See [`ShadowReplacer`] for how decisions are compared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::AhoCorasick;
+    ///
+    /// let active = AhoCorasick::new(&["secret"]).unwrap();
+    /// let candidate = AhoCorasick::new(&["secret", "token"]).unwrap();
+    /// let mut replacer = active
+    ///     .replacer(&["[redacted]"])?
+    ///     .shadow(candidate.replacer(&["[redacted]", "[redacted]"])?);
+    ///
+    /// let mut out = replacer.replace(b"secret tok")?.to_vec();
+    /// out.extend_from_slice(replacer.replace(b"en")?);
+    /// out.extend_from_slice(replacer.finish()?);
+    /// assert_eq!(b"[redacted] token".to_vec(), out);
+    ///
+    /// // Only the candidate would have replaced the token.
+    /// let diffs = replacer.diffs();
+    /// assert_eq!(1, diffs.len());
+    /// assert_eq!(7..12, diffs[0].span().range());
+    /// assert_eq!(None, diffs[0].active_replacement());
+    /// assert_eq!(Some(&b"[redacted]"[..]), diffs[0].shadow_replacement());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn shadow(self, candidate: AhoCorasickReplacer) -> ShadowReplacer {
+        ShadowReplacer::new(self, candidate)
+    }
+
+    /// Returns a replacer for a body in the HTTP/1.1 chunked transfer
+    /// coding, which runs this replacer over the payload of its chunks only.
+    ///
+    /// See [`ChunkedReplacer`] for how the body is framed again.
+    pub fn chunked(self) -> ChunkedReplacer {
+        ChunkedReplacer::new(self)
+    }
+
+    /// Returns a replacer for a stream of Server-Sent Events, which runs
+    /// this replacer over the values of their `data` fields only.
+    ///
+    /// See [`EventStreamReplacer`] for how the stream is decoded.
+    pub fn event_stream(self) -> EventStreamReplacer {
+        EventStreamReplacer::new(self)
+    }
+}
+
+/// The number of replacements made by a replacer, in total and for each
+/// pattern.
+///
+/// This is returned by [`AhoCorasickReplacer::stats`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReplaceStats {
+    total: u64,
+    /// The number of replacements of each pattern, indexed by pattern ID,
+    /// up to the largest pattern ID replaced so far.
+    counts: Vec<u64>,
+}
+
+impl ReplaceStats {
+    /// Returns the total number of replacements.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the number of replacements of matches of the given pattern.
+    pub fn count(&self, pattern: PatternID) -> u64 {
+        self.counts.get(pattern.as_usize()).copied().unwrap_or(0)
+    }
+
+    /// Returns an iterator over every pattern that was replaced at least
+    /// once, along with its number of replacements, in ascending order of
+    /// pattern ID.
+    pub fn iter(&self) -> impl Iterator<Item = (PatternID, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(pid, &count)| (PatternID::new_unchecked(pid), count))
+    }
+
+    /// Count a replacement of a match of the given pattern.
+    fn add(&mut self, pattern: PatternID) {
+        let i = pattern.as_usize();
+        if self.counts.len() <= i {
+            self.counts.resize(i + 1, 0);
+        }
+        self.counts[i] += 1;
+        self.total += 1;
+    }
+
+    fn reset(&mut self) {
+        self.total = 0;
+        self.counts.clear();
+    }
+}
+
+/// An iterator that replaces matches in the chunks yielded by another
+/// iterator.
+///
+/// This iterator is constructed via the
+/// [`AhoCorasickReplacer::replace_iter`] method.
+///
+/// The type variable `I` refers to the iterator of chunks. Each of its items
+/// is either a chunk or an error, which is yielded as is.
+pub struct ReplaceIter<I> {
+    replacer: AhoCorasickReplacer,
+    chunks: I,
+    /// Whether the bytes held back by the replacer have been yielded.
+    done: bool,
+}
+
+impl<I> ReplaceIter<I> {
+    /// Returns the replacer used by this iterator.
+    pub fn replacer(&self) -> &AhoCorasickReplacer {
+        &self.replacer
+    }
+
+    /// Returns a mutable reference to the replacer used by this iterator,
+    /// so that matching may be toggled between chunks with
+    /// [`AhoCorasickReplacer::matching_enabled`].
+    pub fn replacer_mut(&mut self) -> &mut AhoCorasickReplacer {
+        &mut self.replacer
+    }
+}
+
+impl<I, B, E> Iterator for ReplaceIter<I>
+where
+    I: Iterator<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+{
+    type Item = Result<Vec<u8>, E>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>, E>> {
+        if self.done {
+            return None;
+        }
+        match self.chunks.next() {
+            Some(Ok(chunk)) => {
+                let chunk = chunk.as_ref();
+                let mut out = Vec::with_capacity(
+                    chunk.len() + self.replacer.potential_buffer.len(),
+                );
+                match self.replacer.run(chunk, &mut out) {
+                    Ok(()) => {}
+                    Err(never) => match never {},
+                }
+                Some(Ok(out))
+            }
+            Some(Err(err)) => Some(Err(err)),
+            None => {
+                self.done = true;
+                let rest = self.replacer.finish().ok()?;
+                if rest.is_empty() {
+                    None
+                } else {
+                    Some(Ok(rest.to_vec()))
+                }
+            }
+        }
+    }
+}
+
+/// An output that drops everything written to it.
+struct Discard;
+
+impl Output for Discard {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, _: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Where the output of a replacer goes.
+trait Output {
+    type Error;
+
+    /// Whether `replacement` uses the bytes of a match, which are only
+    /// collected when it does.
+    const MATCHED: bool = false;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Returns the bytes that replace a match of the given pattern, given
+    /// the bytes it matched, if `MATCHED` is true, and the replacement the
+    /// replacer was built with. This is the latter by default.
+    fn replacement<'r>(
+        &mut self,
+        _pattern_id: PatternID,
+        _matched: &[u8],
+        fixed: &'r [u8],
+    ) -> Cow<'r, [u8]> {
+        Cow::Borrowed(fixed)
+    }
+}
+
+impl Output for Vec<u8> {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Writes the output of a replacer to its internal buffer, failing instead
+/// of growing it past the cap set by [`MemoryLimits::output`].
+struct Bounded<'b> {
+    buf: &'b mut Vec<u8>,
+    limit: usize,
+}
+
+impl<'b> Output for Bounded<'b> {
+    type Error = MatchError;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        if bytes.len() > self.limit - self.buf.len() {
+            return Err(MatchError::output_limit_exceeded(self.limit));
+        }
+        self.buf.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Writes the output of a replacer for a chunk to its internal buffer, as
+/// `Bounded` does, unless it's a prefix of the chunk, in which case only its
+/// length is kept, so that the chunk can be returned instead.
+struct PassThrough<'c, 'b> {
+    chunk: &'c [u8],
+    /// The length of the prefix of the chunk written so far, or `None` once
+    /// something else is written and the output is in the buffer.
+    borrowed: Option<usize>,
+    buf: &'b mut Vec<u8>,
+    limit: usize,
+}
+
+impl<'c, 'b> Output for PassThrough<'c, 'b> {
+    type Error = MatchError;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if let Some(len) = self.borrowed {
+            // The bytes continue the prefix only if they're the bytes of
+            // the chunk right after it, which no other allocation can hold.
+            let rest = &self.chunk[len..];
+            if bytes.as_ptr() == rest.as_ptr() && bytes.len() <= rest.len() {
+                self.borrowed = Some(len + bytes.len());
+                return Ok(());
+            }
+            self.borrowed = None;
+            Bounded { buf: self.buf, limit: self.limit }
+                .write(&self.chunk[..len])?;
+        }
+        Bounded { buf: self.buf, limit: self.limit }.write(bytes)
+    }
+}
+
+/// Writes the output of a replacer to a `std::io::Write` sink.
+#[cfg(feature = "std")]
+struct WriteOutput<'w, W>(&'w mut W);
+
+#[cfg(feature = "std")]
+impl<'w, W: std::io::Write> Output for WriteOutput<'w, W> {
+    type Error = std::io::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.0.write_all(bytes)
+    }
+}
+
+/// Wrap an error of a replacer into an I/O error, for the replacements that
+/// write to a sink.
+#[cfg(feature = "std")]
+fn io_error(err: MatchError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
+
+/// Write up to `n` bytes from the front of the potential buffer to the
+/// output, and remove them from the buffer.
+fn write_front<O: Output>(
+    potential: &mut VecDeque<u8>,
+    n: usize,
+    out: &mut O,
+) -> Result<(), O::Error> {
+    let n = n.min(potential.len());
+    if n == 0 {
+        return Ok(());
+    }
+    let (a, b) = potential.as_slices();
+    let from_a = n.min(a.len());
+    out.write(&a[..from_a])?;
+    out.write(&b[..n - from_a])?;
+    potential.drain(..n);
+    Ok(())
+}
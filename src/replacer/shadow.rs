@@ -0,0 +1,281 @@
+use alloc::vec::Vec;
+
+use crate::{
+    replacer::{AhoCorasickReplacer, Discard},
+    Match, MatchError, PatternID, Span,
+};
+
+/// A replacement made by a replacer, as recorded for shadowing.
+#[derive(Clone, Debug)]
+pub(crate) struct Decision {
+    pub(crate) mat: Match,
+    /// The index of the replacement written in the replacements of the
+    /// replacer, which is `None` for a match coalesced into a run.
+    pub(crate) choice: Option<usize>,
+}
+
+impl Decision {
+    /// Returns the bytes that replaced the match.
+    fn replacement<'r>(&self, replacer: &'r AhoCorasickReplacer) -> &'r [u8] {
+        match self.choice {
+            Some(choice) => &replacer.replace_with[choice],
+            None => &[],
+        }
+    }
+}
+
+/// A replacer that runs a candidate replacer in the shadow of the active
+/// one, and reports where their decisions differ.
+///
+/// Every chunk is given to both replacers, but only the output of the active
+/// replacer is returned, so the output is exactly the same as without the
+/// candidate. The output of the candidate is discarded as it is produced,
+/// and is never buffered.
+///
+/// A decision is the replacement of a span of the input by some bytes. Two
+/// decisions are the same when they replace the same span by the same
+/// bytes, even if their pattern IDs differ, since the two replacers usually
+/// have different dictionaries. Every decision of one replacer without the
+/// same decision by the other is reported as a [`ShadowDiff`] once each
+/// chunk has been processed, in the order of the end of their spans. A
+/// match suppressed by deduplication or by disabling its pattern isn't a
+/// decision.
+///
+/// This replacer is constructed via the [`AhoCorasickReplacer::shadow`]
+/// method, which has an example.
+pub struct ShadowReplacer {
+    active: AhoCorasickReplacer,
+    shadow: AhoCorasickReplacer,
+    diffs: Vec<ShadowDiff>,
+}
+
+impl ShadowReplacer {
+    pub(crate) fn new(
+        mut active: AhoCorasickReplacer,
+        mut shadow: AhoCorasickReplacer,
+    ) -> ShadowReplacer {
+        active.decisions = Some(Vec::new());
+        shadow.decisions = Some(Vec::new());
+        ShadowReplacer { active, shadow, diffs: Vec::new() }
+    }
+
+    /// Perform replacements in the chunk with the active replacer, and
+    /// compare them with the decisions of the candidate. This is otherwise
+    /// the same as [`AhoCorasickReplacer::replace`].
+    pub fn replace(&mut self, chunk: &[u8]) -> Result<&[u8], MatchError> {
+        self.active.check_poisoned()?;
+        if chunk.is_empty() {
+            return Ok(&[]);
+        }
+        // The candidate stops where the active replacer does.
+        let at = self.active.quit_position(chunk).unwrap_or(chunk.len());
+        match self.shadow.run(&chunk[..at], &mut Discard) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        if at < chunk.len() {
+            self.shadow.settle();
+            self.shadow.restart();
+        }
+        self.active.replace(chunk)?;
+        if let Err(err) = self.compare() {
+            // The output of the chunk goes first on the next call.
+            let output = core::mem::take(&mut self.active.buffer);
+            self.active.hold_front(output);
+            return Err(err);
+        }
+        Ok(&self.active.buffer)
+    }
+
+    /// Returns the bytes held back by the active replacer. See
+    /// [`AhoCorasickReplacer::finish`].
+    pub fn finish(&mut self) -> Result<&[u8], MatchError> {
+        self.active.check_poisoned()?;
+        self.active.settle();
+        self.shadow.settle();
+        self.compare()?;
+        self.active.finish()
+    }
+
+    /// Returns the differences found so far.
+    pub fn diffs(&self) -> &[ShadowDiff] {
+        &self.diffs
+    }
+
+    /// Returns the differences found so far, and forgets them, so that a
+    /// long running stream doesn't accumulate them.
+    pub fn take_diffs(&mut self) -> Vec<ShadowDiff> {
+        core::mem::take(&mut self.diffs)
+    }
+
+    /// Returns the active replacer, whose output is returned.
+    pub fn active(&self) -> &AhoCorasickReplacer {
+        &self.active
+    }
+
+    /// Returns the candidate replacer, whose output is discarded.
+    pub fn candidate(&self) -> &AhoCorasickReplacer {
+        &self.shadow
+    }
+
+    /// Compare the decisions made by both replacers since the last
+    /// comparison.
+    ///
+    /// A decision is made as soon as the end of its match is seen, and both
+    /// replacers have seen the same bytes at this point, so no decision that
+    /// is still to come can end before the ones made so far. The exception
+    /// is a match waiting for its lookahead, so the decisions that don't end
+    /// before it are compared once it's decided.
+    ///
+    /// Once the differences reach the cap set by [`MemoryLimits::events`],
+    /// the decisions that are left are compared again the next time.
+    fn compare(&mut self) -> Result<(), MatchError> {
+        let horizon = core::cmp::min(
+            self.active.pending_end().unwrap_or(usize::MAX),
+            self.shadow.pending_end().unwrap_or(usize::MAX),
+        );
+        let mut active = self.active.decisions.take().unwrap_or_default();
+        let mut shadow = self.shadow.decisions.take().unwrap_or_default();
+        let later_active = split_at_end(&mut active, horizon);
+        let later_shadow = split_at_end(&mut shadow, horizon);
+        let limit = self.active.limits.events.unwrap_or(usize::MAX);
+        let (a, s, diffs) = (&self.active, &self.shadow, &mut self.diffs);
+        let (i, j) = each_diff(a, s, &active, &shadow, |x, y| {
+            if diffs.len() >= limit {
+                return false;
+            }
+            diffs.push(shadow_diff(a, s, x, y));
+            true
+        });
+        let exceeded = i < active.len() || j < shadow.len();
+        active.drain(..i);
+        shadow.drain(..j);
+        active.extend(later_active);
+        shadow.extend(later_shadow);
+        self.active.decisions = Some(active);
+        self.shadow.decisions = Some(shadow);
+        if exceeded {
+            return Err(MatchError::event_limit_exceeded(limit));
+        }
+        Ok(())
+    }
+}
+
+/// Call `f` with each pair of decisions of the active and the candidate
+/// replacers that differ, where one of them is missing when the other
+/// replacer made no decision about the same span, until it returns false.
+///
+/// The decisions of each replacer are in the order of the end of their
+/// spans, and so are the differences. This returns the number of decisions
+/// of each replacer that were compared, which excludes the ones of the
+/// difference `f` returned false for.
+fn each_diff<F>(
+    active: &AhoCorasickReplacer,
+    shadow: &AhoCorasickReplacer,
+    active_decisions: &[Decision],
+    shadow_decisions: &[Decision],
+    mut f: F,
+) -> (usize, usize)
+where
+    F: FnMut(Option<&Decision>, Option<&Decision>) -> bool,
+{
+    let (mut i, mut j) = (0, 0);
+    loop {
+        let (x, y) = (active_decisions.get(i), shadow_decisions.get(j));
+        let (x, y) = match (x, y) {
+            (None, None) => break,
+            (Some(x), Some(y)) if x.mat.span() == y.mat.span() => {
+                if x.replacement(active) == y.replacement(shadow) {
+                    i += 1;
+                    j += 1;
+                    continue;
+                }
+                (Some(x), Some(y))
+            }
+            (Some(x), Some(y))
+                if (x.mat.end(), x.mat.start())
+                    < (y.mat.end(), y.mat.start()) =>
+            {
+                (Some(x), None)
+            }
+            (Some(x), None) => (Some(x), None),
+            (_, Some(y)) => (None, Some(y)),
+        };
+        if !f(x, y) {
+            break;
+        }
+        i += usize::from(x.is_some());
+        j += usize::from(y.is_some());
+    }
+    (i, j)
+}
+
+/// Returns the difference between the given decisions of the active and the
+/// candidate replacers.
+fn shadow_diff(
+    active: &AhoCorasickReplacer,
+    shadow: &AhoCorasickReplacer,
+    active_decision: Option<&Decision>,
+    shadow_decision: Option<&Decision>,
+) -> ShadowDiff {
+    let mat = active_decision.or(shadow_decision).unwrap().mat;
+    ShadowDiff {
+        span: mat.span(),
+        active: active_decision
+            .map(|d| (d.mat.pattern(), d.replacement(active).to_vec())),
+        shadow: shadow_decision
+            .map(|d| (d.mat.pattern(), d.replacement(shadow).to_vec())),
+    }
+}
+
+/// Remove the decisions that don't end before the given offset, and return
+/// them.
+fn split_at_end(decisions: &mut Vec<Decision>, end: usize) -> Vec<Decision> {
+    let at = decisions.iter().position(|d| d.mat.end() >= end);
+    decisions.split_off(at.unwrap_or(decisions.len()))
+}
+
+/// A difference between the decisions of the active and the candidate
+/// replacers of a [`ShadowReplacer`].
+///
+/// A difference is either a span replaced by only one of the replacers, or
+/// a span replaced by both, but by different bytes. When the replacers
+/// replace overlapping spans, each of them is a separate difference.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShadowDiff {
+    span: Span,
+    active: Option<(PatternID, Vec<u8>)>,
+    shadow: Option<(PatternID, Vec<u8>)>,
+}
+
+impl ShadowDiff {
+    /// Returns the span of the input that the decision is about, measured
+    /// from the first byte given to the replacer.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the pattern of the active replacer that matched the span, or
+    /// `None` if the active replacer left it unchanged.
+    pub fn active_pattern(&self) -> Option<PatternID> {
+        self.active.as_ref().map(|&(pid, _)| pid)
+    }
+
+    /// Returns the bytes that the active replacer replaced the span by, or
+    /// `None` if it left it unchanged.
+    pub fn active_replacement(&self) -> Option<&[u8]> {
+        self.active.as_ref().map(|(_, r)| &**r)
+    }
+
+    /// Returns the pattern of the candidate replacer that matched the span,
+    /// or `None` if the candidate would have left it unchanged.
+    pub fn shadow_pattern(&self) -> Option<PatternID> {
+        self.shadow.as_ref().map(|&(pid, _)| pid)
+    }
+
+    /// Returns the bytes that the candidate replacer would have replaced the
+    /// span by, or `None` if it would have left it unchanged.
+    pub fn shadow_replacement(&self) -> Option<&[u8]> {
+        self.shadow.as_ref().map(|(_, r)| &**r)
+    }
+}
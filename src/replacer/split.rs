@@ -0,0 +1,206 @@
+use alloc::{borrow::Cow, vec, vec::Vec};
+
+use crate::{
+    replacer::{
+        builder::AhoCorasickReplacerBuilder, error::ReplacerError,
+        AhoCorasickReplacer, Output,
+    },
+    AhoCorasick, PatternID,
+};
+
+/// A splitter that consumes a stream in chunks, and yields the records
+/// between the matches of the patterns of an automaton.
+///
+/// This is like splitting a haystack on any of a set of delimiters, but
+/// incremental: the patterns are the delimiters, and each record is yielded as
+/// soon as the match that ends it is found, even if it spans several chunks.
+/// Matches are found in the same way as by an [`AhoCorasickReplacer`],
+/// wherever the stream is split into chunks, so only match kinds
+/// [`MatchKind::Standard`](crate::MatchKind::Standard) and
+/// [`MatchKind::LeftmostLongest`](crate::MatchKind::LeftmostLongest) are
+/// supported.
+///
+/// The bytes of the current record are buffered until the delimiter that
+/// ends it is found, along with the start of a potential delimiter. The
+/// last record of a stream isn't followed by a delimiter, and it is yielded
+/// by [`AhoCorasickStreamSplitter::finish`], even if it's empty, as with
+/// `split` on a string.
+///
+/// This splitter is constructed via the [`AhoCorasick::stream_splitter`]
+/// method.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(&["\r\n", "|"]).unwrap();
+/// let mut splitter = ac.stream_splitter()?;
+/// let mut records = vec![];
+/// for chunk in [&b"GET|/in"[..], b"dex\r", b"\nHost|example"] {
+///     records.extend(splitter.split(chunk).map(|r| r.as_bytes().to_vec()));
+/// }
+/// let last = splitter.finish().last().unwrap();
+/// assert_eq!(None, last.delimiter());
+/// records.push(last.as_bytes().to_vec());
+/// assert_eq!(vec![&b"GET"[..], b"/index", b"Host", b"example"], records);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct AhoCorasickStreamSplitter {
+    replacer: AhoCorasickReplacer,
+    /// The bytes of the records completed by the last call, followed by the
+    /// bytes of the current record seen so far.
+    bytes: Vec<u8>,
+    /// The end of each record completed by the last call in `bytes`, along
+    /// with the delimiter that ended it.
+    ends: Vec<(usize, PatternID)>,
+}
+
+impl AhoCorasickStreamSplitter {
+    /// Create a splitter, checking that the automaton supports stream
+    /// replacements.
+    pub(crate) fn new(
+        ac: &AhoCorasick,
+    ) -> Result<AhoCorasickStreamSplitter, ReplacerError> {
+        // Delimiters are removed from the records, so they are replaced by
+        // nothing.
+        let unused: Vec<Vec<u8>> = vec![Vec::new(); ac.patterns_len()];
+        let replacer =
+            AhoCorasickReplacerBuilder::new().build_replacer(ac, unused)?;
+        Ok(AhoCorasickStreamSplitter {
+            replacer,
+            bytes: Vec::new(),
+            ends: Vec::new(),
+        })
+    }
+
+    /// Give the next chunk of the stream to the splitter, returning the
+    /// records it completes.
+    ///
+    /// The records are borrowed from the splitter, and they are dropped by
+    /// the next call. The bytes of a record that isn't complete yet are
+    /// held back, so there may be no records even if the chunk isn't empty.
+    pub fn split(&mut self, chunk: &[u8]) -> SplitRecords<'_> {
+        self.drop_completed();
+        let mut out = Split { bytes: &mut self.bytes, ends: &mut self.ends };
+        match self.replacer.run(chunk, &mut out) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        SplitRecords { splitter: self, at: 0, start: 0, last: false }
+    }
+
+    /// End the stream, returning the records it completes, followed by its
+    /// last record, which has no delimiter.
+    ///
+    /// The splitter should then be reset before it's given another stream.
+    pub fn finish(&mut self) -> SplitRecords<'_> {
+        self.drop_completed();
+        let mut out = Split { bytes: &mut self.bytes, ends: &mut self.ends };
+        match self.replacer.finish_to(&mut out) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        SplitRecords { splitter: self, at: 0, start: 0, last: true }
+    }
+
+    /// Reset this splitter so that it can be reused for a new stream.
+    pub fn reset(&mut self) {
+        self.replacer.reset();
+        self.bytes.clear();
+        self.ends.clear();
+    }
+
+    /// Forget the records completed by the last call, keeping the bytes of
+    /// the current record.
+    fn drop_completed(&mut self) {
+        if let Some(&(end, _)) = self.ends.last() {
+            self.bytes.drain(..end);
+        }
+        self.ends.clear();
+    }
+}
+
+/// Collects the output of the replacer of a splitter, where each match
+/// ends a record.
+pub(crate) struct Split<'s> {
+    bytes: &'s mut Vec<u8>,
+    ends: &'s mut Vec<(usize, PatternID)>,
+}
+
+impl<'s> Output for Split<'s> {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bytes.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn replacement<'r>(
+        &mut self,
+        pattern_id: PatternID,
+        _matched: &[u8],
+        fixed: &'r [u8],
+    ) -> Cow<'r, [u8]> {
+        // The bytes before the match are always written first.
+        self.ends.push((self.bytes.len(), pattern_id));
+        Cow::Borrowed(fixed)
+    }
+}
+
+/// An iterator of the records completed by a call to an
+/// [`AhoCorasickStreamSplitter`].
+///
+/// The lifetime `'s` refers to the lifetime of the splitter.
+pub struct SplitRecords<'s> {
+    splitter: &'s AhoCorasickStreamSplitter,
+    /// The index of the next completed record.
+    at: usize,
+    /// The offset of the next record in the bytes of the splitter.
+    start: usize,
+    /// Whether the stream ended, so that the current record is yielded
+    /// last.
+    last: bool,
+}
+
+impl<'s> Iterator for SplitRecords<'s> {
+    type Item = SplitRecord<'s>;
+
+    fn next(&mut self) -> Option<SplitRecord<'s>> {
+        let bytes = &self.splitter.bytes;
+        let (end, delimiter) = match self.splitter.ends.get(self.at) {
+            Some(&(end, pid)) => (end, Some(pid)),
+            None if self.last => {
+                self.last = false;
+                (bytes.len(), None)
+            }
+            None => return None,
+        };
+        let record = SplitRecord { bytes: &bytes[self.start..end], delimiter };
+        self.at += 1;
+        self.start = end;
+        Some(record)
+    }
+}
+
+/// A record yielded by an [`AhoCorasickStreamSplitter`].
+///
+/// The lifetime `'s` refers to the lifetime of the splitter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SplitRecord<'s> {
+    bytes: &'s [u8],
+    delimiter: Option<PatternID>,
+}
+
+impl<'s> SplitRecord<'s> {
+    /// Returns the bytes of the record, without its delimiter.
+    pub fn as_bytes(&self) -> &'s [u8] {
+        self.bytes
+    }
+
+    /// Returns the pattern whose match ended the record, or `None` for the
+    /// last record of the stream.
+    pub fn delimiter(&self) -> Option<PatternID> {
+        self.delimiter
+    }
+}
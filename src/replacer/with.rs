@@ -0,0 +1,283 @@
+use alloc::{borrow::Cow, vec::Vec};
+
+use crate::{
+    replacer::{
+        builder::Padding, AhoCorasickReplacer, Bounded, Output, ReplaceStats,
+    },
+    MatchError, PatternID,
+};
+
+#[cfg(feature = "std")]
+use crate::replacer::{io_error, WriteOutput};
+
+/// A replacer whose replacements are computed by a closure.
+///
+/// The closure is called with the pattern of each match that is replaced and
+/// the bytes it matched, and it returns the bytes that replace them. This
+/// permits replacements that can't be known in advance, such as counters,
+/// expanded templates or rewrites that depend on the bytes matched, while
+/// streaming, like
+/// [`AhoCorasick::replace_all_with_bytes`](crate::AhoCorasick::replace_all_with_bytes)
+/// does for a whole haystack.
+///
+/// The closure is only called once a match is decided to be replaced, in the
+/// order of the output, so it isn't called for a match that is suppressed by
+/// deduplication, disabled, rejected by its lookahead or coalesced into a run.
+/// The bytes of an empty match are empty. Every other option of the
+/// [`AhoCorasickReplacerBuilder`](crate::AhoCorasickReplacerBuilder) applies
+/// as it does to an [`AhoCorasickReplacer`], except that the length of the
+/// replacements can't be checked in advance when padding. Instead, a
+/// replacement shorter than its match is padded, and a longer one is truncated
+/// on the side the padding is inserted: [`Padding::Right`] keeps its first
+/// bytes and [`Padding::Left`] keeps its last bytes. Either way, the output
+/// has exactly the same length as the input.
+///
+/// This replacer is constructed via the
+/// [`AhoCorasick::replacer_fn`](crate::AhoCorasick::replacer_fn) and
+/// [`AhoCorasick::replacer_with_fn`](crate::AhoCorasick::replacer_with_fn)
+/// methods.
+///
+/// # Example
+///
+/// This numbers every match, across chunks.
+///
+/// ```
+/// use aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(&["fox", "dog"]).unwrap();
+/// let mut count = 0;
+/// let mut replacer = ac.replacer_fn(|_, matched| {
+///     count += 1;
+///     format!("{}#{}", String::from_utf8_lossy(matched), count).into_bytes()
+/// })?;
+/// let mut out = replacer.replace(b"fox, dog and f")?.to_vec();
+/// out.extend_from_slice(replacer.replace(b"ox")?);
+/// out.extend_from_slice(replacer.finish()?);
+/// assert_eq!(b"fox#1, dog#2 and fox#3".to_vec(), out);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct AhoCorasickReplacerWith<F> {
+    pub(crate) replacer: AhoCorasickReplacer,
+    pub(crate) replace_with: F,
+    pub(crate) padding: Option<Padding>,
+}
+
+impl<F> AhoCorasickReplacerWith<F>
+where
+    F: FnMut(PatternID, &[u8]) -> Vec<u8>,
+{
+    /// Perform replacements in the chunk, returning the bytes that are
+    /// decided. This is otherwise the same as
+    /// [`AhoCorasickReplacer::replace`].
+    pub fn replace(&mut self, chunk: &[u8]) -> Result<&[u8], MatchError> {
+        self.replacer.check_poisoned()?;
+        if chunk.is_empty() {
+            return Ok(&[]);
+        }
+        let mut buffer = core::mem::take(&mut self.replacer.buffer);
+        buffer.clear();
+        let limit = self.replacer.output_limit();
+        buffer.reserve(core::cmp::min(
+            chunk.len() + self.replacer.potential_buffer.len(),
+            limit,
+        ));
+        let mut out = Expand {
+            out: &mut Bounded { buf: &mut buffer, limit },
+            replace_with: &mut self.replace_with,
+            padding: self.padding,
+        };
+        let result = self.replacer.run_bounded(chunk, &mut out);
+        self.replacer.buffer = buffer;
+        result?;
+        self.replacer.check_stopped()?;
+        Ok(&self.replacer.buffer)
+    }
+
+    /// Perform replacements in the chunk, writing the result directly to
+    /// the given sink. This is otherwise the same as
+    /// [`AhoCorasickReplacer::replace_to`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if writing to the sink fails, in which case the
+    /// replacer is poisoned until it's reset, or when the replacer holds back
+    /// more bytes than allowed by
+    /// [`PendingLimit::Error`](crate::PendingLimit::Error).
+    #[cfg(feature = "std")]
+    pub fn replace_to<W: std::io::Write>(
+        &mut self,
+        chunk: &[u8],
+        sink: &mut W,
+    ) -> std::io::Result<()> {
+        self.replacer.check_poisoned().map_err(io_error)?;
+        self.replacer.buffer.clear();
+        let mut out = Expand {
+            out: &mut WriteOutput(sink),
+            replace_with: &mut self.replace_with,
+            padding: self.padding,
+        };
+        self.replacer.run_bounded(chunk, &mut out)?;
+        self.replacer.check_stopped().map_err(io_error)
+    }
+
+    /// Perform replacements in the chunk, appending the result to the given
+    /// buffer. This is otherwise the same as
+    /// [`AhoCorasickReplacer::replace_to_vec`].
+    pub fn replace_to_vec(
+        &mut self,
+        chunk: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), MatchError> {
+        self.replacer.check_poisoned()?;
+        self.replacer.buffer.clear();
+        out.reserve(chunk.len() + self.replacer.potential_buffer.len());
+        let mut out = Expand {
+            out,
+            replace_with: &mut self.replace_with,
+            padding: self.padding,
+        };
+        match self.replacer.run_bounded(chunk, &mut out) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        self.replacer.check_stopped()
+    }
+
+    /// Returns every byte held back whose fate is already decided, without
+    /// ending the stream. See [`AhoCorasickReplacer::flush`].
+    pub fn flush(&mut self) -> Result<&[u8], MatchError> {
+        // Only bytes of the input are ever flushed, never replacements.
+        self.replacer.flush()
+    }
+
+    /// Returns the bytes held back once the stream ends. See
+    /// [`AhoCorasickReplacer::finish`].
+    pub fn finish(&mut self) -> Result<&[u8], MatchError> {
+        self.replacer.check_poisoned()?;
+        let mut output = Vec::new();
+        let limit = self.replacer.output_limit();
+        let mut out = Expand {
+            out: &mut Bounded { buf: &mut output, limit },
+            replace_with: &mut self.replace_with,
+            padding: self.padding,
+        };
+        self.replacer.finish_to(&mut out)?;
+        self.replacer.hold_finished(output)
+    }
+
+    /// Enable or disable matching for the bytes given to the replacer from
+    /// now on. See [`AhoCorasickReplacer::matching_enabled`].
+    pub fn matching_enabled(&mut self, yes: bool) {
+        if yes == self.replacer.matching_enabled {
+            return;
+        }
+        let mut output = Vec::new();
+        let mut out = Expand {
+            out: &mut output,
+            replace_with: &mut self.replace_with,
+            padding: self.padding,
+        };
+        match self.replacer.settle_to(&mut out) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        self.replacer.hold_front(output);
+        self.replacer.toggle_matching();
+    }
+
+    /// Start a new record, so that the bytes given to the replacer from now
+    /// on are searched as if they began a stream. See
+    /// [`AhoCorasickReplacer::start_record`].
+    pub fn start_record(&mut self) {
+        if self.replacer.position == self.replacer.record_start {
+            return;
+        }
+        let mut output = Vec::new();
+        let mut out = Expand {
+            out: &mut output,
+            replace_with: &mut self.replace_with,
+            padding: self.padding,
+        };
+        match self.replacer.settle_to(&mut out) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        self.replacer.hold_front(output);
+        self.replacer.restart();
+    }
+
+    /// Returns true if matching is enabled for the bytes given to the
+    /// replacer from now on.
+    pub fn is_matching_enabled(&self) -> bool {
+        self.replacer.is_matching_enabled()
+    }
+
+    /// Enable or disable replacing the matches of the given pattern. See
+    /// [`AhoCorasickReplacer::set_enabled`].
+    ///
+    /// # Panics
+    ///
+    /// This panics if the given pattern ID isn't a pattern of the automaton
+    /// this replacer was created from.
+    pub fn set_enabled(&mut self, pid: PatternID, yes: bool) {
+        self.replacer.set_enabled(pid, yes);
+    }
+
+    /// Returns true if the matches of the given pattern are replaced.
+    pub fn is_enabled(&self, pid: PatternID) -> bool {
+        self.replacer.is_enabled(pid)
+    }
+
+    /// Returns true if an error left the replacer in an undefined state.
+    /// See [`AhoCorasickReplacer::is_poisoned`].
+    pub fn is_poisoned(&self) -> bool {
+        self.replacer.is_poisoned()
+    }
+
+    /// Returns the number of replacements made since this replacer was
+    /// created or last reset. See [`AhoCorasickReplacer::stats`].
+    pub fn stats(&self) -> &ReplaceStats {
+        self.replacer.stats()
+    }
+
+    /// Reset this replacer so that it can be reused for a new stream. See
+    /// [`AhoCorasickReplacer::reset`].
+    pub fn reset(&mut self) {
+        self.replacer.reset();
+    }
+}
+
+/// Gives the output of a replacer to another output, replacing each match
+/// by the bytes returned by a closure.
+struct Expand<'o, O, F> {
+    out: &'o mut O,
+    replace_with: &'o mut F,
+    padding: Option<Padding>,
+}
+
+impl<'o, O, F> Output for Expand<'o, O, F>
+where
+    O: Output,
+    F: FnMut(PatternID, &[u8]) -> Vec<u8>,
+{
+    type Error = O::Error;
+
+    const MATCHED: bool = true;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.out.write(bytes)
+    }
+
+    fn replacement<'r>(
+        &mut self,
+        pattern_id: PatternID,
+        matched: &[u8],
+        _: &'r [u8],
+    ) -> Cow<'r, [u8]> {
+        let mut replacement = (self.replace_with)(pattern_id, matched);
+        if let Some(padding) = self.padding {
+            padding.fit(&mut replacement, matched.len());
+        }
+        Cow::Owned(replacement)
+    }
+}
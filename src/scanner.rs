@@ -0,0 +1,1012 @@
+/*!
+Provides rule based scanning, where verdicts are computed from the matches of
+many patterns.
+
+A [`RuleSet`] is made up of named patterns and rules that refer to those
+patterns by name. Patterns may be literal byte strings or byte
+[`Signature`]s. Each rule combines the matches of one or more patterns with
+a condition:
+
+* [`Rule::all`] requires every pattern to match.
+* [`Rule::ordered`] requires every pattern to match, in the order given.
+* [`Rule::at_least`] requires a minimum number of distinct patterns to match.
+* [`Rule::weighted`] assigns each pattern a weight and requires the total
+  weight of the patterns that match to reach a threshold.
+
+Conditions can be further constrained with [`Rule::min_count`], which
+requires each pattern to match some number of times before it is considered
+present, and [`Rule::within`], which requires all of the matches used to
+satisfy a rule to be close to one another.
+
+Matches of a pattern may also be subjected to an arbitrary check before they
+are counted by any rule. See [`RuleSetBuilder::verify`].
+
+A [`Scanner`] is created from a rule set and is fed a stream one chunk at a
+time. As soon as a rule is satisfied, a [`Verdict`] for it is reported, and
+once the stream is finished, a verdict for every rule is available. For
+haystacks that are entirely in memory, [`RuleSet::scan`] is a convenient
+shortcut.
+
+# Example
+
+```
+use aho_corasick::{scanner::{Rule, RuleSet}, signature::Signature};
+
+let rules = RuleSet::builder()
+    .signature("mz", Signature::new("4D 5A")?)
+    .pattern("pe", b"PE\x00\x00")
+    .pattern("upx", "UPX!")
+    .rule(Rule::ordered("pe-file", ["mz", "pe"]).within(64))
+    .rule(Rule::all("packed", ["upx"]).min_count(2))
+    .build()?;
+
+let mut scanner = rules.scanner();
+assert!(scanner.write(b"MZ\x90\x00 UPX! ").is_empty());
+// The second chunk completes the "pe-file" rule.
+let verdicts = scanner.write(b"PE\x00\x00");
+assert_eq!(1, verdicts.len());
+assert_eq!("pe-file", rules.rule_name(verdicts[0].rule()));
+
+let verdicts = scanner.finish();
+assert!(verdicts[0].is_match());
+assert!(!verdicts[1].is_match());
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    ahocorasick::AhoCorasickBuilder,
+    signature::{Signature, SignatureError, SignatureSearcher, StreamWindow},
+    util::{error::BuildError, search::Match},
+};
+
+/// A single rule in a [`RuleSet`].
+///
+/// A rule has a name, refers to one or more patterns by name and has a
+/// condition that determines whether the matches of those patterns satisfy
+/// the rule. See the [module documentation](self) for more details.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    name: String,
+    /// The names of the patterns referenced by this rule, along with their
+    /// weight. The weight is only used for weighted rules.
+    patterns: Vec<(String, u64)>,
+    condition: Condition,
+    min_count: usize,
+    within: Option<usize>,
+}
+
+/// The condition under which a rule is satisfied.
+#[derive(Clone, Copy, Debug)]
+enum Condition {
+    All,
+    Ordered,
+    AtLeast(usize),
+    Weighted(u64),
+}
+
+impl Rule {
+    /// Create a rule that is satisfied when every pattern given matches.
+    pub fn all<I, S>(name: &str, patterns: I) -> Rule
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Rule::new(name, patterns, Condition::All)
+    }
+
+    /// Create a rule that is satisfied when every pattern given matches, in
+    /// the order given.
+    ///
+    /// That is, each pattern must match at or after the start of the match
+    /// used for the previous pattern.
+    pub fn ordered<I, S>(name: &str, patterns: I) -> Rule
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Rule::new(name, patterns, Condition::Ordered)
+    }
+
+    /// Create a rule that is satisfied when at least `count` of the patterns
+    /// given match.
+    pub fn at_least<I, S>(name: &str, count: usize, patterns: I) -> Rule
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Rule::new(name, patterns, Condition::AtLeast(count))
+    }
+
+    /// Create a rule that is satisfied when the sum of the weights of the
+    /// patterns that match is at least `threshold`.
+    pub fn weighted<I, S>(name: &str, threshold: u64, patterns: I) -> Rule
+    where
+        I: IntoIterator<Item = (S, u64)>,
+        S: AsRef<str>,
+    {
+        Rule {
+            name: name.to_string(),
+            patterns: patterns
+                .into_iter()
+                .map(|(p, weight)| (p.as_ref().to_string(), weight))
+                .collect(),
+            condition: Condition::Weighted(threshold),
+            min_count: 1,
+            within: None,
+        }
+    }
+
+    fn new<I, S>(name: &str, patterns: I, condition: Condition) -> Rule
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Rule {
+            name: name.to_string(),
+            patterns: patterns
+                .into_iter()
+                .map(|p| (p.as_ref().to_string(), 1))
+                .collect(),
+            condition,
+            min_count: 1,
+            within: None,
+        }
+    }
+
+    /// Require each pattern to match at least `count` times before it is
+    /// considered present.
+    ///
+    /// For ordered rules, this means each pattern must match `count` times
+    /// before matches of the next pattern are considered.
+    ///
+    /// By default, this is `1`.
+    pub fn min_count(mut self, count: usize) -> Rule {
+        self.min_count = count;
+        self
+    }
+
+    /// Require that all of the matches used to satisfy this rule start
+    /// within `len` bytes of one another.
+    ///
+    /// By default, matches may be arbitrarily far apart.
+    pub fn within(mut self, len: usize) -> Rule {
+        self.within = Some(len);
+        self
+    }
+
+    /// Returns the name of this rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A builder for a [`RuleSet`].
+///
+/// Patterns and rules may be added in any order, since rules refer to
+/// patterns by name and are only resolved when the rule set is built.
+#[derive(Clone, Debug, Default)]
+pub struct RuleSetBuilder {
+    automaton: AhoCorasickBuilder,
+    patterns: Vec<(String, PatternDef)>,
+    checks: Vec<(String, Check)>,
+    rules: Vec<Rule>,
+}
+
+/// A check applied to the bytes of every match of a pattern.
+#[derive(Clone)]
+struct Check(Arc<CheckFn>);
+
+/// The type of function used to check a match.
+type CheckFn = dyn Fn(&[u8]) -> bool + Send + Sync;
+
+impl core::fmt::Debug for Check {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Check(..)")
+    }
+}
+
+/// The definition of a pattern given to a rule set builder.
+#[derive(Clone, Debug)]
+enum PatternDef {
+    Literal(Vec<u8>),
+    Signature(Signature),
+}
+
+impl RuleSetBuilder {
+    /// Create a new builder for a rule set.
+    pub fn new() -> RuleSetBuilder {
+        RuleSetBuilder::default()
+    }
+
+    /// Build a rule set from the patterns and rules added to this builder.
+    ///
+    /// A builder may be reused to create more rule sets.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if two patterns have the same name, a rule or
+    /// check refers to a pattern that doesn't exist, a rule can never be satisfied
+    /// (for example, because it refers to no patterns), a literal pattern is
+    /// empty, a signature has no literal byte or the underlying automaton
+    /// could not be built.
+    pub fn build(&self) -> Result<RuleSet, RuleSetError> {
+        let mut names: Vec<String> = vec![];
+        let mut signatures = vec![];
+        for (name, def) in self.patterns.iter() {
+            if names.contains(name) {
+                return Err(RuleSetError::new(ErrorKind::DuplicatePattern {
+                    name: name.clone(),
+                }));
+            }
+            let sig = match *def {
+                PatternDef::Literal(ref bytes) => Signature::literal(bytes)
+                    .map_err(|err| {
+                        RuleSetError::new(ErrorKind::InvalidPattern {
+                            name: name.clone(),
+                            err,
+                        })
+                    })?,
+                PatternDef::Signature(ref sig) => sig.clone(),
+            };
+            names.push(name.clone());
+            signatures.push(sig);
+        }
+
+        let mut checks: Vec<Option<Check>> = vec![None; names.len()];
+        for (name, check) in self.checks.iter() {
+            match names.iter().position(|n| n == name) {
+                Some(pid) => checks[pid] = Some(check.clone()),
+                None => {
+                    return Err(RuleSetError::new(ErrorKind::UnknownCheck {
+                        name: name.clone(),
+                    }))
+                }
+            }
+        }
+
+        let mut rules = vec![];
+        let mut by_pattern: Vec<Vec<usize>> = vec![vec![]; names.len()];
+        for (rule_id, rule) in self.rules.iter().enumerate() {
+            let mut patterns = vec![];
+            for (name, _) in rule.patterns.iter() {
+                let pid = match names.iter().position(|n| n == name) {
+                    Some(pid) => pid,
+                    None => {
+                        return Err(RuleSetError::new(
+                            ErrorKind::UnknownPattern {
+                                rule: rule.name.clone(),
+                                name: name.clone(),
+                            },
+                        ))
+                    }
+                };
+                if !by_pattern[pid].contains(&rule_id) {
+                    by_pattern[pid].push(rule_id);
+                }
+                patterns.push(pid);
+            }
+            let weights: Vec<u64> =
+                rule.patterns.iter().map(|p| p.1).collect();
+            let satisfiable = !patterns.is_empty()
+                && match rule.condition {
+                    Condition::All | Condition::Ordered => true,
+                    Condition::AtLeast(count) => count <= patterns.len(),
+                    Condition::Weighted(threshold) => {
+                        weights.iter().sum::<u64>() >= threshold
+                    }
+                };
+            if !satisfiable {
+                return Err(RuleSetError::new(ErrorKind::Unsatisfiable {
+                    rule: rule.name.clone(),
+                }));
+            }
+            rules.push(CompiledRule {
+                name: rule.name.clone(),
+                patterns,
+                weights,
+                condition: rule.condition,
+                min_count: rule.min_count,
+                within: rule.within,
+            });
+        }
+        let searcher =
+            SignatureSearcher::build(&self.automaton, signatures)
+                .map_err(|err| RuleSetError::new(ErrorKind::Build(err)))?;
+        Ok(RuleSet { searcher, names, checks, rules, by_pattern })
+    }
+
+    /// Add a pattern that matches the given bytes literally.
+    pub fn pattern<B: AsRef<[u8]>>(
+        &mut self,
+        name: &str,
+        bytes: B,
+    ) -> &mut RuleSetBuilder {
+        let def = PatternDef::Literal(bytes.as_ref().to_vec());
+        self.patterns.push((name.to_string(), def));
+        self
+    }
+
+    /// Add a pattern that matches the given byte signature.
+    pub fn signature(
+        &mut self,
+        name: &str,
+        signature: Signature,
+    ) -> &mut RuleSetBuilder {
+        let def = PatternDef::Signature(signature);
+        self.patterns.push((name.to_string(), def));
+        self
+    }
+
+    /// Add a check that every match of the named pattern must pass before it
+    /// counts towards any rule.
+    ///
+    /// The check is given the bytes of the match, and the match is ignored
+    /// if it returns false. This is useful for rejecting false positives
+    /// that can't be expressed by the pattern itself, for example, by
+    /// computing the entropy of a candidate secret. If more than one check is
+    /// added for the same pattern, only the last one is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{scanner::{Rule, RuleSet}, signature::Signature};
+    ///
+    /// let rules = RuleSet::builder()
+    ///     .signature("pin", Signature::new(r#""pin=" 3? 3? 3? 3?"#)?)
+    ///     .verify("pin", |bytes| !bytes.ends_with(b"0000"))
+    ///     .rule(Rule::all("pin", ["pin"]))
+    ///     .build()?;
+    /// assert!(!rules.scan("pin=0000")[0].is_match());
+    /// assert!(rules.scan("pin=1234")[0].is_match());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn verify<F>(&mut self, name: &str, check: F) -> &mut RuleSetBuilder
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.checks.push((name.to_string(), Check(Arc::new(check))));
+        self
+    }
+
+    /// Add a rule.
+    pub fn rule(&mut self, rule: Rule) -> &mut RuleSetBuilder {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Set the builder used to configure the Aho-Corasick automaton that
+    /// underlies the rule set.
+    ///
+    /// See
+    /// [`AhoCorasickBuilder::build_signatures`](crate::AhoCorasickBuilder::build_signatures)
+    /// for which options are respected.
+    pub fn automaton(
+        &mut self,
+        builder: AhoCorasickBuilder,
+    ) -> &mut RuleSetBuilder {
+        self.automaton = builder;
+        self
+    }
+}
+
+/// A compiled set of patterns and rules.
+///
+/// Use [`RuleSet::scanner`] to scan a stream incrementally, or
+/// [`RuleSet::scan`] to scan a haystack that is entirely in memory. See the
+/// [module documentation](self) for more details.
+#[derive(Clone, Debug)]
+pub struct RuleSet {
+    searcher: SignatureSearcher,
+    /// The name of each pattern, indexed by pattern ID.
+    names: Vec<String>,
+    /// The check for each pattern, if any, indexed by pattern ID.
+    checks: Vec<Option<Check>>,
+    rules: Vec<CompiledRule>,
+    /// The rules that refer to each pattern, indexed by pattern ID.
+    by_pattern: Vec<Vec<usize>>,
+}
+
+/// A rule whose pattern names have been resolved to pattern IDs.
+#[derive(Clone, Debug)]
+struct CompiledRule {
+    name: String,
+    patterns: Vec<usize>,
+    weights: Vec<u64>,
+    condition: Condition,
+    min_count: usize,
+    within: Option<usize>,
+}
+
+impl RuleSet {
+    /// Create a new builder for a rule set.
+    pub fn builder() -> RuleSetBuilder {
+        RuleSetBuilder::new()
+    }
+
+    /// Returns the total number of rules in this rule set.
+    pub fn rules_len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns the name of the rule with the given ID. The ID of a rule
+    /// corresponds to the order in which it was added to the builder.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the ID is greater than or equal to
+    /// [`RuleSet::rules_len`].
+    pub fn rule_name(&self, rule: usize) -> &str {
+        &self.rules[rule].name
+    }
+
+    /// Returns the name of the pattern with the given ID. The ID of a pattern
+    /// corresponds to the order in which it was added to the builder.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the ID does not refer to a pattern in this rule set.
+    pub fn pattern_name(&self, pattern: usize) -> &str {
+        &self.names[pattern]
+    }
+
+    /// Create a scanner for incrementally scanning a stream with this rule
+    /// set.
+    pub fn scanner(&self) -> Scanner<'_> {
+        Scanner { eval: Evaluator::new(self), window: StreamWindow::new() }
+    }
+
+    /// Scan the given haystack and return a verdict for every rule, in order
+    /// of rule ID.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::scanner::{Rule, RuleSet};
+    ///
+    /// let rules = RuleSet::builder()
+    ///     .pattern("a", "foo")
+    ///     .pattern("b", "bar")
+    ///     .pattern("c", "quux")
+    ///     .rule(Rule::at_least("two", 2, ["a", "b", "c"]))
+    ///     .rule(Rule::weighted("heavy", 10, [("a", 3), ("b", 3), ("c", 7)]))
+    ///     .build()?;
+    /// let verdicts = rules.scan("foo bar baz");
+    /// assert!(verdicts[0].is_match());
+    /// assert_eq!(Some(7), verdicts[0].offset());
+    /// assert!(!verdicts[1].is_match());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn scan<H: ?Sized + AsRef<[u8]>>(&self, haystack: &H) -> Vec<Verdict> {
+        let haystack = haystack.as_ref();
+        let mut eval = Evaluator::new(self);
+        for m in self.searcher.find_iter(haystack) {
+            eval.add(m, &haystack[m.range()], &mut vec![]);
+        }
+        eval.verdicts()
+    }
+
+    /// Returns an iterator over every match of every pattern in the given
+    /// haystack that passes the check for its pattern, if any.
+    ///
+    /// This reports where each pattern matched, independent of any rule.
+    /// Matches are reported in the same order as
+    /// [`SignatureSearcher::find_iter`](crate::signature::SignatureSearcher::find_iter),
+    /// where the ID of each match's pattern corresponds to the order in which
+    /// the pattern was added to the builder. Use [`RuleSet::pattern_name`]
+    /// to get its name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{scanner::RuleSet, Match};
+    ///
+    /// let rules = RuleSet::builder()
+    ///     .pattern("dollar", "$")
+    ///     .pattern("euro", "€")
+    ///     .verify("dollar", |_| false)
+    ///     .build()?;
+    /// let matches: Vec<Match> = rules.find_iter("$1 €2").collect();
+    /// assert_eq!(vec![Match::must(1, 3..6)], matches);
+    /// assert_eq!("euro", rules.pattern_name(matches[0].pattern().as_usize()));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn find_iter<'r, 'h, H: ?Sized + AsRef<[u8]>>(
+        &'r self,
+        haystack: &'h H,
+    ) -> FindIter<'r, 'h> {
+        let haystack = haystack.as_ref();
+        FindIter {
+            ruleset: self,
+            haystack,
+            it: self.searcher.find_iter(haystack),
+        }
+    }
+
+    /// Returns true if and only if the given match passes the check for its
+    /// pattern, if any.
+    fn check(&self, m: Match, bytes: &[u8]) -> bool {
+        match self.checks[m.pattern().as_usize()] {
+            Some(Check(ref check)) => check(bytes),
+            None => true,
+        }
+    }
+
+    /// Scan the given stream and return a verdict for every rule, in order
+    /// of rule ID.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if reading from the stream fails.
+    #[cfg(feature = "std")]
+    pub fn scan_stream<R: std::io::Read>(
+        &self,
+        mut rdr: R,
+    ) -> std::io::Result<Vec<Verdict>> {
+        let mut scanner = self.scanner();
+        let mut buf = vec![0; crate::signature::CHUNK_LEN];
+        loop {
+            match rdr.read(&mut buf) {
+                Ok(0) => return Ok(scanner.finish()),
+                Ok(n) => {
+                    scanner.write(&buf[..n]);
+                }
+                Err(ref err)
+                    if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// An iterator over the matches in a haystack that pass the checks of a
+/// [`RuleSet`].
+///
+/// This iterator is constructed via [`RuleSet::find_iter`].
+///
+/// `'r` refers to the lifetime of the rule set, and `'h` refers to the
+/// lifetime of the haystack.
+#[derive(Debug)]
+pub struct FindIter<'r, 'h> {
+    ruleset: &'r RuleSet,
+    haystack: &'h [u8],
+    it: crate::signature::FindIter<'r, 'h>,
+}
+
+impl<'r, 'h> Iterator for FindIter<'r, 'h> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let (ruleset, haystack) = (self.ruleset, self.haystack);
+        self.it.find(|&m| ruleset.check(m, &haystack[m.range()]))
+    }
+}
+
+/// A scanner for incrementally computing the verdicts of a [`RuleSet`] on a
+/// stream.
+///
+/// Chunks of the stream are given to [`Scanner::write`], which reports the
+/// rules that are satisfied as a result. Once the stream has ended,
+/// [`Scanner::finish`] returns a verdict for every rule.
+///
+/// Only a bounded amount of the stream is buffered, but the scanner keeps
+/// state for each rule, including the matches that may still contribute to
+/// rules constrained by [`Rule::within`].
+///
+/// `'r` refers to the lifetime of the rule set.
+#[derive(Clone, Debug)]
+pub struct Scanner<'r> {
+    eval: Evaluator<'r>,
+    window: StreamWindow,
+}
+
+impl<'r> Scanner<'r> {
+    /// Scan the next chunk of the stream, and return a verdict for each rule
+    /// that was satisfied for the first time as a result.
+    ///
+    /// Since a match may not be known until more of the stream is seen, the
+    /// verdicts returned may be for rules satisfied by matches in previous
+    /// chunks.
+    pub fn write(&mut self, chunk: &[u8]) -> Vec<Verdict> {
+        let mut verdicts = vec![];
+        self.window.push(chunk);
+        let eval = &mut self.eval;
+        self.window.search(&eval.ruleset.searcher, false, |m, bytes| {
+            eval.add(m, bytes, &mut verdicts)
+        });
+        verdicts
+    }
+
+    /// Finish scanning the stream, and return a verdict for every rule, in
+    /// order of rule ID.
+    pub fn finish(mut self) -> Vec<Verdict> {
+        let eval = &mut self.eval;
+        self.window.search(&eval.ruleset.searcher, true, |m, bytes| {
+            eval.add(m, bytes, &mut vec![])
+        });
+        self.eval.verdicts()
+    }
+
+    /// Returns the total number of bytes written to this scanner.
+    pub fn offset(&self) -> usize {
+        self.window.end()
+    }
+}
+
+/// The verdict of a single rule.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Verdict {
+    rule: usize,
+    offset: Option<usize>,
+}
+
+impl Verdict {
+    /// Returns the ID of the rule this verdict is for.
+    pub fn rule(&self) -> usize {
+        self.rule
+    }
+
+    /// Returns true if and only if the rule was satisfied.
+    pub fn is_match(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    /// Returns the offset at which the rule was first satisfied, if it was.
+    ///
+    /// This is the end offset of the match that completed the rule.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+/// Tracks the state of every rule in a rule set as matches are found.
+#[derive(Clone, Debug)]
+struct Evaluator<'r> {
+    ruleset: &'r RuleSet,
+    states: Vec<RuleState>,
+}
+
+/// The state of a single rule.
+#[derive(Clone, Debug)]
+struct RuleState {
+    progress: Progress,
+    /// The start offset and pattern ID of every recent match, for rules
+    /// constrained by proximity.
+    recent: VecDeque<(usize, usize)>,
+    /// The offset at which this rule was satisfied, if it has been.
+    satisfied: Option<usize>,
+}
+
+impl<'r> Evaluator<'r> {
+    fn new(ruleset: &'r RuleSet) -> Evaluator<'r> {
+        let states = ruleset
+            .rules
+            .iter()
+            .map(|rule| RuleState {
+                progress: Progress::new(rule),
+                recent: VecDeque::new(),
+                satisfied: None,
+            })
+            .collect();
+        Evaluator { ruleset, states }
+    }
+
+    /// Update the state of every rule that refers to the pattern of the given
+    /// match, unless the match fails the check for its pattern. Matches must
+    /// be given in order of their starting offset, along with the bytes they
+    /// matched.
+    ///
+    /// A verdict is added for every rule that becomes satisfied.
+    fn add(&mut self, m: Match, bytes: &[u8], verdicts: &mut Vec<Verdict>) {
+        if !self.ruleset.check(m, bytes) {
+            return;
+        }
+        for &rule_id in self.ruleset.by_pattern[m.pattern().as_usize()].iter()
+        {
+            let rule = &self.ruleset.rules[rule_id];
+            let state = &mut self.states[rule_id];
+            if state.satisfied.is_some() {
+                continue;
+            }
+            let satisfied = match rule.within {
+                None => {
+                    state.progress.add(rule, m.pattern().as_usize());
+                    state.progress.is_satisfied(rule)
+                }
+                Some(len) => {
+                    state
+                        .recent
+                        .push_back((m.start(), m.pattern().as_usize()));
+                    while state.recent[0].0.saturating_add(len) < m.start() {
+                        state.recent.pop_front();
+                    }
+                    let mut progress = Progress::new(rule);
+                    for &(_, pid) in state.recent.iter() {
+                        progress.add(rule, pid);
+                    }
+                    progress.is_satisfied(rule)
+                }
+            };
+            if satisfied {
+                state.satisfied = Some(m.end());
+                state.recent.clear();
+                verdicts
+                    .push(Verdict { rule: rule_id, offset: Some(m.end()) });
+            }
+        }
+    }
+
+    fn verdicts(&self) -> Vec<Verdict> {
+        self.states
+            .iter()
+            .enumerate()
+            .map(|(rule, state)| Verdict { rule, offset: state.satisfied })
+            .collect()
+    }
+}
+
+/// The progress made towards satisfying a rule by a sequence of matches.
+#[derive(Clone, Debug)]
+struct Progress {
+    /// The number of matches of the pattern at each position in the rule.
+    counts: Vec<usize>,
+    /// For ordered rules, the position of the next pattern that needs to
+    /// match, and the number of times it has matched so far.
+    next: (usize, usize),
+}
+
+impl Progress {
+    fn new(rule: &CompiledRule) -> Progress {
+        Progress { counts: vec![0; rule.patterns.len()], next: (0, 0) }
+    }
+
+    fn add(&mut self, rule: &CompiledRule, pid: usize) {
+        for (i, &p) in rule.patterns.iter().enumerate() {
+            if p == pid {
+                self.counts[i] += 1;
+            }
+        }
+        let (pos, count) = self.next;
+        if rule.patterns.get(pos) == Some(&pid) {
+            self.next = if count + 1 >= rule.min_count {
+                (pos + 1, 0)
+            } else {
+                (pos, count + 1)
+            };
+        }
+    }
+
+    fn is_satisfied(&self, rule: &CompiledRule) -> bool {
+        let present = |i: usize| self.counts[i] >= rule.min_count;
+        match rule.condition {
+            Condition::All => (0..self.counts.len()).all(present),
+            Condition::Ordered => self.next.0 >= rule.patterns.len(),
+            Condition::AtLeast(count) => {
+                (0..self.counts.len()).filter(|&i| present(i)).count() >= count
+            }
+            Condition::Weighted(threshold) => {
+                let weight: u64 = (0..self.counts.len())
+                    .filter(|&i| present(i))
+                    .map(|i| rule.weights[i])
+                    .sum();
+                weight >= threshold
+            }
+        }
+    }
+}
+
+/// An error that occurred while building a [`RuleSet`].
+///
+/// When the `std` feature is enabled, this implements the `std::error::Error`
+/// trait.
+#[derive(Clone, Debug)]
+pub struct RuleSetError {
+    kind: ErrorKind,
+}
+
+/// The kind of error that occurred.
+#[derive(Clone, Debug)]
+enum ErrorKind {
+    /// Two patterns were given the same name.
+    DuplicatePattern {
+        /// The name of the patterns.
+        name: String,
+    },
+    /// A rule refers to a pattern that doesn't exist.
+    UnknownPattern {
+        /// The name of the rule.
+        rule: String,
+        /// The name of the pattern that doesn't exist.
+        name: String,
+    },
+    /// A check refers to a pattern that doesn't exist.
+    UnknownCheck {
+        /// The name of the pattern that doesn't exist.
+        name: String,
+    },
+    /// A rule can never be satisfied.
+    Unsatisfiable {
+        /// The name of the rule.
+        rule: String,
+    },
+    /// A pattern isn't valid.
+    InvalidPattern {
+        /// The name of the pattern.
+        name: String,
+        /// The reason the pattern isn't valid.
+        err: SignatureError,
+    },
+    /// The underlying automaton could not be built.
+    Build(BuildError),
+}
+
+impl RuleSetError {
+    fn new(kind: ErrorKind) -> RuleSetError {
+        RuleSetError { kind }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RuleSetError {}
+
+impl core::fmt::Display for RuleSetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            ErrorKind::DuplicatePattern { ref name } => {
+                write!(f, "duplicate pattern name {:?}", name)
+            }
+            ErrorKind::UnknownPattern { ref rule, ref name } => {
+                write!(
+                    f,
+                    "rule {:?} refers to unknown pattern {:?}",
+                    rule, name
+                )
+            }
+            ErrorKind::UnknownCheck { ref name } => {
+                write!(f, "check refers to unknown pattern {:?}", name)
+            }
+            ErrorKind::Unsatisfiable { ref rule } => {
+                write!(f, "rule {:?} can never be satisfied", rule)
+            }
+            ErrorKind::InvalidPattern { ref name, ref err } => {
+                write!(f, "invalid pattern {:?}: {}", name, err)
+            }
+            ErrorKind::Build(ref err) => {
+                write!(f, "failed to build rule set: {}", err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(verdicts: &[Verdict]) -> Vec<Option<usize>> {
+        verdicts.iter().map(|v| v.offset()).collect()
+    }
+
+    #[test]
+    fn conditions() {
+        let rules = RuleSet::builder()
+            .pattern("a", "a")
+            .pattern("b", "b")
+            .pattern("c", "c")
+            .rule(Rule::all("all", ["a", "b"]))
+            .rule(Rule::ordered("ordered", ["b", "a"]))
+            .rule(Rule::at_least("two", 2, ["a", "b", "c"]))
+            .rule(Rule::weighted("weighted", 5, [("a", 1), ("c", 4)]))
+            .rule(Rule::all("twice", ["a"]).min_count(2))
+            .rule(Rule::ordered("twice-ordered", ["a", "b"]).min_count(2))
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec![Some(4), Some(7), Some(4), None, Some(7), None],
+            matched(&rules.scan("xa bxxa")),
+        );
+        assert_eq!(
+            vec![Some(3), Some(5), Some(2), Some(2), Some(5), Some(8)],
+            matched(&rules.scan("acb a bb")),
+        );
+        assert_eq!(vec![None; 6], matched(&rules.scan("")));
+    }
+
+    #[test]
+    fn within() {
+        let rules = RuleSet::builder()
+            .pattern("a", "a")
+            .pattern("b", "b")
+            .rule(Rule::all("near", ["a", "b"]).within(3))
+            .rule(Rule::ordered("near-ordered", ["a", "b"]).within(3))
+            .build()
+            .unwrap();
+        assert_eq!(vec![None, None], matched(&rules.scan("a    b")));
+        assert_eq!(vec![Some(6), None], matched(&rules.scan("a   ba")));
+        assert_eq!(
+            vec![Some(11), Some(11)],
+            matched(&rules.scan("b   a    ab")),
+        );
+    }
+
+    #[test]
+    fn streaming() {
+        let rules = RuleSet::builder()
+            .signature("sig", "DE ?? BE EF".parse().unwrap())
+            .pattern("nul", b"\x00\x00")
+            .rule(Rule::ordered("both", ["sig", "nul"]))
+            .rule(Rule::all("nul", ["nul"]).min_count(3))
+            .build()
+            .unwrap();
+        let haystack = b"\x00\x00 \xDE\xAD\xBE\xEF \x00\x00 \x00\x00\x00";
+        let expected = matched(&rules.scan(haystack));
+        assert_eq!(vec![Some(10), Some(13)], expected);
+        for chunk_len in 1..=haystack.len() {
+            let mut scanner = rules.scanner();
+            let mut early = vec![];
+            for chunk in haystack.chunks(chunk_len) {
+                early.extend(scanner.write(chunk));
+            }
+            assert_eq!(haystack.len(), scanner.offset());
+            let verdicts = scanner.finish();
+            for v in early {
+                assert_eq!(verdicts[v.rule()], v);
+            }
+            assert_eq!(
+                expected,
+                matched(&verdicts),
+                "chunk_len: {}",
+                chunk_len
+            );
+        }
+        #[cfg(feature = "std")]
+        {
+            let rdr = std::io::Cursor::new(&haystack[..]);
+            let verdicts = rules.scan_stream(rdr).unwrap();
+            assert_eq!(expected, matched(&verdicts));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let err = RuleSet::builder()
+            .pattern("a", "a")
+            .pattern("a", "b")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::DuplicatePattern { .. }));
+
+        let err = RuleSet::builder()
+            .pattern("a", "a")
+            .rule(Rule::all("r", ["a", "b"]))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            "rule \"r\" refers to unknown pattern \"b\"",
+            err.to_string(),
+        );
+
+        let err = RuleSet::builder().pattern("a", "").build().unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidPattern { .. }));
+
+        for rule in [
+            Rule::all("r", Vec::<&str>::new()),
+            Rule::at_least("r", 2, ["a"]),
+            Rule::weighted("r", 3, [("a", 2)]),
+        ] {
+            let err = RuleSet::builder()
+                .pattern("a", "a")
+                .rule(rule)
+                .build()
+                .unwrap_err();
+            assert!(matches!(err.kind, ErrorKind::Unsatisfiable { .. }));
+        }
+    }
+}
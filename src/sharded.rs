@@ -0,0 +1,638 @@
+/*!
+Provides a searcher that splits its patterns into several automata, according
+to the first byte of each pattern.
+
+A [`ShardedSearcher`] is built with
+[`AhoCorasickBuilder::build_sharded`](crate::AhoCorasickBuilder::build_sharded).
+Every pattern is assigned to exactly one shard, based on its first byte, and
+each shard is an independent [`AhoCorasick`] automaton built with the same
+configuration. This keeps each shard small, and permits a server to place
+shards on different NUMA nodes, for example by having a thread pinned to each
+node search with the shards it owns.
+
+A search only consults the shards that can possibly match. Namely, an
+anchored search only consults the shard for the byte at the start of the
+search, and an unanchored search only consults the shards with at least one
+pattern that starts with a byte that occurs in the haystack. Empty patterns
+are always assigned to the first shard, which is therefore always consulted
+when there are any.
+
+# Match semantics
+
+A sharded searcher reports exactly the same matches as an [`AhoCorasick`]
+automaton built from all of the patterns with the same configuration. This is
+possible because two matches starting at the same offset always start with
+the same byte, and thus come from the same shard (except for matches of empty
+patterns). So the match of each shard can be combined into the match the
+single automaton would have reported:
+
+* With [`MatchKind::Standard`], the match that ends first is reported, and
+  among matches ending at the same offset, the longest one is reported.
+* With [`MatchKind::LeftmostFirst`], the match that starts first is reported,
+  and among matches starting at the same offset, the one whose pattern was
+  given first is reported.
+* With [`MatchKind::LeftmostLongest`], the match that starts first is
+  reported, and among matches starting at the same offset, the longest one is
+  reported.
+
+The one exception is leftmost match semantics with an empty pattern. A single
+automaton may skip the empty match at an offset where a longer pattern starts
+to match but then fails. For example, with [`MatchKind::LeftmostLongest`] and
+the patterns `["", "a", "xyz"]`, a single automaton only reports the empty
+matches at `2` and `3` in `xyw`, while a sharded searcher with `xyz` in a
+shard of its own also reports the ones at `0` and `1`.
+
+Only non-overlapping searches are supported. An [`Input`] configured to stop
+at the earliest match is still guaranteed to find a match if there is one,
+but which match it finds may differ from a single automaton. A search
+restricted to a [`PatternSet`](crate::PatternSet) translates the set to the
+shards it consults on every call, which takes time proportional to their
+number of patterns.
+
+# Example
+
+```
+use aho_corasick::{AhoCorasick, MatchKind};
+
+let patterns = &["Samwise", "Sam", "Frodo", "Pippin", "Merry"];
+let haystack = "Merry and Pippin followed Frodo, but Samwise was first.";
+
+let mut builder = AhoCorasick::builder();
+builder.match_kind(MatchKind::LeftmostFirst);
+let sharded = builder.build_sharded(patterns, 3)?;
+assert_eq!(3, sharded.shards_len());
+
+let single = builder.build(patterns)?;
+assert_eq!(
+    single.find_iter(haystack).collect::<Vec<_>>(),
+    sharded.find_iter(haystack).collect::<Vec<_>>(),
+);
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    ahocorasick::{
+        enforce_anchored_consistency, AhoCorasick, AhoCorasickBuilder,
+    },
+    util::{
+        error::{BuildError, MatchError},
+        pattern_set::PatternSet,
+        primitives::PatternID,
+        search::{Input, Match, MatchKind},
+    },
+};
+
+/// A searcher whose patterns are split into several automata by their first
+/// byte.
+///
+/// See the [module documentation](self) for more details about how a
+/// searcher is built and the semantics of the matches it reports.
+#[derive(Clone, Debug)]
+pub struct ShardedSearcher {
+    shards: Vec<Shard>,
+    /// The shard that each byte is assigned to, if any pattern starts with
+    /// it.
+    byte_to_shard: [Option<usize>; 256],
+    match_kind: MatchKind,
+    patterns_len: usize,
+}
+
+/// A single shard of a sharded searcher.
+#[derive(Clone, Debug)]
+struct Shard {
+    ac: AhoCorasick,
+    /// The ID of each pattern of this shard in the sharded searcher. Pattern
+    /// `i` in `ac` corresponds to `patterns[i]`.
+    patterns: Vec<PatternID>,
+    /// Whether this shard has an empty pattern.
+    has_empty: bool,
+}
+
+impl ShardedSearcher {
+    /// Build a searcher with at most `shards` shards, where the automaton of
+    /// each shard is configured by the given builder.
+    ///
+    /// First bytes are assigned to shards such that each shard has roughly
+    /// the same number of patterns. With ASCII case insensitivity, both cases
+    /// of a letter are assigned to the same shard, since patterns starting
+    /// with either case match the same haystacks. A searcher always has at
+    /// least one shard.
+    pub(crate) fn build<I, P>(
+        builder: &AhoCorasickBuilder,
+        patterns: I,
+        shards: usize,
+    ) -> Result<ShardedSearcher, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let ascii_case_insensitive = builder.get_ascii_case_insensitive();
+        let key = |byte: u8| {
+            if ascii_case_insensitive {
+                byte.to_ascii_lowercase()
+            } else {
+                byte
+            }
+        };
+        let patterns: Vec<P> = patterns.into_iter().collect();
+        let mut counts = [0usize; 256];
+        for p in patterns.iter() {
+            if let Some(&b) = p.as_ref().first() {
+                counts[usize::from(key(b))] += 1;
+            }
+        }
+        // Assign the most common first bytes first, each to the shard with
+        // the fewest patterns so far.
+        let mut keys: Vec<u8> =
+            (0..=255u8).filter(|&b| counts[usize::from(b)] > 0).collect();
+        keys.sort_by_key(|&b| (core::cmp::Reverse(counts[usize::from(b)]), b));
+        let mut loads = vec![0usize; core::cmp::max(1, shards)];
+        let mut key_to_shard = [None; 256];
+        for &b in keys.iter() {
+            let (shard, _) = loads
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, &load)| load)
+                .unwrap();
+            loads[shard] += counts[usize::from(b)];
+            key_to_shard[usize::from(b)] = Some(shard);
+        }
+
+        let mut members: Vec<Vec<PatternID>> = vec![vec![]; loads.len()];
+        for (pid, p) in PatternID::iter(patterns.len()).zip(patterns.iter()) {
+            let shard = match p.as_ref().first() {
+                None => 0,
+                Some(&b) => key_to_shard[usize::from(key(b))].unwrap(),
+            };
+            members[shard].push(pid);
+        }
+        // Shards without any patterns are dropped, except when there are no
+        // patterns at all, since a searcher always has at least one shard.
+        let mut remap = vec![None; members.len()];
+        let mut built = vec![];
+        for (shard, pids) in members.into_iter().enumerate() {
+            if pids.is_empty() && !(shard == 0 && patterns.is_empty()) {
+                continue;
+            }
+            // Pattern IDs are local to each shard, and so are lookaheads.
+            let subset = builder.for_pattern_subset(&pids);
+            let ac = subset
+                .as_ref()
+                .unwrap_or(builder)
+                .build(
+                    pids.iter().map(|pid| patterns[pid.as_usize()].as_ref()),
+                )
+                .map_err(|err| err.map_pattern_ids(|pid| pids[pid]))?;
+            let has_empty = pids
+                .iter()
+                .any(|pid| patterns[pid.as_usize()].as_ref().is_empty());
+            remap[shard] = Some(built.len());
+            built.push(Shard { ac, patterns: pids, has_empty });
+        }
+        let mut byte_to_shard = [None; 256];
+        // A haystack byte is matched against the patterns as translated by
+        // the translation table, if any.
+        let table = builder.get_translation_table();
+        for b in 0..=255u8 {
+            let t = table.map_or(b, |table| table[usize::from(b)]);
+            byte_to_shard[usize::from(b)] =
+                key_to_shard[usize::from(key(t))].and_then(|s| remap[s]);
+        }
+        Ok(ShardedSearcher {
+            shards: built,
+            byte_to_shard,
+            match_kind: builder.get_match_kind(),
+            patterns_len: patterns.len(),
+        })
+    }
+
+    /// Returns the number of shards in this searcher.
+    pub fn shards_len(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the automaton of the shard at the given index.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the index is not less than
+    /// [`ShardedSearcher::shards_len`].
+    pub fn shard(&self, index: usize) -> &AhoCorasick {
+        &self.shards[index].ac
+    }
+
+    /// Returns the IDs, in this searcher, of the patterns of the shard at
+    /// the given index. Pattern `i` of the shard's automaton has the ID at
+    /// index `i`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the index is not less than
+    /// [`ShardedSearcher::shards_len`].
+    pub fn shard_patterns(&self, index: usize) -> &[PatternID] {
+        &self.shards[index].patterns
+    }
+
+    /// Returns the total number of patterns in this searcher.
+    pub fn patterns_len(&self) -> usize {
+        self.patterns_len
+    }
+
+    /// Returns the match semantics used by this searcher.
+    pub fn match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Returns the approximate total amount of heap used by the automata of
+    /// all shards, in units of bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.shards.iter().map(|s| s.ac.memory_usage()).sum()
+    }
+
+    /// Returns true if and only if any pattern matches the given haystack.
+    ///
+    /// # Panics
+    ///
+    /// This panics when the search is not supported by the configuration of
+    /// this searcher, such as an anchored search when the searcher was built
+    /// without support for them. Use [`ShardedSearcher::try_find`] to handle
+    /// these errors.
+    pub fn is_match<'h, I: Into<Input<'h>>>(&self, input: I) -> bool {
+        let input = input.into().earliest(true);
+        self.try_find(input).unwrap().is_some()
+    }
+
+    /// Returns the location of the first match according to the match
+    /// semantics of this searcher.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`ShardedSearcher::is_match`].
+    pub fn find<'h, I: Into<Input<'h>>>(&self, input: I) -> Option<Match> {
+        self.try_find(input).unwrap()
+    }
+
+    /// Returns an iterator of non-overlapping matches, using the match
+    /// semantics of this searcher.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`ShardedSearcher::is_match`].
+    pub fn find_iter<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> FindIter<'a, 'h> {
+        self.try_find_iter(input).unwrap()
+    }
+
+    /// Returns the location of the first match according to the match
+    /// semantics of this searcher.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when the search is not supported by the
+    /// configuration of the shards, in the same way as
+    /// [`AhoCorasick::try_find`].
+    pub fn try_find<'h, I: Into<Input<'h>>>(
+        &self,
+        input: I,
+    ) -> Result<Option<Match>, MatchError> {
+        let input = input.into();
+        self.check_input(&input)?;
+        let mut best = None;
+        for index in self.relevant_shards(&input) {
+            let m = self.search_shard(index, &input)?;
+            best = leftmost(self.match_kind, best, m);
+        }
+        Ok(best)
+    }
+
+    /// Returns an iterator of non-overlapping matches, using the match
+    /// semantics of this searcher.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`ShardedSearcher::try_find`].
+    pub fn try_find_iter<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> Result<FindIter<'a, 'h>, MatchError> {
+        let input = input.into();
+        // This ensures that searches with this configuration never fail, so
+        // that the iterator never has to report an error.
+        self.check_input(&input)?;
+        let cache = if input.get_anchored().is_anchored() {
+            vec![]
+        } else {
+            self.relevant_shards(&input).map(|index| (index, None)).collect()
+        };
+        Ok(FindIter { searcher: self, input, last_match_end: None, cache })
+    }
+
+    /// Returns an error if the shards don't support searches with the given
+    /// input.
+    ///
+    /// Every shard is built with the same configuration, so it's enough to
+    /// check one of them, even if it's not consulted by the search.
+    fn check_input(&self, input: &Input<'_>) -> Result<(), MatchError> {
+        let start_kind = self.shards[0].ac.start_kind();
+        enforce_anchored_consistency(start_kind, input.get_anchored())
+    }
+
+    /// Returns the indices of the shards that could have a match for the
+    /// given input.
+    fn relevant_shards(
+        &self,
+        input: &Input<'_>,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let mut relevant = vec![false; self.shards.len()];
+        // The start of the span may be past its end once an iterator has
+        // skipped over an empty match at the end of the haystack.
+        let haystack = if input.is_done() {
+            &[]
+        } else {
+            &input.haystack()[input.get_span()]
+        };
+        let bytes = if input.get_anchored().is_anchored() {
+            &haystack[..core::cmp::min(1, haystack.len())]
+        } else {
+            haystack
+        };
+        let mut seen = [false; 256];
+        for &b in bytes {
+            seen[usize::from(b)] = true;
+        }
+        for (b, &seen) in seen.iter().enumerate() {
+            if let Some(index) = self.byte_to_shard[b].filter(|_| seen) {
+                relevant[index] = true;
+            }
+        }
+        for (index, shard) in self.shards.iter().enumerate() {
+            relevant[index] |= shard.has_empty;
+        }
+        relevant.into_iter().enumerate().filter(|&(_, r)| r).map(|(i, _)| i)
+    }
+
+    /// Search the shard at the given index, and return its match with the
+    /// ID of its pattern in this searcher.
+    fn search_shard(
+        &self,
+        index: usize,
+        input: &Input<'_>,
+    ) -> Result<Option<Match>, MatchError> {
+        let shard = &self.shards[index];
+        // A set of patterns refers to the IDs in this searcher, which have
+        // to be translated to the IDs in the shard.
+        let set: Option<PatternSet> = input.get_pattern_set().map(|set| {
+            PatternID::iter(shard.patterns.len())
+                .filter(|&pid| set.contains(shard.patterns[pid]))
+                .collect()
+        });
+        let mut input = input.clone();
+        if set.is_some() {
+            input.set_pattern_set(set.as_ref());
+        }
+        let m = shard.ac.try_find(input)?;
+        Ok(m.map(|m| Match::new(shard.patterns[m.pattern()], m.span())))
+    }
+}
+
+/// Returns whichever of the given matches would be reported first by a single
+/// automaton with the given match semantics.
+pub(crate) fn leftmost(
+    match_kind: MatchKind,
+    a: Option<Match>,
+    b: Option<Match>,
+) -> Option<Match> {
+    use core::cmp::Reverse;
+
+    let (a, b) = match (a, b) {
+        (None, m) | (m, None) => return m,
+        (Some(a), Some(b)) => (a, b),
+    };
+    let a_first = match match_kind {
+        MatchKind::Standard => {
+            (a.end(), a.start(), a.pattern())
+                <= (b.end(), b.start(), b.pattern())
+        }
+        MatchKind::LeftmostFirst => {
+            (a.start(), a.pattern()) <= (b.start(), b.pattern())
+        }
+        MatchKind::LeftmostLongest => {
+            (a.start(), Reverse(a.end()), a.pattern())
+                <= (b.start(), Reverse(b.end()), b.pattern())
+        }
+    };
+    Some(if a_first { a } else { b })
+}
+
+/// An iterator of non-overlapping matches of a [`ShardedSearcher`].
+///
+/// This iterator is constructed via [`ShardedSearcher::find_iter`] or
+/// [`ShardedSearcher::try_find_iter`].
+///
+/// The lifetime `'a` refers to the lifetime of the searcher, and the
+/// lifetime `'h` refers to the lifetime of the haystack being searched.
+#[derive(Debug)]
+pub struct FindIter<'a, 'h> {
+    searcher: &'a ShardedSearcher,
+    /// The input parameters to give to each search call. The start position
+    /// of the search is mutated during iteration.
+    input: Input<'h>,
+    /// The end offset of the most recent match, which is used to prevent
+    /// empty matches from overlapping with the end of a prior match.
+    last_match_end: Option<usize>,
+    /// For an unanchored search, the last result of each relevant shard.
+    ///
+    /// A search only ever moves forward, and the match a shard reports is
+    /// the same for any start offset that is not past the start of that
+    /// match. So a result is only recomputed once the search moves past the
+    /// start of its match. This means each shard scans its part of the
+    /// haystack roughly once, instead of once per match.
+    cache: Vec<(usize, Option<Option<Match>>)>,
+}
+
+impl<'a, 'h> FindIter<'a, 'h> {
+    /// Executes a search from the current start position, and returns a
+    /// match if one is found. This does not advance the input.
+    fn search(&mut self) -> Option<Match> {
+        // The unwraps are OK here because we check at iterator construction
+        // time that no search with the same configuration returns an error.
+        if self.input.get_anchored().is_anchored() {
+            return self.searcher.try_find(self.input.clone()).unwrap();
+        }
+        let start = self.input.start();
+        let mut best = None;
+        for &mut (index, ref mut cached) in self.cache.iter_mut() {
+            let m = match *cached {
+                Some(Some(m)) if m.start() >= start => Some(m),
+                Some(None) => None,
+                _ => {
+                    let m = self
+                        .searcher
+                        .search_shard(index, &self.input)
+                        .unwrap();
+                    *cached = Some(m);
+                    m
+                }
+            };
+            best = leftmost(self.searcher.match_kind, best, m);
+        }
+        best
+    }
+}
+
+impl<'a, 'h> Iterator for FindIter<'a, 'h> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        if self.input.is_done() {
+            return None;
+        }
+        let mut m = self.search()?;
+        if m.is_empty() && Some(m.end()) == self.last_match_end {
+            // As with AhoCorasick::find_iter, an empty match may not overlap
+            // with the end of the previous match.
+            self.input.set_start(self.input.start().checked_add(1).unwrap());
+            m = self.search()?;
+        }
+        self.input.set_start(m.end());
+        self.last_match_end = Some(m.end());
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        util::rng::Rng, AhoCorasick, Anchored, Input, MatchKind, StartKind,
+    };
+
+    #[test]
+    fn same_matches_as_single_automaton() {
+        let mut rng = Rng::new(0x9E3779B97F4A7C15);
+        for _ in 0..300 {
+            let patterns: Vec<Vec<u8>> = (0..1 + rng.below(8))
+                .map(|_| rng.word(0, 4, b"abcAB"))
+                .collect();
+            let haystack = rng.word(0, 30, b"abcAB");
+            let shards = 1 + rng.below(4);
+            for match_kind in [
+                MatchKind::Standard,
+                MatchKind::LeftmostFirst,
+                MatchKind::LeftmostLongest,
+            ] {
+                for ascii_case_insensitive in [false, true] {
+                    // See the module documentation about leftmost semantics
+                    // with empty patterns.
+                    if match_kind != MatchKind::Standard
+                        && patterns.iter().any(|p| p.is_empty())
+                    {
+                        continue;
+                    }
+                    let mut builder = AhoCorasick::builder();
+                    builder
+                        .match_kind(match_kind)
+                        .start_kind(StartKind::Both)
+                        .ascii_case_insensitive(ascii_case_insensitive);
+                    let single = builder.build(&patterns).unwrap();
+                    let sharded =
+                        builder.build_sharded(&patterns, shards).unwrap();
+                    assert!(sharded.shards_len() <= shards);
+                    for anchored in [Anchored::No, Anchored::Yes] {
+                        let at = core::cmp::min(3, haystack.len());
+                        let input = Input::new(&haystack).anchored(anchored);
+                        let msg = (&patterns, &haystack, match_kind, anchored);
+                        assert_eq!(
+                            single
+                                .find_iter(input.clone())
+                                .collect::<Vec<_>>(),
+                            sharded
+                                .find_iter(input.clone())
+                                .collect::<Vec<_>>(),
+                            "{:?}",
+                            msg,
+                        );
+                        assert_eq!(
+                            single.find(input.clone().range(at..)),
+                            sharded.find(input.clone().range(at..)),
+                            "{:?}",
+                            msg,
+                        );
+                        assert_eq!(
+                            single.is_match(input.clone()),
+                            sharded.is_match(input),
+                            "{:?}",
+                            msg,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shards_are_balanced() {
+        let patterns = ["a1", "a2", "a3", "b1", "b2", "c1", "d1", "", "A4"];
+        let sharded = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build_sharded(patterns, 3)
+            .unwrap();
+        let shards: Vec<Vec<usize>> = (0..sharded.shards_len())
+            .map(|i| {
+                sharded
+                    .shard_patterns(i)
+                    .iter()
+                    .map(|p| p.as_usize())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(vec![vec![0, 1, 2, 7, 8], vec![3, 4], vec![5, 6]], shards);
+        assert_eq!(9, sharded.patterns_len());
+        assert_eq!(2, sharded.shard(2).patterns_len());
+
+        let sharded =
+            AhoCorasick::builder().build_sharded(["a", "b"], 8).unwrap();
+        assert_eq!(2, sharded.shards_len());
+        let sharded = AhoCorasick::builder()
+            .build_sharded(Vec::<&str>::new(), 0)
+            .unwrap();
+        assert_eq!(1, sharded.shards_len());
+        assert_eq!(None, sharded.find("abc"));
+    }
+
+    #[test]
+    fn invalid_input() {
+        let sharded = AhoCorasick::builder().build_sharded(["a"], 2).unwrap();
+        let input = Input::new("a").anchored(Anchored::Yes);
+        assert!(sharded.try_find(input.clone()).is_err());
+        assert!(sharded.try_find_iter(input).is_err());
+        let input = Input::new("b").anchored(Anchored::Yes);
+        assert!(sharded.try_find(input).is_err());
+    }
+
+    #[test]
+    fn translated_haystack_bytes_select_shards() {
+        let mut table = [0u8; 256];
+        for b in 0..=255u8 {
+            table[usize::from(b)] = if b == b'x' { b'b' } else { b };
+        }
+        let sharded = AhoCorasick::builder()
+            .translation_table(Some(table))
+            .build_sharded(["ab", "ba"], 2)
+            .unwrap();
+        assert_eq!(2, sharded.shards_len());
+        assert_eq!(
+            vec![crate::Match::must(1, 0..2), crate::Match::must(0, 3..5)],
+            sharded.find_iter("xa ax").collect::<Vec<_>>(),
+        );
+    }
+}
@@ -0,0 +1,1650 @@
+/*!
+Provides byte signature scanning, where patterns may contain wildcard bytes.
+
+A byte signature is a sequence of hex encoded bytes, written in the style
+commonly used by malware and binary scanners. For example, `48 8B ?? 48 85`
+matches the bytes `0x48 0x8B`, followed by any byte, followed by
+`0x48 0x85`. A single nibble may also be a wildcard, so `4? 8B` matches any
+byte in the range `0x40..=0x4F` followed by `0x8B`.
+
+An alternation of single bytes matches any one of them. For example,
+`(0A | 0D | 2?)` matches `0x0A`, `0x0D` or any byte in the range
+`0x20..=0x2F`.
+
+Any byte (including wildcards and alternations) may be followed by a bounded
+repetition. For example, `AA BB 00{2,8} CC` matches `0xAA 0xBB`, followed by
+between two and eight `0x00` bytes, followed by `0xCC`. Similarly, `00{4}`
+matches exactly four `0x00` bytes and `??{0,16}` matches up to sixteen
+arbitrary bytes. The upper bound of a repetition may not exceed `4096`.
+
+Finally, a string literal in double quotes matches its UTF-8 encoding, e.g.,
+`"MZ"` is equivalent to `4D 5A`. Within a literal, whitespace is significant
+and `\"` and `\\` are the only escapes. A literal followed by `i`, such as
+`"kernel32.dll"i`, matches ASCII letters case insensitively. A repetition may
+not follow a literal.
+
+Hex digits may be written in either case, and whitespace outside of literals
+is ignored.
+
+A signature may also be built from a pattern of bytes and a mask with
+[`Signature::masked`], in which case only the bits set in the mask must match.
+For example, the mask `F0` on the byte `45` matches any byte in the range
+`0x40..=0x4F`, just like `4?`, but a mask like `80` can also match on a
+single bit. [`dictionary::parse_masked`](crate::dictionary::parse_masked)
+reads many such signatures from a text file.
+
+Signatures are usually parsed with [`Signature::new`] and then given to
+[`SignatureSearcher::new`]. Alternatively,
+[`AhoCorasickBuilder::build_signatures`](crate::AhoCorasickBuilder::build_signatures)
+does both in one step while also permitting configuration of the underlying
+automaton.
+
+Rather than exploding every wildcard into all of the literal patterns it could
+match (which quickly becomes infeasible), a [`SignatureSearcher`] picks the
+longest run of literal bytes in each signature as its *anchor* and builds a
+single Aho-Corasick automaton from the anchors. Every time an anchor is found,
+the rest of its signature is verified against the bytes surrounding it.
+
+Wildcards, alternations and masks are never compiled into the automaton, so
+only bytes that match a single value (or an ASCII letter in either case) can
+be part of an anchor. A search therefore costs one pass over the haystack to
+find the anchors, plus one verification per occurrence of an anchor, which
+takes time proportional to the length of its signature and to the range of
+its repetitions. This means searching is fastest when every signature has a
+reasonably long literal run, since a short anchor, such as a single common
+byte, may occur at a large fraction of the positions of a haystack. A
+signature without any literal byte, such as `?? 0?`, has no anchor at all
+and would have to be verified at every position, so building a searcher with
+one returns an error.
+
+# Match semantics
+
+A signature searcher reports every position at which any signature matches,
+including matches that overlap one another. Matches are reported in order of
+their starting offset, with ties broken by the ID of the signature. The ID of
+a signature corresponds to its position (starting from `0`) relative to the
+other signatures used to build the searcher.
+
+When a signature can match several lengths at the same starting offset (which
+is only possible when it contains a repetition), only the shortest match is
+reported.
+
+# Example
+
+```
+use aho_corasick::{signature::{Signature, SignatureSearcher}, Match};
+
+let searcher = SignatureSearcher::new([
+    Signature::new("48 8B ?? 48 85")?,
+    Signature::new("?? 85 C0")?,
+    Signature::new("AA BB 00{2,8} CC")?,
+])?;
+let haystack = b"\x00\x48\x8B\x05\x48\x85\xC0\xAA\xBB\x00\x00\x00\xCC";
+let matches: Vec<Match> = searcher.find_iter(haystack).collect();
+assert_eq!(
+    vec![Match::must(0, 1..6), Match::must(1, 4..7), Match::must(2, 7..13)],
+    matches,
+);
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::{
+    ahocorasick::{AhoCorasick, AhoCorasickBuilder},
+    util::{
+        error::BuildError,
+        search::{DuplicatePolicy, Input, Match, MatchKind, StartKind},
+    },
+};
+
+/// The number of starting offsets that are searched at a time.
+///
+/// Matches are buffered and sorted for each chunk, so this bounds the amount
+/// of memory needed for an iterator in addition to the haystack itself.
+pub(crate) const CHUNK_LEN: usize = 64 * (1 << 10); // 64 KB
+
+/// The maximum upper bound permitted in a repetition.
+const MAX_REPEAT: usize = 4096;
+
+/// A single parsed byte signature.
+///
+/// A signature is built from a string of hex encoded bytes, where either
+/// nibble of a byte may be replaced with a `?` wildcard, alternations and
+/// string literals may be used and any byte may be followed by a bounded
+/// repetition. See the [module documentation](self) for more details.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::signature::Signature;
+///
+/// let sig = Signature::new("de ad ?? ef")?;
+/// assert_eq!(4, sig.min_len());
+/// assert_eq!(4, sig.max_len());
+/// assert!(sig.is_match(b"\xDE\xAD\x00\xEF"));
+/// assert!(!sig.is_match(b"\xDE\xAD\x00\xEE"));
+///
+/// // Whitespace is optional, and a single nibble may be a wildcard.
+/// let sig = Signature::new("dead?5ef")?;
+/// assert!(sig.is_match(b"\xDE\xAD\x15\xEF"));
+/// assert!(!sig.is_match(b"\xDE\xAD\x16\xEF"));
+///
+/// // Repetitions make the length of a match variable.
+/// let sig = Signature::new("01 ??{1,3} 02")?;
+/// assert_eq!(3, sig.min_len());
+/// assert_eq!(5, sig.max_len());
+/// assert!(sig.is_match(b"\x01\x00\x00\x00\x02"));
+/// assert!(!sig.is_match(b"\x01\x02"));
+///
+/// // Alternations and case insensitive literals match one of several bytes.
+/// let sig = Signature::new(r#""MZ"i (90 | 00)"#)?;
+/// assert!(sig.is_match(b"mZ\x90"));
+/// assert!(!sig.is_match(b"MZ\x91"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signature {
+    elements: Vec<Element>,
+}
+
+impl Signature {
+    /// Parse a new signature from its hex encoded form.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the signature isn't valid according to the
+    /// syntax described in the [module documentation](self). For example,
+    /// when it contains an odd number of nibbles, or an alternation,
+    /// repetition or literal is malformed. An error is also returned if the
+    /// signature can match the empty string.
+    pub fn new(signature: &str) -> Result<Signature, SignatureError> {
+        let sig = Parser::new(signature).parse()?;
+        if sig.min_len() == 0 {
+            return Err(SignatureError::new(ErrorKind::Empty));
+        }
+        Ok(sig)
+    }
+
+    /// Parse a signature from the hex signature field of a ClamAV NDB
+    /// signature, such as `4d5a??{4}(00|ff)`.
+    ///
+    /// In addition to bytes, nibble wildcards and alternations of single
+    /// bytes, this supports the gaps `{n}`, `{n-m}` and `{-m}` of NDB
+    /// signatures. Unbounded gaps, such as `*` and `{n-}`, and all of the
+    /// other constructs of NDB signatures are rejected.
+    pub(crate) fn ndb(signature: &str) -> Result<Signature, SignatureError> {
+        let sig = Parser::new(signature).parse_ndb()?;
+        if sig.min_len() == 0 {
+            return Err(SignatureError::new(ErrorKind::Empty));
+        }
+        Ok(sig)
+    }
+
+    /// Create a signature that matches exactly the bytes given.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the bytes given are empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::signature::Signature;
+    ///
+    /// let sig = Signature::literal(b"PE\x00\x00")?;
+    /// assert_eq!(Signature::new("50 45 00 00")?, sig);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn literal(bytes: &[u8]) -> Result<Signature, SignatureError> {
+        if bytes.is_empty() {
+            return Err(SignatureError::new(ErrorKind::Empty));
+        }
+        let elements = bytes
+            .iter()
+            .map(|&b| Element { class: ByteClass::single(b), min: 1, max: 1 })
+            .collect();
+        Ok(Signature { elements })
+    }
+
+    /// Create a signature from a pattern of bytes and a mask of the same
+    /// length, where only the bits set in the mask must match.
+    ///
+    /// That is, the byte at index `i` of a haystack matches when `haystack[i]
+    /// & mask[i] == bytes[i] & mask[i]`. A mask byte of `0xFF` requires an
+    /// exact match and a mask byte of `0x00` matches any byte. This is useful
+    /// for matching fields of binary protocols that are smaller than a byte,
+    /// such as only the high nibble of a byte or a single flag bit.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the bytes given are empty, or if the mask
+    /// doesn't have the same length as the bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::signature::Signature;
+    ///
+    /// // Match 0x45 followed by any byte whose high bit is set.
+    /// let sig = Signature::masked(b"\x45\x80", b"\xFF\x80")?;
+    /// assert!(sig.is_match(b"\x45\x9C"));
+    /// assert!(!sig.is_match(b"\x45\x1C"));
+    ///
+    /// // Masks that cover whole nibbles are the same as nibble wildcards.
+    /// let sig = Signature::masked(b"\x4D\x50", b"\xFF\xF0")?;
+    /// assert_eq!(Signature::new("4D 5?")?, sig);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn masked(
+        bytes: &[u8],
+        mask: &[u8],
+    ) -> Result<Signature, SignatureError> {
+        if bytes.len() != mask.len() {
+            return Err(SignatureError::new(ErrorKind::MaskLength {
+                len: bytes.len(),
+                mask_len: mask.len(),
+            }));
+        }
+        if bytes.is_empty() {
+            return Err(SignatureError::new(ErrorKind::Empty));
+        }
+        let elements = bytes
+            .iter()
+            .zip(mask.iter())
+            .map(|(&b, &m)| Element {
+                class: ByteClass::masked(b, m),
+                min: 1,
+                max: 1,
+            })
+            .collect();
+        Ok(Signature { elements })
+    }
+
+    /// Returns the length of the shortest match of this signature.
+    ///
+    /// This is always at least `1`.
+    pub fn min_len(&self) -> usize {
+        self.elements.iter().map(|e| e.min).sum()
+    }
+
+    /// Returns the length of the longest match of this signature.
+    pub fn max_len(&self) -> usize {
+        self.elements.iter().map(|e| e.max).sum()
+    }
+
+    /// Returns true if and only if this signature matches the entire
+    /// haystack given.
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        forward(&self.elements, haystack, vec![0]).contains(&haystack.len())
+    }
+
+    /// Returns the end offset of the shortest match of this signature that
+    /// starts at `start` in the haystack given, if one exists.
+    #[cfg(test)]
+    fn shortest_match_at(
+        &self,
+        haystack: &[u8],
+        start: usize,
+    ) -> Option<usize> {
+        forward(&self.elements, haystack, vec![start]).first().copied()
+    }
+
+    /// Returns the range of elements making up the longest run of literal
+    /// bytes in this signature. When there are several of the same length,
+    /// the first is chosen. The range returned is empty when there are no
+    /// literal bytes, in which case a searcher can't be built.
+    fn anchor(&self) -> core::ops::Range<usize> {
+        let (mut best, mut best_len) = (0..0, 0);
+        let (mut start, mut len) = (0, 0);
+        for (i, element) in self.elements.iter().enumerate() {
+            if element.literal().is_none() {
+                start = i + 1;
+                len = 0;
+                continue;
+            }
+            len += element.min;
+            if len > best_len {
+                best = start..i + 1;
+                best_len = len;
+            }
+        }
+        best
+    }
+}
+
+impl core::str::FromStr for Signature {
+    type Err = SignatureError;
+
+    fn from_str(signature: &str) -> Result<Signature, SignatureError> {
+        Signature::new(signature)
+    }
+}
+
+/// A parser for the signature syntax described in the module documentation.
+struct Parser<'a> {
+    chars: core::iter::Peekable<core::str::CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(signature: &'a str) -> Parser<'a> {
+        Parser { chars: signature.char_indices().peekable() }
+    }
+
+    fn parse(mut self) -> Result<Signature, SignatureError> {
+        let mut elements: Vec<Element> = vec![];
+        // Whether the last element may be followed by a repetition. This is
+        // not the case for bytes from a string literal, since it would be
+        // easy to mistake the repetition as applying to the entire literal.
+        let mut repeatable = false;
+        while let Some((offset, ch)) = self.next() {
+            match ch {
+                '{' => {
+                    let element = match elements.last_mut() {
+                        Some(element) if repeatable => element,
+                        _ => {
+                            return Err(SignatureError::new(
+                                ErrorKind::InvalidRepetition { offset },
+                            ))
+                        }
+                    };
+                    let (min, max) = self.repetition(offset)?;
+                    element.min = min;
+                    element.max = max;
+                    repeatable = false;
+                }
+                '(' => {
+                    let class = self.alternation(offset)?;
+                    elements.push(Element { class, min: 1, max: 1 });
+                    repeatable = true;
+                }
+                '"' => {
+                    self.literal(offset, &mut elements)?;
+                    repeatable = false;
+                }
+                _ => {
+                    let class = self.byte(offset, ch)?;
+                    elements.push(Element { class, min: 1, max: 1 });
+                    repeatable = true;
+                }
+            }
+        }
+        Ok(Signature { elements })
+    }
+
+    /// Parse the hex signature field of a ClamAV NDB signature, where a gap
+    /// in braces repeats an arbitrary byte instead of the preceding one.
+    fn parse_ndb(mut self) -> Result<Signature, SignatureError> {
+        let mut elements: Vec<Element> = vec![];
+        while let Some((offset, ch)) = self.next() {
+            let (class, min, max) = match ch {
+                '{' => {
+                    let (min, max) = self.gap(offset)?;
+                    (
+                        ByteClass::from_nibbles(Nibble::Any, Nibble::Any),
+                        min,
+                        max,
+                    )
+                }
+                '(' => (self.alternation(offset)?, 1, 1),
+                _ => (self.byte(offset, ch)?, 1, 1),
+            };
+            elements.push(Element { class, min, max });
+        }
+        Ok(Signature { elements })
+    }
+
+    /// Returns the next character that isn't whitespace.
+    fn next(&mut self) -> Option<(usize, char)> {
+        self.chars.find(|&(_, ch)| !ch.is_ascii_whitespace())
+    }
+
+    /// Like `next`, but doesn't consume the character returned.
+    fn peek(&mut self) -> Option<char> {
+        while let Some(&(_, ch)) = self.chars.peek() {
+            if !ch.is_ascii_whitespace() {
+                return Some(ch);
+            }
+            self.chars.next();
+        }
+        None
+    }
+
+    /// Parse a single byte made up of two nibbles, given its first nibble.
+    fn byte(
+        &mut self,
+        offset: usize,
+        hi: char,
+    ) -> Result<ByteClass, SignatureError> {
+        let hi = Nibble::new(offset, hi)?;
+        let lo = match self.next() {
+            Some((offset, lo)) => Nibble::new(offset, lo)?,
+            None => {
+                return Err(SignatureError::new(ErrorKind::OddNibbles {
+                    offset,
+                }))
+            }
+        };
+        Ok(ByteClass::from_nibbles(hi, lo))
+    }
+
+    /// Parse the remainder of an alternation of bytes after its opening
+    /// parenthesis, e.g., `(0A | 0D | ?0)`.
+    fn alternation(
+        &mut self,
+        offset: usize,
+    ) -> Result<ByteClass, SignatureError> {
+        let invalid =
+            || SignatureError::new(ErrorKind::InvalidAlternation { offset });
+        let mut class = ByteClass::empty();
+        loop {
+            match self.next() {
+                Some((_, ')')) | Some((_, '|')) | None => {
+                    return Err(invalid())
+                }
+                Some((offset, hi)) => class.union(&self.byte(offset, hi)?),
+            }
+            match self.next() {
+                Some((_, ')')) => return Ok(class),
+                Some((_, '|')) => {}
+                _ => return Err(invalid()),
+            }
+        }
+    }
+
+    /// Parse the remainder of a string literal after its opening quote,
+    /// e.g., `"MZ"` or `"This program"i`. Whitespace in the literal is
+    /// significant, and `\"` and `\\` are the only escapes.
+    fn literal(
+        &mut self,
+        offset: usize,
+        elements: &mut Vec<Element>,
+    ) -> Result<(), SignatureError> {
+        let unclosed =
+            || SignatureError::new(ErrorKind::UnclosedLiteral { offset });
+        let start = elements.len();
+        let mut buf = [0; 4];
+        loop {
+            let ch = match self.chars.next() {
+                None => return Err(unclosed()),
+                Some((_, '"')) => break,
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, ch @ '"')) | Some((_, ch @ '\\')) => ch,
+                    Some((offset, _)) => {
+                        return Err(SignatureError::new(
+                            ErrorKind::InvalidEscape { offset: offset - 1 },
+                        ))
+                    }
+                    None => return Err(unclosed()),
+                },
+                Some((_, ch)) => ch,
+            };
+            for &b in ch.encode_utf8(&mut buf).as_bytes() {
+                elements.push(Element {
+                    class: ByteClass::single(b),
+                    min: 1,
+                    max: 1,
+                });
+            }
+        }
+        if let Some(&(_, 'i')) = self.chars.peek() {
+            self.chars.next();
+            for element in elements[start..].iter_mut() {
+                element.class = element.class.ascii_case_insensitive();
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the remainder of a repetition after its opening brace, which is
+    /// either `{n}` or `{m,n}`.
+    fn repetition(
+        &mut self,
+        offset: usize,
+    ) -> Result<(usize, usize), SignatureError> {
+        let invalid =
+            || SignatureError::new(ErrorKind::InvalidRepetition { offset });
+        let min = self.number().ok_or_else(invalid)?;
+        let max = match self.next() {
+            Some((_, '}')) => min,
+            Some((_, ',')) => {
+                let max = self.number().ok_or_else(invalid)?;
+                match self.next() {
+                    Some((_, '}')) => max,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        check_repetition(offset, min, max)
+    }
+
+    /// Parse the remainder of an NDB gap after its opening brace, which is
+    /// either `{n}`, `{n-m}` or `{-m}`.
+    fn gap(
+        &mut self,
+        offset: usize,
+    ) -> Result<(usize, usize), SignatureError> {
+        let invalid =
+            || SignatureError::new(ErrorKind::InvalidRepetition { offset });
+        let min = self.number();
+        let max = match self.next() {
+            Some((_, '}')) => min.ok_or_else(invalid)?,
+            Some((_, '-')) => {
+                let max = self.number().ok_or_else(invalid)?;
+                match self.next() {
+                    Some((_, '}')) => max,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        check_repetition(offset, min.unwrap_or(0), max)
+    }
+
+    /// Parse a decimal number, returning `None` if there are no digits.
+    /// Numbers that overflow saturate.
+    fn number(&mut self) -> Option<usize> {
+        let mut n: Option<usize> = None;
+        while let Some(digit) = self.peek().and_then(|ch| ch.to_digit(10)) {
+            self.chars.next();
+            let digit = digit as usize;
+            n = Some(n.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+        }
+        n
+    }
+}
+
+/// Check the bounds of a repetition whose opening brace is at `offset`.
+fn check_repetition(
+    offset: usize,
+    min: usize,
+    max: usize,
+) -> Result<(usize, usize), SignatureError> {
+    if min > max {
+        return Err(SignatureError::new(ErrorKind::InvalidRepetition {
+            offset,
+        }));
+    }
+    if max > MAX_REPEAT {
+        return Err(SignatureError::new(ErrorKind::RepetitionTooLarge {
+            offset,
+            max: MAX_REPEAT,
+        }));
+    }
+    Ok((min, max))
+}
+
+/// A searcher for many byte signatures at once.
+///
+/// See the [module documentation](self) for more details about how
+/// a searcher works and the semantics of the matches it reports.
+#[derive(Clone, Debug)]
+pub struct SignatureSearcher {
+    /// An automaton of the anchor of each signature. Pattern `i` in this
+    /// automaton corresponds to signature `i`.
+    anchors: AhoCorasick,
+    /// The signatures, along with the range of elements making up their
+    /// anchor.
+    signatures: Vec<(Signature, core::ops::Range<usize>)>,
+    /// The length of the longest possible match of any signature.
+    max_len: usize,
+}
+
+impl SignatureSearcher {
+    /// Build a searcher for the given signatures.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if a signature has no literal byte to anchor
+    /// its search, as explained in the [module documentation](self), or if
+    /// the automaton for the anchors of the signatures could not be built.
+    /// The latter only happens when some limit has been exceeded, such as
+    /// the total number of signatures.
+    pub fn new<I>(signatures: I) -> Result<SignatureSearcher, BuildError>
+    where
+        I: IntoIterator<Item = Signature>,
+    {
+        SignatureSearcher::build(&AhoCorasickBuilder::new(), signatures)
+    }
+
+    /// Build a searcher for the given signatures, where the automaton for
+    /// their anchors is configured by the given builder.
+    ///
+    /// The match semantics, start kind, ASCII case insensitivity,
+    /// translation table and lookahead options of the builder are
+    /// overridden, since the searcher relies on their specific values.
+    pub(crate) fn build<I>(
+        builder: &AhoCorasickBuilder,
+        signatures: I,
+    ) -> Result<SignatureSearcher, BuildError>
+    where
+        I: IntoIterator<Item = Signature>,
+    {
+        let mut anchors = vec![];
+        let mut sigs = vec![];
+        let mut ascii_case_insensitive = false;
+        // This is never smaller than 1, even when there are no signatures,
+        // so that streams always make progress.
+        let mut max_len = 1;
+        for (i, sig) in signatures.into_iter().enumerate() {
+            let anchor = sig.anchor();
+            if anchor.is_empty() {
+                return Err(BuildError::unanchored_signature(i));
+            }
+            let mut bytes = vec![];
+            for element in sig.elements[anchor.clone()].iter() {
+                let byte = element.literal().unwrap();
+                ascii_case_insensitive |= element.class.singleton().is_none();
+                bytes.extend(core::iter::repeat(byte).take(element.min));
+            }
+            anchors.push(bytes);
+            max_len = core::cmp::max(max_len, sig.max_len());
+            sigs.push((sig, anchor));
+        }
+        // Since every candidate is verified, it's always correct for anchors
+        // to match more than they should. So if any anchor needs to be case
+        // insensitive, we just make all of them case insensitive.
+        let anchors = builder
+            .clone()
+            .match_kind(MatchKind::Standard)
+            .start_kind(StartKind::Unanchored)
+            .ascii_case_insensitive(ascii_case_insensitive)
+            .translation_table(None)
+            .duplicate_policy(DuplicatePolicy::All)
+            .clear_lookaheads()
+            .build(anchors)?;
+        Ok(SignatureSearcher { anchors, signatures: sigs, max_len })
+    }
+
+    /// Returns the total number of signatures in this searcher.
+    pub fn signatures_len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Returns true if and only if any signature matches somewhere in the
+    /// given haystack.
+    pub fn is_match<H: ?Sized + AsRef<[u8]>>(&self, haystack: &H) -> bool {
+        self.find(haystack).is_some()
+    }
+
+    /// Returns the first match in the given haystack, if one exists.
+    ///
+    /// That is, this returns the match with the smallest starting offset.
+    /// When several signatures match at that offset, the one with the
+    /// smallest ID is returned.
+    pub fn find<H: ?Sized + AsRef<[u8]>>(
+        &self,
+        haystack: &H,
+    ) -> Option<Match> {
+        self.find_iter(haystack).next()
+    }
+
+    /// Returns an iterator of all matches in the given haystack, including
+    /// overlapping matches.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{signature::{Signature, SignatureSearcher}, Match};
+    ///
+    /// let searcher = SignatureSearcher::new([Signature::new("0? 0? 02")?])?;
+    /// let matches: Vec<Match> =
+    ///     searcher.find_iter(b"\x01\x02\x02\x02\xFF").collect();
+    /// assert_eq!(vec![Match::must(0, 0..3), Match::must(0, 1..4)], matches);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn find_iter<'s, 'h, H: ?Sized + AsRef<[u8]>>(
+        &'s self,
+        haystack: &'h H,
+    ) -> FindIter<'s, 'h> {
+        FindIter {
+            searcher: self,
+            haystack: haystack.as_ref(),
+            pos: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns an iterator of all matches in the given stream, including
+    /// overlapping matches.
+    ///
+    /// Only a bounded amount of the stream is kept in memory at any point in
+    /// time. The offsets of every match reported are relative to the start
+    /// of the stream.
+    ///
+    /// # Errors
+    ///
+    /// The iterator yields an error if reading from the stream fails.
+    ///
+    /// # Example: basic usage
+    ///
+    /// ```
+    /// use aho_corasick::{signature::{Signature, SignatureSearcher}, Match};
+    ///
+    /// let searcher = SignatureSearcher::new([Signature::new("4D 5A ?? 00")?])?;
+    /// let rdr = &b"junk MZ\x90\x00 MZ\x00\x00"[..];
+    /// let matches = searcher
+    ///     .stream_find_iter(rdr)
+    ///     .collect::<Result<Vec<Match>, std::io::Error>>()?;
+    /// assert_eq!(vec![Match::must(0, 5..9), Match::must(0, 10..14)], matches);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn stream_find_iter<R: std::io::Read>(
+        &self,
+        rdr: R,
+    ) -> StreamFindIter<'_, R> {
+        StreamFindIter {
+            searcher: self,
+            rdr,
+            window: StreamWindow::new(),
+            eof: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Add every match whose starting offset is in `starts` to `matches`, in
+    /// order. Every byte in `haystack` after `starts.start` may be inspected,
+    /// but never any bytes before it.
+    fn find_starting_in(
+        &self,
+        haystack: &[u8],
+        starts: core::ops::Range<usize>,
+        matches: &mut VecDeque<Match>,
+    ) {
+        if starts.is_empty() {
+            return;
+        }
+        // An anchor can't end any later than the end of a match starting at
+        // the last position in 'starts'.
+        let end =
+            core::cmp::min(haystack.len(), starts.end - 1 + self.max_len);
+        let input = Input::new(haystack).range(starts.start..end);
+        let mut found = vec![];
+        for anchor in self.anchors.find_overlapping_iter(input) {
+            let (ref sig, ref elements) = self.signatures[anchor.pattern()];
+            // The anchor itself is verified too, since it may have matched
+            // case insensitively when it shouldn't have.
+            let end = match forward(
+                &sig.elements[elements.start..],
+                haystack,
+                vec![anchor.start()],
+            )
+            .first()
+            {
+                None => continue,
+                Some(&end) => end,
+            };
+            let match_starts = backward(
+                &sig.elements[..elements.start],
+                haystack,
+                starts.start,
+                vec![anchor.start()],
+            );
+            for start in match_starts.into_iter().filter(|s| *s < starts.end) {
+                found.push(Match::new(anchor.pattern(), start..end));
+            }
+        }
+        // The same signature may be found at the same starting offset via
+        // different occurrences of its anchor. In that case, only keep the
+        // shortest match.
+        found.sort_by_key(|m| (m.start(), m.pattern(), m.end()));
+        found.dedup_by_key(|m| (m.start(), m.pattern()));
+        matches.extend(found);
+    }
+}
+
+/// An iterator of matches of a [`SignatureSearcher`] in a haystack.
+///
+/// This iterator yields matches in order of their starting offset, and
+/// includes overlapping matches.
+///
+/// `'s` refers to the lifetime of the searcher and `'h` refers to the
+/// lifetime of the haystack.
+#[derive(Debug)]
+pub struct FindIter<'s, 'h> {
+    searcher: &'s SignatureSearcher,
+    haystack: &'h [u8],
+    /// The next starting offset to search from.
+    pos: usize,
+    pending: VecDeque<Match>,
+}
+
+impl<'s, 'h> Iterator for FindIter<'s, 'h> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        while self.pending.is_empty() && self.pos < self.haystack.len() {
+            let end = core::cmp::min(
+                self.haystack.len(),
+                self.pos.saturating_add(CHUNK_LEN),
+            );
+            self.searcher.find_starting_in(
+                self.haystack,
+                self.pos..end,
+                &mut self.pending,
+            );
+            self.pos = end;
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// An iterator of matches of a [`SignatureSearcher`] in a stream.
+///
+/// This iterator yields matches in order of their starting offset, and
+/// includes overlapping matches. If reading from the stream fails, then an
+/// error is yielded.
+///
+/// `'s` refers to the lifetime of the searcher and `R` is the type of the
+/// underlying reader.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamFindIter<'s, R> {
+    searcher: &'s SignatureSearcher,
+    rdr: R,
+    window: StreamWindow,
+    /// Set once the reader has reported that it has no more bytes.
+    eof: bool,
+    pending: VecDeque<Match>,
+}
+
+#[cfg(feature = "std")]
+impl<'s, R: std::io::Read> StreamFindIter<'s, R> {
+    /// Read from the stream until either the buffer holds a full chunk of
+    /// starting offsets (along with enough bytes after them to verify any
+    /// match), or EOF is reached.
+    fn fill(&mut self) -> std::io::Result<()> {
+        let want = CHUNK_LEN + self.searcher.max_len;
+        let buf = &mut self.window.buf;
+        while !self.eof && buf.len() < want {
+            let len = buf.len();
+            buf.resize(want, 0);
+            let result = self.rdr.read(&mut buf[len..]);
+            buf.truncate(len + *result.as_ref().unwrap_or(&0));
+            match result {
+                Ok(0) => self.eof = true,
+                Ok(_) => {}
+                Err(ref err)
+                    if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'s, R: std::io::Read> Iterator for StreamFindIter<'s, R> {
+    type Item = std::io::Result<Match>;
+
+    fn next(&mut self) -> Option<std::io::Result<Match>> {
+        while self.pending.is_empty()
+            && !(self.eof && self.window.buf.is_empty())
+        {
+            if let Err(err) = self.fill() {
+                return Some(Err(err));
+            }
+            let pending = &mut self.pending;
+            self.window
+                .search(self.searcher, self.eof, |m, _| pending.push_back(m));
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+/// The state needed to search a stream that is provided incrementally.
+///
+/// Bytes are pushed into the window as they become available, and each
+/// search reports every match that can be completely verified with the bytes
+/// seen so far. Only the bytes that might still be part of a future match are
+/// retained.
+#[derive(Clone, Debug)]
+pub(crate) struct StreamWindow {
+    /// Bytes from the stream that haven't been searched yet.
+    buf: Vec<u8>,
+    /// The offset in the stream of the first byte in `buf`.
+    offset: usize,
+}
+
+impl StreamWindow {
+    pub(crate) fn new() -> StreamWindow {
+        StreamWindow { buf: vec![], offset: 0 }
+    }
+
+    /// Add the given bytes to the end of this window.
+    pub(crate) fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the offset in the stream immediately following the last byte
+    /// pushed into this window.
+    pub(crate) fn end(&self) -> usize {
+        self.offset + self.buf.len()
+    }
+
+    /// Report every match, in order, that starts at a position with enough
+    /// bytes after it to verify a match of the longest signature. When `eof`
+    /// is true, no more bytes are coming and so every remaining position is
+    /// searched.
+    ///
+    /// The callback is given each match along with the bytes it matched.
+    /// Offsets are relative to the start of the stream.
+    pub(crate) fn search<F: FnMut(Match, &[u8])>(
+        &mut self,
+        searcher: &SignatureSearcher,
+        eof: bool,
+        mut f: F,
+    ) {
+        let ready = if eof {
+            self.buf.len()
+        } else {
+            self.buf.len().saturating_sub(searcher.max_len - 1)
+        };
+        let mut matches = VecDeque::new();
+        searcher.find_starting_in(&self.buf, 0..ready, &mut matches);
+        for m in matches {
+            let bytes = &self.buf[m.range()];
+            f(m.offset(self.offset), bytes);
+        }
+        self.buf.drain(..ready);
+        self.offset += ready;
+    }
+}
+
+/// An error that occurred while parsing a [`Signature`].
+///
+/// When the `std` feature is enabled, this implements the `std::error::Error`
+/// trait.
+#[derive(Clone, Debug)]
+pub struct SignatureError {
+    kind: ErrorKind,
+    /// The index of the signature that caused this error, if known.
+    signature: Option<usize>,
+}
+
+/// The kind of error that occurred.
+#[derive(Clone, Debug)]
+enum ErrorKind {
+    /// A character that isn't a hex digit, `?` or whitespace was found.
+    InvalidChar {
+        /// The byte offset of the character in the signature.
+        offset: usize,
+        /// The invalid character.
+        ch: char,
+    },
+    /// The final byte of the signature is missing its second nibble.
+    OddNibbles {
+        /// The byte offset of the first nibble of the incomplete byte.
+        offset: usize,
+    },
+    /// A repetition is malformed, has a lower bound greater than its upper
+    /// bound or doesn't follow a byte.
+    InvalidRepetition {
+        /// The byte offset of the opening brace of the repetition.
+        offset: usize,
+    },
+    /// The upper bound of a repetition is too large.
+    RepetitionTooLarge {
+        /// The byte offset of the opening brace of the repetition.
+        offset: usize,
+        /// The maximum upper bound permitted.
+        max: usize,
+    },
+    /// An alternation is malformed or empty.
+    InvalidAlternation {
+        /// The byte offset of the opening parenthesis of the alternation.
+        offset: usize,
+    },
+    /// A string literal is missing its closing quote.
+    UnclosedLiteral {
+        /// The byte offset of the opening quote of the literal.
+        offset: usize,
+    },
+    /// A backslash in a string literal is followed by something other than
+    /// `"` or `\`.
+    InvalidEscape {
+        /// The byte offset of the backslash.
+        offset: usize,
+    },
+    /// The mask of a masked signature doesn't have the same length as its
+    /// bytes.
+    MaskLength {
+        /// The number of bytes in the signature.
+        len: usize,
+        /// The number of bytes in the mask.
+        mask_len: usize,
+    },
+    /// The signature can match the empty string.
+    Empty,
+    /// The automaton for the anchors of the signatures could not be built.
+    Build(BuildError),
+}
+
+impl SignatureError {
+    fn new(kind: ErrorKind) -> SignatureError {
+        SignatureError { kind, signature: None }
+    }
+
+    pub(crate) fn with_signature(self, index: usize) -> SignatureError {
+        SignatureError { signature: Some(index), ..self }
+    }
+
+    /// Returns the index of the signature that caused this error, when the
+    /// error occurred while compiling many signatures at once via
+    /// [`AhoCorasickBuilder::build_signatures`](crate::AhoCorasickBuilder::build_signatures).
+    pub fn signature(&self) -> Option<usize> {
+        self.signature
+    }
+}
+
+impl From<BuildError> for SignatureError {
+    fn from(err: BuildError) -> SignatureError {
+        SignatureError::new(ErrorKind::Build(err))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignatureError {}
+
+impl core::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(index) = self.signature {
+            write!(f, "signature {}: ", index)?;
+        }
+        match self.kind {
+            ErrorKind::InvalidChar { offset, ch } => write!(
+                f,
+                "invalid character {:?} in signature at offset {}",
+                ch, offset,
+            ),
+            ErrorKind::OddNibbles { offset } => write!(
+                f,
+                "incomplete byte in signature at offset {} \
+                 (every byte must have two nibbles)",
+                offset,
+            ),
+            ErrorKind::InvalidRepetition { offset } => write!(
+                f,
+                "invalid repetition in signature at offset {}",
+                offset,
+            ),
+            ErrorKind::RepetitionTooLarge { offset, max } => write!(
+                f,
+                "repetition in signature at offset {} exceeds the \
+                 maximum of {}",
+                offset, max,
+            ),
+            ErrorKind::InvalidAlternation { offset } => write!(
+                f,
+                "invalid alternation in signature at offset {}",
+                offset,
+            ),
+            ErrorKind::UnclosedLiteral { offset } => write!(
+                f,
+                "unclosed string literal in signature at offset {}",
+                offset,
+            ),
+            ErrorKind::InvalidEscape { offset } => write!(
+                f,
+                "invalid escape sequence in signature at offset {}",
+                offset,
+            ),
+            ErrorKind::MaskLength { len, mask_len } => write!(
+                f,
+                "mask of length {} doesn't match signature of length {}",
+                mask_len, len,
+            ),
+            ErrorKind::Empty => {
+                write!(f, "signature must match at least one byte")
+            }
+            ErrorKind::Build(ref err) => {
+                write!(f, "failed to build signature searcher: {}", err)
+            }
+        }
+    }
+}
+
+/// A single element of a signature, which matches between `min` and `max`
+/// bytes (inclusive) in `class`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Element {
+    class: ByteClass,
+    min: usize,
+    max: usize,
+}
+
+impl Element {
+    /// If this element always matches the same byte (ignoring ASCII case) a
+    /// fixed number of times, then return that byte.
+    fn literal(&self) -> Option<u8> {
+        if self.min != self.max {
+            return None;
+        }
+        self.class.anchor_byte()
+    }
+}
+
+/// Returns the sorted set of end offsets at which the given elements match in
+/// the haystack, when starting at any of the offsets given.
+fn forward(
+    elements: &[Element],
+    haystack: &[u8],
+    mut positions: Vec<usize>,
+) -> Vec<usize> {
+    for element in elements {
+        let mut next = vec![];
+        for &pos in positions.iter() {
+            let mut len = 0;
+            while len < element.max
+                && pos + len < haystack.len()
+                && element.class.contains(haystack[pos + len])
+            {
+                len += 1;
+                if len >= element.min {
+                    next.push(pos + len);
+                }
+            }
+            if element.min == 0 {
+                next.push(pos);
+            }
+        }
+        next.sort_unstable();
+        next.dedup();
+        positions = next;
+    }
+    positions
+}
+
+/// Returns the sorted set of start offsets at which the given elements match
+/// in the haystack, when ending at any of the offsets given. No bytes before
+/// `lower` are inspected.
+fn backward(
+    elements: &[Element],
+    haystack: &[u8],
+    lower: usize,
+    mut positions: Vec<usize>,
+) -> Vec<usize> {
+    for element in elements.iter().rev() {
+        let mut next = vec![];
+        for &pos in positions.iter() {
+            let mut len = 0;
+            while len < element.max
+                && pos - len > lower
+                && element.class.contains(haystack[pos - len - 1])
+            {
+                len += 1;
+                if len >= element.min {
+                    next.push(pos - len);
+                }
+            }
+            if element.min == 0 {
+                next.push(pos);
+            }
+        }
+        next.sort_unstable();
+        next.dedup();
+        positions = next;
+    }
+    positions
+}
+
+/// A single nibble of a signature, which is either a fixed value or a
+/// wildcard.
+#[derive(Clone, Copy, Debug)]
+enum Nibble {
+    Fixed(u8),
+    Any,
+}
+
+impl Nibble {
+    fn new(offset: usize, ch: char) -> Result<Nibble, SignatureError> {
+        match ch {
+            '?' => Ok(Nibble::Any),
+            _ => match ch.to_digit(16) {
+                Some(value) => Ok(Nibble::Fixed(value as u8)),
+                None => Err(SignatureError::new(ErrorKind::InvalidChar {
+                    offset,
+                    ch,
+                })),
+            },
+        }
+    }
+
+    fn matches(self, value: u8) -> bool {
+        match self {
+            Nibble::Fixed(fixed) => fixed == value,
+            Nibble::Any => true,
+        }
+    }
+}
+
+/// A set of bytes, represented as a 256-bit bitset.
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct ByteClass([u64; 4]);
+
+impl ByteClass {
+    fn empty() -> ByteClass {
+        ByteClass([0; 4])
+    }
+
+    fn single(byte: u8) -> ByteClass {
+        let mut class = ByteClass::empty();
+        class.insert(byte);
+        class
+    }
+
+    fn insert(&mut self, byte: u8) {
+        self.0[usize::from(byte >> 6)] |= 1 << (byte & 0x3F);
+    }
+
+    fn union(&mut self, other: &ByteClass) {
+        for (bits, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *bits |= *other;
+        }
+    }
+
+    /// Returns a copy of this class that also contains the opposite case of
+    /// every ASCII letter in this class.
+    fn ascii_case_insensitive(&self) -> ByteClass {
+        let mut class = *self;
+        for b in (b'A'..=b'Z').chain(b'a'..=b'z') {
+            if self.contains(b) {
+                class.insert(b ^ 0x20);
+            }
+        }
+        class
+    }
+
+    fn from_nibbles(hi: Nibble, lo: Nibble) -> ByteClass {
+        let mut class = ByteClass::empty();
+        for b in 0..=255u8 {
+            if hi.matches(b >> 4) && lo.matches(b & 0xF) {
+                class.insert(b);
+            }
+        }
+        class
+    }
+
+    /// Returns the class of every byte that is equivalent to the byte given
+    /// in all of the bits set in the mask given.
+    fn masked(byte: u8, mask: u8) -> ByteClass {
+        let mut class = ByteClass::empty();
+        for b in 0..=255u8 {
+            if b & mask == byte & mask {
+                class.insert(b);
+            }
+        }
+        class
+    }
+
+    fn contains(&self, byte: u8) -> bool {
+        self.0[usize::from(byte >> 6)] & (1 << (byte & 0x3F)) != 0
+    }
+
+    /// If this class contains exactly one byte, or exactly the two cases of
+    /// one ASCII letter, then return that byte. (In the latter case, the
+    /// lowercase letter is returned.)
+    fn anchor_byte(&self) -> Option<u8> {
+        if let Some(b) = self.singleton() {
+            return Some(b);
+        }
+        let lower = (b'a'..=b'z').find(|&b| self.contains(b))?;
+        if *self == ByteClass::single(lower).ascii_case_insensitive() {
+            Some(lower)
+        } else {
+            None
+        }
+    }
+
+    /// If this class contains exactly one byte, then return it.
+    fn singleton(&self) -> Option<u8> {
+        let count: u32 = self.0.iter().map(|bits| bits.count_ones()).sum();
+        if count != 1 {
+            return None;
+        }
+        (0..=255u8).find(|&b| self.contains(b))
+    }
+}
+
+impl core::fmt::Debug for ByteClass {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.singleton() {
+            Some(b) => write!(f, "{:02X}", b),
+            None => f
+                .debug_set()
+                .entries((0..=255u8).filter(|&b| self.contains(b)))
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::*;
+
+    /// Find all matches by trying every signature at every position.
+    fn naive(sigs: &[Signature], haystack: &[u8]) -> Vec<Match> {
+        let mut matches = vec![];
+        for start in 0..haystack.len() {
+            for (id, sig) in sigs.iter().enumerate() {
+                if let Some(end) = sig.shortest_match_at(haystack, start) {
+                    matches.push(Match::must(id, start..end));
+                }
+            }
+        }
+        matches
+    }
+
+    /// A reader that returns at most one byte per call, and interrupts every
+    /// other call.
+    #[cfg(feature = "std")]
+    struct SlowReader<'a> {
+        bytes: &'a [u8],
+        interrupt: bool,
+    }
+
+    #[cfg(feature = "std")]
+    impl<'a> std::io::Read for SlowReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            if self.bytes.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.bytes[0];
+            self.bytes = &self.bytes[1..];
+            Ok(1)
+        }
+    }
+
+    fn sigs(sigs: &[&str]) -> Vec<Signature> {
+        sigs.iter().map(|s| Signature::new(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let sig = Signature::new(" 0a?B  ?? 7? ").unwrap();
+        assert_eq!(4, sig.min_len());
+        assert_eq!(0..1, sig.anchor());
+
+        macro_rules! assert_err {
+            ($sig:expr, $kind:pat) => {
+                let err = Signature::new($sig).unwrap_err();
+                assert!(matches!(err.kind, $kind), "{:?}: {:?}", $sig, err);
+            };
+        }
+        assert_err!("  ", ErrorKind::Empty);
+        assert_err!("00{0} ??{0,3}", ErrorKind::Empty);
+        for bad in ["{1}", "00{}", "00{1", "00{,2}", "00{2,1}", "00{2}{1}"] {
+            assert_err!(bad, ErrorKind::InvalidRepetition { .. });
+        }
+        assert_err!(
+            "00 {1,4097}",
+            ErrorKind::RepetitionTooLarge { offset: 3, max: MAX_REPEAT }
+        );
+        assert_err!("ab c", ErrorKind::OddNibbles { offset: 3 });
+        assert_err!("ag", ErrorKind::InvalidChar { offset: 1, ch: 'g' });
+        assert_err!("ab é", ErrorKind::InvalidChar { offset: 3, ch: 'é' });
+        for bad in ["()", "(00", "(00|)", "(|00)", "(00 01)", "(0?|)"] {
+            assert_err!(bad, ErrorKind::InvalidAlternation { .. });
+        }
+        assert_err!("00 \"ab", ErrorKind::UnclosedLiteral { offset: 3 });
+        assert_err!("\"a\\", ErrorKind::UnclosedLiteral { offset: 0 });
+        assert_err!("\"a\\n\"", ErrorKind::InvalidEscape { offset: 2 });
+        assert_err!("\"ab\"{2}", ErrorKind::InvalidRepetition { offset: 4 });
+        assert_err!("\"\"", ErrorKind::Empty);
+        assert_eq!(
+            "invalid character 'x' in signature at offset 0",
+            Signature::new("x").unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn parse_ndb() {
+        let sig = Signature::ndb("4d5a{2}??{-3}(50|4?){1-2}00").unwrap();
+        assert_eq!(
+            Signature::new("4d 5a ??{2} ?? ??{0,3} (50 | 4?) ??{1,2} 00")
+                .unwrap(),
+            sig,
+        );
+
+        for bad in ["00{}", "00{2-}", "00{3-2}", "00{-}", "00{1,2}"] {
+            let err = Signature::ndb(bad).unwrap_err();
+            assert!(
+                matches!(err.kind, ErrorKind::InvalidRepetition { offset: 2 }),
+                "{:?}: {:?}",
+                bad,
+                err,
+            );
+        }
+        let err = Signature::ndb("00*01").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidChar { offset: 2, .. }));
+        let err = Signature::ndb("{-4}").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Empty));
+        let err = Signature::ndb("00{4097}").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::RepetitionTooLarge { .. }));
+    }
+
+    #[test]
+    fn anchor_is_longest_literal_run() {
+        let anchor = |s: &str| Signature::new(s).unwrap().anchor();
+        assert_eq!(0..2, anchor("01 02 ?? 03"));
+        assert_eq!(2..5, anchor("01 ?? 02 03 04 ?? 05 06 ?1"));
+        assert_eq!(0..0, anchor("?? 1? ?2"));
+        assert_eq!(3..4, anchor("01 02 ?? 03{3} 04{1,2}"));
+        assert_eq!(0..3, anchor("01 02{2} 03 00{0,1}"));
+
+        // A searcher can't be built with a signature that has no anchor.
+        let err = SignatureSearcher::new(sigs(&["01", "?? 1? (02 | 03)"]))
+            .unwrap_err();
+        assert_eq!(
+            "signature 1 has no literal byte to anchor its search",
+            err.to_string(),
+        );
+    }
+
+    #[test]
+    fn matches_naive() {
+        let sigs = sigs(&[
+            "01 02 ?? 03",
+            "?? 02",
+            "0? 01 0?",
+            "03 ?? ??",
+            "?? 00 ??",
+            "01 02 ?? 03",
+            "01 00{0,3} 02",
+            "??{2,4} 03",
+            "02{2} ?? 04{1,2}",
+            "(01|03) (00 | 1?){2} 04",
+        ]);
+        let searcher = SignatureSearcher::new(sigs.clone()).unwrap();
+        // A simple linear congruential generator, which is good enough to
+        // get a haystack in which every signature matches.
+        let mut state = 1u32;
+        let haystack: Vec<u8> = (0..2000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((state >> 16) % 5) as u8
+            })
+            .collect();
+        let expected = naive(&sigs, &haystack);
+        for id in 0..sigs.len() {
+            assert!(
+                expected.iter().any(|m| m.pattern().as_usize() == id),
+                "{}",
+                id
+            );
+        }
+
+        let got: Vec<Match> = searcher.find_iter(&haystack).collect();
+        assert_eq!(expected, got);
+
+        #[cfg(feature = "std")]
+        {
+            let rdr = SlowReader { bytes: &haystack, interrupt: false };
+            let got: Vec<Match> = searcher
+                .stream_find_iter(rdr)
+                .collect::<std::io::Result<_>>()
+                .unwrap();
+            assert_eq!(expected, got);
+        }
+        assert_eq!(expected.first().copied(), searcher.find(&haystack));
+    }
+
+    #[test]
+    fn matches_across_chunks() {
+        let sigs = sigs(&["AA ?? ?? ?? BB", "?? CC"]);
+        let searcher = SignatureSearcher::new(sigs.clone()).unwrap();
+        let mut haystack = vec![0u8; 3 * CHUNK_LEN];
+        for &at in &[0, CHUNK_LEN - 2, 2 * CHUNK_LEN - 1, 3 * CHUNK_LEN - 5] {
+            haystack[at] = 0xAA;
+            haystack[at + 4] = 0xBB;
+        }
+        for &at in &[CHUNK_LEN, 2 * CHUNK_LEN + 1, 3 * CHUNK_LEN - 2] {
+            haystack[at] = 0xCC;
+        }
+        let expected = naive(&sigs, &haystack);
+        assert_eq!(7, expected.len());
+
+        let got: Vec<Match> = searcher.find_iter(&haystack).collect();
+        assert_eq!(expected, got);
+        #[cfg(feature = "std")]
+        {
+            let got: Vec<Match> = searcher
+                .stream_find_iter(&haystack[..])
+                .collect::<std::io::Result<_>>()
+                .unwrap();
+            assert_eq!(expected, got);
+        }
+    }
+
+    #[test]
+    fn case_insensitive_literals() {
+        let sigs = sigs(&[
+            r#""abc"i"#,
+            r#""aBc""#,
+            r#"?? "b\\"i"#,
+            "41 (62 | 42) 63",
+        ]);
+        let searcher = SignatureSearcher::new(sigs.clone()).unwrap();
+        // The anchor of the first signature needs to be case insensitive, so
+        // all anchors are case insensitive.
+        assert!(searcher.anchors.find("ABC").is_some());
+
+        let haystack = b"abc aBc ABC Abc xB\\ Ab\\";
+        let expected = naive(&sigs, haystack);
+        assert_eq!(8, expected.len());
+        let got: Vec<Match> = searcher.find_iter(haystack).collect();
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn masked() {
+        let sig = Signature::masked(b"\x45\x80\x0A", b"\xFF\x80\x0F").unwrap();
+        assert!(sig.is_match(b"\x45\xFF\x7A"));
+        assert!(!sig.is_match(b"\x45\x7F\x7A"));
+        // A mask of 0xDF on an ASCII letter matches it case insensitively,
+        // so it can be used as an anchor.
+        let sig = Signature::masked(b"ab", b"\xDF\xDF").unwrap();
+        assert_eq!(Signature::new(r#""ab"i"#).unwrap(), sig);
+        assert_eq!(0..2, sig.anchor());
+
+        let err = Signature::masked(b"ab", b"\xFF").unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MaskLength { len: 2, mask_len: 1 }
+        ));
+        assert_eq!(
+            "mask of length 1 doesn't match signature of length 2",
+            err.to_string(),
+        );
+        let err = Signature::masked(b"", b"").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Empty));
+
+        let sigs = vec![
+            Signature::masked(b"\x01\x80", b"\xFF\x80").unwrap(),
+            Signature::masked(b"\x02\x00\x36", b"\x03\x00\xFF").unwrap(),
+            Signature::masked(b"\x36\x10", b"\xFF\x10").unwrap(),
+        ];
+        let searcher = SignatureSearcher::new(sigs.clone()).unwrap();
+        let haystack: Vec<u8> = (0..600u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let expected = naive(&sigs, &haystack);
+        for id in 0..sigs.len() {
+            assert!(expected.iter().any(|m| m.pattern().as_usize() == id));
+        }
+        let got: Vec<Match> = searcher.find_iter(&haystack).collect();
+        assert_eq!(expected, got);
+        #[cfg(feature = "std")]
+        {
+            let rdr = SlowReader { bytes: &haystack, interrupt: false };
+            let got: Vec<Match> = searcher
+                .stream_find_iter(rdr)
+                .collect::<std::io::Result<_>>()
+                .unwrap();
+            assert_eq!(expected, got);
+        }
+    }
+
+    #[test]
+    fn build_with_builder() {
+        use crate::{AhoCorasickBuilder, AhoCorasickKind};
+
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            let searcher = AhoCorasickBuilder::new()
+                .kind(Some(kind))
+                .match_kind(crate::MatchKind::LeftmostFirst)
+                .start_kind(crate::StartKind::Anchored)
+                .build_signatures(["01 02", "02 03", r#""bc"i"#])
+                .unwrap();
+            assert_eq!(kind, searcher.anchors.kind());
+            let got: Vec<Match> =
+                searcher.find_iter(b"x\x01\x02\x03BC").collect();
+            assert_eq!(
+                vec![
+                    Match::must(0, 1..3),
+                    Match::must(1, 2..4),
+                    Match::must(2, 4..6),
+                ],
+                got,
+            );
+        }
+
+        let err = AhoCorasickBuilder::new()
+            .build_signatures(["00", "01", "(02"])
+            .unwrap_err();
+        assert_eq!(Some(2), err.signature());
+        assert_eq!(
+            "signature 2: invalid alternation in signature at offset 0",
+            err.to_string(),
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_error() {
+        struct Failing;
+
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::Other.into())
+            }
+        }
+
+        let searcher = SignatureSearcher::new(sigs(&["00"])).unwrap();
+        let mut it = searcher.stream_find_iter(Failing);
+        assert!(it.next().unwrap().is_err());
+    }
+}
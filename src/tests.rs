@@ -1,8 +1,9 @@
-use std::{collections::HashMap, format, string::String, vec::Vec};
+use std::{collections::HashMap, format, string::String, vec, vec::Vec};
 
 use crate::{
-    AhoCorasick, AhoCorasickBuilder, AhoCorasickKind, Anchored, Input, Match,
-    MatchKind, StartKind,
+    AhoCorasick, AhoCorasickBuilder, AhoCorasickKind,
+    AhoCorasickReplacerBuilder, Anchored, Input, Match, MatchKind, Padding,
+    StartKind,
 };
 
 /// A description of a single test against an Aho-Corasick automaton.
@@ -703,6 +704,31 @@ macro_rules! testconfig {
             });
         }
     };
+    (spanned, $name:ident, $collection:expr, $kind:ident, $with:expr) => {
+        #[test]
+        fn $name() {
+            run_search_tests($collection, |test| {
+                // Surround the haystack with copies of itself and restrict
+                // the search to the middle copy. The bytes outside the span
+                // must not influence the matches reported, and offsets must
+                // be relative to the full haystack.
+                let len = test.haystack.len();
+                let haystack = test.haystack.repeat(3);
+                let input = Input::new(&haystack).range(len..2 * len);
+                let mut builder = AhoCorasick::builder();
+                $with(&mut builder);
+                builder
+                    .match_kind(MatchKind::$kind)
+                    .build(test.patterns)
+                    .unwrap()
+                    .find_iter(input)
+                    .map(|m| {
+                        Match::new(m.pattern(), m.start() - len..m.end() - len)
+                    })
+                    .collect()
+            });
+        }
+    };
     ($name:ident, $collection:expr, $kind:ident, $with:expr) => {
         #[test]
         fn $name() {
@@ -726,6 +752,34 @@ macro_rules! testcombo {
             use super::*;
 
             testconfig!(default, $collection, $kind, |_| ());
+            testconfig!(spanned, spanned_default, $collection, $kind, |_| ());
+            testconfig!(
+                spanned,
+                spanned_nfa_noncontig,
+                $collection,
+                $kind,
+                |b: &mut AhoCorasickBuilder| {
+                    b.kind(Some(AhoCorasickKind::NoncontiguousNFA));
+                }
+            );
+            testconfig!(
+                spanned,
+                spanned_nfa_contig,
+                $collection,
+                $kind,
+                |b: &mut AhoCorasickBuilder| {
+                    b.kind(Some(AhoCorasickKind::ContiguousNFA));
+                }
+            );
+            testconfig!(
+                spanned,
+                spanned_dfa,
+                $collection,
+                $kind,
+                |b: &mut AhoCorasickBuilder| {
+                    b.kind(Some(AhoCorasickKind::DFA));
+                }
+            );
             testconfig!(
                 nfa_default,
                 $collection,
@@ -1426,192 +1480,4115 @@ fn stream_not_allowed_leftmost_longest() {
     assert_eq!(fsm.stream_find_iter(&b""[..]).count(), 0);
 }
 
+// This tests that an earliest stream search is permitted for every match
+// kind, and that it reports precisely the same matches as an earliest search
+// on a contiguous haystack. Prefilters are disabled, since a prefilter that
+// can confirm matches on its own may report a different (but still correct)
+// earliest match than the automaton would.
+#[cfg(feature = "std")]
 #[test]
-#[should_panic]
-fn overlapping_not_allowed_leftmost_first() {
-    let fsm = AhoCorasick::builder()
-        .match_kind(MatchKind::LeftmostFirst)
-        .build(None::<String>)
-        .unwrap();
-    assert_eq!(fsm.find_overlapping_iter("").count(), 0);
+fn stream_earliest_matches_batch_earliest() {
+    let collections = [
+        (MatchKind::Standard, AC_STANDARD_NON_OVERLAPPING),
+        (MatchKind::LeftmostFirst, AC_LEFTMOST_FIRST),
+        (MatchKind::LeftmostLongest, AC_LEFTMOST_LONGEST),
+    ];
+    let kinds = [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ];
+    for (match_kind, collection) in collections {
+        for &tests in collection {
+            for test in tests {
+                if test.patterns.iter().any(|p| p.is_empty()) {
+                    continue;
+                }
+                for kind in kinds {
+                    let ac = AhoCorasick::builder()
+                        .match_kind(match_kind)
+                        .kind(Some(kind))
+                        .prefilter(false)
+                        .build(test.patterns)
+                        .unwrap();
+                    let input = Input::new(test.haystack).earliest(true);
+                    let expected: Vec<Match> = ac.find_iter(input).collect();
+                    let buf = std::io::BufReader::with_capacity(
+                        1,
+                        test.haystack.as_bytes(),
+                    );
+                    let got: Vec<Match> = ac
+                        .try_stream_find_iter_earliest(buf)
+                        .unwrap()
+                        .map(|result| result.unwrap())
+                        .collect();
+                    assert_eq!(
+                        expected, got,
+                        "test: {}, match kind: {:?}, kind: {:?}",
+                        test.name, match_kind, kind,
+                    );
+                }
+            }
+        }
+    }
 }
 
+// This tests that batch searching many haystacks at once reports the same
+// matches as searching each haystack individually.
 #[test]
-#[should_panic]
-fn overlapping_not_allowed_leftmost_longest() {
-    let fsm = AhoCorasick::builder()
-        .match_kind(MatchKind::LeftmostLongest)
-        .build(None::<String>)
-        .unwrap();
-    assert_eq!(fsm.find_overlapping_iter("").count(), 0);
+fn find_many_matches_find_iter() {
+    let collections = [
+        (MatchKind::Standard, AC_STANDARD_NON_OVERLAPPING),
+        (MatchKind::LeftmostFirst, AC_LEFTMOST_FIRST),
+        (MatchKind::LeftmostLongest, AC_LEFTMOST_LONGEST),
+    ];
+    for (match_kind, collection) in collections {
+        for &tests in collection {
+            for test in tests {
+                let ac = AhoCorasick::builder()
+                    .match_kind(match_kind)
+                    .build(test.patterns)
+                    .unwrap();
+                let haystacks =
+                    [test.haystack, "", test.haystack, "zzz", test.haystack];
+                let expected: Vec<Vec<Match>> = haystacks
+                    .iter()
+                    .map(|h| ac.find_iter(h).collect())
+                    .collect();
+                assert_eq!(expected, ac.find_many(&haystacks));
+                #[cfg(feature = "rayon")]
+                assert_eq!(expected, ac.par_find_many(&haystacks));
+            }
+        }
+    }
 }
 
-// This tests that if we build an AC matcher with an "unanchored" start kind,
-// then we can't run an anchored search even if the underlying searcher
-// supports it.
-//
-// The key bit here is that both of the NFAs in this crate unconditionally
-// support both unanchored and anchored searches, but the DFA does not because
-// of the added cost of doing so. To avoid the top-level AC matcher sometimes
-// supporting anchored and sometimes not (depending on which searcher it
-// chooses to use internally), we ensure that the given 'StartKind' is always
-// respected.
 #[test]
-fn anchored_not_allowed_even_if_technically_available() {
+fn find_many_requires_unanchored_support() {
     let ac = AhoCorasick::builder()
-        .kind(Some(AhoCorasickKind::NoncontiguousNFA))
-        .start_kind(StartKind::Unanchored)
-        .build(&["foo"])
+        .start_kind(StartKind::Anchored)
+        .build(["foo"])
         .unwrap();
-    assert!(ac.try_find(Input::new("foo").anchored(Anchored::Yes)).is_err());
+    assert!(ac.try_find_many(&["foo"]).is_err());
+}
 
-    let ac = AhoCorasick::builder()
-        .kind(Some(AhoCorasickKind::ContiguousNFA))
-        .start_kind(StartKind::Unanchored)
-        .build(&["foo"])
-        .unwrap();
-    assert!(ac.try_find(Input::new("foo").anchored(Anchored::Yes)).is_err());
+// This tests that compact matches are the same as normal matches, and that
+// searches whose matches might not fit are rejected up front.
+#[test]
+fn find_iter_u32_matches_find_iter() {
+    let ac = AhoCorasick::new(["foo", "bar", "quux"]).unwrap();
+    let haystack = "foo bar quux barfoo";
+    let expected: Vec<Match> = ac.find_iter(haystack).collect();
+    let got: Vec<Match> = ac
+        .try_find_iter_u32(haystack)
+        .unwrap()
+        .map(|m| m.to_match())
+        .collect();
+    assert_eq!(expected, got);
+    assert_eq!(12, core::mem::size_of::<crate::MatchU32>());
 
-    // For completeness, check that the DFA returns an error too.
-    let ac = AhoCorasick::builder()
-        .kind(Some(AhoCorasickKind::DFA))
-        .start_kind(StartKind::Unanchored)
-        .build(&["foo"])
-        .unwrap();
-    assert!(ac.try_find(Input::new("foo").anchored(Anchored::Yes)).is_err());
+    let too_long = Match::must(0, 0..u16::MAX as usize + 1);
+    assert_eq!(None, crate::MatchU32::from_match(too_long));
+    let fits = Match::must(0, 5..u16::MAX as usize + 5);
+    assert_eq!(Some(fits), crate::MatchU32::from_match(fits).map(Match::from));
 }
 
-// This is like the test aboved, but with unanchored and anchored flipped. That
-// is, we asked for an AC searcher with anchored support and we check that
-// unanchored searches return an error even if the underlying searcher would
-// technically support it.
+#[cfg(feature = "serde")]
 #[test]
-fn unanchored_not_allowed_even_if_technically_available() {
-    let ac = AhoCorasick::builder()
-        .kind(Some(AhoCorasickKind::NoncontiguousNFA))
-        .start_kind(StartKind::Anchored)
-        .build(&["foo"])
-        .unwrap();
-    assert!(ac.try_find(Input::new("foo").anchored(Anchored::No)).is_err());
+fn serde_roundtrip() {
+    let ac = AhoCorasick::new(["foo", "bar"]).unwrap();
+    let matches: Vec<Match> = ac.find_iter("xfoo bar").collect();
+    let json = serde_json::to_string(&matches).unwrap();
+    assert_eq!(
+        r#"[{"pattern":0,"start":1,"end":4},{"pattern":1,"start":5,"end":8}]"#,
+        json,
+    );
+    let got: Vec<Match> = serde_json::from_str(&json).unwrap();
+    assert_eq!(matches, got);
 
-    let ac = AhoCorasick::builder()
-        .kind(Some(AhoCorasickKind::ContiguousNFA))
-        .start_kind(StartKind::Anchored)
-        .build(&["foo"])
-        .unwrap();
-    assert!(ac.try_find(Input::new("foo").anchored(Anchored::No)).is_err());
+    let span: crate::Span =
+        serde_json::from_str(r#"{"start":5,"end":2}"#).unwrap();
+    assert_eq!(crate::Span { start: 5, end: 2 }, span);
 
-    // For completeness, check that the DFA returns an error too.
-    let ac = AhoCorasick::builder()
-        .kind(Some(AhoCorasickKind::DFA))
-        .start_kind(StartKind::Anchored)
-        .build(&["foo"])
-        .unwrap();
-    assert!(ac.try_find(Input::new("foo").anchored(Anchored::No)).is_err());
+    // Deserialization upholds the invariants of the corresponding types.
+    let json = r#"{"pattern":0,"start":5,"end":2}"#;
+    assert!(serde_json::from_str::<Match>(json).is_err());
+    let json = format!("{}", u32::MAX);
+    assert!(serde_json::from_str::<crate::PatternID>(&json).is_err());
 }
 
-// This tests that a prefilter does not cause a search to report a match
-// outside the bounds provided by the caller.
-//
-// This is a regression test for a bug I introduced during the rewrite of most
-// of the crate after 0.7. It was never released. The tricky part here is
-// ensuring we get a prefilter that can report matches on its own (such as the
-// packed searcher). Otherwise, prefilters that report false positives might
-// have searched past the bounds provided by the caller, but confirming the
-// match would subsequently fail.
 #[test]
-fn prefilter_stays_in_bounds() {
-    let ac = AhoCorasick::builder()
+fn automata_roundtrip_through_bytes() {
+    use crate::{automaton::Automaton, dfa, nfa::contiguous};
+
+    fn matches<A: Automaton>(aut: &A, haystack: &str) -> Vec<Match> {
+        let mut matches: Vec<Match> =
+            aut.try_find_iter(Input::new(haystack)).unwrap().collect();
+        if aut.match_kind().is_standard() {
+            let input = Input::new(haystack);
+            matches.extend(aut.try_find_overlapping_iter(input).unwrap());
+        }
+        let input = Input::new(haystack).anchored(Anchored::Yes);
+        matches.extend(aut.try_find_iter(input).unwrap());
+        matches
+    }
+
+    let patterns = ["Sherlock", "Holmes", "Watson", "Sher", "", "son"];
+    let haystacks = ["Sherlock Holmes and Dr. Watson", "", "ShSherSherlo"];
+    for kind in [
+        MatchKind::Standard,
+        MatchKind::LeftmostFirst,
+        MatchKind::LeftmostLongest,
+    ] {
+        for prefilter in [false, true] {
+            // Without its empty pattern, the automata get a prefilter when
+            // the prefilters of the standard library are available.
+            for patterns in [&patterns[..], &patterns[..4]] {
+                let dfa = dfa::DFA::builder()
+                    .match_kind(kind)
+                    .start_kind(StartKind::Both)
+                    .prefilter(prefilter)
+                    .build(patterns)
+                    .unwrap();
+                let has_prefilter =
+                    cfg!(feature = "std") && prefilter && patterns.len() == 4;
+                assert_eq!(has_prefilter, dfa.prefilter().is_some());
+                let read = dfa::DFA::from_bytes(&dfa.to_bytes()).unwrap();
+                assert!(read.prefilter().is_none());
+                for haystack in haystacks {
+                    assert_eq!(
+                        matches(&dfa, haystack),
+                        matches(&read, haystack)
+                    );
+                }
+
+                let nfa = contiguous::NFA::builder()
+                    .match_kind(kind)
+                    .prefilter(prefilter)
+                    .build(patterns)
+                    .unwrap();
+                assert_eq!(has_prefilter, nfa.prefilter().is_some());
+                let read =
+                    contiguous::NFA::from_bytes(&nfa.to_bytes()).unwrap();
+                assert!(read.prefilter().is_none());
+                for haystack in haystacks {
+                    assert_eq!(
+                        matches(&nfa, haystack),
+                        matches(&read, haystack)
+                    );
+                }
+            }
+        }
+    }
+
+    // Truncated or corrupted bytes, and the bytes of another kind of
+    // automaton, are rejected.
+    let bytes = dfa::DFA::new(["foo", "bar"]).unwrap().to_bytes();
+    for len in 0..bytes.len() {
+        assert!(dfa::DFA::from_bytes(&bytes[..len]).is_err());
+    }
+    for at in 0..bytes.len() {
+        let mut corrupted = bytes.clone();
+        corrupted[at] ^= 0x01;
+        assert!(dfa::DFA::from_bytes(&corrupted).is_err(), "at {}", at);
+    }
+    assert!(contiguous::NFA::from_bytes(&bytes).is_err());
+
+    // Corrupted bytes with a valid checksum are either rejected, or describe
+    // a DFA that is searched without panicking. The magic number and the
+    // version (the first 12 bytes) are left alone.
+    let bytes = dfa::DFA::builder()
         .match_kind(MatchKind::LeftmostFirst)
-        .build(&["sam", "frodo", "pippin", "merry", "gandalf", "sauron"])
-        .unwrap();
-    let haystack = "foo gandalf";
-    assert_eq!(None, ac.find(Input::new(haystack).range(0..10)));
+        .start_kind(StartKind::Both)
+        .build(["foo", "bar", "o"])
+        .unwrap()
+        .to_bytes();
+    let body_len = bytes.len() - 8;
+    for at in 12..body_len {
+        for &flip in [0x01, 0x02, 0x10, 0x80, 0xFF].iter() {
+            let mut corrupted = bytes.clone();
+            corrupted[at] ^= flip;
+            let checksum = crate::util::wire::fnv1a(&corrupted[..body_len]);
+            corrupted[body_len..].copy_from_slice(&checksum.to_le_bytes());
+            let read = match dfa::DFA::from_bytes(&corrupted) {
+                Ok(read) => read,
+                Err(_) => continue,
+            };
+            for anchored in [Anchored::No, Anchored::Yes] {
+                let input =
+                    Input::new("foobarfoo barf oof").anchored(anchored);
+                if let Ok(it) = read.try_find_iter(input.clone()) {
+                    it.for_each(drop);
+                }
+                if let Ok(it) = read.try_find_overlapping_iter(input) {
+                    it.for_each(drop);
+                }
+            }
+        }
+    }
+
+    // A contiguous NFA whose only pattern claims to be longer than the
+    // bytes leading to the state that reports it is rejected, since
+    // computing the start of its matches would overflow. The length of the
+    // pattern is the last value before the prefilter flag.
+    let bytes = contiguous::NFA::new(["ab"]).unwrap().to_bytes();
+    let body_len = bytes.len() - 8;
+    let len_at = body_len - 1 - 4;
+    assert_eq!(2u32.to_le_bytes(), bytes[len_at..body_len - 1]);
+    for (len, is_valid) in [(1u32, true), (2, true), (3, false)] {
+        let mut crafted = bytes.clone();
+        crafted[len_at..body_len - 1].copy_from_slice(&len.to_le_bytes());
+        let checksum = crate::util::wire::fnv1a(&crafted[..body_len]);
+        crafted[body_len..].copy_from_slice(&checksum.to_le_bytes());
+        let read = contiguous::NFA::from_bytes(&crafted);
+        assert_eq!(is_valid, read.is_ok(), "pattern length {}", len);
+        if let Ok(read) = read {
+            let input = Input::new("xxab");
+            read.try_find_iter(input).unwrap().for_each(drop);
+        }
+    }
 }
 
-// See: https://github.com/BurntSushi/aho-corasick/issues/44
-//
-// In short, this test ensures that enabling ASCII case insensitivity does not
-// visit an exponential number of states when filling in failure transitions.
 #[test]
-fn regression_ascii_case_insensitive_no_exponential() {
-    let ac = AhoCorasick::builder()
-        .ascii_case_insensitive(true)
-        .build(&["Tsubaki House-Triple Shot Vol01校花三姐妹"])
-        .unwrap();
-    assert!(ac.find("").is_none());
+fn binary_patterns_through_pipeline() {
+    let text = b"\\x00\\x00\tNUL\n\\xFF\\x00\\x8B\t\\x00\n\\x00\\x00\\x00\t\n";
+    let dict = crate::dictionary::parse_replacements(text).unwrap();
+    let (patterns, replacements) = dict.into_parts();
+    assert_eq!(
+        vec![
+            b"\x00\x00".to_vec(),
+            b"\xFF\x00\x8B".to_vec(),
+            b"\x00\x00\x00".to_vec()
+        ],
+        patterns,
+    );
+
+    let haystack = b"a\x00\x00b\xFF\x00\x8B\x00\x00\x00\x00";
+    let expected = b"aNULb\x00NULNUL".to_vec();
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        let ac =
+            AhoCorasick::builder().kind(Some(kind)).build(&patterns).unwrap();
+        let matches: Vec<Match> = ac.find_iter(haystack).collect();
+        assert_eq!(
+            vec![
+                Match::must(0, 1..3),
+                Match::must(1, 4..7),
+                Match::must(0, 7..9),
+                Match::must(0, 9..11),
+            ],
+            matches,
+        );
+        assert_eq!(expected, ac.replace_all_bytes(haystack, &replacements));
+
+        #[cfg(feature = "std")]
+        {
+            let mut streamed = vec![];
+            ac.try_stream_replace_all(
+                &haystack[..],
+                &mut streamed,
+                &replacements,
+            )
+            .unwrap();
+            assert_eq!(expected, streamed);
+
+            for chunk_size in 1..=haystack.len() {
+                let mut replacer = ac.replacer(replacements.clone()).unwrap();
+                let mut chunked = vec![];
+                for chunk in haystack.chunks(chunk_size) {
+                    chunked
+                        .extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                chunked.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(expected, chunked, "chunk size {}", chunk_size);
+            }
+        }
+    }
 }
 
-// See: https://github.com/BurntSushi/aho-corasick/issues/53
-//
-// This test ensures that the rare byte prefilter works in a particular corner
-// case. In particular, the shift offset detected for '/' in the patterns below
-// was incorrect, leading to a false negative.
 #[test]
-fn regression_rare_byte_prefilter() {
-    use crate::AhoCorasick;
+fn replacers_are_ascii_case_insensitive() {
+    let patterns = ["Foo", "bAR"];
+    let replacements = ["1", "22"];
+    let haystack = b"FOOBAR foo Bar fOoBaR! xbarfoo";
+    let expected = b"122 1 22 122! x221".to_vec();
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        let ac = AhoCorasick::builder()
+            .kind(Some(kind))
+            .ascii_case_insensitive(true)
+            .build(patterns)
+            .unwrap();
+        assert_eq!(expected, ac.replace_all_bytes(haystack, &replacements));
 
-    let ac = AhoCorasick::new(&["ab/j/", "x/"]).unwrap();
-    assert!(ac.is_match("ab/j/"));
+        #[cfg(feature = "std")]
+        {
+            let mut streamed = vec![];
+            ac.try_stream_replace_all(
+                &haystack[..],
+                &mut streamed,
+                &replacements,
+            )
+            .unwrap();
+            assert_eq!(expected, streamed);
+
+            for chunk_size in 1..=haystack.len() {
+                let mut replacer = ac.replacer(replacements).unwrap();
+                let mut chunked = vec![];
+                for chunk in haystack.chunks(chunk_size) {
+                    chunked
+                        .extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                chunked.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(expected, chunked, "chunk size {}", chunk_size);
+            }
+        }
+    }
 }
 
 #[test]
-fn regression_case_insensitive_prefilter() {
-    for c in b'a'..b'z' {
-        for c2 in b'a'..b'z' {
-            let c = c as char;
-            let c2 = c2 as char;
-            let needle = format!("{}{}", c, c2).to_lowercase();
-            let haystack = needle.to_uppercase();
-            let ac = AhoCorasick::builder()
-                .ascii_case_insensitive(true)
-                .prefilter(true)
-                .build(&[&needle])
+fn padded_replacer_preserves_length() {
+    let patterns = [&b"\x00\xFF\x00"[..], b"ab", b"abcd"];
+    let haystack = b"x\x00\xFF\x00abcdab\x00";
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        let ac =
+            AhoCorasick::builder().kind(Some(kind)).build(patterns).unwrap();
+        for (padding, expected) in [
+            (Padding::Right(b'.'), &b"x1..2.cd2.\x00"[..]),
+            (Padding::Left(0), b"x\x00\x001\x002cd\x002\x00"),
+        ] {
+            for chunk_size in 1..=haystack.len() {
+                let mut replacer = ac
+                    .replacer_with(
+                        AhoCorasickReplacerBuilder::new()
+                            .padding(Some(padding)),
+                        ["1", "2", "2"],
+                    )
+                    .unwrap();
+                let mut out = vec![];
+                for chunk in haystack.chunks(chunk_size) {
+                    out.extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                out.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(expected, out, "chunk size {}", chunk_size);
+            }
+        }
+
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.padding(Some(Padding::Right(b' ')));
+        assert!(ac.replacer_with(&builder, ["1", "toolong", "2"]).is_err());
+        assert!(ac.replacer_with(&builder, ["1", "2"]).is_err());
+        assert!(ac
+            .replacer_with(builder.padding(None), ["1", "toolong", "2"])
+            .is_ok());
+    }
+}
+
+#[test]
+fn coalescing_replacer() {
+    let ac = AhoCorasick::new(["foo", "ab", "b"]).unwrap();
+    let haystack = b"foofoofoo!abbfoo-fofoo-abab";
+    for (coalesce, expected) in
+        [(false, &b"XXX!YZX-foX-YY"[..]), (true, b"X!Y-foX-Y")]
+    {
+        for chunk_size in 1..=haystack.len() {
+            let mut replacer = ac
+                .replacer_with(
+                    AhoCorasickReplacerBuilder::new().coalesce(coalesce),
+                    ["X", "Y", "Z"],
+                )
                 .unwrap();
-            assert_eq!(
-                1,
-                ac.find_iter(&haystack).count(),
-                "failed to find {:?} in {:?}\n\nautomaton:\n{:?}",
-                needle,
-                haystack,
-                ac,
-            );
+            let mut out = vec![];
+            for chunk in haystack.chunks(chunk_size) {
+                out.extend_from_slice(replacer.replace(chunk).unwrap());
+            }
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(expected, out, "chunk size {}", chunk_size);
         }
     }
+
+    // A run is longer than the padded replacement of its first match, so
+    // padding can't be combined with coalescing.
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.coalesce(true).padding(Some(Padding::Right(b' ')));
+    assert!(ac.replacer_with(&builder, ["X", "Y", "Z"]).is_err());
+    assert!(ac.replacer_with_fn(&builder, |_, _| vec![]).is_err());
+    assert!(ac
+        .replacer_with(builder.coalesce(false), ["X", "Y", "Z"])
+        .is_ok());
 }
 
-// See: https://github.com/BurntSushi/aho-corasick/issues/64
-//
-// This occurs when the rare byte prefilter is active.
-#[cfg(feature = "std")]
 #[test]
-fn regression_stream_rare_byte_prefilter() {
-    use std::io::Read;
+fn dedup_replacer() {
+    use crate::{MatchDeduper, PatternID};
 
-    // NOTE: The test only fails if this ends with j.
-    const MAGIC: [u8; 5] = *b"1234j";
+    let ac = AhoCorasick::new(["ab", "c"]).unwrap();
+    let haystack = b"ab ab c abab ccc ab";
+    let mut dedup = MatchDeduper::new(Some(6));
+    dedup.window(PatternID::must(1), Some(2));
+    let expected = b"X ab Y Xab YcY X";
+    for chunk_size in 1..=haystack.len() {
+        let mut replacer = ac
+            .replacer_with(
+                AhoCorasickReplacerBuilder::new().dedup(Some(dedup.clone())),
+                ["X", "Y"],
+            )
+            .unwrap();
+        let mut out = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(&expected[..], out, "chunk size {}", chunk_size);
+    }
+}
 
-    // NOTE: The test fails for value in 8188..=8191 These value put the string
-    // to search accross two call to read because the buffer size is 64KB by
-    // default.
-    const BEGIN: usize = 65_535;
+#[test]
+fn replacer_checks_automaton_and_replacements() {
+    let ac = AhoCorasick::new(["ab", "c"]).unwrap();
+    assert!(ac.replacer(["X", "Y"]).is_ok());
+    assert!(ac.replacer(["X"]).is_err());
+    assert!(ac.replacer(["X", "Y", "Z"]).is_err());
+    // The reason a replacer can't be built is kept in its match error.
+    let err = ac.replacer(["X"]).err().unwrap();
+    assert!(matches!(err.kind(), crate::MatchErrorKind::Replacer(_)));
+    assert_eq!(
+        "expected 2 replacements (one per pattern), but got 1",
+        format!("{}", err),
+    );
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(["ab", "c"])
+        .unwrap();
+    assert!(ac.replacer(["X", "Y"]).is_err());
+    let ac = AhoCorasick::builder()
+        .start_kind(StartKind::Anchored)
+        .build(["ab", "c"])
+        .unwrap();
+    assert_eq!(
+        &crate::MatchErrorKind::InvalidInputUnanchored,
+        ac.replacer(["X", "Y"]).err().unwrap().kind(),
+    );
+}
 
-    /// This is just a structure that implements Reader. The reader
-    /// implementation will simulate a file filled with 0, except for the MAGIC
-    /// string at offset BEGIN.
-    #[derive(Default)]
-    struct R {
-        read: usize,
+#[test]
+fn try_replacer_reports_build_errors() {
+    let ac = AhoCorasick::new(["ab", "c"]).unwrap();
+    // Any iterator of replacements is accepted, and the replacer built is
+    // the same as the one of AhoCorasick::replacer.
+    let owned: Vec<String> = vec!["X".into(), "Y".into()];
+    let bytes: Vec<Vec<u8>> = vec![b"X".to_vec(), b"Y".to_vec()];
+    let replacers = [
+        ac.try_replacer(["X", "Y"]).unwrap(),
+        ac.try_replacer(&owned).unwrap(),
+        ac.try_replacer(bytes).unwrap(),
+        ac.try_replacer(owned.iter().map(|r| r.as_bytes())).unwrap(),
+    ];
+    for mut replacer in replacers {
+        let mut out = replacer.replace(b"abca").unwrap().to_vec();
+        out.extend_from_slice(replacer.replace(b"b").unwrap());
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(b"XYX".to_vec(), out);
     }
 
-    impl Read for R {
-        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-            if self.read > 100000 {
-                return Ok(0);
-            }
-            let mut from = 0;
-            if self.read < BEGIN {
-                from = buf.len().min(BEGIN - self.read);
+    // The count of replacements is checked up front, whether there are too
+    // few or too many of them.
+    for (replace_with, got) in [(&["X"][..], 1), (&["X", "Y", "Z"], 3)] {
+        let err = ac.try_replacer(replace_with).err().unwrap();
+        assert_eq!(
+            format!(
+                "expected 2 replacements (one per pattern), but got {}",
+                got,
+            ),
+            format!("{}", err),
+        );
+    }
+    let empty: [&str; 0] = [];
+    assert!(ac.try_replacer(empty).is_err());
+
+    // Automata that can't be used for replacement are reported the same
+    // way.
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(["ab", "c"])
+        .unwrap();
+    let err = ac.try_replacer(["X", "Y"]).err().unwrap();
+    assert_eq!(
+        format!("{}", ac.replacer(["X", "Y"]).err().unwrap()),
+        format!("{}", err),
+    );
+}
+
+#[test]
+fn replacer_passes_gated_regions_through() {
+    let ac = AhoCorasick::new(["abc", "bcd", "x"]).unwrap();
+    let replacements = ["1", "2", "3"];
+    // Regions alternate between gated off and matched, starting gated off.
+    let regions: &[&[u8]] =
+        &[b"xab", b"cxabcdab", b"cx", b"", b"ab", b"cd", b"abcab", b"x"];
+    let mut expected = vec![];
+    for (i, region) in regions.iter().enumerate() {
+        if i % 2 == 0 {
+            expected.extend_from_slice(region);
+        } else {
+            expected.extend(ac.replace_all_bytes(region, &replacements));
+        }
+    }
+    for chunk_size in 1..=6 {
+        let mut replacer = ac.replacer(replacements).unwrap();
+        let mut out = vec![];
+        for (i, region) in regions.iter().enumerate() {
+            replacer.matching_enabled(i % 2 == 1);
+            assert_eq!(i % 2 == 1, replacer.is_matching_enabled());
+            for chunk in region.chunks(chunk_size) {
+                out.extend_from_slice(replacer.replace(chunk).unwrap());
+            }
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(expected, out, "chunk size {}", chunk_size);
+    }
+}
+
+#[test]
+fn replace_iter_yields_remainder_and_errors() {
+    let ac = AhoCorasick::new(["abc", "bcd", "x"]).unwrap();
+    let replacements = ["1", "2", "3"];
+    let haystack = b"abcd abbcd xabc bcab";
+    let expected = ac.replace_all_bytes(haystack, &replacements);
+    for chunk_size in 1..=haystack.len() {
+        let chunks: Vec<Result<&[u8], &str>> =
+            haystack.chunks(chunk_size).map(Ok).collect();
+        let replacer = ac.replacer(replacements).unwrap();
+        let out: Vec<Vec<u8>> =
+            replacer.replace_iter(chunks).map(|r| r.unwrap()).collect();
+        assert_eq!(expected, out.concat(), "chunk size {}", chunk_size);
+    }
+
+    // An error doesn't disturb the chunks around it, and the bytes held
+    // back at the end come last.
+    let chunks = vec![Ok(&b"xa"[..]), Err("oops"), Ok(b"bc a")];
+    let replacer = ac.replacer(replacements).unwrap();
+    let items: Vec<Result<Vec<u8>, &str>> =
+        replacer.replace_iter(chunks).collect();
+    assert_eq!(
+        vec![
+            Ok(b"3".to_vec()),
+            Err("oops"),
+            Ok(b"1 ".to_vec()),
+            Ok(b"a".to_vec())
+        ],
+        items,
+    );
+    let replacer = ac.replacer(replacements).unwrap();
+    let mut it = replacer.replace_iter(Vec::<Result<&[u8], ()>>::new());
+    assert_eq!(None, it.next());
+    assert_eq!(None, it.next());
+}
+
+#[test]
+fn replacer_with_table_selects_replacements() {
+    let ac = AhoCorasick::builder()
+        .replacement_tables(vec![
+            vec!["1", "22"],
+            vec!["333", ""],
+            vec!["too few"],
+        ])
+        .build(["ab", "cd"])
+        .unwrap();
+    assert_eq!(3, ac.replacement_tables_len());
+    assert_eq!(
+        Some(&[b"1".to_vec(), b"22".to_vec()][..]),
+        ac.replacement_table(0)
+    );
+    assert_eq!(None, ac.replacement_table(3));
+
+    let replace = |table_id: usize, haystack: &[u8]| {
+        let mut replacer = ac.replacer_with_table(table_id).unwrap();
+        let mut out = vec![];
+        for chunk in haystack.chunks(3) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        out
+    };
+    assert_eq!(b"1 22 x1".to_vec(), replace(0, b"ab cd xab"));
+    assert_eq!(b"333  x333".to_vec(), replace(1, b"ab cd xab"));
+    assert!(ac.replacer_with_table(2).is_err());
+    assert!(ac.replacer_with_table(3).is_err());
+
+    // A table may also be given to a configured replacer.
+    let mut replacer = ac
+        .replacer_with(
+            AhoCorasickReplacerBuilder::new()
+                .padding(Some(Padding::Right(b'.'))),
+            ac.replacement_table(0).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(b"1. 22".to_vec(), replacer.replace(b"ab cd").unwrap());
+
+    // Clones of the automaton keep its tables.
+    let clone = ac.clone();
+    assert_eq!(ac.replacement_table(1), clone.replacement_table(1));
+}
+
+#[test]
+fn replacer_disabled_patterns_act_as_removed() {
+    use crate::PatternID;
+
+    let patterns = ["abcd", "bc", "b", "cda", "xbcdax"];
+    let replacements = ["1", "22", "333", "4444", "5"];
+    let haystack = b"abcdaxbcdax abcabcd bbcda";
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        let ac =
+            AhoCorasick::builder().kind(Some(kind)).build(patterns).unwrap();
+        for enabled in 0u32..(1 << patterns.len()) {
+            let is_enabled = |i: usize| enabled & (1 << i) != 0;
+            let mut replacer = ac.replacer(replacements).unwrap();
+            for i in 0..patterns.len() {
+                replacer.set_enabled(PatternID::must(i), is_enabled(i));
+                assert_eq!(
+                    is_enabled(i),
+                    replacer.is_enabled(PatternID::must(i))
+                );
+            }
+            let mut got = vec![];
+            for chunk in haystack.chunks(4) {
+                got.extend_from_slice(replacer.replace(chunk).unwrap());
+            }
+            got.extend_from_slice(replacer.finish().unwrap());
+
+            let kept: Vec<usize> =
+                (0..patterns.len()).filter(|&i| is_enabled(i)).collect();
+            let expected = if kept.is_empty() {
+                haystack.to_vec()
+            } else {
+                let ac = AhoCorasick::builder()
+                    .kind(Some(kind))
+                    .build(kept.iter().map(|&i| patterns[i]))
+                    .unwrap();
+                let mut replacer = ac
+                    .replacer(kept.iter().map(|&i| replacements[i]))
+                    .unwrap();
+                let mut out = replacer.replace(haystack).unwrap().to_vec();
+                out.extend_from_slice(replacer.finish().unwrap());
+                out
+            };
+            assert_eq!(
+                expected, got,
+                "kind {:?}, enabled {:05b}",
+                kind, enabled
+            );
+        }
+    }
+}
+
+#[test]
+fn disabled_patterns_act_as_removed() {
+    use crate::PatternID;
+
+    let patterns = ["abcd", "bc", "b", "cda", "xbcdax"];
+    let replacements = ["1", "22", "333", "4444", "5"];
+    let haystack = b"abcdaxbcdax abcabcd bbcda";
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        for match_kind in [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ] {
+            let builder = AhoCorasick::builder()
+                .kind(Some(kind))
+                .match_kind(match_kind)
+                .store_patterns(true)
+                .clone();
+            for enabled in 0u32..(1 << patterns.len()) {
+                let is_enabled = |i: usize| enabled & (1 << i) != 0;
+                let mut ac = builder.build(patterns).unwrap();
+                for i in (0..patterns.len()).filter(|&i| !is_enabled(i)) {
+                    ac.disable_pattern(PatternID::must(i)).unwrap();
+                }
+                for i in 0..patterns.len() {
+                    let pid = PatternID::must(i);
+                    assert_eq!(is_enabled(i), ac.is_pattern_enabled(pid));
+                }
+
+                // The reference automaton only has the enabled patterns, and
+                // its matches are mapped back to the original pattern IDs.
+                let kept: Vec<usize> =
+                    (0..patterns.len()).filter(|&i| is_enabled(i)).collect();
+                let reference =
+                    builder.build(kept.iter().map(|&i| patterns[i])).unwrap();
+                let map = |m: Match| {
+                    Match::must(kept[m.pattern().as_usize()], m.span())
+                };
+                let context = format!(
+                    "kind {:?}, match kind {:?}, enabled {:05b}",
+                    kind, match_kind, enabled
+                );
+
+                let got: Vec<Match> = ac.find_iter(haystack).collect();
+                let expected: Vec<Match> =
+                    reference.find_iter(haystack).map(map).collect();
+                assert_eq!(expected, got, "{}", context);
+                assert_eq!(
+                    vec![expected.clone()],
+                    ac.find_many(&[haystack]),
+                    "{}",
+                    context
+                );
+                if match_kind == MatchKind::Standard {
+                    let got: Vec<Match> =
+                        ac.find_overlapping_iter(haystack).collect();
+                    let expected: Vec<Match> = reference
+                        .find_overlapping_iter(haystack)
+                        .map(map)
+                        .collect();
+                    assert_eq!(expected, got, "{}", context);
+                }
+
+                let kept_replacements: Vec<&str> =
+                    kept.iter().map(|&i| replacements[i]).collect();
+                let expected =
+                    reference.replace_all_bytes(haystack, &kept_replacements);
+                let got = ac.replace_all_bytes(haystack, &replacements);
+                assert_eq!(expected, got, "{}", context);
+
+                // Streams only support standard match semantics.
+                if match_kind != MatchKind::Standard {
+                    continue;
+                }
+                let got: Vec<Match> = ac
+                    .stream_find_iter(&haystack[..])
+                    .map(|r| r.unwrap())
+                    .collect();
+                let expected: Vec<Match> =
+                    reference.find_iter(haystack).map(map).collect();
+                assert_eq!(expected, got, "{}", context);
+                let got: Vec<Match> = ac
+                    .try_stream_find_iter_line_column(
+                        &haystack[..],
+                        crate::ColumnUnit::Byte,
+                    )
+                    .unwrap()
+                    .map(|r| r.unwrap().0)
+                    .collect();
+                assert_eq!(expected, got, "{}", context);
+                let mut finder = ac.stream_finder().unwrap();
+                let mut got = vec![];
+                for chunk in haystack.chunks(3) {
+                    got.extend_from_slice(finder.find(chunk));
+                }
+                assert_eq!(expected, got, "{}", context);
+                let mut got = vec![];
+                ac.try_stream_replace_all(
+                    &haystack[..],
+                    &mut got,
+                    &replacements,
+                )
+                .unwrap();
+                let expected =
+                    reference.replace_all_bytes(haystack, &kept_replacements);
+                assert_eq!(expected, got, "{}", context);
+
+                let mut replacer = ac.replacer(replacements).unwrap();
+                let mut got = vec![];
+                for chunk in haystack.chunks(4) {
+                    got.extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                got.extend_from_slice(replacer.finish().unwrap());
+                let mut replacer =
+                    reference.replacer(&kept_replacements).unwrap();
+                let mut expected =
+                    replacer.replace(haystack).unwrap().to_vec();
+                expected.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(expected, got, "{}", context);
+            }
+        }
+    }
+
+    // Enabling every pattern again goes back to the automaton's own
+    // matches, and clones are unaffected.
+    let mut ac = AhoCorasick::builder()
+        .store_patterns(true)
+        .build(["ab", "b"])
+        .unwrap();
+    let clone = ac.clone();
+    ac.disable_pattern(PatternID::must(0)).unwrap();
+    assert_eq!(Some(Match::must(1, 1..2)), ac.find("ab"));
+    assert_eq!(Some(Match::must(0, 0..2)), clone.find("ab"));
+    ac.enable_pattern(PatternID::must(0));
+    assert_eq!(Some(Match::must(0, 0..2)), ac.find("ab"));
+    assert!(!ac.is_pattern_enabled(PatternID::must(2)));
+}
+
+#[test]
+fn disable_pattern_requires_stored_patterns() {
+    let mut ac = AhoCorasick::new(["ab", "b"]).unwrap();
+    assert_eq!(
+        crate::MatchErrorKind::UnsupportedDisabledPattern,
+        *ac.disable_pattern(crate::PatternID::must(0)).unwrap_err().kind(),
+    );
+    assert!(ac.is_pattern_enabled(crate::PatternID::must(0)));
+    assert_eq!(Some(Match::must(0, 0..2)), ac.find("ab"));
+}
+
+#[test]
+#[should_panic]
+fn disable_pattern_panics_on_invalid_id() {
+    let mut ac =
+        AhoCorasick::builder().store_patterns(true).build(["ab"]).unwrap();
+    let _ = ac.disable_pattern(crate::PatternID::must(1));
+}
+
+#[test]
+fn duplicate_policy_after_folding() {
+    use crate::DuplicatePolicy;
+
+    let patterns = ["FOO", "bar", "foo", "ba", "Foo", "bar"];
+    let haystack = "xfoo bar fOo";
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        for match_kind in [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ] {
+            let build = |policy| {
+                AhoCorasick::builder()
+                    .kind(Some(kind))
+                    .match_kind(match_kind)
+                    .ascii_case_insensitive(true)
+                    .duplicate_policy(policy)
+                    .build(patterns)
+            };
+            let all = build(DuplicatePolicy::All).unwrap();
+            let lowest = build(DuplicatePolicy::Lowest).unwrap();
+            // Non-overlapping searches always report the lowest ID.
+            let expected: Vec<Match> = match match_kind {
+                MatchKind::Standard => vec![
+                    Match::must(0, 1..4),
+                    Match::must(3, 5..7),
+                    Match::must(0, 9..12),
+                ],
+                _ => vec![
+                    Match::must(0, 1..4),
+                    Match::must(1, 5..8),
+                    Match::must(0, 9..12),
+                ],
+            };
+            assert_eq!(expected, all.find_iter(haystack).collect::<Vec<_>>());
+            assert_eq!(
+                expected,
+                lowest.find_iter(haystack).collect::<Vec<_>>(),
+            );
+            if match_kind == MatchKind::Standard {
+                let pids = |ac: &AhoCorasick| {
+                    ac.find_overlapping_iter(haystack)
+                        .map(|m| m.pattern().as_usize())
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(vec![0, 2, 4, 3, 1, 5, 0, 2, 4], pids(&all));
+                assert_eq!(vec![0, 3, 1, 0], pids(&lowest));
+            }
+
+            let err = build(DuplicatePolicy::Error).unwrap_err();
+            assert_eq!(
+                "pattern 2 is a duplicate of pattern 0",
+                format!("{}", err),
+            );
+            // Without folding, only the byte-for-byte duplicate is rejected.
+            let err = AhoCorasick::builder()
+                .kind(Some(kind))
+                .match_kind(match_kind)
+                .duplicate_policy(DuplicatePolicy::Error)
+                .build(patterns)
+                .unwrap_err();
+            assert_eq!(
+                "pattern 5 is a duplicate of pattern 1",
+                format!("{}", err),
+            );
+            AhoCorasick::builder()
+                .kind(Some(kind))
+                .match_kind(match_kind)
+                .duplicate_policy(DuplicatePolicy::Error)
+                .build(&patterns[..5])
+                .unwrap();
+        }
+    }
+
+    // Sharded searchers report the IDs of the patterns as given.
+    let err = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .duplicate_policy(DuplicatePolicy::Error)
+        .build_sharded(["bar", "x", "FOO", "y", "foo"], 3)
+        .unwrap_err();
+    assert_eq!("pattern 4 is a duplicate of pattern 2", format!("{}", err));
+}
+
+#[test]
+fn provenance_matches_overlapping_duplicates() {
+    use crate::PatternID;
+
+    let patterns = ["ab", "AB", "b", "aB", "B", "ab", "xab"];
+    let haystack = "xAb Ab b";
+    for ascii_case_insensitive in [false, true] {
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            let ac = AhoCorasick::builder()
+                .kind(Some(kind))
+                .ascii_case_insensitive(ascii_case_insensitive)
+                .track_provenance(true)
+                .build(patterns)
+                .unwrap();
+            assert!(ac.pattern(PatternID::must(0)).is_none());
+            // An overlapping search reports every duplicate of a pattern,
+            // which are the patterns it originates from, and no others.
+            let matches: Vec<Match> =
+                ac.find_overlapping_iter(haystack).collect();
+            for m in matches.iter() {
+                let same: Vec<PatternID> = matches
+                    .iter()
+                    .filter(|o| o.span() == m.span())
+                    .map(|o| o.pattern())
+                    .collect();
+                assert_eq!(same, ac.provenance(m.pattern()).unwrap());
+            }
+        }
+    }
+    let ac = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .track_provenance(true)
+        .build(patterns)
+        .unwrap();
+    assert_eq!(None, ac.provenance(PatternID::must(patterns.len())));
+}
+
+#[test]
+fn pattern_set_acts_as_subset() {
+    use crate::{PatternID, PatternSet};
+
+    let patterns = ["abcd", "bc", "b", "cda", "xbcdax"];
+    let haystack = b"abcdaxbcdax abcabcd bbcda";
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        for match_kind in [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ] {
+            let builder = AhoCorasick::builder()
+                .kind(Some(kind))
+                .match_kind(match_kind)
+                .start_kind(StartKind::Both)
+                .store_patterns(true)
+                .clone();
+            let ac = builder.build(patterns).unwrap();
+            let sharded = builder.build_sharded(patterns, 3).unwrap();
+            for selected in 0u32..(1 << patterns.len()) {
+                let kept: Vec<usize> = (0..patterns.len())
+                    .filter(|&i| selected & (1 << i) != 0)
+                    .collect();
+                let set: PatternSet =
+                    kept.iter().map(|&i| PatternID::must(i)).collect();
+                let reference =
+                    builder.build(kept.iter().map(|&i| patterns[i])).unwrap();
+                let map = |m: Match| {
+                    Match::must(kept[m.pattern().as_usize()], m.span())
+                };
+                let input = Input::new(haystack).pattern_set(Some(&set));
+
+                let expected: Vec<Match> =
+                    reference.find_iter(haystack).map(map).collect();
+                let got: Vec<Match> = ac.find_iter(input.clone()).collect();
+                assert_eq!(expected, got, "{:?} {:?}", match_kind, kept);
+                let got: Vec<Match> =
+                    sharded.find_iter(input.clone()).collect();
+                assert_eq!(expected, got, "{:?} {:?}", match_kind, kept);
+                assert_eq!(!expected.is_empty(), ac.is_match(input.clone()));
+                for start in 0..haystack.len() {
+                    let input = input.clone().range(start..);
+                    let anchored = input.clone().anchored(Anchored::Yes);
+                    let expected = reference
+                        .find(Input::new(haystack).range(start..))
+                        .map(map);
+                    assert_eq!(expected, ac.find(input));
+                    let expected = reference
+                        .find(
+                            Input::new(haystack)
+                                .range(start..)
+                                .anchored(Anchored::Yes),
+                        )
+                        .map(map);
+                    assert_eq!(expected, ac.find(anchored));
+                }
+                if match_kind == MatchKind::Standard {
+                    let expected: Vec<Match> = reference
+                        .find_overlapping_iter(haystack)
+                        .map(map)
+                        .collect();
+                    let got: Vec<Match> =
+                        ac.find_overlapping_iter(input).collect();
+                    assert_eq!(expected, got, "{:?}", kept);
+                }
+            }
+        }
+    }
+
+    // The matches of patterns outside the set need the stored patterns to
+    // be rejected, and IDs beyond the patterns are ignored.
+    let ac = AhoCorasick::new(["ab", "b"]).unwrap();
+    let set: PatternSet =
+        [PatternID::must(1), PatternID::must(5)].into_iter().collect();
+    let input = Input::new("ab").pattern_set(Some(&set));
+    assert_eq!(
+        crate::MatchErrorKind::UnsupportedPatternSet,
+        *ac.try_find(input.clone()).unwrap_err().kind(),
+    );
+    let ac = AhoCorasick::builder()
+        .store_patterns(true)
+        .build(["ab", "b"])
+        .unwrap();
+    assert_eq!(Some(Match::must(1, 1..2)), ac.find(input));
+}
+
+#[test]
+fn shadow_replacer_reports_differing_decisions() {
+    let haystack = b"abcd xyz abab";
+    let active = AhoCorasick::new(["abc", "xyz"]).unwrap();
+    let candidate = AhoCorasick::new(["bcd", "xyz", "ab"]).unwrap();
+    let expected = active.replace_all_bytes(haystack, &["1", "2"]);
+    for chunk_size in 1..=haystack.len() {
+        let mut replacer = active
+            .replacer(["1", "2"])
+            .unwrap()
+            .shadow(candidate.replacer(["4", "22", "3"]).unwrap());
+        let mut out = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(expected, out, "chunk size {}", chunk_size);
+
+        let diffs: Vec<_> = replacer
+            .take_diffs()
+            .into_iter()
+            .map(|d| {
+                (
+                    d.span().range(),
+                    d.active_replacement().map(<[u8]>::to_vec),
+                    d.shadow_replacement().map(<[u8]>::to_vec),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (0..2, None, Some(b"3".to_vec())),
+                (0..3, Some(b"1".to_vec()), None),
+                (5..8, Some(b"2".to_vec()), Some(b"22".to_vec())),
+                (9..11, None, Some(b"3".to_vec())),
+                (11..13, None, Some(b"3".to_vec())),
+            ],
+            diffs,
+            "chunk size {}",
+            chunk_size,
+        );
+        assert!(replacer.diffs().is_empty());
+    }
+
+    // The same dictionary never differs.
+    let mut replacer = active
+        .replacer(["1", "2"])
+        .unwrap()
+        .shadow(active.replacer(["1", "2"]).unwrap());
+    let mut out = replacer.replace(haystack).unwrap().to_vec();
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(expected, out);
+    assert!(replacer.diffs().is_empty());
+}
+
+#[test]
+fn lookahead_decides_matches_across_chunks() {
+    use crate::{Lookahead, PatternID};
+
+    let dot = Lookahead::new(3).bytes(b".");
+    let ac = AhoCorasick::builder()
+        .lookahead(PatternID::must(0), Some(dot))
+        .lookahead(PatternID::must(2), Some(Lookahead::new(1).bytes(b"!")))
+        .lookahead(PatternID::must(9), Some(dot))
+        .build(["host", "st", "b", "ab"])
+        .unwrap();
+    let replace = |chunk_size: usize, haystack: &[u8]| {
+        let mut replacer = ac.replacer(["H", "S", "B", "A"]).unwrap();
+        let mut out = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        out
+    };
+    let tests: &[(&[u8], &[u8])] = &[
+        (b"host.com", b"H.com"),
+        (b"hostabc.", b"hoSAc."),
+        (b"hostabcd.", b"hoSAcd."),
+        (b"host", b"hoS"),
+        (b"hostab", b"hoSA"),
+        (b"b! b", b"B! b"),
+        (b"hostb!.", b"HB!."),
+        (b"hostb", b"hoSb"),
+    ];
+    for &(haystack, expected) in tests {
+        for chunk_size in 1..=haystack.len() {
+            assert_eq!(
+                expected.to_vec(),
+                replace(chunk_size, haystack),
+                "haystack {:?}, chunk size {}",
+                String::from_utf8_lossy(haystack),
+                chunk_size,
+            );
+        }
+    }
+
+    // Searches see the whole haystack, so they decide every match at once.
+    let starts: Vec<usize> =
+        ac.find_iter("host. host b!").map(|m| m.start()).collect();
+    assert_eq!(vec![0, 8, 11], starts);
+    let input = Input::new("host").verify(false);
+    assert_eq!(Some(Match::must(0, 0..4)), ac.find(input));
+    let sharded = AhoCorasick::builder()
+        .lookahead(PatternID::must(0), Some(dot))
+        .lookahead(PatternID::must(2), Some(Lookahead::new(1).bytes(b"!")))
+        .build_sharded(["host", "st", "b", "ab"], 3)
+        .unwrap();
+    for haystack in ["host. host b!", "hostb!", "xb b!.ab"] {
+        assert_eq!(
+            ac.find_iter(haystack).collect::<Vec<_>>(),
+            sharded.find_iter(haystack).collect::<Vec<_>>(),
+        );
+    }
+
+    // A match never waits for its lookahead across a toggle point.
+    let mut replacer = ac.replacer(["H", "S", "B", "A"]).unwrap();
+    let mut out = replacer.replace(b"host").unwrap().to_vec();
+    replacer.matching_enabled(false);
+    out.extend_from_slice(replacer.replace(b".com").unwrap());
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(b"hoS.com".to_vec(), out);
+
+    // A shadow replacer compares matches once they're decided.
+    for chunk_size in 1..=8 {
+        let mut replacer = ac
+            .replacer(["H", "S", "B", "A"])
+            .unwrap()
+            .shadow(ac.replacer(["H", "S", "B", "A"]).unwrap());
+        for chunk in b"hostabc. host.b!".chunks(chunk_size) {
+            replacer.replace(chunk).unwrap();
+        }
+        replacer.finish().unwrap();
+        assert!(replacer.diffs().is_empty(), "chunk size {}", chunk_size);
+    }
+}
+
+// This tests that replacing all matches replaces the matches reported by
+// `find_iter`, not those of the automaton, when patterns have lookaheads.
+#[test]
+fn replace_all_honors_lookaheads() {
+    use alloc::borrow::Cow;
+
+    use crate::{Lookahead, PatternID};
+
+    let dot = Some(Lookahead::new(1).bytes(b"."));
+    let ac = AhoCorasick::builder()
+        .lookahead(PatternID::must(0), dot)
+        .build(["a"])
+        .unwrap();
+    let found: Vec<Match> = ac.find_iter("ab a. aa").collect();
+    assert_eq!(vec![Match::must(0, 3..4)], found);
+    assert_eq!("ab X. aa", ac.replace_all("ab a. aa", &["X"]));
+    assert!(matches!(
+        ac.replace_all_cow(b"ab aa", &["X"]),
+        Cow::Borrowed(b"ab aa"),
+    ));
+
+    // The verifier is ignored, as it is by replacers.
+    let mut builder = AhoCorasick::builder();
+    builder
+        .lookahead(PatternID::must(0), dot)
+        .lookahead(PatternID::must(2), Some(Lookahead::new(2).bytes(b"!")));
+    let patterns = ["a", "ab", "b", "ba"];
+    let ac = builder.build(patterns).unwrap();
+    let verified = builder.verify(|_, _| false).build(patterns).unwrap();
+    let with = ["<a>", "<ab>", "<b>", "<ba>"];
+    for haystack in ["ab a. aa", "ab. b!a ba!", "a.b a b. ba.", "b a!"] {
+        let mut expected = String::new();
+        let mut last = 0;
+        for m in ac.find_iter(haystack) {
+            expected.push_str(&haystack[last..m.start()]);
+            expected.push_str(with[m.pattern()]);
+            last = m.end();
+        }
+        expected.push_str(&haystack[last..]);
+        for ac in [&ac, &verified] {
+            assert_eq!(expected, ac.replace_all(haystack, &with));
+            assert_eq!(
+                expected.as_bytes(),
+                ac.replace_all_bytes(haystack.as_bytes(), &with),
+            );
+            assert_eq!(expected, ac.replace_all_cow_str(haystack, &with));
+            let mut replacer = ac.replacer(with).unwrap();
+            let mut out =
+                replacer.replace(haystack.as_bytes()).unwrap().to_vec();
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(expected.as_bytes(), out, "{:?}", haystack);
+        }
+    }
+}
+
+#[test]
+fn stream_searches_reject_lookaheads() {
+    use crate::{Lookahead, MatchErrorKind, PatternID};
+
+    let ac = AhoCorasick::builder()
+        .lookahead(PatternID::must(0), Some(Lookahead::new(1).bytes(b".")))
+        .build(["a"])
+        .unwrap();
+    assert_eq!(
+        &MatchErrorKind::UnsupportedLookahead,
+        ac.stream_finder().err().unwrap().kind(),
+    );
+    #[cfg(feature = "std")]
+    {
+        let rdr = &b"ab a. aa"[..];
+        assert!(ac.try_stream_find_iter(rdr).is_err());
+        assert!(ac.try_stream_find_iter_earliest(rdr).is_err());
+        let unit = crate::ColumnUnit::Byte;
+        assert!(ac.try_stream_find_iter_line_column(rdr, unit).is_err());
+        let err = ac.try_stream_replace_all(rdr, vec![], &["X"]).unwrap_err();
+        assert!(format!("{}", err).contains("lookaheads"));
+        let err = ac
+            .try_stream_replace_all_with(rdr, vec![], |_, _, _| Ok(()))
+            .unwrap_err();
+        assert!(format!("{}", err).contains("lookaheads"));
+    }
+
+    // A lookahead set for a pattern that doesn't exist is ignored.
+    let ac = AhoCorasick::builder()
+        .lookahead(PatternID::must(1), Some(Lookahead::new(1).bytes(b".")))
+        .build(["a"])
+        .unwrap();
+    assert!(ac.stream_finder().is_ok());
+}
+
+#[test]
+fn replacer_matches_replace_all_at_boundaries() {
+    use crate::{AhoCorasickReplacerBuilder, Lookahead, PatternID};
+
+    let replace = |ac: &AhoCorasick,
+                   builder: &AhoCorasickReplacerBuilder,
+                   replace_with: &[&str],
+                   chunk_size: usize,
+                   haystack: &str| {
+        let mut replacer = ac.replacer_with(builder, replace_with).unwrap();
+        let mut out = vec![];
+        for chunk in haystack.as_bytes().chunks(chunk_size) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        String::from_utf8(out).unwrap()
+    };
+    let tests: &[(&[&str], &str, &str)] = &[
+        // Back-to-back matches, and a match at position 0.
+        (&["foo"], "foofoo", "XX"),
+        (&["ab", "ba"], "abab", "XX"),
+        // A match immediately following a replacement, which would have
+        // overlapped with it.
+        (&["ab", "bc"], "abcbc", "XcY"),
+        (&["aa"], "aaa", "Xa"),
+        // An empty pattern matches at every position, even in an empty
+        // haystack, and no other pattern ever matches.
+        (&["", "a"], "aa", "XaXaX"),
+        (&["ab", ""], "xab", "YxYaYbY"),
+        (&["", "a"], "", "X"),
+        (&["a"], "", ""),
+    ];
+    let default = AhoCorasickReplacerBuilder::new();
+    for &(patterns, haystack, expected) in tests {
+        let ac = AhoCorasick::new(patterns).unwrap();
+        let replace_with = &["X", "Y"][..patterns.len()];
+        assert_eq!(expected, ac.replace_all(haystack, replace_with));
+        for chunk_size in 1..=haystack.len().max(1) {
+            assert_eq!(
+                expected,
+                replace(&ac, &default, replace_with, chunk_size, haystack),
+                "patterns {:?}, haystack {:?}, chunk size {}",
+                patterns,
+                haystack,
+                chunk_size,
+            );
+        }
+    }
+
+    // Ignored empty patterns act as if they were removed.
+    let mut ignore = AhoCorasickReplacerBuilder::new();
+    ignore.ignore_empty_matches(true);
+    let ac = AhoCorasick::new(["", "ab", "b"]).unwrap();
+    let without = AhoCorasick::new(["ab", "b"]).unwrap();
+    for haystack in ["", "ab", "xabbab"] {
+        assert_eq!(
+            without.replace_all(haystack, &["Y", "Z"]),
+            replace(&ac, &ignore, &["X", "Y", "Z"], 1, haystack),
+        );
+    }
+
+    // A disabled empty pattern doesn't match, and matching stops at the
+    // position where it's enabled again.
+    let mut replacer = ac.replacer(["X", "Y", "Z"]).unwrap();
+    replacer.set_enabled(PatternID::must(0), false);
+    let mut out = replacer.replace(b"abx").unwrap().to_vec();
+    replacer.set_enabled(PatternID::must(0), true);
+    out.extend_from_slice(replacer.replace(b"ab").unwrap());
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(b"YxXaXbX".to_vec(), out);
+
+    // An empty match doesn't wait for a lookahead.
+    let ac = AhoCorasick::builder()
+        .lookahead(PatternID::must(0), Some(Lookahead::new(1).bytes(b"a")))
+        .build(["", "a"])
+        .unwrap();
+    assert!(ac.replacer(["X", "Y"]).is_err());
+    assert!(ac.replacer_with(&ignore, ["X", "Y"]).is_ok());
+}
+
+#[test]
+fn leftmost_longest_replacer_matches_replace_all() {
+    use crate::{AhoCorasickReplacerBuilder, Lookahead, PatternID};
+
+    let tests: &[(&[&str], &str)] = &[
+        (&["foo", "foobar"], "foo foobar foob fofoobarfoo"),
+        (&["foobar", "foo"], "foobafoobar"),
+        (&["abcd", "bc", "c"], "abcabcdbcc"),
+        (&["a", "aa", "aaa"], "aaaaaaa"),
+        (&["ab", "abcdefgh", "cd"], "abcdefgabcdefgh"),
+        // Empty matches are skipped right after a match, as in a
+        // non-overlapping search.
+        (&["", "ab"], "xabab"),
+        (&["ab", ""], "ab"),
+        (&[""], ""),
+        (&["a"], ""),
+        // Nothing is held back without patterns, or with only empty ones.
+        (&[], "abc"),
+        (&["", ""], "abc"),
+    ];
+    for &(patterns, haystack) in tests {
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            let ac = AhoCorasick::builder()
+                .kind(Some(kind))
+                .match_kind(MatchKind::LeftmostLongest)
+                .build(patterns)
+                .unwrap();
+            let replace_with = &["X", "Y", "Z"][..patterns.len()];
+            let expected = ac.replace_all(haystack, replace_with);
+            for chunk_size in 1..=haystack.len().max(1) {
+                let mut replacer = ac.replacer(replace_with).unwrap();
+                let mut out = vec![];
+                for chunk in haystack.as_bytes().chunks(chunk_size) {
+                    out.extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                out.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(
+                    expected.as_bytes(),
+                    out,
+                    "{:?}, patterns {:?}, haystack {:?}, chunk size {}",
+                    kind,
+                    patterns,
+                    haystack,
+                    chunk_size,
+                );
+            }
+        }
+    }
+
+    // The bytes held back are bounded by the longest pattern, and a match
+    // is replaced once no longer match can begin as early.
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(["ab", "abcd"])
+        .unwrap();
+    let mut replacer = ac.replacer(["X", "Y"]).unwrap();
+    assert_eq!(b"xxxx".to_vec(), replacer.replace(b"xxxxxab").unwrap());
+    assert_eq!(b"xXx".to_vec(), replacer.replace(b"xabc").unwrap());
+    assert_eq!(b"Y".to_vec(), replacer.replace(b"dab").unwrap());
+    assert_eq!(b"X".to_vec(), replacer.finish().unwrap());
+
+    // Ignored empty patterns act as if they were removed.
+    let mut ignore = AhoCorasickReplacerBuilder::new();
+    ignore.ignore_empty_matches(true);
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(["", "ab", "b"])
+        .unwrap();
+    let mut replacer = ac.replacer_with(&ignore, ["X", "Y", "Z"]).unwrap();
+    let mut out = vec![];
+    for &byte in b"xabbab".iter() {
+        out.extend_from_slice(replacer.replace(&[byte]).unwrap());
+    }
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(b"xYZY".to_vec(), out);
+
+    // Options that need more than the first match of a state aren't
+    // supported.
+    let builder = || {
+        let mut builder = AhoCorasick::builder();
+        builder.match_kind(MatchKind::LeftmostLongest);
+        builder
+    };
+    let unsupported = |option: &str| {
+        format!(
+            "{} are not supported by replacers with leftmost-longest match \
+             semantics",
+            option,
+        )
+    };
+    let rejected = |ac: &AhoCorasick, with: &AhoCorasickReplacerBuilder| {
+        let err = ac.replacer_with(with, ["X"]).err().unwrap();
+        format!("{}", err)
+    };
+    let default = AhoCorasickReplacerBuilder::new();
+    let ac = builder()
+        .lookahead(PatternID::must(0), Some(Lookahead::new(1).bytes(b"a")))
+        .build(["ab"])
+        .unwrap();
+    assert_eq!(unsupported("lookaheads"), rejected(&ac, &default));
+    let ac = builder().word_boundary(true).build(["ab"]).unwrap();
+    assert_eq!(unsupported("word boundaries"), rejected(&ac, &default));
+    let mut ac = builder().store_patterns(true).build(["ab"]).unwrap();
+    ac.disable_pattern(PatternID::must(0)).unwrap();
+    assert_eq!(unsupported("disabled patterns"), rejected(&ac, &default));
+    ac.enable_pattern(PatternID::must(0));
+    assert!(ac.replacer(["X"]).is_ok());
+    let ac = builder().start_kind(StartKind::Both).build(["ab"]).unwrap();
+    let mut anchored = AhoCorasickReplacerBuilder::new();
+    anchored.anchored(Anchored::Yes);
+    assert_eq!(unsupported("anchored replacements"), rejected(&ac, &anchored));
+    let mut context = AhoCorasickReplacerBuilder::new();
+    context.replacement_context(true);
+    assert_eq!(unsupported("replacement contexts"), rejected(&ac, &context));
+}
+
+#[test]
+fn overlap_policy_is_consistent_across_paths() {
+    use crate::OverlapPolicy::{self, *};
+
+    let tests: &[(OverlapPolicy, &[&str], &str, &str)] = &[
+        (LeftmostWins, &["abc", "bcdef", "cd"], "abcdef", "Xdef"),
+        (LongestWins, &["abc", "bcdef", "cd"], "abcdef", "aY"),
+        (PriorityWins, &["cd", "abc", "bcdef"], "abcdef", "abXef"),
+        // A match wins against a later one that overlaps it, which then
+        // can't prevent a match that it overlaps from winning.
+        (PriorityWins, &["efg", "cde", "abc"], "abcdefg", "ZdX"),
+        (LongestWins, &["abc", "cdef", "fg"], "abcdefg", "abYg"),
+        (PriorityWins, &["ab", "bc", "cd"], "abcd", "XZ"),
+        (PriorityWins, &["aa"], "aaaaa", "XXa"),
+        // Back-to-back matches and empty matches don't overlap.
+        (LeftmostWins, &["ab", "b"], "abb", "XY"),
+        (LongestWins, &["", "ab"], "ab", "XYX"),
+        (LeftmostWins, &["a"], "", ""),
+    ];
+    for &(policy, patterns, haystack, expected) in tests {
+        let ac = AhoCorasick::builder()
+            .overlap_policy(Some(policy))
+            .build(patterns)
+            .unwrap();
+        let replace_with = &["X", "Y", "Z"][..patterns.len()];
+        assert_eq!(expected, ac.replace_all(haystack, replace_with));
+        assert_eq!(
+            expected.as_bytes(),
+            ac.replace_all_bytes(haystack.as_bytes(), replace_with),
+        );
+        // A replacer given the policy by its builder behaves the same as
+        // one built from an automaton with the policy, even when the
+        // automaton has another one.
+        let plain = AhoCorasick::new(patterns).unwrap();
+        let other = AhoCorasick::builder()
+            .overlap_policy(Some(match policy {
+                LongestWins => PriorityWins,
+                _ => LongestWins,
+            }))
+            .build(patterns)
+            .unwrap();
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.overlap_policy(Some(policy));
+        for chunk_size in 1..=haystack.len().max(1) {
+            let replacers = [
+                ac.replacer(replace_with).unwrap(),
+                plain.replacer_with(&builder, replace_with).unwrap(),
+                other.replacer_with(&builder, replace_with).unwrap(),
+            ];
+            for mut replacer in replacers {
+                let mut out = vec![];
+                for chunk in haystack.as_bytes().chunks(chunk_size) {
+                    out.extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                out.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(
+                    expected.as_bytes(),
+                    out,
+                    "{:?}, patterns {:?}, haystack {:?}, chunk size {}",
+                    policy,
+                    patterns,
+                    haystack,
+                    chunk_size,
+                );
+            }
+        }
+    }
+
+    // Searches are unaffected, and stream replacements don't support
+    // policies.
+    let ac = AhoCorasick::builder()
+        .overlap_policy(Some(LongestWins))
+        .build(["ab", "abcd"])
+        .unwrap();
+    assert_eq!(Some(LongestWins), ac.overlap_policy());
+    assert_eq!(Some(Match::must(0, 0..2)), ac.find("abcd"));
+    #[cfg(feature = "std")]
+    {
+        let mut wtr = vec![];
+        let rdr = "abcd".as_bytes();
+        assert!(ac
+            .try_stream_replace_all(rdr, &mut wtr, &["X", "Y"])
+            .is_err());
+    }
+
+    // Policies need every overlapping match, so they require standard
+    // semantics.
+    for match_kind in [MatchKind::LeftmostFirst, MatchKind::LeftmostLongest] {
+        let err = AhoCorasick::builder()
+            .match_kind(match_kind)
+            .overlap_policy(Some(LongestWins))
+            .build(["ab", "abcd"])
+            .unwrap_err();
+        assert!(format!("{}", err).contains("standard match semantics"));
+
+        let ac = AhoCorasick::builder()
+            .match_kind(match_kind)
+            .build(["ab", "abcd"])
+            .unwrap();
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.overlap_policy(Some(LongestWins));
+        assert!(ac.replacer_with(&builder, ["X", "Y"]).is_err());
+    }
+}
+
+#[test]
+fn replacer_resumes_from_replacement_context() {
+    use crate::AhoCorasickReplacerBuilder;
+
+    // The patterns, their replacements, the haystack, whether to coalesce
+    // and the expected output.
+    type Test = (
+        &'static [&'static str],
+        &'static [&'static str],
+        &'static str,
+        bool,
+        &'static str,
+    );
+    let tests: &[Test] = &[
+        (
+            &["[b]", "[/b]", "</b><b>"],
+            &["<b>", "</b>", ""],
+            "[b]one[/b]<b>two</b>",
+            false,
+            "<b>onetwo</b>",
+        ),
+        // A match that begins with a replacement rewrites it.
+        (&["a", "bb"], &["b", "X"], "ab", false, "X"),
+        // A match that ends within a replacement is ignored.
+        (&["a", "bb"], &["bb", "X"], "a", false, "bb"),
+        (&["a", "bb"], &["bb", "X"], "ab", false, "bX"),
+        // A match rewriting a replacement isn't part of its run, and a
+        // match coalesced into a run leaves no context.
+        (&["ab", "Xc"], &["X", "Y"], "abc", true, "Y"),
+        (&["ab", "Xc"], &["X", "Y"], "ababc", true, "Xc"),
+        (&["ab", "Xc"], &["X", "Y"], "ab abc", true, "X Y"),
+    ];
+    let kinds = [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ];
+    for &(patterns, replace_with, haystack, coalesce, expected) in tests {
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.replacement_context(true).coalesce(coalesce);
+        for &kind in kinds.iter() {
+            let ac = AhoCorasick::builder()
+                .kind(Some(kind))
+                .build(patterns)
+                .unwrap();
+            for chunk_size in 1..=haystack.len() {
+                let mut replacer =
+                    ac.replacer_with(&builder, replace_with).unwrap();
+                let mut out = vec![];
+                for chunk in haystack.as_bytes().chunks(chunk_size) {
+                    out.extend_from_slice(replacer.replace(chunk).unwrap());
+                }
+                out.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(
+                    expected,
+                    String::from_utf8(out).unwrap(),
+                    "patterns {:?}, haystack {:?}, {:?}, chunk size {}",
+                    patterns,
+                    haystack,
+                    kind,
+                    chunk_size,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn line_replacer_rewrites_complete_lines() {
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .start_kind(StartKind::Both)
+        .build(["ab", "abc", "c"])
+        .unwrap();
+    let replace = |anchored, chunk_size, haystack: &str| {
+        let mut lines =
+            ac.line_replacer(anchored, 5, ["X", "Y", "Z"]).unwrap();
+        let mut out = vec![];
+        for chunk in haystack.as_bytes().chunks(chunk_size) {
+            out.extend_from_slice(lines.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(lines.finish().unwrap());
+        (
+            String::from_utf8(out).unwrap(),
+            lines
+                .take_overlong_lines()
+                .iter()
+                .map(|l| (l.line(), l.start()))
+                .collect::<Vec<_>>(),
+        )
+    };
+    let haystack = "abc c\n\nabcabc\nab\ncab";
+    let overlong = vec![(3, 7)];
+    for chunk_size in 1..=haystack.len() {
+        assert_eq!(
+            (String::from("Y Z\n\nabcabc\nX\nZX"), overlong.clone()),
+            replace(Anchored::No, chunk_size, haystack),
+            "chunk size {}",
+            chunk_size,
+        );
+        assert_eq!(
+            (String::from("Y c\n\nabcabc\nX\nZab"), overlong.clone()),
+            replace(Anchored::Yes, chunk_size, haystack),
+            "chunk size {}",
+            chunk_size,
+        );
+    }
+    // A line that ends with no terminator is rewritten once the stream
+    // ends, unless it's overlong.
+    assert_eq!((String::from("Z"), vec![]), replace(Anchored::No, 1, "c"));
+    assert_eq!(1, replace(Anchored::No, 1, "cccccc").1.len());
+
+    let anchored =
+        AhoCorasick::builder().start_kind(StartKind::Anchored).build(["a"]);
+    assert!(anchored.unwrap().line_replacer(Anchored::No, 5, ["X"]).is_err());
+    assert!(ac.line_replacer(Anchored::No, 5, ["X"]).is_err());
+}
+
+#[test]
+fn chunked_replacer_frames_rewritten_payload() {
+    use crate::MatchErrorKind;
+
+    /// Decode a body written by a chunked replacer, checking its framing.
+    fn decode(mut body: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut payload = vec![];
+        loop {
+            let line = body.iter().position(|&b| b == b'\r').unwrap();
+            let size = core::str::from_utf8(&body[..line]).unwrap();
+            assert_eq!(size, size.to_lowercase());
+            let size = usize::from_str_radix(size, 16).unwrap();
+            assert_eq!(b"\r\n", &body[line..line + 2]);
+            body = &body[line + 2..];
+            if size == 0 {
+                return (payload, body.to_vec());
+            }
+            payload.extend_from_slice(&body[..size]);
+            assert_eq!(b"\r\n", &body[size..size + 2]);
+            body = &body[size + 2..];
+        }
+    }
+
+    let ac = AhoCorasick::new(["secret", "abc", "c"]).unwrap();
+    let replacements = ["[redacted]", "", "CC"];
+    let payload = b"a secret, abc and secretc";
+    let expected = ac.replace_all_bytes(payload, &replacements);
+    let trailer = b"X-Sum: 1\r\nX-Other: 2\r\n\r\n";
+    for size in 1..=payload.len() {
+        let mut body = vec![];
+        for (i, part) in payload.chunks(size).enumerate() {
+            let ext = ["", ";a=b", " ; c"][i % 3];
+            body.extend_from_slice(
+                format!("{:X}{}\r\n", part.len(), ext).as_bytes(),
+            );
+            body.extend_from_slice(part);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"0\r\n");
+        body.extend_from_slice(trailer);
+        for split in [1, 3, 7, body.len()] {
+            let replacer = ac.replacer(replacements).unwrap();
+            let mut chunked = replacer.chunked();
+            let mut out = vec![];
+            for part in body.chunks(split) {
+                out.extend_from_slice(chunked.replace(part).unwrap());
+            }
+            assert!(chunked.is_done());
+            chunked.finish().unwrap();
+            let (got, rest) = decode(&out);
+            assert_eq!(expected, got, "size {} split {}", size, split);
+            assert_eq!(trailer.to_vec(), rest);
+        }
+    }
+
+    let invalid = |body: &[u8]| {
+        let mut chunked = ac.replacer(replacements).unwrap().chunked();
+        let err = match chunked.replace(body) {
+            Ok(_) => chunked.finish().unwrap_err(),
+            Err(err) => err,
+        };
+        match *err.kind() {
+            MatchErrorKind::InvalidChunkedEncoding { offset } => offset,
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+    };
+    assert_eq!(0, invalid(b""));
+    assert_eq!(0, invalid(b"\r\n"));
+    assert_eq!(1, invalid(b"3\n"));
+    assert_eq!(2, invalid(b"3 4\r\n"));
+    assert_eq!(6, invalid(b"3\r\nabcd\r\n"));
+    assert_eq!(16, invalid(b"ffffffffffffffffff\r\n"));
+    assert_eq!(9, invalid(b"1\r\na\r\n0\r\n"));
+    assert_eq!(8, invalid(b"0\r\nX-Sum\n"));
+    assert_eq!(5, invalid(b"0\r\n\r\nextra"));
+
+    // An empty body has only the last chunk.
+    let mut chunked = ac.replacer(replacements).unwrap().chunked();
+    assert_eq!(b"0\r\n\r\n", chunked.replace(b"0;ext\r\n\r\n").unwrap());
+    // The payload held back for a match that spans chunks is decided by
+    // the next chunks, or written once the body ends.
+    let ac = AhoCorasick::new(["secret"]).unwrap();
+    let mut chunked = ac.replacer(["[redacted]"]).unwrap().chunked();
+    chunked.replace(b"3\r\nfoo").unwrap();
+    chunked.reset();
+    assert_eq!(b"", chunked.replace(b"3\r\nse").unwrap());
+    assert_eq!(
+        b"a\r\n[redacted]\r\n",
+        chunked.replace(b"c\r\n3\r\nret\r\n").unwrap(),
+    );
+    assert_eq!(b"", chunked.replace(b"2\r\nse\r\n").unwrap());
+    assert_eq!(
+        b"2\r\nse\r\n0\r\n\r\n",
+        chunked.replace(b"0\r\n\r\n").unwrap()
+    );
+    chunked.finish().unwrap();
+}
+
+#[test]
+fn event_stream_replacer_rewrites_data_values() {
+    let ac = AhoCorasick::new(["secret", "data", "\n"]).unwrap();
+    let stream = b"event: secret\r\ndata: a secret\rdata:secret\n\n\
+        : secret comment\ndata\ndata: sec\n\ndata:  secret\r\n\r\nid: 1\n\
+        data: secret";
+    let expected = b"event: secret\r\ndata: a ******\rdata:******\n\n\
+        : secret comment\ndata\ndata: sec\n\ndata:  ******\r\n\r\nid: 1\n\
+        data: ******";
+    for chunk_size in 1..=stream.len() {
+        let replacer = ac.replacer(["******", "DATA", "NL"]).unwrap();
+        let mut events = replacer.event_stream();
+        let mut out = vec![];
+        for chunk in stream.chunks(chunk_size) {
+            out.extend_from_slice(events.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(events.finish().unwrap());
+        assert_eq!(
+            String::from_utf8_lossy(expected),
+            String::from_utf8_lossy(&out),
+            "chunk size {}",
+            chunk_size,
+        );
+    }
+
+    // A potential match is written once its line ends, so an event is
+    // complete as soon as it's dispatched.
+    let mut events =
+        ac.replacer(["******", "DATA", "NL"]).unwrap().event_stream();
+    assert_eq!(b"data: ", events.replace(b"data: secr").unwrap());
+    assert_eq!(b"secr\r\n\r\n", events.replace(b"\r\n\r\n").unwrap());
+    assert_eq!(b"data: ", events.replace(b"data: se").unwrap());
+    events.reset();
+    assert_eq!(b"data: ******", events.replace(b"data: secret").unwrap());
+}
+
+#[test]
+fn stream_splitter_yields_records_between_delimiters() {
+    let ac = AhoCorasick::new(["\r\n", "\n", "||", "|"]).unwrap();
+    let haystack = b"a|b||c\r\n\nlong record|\r|";
+    let expected: Vec<(&[u8], Option<usize>)> = vec![
+        (b"a", Some(3)),
+        // As with a replacer, a match is found as soon as it ends, so "|"
+        // matches before "||" could.
+        (b"b", Some(3)),
+        (b"", Some(3)),
+        (b"c", Some(0)),
+        (b"", Some(1)),
+        (b"long record", Some(3)),
+        (b"\r", Some(3)),
+        (b"", None),
+    ];
+    let mut splitter = ac.stream_splitter().unwrap();
+    for chunk_size in 1..=haystack.len() {
+        let mut records = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            for record in splitter.split(chunk) {
+                let pid = record.delimiter().map(|pid| pid.as_usize());
+                records.push((record.as_bytes().to_vec(), pid));
+            }
+        }
+        for record in splitter.finish() {
+            let pid = record.delimiter().map(|pid| pid.as_usize());
+            records.push((record.as_bytes().to_vec(), pid));
+        }
+        let records: Vec<(&[u8], Option<usize>)> =
+            records.iter().map(|(r, pid)| (&r[..], *pid)).collect();
+        assert_eq!(expected, records, "chunk size {}", chunk_size);
+        splitter.reset();
+    }
+
+    // A stream without delimiters is a single record.
+    assert_eq!(0, splitter.split(b"a").count());
+    let last: Vec<_> = splitter.finish().collect();
+    assert_eq!(1, last.len());
+    assert_eq!((&b"a"[..], None), (last[0].as_bytes(), last[0].delimiter()));
+
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(["|"])
+        .unwrap();
+    assert!(ac.stream_splitter().is_err());
+}
+
+#[test]
+fn replacer_fn_computes_replacements_while_streaming() {
+    use crate::{AhoCorasickReplacerBuilder, Lookahead, PatternID};
+
+    let ac = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .lookahead(PatternID::must(2), Some(Lookahead::new(2).bytes(b"!")))
+        .build(["fox", "ox", "dog"])
+        .unwrap();
+    // Each match is replaced by its pattern, the bytes it matched and the
+    // number of replacements so far.
+    let expand = |count: &mut usize, pid: PatternID, matched: &[u8]| {
+        *count += 1;
+        let matched = String::from_utf8_lossy(matched);
+        format!("<{}:{}:{}>", pid.as_usize(), matched, count).into_bytes()
+    };
+    let haystack = "FoX dOg ox dog! o";
+    let expected = "<0:FoX:1> dOg <1:ox:2> <2:dog:3>! o";
+    for chunk_size in 1..=haystack.len() {
+        let mut count = 0;
+        let mut replacer = ac
+            .replacer_fn(|pid, matched| expand(&mut count, pid, matched))
+            .unwrap();
+        let mut out = vec![];
+        for chunk in haystack.as_bytes().chunks(chunk_size) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(
+            expected,
+            String::from_utf8(out).unwrap(),
+            "chunk size {}",
+            chunk_size,
+        );
+    }
+
+    // A match coalesced into a run isn't replaced, so the closure isn't
+    // called for it.
+    let ac = AhoCorasick::new(["ab"]).unwrap();
+    let mut calls = 0;
+    let mut replacer = ac
+        .replacer_with_fn(
+            AhoCorasickReplacerBuilder::new().coalesce(true),
+            |_, _| {
+                calls += 1;
+                b"X".to_vec()
+            },
+        )
+        .unwrap();
+    let mut out = replacer.replace(b"ababa").unwrap().to_vec();
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(b"Xa".to_vec(), out);
+    assert_eq!(1, calls);
+}
+
+// This tests that a replacement computed by a closure that is longer than
+// its match is truncated when padding, so that the length of the output is
+// still preserved.
+#[test]
+fn replacer_fn_truncates_long_replacements_when_padding() {
+    use crate::{AhoCorasickReplacerBuilder, Padding};
+
+    let ac = AhoCorasick::new(["ab", "c"]).unwrap();
+    let haystack = "xabycz";
+    let tests =
+        [(Padding::Right(b'_'), "x12y5z"), (Padding::Left(b'_'), "x34y5z")];
+    for &(padding, expected) in tests.iter() {
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.padding(Some(padding));
+        for chunk_size in 1..=haystack.len() {
+            let mut replacer = ac
+                .replacer_with_fn(&builder, |pid, _| {
+                    let replacements = ["1234", "5"];
+                    replacements[pid].as_bytes().to_vec()
+                })
+                .unwrap();
+            let mut out = vec![];
+            for chunk in haystack.as_bytes().chunks(chunk_size) {
+                out.extend_from_slice(replacer.replace(chunk).unwrap());
+            }
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(expected.as_bytes(), out, "chunk size {}", chunk_size);
+        }
+    }
+}
+
+#[test]
+fn replacer_reset_matches_new_replacer() {
+    use crate::{
+        AhoCorasickReplacerBuilder, Lookahead, MatchDeduper, OverlapPolicy,
+        PatternID,
+    };
+
+    let patterns = ["abc", "bcd", "cab", "d"];
+    let replacements = ["1", "2", "3", "4"];
+    let streams = ["abcabcd", "xcabcdy", "d", "", "abcdabc"];
+    let mut dedup = AhoCorasickReplacerBuilder::new();
+    dedup.dedup(Some(MatchDeduper::new(Some(8))));
+    let mut coalesce = AhoCorasickReplacerBuilder::new();
+    coalesce.coalesce(true);
+    let default = AhoCorasickReplacerBuilder::new();
+    let lookahead = Some(Lookahead::new(1).bytes(b"y"));
+    let automata = [
+        AhoCorasick::new(patterns).unwrap(),
+        AhoCorasick::builder()
+            .lookahead(PatternID::must(1), lookahead)
+            .build(patterns)
+            .unwrap(),
+        AhoCorasick::builder()
+            .overlap_policy(Some(OverlapPolicy::LongestWins))
+            .build(patterns)
+            .unwrap(),
+    ];
+    for ac in automata.iter() {
+        for builder in [&default, &dedup, &coalesce] {
+            let mut reused = ac.replacer_with(builder, replacements).unwrap();
+            for (i, stream) in streams.iter().enumerate() {
+                let mut fresh =
+                    ac.replacer_with(builder, replacements).unwrap();
+                let mut expected = vec![];
+                let mut out = vec![];
+                for chunk in stream.as_bytes().chunks(2) {
+                    expected.extend_from_slice(fresh.replace(chunk).unwrap());
+                    out.extend_from_slice(reused.replace(chunk).unwrap());
+                }
+                expected.extend_from_slice(fresh.finish().unwrap());
+                // Every other stream is abandoned midway, so that the reset
+                // discards a potential match.
+                if i % 2 == 0 {
+                    out.extend_from_slice(reused.finish().unwrap());
+                    assert_eq!(expected, out, "{:?}", stream);
+                }
+                reused.reset();
+            }
+        }
+    }
+}
+
+#[test]
+fn replacer_anchored_matches_anchored_search() {
+    use crate::{AhoCorasickKind, AhoCorasickReplacerBuilder, StartKind};
+
+    let pattern_sets: &[&[&str]] =
+        &[&["abc", "bcd", "b", "cd"], &["ab", "", "b"], &["abcd", "abx"]];
+    // The last record is started before the end of the stream, but is
+    // empty.
+    let records = ["abcd", "bcdab", "xab", "b", "abxabc", "cdcd", ""];
+    let mut anchored = AhoCorasickReplacerBuilder::new();
+    anchored.anchored(Anchored::Yes);
+    for &patterns in pattern_sets.iter() {
+        let replacements: Vec<String> =
+            (0..patterns.len()).map(|i| format!("<{}>", i)).collect();
+        for kind in [AhoCorasickKind::NoncontiguousNFA, AhoCorasickKind::DFA] {
+            let ac = AhoCorasick::builder()
+                .kind(Some(kind))
+                .start_kind(StartKind::Anchored)
+                .build(patterns)
+                .unwrap();
+            // Only the earliest match at the start of each record is
+            // replaced, which is what an anchored standard search reports.
+            let mut expected = vec![];
+            for record in records.iter() {
+                let input = Input::new(record).anchored(Anchored::Yes);
+                match ac.try_find(input).unwrap() {
+                    Some(m) => {
+                        let replacement = &replacements[m.pattern()];
+                        expected.extend_from_slice(replacement.as_bytes());
+                        expected
+                            .extend_from_slice(&record.as_bytes()[m.end()..]);
+                    }
+                    None => expected.extend_from_slice(record.as_bytes()),
+                }
+            }
+            for chunk_size in 1..=3 {
+                let mut replacer =
+                    ac.replacer_with(&anchored, &replacements).unwrap();
+                let mut out = vec![];
+                for record in records.iter() {
+                    replacer.start_record();
+                    for chunk in record.as_bytes().chunks(chunk_size) {
+                        out.extend_from_slice(
+                            replacer.replace(chunk).unwrap(),
+                        );
+                    }
+                }
+                out.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(
+                    String::from_utf8(expected.clone()).unwrap(),
+                    String::from_utf8(out).unwrap(),
+                    "{:?} {:?} chunk size {}",
+                    patterns,
+                    kind,
+                    chunk_size,
+                );
+
+                // Records start in the same way when the replacements are
+                // computed.
+                let mut replacer = ac
+                    .replacer_with_fn(&anchored, |pid, _| {
+                        replacements[pid].clone().into_bytes()
+                    })
+                    .unwrap();
+                let mut out = vec![];
+                for record in records.iter() {
+                    replacer.start_record();
+                    for chunk in record.as_bytes().chunks(chunk_size) {
+                        out.extend_from_slice(
+                            replacer.replace(chunk).unwrap(),
+                        );
+                    }
+                }
+                out.extend_from_slice(replacer.finish().unwrap());
+                assert_eq!(expected, out, "{:?} {:?}", patterns, kind);
+            }
+        }
+    }
+}
+
+#[test]
+fn replacer_pending_limit_bounds_held_bytes() {
+    use crate::{Lookahead, OverlapPolicy, PatternID, PendingLimit};
+
+    // The replacements are as long as their patterns, so the bytes held
+    // back are the ones consumed but not written yet.
+    let patterns = ["abcdef", "cd", "ef"];
+    let replacements = ["ABCDEF", "CD", "EF"];
+    let haystack = b"abcdefabcdxcdefab abcdeabcdef";
+    let automata = [
+        AhoCorasick::new(patterns).unwrap(),
+        AhoCorasick::builder()
+            .lookahead(PatternID::must(1), Some(Lookahead::new(1).bytes(b"x")))
+            .build(patterns)
+            .unwrap(),
+        AhoCorasick::builder()
+            .overlap_policy(Some(OverlapPolicy::LongestWins))
+            .build(patterns)
+            .unwrap(),
+    ];
+    for ac in automata.iter() {
+        for limit in 0..7 {
+            let mut flush = AhoCorasickReplacerBuilder::new();
+            flush.max_pending_bytes(Some(PendingLimit::Flush(limit)));
+            let mut replacer = ac.replacer_with(&flush, replacements).unwrap();
+            let mut out = vec![];
+            for (i, chunk) in haystack.chunks(1).enumerate() {
+                out.extend_from_slice(replacer.replace(chunk).unwrap());
+                assert!(i + 1 - out.len() <= limit, "limit {}", limit);
+            }
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(haystack.len(), out.len());
+
+            // A match within a single chunk is still found.
+            let mut replacer = ac.replacer_with(&flush, replacements).unwrap();
+            let mut out = replacer.replace(haystack).unwrap().to_vec();
+            out.extend_from_slice(replacer.finish().unwrap());
+            let unlimited = ac
+                .replacer_with(
+                    &AhoCorasickReplacerBuilder::new(),
+                    replacements,
+                )
+                .unwrap()
+                .replace(haystack)
+                .unwrap()
+                .to_vec();
+            assert!(out.starts_with(&unlimited));
+
+            let mut error = AhoCorasickReplacerBuilder::new();
+            error.max_pending_bytes(Some(PendingLimit::Error(limit)));
+            let mut replacer = ac.replacer_with(&error, replacements).unwrap();
+            let mut out = vec![];
+            let mut consumed = 0;
+            for chunk in haystack.chunks(1) {
+                consumed += 1;
+                match replacer.replace(chunk) {
+                    Ok(output) => out.extend_from_slice(output),
+                    Err(_) => break,
+                }
+            }
+            assert!(
+                consumed - out.len() > limit || consumed == haystack.len()
+            );
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(consumed, out.len(), "limit {}", limit);
+        }
+    }
+}
+
+#[test]
+fn replacer_alternatives_select_deterministically() {
+    use crate::{Padding, Selection};
+
+    let ac = AhoCorasick::new(["a", "bb"]).unwrap();
+    let builder = AhoCorasickReplacerBuilder::new();
+    let alternatives = [&["1", "2", "3"][..], &["x", "y"]];
+    let replace = |builder: &AhoCorasickReplacerBuilder,
+                   selection: Selection,
+                   haystack: &[u8]| {
+        let mut replacer = ac
+            .replacer_with_alternatives(
+                builder,
+                alternatives.iter().copied(),
+                selection,
+            )
+            .unwrap();
+        let mut out = vec![];
+        for chunk in haystack.chunks(1) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        // Resetting starts the selection over.
+        replacer.reset();
+        let mut again = replacer.replace(haystack).unwrap().to_vec();
+        again.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(out, again);
+        String::from_utf8(out).unwrap()
+    };
+
+    let haystack = b"a-bb-a-a-bb-bb-a";
+    assert_eq!(
+        "1-x-2-3-y-x-1",
+        replace(&builder, Selection::RoundRobin, haystack),
+    );
+    let random = replace(&builder, Selection::Random { seed: 3 }, haystack);
+    assert_eq!(
+        random,
+        replace(&builder, Selection::Random { seed: 3 }, haystack)
+    );
+    let haystack = b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    let random = replace(&builder, Selection::Random { seed: 7 }, haystack);
+    assert!(b"123".iter().all(|&b| random.as_bytes().contains(&b)));
+
+    let weights = vec![vec![1, 0, 2], vec![0, 1]];
+    let weighted = Selection::Weighted { seed: 1, weights };
+    let out = replace(&builder, weighted, b"aaaaaaaaaaaaaaaaaaaabbbb");
+    assert!(!out.contains('2') && !out.contains('x'), "{}", out);
+    assert!(out.contains('1') && out.contains('3'), "{}", out);
+
+    // Each alternative is padded to the length of its pattern.
+    let mut padded = AhoCorasickReplacerBuilder::new();
+    padded.padding(Some(Padding::Right(b'.')));
+    assert_eq!(
+        "1-x.-2-y.",
+        replace(&padded, Selection::RoundRobin, b"a-bb-a-bb"),
+    );
+
+    let select = |alternatives: &[&[&str]], selection| {
+        ac.replacer_with_alternatives(
+            &builder,
+            alternatives.iter().copied(),
+            selection,
+        )
+    };
+    assert!(select(&[&["1"]], Selection::RoundRobin).is_err());
+    assert!(select(&[&["1"], &[]], Selection::RoundRobin).is_err());
+    let weights = vec![vec![1], vec![1]];
+    let short = Selection::Weighted { seed: 0, weights };
+    assert!(select(&[&["1", "2"], &["x"]], short).is_err());
+    let weights = vec![vec![1], vec![0]];
+    let zero = Selection::Weighted { seed: 0, weights };
+    assert!(select(&[&["1"], &["x"]], zero).is_err());
+}
+
+#[test]
+fn replacer_selection_state_resumes_selection() {
+    use crate::{Selection, SelectionState};
+
+    let ac = AhoCorasick::new(["a", "bb"]).unwrap();
+    let builder = AhoCorasickReplacerBuilder::new();
+    let alternatives = [&["1", "2", "3"][..], &["x", "y"]];
+    let replacer = |selection: &Selection| {
+        ac.replacer_with_alternatives(
+            &builder,
+            alternatives.iter().copied(),
+            selection.clone(),
+        )
+        .unwrap()
+    };
+    let records: &[&[u8]] = &[b"a-bb-a ", b"bb-a-a-bb ", b"a-a-bb-a"];
+    let weights = vec![vec![3, 0, 1], vec![1, 1]];
+    for selection in [
+        Selection::RoundRobin,
+        Selection::Random { seed: 5 },
+        Selection::Weighted { seed: 5, weights },
+    ] {
+        let mut once = replacer(&selection);
+        let mut expected = once.replace(&records.concat()).unwrap().to_vec();
+        expected.extend_from_slice(once.finish().unwrap());
+        // Each record is processed by a new replacer, which resumes from the
+        // state of the previous one.
+        let mut out = vec![];
+        let mut state = replacer(&selection).selection_state().unwrap();
+        for record in records {
+            let mut r = replacer(&selection);
+            r.set_selection_state(&state).unwrap();
+            out.extend_from_slice(r.replace(record).unwrap());
+            out.extend_from_slice(r.finish().unwrap());
+            state = r.selection_state().unwrap();
+        }
+        assert_eq!(expected, out, "{:?}", selection);
+    }
+
+    let mut r = replacer(&Selection::RoundRobin);
+    let bad = [
+        SelectionState::Generator { state: 1 },
+        SelectionState::RoundRobin { next: vec![0] },
+        SelectionState::RoundRobin { next: vec![0, 2] },
+    ];
+    for state in bad.iter() {
+        assert!(r.set_selection_state(state).is_err());
+    }
+    let state = SelectionState::RoundRobin { next: vec![2, 1] };
+    r.set_selection_state(&state).unwrap();
+    assert_eq!(b"3-y-1", r.replace(b"a-bb-a").unwrap());
+    let mut random = replacer(&Selection::Random { seed: 1 });
+    assert!(random.set_selection_state(&state).is_err());
+    let mut without = ac.replacer(["1", "x"]).unwrap();
+    assert_eq!(None, without.selection_state());
+    assert!(without.set_selection_state(&state).is_err());
+}
+
+#[test]
+fn replacer_memory_limits_cap_buffers() {
+    use crate::{MatchErrorKind, MemoryLimits};
+
+    let ac = AhoCorasick::new(["ab", "abcdef"]).unwrap();
+    let haystack = b"ab abab abcdefab abcde!babab";
+    let replacements = ["<AB>", "<F>"];
+    let unlimited = ac.replace_all_bytes(haystack, &replacements);
+    let output_limit = |r: Result<&[u8], crate::MatchError>| match r {
+        Ok(output) => Some(output.to_vec()),
+        Err(err) => {
+            let kind = MatchErrorKind::OutputLimitExceeded { limit: 8 };
+            assert_eq!(kind, *err.kind());
+            None
+        }
+    };
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.memory_limits(Some(MemoryLimits::new().output(8)));
+    for len in 1..=haystack.len() {
+        // Every call returns the same output as without the cap, unless it
+        // would exceed it, in which case it fails without growing the
+        // buffer.
+        let mut replacer = ac.replacer_with(&builder, replacements).unwrap();
+        let mut fn_replacer = ac
+            .replacer_with_fn(&builder, |pid, _| replacements[pid].into())
+            .unwrap();
+        let mut expected = ac.replacer(replacements).unwrap();
+        for chunk in haystack.chunks(len) {
+            let output = output_limit(replacer.replace(chunk));
+            assert_eq!(output, output_limit(fn_replacer.replace(chunk)));
+            let unlimited = expected.replace(chunk).unwrap();
+            match output {
+                Some(output) => assert_eq!(unlimited, output),
+                None => {
+                    assert!(unlimited.len() > 8, "chunk length {}", len);
+                    break;
+                }
+            }
+        }
+    }
+    let mut replacer = ac.replacer_with(&builder, replacements).unwrap();
+    assert_eq!(b"<AB> ", replacer.replace(b"ab a").unwrap());
+    assert!(output_limit(replacer.replace(b"b ab")).is_none());
+
+    // The pending cap applies along with the limit of the builder.
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder
+        .max_pending_bytes(Some(crate::PendingLimit::Error(5)))
+        .memory_limits(Some(MemoryLimits::new().pending(3)));
+    let long = AhoCorasick::new(["abcdef"]).unwrap();
+    let mut replacer = long.replacer_with(&builder, ["<F>"]).unwrap();
+    assert_eq!(b"x", replacer.replace(b"xabc").unwrap());
+    let err = replacer.replace(b"d").unwrap_err();
+    assert_eq!(MatchErrorKind::PendingLimitExceeded { limit: 3 }, *err.kind());
+
+    // A shadow replacer records no more differences than its cap, and the
+    // ones it couldn't record are recorded once they are taken.
+    let candidate = AhoCorasick::new(["ab", "b"]).unwrap();
+    let events = |limit| {
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.memory_limits(Some(MemoryLimits::new().events(limit)));
+        ac.replacer_with(&builder, replacements).unwrap().shadow(
+            candidate.replacer_with(&builder, ["<ab>", "<b>"]).unwrap(),
+        )
+    };
+    let mut expected = events(usize::MAX);
+    let mut out = expected.replace(haystack).unwrap().to_vec();
+    out.extend_from_slice(expected.finish().unwrap());
+    assert_eq!(unlimited, out);
+    let expected = expected.take_diffs();
+    assert!(expected.len() > 4);
+    for limit in 1..4 {
+        let mut replacer = events(limit);
+        let (mut out, mut diffs) = (vec![], vec![]);
+        for chunk in haystack.chunks(3) {
+            match replacer.replace(chunk) {
+                Ok(output) => out.extend_from_slice(output),
+                Err(err) => {
+                    let kind = MatchErrorKind::EventLimitExceeded { limit };
+                    assert_eq!(kind, *err.kind());
+                }
+            }
+            assert!(replacer.diffs().len() <= limit);
+            diffs.extend(replacer.take_diffs());
+        }
+        loop {
+            let finished = replacer.finish().map(<[u8]>::to_vec);
+            diffs.extend(replacer.take_diffs());
+            if let Ok(finished) = finished {
+                out.extend_from_slice(&finished);
+                break;
+            }
+        }
+        assert_eq!(unlimited, out, "limit {}", limit);
+        assert_eq!(expected, diffs, "limit {}", limit);
+    }
+}
+
+#[test]
+fn replacer_stops_at_quit_bytes() {
+    use crate::{AhoCorasickReplacer, MatchErrorKind, OverlapPolicy};
+
+    // The bytes between quit bytes are replaced as separate streams, when
+    // the caller skips each quit byte.
+    let feed = |replacer: &mut AhoCorasickReplacer,
+                haystack: &[u8],
+                chunk_size: usize| {
+        let (mut out, mut quits) = (vec![], vec![]);
+        let mut start = 0;
+        for chunk in haystack.chunks(chunk_size) {
+            let mut rest = chunk;
+            loop {
+                let err = match replacer.replace(rest) {
+                    Ok(output) => {
+                        out.extend_from_slice(output);
+                        break;
+                    }
+                    Err(err) => err,
+                };
+                let offset = match *err.kind() {
+                    MatchErrorKind::Quit { byte: b'!', offset } => offset,
+                    ref kind => panic!("unexpected error {:?}", kind),
+                };
+                assert!(!replacer.is_poisoned());
+                quits.push(offset);
+                // The skipped quit byte isn't part of the stream.
+                rest = &rest[offset - start + 1..];
+                start = offset;
+            }
+            start += rest.len();
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        (out, quits)
+    };
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.quit(b'!', true).quit(b'?', true).quit(b'?', false);
+    let patterns = ["ab", "abcd", "cd"];
+    let replace_with = ["X", "Y", "Z"];
+    let automata = [
+        AhoCorasick::new(patterns).unwrap(),
+        AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns)
+            .unwrap(),
+        AhoCorasick::builder()
+            .overlap_policy(Some(OverlapPolicy::LongestWins))
+            .build(patterns)
+            .unwrap(),
+    ];
+    let haystacks: &[&[u8]] =
+        &[b"xabc!dab?c", b"!abcd!!ab", b"abcab!", b"ab", b""];
+    for ac in automata.iter() {
+        for &haystack in haystacks {
+            let mut expected = vec![];
+            for (i, part) in haystack.split(|&b| b == b'!').enumerate() {
+                if i > 0 {
+                    expected.push(b'!');
+                }
+                expected.extend(ac.replace_all_bytes(part, &replace_with));
+            }
+            // The quit bytes are skipped.
+            expected.retain(|&b| b != b'!');
+            let quits: Vec<usize> = (0..haystack.len())
+                .filter(|&i| haystack[i] == b'!')
+                .enumerate()
+                .map(|(skipped, i)| i - skipped)
+                .collect();
+            for chunk_size in 1..=haystack.len().max(1) {
+                let mut replacer =
+                    ac.replacer_with(&builder, replace_with).unwrap();
+                let got = feed(&mut replacer, haystack, chunk_size);
+                assert_eq!(
+                    (expected.clone(), quits.clone()),
+                    got,
+                    "{:?}, haystack {:?}, chunk size {}",
+                    ac.match_kind(),
+                    haystack,
+                    chunk_size,
+                );
+            }
+        }
+    }
+
+    // The bytes held back are emitted before the error, and the rest of
+    // the chunk isn't consumed.
+    let ac = &automata[0];
+    let mut replacer = ac.replacer_with(&builder, replace_with).unwrap();
+    let mut out = vec![];
+    let err = replacer.replace_to_vec(b"xab!cd", &mut out).unwrap_err();
+    assert_eq!(MatchErrorKind::Quit { byte: b'!', offset: 3 }, *err.kind());
+    assert_eq!(b"xX".to_vec(), out);
+    let err = replacer.replace_to_vec(b"abc!d", &mut out).unwrap_err();
+    assert_eq!(MatchErrorKind::Quit { byte: b'!', offset: 6 }, *err.kind());
+    assert_eq!(b"xXXc".to_vec(), out);
+    assert_eq!(b"", replacer.finish().unwrap());
+    #[cfg(feature = "std")]
+    {
+        let mut replacer = ac.replacer_with(&builder, replace_with).unwrap();
+        let slices = [
+            std::io::IoSlice::new(b"ab"),
+            std::io::IoSlice::new(b"c!d"),
+            std::io::IoSlice::new(b"ab"),
+        ];
+        let err = replacer.replace_vectored(&slices).unwrap_err();
+        assert_eq!(
+            MatchErrorKind::Quit { byte: b'!', offset: 3 },
+            *err.kind(),
+        );
+        assert_eq!(b"Xc", replacer.finish().unwrap());
+    }
+}
+
+#[test]
+fn replacer_flush_keeps_output_of_stream() {
+    use crate::{AhoCorasickKind, OverlapPolicy};
+
+    let mut seed: u64 = 0x2545F4914F6CDD1D;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n) as usize
+    };
+    for _ in 0..300 {
+        let count = 1 + below(6);
+        let mut word = |max: usize| -> Vec<u8> {
+            let len = below(max as u64 + 1);
+            (0..len).map(|_| b"abc "[below(4)]).collect()
+        };
+        let patterns: Vec<Vec<u8>> = (0..count).map(|_| word(4)).collect();
+        let replacements: Vec<Vec<u8>> =
+            patterns.iter().map(|_| word(3)).collect();
+        let haystack = word(40);
+        let mut builder = AhoCorasick::builder();
+        builder.kind(Some(
+            [
+                AhoCorasickKind::NoncontiguousNFA,
+                AhoCorasickKind::ContiguousNFA,
+                AhoCorasickKind::DFA,
+            ][below(3)],
+        ));
+        match below(4) {
+            0 => builder.match_kind(MatchKind::LeftmostLongest),
+            1 => builder.overlap_policy(Some(OverlapPolicy::LongestWins)),
+            2 => builder.word_boundary(true),
+            _ => &mut builder,
+        };
+        let ac = builder.build(&patterns).unwrap();
+        let mut config = AhoCorasickReplacerBuilder::new();
+        config.coalesce(below(2) == 0).replacement_context(below(2) == 0);
+        let mut replacer = match ac.replacer_with(&config, &replacements) {
+            Ok(replacer) => replacer,
+            // Not every configuration is supported with every automaton.
+            Err(_) => continue,
+        };
+        let mut expected = replacer.replace(&haystack).unwrap().to_vec();
+        expected.extend_from_slice(replacer.finish().unwrap());
+
+        replacer.reset();
+        let mut out = vec![];
+        let mut rest = &haystack[..];
+        while !rest.is_empty() {
+            let (chunk, next) = rest.split_at(1 + below(rest.len() as u64));
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+            out.extend_from_slice(replacer.flush().unwrap());
+            // Flushing again has nothing left to emit.
+            assert!(replacer.flush().unwrap().is_empty());
+            rest = next;
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(
+            expected,
+            out,
+            "patterns {:?}, haystack {:?}, match kind {:?}",
+            patterns,
+            haystack,
+            ac.match_kind(),
+        );
+    }
+
+    // Only the bytes that may still begin a match stay held back.
+    let ac = AhoCorasick::new(["abcd", "bce"]).unwrap();
+    let mut replacer = ac.replacer(["X", "Y"]).unwrap();
+    assert_eq!(b"", replacer.replace(b"abcabcab").unwrap());
+    assert_eq!(b"abcabc", replacer.flush().unwrap());
+    assert_eq!(b"", replacer.replace(b"c").unwrap());
+    assert_eq!(b"", replacer.flush().unwrap());
+    assert_eq!(b"aY", replacer.replace(b"e").unwrap());
+    assert_eq!(b"", replacer.finish().unwrap());
+
+    // A match waiting for its lookahead stays held back.
+    let ac = AhoCorasick::builder().word_boundary(true).build(["ab"]).unwrap();
+    let mut replacer = ac.replacer(["X"]).unwrap();
+    assert_eq!(b"c ", replacer.replace(b"c ab").unwrap());
+    assert_eq!(b"", replacer.flush().unwrap());
+    assert_eq!(b"X c", replacer.replace(b" c").unwrap());
+}
+
+#[test]
+fn replacer_poisoned_until_reset() {
+    use crate::{MatchError, MatchErrorKind, MemoryLimits, PendingLimit};
+
+    let ac = AhoCorasick::new(["ab", "abcdef"]).unwrap();
+    let haystack = b"ab abab abcdefab abcde!babab";
+    let replacements = ["<AB>", "<F>"];
+    let unlimited = ac.replace_all_bytes(haystack, &replacements);
+    let kind = |r: Result<&[u8], MatchError>| {
+        r.map(<[u8]>::to_vec).map_err(|e| e.kind().clone())
+    };
+    // Every call either succeeds, or fails with the output limit and
+    // poisons the replacer, which then fails until it's reset. A reset
+    // replacer behaves as a new one.
+    for limit in 0..12 {
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.memory_limits(Some(MemoryLimits::new().output(limit)));
+        for len in 1..=haystack.len() {
+            let run = |replacer: &mut crate::AhoCorasickReplacer| {
+                let mut results = vec![];
+                for chunk in haystack.chunks(len) {
+                    let result = kind(replacer.replace(chunk));
+                    let poisoned = result.is_err();
+                    results.push(result);
+                    assert_eq!(poisoned, replacer.is_poisoned());
+                    if poisoned {
+                        let poisoned = Err(MatchErrorKind::Poisoned);
+                        assert_eq!(poisoned, kind(replacer.replace(chunk)));
+                        assert_eq!(poisoned, kind(replacer.replace(b"")));
+                        assert_eq!(poisoned, kind(replacer.finish()));
+                        let mut out = vec![];
+                        let err = replacer
+                            .replace_to_vec(chunk, &mut out)
+                            .unwrap_err();
+                        assert_eq!(MatchErrorKind::Poisoned, *err.kind());
+                        assert!(out.is_empty());
+                        return results;
+                    }
+                }
+                results.push(kind(replacer.finish()));
+                results
+            };
+            let mut replacer =
+                ac.replacer_with(&builder, replacements).unwrap();
+            let expected = run(&mut replacer);
+            let last = expected.last().unwrap();
+            if let Err(ref kind) = *last {
+                assert_eq!(
+                    MatchErrorKind::OutputLimitExceeded { limit },
+                    *kind
+                );
+            } else {
+                let out: Vec<u8> =
+                    expected.iter().flat_map(|r| r.clone().unwrap()).collect();
+                assert_eq!(unlimited, out);
+            }
+            replacer.reset();
+            assert!(!replacer.is_poisoned());
+            assert_eq!(
+                expected,
+                run(&mut replacer),
+                "limit {} len {}",
+                limit,
+                len
+            );
+        }
+    }
+
+    // Exceeding the pending limit doesn't poison the replacer, and the
+    // stream may still be finished.
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.max_pending_bytes(Some(PendingLimit::Error(3)));
+    let long = AhoCorasick::new(["abcdef"]).unwrap();
+    let mut replacer = long.replacer_with(&builder, ["<F>"]).unwrap();
+    let mut out = replacer.replace(b"x ").unwrap().to_vec();
+    assert!(replacer.replace(b"abcde").is_err());
+    assert!(!replacer.is_poisoned());
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(b"x abcde".to_vec(), out);
+
+    // A failing sink poisons the replacer, whichever way it's built.
+    #[cfg(feature = "std")]
+    {
+        struct Fail;
+
+        impl std::io::Write for Fail {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "full"))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut replacer = ac.replacer(replacements).unwrap();
+        assert!(replacer.replace_to(b"xab", &mut Fail).is_err());
+        assert!(replacer.is_poisoned());
+        let mut out = vec![];
+        assert!(replacer.replace_to(b"ab", &mut out).is_err());
+        assert!(out.is_empty());
+        let mut replacer =
+            ac.replacer_fn(|pid, _| replacements[pid].into()).unwrap();
+        assert!(replacer.replace_to(b"xab", &mut Fail).is_err());
+        assert!(replacer.is_poisoned());
+        assert!(replacer.finish().is_err());
+        replacer.reset();
+        assert_eq!(b"<AB>", replacer.replace(b"ab").unwrap());
+    }
+}
+
+#[test]
+fn replacer_reuses_buffer() {
+    // Each call only returns the output of its own chunk, even when an
+    // earlier chunk needed a much larger buffer.
+    let ac = AhoCorasick::new(["ab"]).unwrap();
+    let mut replacer = ac.replacer(["XYZ"]).unwrap();
+    let big = b"ab".repeat(1000);
+    assert_eq!(b"XYZ".repeat(1000), replacer.replace(&big).unwrap());
+    assert_eq!(b"cXYZ", replacer.replace(b"cab").unwrap());
+    assert_eq!(b"", replacer.replace(b"a").unwrap());
+    assert_eq!(b"XYZ", replacer.replace(b"b").unwrap());
+    assert_eq!(b"", replacer.replace(b"").unwrap());
+    assert_eq!(b"", replacer.finish().unwrap());
+}
+
+#[test]
+fn replacer_tiny_and_empty_chunks() {
+    let ac = AhoCorasick::new(["abc", "bcd", "x"]).unwrap();
+    let haystack = b"abcd abbcd xabc bc";
+    let expected = b"1d ab2 31 bc";
+    let mut replacer = ac.replacer(["1", "2", "3"]).unwrap();
+    // An empty chunk at the start, or in the middle of a potential match,
+    // is a no-op.
+    assert_eq!(b"", replacer.replace(b"").unwrap());
+    let mut out = vec![];
+    for byte in haystack.iter() {
+        out.extend_from_slice(replacer.replace(&[*byte]).unwrap());
+        assert_eq!(b"", replacer.replace(b"").unwrap());
+        assert_eq!(b"", replacer.replace(&[]).unwrap());
+    }
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(&expected[..], out);
+
+    // Empty chunks write nothing to a sink either.
+    #[cfg(feature = "std")]
+    {
+        let mut replacer = ac.replacer(["1", "2", "3"]).unwrap();
+        let mut out = vec![];
+        for byte in haystack.iter() {
+            replacer.replace_to(&[*byte], &mut out).unwrap();
+            let len = out.len();
+            replacer.replace_to(b"", &mut out).unwrap();
+            assert_eq!(len, out.len());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(&expected[..], out);
+    }
+}
+
+// This tests that the matches ending at the end of the stream are decided
+// the same way wherever the stream is split, including when the last chunk
+// ends with their last byte and only `finish` can decide them.
+#[test]
+fn replacer_finish_decides_matches_at_end_of_stream() {
+    use crate::{Lookahead, OverlapPolicy, PatternID};
+
+    let x = |within| Some(Lookahead::new(within).bytes(b"x"));
+    let lookahead = |within, patterns: &[&str]| {
+        AhoCorasick::builder()
+            .lookahead(PatternID::must(0), x(within))
+            .build(patterns)
+            .unwrap()
+    };
+    let finish = |ac: &AhoCorasick, chunks: &[&str]| {
+        let mut replacer = ac.replacer(["X", "Y"]).unwrap();
+        let mut out = vec![];
+        for chunk in chunks {
+            out.extend_from_slice(replacer.replace(chunk.as_bytes()).unwrap());
+        }
+        let finished = replacer.finish().unwrap().to_vec();
+        // Finishing again returns the same bytes.
+        assert_eq!(finished, replacer.finish().unwrap());
+        out.extend_from_slice(&finished);
+        String::from_utf8(out).unwrap()
+    };
+    let tests: &[(AhoCorasick, &[&str], &str)] = &[
+        // A match ending with the last chunk was already replaced.
+        (AhoCorasick::new(["ab", "b"]).unwrap(), &["xa", "b"], "xX"),
+        (AhoCorasick::new(["abc", "b"]).unwrap(), &["ab", ""], "aY"),
+        // A match waiting for its lookahead is rejected once the stream
+        // ends, and a shorter one ending at the same position is tried.
+        (lookahead(1, &["ab", "b"]), &["a", "b"], "aY"),
+        (lookahead(2, &["ab", "b"]), &["ab", "x"], "Xx"),
+        // The bytes held back after it are then searched.
+        (lookahead(2, &["ab", "c"]), &["ab", "c"], "abY"),
+        (lookahead(3, &["ab", "b"]), &["abab"], "aYaY"),
+        // An empty pattern also matches at the end.
+        (AhoCorasick::new(["", "a"]).unwrap(), &["a"], "XaX"),
+    ];
+    for (ac, chunks, expected) in tests {
+        assert_eq!(*expected, finish(ac, chunks), "{:?}", chunks);
+    }
+
+    let mut seed = 0x9E3779B97F4A7C15u64;
+    let mut random = |n: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % (n as u64)) as usize
+    };
+    for _ in 0..2000 {
+        let patterns: Vec<Vec<u8>> = (0..1 + random(3))
+            .map(|_| (0..1 + random(3)).map(|_| b"abc"[random(3)]).collect())
+            .collect();
+        let haystack: Vec<u8> =
+            (0..random(9)).map(|_| b"abcx"[random(4)]).collect();
+        let mut builder = AhoCorasick::builder();
+        let mut replacer_builder = AhoCorasickReplacerBuilder::new();
+        let config = random(4);
+        match config {
+            1 => {
+                builder.lookahead(PatternID::must(0), x(1 + random(2)));
+            }
+            2 => {
+                builder.overlap_policy(Some(OverlapPolicy::LongestWins));
+            }
+            3 => {
+                replacer_builder.coalesce(true);
+            }
+            _ => {}
+        }
+        let ac = builder.build(&patterns).unwrap();
+        let replacements = ["X", "Y", "Z"];
+        let replace_with = &replacements[..patterns.len()];
+        let mut replacer =
+            ac.replacer_with(&replacer_builder, replace_with).unwrap();
+        let mut expected = replacer.replace(&haystack).unwrap().to_vec();
+        expected.extend_from_slice(replacer.finish().unwrap());
+        if config == 0 {
+            assert_eq!(
+                ac.replace_all_bytes(&haystack, replace_with),
+                expected
+            );
+        }
+        // The rest of the stream after the split is given a byte at a time,
+        // followed by an empty chunk.
+        for split in 0..=haystack.len() {
+            let mut replacer =
+                ac.replacer_with(&replacer_builder, replace_with).unwrap();
+            let mut out =
+                replacer.replace(&haystack[..split]).unwrap().to_vec();
+            for byte in haystack[split..].chunks(1) {
+                out.extend_from_slice(replacer.replace(byte).unwrap());
+            }
+            out.extend_from_slice(replacer.replace(b"").unwrap());
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(
+                expected, out,
+                "patterns {:?}, haystack {:?}, split {}",
+                patterns, haystack, split,
+            );
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn replacer_replace_to() {
+    use crate::MatchDeduper;
+
+    let ac = AhoCorasick::new(["ab", "abcd", "bc", "d"]).unwrap();
+    let haystack = b"xabcdabcabcd dd ababcbcd abc";
+    let replacements = ["1", "22", "", "4"];
+    for (coalesce, dedup) in [
+        (false, None),
+        (true, None),
+        (false, Some(MatchDeduper::new(Some(5)))),
+    ] {
+        let mut builder = AhoCorasickReplacerBuilder::new();
+        builder.coalesce(coalesce).dedup(dedup);
+        for chunk_size in 1..=haystack.len() {
+            let mut replacer =
+                ac.replacer_with(&builder, replacements).unwrap();
+            let mut expected = vec![];
+            for chunk in haystack.chunks(chunk_size) {
+                expected.extend_from_slice(replacer.replace(chunk).unwrap());
+            }
+            expected.extend_from_slice(replacer.finish().unwrap());
+
+            let mut replacer =
+                ac.replacer_with(&builder, replacements).unwrap();
+            let mut out = vec![];
+            for chunk in haystack.chunks(chunk_size) {
+                replacer.replace_to(chunk, &mut out).unwrap();
+            }
+            out.extend_from_slice(replacer.finish().unwrap());
+            assert_eq!(expected, out, "chunk size {}", chunk_size);
+        }
+    }
+}
+
+#[test]
+fn replacer_replace_to_vec() {
+    use crate::{MatchErrorKind, PendingLimit};
+
+    let ac = AhoCorasick::new(["ab", "abcd", "bc", "d"]).unwrap();
+    let haystack = b"xabcdabcabcd dd ababcbcd abc";
+    let replacements = ["1", "22", "", "4"];
+    for chunk_size in 1..=haystack.len() {
+        let mut replacer = ac.replacer(replacements).unwrap();
+        let mut expected = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            expected.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        expected.extend_from_slice(replacer.finish().unwrap());
+
+        // The output is appended to what the buffer already holds.
+        let mut replacer = ac.replacer(replacements).unwrap();
+        let mut out = b"head:".to_vec();
+        for chunk in haystack.chunks(chunk_size) {
+            replacer.replace_to_vec(chunk, &mut out).unwrap();
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(b"head:", &out[..5]);
+        assert_eq!(expected, &out[5..], "chunk size {}", chunk_size);
+
+        let mut replacer = ac
+            .replacer_fn(|pid, _| replacements[pid].as_bytes().to_vec())
+            .unwrap();
+        let mut out = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            replacer.replace_to_vec(chunk, &mut out).unwrap();
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(expected, out, "chunk size {}", chunk_size);
+    }
+
+    // On an error, the output of the chunk was already appended, so only
+    // the bytes held back are returned once the stream is finished, even
+    // after a call that returned its output from the internal buffer.
+    let ac = AhoCorasick::new(["abcdef"]).unwrap();
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.max_pending_bytes(Some(PendingLimit::Error(3)));
+    let mut replacer = ac.replacer_with(&builder, ["X"]).unwrap();
+    assert_eq!(b"xy", replacer.replace(b"xy").unwrap());
+    let mut out = vec![];
+    let err = replacer.replace_to_vec(b"zabcd", &mut out).unwrap_err();
+    assert_eq!(MatchErrorKind::PendingLimitExceeded { limit: 3 }, *err.kind());
+    assert_eq!(b"z", &out[..]);
+    assert_eq!(b"abcd", replacer.finish().unwrap());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn replacer_replace_vectored_matches_replace() {
+    use std::io::IoSlice;
+
+    use crate::PendingLimit;
+
+    let ac = AhoCorasick::new(["abcd", "bc", "d"]).unwrap();
+    let haystack = b"xabcdabcabcd dd abcabd";
+    let replacements = ["1", "22", "3"];
+    let mut bounded = AhoCorasickReplacerBuilder::new();
+    bounded.max_pending_bytes(Some(PendingLimit::Flush(2)));
+    for builder in [AhoCorasickReplacerBuilder::new(), bounded] {
+        for len in 1..=haystack.len() {
+            // The same chunks are given as is, and as slices of a byte, with
+            // an empty slice in between.
+            let mut expected = vec![];
+            let mut out = vec![];
+            let mut replacer =
+                ac.replacer_with(&builder, replacements).unwrap();
+            let mut vectored =
+                ac.replacer_with(&builder, replacements).unwrap();
+            for chunk in haystack.chunks(len) {
+                expected.extend_from_slice(replacer.replace(chunk).unwrap());
+                let slices: Vec<IoSlice<'_>> = chunk
+                    .chunks(1)
+                    .flat_map(|byte| [IoSlice::new(byte), IoSlice::new(b"")])
+                    .collect();
+                out.extend_from_slice(
+                    vectored.replace_vectored(&slices).unwrap(),
+                );
+            }
+            assert_eq!(&[] as &[u8], vectored.replace_vectored(&[]).unwrap());
+            expected.extend_from_slice(replacer.finish().unwrap());
+            out.extend_from_slice(vectored.finish().unwrap());
+            assert_eq!(expected, out, "chunk length {}", len);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn stream_line_column() {
+    use crate::ColumnUnit;
+
+    // Make the haystack larger than the stream buffer, so that positions
+    // must be tracked across buffer rolls.
+    let mut haystack = String::new();
+    let (mut bytes, mut chars) = (vec![], vec![]);
+    for i in 0..3_000 {
+        let prefix = format!("line {} ☃ ", i);
+        let line = format!("{}foo{}\r\n", prefix, "ß".repeat(i % 7));
+        let (at_foo, at_nl) =
+            (haystack.len() + prefix.len(), haystack.len() + line.len() - 1);
+        let foo = Match::must(0, at_foo..at_foo + 3);
+        let nl = Match::must(1, at_nl..at_nl + 1);
+        bytes.push((foo, i + 1, prefix.len() + 1));
+        bytes.push((nl, i + 1, line.len()));
+        chars.push((foo, i + 1, prefix.chars().count() + 1));
+        chars.push((nl, i + 1, line.chars().count()));
+        haystack.push_str(&line);
+    }
+    let ac = AhoCorasick::new(["foo", "\n"]).unwrap();
+    for (unit, expected) in
+        [(ColumnUnit::Byte, bytes), (ColumnUnit::Char, chars)]
+    {
+        let got: Vec<(Match, usize, usize)> = ac
+            .try_stream_find_iter_line_column(haystack.as_bytes(), unit)
+            .unwrap()
+            .map(|r| {
+                let (m, pos) = r.unwrap();
+                (m, pos.line(), pos.column())
+            })
+            .collect();
+        assert!(expected == got);
+    }
+}
+
+#[test]
+#[should_panic]
+fn overlapping_not_allowed_leftmost_first() {
+    let fsm = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(None::<String>)
+        .unwrap();
+    assert_eq!(fsm.find_overlapping_iter("").count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn overlapping_not_allowed_leftmost_longest() {
+    let fsm = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(None::<String>)
+        .unwrap();
+    assert_eq!(fsm.find_overlapping_iter("").count(), 0);
+}
+
+// This tests that if we build an AC matcher with an "unanchored" start kind,
+// then we can't run an anchored search even if the underlying searcher
+// supports it.
+//
+// The key bit here is that both of the NFAs in this crate unconditionally
+// support both unanchored and anchored searches, but the DFA does not because
+// of the added cost of doing so. To avoid the top-level AC matcher sometimes
+// supporting anchored and sometimes not (depending on which searcher it
+// chooses to use internally), we ensure that the given 'StartKind' is always
+// respected.
+#[test]
+fn anchored_not_allowed_even_if_technically_available() {
+    let ac = AhoCorasick::builder()
+        .kind(Some(AhoCorasickKind::NoncontiguousNFA))
+        .start_kind(StartKind::Unanchored)
+        .build(&["foo"])
+        .unwrap();
+    assert!(ac.try_find(Input::new("foo").anchored(Anchored::Yes)).is_err());
+
+    let ac = AhoCorasick::builder()
+        .kind(Some(AhoCorasickKind::ContiguousNFA))
+        .start_kind(StartKind::Unanchored)
+        .build(&["foo"])
+        .unwrap();
+    assert!(ac.try_find(Input::new("foo").anchored(Anchored::Yes)).is_err());
+
+    // For completeness, check that the DFA returns an error too.
+    let ac = AhoCorasick::builder()
+        .kind(Some(AhoCorasickKind::DFA))
+        .start_kind(StartKind::Unanchored)
+        .build(&["foo"])
+        .unwrap();
+    assert!(ac.try_find(Input::new("foo").anchored(Anchored::Yes)).is_err());
+}
+
+// This is like the test aboved, but with unanchored and anchored flipped. That
+// is, we asked for an AC searcher with anchored support and we check that
+// unanchored searches return an error even if the underlying searcher would
+// technically support it.
+#[test]
+fn unanchored_not_allowed_even_if_technically_available() {
+    let ac = AhoCorasick::builder()
+        .kind(Some(AhoCorasickKind::NoncontiguousNFA))
+        .start_kind(StartKind::Anchored)
+        .build(&["foo"])
+        .unwrap();
+    assert!(ac.try_find(Input::new("foo").anchored(Anchored::No)).is_err());
+
+    let ac = AhoCorasick::builder()
+        .kind(Some(AhoCorasickKind::ContiguousNFA))
+        .start_kind(StartKind::Anchored)
+        .build(&["foo"])
+        .unwrap();
+    assert!(ac.try_find(Input::new("foo").anchored(Anchored::No)).is_err());
+
+    // For completeness, check that the DFA returns an error too.
+    let ac = AhoCorasick::builder()
+        .kind(Some(AhoCorasickKind::DFA))
+        .start_kind(StartKind::Anchored)
+        .build(&["foo"])
+        .unwrap();
+    assert!(ac.try_find(Input::new("foo").anchored(Anchored::No)).is_err());
+}
+
+// This tests that a prefilter does not cause a search to report a match
+// outside the bounds provided by the caller.
+//
+// This is a regression test for a bug I introduced during the rewrite of most
+// of the crate after 0.7. It was never released. The tricky part here is
+// ensuring we get a prefilter that can report matches on its own (such as the
+// packed searcher). Otherwise, prefilters that report false positives might
+// have searched past the bounds provided by the caller, but confirming the
+// match would subsequently fail.
+#[test]
+fn prefilter_stays_in_bounds() {
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(&["sam", "frodo", "pippin", "merry", "gandalf", "sauron"])
+        .unwrap();
+    let haystack = "foo gandalf";
+    assert_eq!(None, ac.find(Input::new(haystack).range(0..10)));
+}
+
+// This tests that restricting a search to a span of the haystack treats the
+// start of the span as the start of the search, both for unanchored and
+// anchored searches, while still reporting offsets into the full haystack.
+#[test]
+fn span_restriction_uses_span_start_as_search_start() {
+    for kind in [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ] {
+        let ac = AhoCorasick::builder()
+            .kind(Some(kind))
+            .start_kind(StartKind::Both)
+            .build(["abc", "bcd"])
+            .unwrap();
+        let haystack = "xabcdx";
+
+        // 'abc' starts before the span, so only 'bcd' can be found.
+        let input = Input::new(haystack).range(2..);
+        assert_eq!(Some(Match::must(1, 2..5)), ac.find(input));
+        // An anchored search must match at the start of the span, not at the
+        // start of the haystack.
+        let input = Input::new(haystack).range(2..).anchored(Anchored::Yes);
+        assert_eq!(Some(Match::must(1, 2..5)), ac.find(input));
+        let input = Input::new(haystack).range(3..).anchored(Anchored::Yes);
+        assert_eq!(None, ac.find(input));
+        // Matches never extend beyond the end of the span.
+        let input = Input::new(haystack).range(1..3);
+        assert_eq!(None, ac.find(input));
+        let input = Input::new(haystack).range(1..5);
+        assert_eq!(
+            alloc::vec![Match::must(0, 1..4)],
+            ac.find_iter(input).collect::<Vec<_>>(),
+        );
+    }
+
+    // Matches are still verified against the bytes around them, even when
+    // they're outside the span.
+    let ac =
+        AhoCorasick::builder().word_boundary(true).build(["foo"]).unwrap();
+    assert_eq!(None, ac.find(Input::new("xfoo").range(1..)));
+    assert_eq!(None, ac.find(Input::new("foox").range(..3)));
+    assert_eq!(
+        Some(Match::must(0, 1..4)),
+        ac.find(Input::new(" foo ").range(1..4))
+    );
+    let ac = AhoCorasick::builder()
+        .lookahead(
+            crate::PatternID::must(0),
+            Some(crate::Lookahead::new(1).bytes(b".")),
+        )
+        .build(["foo"])
+        .unwrap();
+    assert_eq!(None, ac.find(Input::new("foox").range(..3)));
+    assert_eq!(
+        Some(Match::must(0, 0..3)),
+        ac.find(Input::new("foo.").range(..3))
+    );
+}
+
+// This tests that an exact case search with an ASCII case insensitive
+// automaton reports the same matches as a case sensitive automaton, for
+// random patterns and haystacks over a small alphabet.
+#[test]
+fn exact_case_matches_case_sensitive_automaton() {
+    let mut seed = 0x2545F4914F6CDD1Du64;
+    let mut random = |n: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % (n as u64)) as usize
+    };
+    let mut word = |max: usize| -> String {
+        let len = random(max + 1);
+        (0..len).map(|_| ['a', 'A', 'b', 'B'][random(4)]).collect()
+    };
+    for _ in 0..200 {
+        let patterns: Vec<String> = (0..6).map(|_| word(3)).collect();
+        let haystack = word(20);
+        for match_kind in [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ] {
+            // With leftmost semantics, an automaton may skip an empty match
+            // where a longer pattern starts to match but then fails. Which
+            // ones it skips depends on case insensitivity.
+            if match_kind != MatchKind::Standard
+                && patterns.iter().any(|p| p.is_empty())
+            {
+                continue;
+            }
+            let mut builder = AhoCorasick::builder();
+            builder.match_kind(match_kind).start_kind(StartKind::Both);
+            let exact = builder.build(&patterns).unwrap();
+            let ac = builder
+                .ascii_case_insensitive(true)
+                .store_patterns(true)
+                .build(&patterns)
+                .unwrap();
+            for anchored in [Anchored::No, Anchored::Yes] {
+                let input = Input::new(&haystack).anchored(anchored);
+                let msg = (&patterns, &haystack, match_kind, anchored);
+                assert_eq!(
+                    exact.find_iter(input.clone()).collect::<Vec<_>>(),
+                    ac.find_iter(input.clone().exact_case(true))
+                        .collect::<Vec<_>>(),
+                    "{:?}",
+                    msg,
+                );
+                assert_eq!(
+                    exact.is_match(input.clone()),
+                    ac.is_match(input.clone().exact_case(true)),
+                    "{:?}",
+                    msg,
+                );
+                // An overlapping search with an ASCII case insensitive
+                // automaton may report the same empty match more than once.
+                if match_kind == MatchKind::Standard
+                    && !anchored.is_anchored()
+                    && patterns.iter().all(|p| !p.is_empty())
+                {
+                    assert_eq!(
+                        exact
+                            .find_overlapping_iter(input.clone())
+                            .collect::<Vec<_>>(),
+                        ac.find_overlapping_iter(input.exact_case(true))
+                            .collect::<Vec<_>>(),
+                        "{:?}",
+                        msg,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn exact_case_requires_stored_patterns() {
+    let ac = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .build(["foo"])
+        .unwrap();
+    let input = Input::new("FOO foo").exact_case(true);
+    assert!(ac.try_find(input.clone()).is_err());
+    assert!(ac.try_find_iter(input.clone()).is_err());
+    assert!(ac.try_find_overlapping_iter(input.clone()).is_err());
+    assert_eq!(Some(Match::must(0, 0..3)), ac.find("FOO foo"));
+
+    // Without ASCII case insensitivity, every match already has the exact
+    // case of its pattern.
+    let ac = AhoCorasick::new(["foo"]).unwrap();
+    assert_eq!(Some(Match::must(0, 4..7)), ac.try_find(input).unwrap());
+}
+
+// This tests that a verifier that rejects every match of some patterns
+// results in the same matches as an automaton built without those patterns.
+#[test]
+fn verify_matches_automaton_without_rejected_patterns() {
+    let mut seed = 0x9E3779B97F4A7C15u64;
+    let mut random = |n: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % (n as u64)) as usize
+    };
+    let mut word = |min: usize, max: usize| -> String {
+        let len = min + random(max - min + 1);
+        (0..len).map(|_| ['a', 'b', 'c'][random(3)]).collect()
+    };
+    for _ in 0..200 {
+        let patterns: Vec<String> = (0..6).map(|_| word(1, 3)).collect();
+        let rejected = patterns[0].clone();
+        let haystack = word(0, 20);
+        let kept: Vec<usize> =
+            (0..patterns.len()).filter(|&i| patterns[i] != rejected).collect();
+        for match_kind in [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ] {
+            let mut builder = AhoCorasick::builder();
+            builder.match_kind(match_kind).start_kind(StartKind::Both);
+            let without =
+                builder.build(kept.iter().map(|&i| &patterns[i])).unwrap();
+            let all = builder.build(&patterns).unwrap();
+            let rejected = rejected.clone();
+            let verified = builder
+                .verify(move |pattern, _| pattern != rejected.as_bytes())
+                .build(&patterns)
+                .unwrap();
+            for anchored in [Anchored::No, Anchored::Yes] {
+                let input = Input::new(&haystack).anchored(anchored);
+                let msg = (&patterns, &haystack, match_kind, anchored);
+                let expected: Vec<Match> = without
+                    .find_iter(input.clone())
+                    .map(|m| {
+                        Match::must(kept[m.pattern().as_usize()], m.span())
+                    })
+                    .collect();
+                assert_eq!(
+                    expected,
+                    verified.find_iter(input.clone()).collect::<Vec<_>>(),
+                    "{:?}",
+                    msg,
+                );
+                assert_eq!(
+                    all.find_iter(input.clone()).collect::<Vec<_>>(),
+                    verified
+                        .find_iter(input.verify(false))
+                        .collect::<Vec<_>>(),
+                    "{:?}",
+                    msg,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn verify_rejects_empty_matches() {
+    let ac = AhoCorasick::builder()
+        .verify(|_, bytes| !bytes.is_empty())
+        .build(["", "b"])
+        .unwrap();
+    assert_eq!(
+        vec![Match::must(1, 1..2)],
+        ac.find_iter("abc").collect::<Vec<_>>(),
+    );
+    assert!(!ac.is_match("ac"));
+    assert_eq!(
+        vec![Match::must(1, 1..2)],
+        ac.find_overlapping_iter("abc").collect::<Vec<_>>(),
+    );
+    // A verifier stores the patterns, so exact case searches work too.
+    let ac = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .verify(|_, _| true)
+        .build(["b"])
+        .unwrap();
+    assert_eq!(Some(&b"b"[..]), ac.pattern(crate::PatternID::must(0)));
+    let input = Input::new("B b").exact_case(true);
+    assert_eq!(Some(Match::must(0, 2..3)), ac.find(input));
+}
+
+#[test]
+fn word_boundary_applies_to_searches_and_replacers() {
+    use crate::OverlapPolicy;
+
+    let patterns = ["cat", "at", "x-b", "b"];
+    let replacements = ["C", "A", "X", "B"];
+    let tests: &[(&str, &[Match], &str)] = &[
+        (
+            "concat cat at_ at",
+            &[Match::must(0, 7..10), Match::must(1, 15..17)],
+            "concat C at_ A",
+        ),
+        ("yx-b x-b", &[Match::must(3, 3..4), Match::must(2, 5..8)], "yx-B X"),
+        ("cat", &[Match::must(0, 0..3)], "C"),
+        ("cats scat", &[], "cats scat"),
+    ];
+    let kinds = [
+        AhoCorasickKind::NoncontiguousNFA,
+        AhoCorasickKind::ContiguousNFA,
+        AhoCorasickKind::DFA,
+    ];
+    let match_kinds = [
+        MatchKind::Standard,
+        MatchKind::LeftmostFirst,
+        MatchKind::LeftmostLongest,
+    ];
+    for kind in kinds {
+        for match_kind in match_kinds {
+            let ac = AhoCorasick::builder()
+                .kind(Some(kind))
+                .match_kind(match_kind)
+                .word_boundary(true)
+                .build(patterns)
+                .unwrap();
+            for &(haystack, matches, replaced) in tests {
+                let name =
+                    format!("{:?}, {:?}, {:?}", kind, match_kind, haystack);
+                let found: Vec<Match> = ac.find_iter(haystack).collect();
+                assert_eq!(matches.to_vec(), found, "{}", name);
+                let input = Input::new(haystack).verify(false);
+                assert_eq!(
+                    matches.first().copied(),
+                    ac.find(input),
+                    "{}",
+                    name
+                );
+                assert_eq!(
+                    replaced,
+                    ac.replace_all(haystack, &replacements),
+                    "{}",
+                    name,
+                );
+                if match_kind != MatchKind::Standard {
+                    continue;
+                }
+                let haystack = haystack.as_bytes();
+                for chunk_size in 1..=haystack.len() {
+                    let mut replacer = ac.replacer(replacements).unwrap();
+                    let mut out = vec![];
+                    for chunk in haystack.chunks(chunk_size) {
+                        out.extend_from_slice(
+                            replacer.replace(chunk).unwrap(),
+                        );
+                    }
+                    out.extend_from_slice(replacer.finish().unwrap());
+                    assert_eq!(
+                        replaced.as_bytes().to_vec(),
+                        out,
+                        "{}, chunk size {}",
+                        name,
+                        chunk_size,
+                    );
+                }
+            }
+        }
+    }
+
+    // Overlapping matches are only resolved once their bytes after them
+    // are seen.
+    let ac = AhoCorasick::builder()
+        .word_boundary(true)
+        .overlap_policy(Some(OverlapPolicy::LongestWins))
+        .build(["ab", "abc", "bc d"])
+        .unwrap();
+    let haystack = b"abc d abcd ab";
+    let expected = ac.replace_all_bytes(haystack, &["1", "2", "3"]);
+    assert_eq!(b"2 d abcd 1".to_vec(), expected);
+    for chunk_size in 1..=haystack.len() {
+        let mut replacer = ac.replacer(["1", "2", "3"]).unwrap();
+        let mut out = vec![];
+        for chunk in haystack.chunks(chunk_size) {
+            out.extend_from_slice(replacer.replace(chunk).unwrap());
+        }
+        out.extend_from_slice(replacer.finish().unwrap());
+        assert_eq!(expected, out, "chunk size {}", chunk_size);
+    }
+
+    // An empty match inserted before every byte can't wait for the byte
+    // after it.
+    let ac =
+        AhoCorasick::builder().word_boundary(true).build(["", "a"]).unwrap();
+    assert_eq!(
+        vec![Match::must(0, 2..2), Match::must(1, 3..4)],
+        ac.find_iter("b  a").collect::<Vec<_>>(),
+    );
+    let err = ac.replacer(["", "A"]).err().unwrap();
+    assert!(format!("{}", err).contains("non-word"));
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.ignore_empty_matches(true);
+    let mut replacer = ac.replacer_with(&builder, ["", "A"]).unwrap();
+    let mut out = replacer.replace(b"b a ba").unwrap().to_vec();
+    out.extend_from_slice(replacer.finish().unwrap());
+    assert_eq!(b"b A ba".to_vec(), out);
+
+    // Other stream searches and replacements don't honor word boundaries,
+    // so they return an error instead of reporting more matches.
+    let ac =
+        AhoCorasick::builder().word_boundary(true).build(["foo"]).unwrap();
+    assert_eq!("foobar X", ac.replace_all("foobar foo", &["X"]));
+    assert_eq!(
+        &crate::MatchErrorKind::UnsupportedWordBoundary,
+        ac.stream_finder().err().unwrap().kind(),
+    );
+    #[cfg(feature = "std")]
+    {
+        let rdr = &b"foobar foo"[..];
+        assert!(ac.try_stream_find_iter(rdr).is_err());
+        assert!(ac.try_stream_find_iter_earliest(rdr).is_err());
+        let unit = crate::ColumnUnit::Byte;
+        assert!(ac.try_stream_find_iter_line_column(rdr, unit).is_err());
+        let err = ac.try_stream_replace_all(rdr, vec![], &["X"]).unwrap_err();
+        assert!(format!("{}", err).contains("word boundaries"));
+        let err = ac
+            .try_stream_replace_all_with(rdr, vec![], |_, _, _| Ok(()))
+            .unwrap_err();
+        assert!(format!("{}", err).contains("word boundaries"));
+    }
+}
+
+#[test]
+fn translation_table_matches_translated_haystack() {
+    let mut seed = 0x2545F4914F6CDD1Du64;
+    let mut random = |n: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % (n as u64)) as usize
+    };
+    // No byte translates to 'c', so patterns with a 'c' never match.
+    let mut table = [0u8; 256];
+    for b in 0..=255u8 {
+        table[usize::from(b)] = match b {
+            b'x' => b'a',
+            b'y' | b'B' => b'b',
+            b'c' => b'z',
+            _ => b,
+        };
+    }
+    for _ in 0..100 {
+        let mut word = |alphabet: &[u8], min: usize, max: usize| {
+            let len = min + random(max - min + 1);
+            (0..len)
+                .map(|_| alphabet[random(alphabet.len())])
+                .collect::<Vec<u8>>()
+        };
+        let patterns: Vec<Vec<u8>> =
+            (0..5).map(|_| word(b"abczA", 1, 3)).collect();
+        let haystack = word(b"abcxyzBA", 0, 20);
+        let translated: Vec<u8> =
+            haystack.iter().map(|&b| table[usize::from(b)]).collect();
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            for match_kind in [
+                MatchKind::Standard,
+                MatchKind::LeftmostFirst,
+                MatchKind::LeftmostLongest,
+            ] {
+                for ci in [false, true] {
+                    let mut builder = AhoCorasick::builder();
+                    builder
+                        .kind(Some(kind))
+                        .match_kind(match_kind)
+                        .ascii_case_insensitive(ci)
+                        .store_patterns(true);
+                    let plain = builder.build(&patterns).unwrap();
+                    let ac = builder
+                        .translation_table(Some(table))
+                        .build(&patterns)
+                        .unwrap();
+                    let msg = (&patterns, &haystack, kind, match_kind, ci);
+                    assert_eq!(
+                        plain.find_iter(&translated).collect::<Vec<_>>(),
+                        ac.find_iter(&haystack).collect::<Vec<_>>(),
+                        "{:?}",
+                        msg,
+                    );
+                    let exact = Input::new(&haystack).exact_case(true);
+                    assert_eq!(
+                        plain
+                            .find_iter(
+                                Input::new(&translated).exact_case(true)
+                            )
+                            .collect::<Vec<_>>(),
+                        ac.find_iter(exact).collect::<Vec<_>>(),
+                        "{:?}",
+                        msg,
+                    );
+                    if match_kind == MatchKind::Standard {
+                        assert_eq!(
+                            plain
+                                .find_overlapping_iter(&translated)
+                                .collect::<Vec<_>>(),
+                            ac.find_overlapping_iter(&haystack)
+                                .collect::<Vec<_>>(),
+                            "{:?}",
+                            msg,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn recommend_kind_matches_automatic_kind() {
+    let many: Vec<String> =
+        (0..101).map(|i| format!("pattern{}", i)).collect();
+    for start_kind in
+        [StartKind::Unanchored, StartKind::Anchored, StartKind::Both]
+    {
+        for len in [0, 1, 100, 101] {
+            let patterns = &many[..len];
+            let mut builder = AhoCorasick::builder();
+            builder.start_kind(start_kind);
+            let recommended = builder.recommend_kind(patterns);
+            assert_eq!(
+                recommended,
+                builder.build(patterns).unwrap().kind(),
+                "{:?}",
+                (start_kind, len),
+            );
+            // The kind set on the builder doesn't change the recommendation.
+            builder.kind(Some(AhoCorasickKind::NoncontiguousNFA));
+            assert_eq!(recommended, builder.recommend_kind(patterns));
+        }
+    }
+}
+
+#[test]
+fn span_helpers_agree_with_covered_offsets() {
+    use crate::Span;
+
+    let covered = |spans: &[Span]| -> Vec<usize> {
+        let mut offsets: Vec<usize> =
+            spans.iter().flat_map(|s| s.range()).collect();
+        offsets.sort();
+        offsets.dedup();
+        offsets
+    };
+    let all: Vec<Span> = (0..6)
+        .flat_map(|start| (start..6).map(move |end| Span::from(start..end)))
+        .collect();
+    for &a in all.iter() {
+        for &b in all.iter() {
+            let both: Vec<usize> =
+                a.range().filter(|i| b.range().contains(i)).collect();
+            assert_eq!(!both.is_empty(), a.overlaps(b), "{:?} {:?}", a, b);
+
+            let (before, after) = a.subtract(b);
+            let rest: Vec<Span> = before.into_iter().chain(after).collect();
+            let only_a: Vec<usize> =
+                a.range().filter(|i| !b.range().contains(i)).collect();
+            assert_eq!(only_a, covered(&rest), "{:?} {:?}", a, b);
+            assert!(rest.iter().all(|s| !s.is_empty()));
+
+            let mut sorted = vec![a, b];
+            sorted.sort_by_key(|s| s.start);
+            let mut coalesced = sorted.clone();
+            Span::coalesce(&mut coalesced);
+            assert_eq!(covered(&sorted), covered(&coalesced));
+            assert!(coalesced.windows(2).all(|w| w[0].end < w[1].start));
+            if let Some(union) = a.union(b) {
+                assert!(union.start <= a.start && a.end <= union.end);
+                assert!(union.start <= b.start && b.end <= union.end);
+                assert!(coalesced.len() <= 1);
+            } else {
+                let nonempty = sorted.iter().filter(|s| !s.is_empty());
+                assert_eq!(nonempty.count(), coalesced.len());
+            }
+        }
+    }
+
+    let mut buf = b"abcdef".to_vec();
+    Span::apply_edits(
+        &mut buf,
+        vec![
+            (Span::from(0..0), "<"),
+            (Span::from(0..0), "["),
+            (Span::from(1..3), "X"),
+            (Span::from(3..4), ""),
+            (Span::from(6..6), ">"),
+        ],
+    );
+    assert_eq!("<[aXef>", String::from_utf8(buf).unwrap());
+}
+
+#[test]
+fn contained_patterns_agree_with_substring_search() {
+    use crate::PatternID;
+
+    fn occurs(
+        inner: &[u8],
+        outer: &[u8],
+        ascii_case_insensitive: bool,
+    ) -> bool {
+        inner.is_empty()
+            || outer.windows(inner.len()).any(|w| {
+                if ascii_case_insensitive {
+                    w.eq_ignore_ascii_case(inner)
+                } else {
+                    w == inner
+                }
+            })
+    }
+
+    let mut seed: u64 = 0x9E3779B97F4A7C15;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % n
+    };
+    for _ in 0..300 {
+        let patterns: Vec<Vec<u8>> = (0..1 + below(6))
+            .map(|_| {
+                (0..below(5)).map(|_| b"abA"[below(3) as usize]).collect()
+            })
+            .collect();
+        let aci = below(2) == 0;
+        let mut builder = AhoCorasick::builder();
+        builder
+            .match_kind(MatchKind::LeftmostFirst)
+            .ascii_case_insensitive(aci)
+            .dense_depth(below(3) as usize);
+        let contained = builder.contained_patterns(&patterns).unwrap();
+        for (i, outer) in patterns.iter().enumerate() {
+            let expected: Vec<PatternID> = patterns
+                .iter()
+                .enumerate()
+                .filter(|&(j, inner)| j != i && occurs(inner, outer, aci))
+                .map(|(j, _)| PatternID::must(j))
+                .collect();
+            let outer = PatternID::must(i);
+            assert_eq!(
+                expected,
+                contained.contained_in(outer),
+                "{:?} {}",
+                patterns,
+                aci
+            );
+            for &inner in expected.iter() {
+                assert!(contained.contains(outer, inner));
+                assert!(contained.containers(inner).any(|pid| pid == outer));
+            }
+        }
+    }
+}
+
+#[test]
+fn count_overlapping_fast_agrees_with_overlapping_iter() {
+    use crate::{Lookahead, PatternID};
+
+    let mut seed: u64 = 0x2545F4914F6CDD1D;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % n
+    };
+    for _ in 0..300 {
+        let patterns: Vec<Vec<u8>> = (0..1 + below(6))
+            .map(|_| {
+                (0..below(4)).map(|_| b"abA"[below(3) as usize]).collect()
+            })
+            .collect();
+        let haystack: Vec<u8> =
+            (0..below(30)).map(|_| b"abAc"[below(4) as usize]).collect();
+        let start = below(haystack.len() as u64 + 1) as usize;
+        let end = start + below((haystack.len() - start) as u64 + 1) as usize;
+        let mut builder = AhoCorasick::builder();
+        builder.ascii_case_insensitive(below(2) == 0).prefilter(below(2) == 0);
+        if below(4) == 0 {
+            let lookahead = Lookahead::new(1).bytes(b"a");
+            builder.lookahead(PatternID::must(0), Some(lookahead));
+        }
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            let ac = builder.kind(Some(kind)).build(&patterns).unwrap();
+            let input = Input::new(&haystack).range(start..end);
+            assert_eq!(
+                ac.find_overlapping_iter(input.clone()).count(),
+                ac.count_overlapping_fast(input),
+                "{:?} {:?} {:?}",
+                patterns,
+                haystack,
+                kind,
+            );
+        }
+    }
+
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(["a"])
+        .unwrap();
+    assert!(ac.try_count_overlapping_fast("a").is_err());
+    let ac = AhoCorasick::builder()
+        .start_kind(StartKind::Both)
+        .build(["a"])
+        .unwrap();
+    let input = Input::new("a").anchored(Anchored::Yes);
+    assert!(ac.try_count_overlapping_fast(input).is_err());
+}
+
+#[test]
+fn replace_all_cow_borrows_only_without_matches() {
+    use alloc::borrow::Cow;
+
+    use crate::{OverlapPolicy, PatternID};
+
+    let mut seed: u64 = 0x9E3779B97F4A7C15;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % n
+    };
+    for _ in 0..300 {
+        let patterns: Vec<Vec<u8>> = (0..1 + below(4))
+            .map(|_| {
+                (0..1 + below(3)).map(|_| b"ab "[below(3) as usize]).collect()
+            })
+            .collect();
+        let replace_with: Vec<String> =
+            (0..patterns.len()).map(|i| alloc::format!("<{}>", i)).collect();
+        let haystack: String = (0..below(12))
+            .map(|_| ['a', 'b', ' ', 'c'][below(4) as usize])
+            .collect();
+        let mut builder = AhoCorasick::builder();
+        builder
+            .match_kind(
+                [MatchKind::Standard, MatchKind::LeftmostFirst]
+                    [below(2) as usize],
+            )
+            .word_boundary(below(3) == 0)
+            .store_patterns(true);
+        if below(3) == 0 {
+            builder.match_kind(MatchKind::Standard);
+            builder.overlap_policy(Some(OverlapPolicy::LongestWins));
+        }
+        let mut ac = builder.build(&patterns).unwrap();
+        if below(3) == 0 {
+            ac.disable_pattern(PatternID::must(0)).unwrap();
+        }
+        let expected =
+            ac.replace_all_bytes(haystack.as_bytes(), &replace_with);
+        let got = ac.replace_all_cow(haystack.as_bytes(), &replace_with);
+        assert_eq!(expected, &*got, "{:?} {:?}", patterns, haystack);
+        let unchanged = ac.find(haystack.as_str()).is_none();
+        assert_eq!(unchanged, matches!(got, Cow::Borrowed(_)));
+        let got = ac.replace_all_cow_str(&haystack, &replace_with);
+        assert_eq!(expected, got.as_bytes());
+        assert_eq!(unchanged, matches!(got, Cow::Borrowed(_)));
+    }
+
+    let ac = AhoCorasick::builder()
+        .start_kind(StartKind::Anchored)
+        .build(["a"])
+        .unwrap();
+    assert!(ac.try_replace_all_cow(b"b", &["x"]).is_err());
+    assert!(ac.try_replace_all_cow_str("b", &["x"]).is_err());
+}
+
+#[test]
+fn estimate_hit_rate_samples_windows_at_each_stride() {
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(["ab", "b", "abc"])
+        .unwrap();
+    // With a stride no longer than a window, every match is counted.
+    let haystack = b"xabcabbxb".repeat(50);
+    let exact = ac.find_iter(&haystack).count() as f64 / haystack.len() as f64;
+    for stride in [0, 1, 64] {
+        assert_eq!(exact, ac.estimate_hit_rate(&haystack, stride));
+    }
+    assert_eq!(0.0, ac.estimate_hit_rate(b"", 100));
+
+    // Only the first 64 bytes of each stride of 100 bytes are searched,
+    // and a match starting in a window may end after it.
+    let mut haystack = vec![b'x'; 300];
+    haystack[63] = b'a';
+    haystack[64] = b'b';
+    haystack[70] = b'b';
+    haystack[150] = b'b';
+    haystack[299] = b'b';
+    assert_eq!(2.0 / 192.0, ac.estimate_hit_rate(&haystack, 100));
+    let rate = ac.estimate_hit_rate(&haystack[100..], 100);
+    assert_eq!(1.0 / 128.0, rate);
+
+    let ac = AhoCorasick::builder()
+        .start_kind(StartKind::Anchored)
+        .build(["a"])
+        .unwrap();
+    assert!(ac.try_estimate_hit_rate(b"a", 10).is_err());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_dfa_is_identical() {
+    use crate::dfa::DFA;
+
+    let patterns: Vec<String> = (0..300)
+        .map(|i| alloc::format!("{:x}{}", i * 7919, i % 13))
+        .chain(["", "a", "abc"].iter().map(|&p| String::from(p)))
+        .collect();
+    for match_kind in [MatchKind::Standard, MatchKind::LeftmostFirst] {
+        for start_kind in
+            [StartKind::Unanchored, StartKind::Anchored, StartKind::Both]
+        {
+            for byte_classes in [false, true] {
+                let mut builder = DFA::builder();
+                builder
+                    .match_kind(match_kind)
+                    .start_kind(start_kind)
+                    .byte_classes(byte_classes);
+                let dfa = builder.build(&patterns).unwrap();
+                let par = builder.parallel(true).build(&patterns).unwrap();
+                assert_eq!(dfa.to_bytes(), par.to_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_find_iter_matches_find_iter() {
+    let mut seed: u64 = 0x853C49E6748FEA9B;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % n
+    };
+    let match_kinds = [
+        MatchKind::Standard,
+        MatchKind::LeftmostFirst,
+        MatchKind::LeftmostLongest,
+    ];
+    for _ in 0..300 {
+        let patterns: Vec<Vec<u8>> = (0..1 + below(5))
+            .map(|_| {
+                (0..below(5)).map(|_| b"ab "[below(3) as usize]).collect()
+            })
+            .collect();
+        let haystack: Vec<u8> =
+            (0..below(60)).map(|_| b"ab c"[below(4) as usize]).collect();
+        let ac = AhoCorasick::builder()
+            .match_kind(match_kinds[below(3) as usize])
+            .word_boundary(below(4) == 0)
+            .store_patterns(true)
+            .build(&patterns)
+            .unwrap();
+        let expected: Vec<Match> = ac.find_iter(&haystack).collect();
+        for shard_len in 1..=8 {
+            assert_eq!(
+                expected,
+                ac.try_par_find_sharded(&haystack, shard_len).unwrap(),
+                "{:?} {:?} {:?} shard length {}",
+                ac.match_kind(),
+                patterns,
+                haystack,
+                shard_len,
+            );
+        }
+        assert_eq!(expected, ac.par_find_iter(&haystack).collect::<Vec<_>>());
+    }
+
+    let ac = AhoCorasick::builder()
+        .start_kind(StartKind::Anchored)
+        .build(["a"])
+        .unwrap();
+    assert!(ac.try_par_find_iter(b"a").is_err());
+}
+
+// See: https://github.com/BurntSushi/aho-corasick/issues/44
+//
+// In short, this test ensures that enabling ASCII case insensitivity does not
+// visit an exponential number of states when filling in failure transitions.
+#[test]
+fn regression_ascii_case_insensitive_no_exponential() {
+    let ac = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .build(&["Tsubaki House-Triple Shot Vol01校花三姐妹"])
+        .unwrap();
+    assert!(ac.find("").is_none());
+}
+
+// See: https://github.com/BurntSushi/aho-corasick/issues/53
+//
+// This test ensures that the rare byte prefilter works in a particular corner
+// case. In particular, the shift offset detected for '/' in the patterns below
+// was incorrect, leading to a false negative.
+#[test]
+fn regression_rare_byte_prefilter() {
+    use crate::AhoCorasick;
+
+    let ac = AhoCorasick::new(&["ab/j/", "x/"]).unwrap();
+    assert!(ac.is_match("ab/j/"));
+}
+
+#[test]
+fn regression_case_insensitive_prefilter() {
+    for c in b'a'..b'z' {
+        for c2 in b'a'..b'z' {
+            let c = c as char;
+            let c2 = c2 as char;
+            let needle = format!("{}{}", c, c2).to_lowercase();
+            let haystack = needle.to_uppercase();
+            let ac = AhoCorasick::builder()
+                .ascii_case_insensitive(true)
+                .prefilter(true)
+                .build(&[&needle])
+                .unwrap();
+            assert_eq!(
+                1,
+                ac.find_iter(&haystack).count(),
+                "failed to find {:?} in {:?}\n\nautomaton:\n{:?}",
+                needle,
+                haystack,
+                ac,
+            );
+        }
+    }
+}
+
+// See: https://github.com/BurntSushi/aho-corasick/issues/64
+//
+// This occurs when the rare byte prefilter is active.
+#[cfg(feature = "std")]
+#[test]
+fn regression_stream_rare_byte_prefilter() {
+    use std::io::Read;
+
+    // NOTE: The test only fails if this ends with j.
+    const MAGIC: [u8; 5] = *b"1234j";
+
+    // NOTE: The test fails for value in 8188..=8191 These value put the string
+    // to search accross two call to read because the buffer size is 64KB by
+    // default.
+    const BEGIN: usize = 65_535;
+
+    /// This is just a structure that implements Reader. The reader
+    /// implementation will simulate a file filled with 0, except for the MAGIC
+    /// string at offset BEGIN.
+    #[derive(Default)]
+    struct R {
+        read: usize,
+    }
+
+    impl Read for R {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.read > 100000 {
+                return Ok(0);
+            }
+            let mut from = 0;
+            if self.read < BEGIN {
+                from = buf.len().min(BEGIN - self.read);
                 for x in 0..from {
                     buf[x] = 0;
                 }
@@ -1662,3 +5639,431 @@ fn regression_stream_rare_byte_prefilter() {
 
     run().unwrap()
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn replacer_reader_and_writer_keep_output_of_stream() {
+    use std::io::{Read, Write};
+
+    /// A source that returns its pieces one read at a time, and is
+    /// interrupted before each of them.
+    struct Pieces(Vec<Vec<u8>>, bool);
+
+    impl Read for Pieces {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.1 = !self.1;
+            if self.1 {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            let piece = self.0.remove(0);
+            buf[..piece.len()].copy_from_slice(&piece);
+            Ok(piece.len())
+        }
+    }
+
+    let mut seed: u64 = 0x9E3779B97F4A7C15;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n) as usize
+    };
+    for _ in 0..300 {
+        let count = 1 + below(6);
+        let mut word = |max: usize| -> Vec<u8> {
+            let len = below(max as u64 + 1);
+            (0..len).map(|_| b"abc "[below(4)]).collect()
+        };
+        let patterns: Vec<Vec<u8>> = (0..count).map(|_| word(4)).collect();
+        let replacements: Vec<Vec<u8>> =
+            patterns.iter().map(|_| word(3)).collect();
+        let haystack = word(40);
+        let match_kind = if below(2) == 0 {
+            MatchKind::Standard
+        } else {
+            MatchKind::LeftmostLongest
+        };
+        let ac = AhoCorasick::builder()
+            .match_kind(match_kind)
+            .build(&patterns)
+            .unwrap();
+        let mut replacer = ac.replacer(&replacements).unwrap();
+        let mut expected = replacer.replace(&haystack).unwrap().to_vec();
+        expected.extend_from_slice(replacer.finish().unwrap());
+
+        let mut pieces = vec![];
+        let mut rest = &haystack[..];
+        while !rest.is_empty() {
+            let (piece, next) = rest.split_at(1 + below(rest.len() as u64));
+            pieces.push(piece.to_vec());
+            rest = next;
+        }
+
+        let mut rdr = ac
+            .replacer(&replacements)
+            .unwrap()
+            .reader(Pieces(pieces.clone(), false));
+        let mut out = vec![];
+        let mut buf = [0; 3];
+        loop {
+            let n = rdr.read(&mut buf[..1 + below(3)]).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(0, rdr.read(&mut buf).unwrap());
+        assert_eq!(
+            expected, out,
+            "patterns {:?}, haystack {:?}",
+            patterns, haystack
+        );
+
+        let mut wtr = ac.replacer(&replacements).unwrap().writer(vec![]);
+        for piece in pieces.iter() {
+            wtr.write_all(piece).unwrap();
+            if below(2) == 0 {
+                wtr.flush().unwrap();
+            }
+        }
+        assert_eq!(expected, wtr.into_inner().unwrap());
+    }
+
+    // The bytes held back are written when the writer is dropped, and a
+    // finished writer starts a new stream.
+    let ac = AhoCorasick::new(["abcd", "bce"]).unwrap();
+    let mut out = vec![];
+    let mut wtr = ac.replacer(["X", "Y"]).unwrap().writer(&mut out);
+    wtr.write_all(b"abcabcab").unwrap();
+    wtr.flush().unwrap();
+    assert_eq!(b"abcabc", &wtr.get_ref()[..]);
+    wtr.write_all(b"cd ab").unwrap();
+    wtr.finish().unwrap();
+    wtr.write_all(b"cd ab").unwrap();
+    drop(wtr);
+    assert_eq!(b"abcabcX abcd ab", &out[..]);
+}
+
+#[test]
+fn stream_finder_keeps_matches_of_stream() {
+    use crate::AhoCorasickKind;
+
+    let mut seed: u64 = 0xD1B54A32D192ED03;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n) as usize
+    };
+    for _ in 0..300 {
+        let count = 1 + below(6);
+        let mut word = |min: usize, max: usize| -> Vec<u8> {
+            let len = min + below((max - min) as u64 + 1);
+            (0..len).map(|_| b"abc "[below(4)]).collect()
+        };
+        let patterns: Vec<Vec<u8>> = (0..count).map(|_| word(1, 4)).collect();
+        let haystack = word(0, 40);
+        let ac = AhoCorasick::builder()
+            .kind(Some(
+                [
+                    AhoCorasickKind::NoncontiguousNFA,
+                    AhoCorasickKind::ContiguousNFA,
+                    AhoCorasickKind::DFA,
+                ][below(3)],
+            ))
+            .build(&patterns)
+            .unwrap();
+        let expected: Vec<Match> = ac.find_iter(&haystack).collect();
+
+        let mut finder = ac.stream_finder().unwrap();
+        let mut matches = vec![];
+        let mut rest = &haystack[..];
+        while !rest.is_empty() {
+            let (chunk, next) = rest.split_at(1 + below(rest.len() as u64));
+            let at = finder.position();
+            for m in finder.find(chunk) {
+                assert!(at < m.end() && m.end() <= at + chunk.len());
+                matches.push(*m);
+            }
+            rest = next;
+        }
+        assert_eq!(haystack.len(), finder.position());
+        assert!(finder.finish().is_empty());
+        assert_eq!(0, finder.position());
+        assert_eq!(
+            expected, matches,
+            "patterns {:?}, haystack {:?}",
+            patterns, haystack,
+        );
+    }
+
+    // The same restrictions as for stream searches apply.
+    let ac = AhoCorasick::new(["a", ""]).unwrap();
+    assert!(ac.stream_finder().is_err());
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostFirst)
+        .build(["a"])
+        .unwrap();
+    assert!(ac.stream_finder().is_err());
+    let ac = AhoCorasick::builder()
+        .start_kind(crate::StartKind::Anchored)
+        .build(["a"])
+        .unwrap();
+    assert!(ac.stream_finder().is_err());
+}
+
+#[test]
+fn replacer_stats_count_replacements() {
+    use crate::{PatternID, ReplaceStats};
+
+    let mut seed: u64 = 0xA0761D6478BD642F;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n) as usize
+    };
+    for _ in 0..300 {
+        let count = 1 + below(6);
+        let mut word = |max: usize| -> Vec<u8> {
+            let len = below(max as u64 + 1);
+            (0..len).map(|_| b"abc "[below(4)]).collect()
+        };
+        let patterns: Vec<Vec<u8>> = (0..count).map(|_| word(4)).collect();
+        let replacements: Vec<Vec<u8>> =
+            patterns.iter().map(|_| word(3)).collect();
+        let haystack = word(40);
+        let match_kind = if below(2) == 0 {
+            MatchKind::Standard
+        } else {
+            MatchKind::LeftmostLongest
+        };
+        let ac = AhoCorasick::builder()
+            .match_kind(match_kind)
+            .build(&patterns)
+            .unwrap();
+        let mut expected = vec![0; count];
+        for m in ac.find_iter(&haystack) {
+            expected[m.pattern()] += 1;
+        }
+
+        let mut replacer = ac.replacer(&replacements).unwrap();
+        let mut rest = &haystack[..];
+        while !rest.is_empty() {
+            let (chunk, next) = rest.split_at(1 + below(rest.len() as u64));
+            replacer.replace(chunk).unwrap();
+            rest = next;
+        }
+        replacer.finish().unwrap();
+        let stats = replacer.stats();
+        let counts: Vec<u64> =
+            PatternID::iter(count).map(|pid| stats.count(pid)).collect();
+        assert_eq!(
+            expected, counts,
+            "patterns {:?}, haystack {:?}",
+            patterns, haystack,
+        );
+        assert_eq!(expected.iter().sum::<u64>(), stats.total());
+        assert!(stats.iter().all(|(pid, n)| n > 0 && expected[pid] == n));
+    }
+
+    // A run of matches replaced once is counted once, and a reset clears
+    // the counts.
+    let ac = AhoCorasick::new(["ab", "c"]).unwrap();
+    let mut config = AhoCorasickReplacerBuilder::new();
+    config.coalesce(true);
+    let mut replacer = ac.replacer_with(&config, ["X", "Y"]).unwrap();
+    assert_eq!(b"X Y", replacer.replace(b"abcab c").unwrap());
+    assert_eq!(2, replacer.stats().total());
+    assert_eq!(1, replacer.stats().count(PatternID::must(0)));
+    replacer.reset();
+    assert_eq!(ReplaceStats::default(), *replacer.stats());
+}
+
+#[test]
+fn replacer_borrowed_keeps_output_of_stream() {
+    use crate::{AhoCorasickKind, PendingLimit};
+
+    let mut seed: u64 = 0xE7037ED1A0B428DB;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n) as usize
+    };
+    for _ in 0..300 {
+        let count = 1 + below(6);
+        let mut word = |max: usize| -> Vec<u8> {
+            let len = below(max as u64 + 1);
+            (0..len).map(|_| b"abcd "[below(5)]).collect()
+        };
+        let patterns: Vec<Vec<u8>> = (0..count).map(|_| word(4)).collect();
+        let replacements: Vec<Vec<u8>> =
+            patterns.iter().map(|_| word(3)).collect();
+        let haystack = word(60);
+        let mut builder = AhoCorasick::builder();
+        builder.kind(Some(
+            [
+                AhoCorasickKind::NoncontiguousNFA,
+                AhoCorasickKind::ContiguousNFA,
+                AhoCorasickKind::DFA,
+            ][below(3)],
+        ));
+        match below(3) {
+            0 => builder.match_kind(MatchKind::LeftmostLongest),
+            1 => builder.word_boundary(true),
+            _ => &mut builder,
+        };
+        let ac = builder.build(&patterns).unwrap();
+        let mut config = AhoCorasickReplacerBuilder::new();
+        config.coalesce(below(2) == 0);
+        let mut expected = match ac.replacer_with(&config, &replacements) {
+            Ok(replacer) => replacer,
+            // Not every configuration is supported with every automaton.
+            Err(_) => continue,
+        };
+        let mut replacer = ac.replacer_with(&config, &replacements).unwrap();
+        let mut rest = &haystack[..];
+        while !rest.is_empty() {
+            let (chunk, next) = rest.split_at(1 + below(rest.len() as u64));
+            let want = expected.replace(chunk).unwrap().to_vec();
+            let out = replacer.replace_borrowed(chunk).unwrap();
+            assert_eq!(
+                want, out,
+                "patterns {:?}, haystack {:?}, chunk {:?}",
+                patterns, haystack, chunk,
+            );
+            rest = next;
+        }
+        assert_eq!(expected.finish().unwrap(), replacer.finish().unwrap());
+    }
+
+    // Only the output that isn't a prefix of the chunk is copied.
+    let ac = AhoCorasick::new(["abcd", "bce"]).unwrap();
+    let mut replacer = ac.replacer(["X", "Y"]).unwrap();
+    let chunk = b"ab ab";
+    let out = replacer.replace_borrowed(chunk).unwrap();
+    assert_eq!((b"ab ".as_ref(), chunk.as_ptr()), (out, out.as_ptr()));
+    let chunk = b"cd ab";
+    let out = replacer.replace_borrowed(chunk).unwrap();
+    assert_eq!(b"X ", out);
+    assert_ne!(chunk.as_ptr(), out.as_ptr());
+
+    // The output borrowed from a chunk isn't lost when the bytes held back
+    // exceed their limit.
+    let ac = AhoCorasick::new(["0123456789"]).unwrap();
+    let mut config = AhoCorasickReplacerBuilder::new();
+    config.max_pending_bytes(Some(PendingLimit::Error(4)));
+    let mut replacer = ac.replacer_with(&config, ["X"]).unwrap();
+    assert!(replacer.replace_borrowed(b"x01234").is_err());
+    assert_eq!(b"x01234", replacer.finish().unwrap());
+}
+
+#[test]
+fn replacer_prefilter_keeps_output_of_stream() {
+    use crate::MatchDeduper;
+
+    let mut seed: u64 = 0x8EBC6AF09C88C6E3;
+    let mut below = |n: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n) as usize
+    };
+    for _ in 0..300 {
+        let count = 1 + below(4);
+        let mut word = |min: usize, max: usize| -> Vec<u8> {
+            let len = min + below((max - min) as u64 + 1);
+            (0..len).map(|_| b"abcdefgh xyzAB"[below(14)]).collect()
+        };
+        let patterns: Vec<Vec<u8>> = (0..count).map(|_| word(1, 5)).collect();
+        let replacements: Vec<Vec<u8>> =
+            patterns.iter().map(|_| word(0, 3)).collect();
+        let haystack = word(0, 200);
+        let mut builder = AhoCorasick::builder();
+        builder.ascii_case_insensitive(below(3) == 0);
+        if below(3) == 0 {
+            builder.word_boundary(true);
+        }
+        let mut config = AhoCorasickReplacerBuilder::new();
+        config.coalesce(below(2) == 0).replacement_context(below(3) == 0);
+        if below(3) == 0 {
+            config.dedup(Some(MatchDeduper::new(Some(1 + below(8)))));
+        }
+        let mut outputs = vec![];
+        for &prefilter in [false, true].iter() {
+            let ac = builder.prefilter(prefilter).build(&patterns).unwrap();
+            let mut replacer = match ac.replacer_with(&config, &replacements) {
+                Ok(replacer) => replacer,
+                Err(_) => break,
+            };
+            let mut out = vec![];
+            let mut rest = &haystack[..];
+            while !rest.is_empty() {
+                let len = 1 + below(rest.len() as u64);
+                let (chunk, next) = rest.split_at(len);
+                out.extend_from_slice(replacer.replace(chunk).unwrap());
+                rest = next;
+            }
+            out.extend_from_slice(replacer.finish().unwrap());
+            outputs.push(out);
+        }
+        if outputs.len() == 2 {
+            assert_eq!(
+                outputs[0], outputs[1],
+                "patterns {:?}, haystack {:?}",
+                patterns, haystack,
+            );
+        }
+    }
+}
+
+#[cfg(all(feature = "async", feature = "std"))]
+#[test]
+fn replacer_replaces_chunks_on_blocking_threads() {
+    use std::future::Future;
+
+    use futures::{channel::oneshot, executor::block_on};
+
+    use crate::MemoryLimits;
+
+    fn spawn<T: Send + 'static>(
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> impl Future<Output = T> {
+        let (tx, rx) = oneshot::channel();
+        std::thread::spawn(move || tx.send(job()));
+        async { rx.await.unwrap() }
+    }
+
+    let ac = AhoCorasick::new(["ab", "bcd", "x"]).unwrap();
+    let haystack = b"abcd xbcd abx ab";
+    let expected = ac.replace_all_bytes(haystack, &["1", "2", "3"]);
+    for chunk_size in 1..=haystack.len() {
+        let mut replacer = ac.replacer(["1", "2", "3"]).unwrap();
+        let got = block_on(async move {
+            let mut out = vec![];
+            for chunk in haystack.chunks(chunk_size) {
+                let (back, output) = replacer
+                    .replace_chunk_blocking(chunk.to_vec(), spawn)
+                    .await;
+                replacer = back;
+                out.extend(output.unwrap());
+            }
+            out.extend_from_slice(replacer.finish().unwrap());
+            out
+        });
+        assert_eq!(expected, got, "chunk size {}", chunk_size);
+    }
+
+    // The replacer comes back when replacing fails.
+    let mut builder = AhoCorasickReplacerBuilder::new();
+    builder.memory_limits(Some(MemoryLimits::new().output(2)));
+    let replacer = ac.replacer_with(&builder, ["111", "2", "3"]).unwrap();
+    let (replacer, output) =
+        block_on(replacer.replace_chunk_blocking(b"ab".to_vec(), spawn));
+    assert!(output.is_err());
+    assert!(replacer.is_poisoned());
+}
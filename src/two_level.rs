@@ -0,0 +1,704 @@
+/*!
+Provides a searcher that routes each position of a haystack to a small
+automaton, according to the first few bytes at that position.
+
+A [`TwoLevelSearcher`] is built with
+[`AhoCorasickBuilder::build_two_level`](crate::AhoCorasickBuilder::build_two_level).
+Every pattern that is at least `k` bytes long is put into a bucket according
+to its first `k` bytes, and each bucket is an independent anchored
+[`AhoCorasick`] automaton built with the same configuration. Patterns shorter
+than `k` bytes, including empty patterns, are put into one more automaton,
+which is searched like an ordinary automaton.
+
+The buckets are found through a hash table keyed on `k` bytes. A search looks
+up the `k` bytes at each position of the haystack, and when a bucket has
+that prefix, runs an anchored search of the bucket's automaton at that
+position. This is meant for dictionaries with millions of patterns that share
+few distinct prefixes. A single automaton for such a dictionary is far too big
+to stay in cache, while the automaton of a bucket is small, and only the
+buckets whose prefixes occur in the haystack are ever touched. For smaller
+dictionaries, a single automaton is usually faster, since it never looks at a
+byte of the haystack more than once.
+
+Since a search switches between automata at every position, a two-level
+searcher can't be driven through the [`Automaton`](crate::automaton::Automaton)
+trait, and is thus a searcher of its own rather than another
+[`AhoCorasickKind`](crate::AhoCorasickKind). The kind of the automaton of each
+bucket is still chosen by the builder.
+
+# Match semantics
+
+A two-level searcher reports exactly the same matches as an [`AhoCorasick`]
+automaton built from all of the patterns with the same configuration. The
+match found at each position and the match of the short patterns are combined
+in the same way as the matches of the shards of a
+[`ShardedSearcher`](crate::sharded::ShardedSearcher), and with the same
+exception for leftmost match semantics with an empty pattern. See the
+[`sharded`](crate::sharded) module for details.
+
+Only non-overlapping searches are supported. An [`Input`] configured to stop
+at the earliest match is still guaranteed to find a match if there is one,
+but which match it finds may differ from a single automaton. A search
+restricted to a [`PatternSet`](crate::PatternSet) translates the set to each
+bucket it consults at every position, which takes time proportional to the
+number of patterns of the bucket.
+
+# Example
+
+```
+use aho_corasick::{AhoCorasick, MatchKind};
+
+let patterns = &["Samwise", "Sam", "Samuel", "Frodo", "Fredegar", "Pip"];
+let haystack = "Pippin and Fredegar followed Frodo, but Samwise was first.";
+
+let mut builder = AhoCorasick::builder();
+builder.match_kind(MatchKind::LeftmostFirst);
+let two_level = builder.build_two_level(patterns, 4)?;
+// 'Sam' and 'Pip' are shorter than 4 bytes, and 'Samwise' and 'Samuel' are
+// in different buckets.
+assert_eq!(4, two_level.buckets_len());
+
+let single = builder.build(patterns)?;
+assert_eq!(
+    single.find_iter(haystack).collect::<Vec<_>>(),
+    two_level.find_iter(haystack).collect::<Vec<_>>(),
+);
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use crate::{
+    ahocorasick::{
+        enforce_anchored_consistency, AhoCorasick, AhoCorasickBuilder,
+    },
+    sharded::leftmost,
+    util::{
+        error::{BuildError, MatchError},
+        pattern_set::PatternSet,
+        primitives::PatternID,
+        search::{Anchored, Input, Match, MatchKind, StartKind},
+    },
+};
+
+/// A searcher whose patterns are grouped into small automata by their first
+/// few bytes.
+///
+/// See the [module documentation](self) for more details about how a
+/// searcher is built and the semantics of the matches it reports.
+#[derive(Clone, Debug)]
+pub struct TwoLevelSearcher {
+    /// The number of bytes that patterns are grouped by.
+    prefix_len: usize,
+    /// The buckets, in ascending order of their prefixes.
+    buckets: Vec<Bucket>,
+    /// An open addressing hash table of the index of each bucket, whose
+    /// length is a power of two. There is always at least one empty slot.
+    slots: Vec<Option<usize>>,
+    /// The automaton of the patterns shorter than the prefix length, if any.
+    short: Option<Bucket>,
+    /// The byte that each haystack byte is compared to the prefixes as,
+    /// after applying the translation table and folding its case.
+    fold: [u8; 256],
+    /// Whether any prefix starts with each folded byte.
+    first: [bool; 256],
+    start_kind: StartKind,
+    match_kind: MatchKind,
+    patterns_len: usize,
+}
+
+/// The automaton of a group of patterns of a two-level searcher.
+#[derive(Clone, Debug)]
+struct Bucket {
+    /// The folded prefix shared by every pattern of this bucket, which is
+    /// empty for the automaton of the short patterns.
+    prefix: Vec<u8>,
+    ac: AhoCorasick,
+    /// The ID of each pattern of this bucket in the two-level searcher.
+    /// Pattern `i` in `ac` corresponds to `patterns[i]`.
+    patterns: Vec<PatternID>,
+}
+
+impl TwoLevelSearcher {
+    /// Build a searcher that groups patterns by their first `prefix_len`
+    /// bytes, where the automaton of each group is configured by the given
+    /// builder.
+    ///
+    /// With ASCII case insensitivity, prefixes are compared after folding
+    /// ASCII letters to lowercase, since patterns starting with either case
+    /// match the same haystacks.
+    pub(crate) fn build<I, P>(
+        builder: &AhoCorasickBuilder,
+        patterns: I,
+        prefix_len: usize,
+    ) -> Result<TwoLevelSearcher, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let prefix_len = core::cmp::max(1, prefix_len);
+        let ascii_case_insensitive = builder.get_ascii_case_insensitive();
+        let key = |byte: u8| {
+            if ascii_case_insensitive {
+                byte.to_ascii_lowercase()
+            } else {
+                byte
+            }
+        };
+        let patterns: Vec<P> = patterns.into_iter().collect();
+        let mut short = vec![];
+        let mut groups: BTreeMap<Vec<u8>, Vec<PatternID>> = BTreeMap::new();
+        for (pid, p) in PatternID::iter(patterns.len()).zip(patterns.iter()) {
+            let p = p.as_ref();
+            if p.len() < prefix_len {
+                short.push(pid);
+            } else {
+                let prefix = p[..prefix_len].iter().map(|&b| key(b));
+                groups.entry(prefix.collect()).or_default().push(pid);
+            }
+        }
+
+        let build = |pids: &[PatternID], anchored: bool| {
+            // Pattern IDs are local to each bucket, and so are lookaheads.
+            let mut bucket_builder = builder
+                .for_pattern_subset(pids)
+                .unwrap_or_else(|| builder.clone());
+            if anchored {
+                bucket_builder.start_kind(StartKind::Anchored);
+            }
+            bucket_builder
+                .build(
+                    pids.iter().map(|pid| patterns[pid.as_usize()].as_ref()),
+                )
+                .map_err(|err| err.map_pattern_ids(|pid| pids[pid]))
+        };
+        let short = if short.is_empty() {
+            None
+        } else {
+            let ac = build(&short, false)?;
+            Some(Bucket { prefix: vec![], ac, patterns: short })
+        };
+        let mut buckets = Vec::with_capacity(groups.len());
+        let mut first = [false; 256];
+        for (prefix, pids) in groups {
+            let ac = build(&pids, true)?;
+            first[usize::from(prefix[0])] = true;
+            buckets.push(Bucket { prefix, ac, patterns: pids });
+        }
+
+        let mut slots =
+            vec![None; buckets.len().saturating_mul(2).next_power_of_two()];
+        let mask = slots.len() - 1;
+        for (index, bucket) in buckets.iter().enumerate() {
+            let mut slot = hash(bucket.prefix.iter().copied()) & mask;
+            while slots[slot].is_some() {
+                slot = (slot + 1) & mask;
+            }
+            slots[slot] = Some(index);
+        }
+        let mut fold = [0u8; 256];
+        // A haystack byte is matched against the patterns as translated by
+        // the translation table, if any.
+        let table = builder.get_translation_table();
+        for b in 0..=255u8 {
+            let t = table.map_or(b, |table| table[usize::from(b)]);
+            fold[usize::from(b)] = key(t);
+        }
+        Ok(TwoLevelSearcher {
+            prefix_len,
+            buckets,
+            slots,
+            short,
+            fold,
+            first,
+            start_kind: builder.get_start_kind(),
+            match_kind: builder.get_match_kind(),
+            patterns_len: patterns.len(),
+        })
+    }
+
+    /// Returns the number of bytes that patterns are grouped by.
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// Returns the number of buckets in this searcher. This doesn't count
+    /// the automaton of the patterns shorter than the prefix length.
+    pub fn buckets_len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Returns the prefix shared by the patterns of the bucket at the given
+    /// index. With ASCII case insensitivity, its letters are lowercase.
+    ///
+    /// Buckets are in ascending order of their prefixes.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the index is not less than
+    /// [`TwoLevelSearcher::buckets_len`].
+    pub fn bucket_prefix(&self, index: usize) -> &[u8] {
+        &self.buckets[index].prefix
+    }
+
+    /// Returns the anchored automaton of the bucket at the given index.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the index is not less than
+    /// [`TwoLevelSearcher::buckets_len`].
+    pub fn bucket(&self, index: usize) -> &AhoCorasick {
+        &self.buckets[index].ac
+    }
+
+    /// Returns the IDs, in this searcher, of the patterns of the bucket at
+    /// the given index. Pattern `i` of the bucket's automaton has the ID at
+    /// index `i`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the index is not less than
+    /// [`TwoLevelSearcher::buckets_len`].
+    pub fn bucket_patterns(&self, index: usize) -> &[PatternID] {
+        &self.buckets[index].patterns
+    }
+
+    /// Returns the IDs of the patterns shorter than the prefix length, which
+    /// are searched by an automaton of their own.
+    pub fn short_patterns(&self) -> &[PatternID] {
+        self.short.as_ref().map_or(&[], |short| &short.patterns)
+    }
+
+    /// Returns the total number of patterns in this searcher.
+    pub fn patterns_len(&self) -> usize {
+        self.patterns_len
+    }
+
+    /// Returns the match semantics used by this searcher.
+    pub fn match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    /// Returns the approximate total amount of heap used by this searcher,
+    /// including the automata of all buckets, in units of bytes.
+    pub fn memory_usage(&self) -> usize {
+        let bucket = |b: &Bucket| {
+            b.ac.memory_usage()
+                + b.prefix.len()
+                + b.patterns.len() * core::mem::size_of::<PatternID>()
+        };
+        self.buckets.iter().map(bucket).sum::<usize>()
+            + self.short.as_ref().map_or(0, bucket)
+            + self.buckets.len() * core::mem::size_of::<Bucket>()
+            + self.slots.len() * core::mem::size_of::<Option<usize>>()
+    }
+
+    /// Returns true if and only if any pattern matches the given haystack.
+    ///
+    /// # Panics
+    ///
+    /// This panics when the search is not supported by the configuration of
+    /// this searcher, such as an anchored search when the searcher was built
+    /// without support for them. Use [`TwoLevelSearcher::try_find`] to
+    /// handle these errors.
+    pub fn is_match<'h, I: Into<Input<'h>>>(&self, input: I) -> bool {
+        let input = input.into().earliest(true);
+        self.try_find(input).unwrap().is_some()
+    }
+
+    /// Returns the location of the first match according to the match
+    /// semantics of this searcher.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`TwoLevelSearcher::is_match`].
+    pub fn find<'h, I: Into<Input<'h>>>(&self, input: I) -> Option<Match> {
+        self.try_find(input).unwrap()
+    }
+
+    /// Returns an iterator of non-overlapping matches, using the match
+    /// semantics of this searcher.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`TwoLevelSearcher::is_match`].
+    pub fn find_iter<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> FindIter<'a, 'h> {
+        self.try_find_iter(input).unwrap()
+    }
+
+    /// Returns the location of the first match according to the match
+    /// semantics of this searcher.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when the search is not supported by the
+    /// configuration of this searcher, in the same way as
+    /// [`AhoCorasick::try_find`].
+    pub fn try_find<'h, I: Into<Input<'h>>>(
+        &self,
+        input: I,
+    ) -> Result<Option<Match>, MatchError> {
+        let input = input.into();
+        enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
+        if input.is_done() {
+            return Ok(None);
+        }
+        let short = self.search_short(&input)?;
+        self.search_buckets(&input, short)
+    }
+
+    /// Returns an iterator of non-overlapping matches, using the match
+    /// semantics of this searcher.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`TwoLevelSearcher::try_find`].
+    pub fn try_find_iter<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> Result<FindIter<'a, 'h>, MatchError> {
+        let input = input.into();
+        // This ensures that searches with this configuration never fail, so
+        // that the iterator never has to report an error.
+        enforce_anchored_consistency(self.start_kind, input.get_anchored())?;
+        Ok(FindIter {
+            searcher: self,
+            input,
+            last_match_end: None,
+            short: None,
+        })
+    }
+
+    /// Search the automaton of the short patterns, if any.
+    fn search_short(
+        &self,
+        input: &Input<'_>,
+    ) -> Result<Option<Match>, MatchError> {
+        match self.short {
+            None => Ok(None),
+            Some(ref short) => search_bucket(short, input),
+        }
+    }
+
+    /// Search the bucket of each position of the given input, and return
+    /// whichever of their matches and the given match of the short patterns
+    /// would be reported first by a single automaton.
+    ///
+    /// The search stops at the first position where no match of a bucket
+    /// could be reported before the best match found so far.
+    fn search_buckets(
+        &self,
+        input: &Input<'_>,
+        mut best: Option<Match>,
+    ) -> Result<Option<Match>, MatchError> {
+        let span = input.get_span();
+        if span.end - span.start < self.prefix_len {
+            return Ok(best);
+        }
+        let last = if input.get_anchored().is_anchored() {
+            span.start
+        } else {
+            span.end - self.prefix_len
+        };
+        let haystack = input.haystack();
+        let mut at_input = input.clone().anchored(Anchored::Yes);
+        for at in span.start..=last {
+            if let Some(m) = best {
+                let past = match self.match_kind {
+                    MatchKind::Standard => at + self.prefix_len > m.end(),
+                    _ => at > m.start(),
+                };
+                if past {
+                    break;
+                }
+            }
+            let bucket = match self.bucket_at(haystack, at) {
+                None => continue,
+                Some(bucket) => bucket,
+            };
+            at_input.set_start(at);
+            let m = search_bucket(bucket, &at_input)?;
+            best = leftmost(self.match_kind, best, m);
+        }
+        Ok(best)
+    }
+
+    /// Returns the bucket whose prefix is at the given position of the
+    /// haystack, if any. There must be at least as many bytes as the prefix
+    /// length at that position.
+    #[inline]
+    fn bucket_at(&self, haystack: &[u8], at: usize) -> Option<&Bucket> {
+        let bytes = &haystack[at..at + self.prefix_len];
+        if !self.first[usize::from(self.fold[usize::from(bytes[0])])] {
+            return None;
+        }
+        let folded = bytes.iter().map(|&b| self.fold[usize::from(b)]);
+        let mask = self.slots.len() - 1;
+        let mut slot = hash(folded.clone()) & mask;
+        while let Some(index) = self.slots[slot] {
+            let bucket = &self.buckets[index];
+            if folded.clone().eq(bucket.prefix.iter().copied()) {
+                return Some(bucket);
+            }
+            slot = (slot + 1) & mask;
+        }
+        None
+    }
+}
+
+/// Search the given bucket, and return its match with the ID of its pattern
+/// in the two-level searcher.
+fn search_bucket(
+    bucket: &Bucket,
+    input: &Input<'_>,
+) -> Result<Option<Match>, MatchError> {
+    // A set of patterns refers to the IDs in the two-level searcher, which
+    // have to be translated to the IDs in the bucket.
+    let set: Option<PatternSet> = input.get_pattern_set().map(|set| {
+        PatternID::iter(bucket.patterns.len())
+            .filter(|&pid| set.contains(bucket.patterns[pid]))
+            .collect()
+    });
+    let mut input = input.clone();
+    if set.is_some() {
+        input.set_pattern_set(set.as_ref());
+    }
+    let m = bucket.ac.try_find(input)?;
+    Ok(m.map(|m| Match::new(bucket.patterns[m.pattern()], m.span())))
+}
+
+/// Returns the FNV-1a hash of the given bytes.
+#[inline]
+fn hash<I: Iterator<Item = u8>>(bytes: I) -> usize {
+    const INIT: u64 = 0xCBF29CE484222325;
+    const PRIME: u64 = 0x100000001B3;
+
+    let h = bytes.fold(INIT, |h, b| (h ^ u64::from(b)).wrapping_mul(PRIME));
+    h as usize
+}
+
+/// An iterator of non-overlapping matches of a [`TwoLevelSearcher`].
+///
+/// This iterator is constructed via [`TwoLevelSearcher::find_iter`] or
+/// [`TwoLevelSearcher::try_find_iter`].
+///
+/// The lifetime `'a` refers to the lifetime of the searcher, and the
+/// lifetime `'h` refers to the lifetime of the haystack being searched.
+#[derive(Debug)]
+pub struct FindIter<'a, 'h> {
+    searcher: &'a TwoLevelSearcher,
+    /// The input parameters to give to each search call. The start position
+    /// of the search is mutated during iteration.
+    input: Input<'h>,
+    /// The end offset of the most recent match, which is used to prevent
+    /// empty matches from overlapping with the end of a prior match.
+    last_match_end: Option<usize>,
+    /// For an unanchored search, the last result of the automaton of the
+    /// short patterns. It's only recomputed once the search moves past the
+    /// start of its match, in the same way as the results of the shards of
+    /// a sharded searcher.
+    short: Option<Option<Match>>,
+}
+
+impl<'a, 'h> FindIter<'a, 'h> {
+    /// Executes a search from the current start position, and returns a
+    /// match if one is found. This does not advance the input.
+    fn search(&mut self) -> Option<Match> {
+        // The unwraps are OK here because we check at iterator construction
+        // time that no search with the same configuration returns an error.
+        if self.input.get_anchored().is_anchored() {
+            return self.searcher.try_find(self.input.clone()).unwrap();
+        }
+        let short = match self.short {
+            Some(Some(m)) if m.start() >= self.input.start() => Some(m),
+            Some(None) => None,
+            _ => {
+                let m = self.searcher.search_short(&self.input).unwrap();
+                self.short = Some(m);
+                m
+            }
+        };
+        self.searcher.search_buckets(&self.input, short).unwrap()
+    }
+}
+
+impl<'a, 'h> Iterator for FindIter<'a, 'h> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        if self.input.is_done() {
+            return None;
+        }
+        let mut m = self.search()?;
+        if m.is_empty() && Some(m.end()) == self.last_match_end {
+            // As with AhoCorasick::find_iter, an empty match may not overlap
+            // with the end of the previous match.
+            self.input.set_start(self.input.start().checked_add(1).unwrap());
+            if self.input.is_done() {
+                return None;
+            }
+            m = self.search()?;
+        }
+        self.input.set_start(m.end());
+        self.last_match_end = Some(m.end());
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        util::rng::Rng, AhoCorasick, Anchored, Input, Match, MatchKind,
+        PatternID, PatternSet, StartKind,
+    };
+
+    #[test]
+    fn same_matches_as_single_automaton() {
+        let mut rng = Rng::new(0x2545F4914F6CDD1D);
+        for _ in 0..300 {
+            let patterns: Vec<Vec<u8>> = (0..1 + rng.below(8))
+                .map(|_| rng.word(0, 5, b"abcAB"))
+                .collect();
+            let haystack = rng.word(0, 30, b"abcAB");
+            let prefix_len = rng.below(4);
+            let set: PatternSet = PatternID::iter(patterns.len())
+                .filter(|_| rng.below(2) == 0)
+                .collect();
+            for match_kind in [
+                MatchKind::Standard,
+                MatchKind::LeftmostFirst,
+                MatchKind::LeftmostLongest,
+            ] {
+                for ascii_case_insensitive in [false, true] {
+                    // See the module documentation about leftmost semantics
+                    // with empty patterns.
+                    if match_kind != MatchKind::Standard
+                        && patterns.iter().any(|p| p.is_empty())
+                    {
+                        continue;
+                    }
+                    let mut builder = AhoCorasick::builder();
+                    builder
+                        .match_kind(match_kind)
+                        .start_kind(StartKind::Both)
+                        .ascii_case_insensitive(ascii_case_insensitive)
+                        .store_patterns(true);
+                    let single = builder.build(&patterns).unwrap();
+                    let two_level = builder
+                        .build_two_level(&patterns, prefix_len)
+                        .unwrap();
+                    for anchored in [Anchored::No, Anchored::Yes] {
+                        let at = core::cmp::min(3, haystack.len());
+                        let input = Input::new(&haystack).anchored(anchored);
+                        let msg = (&patterns, &haystack, match_kind, anchored);
+                        assert_eq!(
+                            single
+                                .find_iter(input.clone())
+                                .collect::<Vec<_>>(),
+                            two_level
+                                .find_iter(input.clone())
+                                .collect::<Vec<_>>(),
+                            "{:?}",
+                            msg,
+                        );
+                        assert_eq!(
+                            single.find(input.clone().range(at..)),
+                            two_level.find(input.clone().range(at..)),
+                            "{:?}",
+                            msg,
+                        );
+                        assert_eq!(
+                            single.is_match(input.clone()),
+                            two_level.is_match(input.clone()),
+                            "{:?}",
+                            msg,
+                        );
+                        let input = input.pattern_set(Some(&set));
+                        assert_eq!(
+                            single
+                                .find_iter(input.clone())
+                                .collect::<Vec<_>>(),
+                            two_level.find_iter(input).collect::<Vec<_>>(),
+                            "{:?}",
+                            msg,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn buckets_by_prefix() {
+        let patterns = ["abc", "ABd", "ab", "b", "", "bcd", "abx"];
+        let two_level = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build_two_level(patterns, 2)
+            .unwrap();
+        assert_eq!(2, two_level.prefix_len());
+        assert_eq!(2, two_level.buckets_len());
+        assert_eq!(b"ab", two_level.bucket_prefix(0));
+        assert_eq!(b"bc", two_level.bucket_prefix(1));
+        let pids = |pids: &[PatternID]| -> Vec<usize> {
+            pids.iter().map(|p| p.as_usize()).collect()
+        };
+        assert_eq!(vec![0, 1, 2, 6], pids(two_level.bucket_patterns(0)));
+        assert_eq!(vec![5], pids(two_level.bucket_patterns(1)));
+        assert_eq!(vec![3, 4], pids(two_level.short_patterns()));
+        assert_eq!(7, two_level.patterns_len());
+        assert_eq!(StartKind::Anchored, two_level.bucket(0).start_kind());
+
+        let two_level =
+            AhoCorasick::builder().build_two_level(["a", "b"], 0).unwrap();
+        assert_eq!(1, two_level.prefix_len());
+        assert_eq!(2, two_level.buckets_len());
+        assert!(two_level.short_patterns().is_empty());
+        let two_level = AhoCorasick::builder()
+            .build_two_level(Vec::<&str>::new(), 3)
+            .unwrap();
+        assert_eq!(0, two_level.buckets_len());
+        assert_eq!(None, two_level.find("abc"));
+    }
+
+    #[test]
+    fn invalid_input() {
+        let two_level =
+            AhoCorasick::builder().build_two_level(["abc"], 2).unwrap();
+        let input = Input::new("abc").anchored(Anchored::Yes);
+        assert!(two_level.try_find(input.clone()).is_err());
+        assert!(two_level.try_find_iter(input).is_err());
+
+        let two_level = AhoCorasick::builder()
+            .start_kind(StartKind::Anchored)
+            .build_two_level(["abc"], 2)
+            .unwrap();
+        assert!(two_level.try_find("abc").is_err());
+        let input = Input::new("abc").anchored(Anchored::Yes);
+        assert_eq!(Some(Match::must(0, 0..3)), two_level.find(input));
+    }
+
+    #[test]
+    fn translated_haystack_bytes_select_buckets() {
+        let mut table = [0u8; 256];
+        for b in 0..=255u8 {
+            table[usize::from(b)] = if b == b'x' { b'b' } else { b };
+        }
+        let two_level = AhoCorasick::builder()
+            .translation_table(Some(table))
+            .build_two_level(["abc", "bac"], 2)
+            .unwrap();
+        assert_eq!(
+            vec![Match::must(1, 0..3), Match::must(0, 4..7)],
+            two_level.find_iter("xac axc").collect::<Vec<_>>(),
+        );
+    }
+}
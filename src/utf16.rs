@@ -0,0 +1,595 @@
+/*!
+Provides searches in haystacks encoded as UTF-16.
+
+A [`Utf16Searcher`] is built with
+[`AhoCorasickBuilder::build_utf16`](crate::AhoCorasickBuilder::build_utf16).
+Its patterns are given as `&str` and encoded as UTF-16, with the byte order
+of the haystacks it searches, before the automaton is built. A search then
+runs over the bytes of the haystack directly, without having to transcode it
+first, and every match offset is a byte offset into the UTF-16 haystack.
+
+Since the automaton works on bytes, its patterns may match at an odd offset,
+straddling two code units of the haystack. Such matches are never reported:
+a match must start on a code unit boundary, and since every pattern consists
+of whole code units, it then also ends on one. When a match is rejected, the
+searcher reports the match that it would have reported if the pattern didn't
+match there. A trailing odd byte of a haystack is never part of a match.
+
+# Byte order
+
+The byte order of a haystack is usually given by its byte order mark, which
+[`Utf16Endian::from_bom`] detects. The byte order mark itself is not
+skipped, which can be done by searching the haystack with
+[`Input::range`] starting at `2`. Haystacks in both byte orders require a
+searcher for each byte order.
+
+# ASCII case insensitivity
+
+With [`AhoCorasickBuilder::ascii_case_insensitive`], a match only differs
+from its pattern in the case of code units that are ASCII letters. A code
+unit of a haystack, such as `U+0141` (`Ł`), may have an ASCII letter as one
+of its bytes, but it never matches a pattern in another case.
+
+# Streams
+
+With the `std` feature, [`Utf16Searcher::try_stream_find_iter`] searches a
+UTF-16 stream. Its chunks may end on any byte, including in the middle of a
+code unit, since whether a match is aligned only depends on its offset in
+the entire stream.
+
+# Example
+
+```
+use aho_corasick::{utf16::Utf16Endian, AhoCorasick, PatternID};
+
+let mut haystack = vec![0xFF, 0xFE];
+haystack.extend("Ünïcode ODE".encode_utf16().flat_map(|u| u.to_le_bytes()));
+let endian = Utf16Endian::from_bom(&haystack).unwrap();
+assert_eq!(Utf16Endian::Little, endian);
+
+let searcher = AhoCorasick::builder()
+    .ascii_case_insensitive(true)
+    .build_utf16(&["ode", "ïc"], endian)?;
+let matches: Vec<(PatternID, usize, usize)> = searcher
+    .find_iter(&haystack)
+    .map(|m| (m.pattern(), m.start(), m.end()))
+    .collect();
+assert_eq!(vec![
+    (PatternID::must(1), 6, 10),
+    (PatternID::must(0), 10, 16),
+    (PatternID::must(0), 18, 24),
+], matches);
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+
+use alloc::vec::Vec;
+
+use crate::{
+    ahocorasick::{AhoCorasick, AhoCorasickBuilder, FindIter},
+    util::{
+        error::{BuildError, MatchError},
+        search::{Input, Match, MatchKind},
+    },
+};
+
+/// The byte order of the code units of a UTF-16 haystack.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Utf16Endian {
+    /// UTF-16LE, where the least significant byte of a code unit comes
+    /// first.
+    Little,
+    /// UTF-16BE, where the most significant byte of a code unit comes
+    /// first.
+    Big,
+}
+
+impl Utf16Endian {
+    /// Returns the byte order given by the byte order mark at the start of
+    /// the given haystack, if it starts with one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::utf16::Utf16Endian;
+    ///
+    /// assert_eq!(Some(Utf16Endian::Big), Utf16Endian::from_bom(b"\xFE\xFF"));
+    /// assert_eq!(None, Utf16Endian::from_bom(b"\xFF"));
+    /// ```
+    pub fn from_bom(haystack: &[u8]) -> Option<Utf16Endian> {
+        match haystack.get(..2)? {
+            [0xFF, 0xFE] => Some(Utf16Endian::Little),
+            [0xFE, 0xFF] => Some(Utf16Endian::Big),
+            _ => None,
+        }
+    }
+
+    /// Encode the given string as UTF-16 with this byte order.
+    pub(crate) fn encode(self, string: &str) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 * string.len());
+        for unit in string.encode_utf16() {
+            match self {
+                Utf16Endian::Little => bytes.extend(unit.to_le_bytes()),
+                Utf16Endian::Big => bytes.extend(unit.to_be_bytes()),
+            }
+        }
+        bytes
+    }
+
+    /// Returns the most significant byte of the given code unit.
+    fn high_byte(self, unit: &[u8]) -> u8 {
+        match self {
+            Utf16Endian::Little => unit[1],
+            Utf16Endian::Big => unit[0],
+        }
+    }
+}
+
+/// A searcher for haystacks encoded as UTF-16.
+///
+/// See the [module documentation](self) for more details about how a
+/// searcher is built and the matches it reports.
+#[derive(Clone, Debug)]
+pub struct Utf16Searcher {
+    ac: AhoCorasick,
+    endian: Utf16Endian,
+}
+
+impl Utf16Searcher {
+    /// Build a searcher for the given patterns encoded with the given byte
+    /// order, whose automaton is configured by the given builder.
+    ///
+    /// The patterns are always stored, since they're needed to check the
+    /// matches reported by the automaton.
+    pub(crate) fn build<I, P>(
+        builder: &AhoCorasickBuilder,
+        patterns: I,
+        endian: Utf16Endian,
+    ) -> Result<Utf16Searcher, BuildError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let ac = builder
+            .clone()
+            .store_patterns(true)
+            .build(patterns.into_iter().map(|p| endian.encode(p.as_ref())))?;
+        Ok(Utf16Searcher { ac, endian })
+    }
+
+    /// Returns the byte order of the haystacks searched by this searcher.
+    pub fn endian(&self) -> Utf16Endian {
+        self.endian
+    }
+
+    /// Returns the automaton of this searcher, whose patterns are encoded as
+    /// UTF-16.
+    ///
+    /// A search with this automaton may report matches that don't start on
+    /// a code unit boundary.
+    pub fn automaton(&self) -> &AhoCorasick {
+        &self.ac
+    }
+
+    /// Returns the total number of patterns in this searcher.
+    pub fn patterns_len(&self) -> usize {
+        self.ac.patterns_len()
+    }
+
+    /// Returns the match semantics used by this searcher.
+    pub fn match_kind(&self) -> MatchKind {
+        self.ac.match_kind()
+    }
+
+    /// Returns true if and only if any pattern matches the given haystack.
+    ///
+    /// # Panics
+    ///
+    /// This panics when the search is not supported by the configuration of
+    /// this searcher, such as an anchored search when the searcher was built
+    /// without support for them. Use [`Utf16Searcher::try_find`] to handle
+    /// these errors.
+    pub fn is_match<'h, I: Into<Input<'h>>>(&self, input: I) -> bool {
+        self.try_find(input).unwrap().is_some()
+    }
+
+    /// Returns the location of the first match according to the match
+    /// semantics of this searcher.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`Utf16Searcher::is_match`].
+    pub fn find<'h, I: Into<Input<'h>>>(&self, input: I) -> Option<Match> {
+        self.try_find(input).unwrap()
+    }
+
+    /// Returns an iterator of non-overlapping matches, using the match
+    /// semantics of this searcher.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`Utf16Searcher::is_match`].
+    pub fn find_iter<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> FindIter<'a, 'h> {
+        self.try_find_iter(input).unwrap()
+    }
+
+    /// Returns the location of the first match according to the match
+    /// semantics of this searcher.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error when the search is not supported by the
+    /// configuration of the automaton, in the same way as
+    /// [`AhoCorasick::try_find`].
+    pub fn try_find<'h, I: Into<Input<'h>>>(
+        &self,
+        input: I,
+    ) -> Result<Option<Match>, MatchError> {
+        self.ac.try_find_utf16(input.into(), self.endian)
+    }
+
+    /// Returns an iterator of non-overlapping matches, using the match
+    /// semantics of this searcher.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same circumstances as
+    /// [`Utf16Searcher::try_find`].
+    pub fn try_find_iter<'a, 'h, I: Into<Input<'h>>>(
+        &'a self,
+        input: I,
+    ) -> Result<FindIter<'a, 'h>, MatchError> {
+        self.ac.try_find_iter_utf16(input.into(), self.endian)
+    }
+
+    /// Returns an iterator of non-overlapping matches in the given UTF-16
+    /// stream.
+    ///
+    /// # Panics
+    ///
+    /// This panics in the same circumstances as
+    /// [`Utf16Searcher::try_stream_find_iter`].
+    #[cfg(feature = "std")]
+    pub fn stream_find_iter<'a, R: std::io::Read>(
+        &'a self,
+        rdr: R,
+    ) -> StreamFindIter<'a, R> {
+        self.try_stream_find_iter(rdr).unwrap()
+    }
+
+    /// Returns an iterator of non-overlapping matches in the given UTF-16
+    /// stream.
+    ///
+    /// The stream is read in chunks, which may end on any byte. The matches
+    /// are the same as those found by [`Utf16Searcher::find_iter`] in the
+    /// entire contents of the stream.
+    ///
+    /// # Errors
+    ///
+    /// As with [`AhoCorasick::try_stream_find_iter`], this returns an error
+    /// when this searcher doesn't use [`MatchKind::Standard`] semantics, or
+    /// doesn't support unanchored searches. It also returns an error when
+    /// any of the patterns is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{utf16::Utf16Endian, AhoCorasick, PatternID};
+    ///
+    /// let searcher = AhoCorasick::builder()
+    ///     .build_utf16(&["fox", "dog"], Utf16Endian::Big)?;
+    /// let haystack: Vec<u8> = "the quick fox"
+    ///     .encode_utf16()
+    ///     .flat_map(|u| u.to_be_bytes())
+    ///     .collect();
+    /// let matches: Vec<PatternID> = searcher
+    ///     .try_stream_find_iter(&haystack[..])?
+    ///     .map(|result| result.map(|m| m.pattern()))
+    ///     .collect::<Result<_, _>>()?;
+    /// assert_eq!(vec![PatternID::must(0)], matches);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn try_stream_find_iter<'a, R: std::io::Read>(
+        &'a self,
+        rdr: R,
+    ) -> Result<StreamFindIter<'a, R>, MatchError> {
+        StreamFindIter::new(self, rdr)
+    }
+}
+
+/// Returns true if a match of the given pattern on the given bytes, starting
+/// at the given offset of a haystack encoded with the given byte order, is
+/// aligned to the code units of the haystack.
+///
+/// The bytes matched must be equal to the pattern, except for code units
+/// that are both ASCII, which may differ by case.
+pub(crate) fn is_aligned_match(
+    endian: Utf16Endian,
+    pattern: &[u8],
+    bytes: &[u8],
+    start: usize,
+) -> bool {
+    start % 2 == 0
+        && pattern.chunks(2).zip(bytes.chunks(2)).all(|(p, b)| {
+            p == b || (endian.high_byte(p) == 0 && endian.high_byte(b) == 0)
+        })
+}
+
+/// An iterator of non-overlapping matches in a UTF-16 stream.
+///
+/// This iterator yields elements of type `Result<Match, std::io::Error>`,
+/// where an error is reported if there was a problem reading from the
+/// underlying stream. The iterator terminates only when the underlying stream
+/// reaches `EOF`.
+///
+/// This iterator is constructed via the
+/// [`Utf16Searcher::stream_find_iter`] and
+/// [`Utf16Searcher::try_stream_find_iter`] methods.
+///
+/// The type variable `R` refers to the `io::Read` stream that is being read
+/// from.
+///
+/// The lifetime `'a` refers to the lifetime of the corresponding
+/// [`Utf16Searcher`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamFindIter<'a, R> {
+    searcher: &'a Utf16Searcher,
+    rdr: R,
+    /// The bytes read from the stream that a match may still start at,
+    /// followed by the bytes that haven't been searched yet.
+    buf: Vec<u8>,
+    /// The offset in the stream of the first byte of `buf`.
+    buf_offset: usize,
+    /// The number of bytes of `buf` that have been searched.
+    pos: usize,
+    /// The state of the automaton after the bytes searched so far.
+    sid: crate::automaton::StateID,
+    /// The offset in the stream at which the last match ended. A match may
+    /// not start before it.
+    last_match_end: usize,
+    /// Whether the stream has reached `EOF`.
+    eof: bool,
+}
+
+#[cfg(feature = "std")]
+impl<'a, R: std::io::Read> StreamFindIter<'a, R> {
+    /// The number of bytes read from the stream at a time.
+    const CHUNK_LEN: usize = 8 * (1 << 10);
+
+    fn new(
+        searcher: &'a Utf16Searcher,
+        rdr: R,
+    ) -> Result<StreamFindIter<'a, R>, MatchError> {
+        use crate::automaton::Automaton;
+
+        let ac = &searcher.ac;
+        if ac.match_kind() != MatchKind::Standard {
+            return Err(MatchError::unsupported_stream(ac.match_kind()));
+        }
+        if ac.min_pattern_len() == 0 {
+            return Err(MatchError::unsupported_empty());
+        }
+        let sid = ac.automaton().start_state(crate::Anchored::No)?;
+        Ok(StreamFindIter {
+            searcher,
+            rdr,
+            buf: Vec::new(),
+            buf_offset: 0,
+            pos: 0,
+            sid,
+            last_match_end: 0,
+            eof: false,
+        })
+    }
+
+    /// Returns the first match of the current state that is accepted, if
+    /// any.
+    ///
+    /// The matches of a state are ordered from longest to shortest, so this
+    /// is the match that a non-overlapping search reports, as long as it
+    /// doesn't start before the end of the previous match.
+    fn accepted_match(&self) -> Option<Match> {
+        use crate::automaton::Automaton;
+
+        let aut = self.searcher.ac.automaton();
+        let end = self.buf_offset + self.pos;
+        for index in 0..aut.match_len(self.sid) {
+            let pid = aut.match_pattern(self.sid, index);
+            let len = aut.pattern_len(pid);
+            let start = end - len;
+            if start < self.last_match_end || start % 2 != 0 {
+                continue;
+            }
+            let bytes = &self.buf[self.pos - len..self.pos];
+            if self.searcher.ac.accepts_utf16(self.searcher.endian, pid, bytes)
+            {
+                return Some(Match::new(pid, start..end));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, R: std::io::Read> Iterator for StreamFindIter<'a, R> {
+    type Item = std::io::Result<Match>;
+
+    fn next(&mut self) -> Option<std::io::Result<Match>> {
+        use crate::automaton::Automaton;
+
+        let aut = self.searcher.ac.automaton();
+        loop {
+            while self.pos < self.buf.len() {
+                let byte = self.buf[self.pos];
+                self.sid = aut.next_state(crate::Anchored::No, self.sid, byte);
+                self.pos += 1;
+                if !aut.is_match(self.sid) {
+                    continue;
+                }
+                if let Some(m) = self.accepted_match() {
+                    self.last_match_end = m.end();
+                    return Some(Ok(m));
+                }
+            }
+            if self.eof {
+                return None;
+            }
+            // Every byte has been searched, so only the bytes that a match
+            // ending at a byte not read yet may start at are kept.
+            let keep = core::cmp::min(
+                self.buf.len(),
+                self.searcher.ac.max_pattern_len() - 1,
+            );
+            let searched = self.buf.len() - keep;
+            self.buf.drain(..searched);
+            self.buf_offset += searched;
+            self.pos -= searched;
+
+            let old_len = self.buf.len();
+            self.buf.resize(old_len + Self::CHUNK_LEN, 0);
+            match self.rdr.read(&mut self.buf[old_len..]) {
+                Ok(n) => {
+                    self.buf.truncate(old_len + n);
+                    self.eof = n == 0;
+                }
+                Err(err) => {
+                    self.buf.truncate(old_len);
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{AhoCorasick, MatchKind, PatternID};
+
+    use super::*;
+
+    /// Returns the byte offset in the UTF-16 encoding of the given string of
+    /// each of its UTF-8 offsets.
+    fn utf16_offsets(string: &str) -> Vec<usize> {
+        let mut offsets = vec![0; string.len() + 1];
+        let mut at = 0;
+        for (i, c) in string.char_indices() {
+            offsets[i] = at;
+            at += 2 * c.len_utf16();
+        }
+        offsets[string.len()] = at;
+        offsets
+    }
+
+    #[test]
+    fn same_matches_as_utf8() {
+        let patterns = ["ab", "bc", "é", "Ł", "abcd", "c", "日本"];
+        let haystacks = [
+            "xabcdx",
+            "ÉéŁłABCDabc",
+            "日本語 abc 日本",
+            "\u{6100}\u{6200}ab\u{100}c",
+            "",
+        ];
+        for &match_kind in [
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ]
+        .iter()
+        {
+            for &ci in [false, true].iter() {
+                let mut builder = AhoCorasick::builder();
+                builder.match_kind(match_kind).ascii_case_insensitive(ci);
+                let utf8 = builder.build(patterns).unwrap();
+                for &endian in [Utf16Endian::Little, Utf16Endian::Big].iter() {
+                    let utf16 = builder.build_utf16(patterns, endian).unwrap();
+                    for haystack in haystacks.iter() {
+                        let offsets = utf16_offsets(haystack);
+                        let expected: Vec<(PatternID, usize, usize)> = utf8
+                            .find_iter(haystack)
+                            .map(|m| {
+                                (
+                                    m.pattern(),
+                                    offsets[m.start()],
+                                    offsets[m.end()],
+                                )
+                            })
+                            .collect();
+                        let encoded = endian.encode(haystack);
+                        let got: Vec<(PatternID, usize, usize)> = utf16
+                            .find_iter(&encoded)
+                            .map(|m| (m.pattern(), m.start(), m.end()))
+                            .collect();
+                        assert_eq!(expected, got, "{:?}", haystack);
+                        #[cfg(feature = "std")]
+                        if match_kind == MatchKind::Standard {
+                            let streamed: Vec<(PatternID, usize, usize)> =
+                                utf16
+                                    .stream_find_iter(OddChunks(&encoded))
+                                    .map(|m| m.unwrap())
+                                    .map(|m| (m.pattern(), m.start(), m.end()))
+                                    .collect();
+                            assert_eq!(expected, streamed, "{:?}", haystack);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn misaligned_matches_are_rejected() {
+        let searcher =
+            AhoCorasick::builder().build_utf16(["ab"], Utf16Endian::Little);
+        let searcher = searcher.unwrap();
+        // The bytes of 'ab' in UTF-16LE start at offset 1, in the middle of
+        // the first code unit.
+        let mut haystack = Utf16Endian::Little.encode("\u{6100}\u{6200}\0");
+        assert!(searcher.automaton().is_match(&haystack));
+        assert!(!searcher.is_match(&haystack));
+        haystack.extend(Utf16Endian::Little.encode("ab"));
+        let m = searcher.find(&haystack).unwrap();
+        assert_eq!(6..10, m.range());
+        // A trailing odd byte is never part of a match.
+        haystack.push(b'x');
+        assert_eq!(1, searcher.find_iter(&haystack).count());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn unsupported_streams() {
+        let builder = AhoCorasick::builder();
+        let searcher = builder.build_utf16(["", "a"], Utf16Endian::Big);
+        assert!(searcher.unwrap().try_stream_find_iter(&b""[..]).is_err());
+        let mut builder = AhoCorasick::builder();
+        builder.match_kind(MatchKind::LeftmostFirst);
+        let searcher = builder.build_utf16(["a"], Utf16Endian::Big).unwrap();
+        assert!(searcher.try_stream_find_iter(&b""[..]).is_err());
+    }
+
+    /// A reader that returns at most 3 bytes at a time, so that chunks end
+    /// in the middle of code units.
+    #[cfg(feature = "std")]
+    struct OddChunks<'a>(&'a [u8]);
+
+    #[cfg(feature = "std")]
+    impl<'a> std::io::Read for OddChunks<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = core::cmp::min(3, core::cmp::min(buf.len(), self.0.len()));
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+}
@@ -26,6 +26,26 @@ impl ByteClasses {
         classes
     }
 
+    /// Creates equivalence classes from the class of each byte, or returns
+    /// `None` if they aren't numbered in ascending order of byte, starting
+    /// at `0`, like the classes built by `ByteClassSet`.
+    pub(crate) fn from_bytes(classes: [u8; 256]) -> Option<ByteClasses> {
+        if classes[0] != 0 {
+            return None;
+        }
+        for pair in classes.windows(2) {
+            if pair[1] != pair[0] && pair[1] != pair[0].checked_add(1)? {
+                return None;
+            }
+        }
+        Some(ByteClasses(classes))
+    }
+
+    /// Returns the class of each byte.
+    pub(crate) fn as_bytes(&self) -> &[u8; 256] {
+        &self.0
+    }
+
     /// Set the equivalence class for the given byte.
     #[inline]
     pub(crate) fn set(&mut self, byte: u8, class: u8) {
@@ -276,12 +296,26 @@ impl ByteSet {
         self.bits.0[usize::from(bucket)] |= 1 << bit;
     }
 
+    /// Remove a byte from this set.
+    ///
+    /// If the given byte is not in this set, then this is a no-op.
+    pub(crate) fn remove(&mut self, byte: u8) {
+        let bucket = byte / 128;
+        let bit = byte % 128;
+        self.bits.0[usize::from(bucket)] &= !(1 << bit);
+    }
+
     /// Return true if and only if the given byte is in this set.
     pub(crate) fn contains(&self, byte: u8) -> bool {
         let bucket = byte / 128;
         let bit = byte % 128;
         self.bits.0[usize::from(bucket)] & (1 << bit) > 0
     }
+
+    /// Return true if and only if this set has no bytes.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bits.0 == [0; 2]
+    }
 }
 
 impl core::fmt::Debug for BitSet {
@@ -0,0 +1,297 @@
+/*!
+Trait impls for `arbitrary::Arbitrary`, along with structured inputs for
+fuzzers and property tests.
+
+These are only available when the `arbitrary` feature is enabled.
+
+The impls for the configuration enums, such as `MatchKind`, pick any of
+their variants, and the impl for `PatternID` always produces a valid ID.
+
+Patterns built from unstructured bytes almost never overlap each other or
+occur in a haystack, which leaves most of the interesting code paths
+unexercised. So instead of deriving impls for collections of patterns, this
+module provides [`PatternSetSpec`], whose patterns and replacements are drawn
+from a small alphabet and commonly include empty and duplicate patterns.
+[`PatternSetSpec::haystack`] assembles haystacks from a spec's own patterns,
+[`ChunkPlan`] splits haystacks into chunks for stream and chunked searches and
+[`BuilderOptions`] produces an arbitrary [`AhoCorasickBuilder`].
+*/
+
+use alloc::{vec, vec::Vec};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    ahocorasick::{AhoCorasickBuilder, AhoCorasickKind},
+    util::{
+        primitives::PatternID,
+        search::{Anchored, MatchKind, StartKind},
+    },
+};
+
+/// The bytes that patterns, replacements and haystacks are drawn from. This
+/// includes both cases of an ASCII letter, for ASCII case insensitivity, and
+/// a couple of bytes that are not valid UTF-8.
+const ALPHABET: &[u8] = b"abcA\x00\xFF";
+
+impl<'a> Arbitrary<'a> for PatternID {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<PatternID> {
+        let id = u.int_in_range(0..=PatternID::MAX.as_usize())?;
+        Ok(PatternID::must(id))
+    }
+}
+
+impl<'a> Arbitrary<'a> for MatchKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<MatchKind> {
+        Ok(*u.choose(&[
+            MatchKind::Standard,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+        ])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for StartKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<StartKind> {
+        Ok(*u.choose(&[
+            StartKind::Unanchored,
+            StartKind::Anchored,
+            StartKind::Both,
+        ])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Anchored {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Anchored> {
+        Ok(*u.choose(&[Anchored::No, Anchored::Yes])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for AhoCorasickKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<AhoCorasickKind> {
+        Ok(*u.choose(&[
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ])?)
+    }
+}
+
+/// A set of patterns along with a replacement for each pattern.
+///
+/// An arbitrary spec has between 1 and 8 patterns of at most 6 bytes each,
+/// and replacements of at most 4 bytes each. All of them are drawn from a
+/// small alphabet, so that patterns commonly overlap. Empty patterns and
+/// duplicates of earlier patterns are also generated.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PatternSetSpec {
+    patterns: Vec<Vec<u8>>,
+    replacements: Vec<Vec<u8>>,
+}
+
+impl PatternSetSpec {
+    /// Returns the patterns in this spec.
+    pub fn patterns(&self) -> &[Vec<u8>] {
+        &self.patterns
+    }
+
+    /// Returns the replacements in this spec. The replacement at index `i`
+    /// corresponds to the pattern at index `i`.
+    pub fn replacements(&self) -> &[Vec<u8>] {
+        &self.replacements
+    }
+
+    /// Returns true if and only if any pattern in this spec is empty.
+    pub fn has_empty(&self) -> bool {
+        self.patterns.iter().any(|p| p.is_empty())
+    }
+
+    /// Generate a haystack of up to 16 pieces, where each piece is one of
+    /// the patterns in this spec, a prefix of one of them or a short run of
+    /// other bytes from the alphabet.
+    ///
+    /// This makes matches, and partial matches that a search has to give up
+    /// on, far more common than in arbitrary bytes.
+    pub fn haystack(&self, u: &mut Unstructured<'_>) -> Result<Vec<u8>> {
+        let mut haystack = vec![];
+        for _ in 0..u.int_in_range(0..=16)? {
+            let pattern = if self.patterns.is_empty() {
+                &[][..]
+            } else {
+                u.choose(&self.patterns)?.as_slice()
+            };
+            match u.int_in_range(0..=2)? {
+                0 => haystack.extend_from_slice(pattern),
+                1 => {
+                    let len = u.int_in_range(0..=pattern.len())?;
+                    haystack.extend_from_slice(&pattern[..len]);
+                }
+                _ => haystack.extend_from_slice(&word(u, 1, 3)?),
+            }
+        }
+        Ok(haystack)
+    }
+}
+
+impl<'a> Arbitrary<'a> for PatternSetSpec {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<PatternSetSpec> {
+        let len = u.int_in_range(1..=8)?;
+        let mut patterns: Vec<Vec<u8>> = vec![];
+        for _ in 0..len {
+            let pattern = match u.int_in_range(0..=9)? {
+                0 => vec![],
+                1 if !patterns.is_empty() => u.choose(&patterns)?.clone(),
+                _ => word(u, 1, 6)?,
+            };
+            patterns.push(pattern);
+        }
+        let replacements = (0..len)
+            .map(|_| word(u, 0, 4))
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+        Ok(PatternSetSpec { patterns, replacements })
+    }
+}
+
+/// A plan for splitting a haystack into chunks.
+///
+/// A plan is a non-empty sequence of chunk sizes between `0` and `9`, which
+/// are cycled through until the haystack is exhausted. A size of `0`
+/// produces an empty chunk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkPlan {
+    sizes: Vec<usize>,
+}
+
+impl ChunkPlan {
+    /// Returns the chunk sizes of this plan.
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// Split the given haystack into chunks according to this plan.
+    ///
+    /// Concatenating the chunks always gives back the haystack. If every
+    /// size in this plan is `0`, then the result is an empty chunk followed
+    /// by the entire haystack.
+    pub fn split<'h>(&self, mut haystack: &'h [u8]) -> Vec<&'h [u8]> {
+        let mut chunks = vec![];
+        if self.sizes.iter().all(|&size| size == 0) {
+            chunks.push(&haystack[..0]);
+            chunks.push(haystack);
+            return chunks;
+        }
+        for &size in self.sizes.iter().cycle() {
+            if haystack.is_empty() {
+                break;
+            }
+            let (chunk, rest) = haystack.split_at(size.min(haystack.len()));
+            chunks.push(chunk);
+            haystack = rest;
+        }
+        chunks
+    }
+}
+
+impl<'a> Arbitrary<'a> for ChunkPlan {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<ChunkPlan> {
+        let len = u.int_in_range(1..=8)?;
+        let sizes = (0..len)
+            .map(|_| u.int_in_range(0..=9))
+            .collect::<Result<Vec<usize>>>()?;
+        Ok(ChunkPlan { sizes })
+    }
+}
+
+/// A set of options for an [`AhoCorasickBuilder`].
+///
+/// An arbitrary set of options covers every option that changes how an
+/// automaton is built or searched, including the implementation used, with
+/// a small dense depth so that building stays fast.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuilderOptions {
+    kind: Option<AhoCorasickKind>,
+    match_kind: MatchKind,
+    start_kind: StartKind,
+    ascii_case_insensitive: bool,
+    prefilter: bool,
+    dense_depth: usize,
+    byte_classes: bool,
+}
+
+impl BuilderOptions {
+    /// Returns a builder configured with these options.
+    ///
+    /// Options may be overridden on the returned builder, for example, to
+    /// force [`MatchKind::Standard`] semantics for a stream search.
+    pub fn builder(&self) -> AhoCorasickBuilder {
+        let mut builder = AhoCorasickBuilder::new();
+        builder
+            .kind(self.kind)
+            .match_kind(self.match_kind)
+            .start_kind(self.start_kind)
+            .ascii_case_insensitive(self.ascii_case_insensitive)
+            .prefilter(self.prefilter)
+            .dense_depth(self.dense_depth)
+            .byte_classes(self.byte_classes);
+        builder
+    }
+}
+
+impl<'a> Arbitrary<'a> for BuilderOptions {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<BuilderOptions> {
+        Ok(BuilderOptions {
+            kind: Option::<AhoCorasickKind>::arbitrary(u)?,
+            match_kind: MatchKind::arbitrary(u)?,
+            start_kind: StartKind::arbitrary(u)?,
+            ascii_case_insensitive: bool::arbitrary(u)?,
+            prefilter: bool::arbitrary(u)?,
+            dense_depth: u.int_in_range(0..=3)?,
+            byte_classes: bool::arbitrary(u)?,
+        })
+    }
+}
+
+/// Returns a word of between `min` and `max` bytes from the alphabet.
+fn word(u: &mut Unstructured<'_>, min: usize, max: usize) -> Result<Vec<u8>> {
+    let len = u.int_in_range(min..=max)?;
+    (0..len).map(|_| u.choose(ALPHABET).copied()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns deterministic bytes to generate values from.
+    fn data(seed: u8) -> Vec<u8> {
+        (0..4096u32).map(|i| (i.wrapping_mul(31) as u8) ^ seed).collect()
+    }
+
+    #[test]
+    fn chunk_plans() {
+        let plan = ChunkPlan { sizes: vec![2, 0, 3] };
+        assert_eq!(
+            vec![&b"ab"[..], b"", b"cde", b"fg"],
+            plan.split(b"abcdefg"),
+        );
+        let plan = ChunkPlan { sizes: vec![0] };
+        assert_eq!(vec![&b""[..], b"abc"], plan.split(b"abc"));
+        assert!(ChunkPlan { sizes: vec![1] }.split(b"").is_empty());
+    }
+
+    #[test]
+    fn generated_inputs_are_usable() {
+        for seed in 0..=255 {
+            let data = data(seed);
+            let mut u = Unstructured::new(&data);
+            let spec = PatternSetSpec::arbitrary(&mut u).unwrap();
+            assert_eq!(spec.patterns().len(), spec.replacements().len());
+            let haystack = spec.haystack(&mut u).unwrap();
+            let plan = ChunkPlan::arbitrary(&mut u).unwrap();
+            assert_eq!(haystack, plan.split(&haystack).concat());
+
+            let options = BuilderOptions::arbitrary(&mut u).unwrap();
+            let ac = options.builder().build(spec.patterns()).unwrap();
+            assert_eq!(spec.patterns().len(), ac.patterns_len());
+        }
+    }
+}
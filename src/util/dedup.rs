@@ -0,0 +1,156 @@
+use alloc::vec::Vec;
+
+use crate::util::{primitives::PatternID, search::Match};
+
+/// Suppresses repeated matches of the same pattern within a window of bytes.
+///
+/// A deduper is given matches in the order they occur in a haystack or
+/// stream, and decides whether each one should be reported. A match is
+/// suppressed when it starts less than `N` bytes after the start of the last
+/// match of the same pattern that was reported, where `N` is the window for
+/// that pattern. Otherwise, it is reported and begins a new window. Since the
+/// window is anchored at the last reported match, a pattern that matches
+/// continuously is still reported once every `N` bytes.
+///
+/// The window may be set for all patterns with [`MatchDeduper::new`], and
+/// overridden for individual patterns with [`MatchDeduper::window`]. A window
+/// of `None` disables de-duplication.
+///
+/// A deduper can be used to filter the matches of any search, including
+/// stream searches, by calling [`MatchDeduper::accept`]. It can also be given
+/// to [`AhoCorasickReplacerBuilder::dedup`](crate::AhoCorasickReplacerBuilder::dedup),
+/// in which case suppressed matches are left as is instead of being
+/// replaced.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, MatchDeduper, PatternID};
+///
+/// let ac = AhoCorasick::new(&["ERROR", "WARN"]).unwrap();
+/// let log = "ERROR ERROR WARN ERROR WARN";
+///
+/// // Report each pattern at most once every 10 bytes, except for 'WARN',
+/// // which is reported every time.
+/// let mut dedup = MatchDeduper::new(Some(10));
+/// dedup.window(PatternID::must(1), None);
+///
+/// let starts: Vec<usize> = ac
+///     .find_iter(log)
+///     .filter(|m| dedup.accept(m))
+///     .map(|m| m.start())
+///     .collect();
+/// assert_eq!(vec![0, 12, 17, 23], starts);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MatchDeduper {
+    /// The window used for patterns without a window of their own.
+    default: Option<usize>,
+    /// The window of each pattern that has one, indexed by pattern ID.
+    windows: Vec<Option<Option<usize>>>,
+    /// The start of the last reported match of each pattern, indexed by
+    /// pattern ID.
+    last: Vec<Option<usize>>,
+}
+
+impl MatchDeduper {
+    /// Create a new deduper that uses the given window for every pattern.
+    pub fn new(window: Option<usize>) -> MatchDeduper {
+        MatchDeduper { default: window, windows: Vec::new(), last: Vec::new() }
+    }
+
+    /// Set the window for the given pattern, overriding the window given to
+    /// [`MatchDeduper::new`]. A window of `None` disables de-duplication for
+    /// this pattern.
+    pub fn window(
+        &mut self,
+        pattern: PatternID,
+        window: Option<usize>,
+    ) -> &mut MatchDeduper {
+        let i = pattern.as_usize();
+        if self.windows.len() <= i {
+            self.windows.resize(i + 1, None);
+        }
+        self.windows[i] = Some(window);
+        self
+    }
+
+    /// Returns true if the given match should be reported and false if it
+    /// should be suppressed.
+    ///
+    /// Matches must be given in order of their starting offset.
+    pub fn accept(&mut self, m: &Match) -> bool {
+        let i = m.pattern().as_usize();
+        let window = match self.windows.get(i) {
+            Some(&Some(window)) => window,
+            _ => self.default,
+        };
+        let window = match window {
+            None => return true,
+            Some(window) => window,
+        };
+        if self.last.len() <= i {
+            self.last.resize(i + 1, None);
+        }
+        if let Some(last) = self.last[i] {
+            if m.start().saturating_sub(last) < window {
+                return false;
+            }
+        }
+        self.last[i] = Some(m.start());
+        true
+    }
+
+    /// Forget every match seen so far, so that the deduper can be reused for
+    /// a new haystack or stream. The windows are kept.
+    pub fn reset(&mut self) {
+        self.last.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn accepted(dedup: &mut MatchDeduper, matches: &[Match]) -> Vec<bool> {
+        matches.iter().map(|m| dedup.accept(m)).collect()
+    }
+
+    #[test]
+    fn windows() {
+        let matches = [
+            Match::must(0, 0..2),
+            Match::must(1, 1..2),
+            Match::must(0, 4..6),
+            Match::must(0, 5..7),
+            Match::must(1, 6..7),
+            Match::must(0, 9..11),
+        ];
+        let mut dedup = MatchDeduper::new(None);
+        assert_eq!(vec![true; 6], accepted(&mut dedup, &matches));
+
+        // The window is anchored at the last reported match, so the match at
+        // '9' is suppressed since it's within 5 bytes of the match at '5'.
+        let mut dedup = MatchDeduper::new(Some(5));
+        assert_eq!(
+            vec![true, true, false, true, true, false],
+            accepted(&mut dedup, &matches),
+        );
+        dedup.reset();
+        dedup.window(PatternID::must(1), Some(1));
+        let again = [Match::must(0, 0..1), Match::must(0, 5..6)];
+        assert_eq!(vec![true, true], accepted(&mut dedup, &again));
+        assert!(!dedup.accept(&Match::must(0, 9..11)));
+        assert!(dedup.accept(&Match::must(1, 9..11)));
+        assert!(dedup.accept(&Match::must(1, 10..11)));
+
+        let mut dedup = MatchDeduper::new(Some(100));
+        dedup.window(PatternID::must(0), None);
+        assert_eq!(
+            vec![true, true, true, true, false, true],
+            accepted(&mut dedup, &matches),
+        );
+    }
+}
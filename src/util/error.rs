@@ -1,6 +1,9 @@
-use crate::util::{
-    primitives::{PatternID, SmallIndex},
-    search::MatchKind,
+use crate::{
+    util::{
+        primitives::{PatternID, SmallIndex},
+        search::MatchKind,
+    },
+    ReplacerError,
 };
 
 /// An error that occurred during the construction of an Aho-Corasick
@@ -9,7 +12,12 @@ use crate::util::{
 /// Build errors occur when some kind of limit has been exceeded, either in the
 /// number of states, the number of patterns of the length of a pattern. These
 /// limits aren't part of the public API, but they should generally be large
-/// enough to handle most use cases.
+/// enough to handle most use cases. A build error also occurs when a pattern
+/// is a duplicate of an earlier one and
+/// [`DuplicatePolicy::Error`](crate::DuplicatePolicy::Error) is set, when a
+/// [signature](crate::signature) has no literal byte, or when
+/// [`AhoCorasick::try_replacer`](crate::AhoCorasick::try_replacer) can't
+/// build a replacer.
 ///
 /// When the `std` feature is enabled, this implements the `std::error::Error`
 /// trait.
@@ -46,6 +54,32 @@ enum ErrorKind {
         /// The length that was too long.
         len: usize,
     },
+    /// Occurs when a pattern is identical to an earlier pattern once they
+    /// are folded, and duplicates are rejected.
+    DuplicatePattern {
+        /// The ID of the duplicate pattern.
+        pattern: PatternID,
+        /// The ID of the earliest pattern it duplicates.
+        first: PatternID,
+    },
+    /// Occurs when a signature given to a signature searcher has no literal
+    /// byte to search for, so it would have to be verified at every
+    /// position.
+    UnanchoredSignature {
+        /// The index of the signature.
+        signature: usize,
+    },
+    /// Occurs when an overlap policy is set on an automaton without
+    /// standard match semantics, which can't report every overlapping
+    /// match.
+    OverlapPolicyMatchKind {
+        /// The match semantics of the automaton.
+        got: MatchKind,
+    },
+    /// Occurs when a replacer can't be built for an automaton, for example
+    /// because the number of replacements doesn't match the number of
+    /// patterns.
+    Replacer(ReplacerError),
 }
 
 impl BuildError {
@@ -71,6 +105,46 @@ impl BuildError {
     ) -> BuildError {
         BuildError { kind: ErrorKind::PatternTooLong { pattern, len } }
     }
+
+    pub(crate) fn duplicate_pattern(
+        pattern: PatternID,
+        first: PatternID,
+    ) -> BuildError {
+        BuildError { kind: ErrorKind::DuplicatePattern { pattern, first } }
+    }
+
+    pub(crate) fn unanchored_signature(signature: usize) -> BuildError {
+        BuildError { kind: ErrorKind::UnanchoredSignature { signature } }
+    }
+
+    pub(crate) fn overlap_policy_match_kind(got: MatchKind) -> BuildError {
+        BuildError { kind: ErrorKind::OverlapPolicyMatchKind { got } }
+    }
+
+    pub(crate) fn replacer(err: ReplacerError) -> BuildError {
+        BuildError { kind: ErrorKind::Replacer(err) }
+    }
+
+    /// Translate the pattern IDs this error refers to with the given
+    /// function, for errors from automata built from a subset of patterns.
+    pub(crate) fn map_pattern_ids<F: Fn(PatternID) -> PatternID>(
+        self,
+        map: F,
+    ) -> BuildError {
+        let kind = match self.kind {
+            ErrorKind::PatternTooLong { pattern, len } => {
+                ErrorKind::PatternTooLong { pattern: map(pattern), len }
+            }
+            ErrorKind::DuplicatePattern { pattern, first } => {
+                ErrorKind::DuplicatePattern {
+                    pattern: map(pattern),
+                    first: map(first),
+                }
+            }
+            kind => kind,
+        };
+        BuildError { kind }
+    }
 }
 
 #[cfg(feature = "std")]
@@ -105,6 +179,108 @@ impl core::fmt::Display for BuildError {
                     SmallIndex::MAX.as_usize(),
                 )
             }
+            ErrorKind::DuplicatePattern { pattern, first } => {
+                write!(
+                    f,
+                    "pattern {} is a duplicate of pattern {}",
+                    pattern.as_usize(),
+                    first.as_usize(),
+                )
+            }
+            ErrorKind::UnanchoredSignature { signature } => write!(
+                f,
+                "signature {} has no literal byte to anchor its search",
+                signature,
+            ),
+            ErrorKind::OverlapPolicyMatchKind { got } => write!(
+                f,
+                "overlap policies require standard match semantics, \
+                 but got {:?}",
+                got,
+            ),
+            ErrorKind::Replacer(ref err) => err.fmt(f),
+        }
+    }
+}
+
+/// An error that occurred when deserializing an automaton.
+///
+/// Deserialization fails when the bytes given weren't written by the same
+/// kind of automaton, by a version of this crate with the same encoding, or
+/// were corrupted since they were written. See, for example,
+/// [`dfa::DFA::from_bytes`](crate::dfa::DFA::from_bytes).
+///
+/// When the `std` feature is enabled, this implements the `std::error::Error`
+/// trait.
+#[derive(Clone, Debug)]
+pub struct DeserializeError {
+    kind: DeserializeErrorKind,
+}
+
+/// The kind of error that occurred when deserializing.
+#[derive(Clone, Debug)]
+enum DeserializeErrorKind {
+    /// The bytes don't start with the magic number of the automaton.
+    Magic,
+    /// The bytes were written with another version of the encoding.
+    Version {
+        /// The version this crate reads.
+        expected: u32,
+        /// The version the bytes were written with.
+        got: u32,
+    },
+    /// The checksum of the bytes doesn't match their contents.
+    Checksum,
+    /// The bytes are truncated, or don't describe a valid automaton.
+    Invalid,
+}
+
+impl DeserializeError {
+    pub(crate) fn magic() -> DeserializeError {
+        DeserializeError { kind: DeserializeErrorKind::Magic }
+    }
+
+    pub(crate) fn version(expected: u32, got: u32) -> DeserializeError {
+        DeserializeError {
+            kind: DeserializeErrorKind::Version { expected, got },
+        }
+    }
+
+    pub(crate) fn checksum() -> DeserializeError {
+        DeserializeError { kind: DeserializeErrorKind::Checksum }
+    }
+
+    pub(crate) fn invalid() -> DeserializeError {
+        DeserializeError { kind: DeserializeErrorKind::Invalid }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeserializeError {}
+
+impl core::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            DeserializeErrorKind::Magic => {
+                write!(
+                    f,
+                    "bytes do not hold a serialized automaton of this kind"
+                )
+            }
+            DeserializeErrorKind::Version { expected, got } => {
+                write!(
+                    f,
+                    "serialized automaton has encoding version {}, but only \
+                     version {} is supported",
+                    got, expected,
+                )
+            }
+            DeserializeErrorKind::Checksum => {
+                write!(f, "serialized automaton does not match its checksum")
+            }
+            DeserializeErrorKind::Invalid => {
+                write!(f, "serialized automaton is truncated or invalid")
+            }
         }
     }
 }
@@ -189,6 +365,120 @@ impl MatchError {
     pub fn unsupported_empty() -> MatchError {
         MatchError::new(MatchErrorKind::UnsupportedEmpty)
     }
+
+    /// Create a new "unsupported exact case" error. This occurs when the
+    /// caller requests an exact case search via
+    /// [`Input::exact_case`](crate::Input::exact_case) from an ASCII case
+    /// insensitive searcher that does not store its patterns.
+    pub fn unsupported_exact_case() -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedExactCase)
+    }
+
+    /// Create a new "unsupported pattern set" error. This occurs when the
+    /// caller restricts a search to a set of patterns via
+    /// [`Input::pattern_set`](crate::Input::pattern_set) with a searcher
+    /// that does not store its patterns.
+    pub fn unsupported_pattern_set() -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedPatternSet)
+    }
+
+    /// Create a new "unsupported disabled pattern" error. This occurs when
+    /// the caller disables a pattern via
+    /// [`AhoCorasick::disable_pattern`](crate::AhoCorasick::disable_pattern)
+    /// in a searcher that does not store its patterns.
+    pub fn unsupported_disabled_pattern() -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedDisabledPattern)
+    }
+
+    /// Create a new "unsupported overlap policy" error. This occurs when the
+    /// caller requests a stream replacement that doesn't support the
+    /// [`OverlapPolicy`](crate::OverlapPolicy) set on the automaton.
+    pub fn unsupported_overlap_policy() -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedOverlapPolicy)
+    }
+
+    /// Create a new "unsupported word boundary" error. This occurs when the
+    /// caller requests a stream search or replacement from a searcher built
+    /// with [`AhoCorasickBuilder::word_boundary`](crate::AhoCorasickBuilder::word_boundary)
+    /// enabled, which it doesn't honor.
+    pub fn unsupported_word_boundary() -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedWordBoundary)
+    }
+
+    /// Create a new "unsupported lookahead" error. This occurs when the
+    /// caller requests a stream search or replacement from a searcher with
+    /// a [`Lookahead`](crate::Lookahead), which it doesn't honor.
+    pub fn unsupported_lookahead() -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedLookahead)
+    }
+
+    /// Create a new "replacer" error. This occurs when a replacer can't be
+    /// built by [`AhoCorasick::replacer`](crate::AhoCorasick::replacer), for
+    /// example because the number of replacements doesn't match the number
+    /// of patterns.
+    pub(crate) fn replacer(err: ReplacerError) -> MatchError {
+        MatchError::new(MatchErrorKind::Replacer(err))
+    }
+
+    /// Create a new "unsupported compact match" error. This occurs when the
+    /// caller requests compact matches via
+    /// [`MatchU32`](crate::MatchU32), but where either the end of the search
+    /// does not fit into a `u32` or the longest pattern in the automaton does
+    /// not fit into a `u16`.
+    pub fn unsupported_compact(
+        end: usize,
+        max_pattern_len: usize,
+    ) -> MatchError {
+        MatchError::new(MatchErrorKind::UnsupportedCompact {
+            end,
+            max_pattern_len,
+        })
+    }
+
+    /// Create a new "pending limit exceeded" error. This occurs when a
+    /// stream replacer holds back more bytes than the limit set by
+    /// [`AhoCorasickReplacerBuilder::max_pending_bytes`](crate::AhoCorasickReplacerBuilder::max_pending_bytes).
+    pub fn pending_limit_exceeded(limit: usize) -> MatchError {
+        MatchError::new(MatchErrorKind::PendingLimitExceeded { limit })
+    }
+
+    /// Create a new "output limit exceeded" error. This occurs when the
+    /// output of a single call to a stream replacer would exceed the cap
+    /// set by [`MemoryLimits::output`](crate::MemoryLimits::output).
+    pub fn output_limit_exceeded(limit: usize) -> MatchError {
+        MatchError::new(MatchErrorKind::OutputLimitExceeded { limit })
+    }
+
+    /// Create a new "event limit exceeded" error. This occurs when a shadow
+    /// replacer would record more differences than the cap set by
+    /// [`MemoryLimits::events`](crate::MemoryLimits::events).
+    pub fn event_limit_exceeded(limit: usize) -> MatchError {
+        MatchError::new(MatchErrorKind::EventLimitExceeded { limit })
+    }
+
+    /// Create a new "quit" error. This occurs when a stream replacer stops
+    /// at a byte set by
+    /// [`AhoCorasickReplacerBuilder::quit`](crate::AhoCorasickReplacerBuilder::quit),
+    /// found at the given offset of the stream.
+    pub fn quit(byte: u8, offset: usize) -> MatchError {
+        MatchError::new(MatchErrorKind::Quit { byte, offset })
+    }
+
+    /// Create a new "poisoned replacer" error. This occurs when a stream
+    /// replacer is used after an earlier error left it in the middle of a
+    /// chunk, until it's reset. See
+    /// [`AhoCorasickReplacer::is_poisoned`](crate::AhoCorasickReplacer::is_poisoned).
+    pub fn poisoned() -> MatchError {
+        MatchError::new(MatchErrorKind::Poisoned)
+    }
+
+    /// Create a new "invalid chunked encoding" error. This occurs when the
+    /// stream given to a [`ChunkedReplacer`](crate::ChunkedReplacer) isn't a
+    /// valid body in the HTTP/1.1 chunked transfer coding, at the given
+    /// offset of the stream.
+    pub fn invalid_chunked_encoding(offset: usize) -> MatchError {
+        MatchError::new(MatchErrorKind::InvalidChunkedEncoding { offset })
+    }
 }
 
 /// The underlying kind of a [`MatchError`].
@@ -219,6 +509,74 @@ pub enum MatchErrorKind {
     /// An error indicating that the operation requested doesn't support
     /// automatons that contain an empty pattern string.
     UnsupportedEmpty,
+    /// An error indicating that an exact case search was requested from an
+    /// ASCII case insensitive searcher that does not store its patterns.
+    UnsupportedExactCase,
+    /// An error indicating that a search was restricted to a set of
+    /// patterns with a searcher that does not store its patterns.
+    UnsupportedPatternSet,
+    /// An error indicating that a pattern was disabled in a searcher that
+    /// does not store its patterns.
+    UnsupportedDisabledPattern,
+    /// An error indicating that a stream search or replacement was
+    /// requested from a searcher that only reports matches bounded by
+    /// non-word bytes.
+    UnsupportedWordBoundary,
+    /// An error indicating that a stream search or replacement was
+    /// requested from a searcher with a lookahead.
+    UnsupportedLookahead,
+    /// An error indicating that a replacer can't be built with the
+    /// replacements given, or for the options of its automaton. The
+    /// [`ReplacerError`] says why.
+    Replacer(ReplacerError),
+    /// An error indicating that compact matches were requested, but where
+    /// the search or the automaton cannot guarantee that every match fits
+    /// into a [`MatchU32`](crate::MatchU32).
+    UnsupportedCompact {
+        /// The end offset of the search.
+        end: usize,
+        /// The length of the longest pattern in the automaton.
+        max_pattern_len: usize,
+    },
+    /// An error indicating that a replacement doesn't support the
+    /// [`OverlapPolicy`](crate::OverlapPolicy) set on the automaton.
+    UnsupportedOverlapPolicy,
+    /// An error indicating that a stream replacer would have to hold back
+    /// more bytes than it's allowed to.
+    PendingLimitExceeded {
+        /// The maximum number of bytes the replacer may hold back.
+        limit: usize,
+    },
+    /// An error indicating that the output of a single call to a stream
+    /// replacer would be larger than it's allowed to.
+    OutputLimitExceeded {
+        /// The maximum number of bytes a single call may return.
+        limit: usize,
+    },
+    /// An error indicating that a shadow replacer would record more
+    /// differences than it's allowed to.
+    EventLimitExceeded {
+        /// The maximum number of differences recorded until they are taken.
+        limit: usize,
+    },
+    /// An error indicating that a stream replacer stopped at a byte it was
+    /// configured to quit on.
+    Quit {
+        /// The quit byte that was found.
+        byte: u8,
+        /// The offset of the quit byte in the stream.
+        offset: usize,
+    },
+    /// An error indicating that a stream replacer was used after an earlier
+    /// error left it in an undefined state, before it was reset.
+    Poisoned,
+    /// An error indicating that a stream isn't a valid body in the HTTP/1.1
+    /// chunked transfer coding.
+    InvalidChunkedEncoding {
+        /// The offset of the first byte that makes the stream invalid, or
+        /// the length of the stream when it ends too early.
+        offset: usize,
+    },
 }
 
 #[cfg(feature = "std")]
@@ -254,6 +612,105 @@ impl core::fmt::Display for MatchError {
                      supported for this operation",
                 )
             }
+            MatchErrorKind::UnsupportedExactCase => {
+                write!(
+                    f,
+                    "exact case searches with an ASCII case insensitive \
+                     searcher require it to store its patterns",
+                )
+            }
+            MatchErrorKind::UnsupportedPatternSet => {
+                write!(
+                    f,
+                    "searches restricted to a set of patterns require the \
+                     searcher to store its patterns",
+                )
+            }
+            MatchErrorKind::UnsupportedDisabledPattern => {
+                write!(
+                    f,
+                    "disabling patterns requires the searcher to store its \
+                     patterns",
+                )
+            }
+            MatchErrorKind::UnsupportedWordBoundary => {
+                write!(
+                    f,
+                    "word boundaries are not supported by stream searches \
+                     and replacements",
+                )
+            }
+            MatchErrorKind::UnsupportedLookahead => {
+                write!(
+                    f,
+                    "lookaheads are not supported by stream searches and \
+                     replacements",
+                )
+            }
+            MatchErrorKind::Replacer(ref err) => err.fmt(f),
+            MatchErrorKind::UnsupportedCompact { end, max_pattern_len } => {
+                write!(
+                    f,
+                    "compact matches require a search that ends at or \
+                     before offset {} and patterns no longer than {} \
+                     bytes, but got a search ending at {} and a pattern \
+                     of length {}",
+                    u32::MAX,
+                    u16::MAX,
+                    end,
+                    max_pattern_len,
+                )
+            }
+            MatchErrorKind::UnsupportedOverlapPolicy => {
+                write!(
+                    f,
+                    "overlap policies are not supported for this \
+                     replacement",
+                )
+            }
+            MatchErrorKind::PendingLimitExceeded { limit } => {
+                write!(
+                    f,
+                    "replacement would hold back more than {} bytes",
+                    limit,
+                )
+            }
+            MatchErrorKind::OutputLimitExceeded { limit } => {
+                write!(
+                    f,
+                    "replacement would output more than {} bytes at once",
+                    limit,
+                )
+            }
+            MatchErrorKind::EventLimitExceeded { limit } => {
+                write!(
+                    f,
+                    "shadow replacement would record more than {} \
+                     differences",
+                    limit,
+                )
+            }
+            MatchErrorKind::Quit { byte, offset } => {
+                write!(
+                    f,
+                    "replacement stopped at quit byte 0x{:02X} at offset {}",
+                    byte, offset,
+                )
+            }
+            MatchErrorKind::Poisoned => {
+                write!(
+                    f,
+                    "replacer was poisoned by an earlier error and must be \
+                     reset",
+                )
+            }
+            MatchErrorKind::InvalidChunkedEncoding { offset } => {
+                write!(
+                    f,
+                    "invalid chunked transfer coding at offset {}",
+                    offset,
+                )
+            }
         }
     }
 }
@@ -0,0 +1,451 @@
+use alloc::vec::Vec;
+
+use crate::{
+    automaton::{Automaton, AutomatonImpl, StateID},
+    util::{error::MatchError, primitives::PatternID, search::Anchored},
+};
+
+/// A read-only view of the states and transitions of an automaton.
+///
+/// A graph is created by [`AhoCorasick::graph`](crate::AhoCorasick::graph).
+/// It is meant for tooling that needs to walk the automaton itself, such as
+/// visualizers, analyzers or exporters to other engines, without depending
+/// on how each kind of automaton lays out its states in memory.
+///
+/// The states of the graph are the same for any search that uses the
+/// automaton, and each [`StateID`] is valid for the automaton the graph was
+/// created from only. A state ID not returned by [`AutomatonGraph::states`]
+/// may result in a panic or in nonsensical results.
+///
+/// The dead state is included in the graph. A search stops once it enters
+/// the dead state, which is how leftmost match semantics are implemented.
+/// Both NFA kinds use failure transitions, while a DFA has none, since every
+/// failure transition is resolved when it is built.
+///
+/// Every other state is reachable by transitions from a start state that the
+/// automaton supports, since each one lies on the path of at least one
+/// pattern. (The only exception is a DFA built for one kind of start state,
+/// which keeps an unused start state of the other kind.) In particular, a
+/// prefilter never makes a state unreachable: it only skips over positions
+/// at which no match can start, and a search still walks the path of a
+/// pattern from the start state once it finds a candidate. So there is
+/// nothing that could be pruned from an automaton because of its prefilter.
+///
+/// # Example
+///
+/// This follows the transitions for `ab` from the unanchored start state, and
+/// checks the patterns matched by the state it ends up in.
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, Anchored, PatternID};
+///
+/// let ac = AhoCorasick::new(&["ab", "b"]).unwrap();
+/// let graph = ac.graph();
+///
+/// let start = graph.start_state(Anchored::No)?;
+/// let after_a = graph
+///     .transitions(start)
+///     .find(|&(byte, _)| byte == b'a')
+///     .map(|(_, next)| next)
+///     .unwrap();
+/// let after_ab = graph
+///     .transitions(after_a)
+///     .find(|&(byte, _)| byte == b'b')
+///     .map(|(_, next)| next)
+///     .unwrap();
+/// assert!(graph.is_match(after_ab));
+/// assert_eq!(
+///     vec![PatternID::must(0), PatternID::must(1)],
+///     graph.matches(after_ab).collect::<Vec<_>>(),
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AutomatonGraph<'a> {
+    aut: AutomatonImpl<'a>,
+}
+
+impl<'a> AutomatonGraph<'a> {
+    pub(crate) fn new(aut: AutomatonImpl<'a>) -> AutomatonGraph<'a> {
+        AutomatonGraph { aut }
+    }
+
+    /// Returns the ID of every state in the automaton, in ascending order.
+    pub fn states(&self) -> impl Iterator<Item = StateID> + 'a {
+        let sids = match self.aut {
+            AutomatonImpl::NoncontiguousNFA(nfa) => (0..nfa.states().len())
+                .map(StateID::new_unchecked)
+                .filter(|&sid| sid != crate::nfa::noncontiguous::NFA::FAIL)
+                .collect(),
+            AutomatonImpl::ContiguousNFA(nfa) => nfa.state_ids(),
+            AutomatonImpl::DFA(dfa) => dfa.state_ids(),
+        };
+        sids.into_iter()
+    }
+
+    /// Returns the start state used by searches with the given anchored
+    /// mode.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the automaton doesn't support the given
+    /// anchored mode.
+    pub fn start_state(
+        &self,
+        anchored: Anchored,
+    ) -> Result<StateID, MatchError> {
+        self.aut.start_state(anchored)
+    }
+
+    /// Returns the transitions out of the given state, as pairs of a byte and
+    /// the state that the byte leads to, in ascending order of byte.
+    ///
+    /// For an NFA, a byte without a transition is handled by following the
+    /// [failure transition](AutomatonGraph::fail) of the state, and trying
+    /// again from there. For a DFA, every byte has a transition.
+    pub fn transitions(
+        &self,
+        sid: StateID,
+    ) -> impl Iterator<Item = (u8, StateID)> + 'a {
+        let trans: Vec<(u8, StateID)> = match self.aut {
+            AutomatonImpl::NoncontiguousNFA(nfa) => nfa.byte_transitions(sid),
+            AutomatonImpl::ContiguousNFA(nfa) => nfa.byte_transitions(sid),
+            AutomatonImpl::DFA(dfa) => dfa.byte_transitions(sid),
+        };
+        trans.into_iter()
+    }
+
+    /// Returns the failure transition of the given state, or `None` if the
+    /// automaton is a DFA.
+    ///
+    /// The failure transition of a state leads to the state of the longest
+    /// proper suffix of its prefix that is also a prefix of some pattern.
+    pub fn fail(&self, sid: StateID) -> Option<StateID> {
+        match self.aut {
+            AutomatonImpl::NoncontiguousNFA(nfa) => {
+                Some(nfa.states()[sid].fail())
+            }
+            AutomatonImpl::ContiguousNFA(nfa) => Some(nfa.fail(sid)),
+            AutomatonImpl::DFA(_) => None,
+        }
+    }
+
+    /// Returns the patterns matched when a search enters the given state.
+    ///
+    /// This is empty if and only if the state isn't a match state.
+    pub fn matches(
+        &self,
+        sid: StateID,
+    ) -> impl Iterator<Item = PatternID> + 'a {
+        let aut = self.aut;
+        let len = if aut.is_match(sid) { aut.match_len(sid) } else { 0 };
+        (0..len).map(move |index| aut.match_pattern(sid, index))
+    }
+
+    /// Returns the dictionary link of the given state, or `None` if it has
+    /// none or if the automaton is a DFA.
+    ///
+    /// The dictionary link of a state is the first match state reached by
+    /// following its failure transitions, which is the longest proper suffix
+    /// of its prefix that ends a match. The patterns
+    /// [matched](AutomatonGraph::matches) by a state are its
+    /// [own matches](AutomatonGraph::own_matches) followed by those of its
+    /// dictionary link, so a search that only needs to enumerate every
+    /// occurrence of every pattern may follow dictionary links from the
+    /// state at each position instead.
+    ///
+    /// With leftmost match semantics, failure transitions out of states that
+    /// follow a match lead to the dead state, so dictionary links are only
+    /// complete with [`MatchKind::Standard`](crate::MatchKind::Standard).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, AhoCorasickKind, Anchored, PatternID};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .kind(Some(AhoCorasickKind::NoncontiguousNFA))
+    ///     .build(&["abc", "bc", "c", "x"])
+    ///     .unwrap();
+    /// let graph = ac.graph();
+    ///
+    /// let mut sid = graph.start_state(Anchored::No)?;
+    /// for &byte in b"abc" {
+    ///     sid = graph
+    ///         .transitions(sid)
+    ///         .find(|&(b, _)| b == byte)
+    ///         .map(|(_, next)| next)
+    ///         .unwrap();
+    /// }
+    /// let mut own = vec![];
+    /// let mut link = Some(sid);
+    /// while let Some(sid) = link {
+    ///     own.push(graph.own_matches(sid).collect::<Vec<_>>());
+    ///     link = graph.dictionary_link(sid);
+    /// }
+    /// let pids = |ids: &[usize]| -> Vec<PatternID> {
+    ///     ids.iter().map(|&id| PatternID::must(id)).collect()
+    /// };
+    /// assert_eq!(vec![pids(&[0]), pids(&[1]), pids(&[2])], own);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dictionary_link(&self, sid: StateID) -> Option<StateID> {
+        let mut current = sid;
+        loop {
+            let fail = self.fail(current)?;
+            if self.aut.is_match(fail) {
+                return Some(fail);
+            }
+            // The failure transition of a start state leads to itself or to
+            // the dead state, which ends the chain.
+            if fail == current || self.aut.is_dead(fail) {
+                return None;
+            }
+            current = fail;
+        }
+    }
+
+    /// Returns the patterns matched by the given state that aren't matched
+    /// by its [dictionary link](AutomatonGraph::dictionary_link).
+    ///
+    /// For an NFA with [`MatchKind::Standard`](crate::MatchKind::Standard),
+    /// these are the patterns whose last byte leads to the state, and every
+    /// pattern is matched by exactly one state this way. For a DFA, which
+    /// has no dictionary links, these are the same as
+    /// [`AutomatonGraph::matches`].
+    pub fn own_matches(
+        &self,
+        sid: StateID,
+    ) -> impl Iterator<Item = PatternID> + 'a {
+        let inherited: Vec<PatternID> = match self.dictionary_link(sid) {
+            None => Vec::new(),
+            Some(link) => self.matches(link).collect(),
+        };
+        self.matches(sid).filter(move |pid| !inherited.contains(pid))
+    }
+
+    /// Returns true if and only if the given state is the dead state.
+    pub fn is_dead(&self, sid: StateID) -> bool {
+        self.aut.is_dead(sid)
+    }
+
+    /// Returns true if and only if the given state is a match state.
+    pub fn is_match(&self, sid: StateID) -> bool {
+        self.aut.is_match(sid)
+    }
+
+    /// Returns true if and only if the given state is a start state, either
+    /// anchored or unanchored.
+    pub fn is_start(&self, sid: StateID) -> bool {
+        self.aut.is_start(sid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        collections::{BTreeMap, BTreeSet},
+        vec,
+        vec::Vec,
+    };
+
+    use crate::{AhoCorasick, AhoCorasickKind, Anchored, MatchKind};
+
+    use super::*;
+
+    /// Returns the prefix leading to every state reachable from the start
+    /// state along with the patterns it matches, as found by following the
+    /// transitions and failure transitions of the graph like a search would.
+    fn walk(graph: &AutomatonGraph<'_>) -> BTreeMap<Vec<u8>, Vec<usize>> {
+        let next = |mut sid: StateID, byte: u8| loop {
+            if let Some((_, next)) =
+                graph.transitions(sid).find(|&(b, _)| b == byte)
+            {
+                return next;
+            }
+            sid = graph.fail(sid).unwrap();
+        };
+        let start = graph.start_state(Anchored::No).unwrap();
+        let mut found = BTreeMap::new();
+        let mut stack = vec![(start, vec![])];
+        while let Some((sid, prefix)) = stack.pop() {
+            let pids = graph.matches(sid).map(|pid| pid.as_usize()).collect();
+            found.insert(prefix.clone(), pids);
+            for byte in [b'a', b'b', b'c'] {
+                let next = next(sid, byte);
+                if prefix.len() < 4 && !graph.is_dead(next) {
+                    let mut prefix = prefix.clone();
+                    prefix.push(byte);
+                    stack.push((next, prefix));
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn dictionary_links_count_every_occurrence() {
+        let patterns = ["abc", "bc", "c", "ca", "abcab", "b"];
+        let haystack = b"abcabcaxbcab";
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            let ac = AhoCorasick::builder()
+                .kind(Some(kind))
+                .build(patterns)
+                .unwrap();
+            let graph = ac.graph();
+            let mut owners = vec![0; patterns.len()];
+            for sid in graph.states() {
+                let own: Vec<PatternID> = graph.own_matches(sid).collect();
+                let mut all = own.clone();
+                if let Some(link) = graph.dictionary_link(sid) {
+                    assert!(graph.is_match(link));
+                    all.extend(graph.matches(link));
+                }
+                assert_eq!(graph.matches(sid).collect::<Vec<_>>(), all);
+                for pid in own {
+                    owners[pid] += 1;
+                }
+            }
+            if kind == AhoCorasickKind::DFA {
+                continue;
+            }
+            // Each pattern is the own match of its state of the trie.
+            assert_eq!(vec![1; patterns.len()], owners);
+
+            let mut counts = vec![0; patterns.len()];
+            let mut sid = graph.start_state(Anchored::No).unwrap();
+            let mut count = |sid| {
+                let mut link = Some(sid);
+                while let Some(sid) = link {
+                    for pid in graph.own_matches(sid) {
+                        counts[pid] += 1;
+                    }
+                    link = graph.dictionary_link(sid);
+                }
+            };
+            count(sid);
+            for &byte in haystack.iter() {
+                sid = loop {
+                    match graph.transitions(sid).find(|&(b, _)| b == byte) {
+                        Some((_, next)) => break next,
+                        None => sid = graph.fail(sid).unwrap(),
+                    }
+                };
+                count(sid);
+            }
+            let mut expected = vec![0; patterns.len()];
+            for m in ac.find_overlapping_iter(&haystack[..]) {
+                expected[m.pattern()] += 1;
+            }
+            assert_eq!(expected, counts, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn every_state_is_reachable() {
+        use crate::StartKind;
+
+        let patterns = ["Sherlock", "Sherwood", "Holmes", "lock", "Wat", "s"];
+        for kind in [
+            AhoCorasickKind::NoncontiguousNFA,
+            AhoCorasickKind::ContiguousNFA,
+            AhoCorasickKind::DFA,
+        ] {
+            for match_kind in [
+                MatchKind::Standard,
+                MatchKind::LeftmostFirst,
+                MatchKind::LeftmostLongest,
+            ] {
+                for start_kind in [
+                    StartKind::Unanchored,
+                    StartKind::Anchored,
+                    StartKind::Both,
+                ] {
+                    let ac = AhoCorasick::builder()
+                        .kind(Some(kind))
+                        .match_kind(match_kind)
+                        .start_kind(start_kind)
+                        .prefilter(true)
+                        .build(patterns)
+                        .unwrap();
+                    let graph = ac.graph();
+                    let mut seen = BTreeSet::new();
+                    let mut stack: Vec<StateID> =
+                        [Anchored::No, Anchored::Yes]
+                            .into_iter()
+                            .filter_map(|a| graph.start_state(a).ok())
+                            .collect();
+                    while let Some(sid) = stack.pop() {
+                        if seen.insert(sid) {
+                            stack.extend(graph.transitions(sid).map(|t| t.1));
+                        }
+                    }
+                    let unreachable: Vec<StateID> = graph
+                        .states()
+                        .filter(|&sid| !graph.is_dead(sid))
+                        .filter(|sid| !seen.contains(sid))
+                        .collect();
+                    let unused_start = kind == AhoCorasickKind::DFA
+                        && start_kind != StartKind::Both;
+                    assert_eq!(
+                        usize::from(unused_start),
+                        unreachable.len(),
+                        "{:?}, {:?}, {:?}",
+                        kind,
+                        match_kind,
+                        start_kind,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_walk_for_every_kind() {
+        for match_kind in [MatchKind::Standard, MatchKind::LeftmostLongest] {
+            let mut walks = vec![];
+            for kind in [
+                AhoCorasickKind::NoncontiguousNFA,
+                AhoCorasickKind::ContiguousNFA,
+                AhoCorasickKind::DFA,
+            ] {
+                let ac = AhoCorasick::builder()
+                    .kind(Some(kind))
+                    .match_kind(match_kind)
+                    .build(["abc", "bc", "ca", "c"])
+                    .unwrap();
+                let graph = ac.graph();
+                let states: Vec<StateID> = graph.states().collect();
+                assert!(states.windows(2).all(|w| w[0] < w[1]));
+                for &sid in states.iter() {
+                    assert_eq!(
+                        graph.is_match(sid),
+                        graph.matches(sid).count() > 0
+                    );
+                    if kind == AhoCorasickKind::DFA {
+                        assert_eq!(None, graph.fail(sid));
+                        assert_eq!(256, graph.transitions(sid).count());
+                    } else {
+                        let fail = graph.fail(sid).unwrap();
+                        assert!(states.binary_search(&fail).is_ok());
+                    }
+                    for (_, next) in graph.transitions(sid) {
+                        assert!(states.binary_search(&next).is_ok());
+                    }
+                }
+                walks.push(walk(&graph));
+            }
+            assert_eq!(walks[0], walks[1]);
+            assert_eq!(walks[0], walks[2]);
+            if match_kind == MatchKind::Standard {
+                let mut pids = walks[0][&b"abc"[..]].clone();
+                pids.sort();
+                assert_eq!(vec![0, 1, 3], pids);
+            }
+        }
+    }
+}
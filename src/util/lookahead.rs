@@ -0,0 +1,159 @@
+/// A requirement on the bytes that follow a match of a pattern.
+///
+/// A lookahead requires that at least one of the bytes in a set occurs
+/// within a fixed number of bytes after the end of a match. A match that
+/// isn't followed by such a byte is rejected, as if the pattern didn't match
+/// there. When fewer bytes follow a match, because the haystack or stream
+/// ends, only those bytes are considered.
+///
+/// A lookahead is set for a pattern with
+/// [`AhoCorasickBuilder::lookahead`](crate::AhoCorasickBuilder::lookahead),
+/// which also documents which searches honor it. Since the number of bytes
+/// it considers is bounded, a streaming replacer only ever holds back that
+/// many bytes more than it otherwise would to decide whether a match is
+/// accepted.
+///
+/// # Example
+///
+/// This requires a host name to be followed by a dot within the next 16
+/// bytes, so that only host names with a top level domain are matched.
+///
+/// ```
+/// use aho_corasick::Lookahead;
+///
+/// let dot = Lookahead::new(16).bytes(b".");
+/// assert!(dot.accepts(b"example.com"));
+/// assert!(!dot.accepts(b"example"));
+///
+/// let letter = Lookahead::new(1).range(b'a', b'z');
+/// assert!(letter.accepts(b"com"));
+/// assert!(!letter.accepts(b".com"));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Lookahead {
+    /// Whether each byte is in the set, indexed by byte.
+    set: [bool; 256],
+    /// The number of bytes after a match that are considered.
+    within: usize,
+}
+
+impl Lookahead {
+    /// Create a lookahead that considers the given number of bytes after a
+    /// match, and whose set of bytes is empty.
+    ///
+    /// A lookahead with an empty set, or whose number of bytes is `0`,
+    /// rejects every match.
+    pub fn new(within: usize) -> Lookahead {
+        Lookahead { set: [false; 256], within }
+    }
+
+    /// Add the given bytes to the set of bytes that must follow a match.
+    pub fn bytes<B: AsRef<[u8]>>(mut self, bytes: B) -> Lookahead {
+        for &b in bytes.as_ref().iter() {
+            self.set[usize::from(b)] = true;
+        }
+        self
+    }
+
+    /// Add the given inclusive range of bytes to the set of bytes that must
+    /// follow a match.
+    pub fn range(mut self, start: u8, end: u8) -> Lookahead {
+        for b in start..=end {
+            self.set[usize::from(b)] = true;
+        }
+        self
+    }
+
+    /// Returns the number of bytes after a match that are considered.
+    pub fn within(&self) -> usize {
+        self.within
+    }
+
+    /// Returns true if the given byte is in the set of bytes that must
+    /// follow a match.
+    pub fn contains(&self, byte: u8) -> bool {
+        self.set[usize::from(byte)]
+    }
+
+    /// Returns true if a match followed by the given bytes is accepted.
+    ///
+    /// The given bytes are every byte after the match. Only the first
+    /// [`Lookahead::within`] of them are considered.
+    pub fn accepts(&self, after: &[u8]) -> bool {
+        self.accepts_within(self.within, after)
+    }
+
+    /// Decide whether a match is accepted, given the `seen` bytes after it
+    /// that didn't decide it followed by the bytes in `more`. This returns
+    /// `None` if all of `more` was considered without reaching a decision,
+    /// in which case more bytes are needed, unless there aren't any.
+    pub(crate) fn decide(&self, seen: usize, more: &[u8]) -> Option<bool> {
+        let remaining = self.within.saturating_sub(seen);
+        if self.accepts_within(remaining, more) {
+            Some(true)
+        } else if more.len() >= remaining {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if one of the first `within` bytes of `after` is in the
+    /// set.
+    fn accepts_within(&self, within: usize, after: &[u8]) -> bool {
+        let len = core::cmp::min(within, after.len());
+        after[..len].iter().any(|&b| self.contains(b))
+    }
+}
+
+/// Returns true if the given byte is a word byte, that is, an ASCII letter,
+/// an ASCII digit or an underscore.
+pub(crate) fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// Returns true if the given span of the haystack is bounded by non-word
+/// bytes, or by the start or end of the haystack. See
+/// [`AhoCorasickBuilder::word_boundary`](crate::AhoCorasickBuilder::word_boundary).
+pub(crate) fn is_word_bounded(
+    haystack: &[u8],
+    span: core::ops::Range<usize>,
+) -> bool {
+    let before = span.start.checked_sub(1).map(|i| haystack[i]);
+    let after = haystack.get(span.end).copied();
+    !before.map_or(false, is_word_byte) && !after.map_or(false, is_word_byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decide_across_chunks() {
+        let la = Lookahead::new(4).bytes(b".");
+        assert_eq!(Some(true), la.decide(0, b"ab.c"));
+        assert_eq!(Some(false), la.decide(0, b"abcd."));
+        assert_eq!(None, la.decide(0, b"ab"));
+        assert_eq!(Some(true), la.decide(2, b"c."));
+        assert_eq!(Some(false), la.decide(2, b"cd."));
+        assert_eq!(None, la.decide(2, b"c"));
+        assert_eq!(Some(false), la.decide(4, b"."));
+
+        let never = Lookahead::new(0).bytes(b".");
+        assert_eq!(Some(false), never.decide(0, b""));
+        assert!(!never.accepts(b"."));
+    }
+
+    #[test]
+    fn word_bounded() {
+        let haystack = b"a foo_bar-baz";
+        assert!(is_word_bounded(haystack, 0..1));
+        assert!(!is_word_bounded(haystack, 2..5));
+        assert!(is_word_bounded(haystack, 2..9));
+        assert!(is_word_bounded(haystack, 10..13));
+        assert!(!is_word_bounded(haystack, 11..13));
+        assert!(!is_word_bounded(haystack, 9..9));
+        assert!(is_word_bounded(b"a  b", 2..2));
+        assert!(is_word_bounded(b"", 0..0));
+    }
+}
@@ -1,12 +1,33 @@
 pub(crate) mod alphabet;
+#[cfg(feature = "arbitrary")]
+pub(crate) mod arbitrary;
 #[cfg(feature = "std")]
 pub(crate) mod buffer;
 pub(crate) mod byte_frequencies;
 pub(crate) mod debug;
+pub(crate) mod dedup;
 pub(crate) mod error;
+pub(crate) mod graph;
 pub(crate) mod int;
+pub(crate) mod lookahead;
+pub(crate) mod overlap;
+pub(crate) mod pattern_set;
+pub(crate) mod position;
 pub(crate) mod prefilter;
 pub(crate) mod primitives;
+pub(crate) mod profile;
+pub(crate) mod provenance;
+#[cfg(feature = "std")]
+pub(crate) mod rate;
 pub(crate) mod remapper;
+#[cfg(test)]
+pub(crate) mod rng;
 pub(crate) mod search;
+#[cfg(feature = "serde")]
+mod serde;
 pub(crate) mod special;
+#[cfg(feature = "std")]
+pub(crate) mod sync;
+pub(crate) mod top_k;
+pub(crate) mod validate;
+pub(crate) mod wire;
@@ -0,0 +1,221 @@
+use alloc::vec::Vec;
+
+use crate::util::search::Match;
+
+/// A policy that decides which of two partially overlapping matches is
+/// replaced.
+///
+/// By default, the matches that are replaced are the ones reported by a
+/// non-overlapping search, which depend on the
+/// [`MatchKind`](crate::MatchKind) of the automaton and, for streaming
+/// replacement with [`MatchKind::Standard`](crate::MatchKind::Standard)
+/// semantics, on which match ends first. An overlap policy replaces this
+/// with an explicit rule. It is set with
+/// [`AhoCorasickBuilder::overlap_policy`](crate::AhoCorasickBuilder::overlap_policy),
+/// and it is used in the same way by batch replacement, such as
+/// [`AhoCorasick::replace_all`](crate::AhoCorasick::replace_all), and by
+/// streaming replacement with an
+/// [`AhoCorasickReplacer`](crate::AhoCorasickReplacer), however the stream is
+/// split into chunks.
+///
+/// Every match of every pattern is considered, as reported by an overlapping
+/// search. The policy orders them from the match that wins against every
+/// other one, and each match is replaced unless it overlaps a match that
+/// comes before it in this order and that is replaced. Two matches overlap
+/// when each one starts before the other one ends, so matches that are
+/// back-to-back, or an empty match at either end of another match, don't
+/// overlap.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, OverlapPolicy};
+///
+/// let patterns = &["abc", "bcdef", "cd"];
+/// let replace = |policy| {
+///     let ac = AhoCorasick::builder()
+///         .overlap_policy(Some(policy))
+///         .build(patterns)
+///         .unwrap();
+///     ac.replace_all("abcdef", &["1", "2", "3"])
+/// };
+/// assert_eq!("1def", replace(OverlapPolicy::LeftmostWins));
+/// assert_eq!("a2", replace(OverlapPolicy::LongestWins));
+/// assert_eq!("1def", replace(OverlapPolicy::PriorityWins));
+///
+/// let ac = AhoCorasick::builder()
+///     .overlap_policy(Some(OverlapPolicy::PriorityWins))
+///     .build(&["cd", "abc"])
+///     .unwrap();
+/// assert_eq!("ab1ef", ac.replace_all("abcdef", &["1", "2"]));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OverlapPolicy {
+    /// The match that starts first wins. Matches that start at the same
+    /// position are ordered from the longest one, and then by pattern ID.
+    LeftmostWins,
+    /// The longest match wins. Matches with the same length are ordered
+    /// from the one that starts first, and then by pattern ID.
+    LongestWins,
+    /// The match of the pattern with the lowest ID wins. Matches of the same
+    /// pattern are ordered from the one that starts first.
+    PriorityWins,
+}
+
+impl OverlapPolicy {
+    /// Returns a key ordering matches from the one that wins against every
+    /// other one.
+    fn key(self, m: &Match) -> (usize, usize, usize) {
+        let (start, len, pid) = (m.start(), m.len(), m.pattern().as_usize());
+        match self {
+            OverlapPolicy::LeftmostWins => (start, usize::MAX - len, pid),
+            OverlapPolicy::LongestWins => (usize::MAX - len, start, pid),
+            OverlapPolicy::PriorityWins => (pid, start, 0),
+        }
+    }
+}
+
+/// Whether a match is replaced, as far as the matches seen so far tell.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Status {
+    Replaced,
+    Rejected,
+    /// A match that may still be overlapped by a match that wins against
+    /// it, either because it isn't decided yet or because it hasn't been
+    /// found yet.
+    Unknown,
+}
+
+/// Decides which matches are replaced under an overlap policy, as matches
+/// are found.
+///
+/// Matches may be added in any order, as long as the caller tells which
+/// offset every match added from then on starts at or after. This makes it
+/// possible to decide matches incrementally, with a window that only holds
+/// the matches that overlap the ones still undecided.
+#[derive(Clone, Debug)]
+pub(crate) struct OverlapResolver {
+    policy: OverlapPolicy,
+    /// The matches that weren't reported or rejected yet.
+    window: Vec<Match>,
+}
+
+impl OverlapResolver {
+    /// Create a resolver for the given policy, with no matches.
+    pub(crate) fn new(policy: OverlapPolicy) -> OverlapResolver {
+        OverlapResolver { policy, window: Vec::new() }
+    }
+
+    /// Add a match to decide. It must start at or after the last bound
+    /// given to `resolve`.
+    ///
+    /// A match that is already waiting to be decided is ignored, since an
+    /// overlapping search may report the match of an empty pattern twice.
+    pub(crate) fn add(&mut self, m: Match) {
+        if !self.window.contains(&m) {
+            self.window.push(m);
+        }
+    }
+
+    /// Forget every match added so far, so that the resolver can be reused
+    /// for a new stream.
+    pub(crate) fn clear(&mut self) {
+        self.window.clear();
+    }
+
+    /// Returns true if every match added was reported or rejected.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.window.is_empty()
+    }
+
+    /// Decide the matches added so far, given that every match added from
+    /// now on starts at or after `bound`, and push the ones that are
+    /// replaced to `replaced` in order of their start. Use `usize::MAX`
+    /// once no match will be added anymore.
+    ///
+    /// This returns an offset before which no match pushed by a later call
+    /// starts. Every byte before it that isn't part of a replaced match is
+    /// therefore known to be written unchanged.
+    pub(crate) fn resolve(
+        &mut self,
+        bound: usize,
+        replaced: &mut Vec<Match>,
+    ) -> usize {
+        if self.window.is_empty() {
+            return bound;
+        }
+        let policy = self.policy;
+        self.window.sort_by_key(|m| policy.key(m));
+        let mut status = Vec::with_capacity(self.window.len());
+        for (i, m) in self.window.iter().enumerate() {
+            let mut unknown = m.end() > bound;
+            let mut rejected = false;
+            for (better, &s) in self.window[..i].iter().zip(status.iter()) {
+                if !overlaps(better, m) {
+                    continue;
+                }
+                match s {
+                    Status::Replaced => rejected = true,
+                    Status::Unknown => unknown = true,
+                    Status::Rejected => {}
+                }
+            }
+            status.push(if rejected {
+                Status::Rejected
+            } else if unknown {
+                Status::Unknown
+            } else {
+                Status::Replaced
+            });
+        }
+        let settled = self
+            .window
+            .iter()
+            .zip(status.iter())
+            .filter(|&(_, &s)| s == Status::Unknown)
+            .map(|(m, _)| m.start())
+            .fold(bound, core::cmp::min);
+        let first = replaced.len();
+        let mut kept = Vec::with_capacity(self.window.len());
+        for (m, s) in self.window.drain(..).zip(status) {
+            match s {
+                Status::Replaced if m.start() < settled => replaced.push(m),
+                Status::Rejected => {}
+                _ => kept.push(m),
+            }
+        }
+        self.window = kept;
+        replaced[first..].sort_by_key(|m| (m.start(), m.end()));
+        settled
+    }
+}
+
+/// Returns true if each of the given matches starts before the other one
+/// ends.
+fn overlaps(a: &Match, b: &Match) -> bool {
+    a.start() < b.end() && b.start() < a.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn resolve_incrementally() {
+        let mut resolver = OverlapResolver::new(OverlapPolicy::PriorityWins);
+        let mut replaced = Vec::new();
+        resolver.add(Match::must(1, 0..3));
+        // A match of pattern 0 may still overlap it.
+        assert_eq!(0, resolver.resolve(1, &mut replaced));
+        assert!(replaced.is_empty());
+        resolver.add(Match::must(0, 2..5));
+        resolver.add(Match::must(1, 5..8));
+        // The match at 5..8 may still be overlapped.
+        assert_eq!(5, resolver.resolve(6, &mut replaced));
+        assert_eq!(vec![Match::must(0, 2..5)], replaced);
+        assert_eq!(usize::MAX, resolver.resolve(usize::MAX, &mut replaced));
+        assert_eq!(vec![Match::must(0, 2..5), Match::must(1, 5..8)], replaced);
+    }
+}
@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+
+use crate::util::primitives::PatternID;
+
+/// A set of pattern IDs that a search is restricted to.
+///
+/// A set is given to a search with
+/// [`Input::pattern_set`](crate::Input::pattern_set), in which case an
+/// [`AhoCorasick`](crate::AhoCorasick) searcher only reports the matches of
+/// the patterns in the set, as if it had been built from those patterns
+/// alone. This permits several users with different patterns to share one
+/// large searcher, instead of building a searcher for each of them.
+///
+/// A set grows as needed when patterns are inserted, so it may contain IDs
+/// that aren't valid for the searcher it's used with. They are ignored.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, Input, Match, PatternID, PatternSet};
+///
+/// let ac = AhoCorasick::builder()
+///     .store_patterns(true)
+///     .build(&["apple", "banana", "cherry"])
+///     .unwrap();
+/// let haystack = "banana, apple and cherry";
+///
+/// let set: PatternSet =
+///     [PatternID::must(0), PatternID::must(2)].into_iter().collect();
+/// let input = Input::new(haystack).pattern_set(Some(&set));
+/// let matches: Vec<Match> = ac.find_iter(input).collect();
+/// assert_eq!(vec![Match::must(0, 8..13), Match::must(2, 18..24)], matches);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PatternSet {
+    /// Whether each pattern is in the set, indexed by pattern ID. This only
+    /// extends up to the largest ID ever inserted.
+    which: Vec<bool>,
+    /// The number of patterns in the set.
+    len: usize,
+}
+
+impl PatternSet {
+    /// Create a new empty set.
+    pub fn new() -> PatternSet {
+        PatternSet::default()
+    }
+
+    /// Insert the given pattern into this set, and return true if it wasn't
+    /// in the set already.
+    pub fn insert(&mut self, pattern: PatternID) -> bool {
+        let i = pattern.as_usize();
+        if self.which.len() <= i {
+            self.which.resize(i + 1, false);
+        }
+        if self.which[i] {
+            return false;
+        }
+        self.which[i] = true;
+        self.len += 1;
+        true
+    }
+
+    /// Remove the given pattern from this set, and return true if it was in
+    /// the set.
+    pub fn remove(&mut self, pattern: PatternID) -> bool {
+        match self.which.get_mut(pattern.as_usize()) {
+            Some(yes) if *yes => {
+                *yes = false;
+                self.len -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the given pattern is in this set.
+    #[inline]
+    pub fn contains(&self, pattern: PatternID) -> bool {
+        self.which.get(pattern.as_usize()).map_or(false, |&yes| yes)
+    }
+
+    /// Returns the number of patterns in this set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if this set has no patterns.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl FromIterator<PatternID> for PatternSet {
+    fn from_iter<I: IntoIterator<Item = PatternID>>(it: I) -> PatternSet {
+        let mut set = PatternSet::new();
+        set.extend(it);
+        set
+    }
+}
+
+impl Extend<PatternID> for PatternSet {
+    fn extend<I: IntoIterator<Item = PatternID>>(&mut self, it: I) {
+        for pattern in it {
+            self.insert(pattern);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut set = PatternSet::new();
+        assert!(set.is_empty());
+        assert!(!set.contains(PatternID::must(3)));
+        assert!(set.insert(PatternID::must(3)));
+        assert!(!set.insert(PatternID::must(3)));
+        assert!(set.insert(PatternID::must(0)));
+        assert_eq!(2, set.len());
+        assert!(set.contains(PatternID::must(0)));
+        assert!(!set.contains(PatternID::must(1)));
+        assert!(!set.contains(PatternID::must(100)));
+
+        assert!(set.remove(PatternID::must(3)));
+        assert!(!set.remove(PatternID::must(3)));
+        assert!(!set.remove(PatternID::must(100)));
+        assert_eq!(1, set.len());
+        assert!(!set.contains(PatternID::must(3)));
+
+        let set: PatternSet =
+            PatternID::iter(4).chain(PatternID::iter(2)).collect();
+        assert_eq!(4, set.len());
+    }
+}
@@ -0,0 +1,120 @@
+/*!
+Provides line and column tracking for reporting match positions to humans.
+*/
+
+/// The unit in which columns are counted by a line and column tracker.
+///
+/// The default is [`ColumnUnit::Byte`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ColumnUnit {
+    /// Count each byte as one column.
+    Byte,
+    /// Count each UTF-8 encoded codepoint as one column.
+    ///
+    /// The haystack is not required to be valid UTF-8. Every byte that does
+    /// not continue a UTF-8 encoding of a codepoint counts as one column.
+    /// That is, each byte in the range `\x80-\xBF` is not counted, and every
+    /// other byte is.
+    Char,
+}
+
+impl Default for ColumnUnit {
+    fn default() -> ColumnUnit {
+        ColumnUnit::Byte
+    }
+}
+
+/// A line and column in a haystack.
+///
+/// Both lines and columns start at `1`. Lines are terminated by `\n`, and a
+/// `\r` preceding a `\n` is counted as part of the line it terminates.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LineColumn {
+    line: usize,
+    column: usize,
+}
+
+impl LineColumn {
+    /// Returns the line number, starting at `1`.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column number, starting at `1`. The unit of the column
+    /// depends on the [`ColumnUnit`] used to compute it.
+    #[inline]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// Tracks the line and column of the end of the bytes it has observed.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub(crate) struct LineColumnTracker {
+    unit: ColumnUnit,
+    pos: LineColumn,
+}
+
+#[cfg(feature = "std")]
+impl LineColumnTracker {
+    /// Create a new tracker positioned at the start of a haystack.
+    pub(crate) fn new(unit: ColumnUnit) -> LineColumnTracker {
+        LineColumnTracker { unit, pos: LineColumn { line: 1, column: 1 } }
+    }
+
+    /// Returns the position immediately following the last byte observed.
+    pub(crate) fn position(&self) -> LineColumn {
+        self.pos
+    }
+
+    /// Observe the given bytes, which must immediately follow the bytes
+    /// previously observed.
+    pub(crate) fn advance(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter() {
+            if byte == b'\n' {
+                self.pos.line += 1;
+                self.pos.column = 1;
+            } else if self.unit == ColumnUnit::Byte
+                || !(0x80..=0xBF).contains(&byte)
+            {
+                self.pos.column += 1;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn position(unit: ColumnUnit, chunks: &[&str]) -> (usize, usize) {
+        let mut tracker = LineColumnTracker::new(unit);
+        for chunk in chunks.iter() {
+            tracker.advance(chunk.as_bytes());
+        }
+        let pos = tracker.position();
+        (pos.line(), pos.column())
+    }
+
+    #[test]
+    fn lines_and_columns() {
+        assert_eq!((1, 1), position(ColumnUnit::Byte, &[]));
+        assert_eq!((1, 4), position(ColumnUnit::Byte, &["abc"]));
+        assert_eq!((3, 2), position(ColumnUnit::Byte, &["a\r\nb\n", "c"]));
+        assert_eq!((2, 1), position(ColumnUnit::Char, &["abc\n"]));
+
+        // 'δ' is two bytes and '☃' is three.
+        assert_eq!((1, 6), position(ColumnUnit::Byte, &["δ☃"]));
+        assert_eq!((1, 3), position(ColumnUnit::Char, &["δ☃"]));
+        // Splitting a codepoint across chunks doesn't change anything.
+        let snowman = "☃";
+        let (a, b) = snowman.as_bytes().split_at(1);
+        let mut tracker = LineColumnTracker::new(ColumnUnit::Char);
+        tracker.advance(a);
+        tracker.advance(b);
+        assert_eq!(2, tracker.position().column());
+    }
+}
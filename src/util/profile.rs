@@ -0,0 +1,313 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+
+use crate::{
+    ahocorasick::{AcAutomaton, AhoCorasickKind},
+    automaton::{Automaton, StateID},
+    util::{error::MatchError, search::Anchored},
+};
+
+/// Records how often each state of an automaton is visited while scanning
+/// haystacks, so that the states, and the pattern prefixes they correspond
+/// to, that searches spend the most time in can be found.
+///
+/// A profiler is created by
+/// [`AhoCorasick::profiler`](crate::AhoCorasick::profiler). Every byte given
+/// to [`SearchProfiler::scan`] moves the automaton to its next state, just
+/// like an unanchored search does, except that no prefilter is used and the
+/// scan carries on after a match. This makes the profile independent of the
+/// search routine used, at the cost of being much slower than a search. It
+/// is meant to be run on a few representative haystacks while tuning a
+/// dictionary.
+///
+/// A state at depth `0` is the start state, and a state at depth `n`
+/// corresponds to a prefix of length `n` of some pattern. A scan that
+/// spends most of its time in deep states, or in a few hot states, usually
+/// indicates patterns that share a prefix that is common in the haystacks.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(&["the cat", "the dog", "zebra"]).unwrap();
+/// let mut profiler = ac.profiler()?;
+/// profiler.scan("the end of the story, then the dog");
+/// let profile = profiler.profile();
+///
+/// // Depth 3 is 'the', depth 4 is 'the '.
+/// assert_eq!(34, profile.bytes_scanned());
+/// assert_eq!(4, profile.depth_histogram()[3]);
+/// assert_eq!(3, profile.depth_histogram()[4]);
+/// // The hottest state other than the start state.
+/// let hot = &profile.states()[1];
+/// assert_eq!(b"t", hot.prefix());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SearchProfiler {
+    aut: Arc<dyn AcAutomaton>,
+    kind: AhoCorasickKind,
+    start: StateID,
+    visits: BTreeMap<StateID, u64>,
+    bytes: u64,
+}
+
+impl SearchProfiler {
+    pub(crate) fn new(
+        aut: Arc<dyn AcAutomaton>,
+        kind: AhoCorasickKind,
+    ) -> Result<SearchProfiler, MatchError> {
+        let start = aut.start_state(Anchored::No)?;
+        Ok(SearchProfiler {
+            aut,
+            kind,
+            start,
+            visits: BTreeMap::new(),
+            bytes: 0,
+        })
+    }
+
+    /// Scan the given haystack from the start state, recording the state
+    /// visited after each byte.
+    ///
+    /// Each haystack is scanned independently from the ones before it.
+    pub fn scan<H: ?Sized + AsRef<[u8]>>(&mut self, haystack: &H) {
+        let aut = self.aut.as_ref().coerce_concrete(self.kind);
+        let mut sid = self.start;
+        for &byte in haystack.as_ref().iter() {
+            sid = aut.next_state(Anchored::No, sid, byte);
+            if aut.is_dead(sid) {
+                // With leftmost semantics, the search stops and restarts
+                // after a match instead of carrying on.
+                sid = aut.next_state(Anchored::No, self.start, byte);
+            }
+            *self.visits.entry(sid).or_insert(0) += 1;
+        }
+        self.bytes += haystack.as_ref().len() as u64;
+    }
+
+    /// Returns the profile of every haystack scanned so far.
+    ///
+    /// This computes the depth and prefix of every state visited, which
+    /// requires a walk over the entire automaton.
+    pub fn profile(&self) -> SearchProfile {
+        let aut = self.aut.as_ref().coerce_concrete(self.kind);
+        // Every transition increases the depth by at most one, so the
+        // length of a shortest path from the start state to a state is its
+        // depth, and the bytes along it are its prefix.
+        let mut prefixes: BTreeMap<StateID, Vec<u8>> = BTreeMap::new();
+        prefixes.insert(self.start, vec![]);
+        let mut queue = vec![self.start];
+        let mut at = 0;
+        // The number of visited states whose prefix hasn't been found yet.
+        // The walk stops early once there are none left.
+        let mut missing = self.visits.len()
+            - usize::from(self.visits.contains_key(&self.start));
+        while at < queue.len() && missing > 0 {
+            let sid = queue[at];
+            at += 1;
+            for byte in 0..=255u8 {
+                let next = aut.next_state(Anchored::No, sid, byte);
+                if aut.is_dead(next) || prefixes.contains_key(&next) {
+                    continue;
+                }
+                if self.visits.contains_key(&next) {
+                    missing -= 1;
+                }
+                let mut prefix = prefixes[&sid].clone();
+                prefix.push(byte);
+                prefixes.insert(next, prefix);
+                queue.push(next);
+            }
+        }
+
+        let mut states: Vec<StateVisits> = self
+            .visits
+            .iter()
+            .map(|(&state, &visits)| StateVisits {
+                state,
+                prefix: prefixes.get(&state).cloned().unwrap_or_default(),
+                visits,
+            })
+            .collect();
+        states.sort_by(|a, b| {
+            b.visits.cmp(&a.visits).then_with(|| a.prefix.cmp(&b.prefix))
+        });
+        let max_depth = states.iter().map(|s| s.depth()).max().unwrap_or(0);
+        let mut depths = vec![0; max_depth + 1];
+        for state in states.iter() {
+            depths[state.depth()] += state.visits;
+        }
+        SearchProfile { bytes: self.bytes, depths, states }
+    }
+
+    /// Forget every visit recorded so far.
+    pub fn reset(&mut self) {
+        self.visits.clear();
+        self.bytes = 0;
+    }
+}
+
+/// The result of profiling searches with a [`SearchProfiler`].
+///
+/// The `Display` impl of a profile writes the depth histogram, followed by
+/// the ten hottest states.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchProfile {
+    bytes: u64,
+    depths: Vec<u64>,
+    states: Vec<StateVisits>,
+}
+
+impl SearchProfile {
+    /// Returns the total number of bytes scanned.
+    pub fn bytes_scanned(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the number of visits to states at each depth, indexed by
+    /// depth. The visits add up to the number of bytes scanned.
+    pub fn depth_histogram(&self) -> &[u64] {
+        &self.depths
+    }
+
+    /// Returns every state that was visited at least once, from the most
+    /// visited to the least visited.
+    pub fn states(&self) -> &[StateVisits] {
+        &self.states
+    }
+}
+
+impl core::fmt::Display for SearchProfile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const BAR_WIDTH: u128 = 40;
+
+        writeln!(f, "bytes scanned: {}", self.bytes)?;
+        writeln!(f, "depth      visits  share")?;
+        let total = core::cmp::max(1, self.bytes);
+        for (depth, &visits) in self.depths.iter().enumerate() {
+            let bar = u128::from(visits) * BAR_WIDTH / u128::from(total);
+            write!(
+                f,
+                "{:>5} {:>11} {:>5.1}% ",
+                depth,
+                visits,
+                100.0 * visits as f64 / total as f64,
+            )?;
+            for _ in 0..bar {
+                write!(f, "#")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "hottest states:")?;
+        for state in self.states.iter().take(10) {
+            writeln!(
+                f,
+                "{:>11} depth {:>3} \"{}\"",
+                state.visits,
+                state.depth(),
+                crate::dictionary::escape(&state.prefix),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of visits to a single state, as recorded by a
+/// [`SearchProfiler`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateVisits {
+    state: StateID,
+    prefix: Vec<u8>,
+    visits: u64,
+}
+
+impl StateVisits {
+    /// Returns the ID of the state.
+    pub fn state(&self) -> StateID {
+        self.state
+    }
+
+    /// Returns the depth of the state, which is the length of its prefix.
+    pub fn depth(&self) -> usize {
+        self.prefix.len()
+    }
+
+    /// Returns the bytes that lead from the start state to this state. This
+    /// is a prefix of at least one pattern. When ASCII case insensitivity
+    /// is enabled, the prefix is given in one of its cases.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Returns the number of times the state was visited.
+    pub fn visits(&self) -> u64 {
+        self.visits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use crate::{AhoCorasick, AhoCorasickKind, MatchKind};
+
+    #[test]
+    fn same_profile_for_every_kind() {
+        let patterns = ["foo", "foobar", "bar", "oof"];
+        let haystack = "xfoobar foofoo barfoobarx oof";
+        for match_kind in [MatchKind::Standard, MatchKind::LeftmostFirst] {
+            let mut profiles = alloc::vec![];
+            for kind in [
+                AhoCorasickKind::NoncontiguousNFA,
+                AhoCorasickKind::ContiguousNFA,
+                AhoCorasickKind::DFA,
+            ] {
+                let ac = AhoCorasick::builder()
+                    .kind(Some(kind))
+                    .match_kind(match_kind)
+                    .build(patterns)
+                    .unwrap();
+                let mut profiler = ac.profiler().unwrap();
+                profiler.scan(haystack);
+                profiler.scan("");
+                profiler.scan("fo");
+                let profile = profiler.profile();
+                assert_eq!(31, profile.bytes_scanned());
+                assert_eq!(31, profile.depth_histogram().iter().sum::<u64>());
+                let prefixes: alloc::vec::Vec<(alloc::vec::Vec<u8>, u64)> =
+                    profile
+                        .states()
+                        .iter()
+                        .map(|s| (s.prefix().to_vec(), s.visits()))
+                        .collect();
+                profiles.push((profile.depth_histogram().to_vec(), prefixes));
+            }
+            assert_eq!(profiles[0], profiles[1]);
+            assert_eq!(profiles[0], profiles[2]);
+        }
+    }
+
+    #[test]
+    fn display_and_reset() {
+        let ac = AhoCorasick::new(["ab"]).unwrap();
+        let mut profiler = ac.profiler().unwrap();
+        profiler.scan("abxa");
+        let text = profiler.profile().to_string();
+        assert!(text.starts_with("bytes scanned: 4\n"), "{}", text);
+        assert!(text.contains("    1           2  50.0% ####"), "{}", text);
+        assert!(text.contains("          2 depth   1 \"a\""), "{}", text);
+
+        profiler.reset();
+        let profile = profiler.profile();
+        assert_eq!(0, profile.bytes_scanned());
+        assert_eq!(&[0], profile.depth_histogram());
+        assert!(profile.states().is_empty());
+
+        let ac = AhoCorasick::builder()
+            .start_kind(crate::StartKind::Anchored)
+            .build(["ab"])
+            .unwrap();
+        assert!(ac.profiler().is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use core::ops::Range;
+
+use alloc::vec::Vec;
+
+use crate::util::primitives::PatternID;
+
+/// The patterns that each pattern of an automaton is identical to once
+/// folded, which are the patterns that a match of it originates from.
+///
+/// This is built when
+/// [`AhoCorasickBuilder::track_provenance`](crate::AhoCorasickBuilder::track_provenance)
+/// is enabled.
+#[derive(Clone, Debug)]
+pub(crate) struct Provenance {
+    /// Every pattern ID, such that patterns that are identical once folded
+    /// are adjacent and in ascending order.
+    grouped: Vec<PatternID>,
+    /// The range of `grouped` holding the patterns identical to each
+    /// pattern, indexed by pattern ID.
+    groups: Vec<Range<usize>>,
+}
+
+impl Provenance {
+    /// Group the given patterns by their bytes once folded, which only folds
+    /// the case of ASCII letters when ASCII case insensitivity is enabled.
+    ///
+    /// This folds patterns the same way as the trie of an automaton, where
+    /// two patterns end in the same state when they're identical once
+    /// folded.
+    pub(crate) fn new(
+        patterns: &[Vec<u8>],
+        ascii_case_insensitive: bool,
+    ) -> Provenance {
+        let fold = |pid: PatternID| {
+            patterns[pid].iter().map(move |&b| {
+                if ascii_case_insensitive {
+                    b.to_ascii_lowercase()
+                } else {
+                    b
+                }
+            })
+        };
+        let mut grouped: Vec<PatternID> =
+            PatternID::iter(patterns.len()).collect();
+        grouped.sort_by(|&a, &b| fold(a).cmp(fold(b)).then(a.cmp(&b)));
+        let mut groups = alloc::vec![0..0; patterns.len()];
+        let mut start = 0;
+        while start < grouped.len() {
+            let end = start
+                + grouped[start..]
+                    .iter()
+                    .take_while(|&&pid| fold(pid).eq(fold(grouped[start])))
+                    .count();
+            for &pid in grouped[start..end].iter() {
+                groups[pid] = start..end;
+            }
+            start = end;
+        }
+        Provenance { grouped, groups }
+    }
+
+    /// Returns the patterns identical to the given pattern once folded,
+    /// including itself, in ascending order.
+    pub(crate) fn origins(&self, pid: PatternID) -> Option<&[PatternID]> {
+        let range = self.groups.get(pid.as_usize())?;
+        Some(&self.grouped[range.clone()])
+    }
+
+    /// Returns the heap memory used by this provenance, in bytes.
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.grouped.len() * core::mem::size_of::<PatternID>()
+            + self.groups.len() * core::mem::size_of::<Range<usize>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn origins(provenance: &Provenance, pid: usize) -> Vec<usize> {
+        provenance
+            .origins(PatternID::must(pid))
+            .unwrap()
+            .iter()
+            .map(|pid| pid.as_usize())
+            .collect()
+    }
+
+    #[test]
+    fn groups() {
+        let patterns: Vec<Vec<u8>> =
+            ["foo", "Foo", "fo", "", "foo", "FOO", ""]
+                .iter()
+                .map(|p| p.as_bytes().to_vec())
+                .collect();
+
+        let provenance = Provenance::new(&patterns, false);
+        assert_eq!(vec![0, 4], origins(&provenance, 0));
+        assert_eq!(vec![0, 4], origins(&provenance, 4));
+        assert_eq!(vec![1], origins(&provenance, 1));
+        assert_eq!(vec![3, 6], origins(&provenance, 6));
+        assert!(provenance.origins(PatternID::must(7)).is_none());
+
+        let provenance = Provenance::new(&patterns, true);
+        assert_eq!(vec![0, 1, 4, 5], origins(&provenance, 5));
+        assert_eq!(vec![2], origins(&provenance, 2));
+        assert_eq!(vec![3, 6], origins(&provenance, 3));
+
+        let provenance = Provenance::new(&[], true);
+        assert!(provenance.origins(PatternID::must(0)).is_none());
+    }
+}
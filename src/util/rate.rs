@@ -0,0 +1,175 @@
+use alloc::vec::Vec;
+
+use crate::util::{primitives::PatternID, search::Match};
+
+/// Tracks an exponentially decayed rate of matches of each pattern, which
+/// can be used to detect a pattern that suddenly starts matching much more
+/// often than it used to.
+///
+/// Time is measured in bytes of the haystack or stream, and a match occurs
+/// at its end offset. Each match adds a weight of `1` that halves every
+/// `half_life` bytes, so that recent matches count more than old ones. The
+/// rate of a pattern is the sum of the weights of its matches, scaled such
+/// that a pattern matching once every `n` bytes for long enough has a rate
+/// that approaches `1 / n` matches per byte.
+///
+/// The current offset is the largest end offset seen so far, and may be
+/// moved forward without a match with [`MatchRates::advance`], so that the
+/// rates of patterns that stopped matching decay.
+///
+/// Matches can be given one at a time with [`MatchRates::add`], or rates
+/// can be updated as a stream is searched with
+/// [`StreamFindIter::track_rates`](crate::StreamFindIter::track_rates).
+///
+/// This is only available when the `std` feature is enabled.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, MatchRates, PatternID};
+///
+/// let ac = AhoCorasick::new(&["ok", "fail"]).unwrap();
+/// let quiet = "ok ".repeat(100);
+/// let noisy = "fail ".repeat(20);
+/// let log = format!("{}{}", quiet, noisy);
+///
+/// let mut rates = MatchRates::new(50);
+/// let mut before = 0.0;
+/// for m in ac.find_iter(&log) {
+///     if m.start() == quiet.len() {
+///         before = rates.rate(PatternID::must(1));
+///     }
+///     rates.add(&m);
+/// }
+/// let (ok, fail) = (PatternID::must(0), PatternID::must(1));
+/// assert_eq!(0.0, before);
+/// assert!(rates.rate(fail) > rates.rate(ok));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MatchRates {
+    /// The number of bytes after which the weight of a match halves.
+    half_life: f64,
+    /// The current offset.
+    now: usize,
+    /// The decayed sum of the weights of the matches of each pattern, as of
+    /// the offset it was last updated at, indexed by pattern ID.
+    sums: Vec<(f64, usize)>,
+}
+
+impl MatchRates {
+    /// Create a new tracker in which the weight of a match halves every
+    /// `half_life` bytes.
+    ///
+    /// # Panics
+    ///
+    /// This panics when `half_life` is zero.
+    pub fn new(half_life: usize) -> MatchRates {
+        assert!(half_life > 0, "half-life of match rates must be non-zero");
+        MatchRates { half_life: half_life as f64, now: 0, sums: Vec::new() }
+    }
+
+    /// Add the given match, which also moves the current offset to its end
+    /// if it's past it.
+    pub fn add(&mut self, m: &Match) {
+        self.advance(m.end());
+        let i = m.pattern().as_usize();
+        if self.sums.len() <= i {
+            self.sums.resize(i + 1, (0.0, 0));
+        }
+        let now = self.now;
+        let sum = self.decayed(self.sums[i]);
+        self.sums[i] = (sum + 1.0, now);
+    }
+
+    /// Move the current offset forward to the given offset. This does
+    /// nothing if the current offset is already past it.
+    pub fn advance(&mut self, offset: usize) {
+        self.now = core::cmp::max(self.now, offset);
+    }
+
+    /// Returns the decayed rate of matches of the given pattern as of the
+    /// current offset, in matches per byte. This is zero for a pattern that
+    /// hasn't matched.
+    pub fn rate(&self, pattern: PatternID) -> f64 {
+        match self.sums.get(pattern.as_usize()) {
+            None => 0.0,
+            Some(&sum) => {
+                self.decayed(sum) * core::f64::consts::LN_2 / self.half_life
+            }
+        }
+    }
+
+    /// Returns the current offset.
+    pub fn offset(&self) -> usize {
+        self.now
+    }
+
+    /// Forget every match added so far and move the current offset back to
+    /// zero, so that the tracker can be reused for a new haystack or stream.
+    pub fn reset(&mut self) {
+        self.now = 0;
+        self.sums.clear();
+    }
+
+    /// Returns the given sum, last updated at the given offset, decayed to
+    /// the current offset.
+    fn decayed(&self, (sum, at): (f64, usize)) -> f64 {
+        if sum == 0.0 {
+            return 0.0;
+        }
+        let elapsed = (self.now - at) as f64;
+        sum * (-elapsed / self.half_life).exp2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(expected: f64, got: f64) -> bool {
+        (expected - got).abs() <= expected.abs() * 1e-9
+    }
+
+    #[test]
+    fn decays() {
+        let mut rates = MatchRates::new(10);
+        let ln2 = core::f64::consts::LN_2;
+        rates.add(&Match::must(1, 5..10));
+        assert!(close(ln2 / 10.0, rates.rate(PatternID::must(1))));
+        assert_eq!(0.0, rates.rate(PatternID::must(0)));
+        assert_eq!(0.0, rates.rate(PatternID::must(2)));
+
+        // The weight of the match halves after 10 bytes, and a match at the
+        // same offset adds its whole weight.
+        rates.advance(20);
+        assert!(close(ln2 / 20.0, rates.rate(PatternID::must(1))));
+        rates.add(&Match::must(1, 18..20));
+        assert!(close(1.5 * ln2 / 10.0, rates.rate(PatternID::must(1))));
+
+        // Moving backwards does nothing.
+        rates.advance(0);
+        assert_eq!(20, rates.offset());
+        rates.add(&Match::must(0, 0..1));
+        assert!(close(ln2 / 10.0, rates.rate(PatternID::must(0))));
+
+        rates.reset();
+        assert_eq!(0, rates.offset());
+        assert_eq!(0.0, rates.rate(PatternID::must(1)));
+    }
+
+    #[test]
+    fn approaches_steady_rate() {
+        let mut rates = MatchRates::new(64);
+        for i in 0..10_000 {
+            rates.add(&Match::must(0, (i * 4)..(i * 4 + 1)));
+        }
+        let rate = rates.rate(PatternID::must(0));
+        assert!((rate - 0.25).abs() < 0.01, "rate was {}", rate);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_half_life() {
+        MatchRates::new(0);
+    }
+}
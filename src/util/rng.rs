@@ -0,0 +1,35 @@
+use alloc::vec::Vec;
+
+/// A small deterministic random number generator, for tests that compare
+/// searchers on random patterns and haystacks.
+///
+/// This is a xorshift generator, so the same seed always produces the same
+/// patterns and haystacks, and a failure can be reproduced.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Create a new generator from the given seed, which must not be zero.
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    /// Returns a number less than `n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % (n as u64)) as usize
+    }
+
+    /// Returns a word of `min` to `max` bytes, inclusive, drawn from the
+    /// given alphabet.
+    pub(crate) fn word(
+        &mut self,
+        min: usize,
+        max: usize,
+        alphabet: &[u8],
+    ) -> Vec<u8> {
+        let len = min + self.below(max - min + 1);
+        (0..len).map(|_| alphabet[self.below(alphabet.len())]).collect()
+    }
+}
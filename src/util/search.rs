@@ -1,6 +1,8 @@
 use core::ops::{Range, RangeBounds};
 
-use crate::util::primitives::PatternID;
+use alloc::vec::Vec;
+
+use crate::util::{pattern_set::PatternSet, primitives::PatternID};
 
 /// The configuration and the haystack to use for an Aho-Corasick search.
 ///
@@ -85,6 +87,9 @@ pub struct Input<'h> {
     span: Span,
     anchored: Anchored,
     earliest: bool,
+    exact_case: bool,
+    verify: bool,
+    pattern_set: Option<&'h PatternSet>,
 }
 
 impl<'h> Input<'h> {
@@ -96,6 +101,9 @@ impl<'h> Input<'h> {
             span: Span { start: 0, end: haystack.as_ref().len() },
             anchored: Anchored::No,
             earliest: false,
+            exact_case: false,
+            verify: true,
+            pattern_set: None,
         }
     }
 
@@ -110,6 +118,20 @@ impl<'h> Input<'h> {
     ///
     /// Note that [`Input::range`] overrides this method and vice versa.
     ///
+    /// Restricting the span is not the same as slicing the haystack. Matches
+    /// are always reported with offsets into the full haystack, and the start
+    /// of the span is treated as the start of the search. That is, an
+    /// anchored search requires a match to begin at the start of the span,
+    /// and a match that begins before the span (or ends after it) is never
+    /// reported. The bytes outside the span are still part of the haystack
+    /// when a match is verified, though: with
+    /// [word boundaries](crate::AhoCorasickBuilder::word_boundary), the
+    /// bytes just before and after a match are checked even when they're
+    /// outside the span, and so are the bytes after a match checked by a
+    /// [lookahead](crate::AhoCorasickBuilder::lookahead). Without these
+    /// options, the bytes outside the span never influence which matches are
+    /// found.
+    ///
     /// # Panics
     ///
     /// This panics if the given span does not correspond to valid bounds in
@@ -304,6 +326,135 @@ impl<'h> Input<'h> {
         self
     }
 
+    /// Whether to require matches to have the exact case of their pattern.
+    ///
+    /// This only has an effect when searching with an
+    /// [`AhoCorasick`](crate::AhoCorasick) searcher that was built with
+    /// [`AhoCorasickBuilder::ascii_case_insensitive`](crate::AhoCorasickBuilder::ascii_case_insensitive)
+    /// enabled. The searcher then reports the same matches as a searcher
+    /// built from the same patterns without ASCII case insensitivity. This
+    /// avoids building a second automaton for the occasional search that
+    /// needs to respect case.
+    ///
+    /// This works by verifying each match against the pattern it reports,
+    /// and so requires a searcher built with
+    /// [`AhoCorasickBuilder::store_patterns`](crate::AhoCorasickBuilder::store_patterns)
+    /// enabled. Otherwise, searching returns an error (or panics for
+    /// infallible search methods). When a match is rejected, the search
+    /// resumes just after its start, so an exact case search can be much
+    /// slower than a normal one when the haystack has many matches that
+    /// differ in case only. The "earliest" option is ignored by exact case
+    /// searches.
+    ///
+    /// The searchers in the [`nfa`](crate::nfa) and [`dfa`](crate::dfa)
+    /// modules don't store their patterns, and ignore this option.
+    ///
+    /// This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Input, Match, MatchKind};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .ascii_case_insensitive(true)
+    ///     .match_kind(MatchKind::LeftmostFirst)
+    ///     .store_patterns(true)
+    ///     .build(&["Sam", "Samwise"])
+    ///     .unwrap();
+    /// let haystack = "SAM and Samwise";
+    ///
+    /// let matches: Vec<Match> = ac.find_iter(haystack).collect();
+    /// assert_eq!(vec![Match::must(0, 0..3), Match::must(0, 8..11)], matches);
+    ///
+    /// let input = Input::new(haystack).exact_case(true);
+    /// let matches: Vec<Match> = ac.find_iter(input).collect();
+    /// assert_eq!(vec![Match::must(0, 8..11)], matches);
+    /// ```
+    #[inline]
+    pub fn exact_case(mut self, yes: bool) -> Input<'h> {
+        self.set_exact_case(yes);
+        self
+    }
+
+    /// Whether to verify matches with the verifier of the searcher.
+    ///
+    /// This only has an effect when searching with an
+    /// [`AhoCorasick`](crate::AhoCorasick) searcher that was built with a
+    /// verifier set via
+    /// [`AhoCorasickBuilder::verify`](crate::AhoCorasickBuilder::verify). When
+    /// disabled, the search reports the matches of its automaton without
+    /// verifying them, which is faster.
+    ///
+    /// This is enabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Input, Match};
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .verify(|_, bytes| bytes != b"bar")
+    ///     .build(&["foo", "bar"])
+    ///     .unwrap();
+    ///
+    /// let input = Input::new("bar foo");
+    /// assert_eq!(Some(Match::must(0, 4..7)), ac.find(input.clone()));
+    /// assert_eq!(Some(Match::must(1, 0..3)), ac.find(input.verify(false)));
+    /// ```
+    #[inline]
+    pub fn verify(mut self, yes: bool) -> Input<'h> {
+        self.set_verify(yes);
+        self
+    }
+
+    /// Restrict the search to the patterns in the given set.
+    ///
+    /// This only has an effect when searching with an
+    /// [`AhoCorasick`](crate::AhoCorasick) searcher. The searcher then
+    /// reports the same matches as a searcher built from only the patterns
+    /// in the set, except that each match reports the ID of its pattern in
+    /// the searcher that was searched. This permits sharing a single
+    /// searcher between users who each search for a subset of its patterns.
+    ///
+    /// Like [`Input::exact_case`], this works by rejecting the matches of
+    /// other patterns, and so requires a searcher built with
+    /// [`AhoCorasickBuilder::store_patterns`](crate::AhoCorasickBuilder::store_patterns)
+    /// enabled. Otherwise, searching returns an error (or panics for
+    /// infallible search methods). A search is slower the more matches it
+    /// rejects, since the search resumes just after the start of each
+    /// rejected match.
+    ///
+    /// The searchers in the [`nfa`](crate::nfa) and [`dfa`](crate::dfa)
+    /// modules ignore this option.
+    ///
+    /// By default, no set is given and every pattern is searched for.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{
+    ///     AhoCorasick, Input, Match, MatchKind, PatternID, PatternSet,
+    /// };
+    ///
+    /// let ac = AhoCorasick::builder()
+    ///     .match_kind(MatchKind::LeftmostLongest)
+    ///     .store_patterns(true)
+    ///     .build(&["Sam", "Samwise"])
+    ///     .unwrap();
+    /// let haystack = "Samwise";
+    ///
+    /// let mut set = PatternSet::new();
+    /// set.insert(PatternID::must(0));
+    /// let input = Input::new(haystack).pattern_set(Some(&set));
+    /// assert_eq!(Some(Match::must(0, 0..3)), ac.find(input));
+    /// ```
+    #[inline]
+    pub fn pattern_set(mut self, set: Option<&'h PatternSet>) -> Input<'h> {
+        self.set_pattern_set(set);
+        self
+    }
+
     /// Set the span for this search configuration.
     ///
     /// This is like the [`Input::span`] method, except this mutates the
@@ -479,6 +630,67 @@ impl<'h> Input<'h> {
         self.earliest = yes;
     }
 
+    /// Set whether matches must have the exact case of their pattern.
+    ///
+    /// This is like [`Input::exact_case`], except it mutates the search
+    /// configuration in place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Input;
+    ///
+    /// let mut input = Input::new("foobar");
+    /// assert!(!input.get_exact_case());
+    /// input.set_exact_case(true);
+    /// assert!(input.get_exact_case());
+    /// ```
+    #[inline]
+    pub fn set_exact_case(&mut self, yes: bool) {
+        self.exact_case = yes;
+    }
+
+    /// Set whether matches are verified with the verifier of the searcher.
+    ///
+    /// This is like [`Input::verify`], except it mutates the search
+    /// configuration in place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Input;
+    ///
+    /// let mut input = Input::new("foobar");
+    /// assert!(input.get_verify());
+    /// input.set_verify(false);
+    /// assert!(!input.get_verify());
+    /// ```
+    #[inline]
+    pub fn set_verify(&mut self, yes: bool) {
+        self.verify = yes;
+    }
+
+    /// Set the patterns that the search is restricted to.
+    ///
+    /// This is like [`Input::pattern_set`], except it mutates the search
+    /// configuration in place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{Input, PatternSet};
+    ///
+    /// let set = PatternSet::new();
+    /// let mut input = Input::new("foobar");
+    /// assert!(input.get_pattern_set().is_none());
+    /// input.set_pattern_set(Some(&set));
+    /// assert!(input.get_pattern_set().is_some());
+    /// ```
+    #[inline]
+    pub fn set_pattern_set(&mut self, set: Option<&'h PatternSet>) {
+        self.pattern_set = set;
+    }
+
     /// Return a borrow of the underlying haystack as a slice of bytes.
     ///
     /// # Example
@@ -605,6 +817,52 @@ impl<'h> Input<'h> {
         self.earliest
     }
 
+    /// Return whether matches must have the exact case of their pattern.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Input;
+    ///
+    /// let input = Input::new("foobar");
+    /// assert!(!input.get_exact_case());
+    /// ```
+    #[inline]
+    pub fn get_exact_case(&self) -> bool {
+        self.exact_case
+    }
+
+    /// Return whether matches are verified with the verifier of the
+    /// searcher.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Input;
+    ///
+    /// let input = Input::new("foobar");
+    /// assert!(input.get_verify());
+    /// ```
+    #[inline]
+    pub fn get_verify(&self) -> bool {
+        self.verify
+    }
+
+    /// Return the patterns that the search is restricted to, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Input;
+    ///
+    /// let input = Input::new("foobar");
+    /// assert!(input.get_pattern_set().is_none());
+    /// ```
+    #[inline]
+    pub fn get_pattern_set(&self) -> Option<&'h PatternSet> {
+        self.pattern_set
+    }
+
     /// Return true if this input has been exhausted, which in turn means all
     /// subsequent searches will return no matches.
     ///
@@ -639,6 +897,9 @@ impl<'h> core::fmt::Debug for Input<'h> {
         .field("span", &self.span)
         .field("anchored", &self.anchored)
         .field("earliest", &self.earliest)
+        .field("exact_case", &self.exact_case)
+        .field("verify", &self.verify)
+        .field("pattern_set", &self.pattern_set)
         .finish()
     }
 }
@@ -713,6 +974,203 @@ impl Span {
     pub fn offset(&self, offset: usize) -> Span {
         Span { start: self.start + offset, end: self.end + offset }
     }
+
+    /// Returns true when this span and the given span overlap. That is, when
+    /// each one starts before the other one ends.
+    ///
+    /// Spans that are back-to-back don't overlap, and an empty span never
+    /// overlaps any span.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Span;
+    ///
+    /// assert!(Span::from(0..3).overlaps(Span::from(2..5)));
+    /// assert!(!Span::from(0..3).overlaps(Span::from(3..5)));
+    /// assert!(!Span::from(0..3).overlaps(Span::from(1..1)));
+    /// ```
+    #[inline]
+    pub fn overlaps(&self, other: Span) -> bool {
+        self.start < other.end
+            && other.start < self.end
+            && !self.is_empty()
+            && !other.is_empty()
+    }
+
+    /// Returns the span covering both this span and the given span, if they
+    /// overlap or are back-to-back.
+    ///
+    /// This returns `None` when there is a gap between the two spans, since
+    /// their union is not contiguous. An empty span is treated as a position,
+    /// so its union with a span that contains or touches it is that span.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Span;
+    ///
+    /// let union = |a: Span, b: Span| a.union(b).map(|s| s.range());
+    /// assert_eq!(Some(0..5), union(Span::from(0..3), Span::from(2..5)));
+    /// assert_eq!(Some(0..5), union(Span::from(3..5), Span::from(0..3)));
+    /// assert_eq!(Some(0..3), union(Span::from(0..3), Span::from(1..1)));
+    /// assert_eq!(None, union(Span::from(0..3), Span::from(4..5)));
+    /// ```
+    #[inline]
+    pub fn union(&self, other: Span) -> Option<Span> {
+        if self.start > other.end || other.start > self.end {
+            return None;
+        }
+        Some(Span {
+            start: core::cmp::min(self.start, other.start),
+            end: core::cmp::max(self.end, other.end),
+        })
+    }
+
+    /// Returns the parts of this span that are not covered by the given
+    /// span, as the part before it and the part after it.
+    ///
+    /// Either part is `None` when it would be empty. When the spans don't
+    /// overlap, this returns this span unchanged as the part on the side of
+    /// the given span it lies on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::Span;
+    ///
+    /// let span = Span::from(2..8);
+    /// let parts = |other: Span| {
+    ///     let (before, after) = span.subtract(other);
+    ///     (before.map(|s| s.range()), after.map(|s| s.range()))
+    /// };
+    /// assert_eq!((Some(2..4), Some(6..8)), parts(Span::from(4..6)));
+    /// assert_eq!((None, Some(5..8)), parts(Span::from(0..5)));
+    /// assert_eq!((None, None), parts(Span::from(0..10)));
+    /// assert_eq!((Some(2..8), None), parts(Span::from(8..10)));
+    /// ```
+    #[inline]
+    pub fn subtract(&self, other: Span) -> (Option<Span>, Option<Span>) {
+        if self.is_empty() {
+            return (None, None);
+        }
+        if !self.overlaps(other) {
+            return if other.start < self.end {
+                (None, Some(*self))
+            } else {
+                (Some(*self), None)
+            };
+        }
+        let before = Span { start: self.start, end: other.start };
+        let after = Span { start: other.end, end: self.end };
+        (
+            if before.is_empty() { None } else { Some(before) },
+            if after.is_empty() { None } else { Some(after) },
+        )
+    }
+
+    /// Merges spans that overlap or are back-to-back, in place.
+    ///
+    /// The spans given must be sorted by their start offset, as the matches
+    /// of a search are. Afterwards, they are sorted and pairwise disjoint,
+    /// with a gap between any two of them, and every offset that was covered
+    /// by a span is covered by exactly one of them. Empty spans are dropped,
+    /// since they don't cover any offset.
+    ///
+    /// # Panics
+    ///
+    /// This panics when the spans given are not sorted by their start
+    /// offset.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Span};
+    ///
+    /// let ac = AhoCorasick::new(&["abc", "cde", "f"]).unwrap();
+    /// let mut spans: Vec<Span> = ac
+    ///     .find_overlapping_iter("abcdefxf")
+    ///     .map(|m| m.span())
+    ///     .collect();
+    /// Span::coalesce(&mut spans);
+    /// assert_eq!(vec![0..6, 7..8], spans);
+    /// ```
+    pub fn coalesce(spans: &mut Vec<Span>) {
+        let mut merged: Vec<Span> = Vec::with_capacity(spans.len());
+        let mut prev_start = 0;
+        for &span in spans.iter() {
+            assert!(
+                prev_start <= span.start,
+                "spans must be sorted by their start offset",
+            );
+            prev_start = span.start;
+            if let Some(last) = merged.last_mut() {
+                if span.start <= last.end {
+                    last.end = core::cmp::max(last.end, span.end);
+                    continue;
+                }
+            }
+            if !span.is_empty() {
+                merged.push(span);
+            }
+        }
+        *spans = merged;
+    }
+
+    /// Replaces every span of the given buffer with the bytes given along
+    /// with it.
+    ///
+    /// The offsets of each span refer to the buffer before any edit is
+    /// applied. The spans must be sorted by their start offset, and they
+    /// must be disjoint, although they may be back-to-back. An empty span
+    /// inserts its bytes at its position, and edits with empty spans at the
+    /// same position are applied in order.
+    ///
+    /// # Panics
+    ///
+    /// This panics when a span is out of the bounds of the buffer, when its
+    /// start is greater than its end, or when it starts before the end of
+    /// the previous span.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aho_corasick::{AhoCorasick, Span};
+    ///
+    /// let ac = AhoCorasick::new(&["cat", "dog"]).unwrap();
+    /// let mut buf = b"the cat chased the dog".to_vec();
+    /// let edits: Vec<(Span, &[u8])> = ac
+    ///     .find_iter(&buf)
+    ///     .map(|m| (m.span(), &b"pet"[..]))
+    ///     .collect();
+    /// Span::apply_edits(&mut buf, edits);
+    /// assert_eq!(&b"the pet chased the pet"[..], &buf[..]);
+    /// ```
+    pub fn apply_edits<I, B>(buf: &mut Vec<u8>, edits: I)
+    where
+        I: IntoIterator<Item = (Span, B)>,
+        B: AsRef<[u8]>,
+    {
+        let mut edited = Vec::with_capacity(buf.len());
+        let mut last = 0;
+        for (span, bytes) in edits {
+            assert!(
+                last <= span.start
+                    && span.start <= span.end
+                    && span.end <= buf.len(),
+                "invalid edit span {:?} after offset {} in buffer of \
+                 length {}",
+                span,
+                last,
+                buf.len(),
+            );
+            edited.extend_from_slice(&buf[last..span.start]);
+            edited.extend_from_slice(bytes.as_ref());
+            last = span.end;
+        }
+        edited.extend_from_slice(&buf[last..]);
+        *buf = edited;
+    }
 }
 
 impl core::fmt::Debug for Span {
@@ -809,6 +1267,12 @@ impl Anchored {
     }
 }
 
+impl Default for Anchored {
+    fn default() -> Anchored {
+        Anchored::No
+    }
+}
+
 /// A representation of a match reported by an Aho-Corasick searcher.
 ///
 /// A match has two essential pieces of information: the [`PatternID`] that
@@ -963,6 +1427,112 @@ impl Match {
     }
 }
 
+/// A compact representation of a [`Match`].
+///
+/// A `Match` stores its span as two `usize` offsets, which makes it 24 bytes
+/// on 64-bit targets. A `MatchU32` instead stores its starting offset as a
+/// `u32` and its length as a `u16`, which brings it down to 12 bytes. This
+/// is useful when collecting very many matches from haystacks smaller than
+/// 4GiB with patterns no longer than 64KiB.
+///
+/// A `MatchU32` can be built from any `Match` that fits via
+/// [`MatchU32::from_match`], and it can always be converted back to a
+/// `Match` losslessly. Searchers can produce compact matches directly
+/// via [`AhoCorasick::try_find_iter_u32`](crate::AhoCorasick::try_find_iter_u32),
+/// which checks once up front that every match will fit.
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{Match, MatchU32};
+///
+/// let m = Match::must(3, 5..10);
+/// let compact = MatchU32::from_match(m).unwrap();
+/// assert_eq!(3, compact.pattern().as_usize());
+/// assert_eq!(5..10, compact.range());
+/// assert_eq!(m, Match::from(compact));
+///
+/// // Matches that don't fit are rejected.
+/// assert_eq!(None, MatchU32::from_match(Match::must(0, 0..70_000)));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MatchU32 {
+    /// The starting offset of the match.
+    start: u32,
+    /// The length of the match.
+    len: u16,
+    /// The pattern ID.
+    pattern: PatternID,
+}
+
+impl MatchU32 {
+    /// Create a compact match from the given match.
+    ///
+    /// This returns `None` when the start of the match does not fit into a
+    /// `u32` or when the length of the match does not fit into a `u16`.
+    #[inline]
+    pub fn from_match(mat: Match) -> Option<MatchU32> {
+        let start = u32::try_from(mat.start()).ok()?;
+        let len = u16::try_from(mat.len()).ok()?;
+        Some(MatchU32 { start, len, pattern: mat.pattern() })
+    }
+
+    /// Returns the ID of the pattern that matched.
+    #[inline]
+    pub fn pattern(&self) -> PatternID {
+        self.pattern
+    }
+
+    /// The starting position of the match.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start as usize
+    }
+
+    /// The ending position of the match.
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.start() + self.len()
+    }
+
+    /// Returns the length of this match.
+    #[inline]
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Returns true when the span in this match is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the match span as a range.
+    #[inline]
+    pub fn range(&self) -> core::ops::Range<usize> {
+        self.start()..self.end()
+    }
+
+    /// Returns the span for this match.
+    #[inline]
+    pub fn span(&self) -> Span {
+        Span { start: self.start(), end: self.end() }
+    }
+
+    /// Converts this compact match back into a [`Match`].
+    #[inline]
+    pub fn to_match(&self) -> Match {
+        Match { pattern: self.pattern, span: self.span() }
+    }
+}
+
+impl From<MatchU32> for Match {
+    #[inline]
+    fn from(mat: MatchU32) -> Match {
+        mat.to_match()
+    }
+}
+
 /// A knob for controlling the match semantics of an Aho-Corasick automaton.
 ///
 /// There are two generally different ways that Aho-Corasick automatons can
@@ -1146,3 +1716,37 @@ impl Default for StartKind {
         StartKind::Unanchored
     }
 }
+
+/// What to do with patterns that are identical to an earlier pattern once
+/// they are folded.
+///
+/// Two distinct patterns can be identical once they are folded, for
+/// example `FOO` and `foo` when
+/// [`AhoCorasickBuilder::ascii_case_insensitive`](crate::AhoCorasickBuilder::ascii_case_insensitive)
+/// is enabled. Patterns that are byte-for-byte identical are duplicates
+/// too. Duplicates always match at the same positions, so this policy,
+/// which is set by
+/// [`AhoCorasickBuilder::duplicate_policy`](crate::AhoCorasickBuilder::duplicate_policy),
+/// determines which of their IDs are reported.
+///
+/// The default is [`DuplicatePolicy::All`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Keep every duplicate. A non-overlapping search reports the lowest ID
+    /// among duplicates, while an overlapping search reports every one of
+    /// them, in ascending order of their IDs.
+    All,
+    /// Keep only the duplicate with the lowest ID, such that every search,
+    /// including an overlapping search, reports only that ID. The other
+    /// duplicates never match.
+    Lowest,
+    /// Return an error when building if any pattern is a duplicate of an
+    /// earlier pattern.
+    Error,
+}
+
+impl Default for DuplicatePolicy {
+    fn default() -> DuplicatePolicy {
+        DuplicatePolicy::All
+    }
+}
@@ -0,0 +1,106 @@
+/*!
+Trait impls for `serde::Serialize` and `serde::Deserialize`.
+
+These are only available when the `serde` feature is enabled. The impls are
+written by hand (instead of derived) so that deserialization can uphold the
+same invariants that the corresponding constructors uphold. For example,
+deserializing a `PatternID` greater than `PatternID::MAX` or a `Match` whose
+start offset is greater than its end offset results in an error.
+
+The encodings are:
+
+* `PatternID` is encoded as its `u32` representation.
+* `Span` is encoded as a struct with `start` and `end` fields.
+* `Match` is encoded as a struct with `pattern`, `start` and `end` fields.
+*/
+
+use serde::{
+    de::{Deserialize, Deserializer, Error},
+    ser::{Serialize, SerializeStruct, Serializer},
+};
+
+use crate::util::{
+    primitives::PatternID,
+    search::{Match, Span},
+};
+
+impl Serialize for PatternID {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_u32(self.as_u32())
+    }
+}
+
+impl<'de> Deserialize<'de> for PatternID {
+    fn deserialize<D: Deserializer<'de>>(
+        de: D,
+    ) -> Result<PatternID, D::Error> {
+        let id = u32::deserialize(de)?;
+        usize::try_from(id)
+            .ok()
+            .and_then(|id| PatternID::new(id).ok())
+            .ok_or_else(|| {
+                D::Error::custom(format_args!(
+                    "pattern ID {} exceeds maximum of {}",
+                    id,
+                    PatternID::MAX.as_u32(),
+                ))
+            })
+    }
+}
+
+impl Serialize for Span {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut st = ser.serialize_struct("Span", 2)?;
+        st.serialize_field("start", &self.start)?;
+        st.serialize_field("end", &self.end)?;
+        st.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Span {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Span, D::Error> {
+        let raw = RawSpan::deserialize(de)?;
+        Ok(Span { start: raw.start, end: raw.end })
+    }
+}
+
+impl Serialize for Match {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut st = ser.serialize_struct("Match", 3)?;
+        st.serialize_field("pattern", &self.pattern())?;
+        st.serialize_field("start", &self.start())?;
+        st.serialize_field("end", &self.end())?;
+        st.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Match {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Match, D::Error> {
+        let raw = RawMatch::deserialize(de)?;
+        if raw.start > raw.end {
+            return Err(D::Error::custom(format_args!(
+                "invalid match span: start {} is greater than end {}",
+                raw.start, raw.end,
+            )));
+        }
+        Ok(Match::new(raw.pattern, raw.start..raw.end))
+    }
+}
+
+/// The wire shape of a `Span`, used to derive its deserialization.
+#[derive(serde::Deserialize)]
+#[serde(rename = "Span", deny_unknown_fields)]
+struct RawSpan {
+    start: usize,
+    end: usize,
+}
+
+/// The wire shape of a `Match`, used to derive its deserialization before
+/// validating its span.
+#[derive(serde::Deserialize)]
+#[serde(rename = "Match", deny_unknown_fields)]
+struct RawMatch {
+    pattern: PatternID,
+    start: usize,
+    end: usize,
+}
@@ -0,0 +1,18 @@
+/*!
+Provides the synchronization primitives used by the types that are shared
+between threads, such as [`AutomatonCache`](crate::cache::AutomatonCache) and
+[`SearcherHandle`](crate::handle::SearcherHandle).
+
+When compiled with `--cfg loom`, these are the primitives of the `loom`
+crate instead of those of `std`, so that the `loom` model tests can explore
+every interleaving of the threads using them. For example:
+
+```text
+RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+```
+*/
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{Mutex, MutexGuard, RwLock};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Mutex, MutexGuard, RwLock};
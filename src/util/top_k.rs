@@ -0,0 +1,216 @@
+use alloc::vec::Vec;
+
+use crate::util::{primitives::PatternID, search::Match};
+
+/// Estimates the patterns that match most often in a haystack or stream,
+/// without storing every match.
+///
+/// This uses the space-saving algorithm, which keeps a counter for at most
+/// `k` patterns at a time. When a pattern without a counter matches and
+/// every counter is in use, the counter with the smallest count is given to
+/// it and incremented, and the count it had is recorded as the error of the
+/// new pattern's count. This means that a count may overestimate the number
+/// of matches of its pattern by at most its error, but never underestimates
+/// it. Every pattern matching more than `total / k` times is guaranteed to
+/// have a counter, and counts are exact when no more than `k` distinct
+/// patterns match.
+///
+/// Matches can be given one at a time with [`TopPatterns::add`], or every
+/// match of a stream search can be counted with
+/// [`StreamFindIter::top_patterns`](crate::StreamFindIter::top_patterns).
+///
+/// # Example
+///
+/// ```
+/// use aho_corasick::{AhoCorasick, PatternID, TopPatterns};
+///
+/// let ac = AhoCorasick::new(&["ERROR", "WARN", "INFO"]).unwrap();
+/// let log = "INFO WARN ERROR WARN INFO WARN";
+///
+/// let mut top = TopPatterns::new(2);
+/// for m in ac.find_iter(log) {
+///     top.add(&m);
+/// }
+/// assert_eq!(6, top.total());
+/// assert_eq!(Some((PatternID::must(1), 3)), top.top().first().copied());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TopPatterns {
+    /// The maximum number of counters.
+    k: usize,
+    /// The number of matches seen.
+    total: u64,
+    /// The counters, in no particular order.
+    counters: Vec<Counter>,
+    /// The index in `counters` of the counter of each pattern that has one,
+    /// indexed by pattern ID.
+    slots: Vec<Option<usize>>,
+}
+
+/// The estimated number of matches of a single pattern.
+#[derive(Clone, Copy, Debug)]
+struct Counter {
+    pattern: PatternID,
+    count: u64,
+    /// The count of the counter this one replaced, which is the most that
+    /// `count` may overestimate the number of matches by.
+    error: u64,
+}
+
+impl TopPatterns {
+    /// Create a new accumulator that tracks up to `k` patterns at a time.
+    ///
+    /// A larger `k` uses more memory and makes adding a match of an
+    /// untracked pattern slower, but makes counts more accurate. When `k` is
+    /// zero, only the total number of matches is counted.
+    pub fn new(k: usize) -> TopPatterns {
+        TopPatterns {
+            k,
+            total: 0,
+            counters: Vec::with_capacity(k),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Count the given match.
+    pub fn add(&mut self, m: &Match) {
+        self.add_pattern(m.pattern());
+    }
+
+    /// Count a match of the given pattern.
+    pub fn add_pattern(&mut self, pattern: PatternID) {
+        self.total += 1;
+        let i = pattern.as_usize();
+        if self.slots.len() <= i {
+            self.slots.resize(i + 1, None);
+        }
+        if let Some(slot) = self.slots[i] {
+            self.counters[slot].count += 1;
+            return;
+        }
+        if self.counters.len() < self.k {
+            self.slots[i] = Some(self.counters.len());
+            self.counters.push(Counter { pattern, count: 1, error: 0 });
+            return;
+        }
+        let slot = match (0..self.counters.len())
+            .min_by_key(|&slot| self.counters[slot].count)
+        {
+            None => return,
+            Some(slot) => slot,
+        };
+        let evicted = self.counters[slot];
+        self.slots[evicted.pattern] = None;
+        self.slots[i] = Some(slot);
+        self.counters[slot] = Counter {
+            pattern,
+            count: evicted.count + 1,
+            error: evicted.count,
+        };
+    }
+
+    /// Returns the tracked patterns along with their estimated number of
+    /// matches, from the most to the least frequent. Patterns with the same
+    /// count are ordered by pattern ID.
+    pub fn top(&self) -> Vec<(PatternID, u64)> {
+        let mut top: Vec<(PatternID, u64)> =
+            self.counters.iter().map(|c| (c.pattern, c.count)).collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        top
+    }
+
+    /// Returns the estimated number of matches of the given pattern, or
+    /// `None` if it isn't tracked.
+    pub fn count(&self, pattern: PatternID) -> Option<u64> {
+        let slot = (*self.slots.get(pattern.as_usize())?)?;
+        Some(self.counters[slot].count)
+    }
+
+    /// Returns the most that the count of the given pattern may overestimate
+    /// its number of matches by, or `None` if it isn't tracked. This is zero
+    /// when the count is exact.
+    pub fn error(&self, pattern: PatternID) -> Option<u64> {
+        let slot = (*self.slots.get(pattern.as_usize())?)?;
+        Some(self.counters[slot].error)
+    }
+
+    /// Returns the number of matches counted, including those of patterns
+    /// that aren't tracked.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Forget every match counted so far, so that the accumulator can be
+    /// reused for a new haystack or stream.
+    pub fn reset(&mut self) {
+        self.total = 0;
+        self.counters.clear();
+        self.slots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn pid(i: usize) -> PatternID {
+        PatternID::must(i)
+    }
+
+    #[test]
+    fn exact_when_few_patterns() {
+        let mut top = TopPatterns::new(3);
+        for &i in [2, 0, 2, 1, 2, 0].iter() {
+            top.add(&Match::must(i, 0..1));
+        }
+        assert_eq!(vec![(pid(2), 3), (pid(0), 2), (pid(1), 1)], top.top());
+        assert_eq!(Some(0), top.error(pid(0)));
+        assert_eq!(None, top.count(pid(3)));
+        assert_eq!(6, top.total());
+
+        top.reset();
+        assert!(top.top().is_empty());
+        assert_eq!(0, top.total());
+    }
+
+    #[test]
+    fn evicts_smallest() {
+        let mut top = TopPatterns::new(2);
+        for &i in [0, 0, 0, 1, 2, 0, 3].iter() {
+            top.add_pattern(pid(i));
+        }
+        // '1' is evicted by '2', which is in turn evicted by '3'.
+        assert_eq!(vec![(pid(0), 4), (pid(3), 3)], top.top());
+        assert_eq!(Some(2), top.error(pid(3)));
+        assert_eq!(None, top.count(pid(1)));
+        assert_eq!(None, top.count(pid(2)));
+        assert_eq!(7, top.total());
+
+        let mut top = TopPatterns::new(0);
+        top.add_pattern(pid(0));
+        assert!(top.top().is_empty());
+        assert_eq!(1, top.total());
+    }
+
+    #[test]
+    fn heavy_hitters_are_kept() {
+        // Pattern '0' matches every third time and the others are all
+        // distinct, so '0' matches more than 'total / k' times.
+        let mut top = TopPatterns::new(4);
+        let mut matches = 0;
+        for i in 0..300 {
+            if i % 3 == 0 {
+                top.add_pattern(pid(0));
+                matches += 1;
+            } else {
+                top.add_pattern(pid(1 + i));
+            }
+        }
+        let count = top.count(pid(0)).unwrap();
+        let error = top.error(pid(0)).unwrap();
+        assert!(count >= matches && count - error <= matches);
+        assert_eq!(pid(0), top.top()[0].0);
+    }
+}
@@ -0,0 +1,319 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use crate::util::{
+    primitives::{PatternID, SmallIndex},
+    search::MatchKind,
+};
+
+/// A report of the problems found in a set of patterns, without building an
+/// automaton from them.
+///
+/// A report is created by
+/// [`AhoCorasickBuilder::validate`](crate::AhoCorasickBuilder::validate).
+/// It lists every [`ValidationIssue`] found, in order of the ID of the
+/// pattern the issue is about.
+///
+/// The `Display` impl of a report writes one line per issue.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValidationReport {
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns true if and only if no issues were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns every issue found, in order of the ID of the pattern the
+    /// issue is about.
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+}
+
+impl core::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single problem with a pattern, as found by
+/// [`AhoCorasickBuilder::validate`](crate::AhoCorasickBuilder::validate).
+#[non_exhaustive]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValidationIssue {
+    /// The pattern is empty. An empty pattern matches at every position,
+    /// and stream searches do not support empty patterns at all.
+    Empty {
+        /// The ID of the empty pattern.
+        pattern: PatternID,
+    },
+    /// The pattern is equivalent to a previous pattern, taking ASCII case
+    /// insensitivity into account. A duplicate pattern can never be
+    /// reported by a non-overlapping search.
+    Duplicate {
+        /// The ID of the duplicate pattern.
+        pattern: PatternID,
+        /// The ID of the first pattern it duplicates.
+        first: PatternID,
+    },
+    /// The pattern can never match because a previous pattern is a prefix
+    /// of it. This only happens with [`MatchKind::LeftmostFirst`]
+    /// semantics, where the earlier pattern always takes priority.
+    Shadowed {
+        /// The ID of the pattern that can never match.
+        pattern: PatternID,
+        /// The ID of the earlier pattern that is a prefix of it.
+        by: PatternID,
+    },
+    /// The pattern is longer than the maximum length supported by an
+    /// automaton.
+    TooLong {
+        /// The ID of the pattern that is too long.
+        pattern: PatternID,
+        /// The length of the pattern.
+        len: usize,
+        /// The maximum length supported.
+        max: usize,
+    },
+    /// There are more patterns than an automaton supports. No patterns
+    /// beyond the maximum are validated.
+    TooManyPatterns {
+        /// The maximum number of patterns supported.
+        max: usize,
+    },
+}
+
+impl ValidationIssue {
+    /// Returns the ID of the pattern this issue is about, if it is about a
+    /// single pattern.
+    ///
+    /// When patterns are read from a dictionary, this can be used to find
+    /// the line the pattern came from. See
+    /// [`dictionary::parse_patterns_with_lines`](crate::dictionary::parse_patterns_with_lines).
+    pub fn pattern(&self) -> Option<PatternID> {
+        match *self {
+            ValidationIssue::Empty { pattern }
+            | ValidationIssue::Duplicate { pattern, .. }
+            | ValidationIssue::Shadowed { pattern, .. }
+            | ValidationIssue::TooLong { pattern, .. } => Some(pattern),
+            ValidationIssue::TooManyPatterns { .. } => None,
+        }
+    }
+}
+
+impl core::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            ValidationIssue::Empty { pattern } => {
+                write!(f, "pattern {} is empty", pattern.as_usize())
+            }
+            ValidationIssue::Duplicate { pattern, first } => write!(
+                f,
+                "pattern {} is a duplicate of pattern {}",
+                pattern.as_usize(),
+                first.as_usize(),
+            ),
+            ValidationIssue::Shadowed { pattern, by } => write!(
+                f,
+                "pattern {} can never match because pattern {} is a \
+                 prefix of it",
+                pattern.as_usize(),
+                by.as_usize(),
+            ),
+            ValidationIssue::TooLong { pattern, len, max } => write!(
+                f,
+                "pattern {} has length {}, which exceeds the limit of {}",
+                pattern.as_usize(),
+                len,
+                max,
+            ),
+            ValidationIssue::TooManyPatterns { max } => {
+                write!(f, "there are more than {} patterns", max)
+            }
+        }
+    }
+}
+
+/// The patterns that occur in other patterns of the same set.
+///
+/// This is created by
+/// [`AhoCorasickBuilder::contained_patterns`](crate::AhoCorasickBuilder::contained_patterns).
+/// A pattern that occurs in another one is matched by every haystack that
+/// the other one is matched by, possibly at a different position. So with
+/// [`MatchKind::Standard`] semantics, its match is reported as well when
+/// searching for overlapping matches, and with leftmost semantics, one of
+/// them hides the other.
+///
+/// A pattern never counts as occurring in itself, but a duplicate of it
+/// does.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContainedPatterns {
+    /// The other patterns that occur in each pattern, indexed by pattern ID,
+    /// in ascending order of ID.
+    contained: Vec<Vec<PatternID>>,
+}
+
+impl ContainedPatterns {
+    pub(crate) fn new(contained: Vec<Vec<PatternID>>) -> ContainedPatterns {
+        ContainedPatterns { contained }
+    }
+
+    /// Returns true if and only if the pattern `inner` occurs anywhere in
+    /// the pattern `outer`.
+    ///
+    /// This returns false if either pattern ID is out of bounds.
+    pub fn contains(&self, outer: PatternID, inner: PatternID) -> bool {
+        self.contained_in(outer).binary_search(&inner).is_ok()
+    }
+
+    /// Returns the other patterns that occur in the given pattern, in
+    /// ascending order of ID.
+    ///
+    /// This returns an empty slice if the pattern ID is out of bounds.
+    pub fn contained_in(&self, outer: PatternID) -> &[PatternID] {
+        self.contained.get(outer.as_usize()).map_or(&[], |pids| pids)
+    }
+
+    /// Returns the patterns that the given pattern occurs in, in ascending
+    /// order of ID.
+    ///
+    /// A dictionary can be pruned of the patterns that occur in another
+    /// pattern and aren't needed to report its matches, since any haystack
+    /// matched by the other pattern is matched by them as well.
+    pub fn containers(
+        &self,
+        inner: PatternID,
+    ) -> impl Iterator<Item = PatternID> + '_ {
+        self.contained
+            .iter()
+            .enumerate()
+            .filter(move |(_, pids)| pids.binary_search(&inner).is_ok())
+            .map(|(i, _)| PatternID::new_unchecked(i))
+    }
+}
+
+/// Validate the given patterns under the given match semantics.
+///
+/// This walks a simple trie of the patterns (with keys folded to lowercase
+/// when `ascii_case_insensitive` is enabled) to find duplicates and, for
+/// leftmost-first semantics, patterns that have an earlier pattern as a
+/// prefix.
+pub(crate) fn validate<I, P>(
+    patterns: I,
+    match_kind: MatchKind,
+    ascii_case_insensitive: bool,
+) -> ValidationReport
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+{
+    let mut issues = vec![];
+    // The transitions of the trie, keyed by state and byte, and the pattern
+    // ending at each state, if any. State '0' is the root.
+    let mut trans: BTreeMap<(usize, u8), usize> = BTreeMap::new();
+    let mut ends: Vec<Option<PatternID>> = vec![None];
+    for (i, pat) in patterns.into_iter().enumerate() {
+        let pid = match PatternID::new(i) {
+            Ok(pid) => pid,
+            Err(_) => {
+                issues.push(ValidationIssue::TooManyPatterns {
+                    max: PatternID::LIMIT,
+                });
+                break;
+            }
+        };
+        let pat = pat.as_ref();
+        if pat.is_empty() {
+            issues.push(ValidationIssue::Empty { pattern: pid });
+        }
+        if pat.len() > SmallIndex::MAX.as_usize() {
+            issues.push(ValidationIssue::TooLong {
+                pattern: pid,
+                len: pat.len(),
+                max: SmallIndex::MAX.as_usize(),
+            });
+        }
+        let mut sid = 0;
+        let mut shadowed_by = None;
+        for &b in pat.iter() {
+            if shadowed_by.is_none() {
+                shadowed_by = ends[sid];
+            }
+            let b = if ascii_case_insensitive {
+                b.to_ascii_lowercase()
+            } else {
+                b
+            };
+            let next = ends.len();
+            sid = *trans.entry((sid, b)).or_insert(next);
+            if sid == next {
+                ends.push(None);
+            }
+        }
+        if let Some(first) = ends[sid] {
+            issues.push(ValidationIssue::Duplicate { pattern: pid, first });
+            continue;
+        }
+        ends[sid] = Some(pid);
+        if let Some(by) =
+            shadowed_by.filter(|_| match_kind.is_leftmost_first())
+        {
+            issues.push(ValidationIssue::Shadowed { pattern: pid, by });
+        }
+    }
+    ValidationReport { issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(
+        patterns: &[&str],
+        kind: MatchKind,
+        ascii_case_insensitive: bool,
+    ) -> Vec<ValidationIssue> {
+        validate(patterns, kind, ascii_case_insensitive).issues
+    }
+
+    fn pid(i: usize) -> PatternID {
+        PatternID::must(i)
+    }
+
+    #[test]
+    fn issues_by_kind() {
+        let patterns = &["foo", "foobar", "", "Foo", "foo", "fo", "bar"];
+        assert_eq!(
+            vec![
+                ValidationIssue::Empty { pattern: pid(2) },
+                ValidationIssue::Duplicate { pattern: pid(4), first: pid(0) },
+            ],
+            issues(patterns, MatchKind::Standard, false),
+        );
+        assert_eq!(
+            vec![
+                ValidationIssue::Shadowed { pattern: pid(1), by: pid(0) },
+                ValidationIssue::Empty { pattern: pid(2) },
+                ValidationIssue::Shadowed { pattern: pid(3), by: pid(2) },
+                ValidationIssue::Duplicate { pattern: pid(4), first: pid(0) },
+                ValidationIssue::Shadowed { pattern: pid(5), by: pid(2) },
+                ValidationIssue::Shadowed { pattern: pid(6), by: pid(2) },
+            ],
+            issues(patterns, MatchKind::LeftmostFirst, false),
+        );
+        assert_eq!(
+            vec![
+                ValidationIssue::Empty { pattern: pid(2) },
+                ValidationIssue::Duplicate { pattern: pid(3), first: pid(0) },
+                ValidationIssue::Duplicate { pattern: pid(4), first: pid(0) },
+            ],
+            issues(patterns, MatchKind::LeftmostLongest, true),
+        );
+        assert!(validate(["a", "b"], MatchKind::LeftmostFirst, true).is_ok());
+    }
+}
@@ -0,0 +1,338 @@
+/*!
+Provides a simple binary encoding for persisting automata.
+
+Every integer is written in little endian byte order, so that the encoding
+doesn't depend on the platform it was written on. Sizes are written as `u64`
+and are checked to fit in a `usize` when read. A [`Reader`] returns `None` as
+soon as its input is truncated or holds a value that is out of range, and
+the automata check the invariants their searches depend on once they are read.
+*/
+
+use alloc::vec::Vec;
+
+use crate::util::{
+    error::DeserializeError,
+    int::U32,
+    primitives::{PatternID, SmallIndex, StateID},
+    search::MatchKind,
+    special::Special,
+};
+
+#[cfg(feature = "std")]
+use crate::util::search::StartKind;
+
+/// The version of the encoding, which is incremented whenever the encoding
+/// of any automaton changes.
+pub(crate) const VERSION: u32 = 1;
+
+/// Returns the given magic number and the version of the encoding, followed
+/// by the body written by the given function and the checksum of them all.
+pub(crate) fn seal<F: FnOnce(&mut Writer)>(
+    magic: &[u8; 8],
+    write_body: F,
+) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.raw(magic);
+    w.u32(VERSION);
+    write_body(&mut w);
+    let checksum = fnv1a(w.as_bytes());
+    w.u64(checksum);
+    w.buf
+}
+
+/// Returns a reader of the body of bytes written by [`seal`] with the given
+/// magic number, once their version and checksum are checked.
+pub(crate) fn unseal<'a>(
+    magic: &[u8; 8],
+    bytes: &'a [u8],
+) -> Result<Reader<'a>, DeserializeError> {
+    let mut r = Reader::new(bytes);
+    if r.raw(magic.len()) != Some(&magic[..]) {
+        return Err(DeserializeError::magic());
+    }
+    let version = r.u32().ok_or_else(DeserializeError::invalid)?;
+    if version != VERSION {
+        return Err(DeserializeError::version(VERSION, version));
+    }
+    let header_len = magic.len() + 4;
+    let body_len = bytes
+        .len()
+        .checked_sub(8)
+        .filter(|&len| len >= header_len)
+        .ok_or_else(DeserializeError::invalid)?;
+    let (body, checksum) = bytes.split_at(body_len);
+    if Reader::new(checksum).u64() != Some(fnv1a(body)) {
+        return Err(DeserializeError::checksum());
+    }
+    Ok(Reader::new(&body[header_len..]))
+}
+
+/// Appends encoded values to a buffer.
+#[derive(Debug, Default)]
+pub(crate) struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new() -> Writer {
+        Writer::default()
+    }
+
+    pub(crate) fn u8(&mut self, n: u8) {
+        self.buf.push(n);
+    }
+
+    pub(crate) fn u32(&mut self, n: u32) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, n: u64) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    pub(crate) fn usize(&mut self, n: usize) {
+        // A usize always fits in a u64 on the platforms Rust supports.
+        self.u64(n as u64);
+    }
+
+    pub(crate) fn bool(&mut self, yes: bool) {
+        self.u8(u8::from(yes));
+    }
+
+    /// Write the given bytes, preceded by their length.
+    #[cfg(any(test, feature = "std"))]
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.usize(bytes.len());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Write the given bytes as is, without their length.
+    pub(crate) fn raw(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Write the given 32-bit values, preceded by their number.
+    pub(crate) fn u32s<I: ExactSizeIterator<Item = u32>>(&mut self, it: I) {
+        self.usize(it.len());
+        for n in it {
+            self.u32(n);
+        }
+    }
+
+    pub(crate) fn state_ids(&mut self, sids: &[StateID]) {
+        self.u32s(sids.iter().map(|sid| sid.as_u32()));
+    }
+
+    pub(crate) fn pattern_ids(&mut self, pids: &[PatternID]) {
+        self.u32s(pids.iter().map(|pid| pid.as_u32()));
+    }
+
+    pub(crate) fn small_indices(&mut self, indices: &[SmallIndex]) {
+        self.u32s(indices.iter().map(|index| index.as_u32()));
+    }
+
+    pub(crate) fn match_kind(&mut self, kind: MatchKind) {
+        self.u8(match kind {
+            MatchKind::Standard => 0,
+            MatchKind::LeftmostFirst => 1,
+            MatchKind::LeftmostLongest => 2,
+        });
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn start_kind(&mut self, kind: StartKind) {
+        self.u8(match kind {
+            StartKind::Both => 0,
+            StartKind::Unanchored => 1,
+            StartKind::Anchored => 2,
+        });
+    }
+
+    pub(crate) fn special(&mut self, special: &Special) {
+        self.u32(special.max_special_id.as_u32());
+        self.u32(special.max_match_id.as_u32());
+        self.u32(special.start_unanchored_id.as_u32());
+        self.u32(special.start_anchored_id.as_u32());
+    }
+
+    /// Returns the number of bytes written so far.
+    #[cfg(feature = "std")]
+    pub(crate) fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the bytes written so far.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Reads encoded values from the front of a slice of bytes.
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes }
+    }
+
+    /// Returns true when every byte was read.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Read the given number of bytes as is.
+    pub(crate) fn raw(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.raw(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.raw(4)?);
+        Some(u32::from_le_bytes(buf))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.raw(8)?);
+        Some(u64::from_le_bytes(buf))
+    }
+
+    pub(crate) fn usize(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+
+    pub(crate) fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Read 32-bit values preceded by their number.
+    pub(crate) fn u32s(&mut self) -> Option<Vec<u32>> {
+        let len = self.usize()?;
+        // This checks the length before allocating, so that a corrupted
+        // length can't request an arbitrarily large allocation.
+        let bytes = self.raw(len.checked_mul(4)?)?;
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn state_ids(&mut self) -> Option<Vec<StateID>> {
+        self.u32s()?
+            .into_iter()
+            .map(|n| StateID::new(n.as_usize()).ok())
+            .collect()
+    }
+
+    pub(crate) fn pattern_ids(&mut self) -> Option<Vec<PatternID>> {
+        self.u32s()?
+            .into_iter()
+            .map(|n| PatternID::new(n.as_usize()).ok())
+            .collect()
+    }
+
+    pub(crate) fn small_indices(&mut self) -> Option<Vec<SmallIndex>> {
+        self.u32s()?
+            .into_iter()
+            .map(|n| SmallIndex::new(n.as_usize()).ok())
+            .collect()
+    }
+
+    pub(crate) fn state_id(&mut self) -> Option<StateID> {
+        StateID::new(self.u32()?.as_usize()).ok()
+    }
+
+    pub(crate) fn match_kind(&mut self) -> Option<MatchKind> {
+        match self.u8()? {
+            0 => Some(MatchKind::Standard),
+            1 => Some(MatchKind::LeftmostFirst),
+            2 => Some(MatchKind::LeftmostLongest),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn special(&mut self) -> Option<Special> {
+        Some(Special {
+            max_special_id: self.state_id()?,
+            max_match_id: self.state_id()?,
+            start_unanchored_id: self.state_id()?,
+            start_anchored_id: self.state_id()?,
+        })
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of the given bytes.
+///
+/// Unlike the hashers of the standard library, this is guaranteed to never
+/// change, so it is used for the names and checksums of persisted data.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_and_truncation() {
+        let mut w = Writer::new();
+        w.u8(7);
+        w.bool(true);
+        w.usize(1 << 40);
+        w.bytes(b"abc");
+        w.state_ids(&[StateID::must(5), StateID::must(9)]);
+        let bytes = w.as_bytes().to_vec();
+
+        let mut r = Reader::new(&bytes);
+        assert_eq!(Some(7), r.u8());
+        assert_eq!(Some(true), r.bool());
+        assert_eq!(Some(1 << 40), r.u64());
+        assert_eq!(Some(3), r.usize());
+        assert_eq!(Some(&b"abc"[..]), r.raw(3));
+        assert_eq!(
+            Some(alloc::vec![StateID::must(5), StateID::must(9)]),
+            r.state_ids(),
+        );
+        assert!(r.is_empty());
+
+        for len in 0..bytes.len() {
+            let mut r = Reader::new(&bytes[..len]);
+            let read = (|| {
+                r.u8()?;
+                r.bool()?;
+                r.u64()?;
+                let len = r.usize()?;
+                r.raw(len)?;
+                r.state_ids()
+            })();
+            assert!(read.is_none(), "truncated to {} bytes", len);
+        }
+        // A huge count is rejected without allocating.
+        let mut w = Writer::new();
+        w.usize(usize::MAX);
+        assert!(Reader::new(w.as_bytes()).u32s().is_none());
+    }
+}
@@ -0,0 +1,190 @@
+// This checks that polling the async adapters never allocates, once they're
+// built. It lives in its own test binary so that its counting allocator
+// doesn't replace the allocator of any other test.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::{Cell, RefCell},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use aho_corasick::AhoCorasick;
+use futures::{AsyncRead, AsyncWrite};
+
+/// A global allocator that counts the allocations made by each thread, so
+/// that allocations made by other threads of the test harness aren't
+/// attributed to the polls being checked.
+struct CountingAllocator;
+
+std::thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+impl CountingAllocator {
+    /// Returns the number of allocations made by the current thread so far.
+    fn allocations() -> usize {
+        ALLOCATIONS.with(|count| count.get())
+    }
+
+    fn record() {
+        // The count may not be available while the thread is shutting down.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        CountingAllocator::record();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        CountingAllocator::record();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        CountingAllocator::record();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// A reader of the given bytes that answers every other poll with
+/// `Pending`, so that the adapters are polled again in the middle of a
+/// stream.
+struct PendingReader {
+    source: Vec<u8>,
+    cursor: usize,
+    pending: bool,
+}
+
+impl AsyncRead for PendingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let rest = &self.source[self.cursor..];
+        let len = core::cmp::min(rest.len(), buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.cursor += len;
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// A writer to a shared buffer that answers every other poll with
+/// `Pending`.
+#[derive(Clone)]
+struct PendingWriter {
+    sink: Rc<RefCell<Vec<u8>>>,
+    pending: bool,
+}
+
+impl AsyncWrite for PendingWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        self.sink.borrow_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn polls_do_not_allocate() {
+    // The potential buffer can only start over after 'aab', so a long run
+    // of 'a' has to be written out without waiting for a start state.
+    let patterns = ["aab", "he", "she", "big"];
+    let replacements = ["X", "she", "he", "huge"];
+    let source = ("a".repeat(50) + "b she is big ").repeat(20).into_bytes();
+    let expected = ("a".repeat(48) + "X he is huge ").repeat(20).into_bytes();
+    let ac = AhoCorasick::new(patterns).unwrap();
+    let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+    for buffer_size in [1, 7, 10000] {
+        let mut buf = vec![0u8; buffer_size];
+        let reader = PendingReader {
+            source: source.clone(),
+            cursor: 0,
+            pending: false,
+        };
+        let mut ac_reader = ac.async_reader(reader, &replacements).unwrap();
+        let mut output: Vec<u8> = Vec::with_capacity(expected.len());
+        loop {
+            let before = CountingAllocator::allocations();
+            let poll = Pin::new(&mut ac_reader).poll_read(&mut cx, &mut buf);
+            assert_eq!(before, CountingAllocator::allocations());
+            match poll {
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(size)) => output.extend(&buf[..size]),
+                Poll::Ready(Err(err)) => panic!("reader error: {}", err),
+                Poll::Pending => {}
+            }
+        }
+        assert_eq!(expected, output);
+
+        let sink = Rc::new(RefCell::new(Vec::with_capacity(expected.len())));
+        let writer = PendingWriter { sink: Rc::clone(&sink), pending: false };
+        let mut ac_writer = ac.async_writer(writer, &replacements).unwrap();
+        for chunk in source.chunks(buffer_size) {
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                let before = CountingAllocator::allocations();
+                let poll = Pin::new(&mut ac_writer).poll_write(&mut cx, chunk);
+                assert_eq!(before, CountingAllocator::allocations());
+                if let Poll::Ready(result) = poll {
+                    chunk = &chunk[result.unwrap()..];
+                }
+            }
+        }
+        loop {
+            let before = CountingAllocator::allocations();
+            let poll = Pin::new(&mut ac_writer).poll_close(&mut cx);
+            assert_eq!(before, CountingAllocator::allocations());
+            if let Poll::Ready(result) = poll {
+                result.unwrap();
+                break;
+            }
+        }
+        assert_eq!(expected, *sink.borrow());
+    }
+}